@@ -0,0 +1,97 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion, Throughput};
+use jfrs::decode::{DecodeError, Decoder, Input, IntEncoding};
+use jfrs::reader::JfrReader;
+use std::io::Cursor;
+use std::path::PathBuf;
+
+fn read_events(c: &mut Criterion) {
+    let mut group = c.benchmark_group("read_events");
+    // all test recordings use compressed integers
+    for file_name in ["profiler-wall.jfr", "recording.jfr"] {
+        let bytes = std::fs::read(test_data(file_name)).unwrap();
+        group.throughput(Throughput::Bytes(bytes.len() as u64));
+        group.bench_function(file_name, |b| {
+            b.iter(|| {
+                let mut reader = JfrReader::new(Cursor::new(bytes.as_slice()));
+                let mut count = 0;
+                for (chunk_reader, chunk) in reader.chunks().flatten() {
+                    count += chunk_reader.events(&chunk).flatten().count();
+                }
+                count
+            })
+        });
+    }
+    group.finish();
+}
+
+fn read_i64(c: &mut Criterion) {
+    // typical values in events: small ids, offsets, ticks and occasionally negative ones
+    let mut values = vec![];
+    let mut seed = 0x2545_f491_4f6c_dd1du64;
+    for i in 0..100_000 {
+        seed ^= seed << 13;
+        seed ^= seed >> 7;
+        seed ^= seed << 17;
+        values.push((seed >> (i % 8 * 8)) as i64);
+    }
+    let bytes = values
+        .iter()
+        .flat_map(|&v| encode_var_i64(v))
+        .collect::<Vec<_>>();
+
+    let mut group = c.benchmark_group("read_i64");
+    group.throughput(Throughput::Bytes(bytes.len() as u64));
+    group.bench_function("slice", |b| {
+        b.iter(|| decode_all(bytes.as_slice(), values.len()))
+    });
+    // the fallback which reads byte by byte, as all inputs did before the in-memory fast path
+    group.bench_function("byte_by_byte", |b| {
+        b.iter(|| decode_all(ByteByByte(bytes.as_slice()), values.len()))
+    });
+    group.finish();
+}
+
+fn decode_all<I: Input<Error = DecodeError>>(input: I, count: usize) -> i64 {
+    let mut decoder = Decoder::new(input);
+    decoder.set_int_encoding(IntEncoding::Compressed);
+    let mut sum = 0i64;
+    for _ in 0..count {
+        sum = sum.wrapping_add(decoder.read_i64().unwrap());
+    }
+    black_box(sum)
+}
+
+/// Input which doesn't expose the unread bytes
+struct ByteByByte<'a>(&'a [u8]);
+
+impl Input for ByteByByte<'_> {
+    type Error = DecodeError;
+
+    fn read_exact(&mut self, buf: &mut [u8]) -> Result<(), Self::Error> {
+        self.0.read_exact(buf)
+    }
+}
+
+fn encode_var_i64(value: i64) -> Vec<u8> {
+    let mut v = value as u64;
+    let mut bytes = vec![];
+    for _ in 0..8 {
+        if v < 0x80 {
+            bytes.push(v as u8);
+            return bytes;
+        }
+        bytes.push(v as u8 | 0x80);
+        v >>= 7;
+    }
+    bytes.push(v as u8);
+    bytes
+}
+
+fn test_data(file_name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("test-data")
+        .join(file_name)
+}
+
+criterion_group!(benches, read_events, read_i64);
+criterion_main!(benches);
@@ -0,0 +1,45 @@
+/*
+ * C interface of jfrs. Link against libjfrs_ffi built by `cargo build --release` in the ffi directory.
+ * See src/lib.rs for the details of each function.
+ */
+#ifndef JFRS_H
+#define JFRS_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+typedef struct JfrsReader JfrsReader;
+typedef struct JfrsChunk JfrsChunk;
+typedef struct JfrsEvent JfrsEvent;
+
+/* Returns the message of the last failure (including a panic) on the current thread, or NULL */
+const char *jfrs_last_error(void);
+
+/* Returns NULL on failure */
+JfrsReader *jfrs_open(const char *path);
+void jfrs_close(JfrsReader *reader);
+
+/* Returns NULL at the end of the file or on failure. Free the chunk by jfrs_chunk_free */
+JfrsChunk *jfrs_next_chunk(JfrsReader *reader);
+void jfrs_chunk_free(JfrsChunk *chunk);
+
+/* Returns NULL at the end of the chunk or on failure.
+ * The event is valid until the next call with the same chunk or jfrs_chunk_free */
+const JfrsEvent *jfrs_next_event(JfrsChunk *chunk);
+
+/* Copy the NUL-terminated string into buf and return the whole length like snprintf, or -1 */
+int64_t jfrs_event_type_name(const JfrsEvent *event, char *buf, size_t buf_len);
+int64_t jfrs_event_get_field_string(const JfrsEvent *event, const char *path, char *buf, size_t buf_len);
+
+/* Returns 0 on success, or -1 if the field is missing or not an integer */
+int32_t jfrs_event_get_field_i64(const JfrsEvent *event, const char *path, int64_t *out);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* JFRS_H */
@@ -0,0 +1,543 @@
+//! C interface of jfrs, so that profiling backends written in C/C++/Go can read JFR files
+//! without shelling out to the JDK.
+//!
+//! All objects are passed as opaque handles. See `include/jfrs.h` for the declarations.
+//!
+//! ```c
+//! JfrsReader *reader = jfrs_open("/path/to/recording.jfr");
+//! JfrsChunk *chunk;
+//! while ((chunk = jfrs_next_chunk(reader)) != NULL) {
+//!     const JfrsEvent *event;
+//!     while ((event = jfrs_next_event(chunk)) != NULL) {
+//!         char buf[256];
+//!         if (jfrs_event_get_field_string(event, "sampledThread.osName", buf, sizeof(buf)) >= 0) {
+//!             printf("%s\n", buf);
+//!         }
+//!     }
+//!     jfrs_chunk_free(chunk);
+//! }
+//! if (jfrs_last_error() != NULL) {
+//!     fprintf(stderr, "%s\n", jfrs_last_error());
+//! }
+//! jfrs_close(reader);
+//! ```
+//!
+//! Functions returning NULL or a negative value on failure set the message retrieved by
+//! [`jfrs_last_error`]. The end of the iteration is not a failure and clears the message.
+//!
+//! Panics never unwind into the caller. A panic is reported as a failure with the panic message.
+
+use jfrs::reader::event::Accessor;
+use jfrs::reader::value_descriptor::ValueDescriptor;
+use jfrs::reader::{Chunk, ChunkReader, JfrReader};
+use std::cell::RefCell;
+use std::ffi::{c_char, CStr, CString};
+use std::fmt::Display;
+use std::fs::File;
+use std::panic::{self, AssertUnwindSafe};
+use std::ptr;
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+fn set_error<E: Display>(error: E) {
+    let message = CString::new(error.to_string().replace('\0', " ")).unwrap_or_default();
+    LAST_ERROR.with(|e| *e.borrow_mut() = Some(message));
+}
+
+fn clear_error() {
+    LAST_ERROR.with(|e| *e.borrow_mut() = None);
+}
+
+/// Runs the body of the entry point, returning `on_panic` if it panics
+/// since unwinding across the C boundary is undefined behavior
+fn guard<R, F: FnOnce() -> R>(on_panic: R, f: F) -> R {
+    match panic::catch_unwind(AssertUnwindSafe(f)) {
+        Ok(r) => r,
+        Err(payload) => {
+            let message = payload
+                .downcast_ref::<&str>()
+                .copied()
+                .or_else(|| payload.downcast_ref::<String>().map(String::as_str))
+                .unwrap_or("unknown");
+            set_error(format!("Panicked: {}", message));
+            on_panic
+        }
+    }
+}
+
+/// Opaque handle of the recording file
+pub struct JfrsReader {
+    inner: JfrReader<File>,
+    // set once the chunk iteration ends, since a new iteration starts over from the current position
+    finished: bool,
+}
+
+/// Opaque handle of the chunk
+pub struct JfrsChunk {
+    // boxed so that the current event can refer to the chunk at the stable address
+    chunk: Box<Chunk>,
+    reader: ChunkReader,
+    offset: u64,
+    current: Option<JfrsEvent>,
+}
+
+/// Opaque handle of the event, owned by the chunk
+pub struct JfrsEvent {
+    chunk: *const Chunk,
+    class_id: i64,
+    value: ValueDescriptor,
+}
+
+impl JfrsEvent {
+    fn chunk(&self) -> &Chunk {
+        // the chunk outlives the event since the event is owned by the chunk handle
+        unsafe { &*self.chunk }
+    }
+
+    fn field(&self, path: &str) -> Option<Accessor<'_>> {
+        path.split('.')
+            .filter(|s| !s.is_empty())
+            .try_fold(Accessor::new(self.chunk(), &self.value), |v, name| {
+                v.get_field(name)
+            })
+    }
+}
+
+/// Returns the message of the last failure on the current thread, or NULL if there is none.
+/// The string is valid until the next call of the functions on the thread.
+#[no_mangle]
+pub extern "C" fn jfrs_last_error() -> *const c_char {
+    guard(ptr::null(), || {
+        LAST_ERROR.with(|e| e.borrow().as_ref().map_or(ptr::null(), |s| s.as_ptr()))
+    })
+}
+
+/// Opens the recording file. Returns NULL on failure.
+///
+/// # Safety
+/// `path` must be a NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn jfrs_open(path: *const c_char) -> *mut JfrsReader {
+    guard(ptr::null_mut(), || {
+        if path.is_null() {
+            set_error("path is NULL");
+            return ptr::null_mut();
+        }
+        let path = match CStr::from_ptr(path).to_str() {
+            Ok(path) => path,
+            Err(e) => {
+                set_error(e);
+                return ptr::null_mut();
+            }
+        };
+        match File::open(path) {
+            Ok(file) => {
+                clear_error();
+                Box::into_raw(Box::new(JfrsReader {
+                    inner: JfrReader::new(file),
+                    finished: false,
+                }))
+            }
+            Err(e) => {
+                set_error(e);
+                ptr::null_mut()
+            }
+        }
+    })
+}
+
+/// Closes the recording file. The chunks read from the reader are still valid.
+///
+/// # Safety
+/// `reader` must be a handle returned by [`jfrs_open`] which is not closed yet, or NULL.
+#[no_mangle]
+pub unsafe extern "C" fn jfrs_close(reader: *mut JfrsReader) {
+    guard((), || {
+        if !reader.is_null() {
+            drop(Box::from_raw(reader));
+        }
+    })
+}
+
+/// Reads the next chunk. Returns NULL at the end of the file or on failure.
+/// The chunk must be freed by [`jfrs_chunk_free`].
+///
+/// # Safety
+/// `reader` must be a valid handle returned by [`jfrs_open`].
+#[no_mangle]
+pub unsafe extern "C" fn jfrs_next_chunk(reader: *mut JfrsReader) -> *mut JfrsChunk {
+    guard(ptr::null_mut(), || {
+        let reader = match reader.as_mut() {
+            Some(reader) => reader,
+            None => {
+                set_error("reader is NULL");
+                return ptr::null_mut();
+            }
+        };
+        if reader.finished {
+            clear_error();
+            return ptr::null_mut();
+        }
+        match reader.inner.chunks().next() {
+            Some(Ok((chunk_reader, chunk))) => {
+                clear_error();
+                Box::into_raw(Box::new(JfrsChunk {
+                    chunk: Box::new(chunk),
+                    reader: chunk_reader,
+                    offset: 0,
+                    current: None,
+                }))
+            }
+            Some(Err(e)) => {
+                reader.finished = true;
+                set_error(e);
+                ptr::null_mut()
+            }
+            None => {
+                reader.finished = true;
+                clear_error();
+                ptr::null_mut()
+            }
+        }
+    })
+}
+
+/// Frees the chunk and its events.
+///
+/// # Safety
+/// `chunk` must be a handle returned by [`jfrs_next_chunk`] which is not freed yet, or NULL.
+#[no_mangle]
+pub unsafe extern "C" fn jfrs_chunk_free(chunk: *mut JfrsChunk) {
+    guard((), || {
+        if !chunk.is_null() {
+            drop(Box::from_raw(chunk));
+        }
+    })
+}
+
+/// Reads the next event of the chunk. Returns NULL at the end of the chunk or on failure.
+/// The event is valid until the next call with the same chunk or [`jfrs_chunk_free`].
+///
+/// # Safety
+/// `chunk` must be a valid handle returned by [`jfrs_next_chunk`].
+#[no_mangle]
+pub unsafe extern "C" fn jfrs_next_event(chunk: *mut JfrsChunk) -> *const JfrsEvent {
+    guard(ptr::null(), || {
+        let chunk = match chunk.as_mut() {
+            Some(chunk) => chunk,
+            None => {
+                set_error("chunk is NULL");
+                return ptr::null();
+            }
+        };
+        chunk.current = None;
+        let mut events = chunk.reader.events_from_offset(&chunk.chunk, chunk.offset);
+        let next = events.next();
+        chunk.offset = events.offset();
+        match next {
+            Some(Ok(event)) => {
+                clear_error();
+                let class_id = event.class.class_id;
+                chunk.current.insert(JfrsEvent {
+                    chunk: chunk.chunk.as_ref(),
+                    class_id,
+                    value: event.into_value(),
+                })
+            }
+            Some(Err(e)) => {
+                // the rest of the chunk can't be located anymore
+                chunk.offset = u64::MAX;
+                set_error(e);
+                ptr::null()
+            }
+            None => {
+                clear_error();
+                ptr::null()
+            }
+        }
+    })
+}
+
+/// Copies the event type name (e.g. `jdk.ExecutionSample`) into `buf` as a NUL-terminated string.
+/// See [`jfrs_event_get_field_string`] for the return value.
+///
+/// # Safety
+/// `event` must be a valid handle returned by [`jfrs_next_event`],
+/// and `buf` must be writable for `buf_len` bytes or NULL.
+#[no_mangle]
+pub unsafe extern "C" fn jfrs_event_type_name(
+    event: *const JfrsEvent,
+    buf: *mut c_char,
+    buf_len: usize,
+) -> i64 {
+    guard(-1, || {
+        let event = match event.as_ref() {
+            Some(event) => event,
+            None => {
+                set_error("event is NULL");
+                return -1;
+            }
+        };
+        match event.chunk().metadata.type_pool.get(event.class_id) {
+            Some(class) => copy_string(class.name(), buf, buf_len),
+            None => {
+                set_error(format!("Class not found for id: {}", event.class_id));
+                -1
+            }
+        }
+    })
+}
+
+/// Copies the field value at the dot-separated path (e.g. `sampledThread.osName`) into `buf`
+/// as a NUL-terminated string. Numbers and booleans are formatted in decimal.
+///
+/// Returns the length of the whole string excluding NUL, like `snprintf`.
+/// The string is truncated if the length is not less than `buf_len`.
+/// Returns -1 if the field is missing, null or not a string nor a primitive.
+///
+/// # Safety
+/// `event` must be a valid handle returned by [`jfrs_next_event`], `path` must be
+/// a NUL-terminated string, and `buf` must be writable for `buf_len` bytes or NULL.
+#[no_mangle]
+pub unsafe extern "C" fn jfrs_event_get_field_string(
+    event: *const JfrsEvent,
+    path: *const c_char,
+    buf: *mut c_char,
+    buf_len: usize,
+) -> i64 {
+    guard(-1, || {
+        let value = match field(event, path) {
+            Some(value) => value,
+            None => return -1,
+        };
+        let s = if let Some(s) = value.as_str() {
+            s.to_string()
+        } else if let Some(v) = value.as_i64() {
+            v.to_string()
+        } else if let Some(v) = value.as_f64() {
+            v.to_string()
+        } else if let Some(v) = value.as_bool() {
+            v.to_string()
+        } else {
+            set_error("The field is not a string or a primitive");
+            return -1;
+        };
+        copy_string(&s, buf, buf_len)
+    })
+}
+
+/// Stores the integer field value at the dot-separated path into `out`.
+/// Returns 0 on success, or -1 if the field is missing or not an integer.
+///
+/// # Safety
+/// `event` must be a valid handle returned by [`jfrs_next_event`], `path` must be
+/// a NUL-terminated string, and `out` must be writable.
+#[no_mangle]
+pub unsafe extern "C" fn jfrs_event_get_field_i64(
+    event: *const JfrsEvent,
+    path: *const c_char,
+    out: *mut i64,
+) -> i32 {
+    guard(-1, || {
+        if out.is_null() {
+            set_error("out is NULL");
+            return -1;
+        }
+        let value = match field(event, path) {
+            Some(value) => value,
+            None => return -1,
+        };
+        match value.as_i64() {
+            Some(v) => {
+                *out = v;
+                0
+            }
+            None => {
+                set_error("The field is not an integer");
+                -1
+            }
+        }
+    })
+}
+
+unsafe fn field<'a>(event: *const JfrsEvent, path: *const c_char) -> Option<Accessor<'a>> {
+    let event = match event.as_ref() {
+        Some(event) => event,
+        None => {
+            set_error("event is NULL");
+            return None;
+        }
+    };
+    if path.is_null() {
+        set_error("path is NULL");
+        return None;
+    }
+    let path = match CStr::from_ptr(path).to_str() {
+        Ok(path) => path,
+        Err(e) => {
+            set_error(e);
+            return None;
+        }
+    };
+    match event.field(path) {
+        Some(value) => {
+            clear_error();
+            Some(value)
+        }
+        None => {
+            set_error(format!("Field not found: {}", path));
+            None
+        }
+    }
+}
+
+unsafe fn copy_string(s: &str, buf: *mut c_char, buf_len: usize) -> i64 {
+    if !buf.is_null() && buf_len > 0 {
+        let len = s.len().min(buf_len - 1);
+        ptr::copy_nonoverlapping(s.as_ptr() as *const c_char, buf, len);
+        *buf.add(len) = 0;
+    }
+    s.len() as i64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_read_events() {
+        unsafe {
+            let path = CString::new(test_data("profiler-wall.jfr")).unwrap();
+            let reader = jfrs_open(path.as_ptr());
+            assert!(!reader.is_null());
+
+            let type_name = CString::new("").unwrap();
+            let state = CString::new("state.name").unwrap();
+            let tid = CString::new("sampledThread.osThreadId").unwrap();
+            let mut buf = [0 as c_char; 64];
+            let mut runnable = 0;
+            let mut samples = 0;
+            loop {
+                let chunk = jfrs_next_chunk(reader);
+                if chunk.is_null() {
+                    break;
+                }
+                loop {
+                    let event = jfrs_next_event(chunk);
+                    if event.is_null() {
+                        break;
+                    }
+                    jfrs_event_type_name(event, buf.as_mut_ptr(), buf.len());
+                    if CStr::from_ptr(buf.as_ptr()).to_str().unwrap() != "jdk.ExecutionSample" {
+                        continue;
+                    }
+                    samples += 1;
+                    let len = jfrs_event_get_field_string(
+                        event,
+                        state.as_ptr(),
+                        buf.as_mut_ptr(),
+                        buf.len(),
+                    );
+                    assert_eq!(len as usize, CStr::from_ptr(buf.as_ptr()).to_bytes().len());
+                    if CStr::from_ptr(buf.as_ptr()).to_str().unwrap() == "STATE_RUNNABLE" {
+                        runnable += 1;
+                    }
+                    let mut id = 0;
+                    assert_eq!(jfrs_event_get_field_i64(event, tid.as_ptr(), &mut id), 0);
+                    assert!(id > 0);
+                    assert_eq!(
+                        jfrs_event_get_field_string(
+                            event,
+                            type_name.as_ptr(),
+                            buf.as_mut_ptr(),
+                            buf.len()
+                        ),
+                        -1
+                    );
+                }
+                jfrs_chunk_free(chunk);
+            }
+            assert!(jfrs_last_error().is_null());
+            assert_eq!((samples, runnable), (8836, 673));
+            assert!(jfrs_next_chunk(reader).is_null());
+            jfrs_close(reader);
+        }
+    }
+
+    #[test]
+    fn test_truncate() {
+        unsafe {
+            let mut buf = [0 as c_char; 4];
+            assert_eq!(copy_string("abcdef", buf.as_mut_ptr(), buf.len()), 6);
+            assert_eq!(CStr::from_ptr(buf.as_ptr()).to_str().unwrap(), "abc");
+            assert_eq!(copy_string("abcdef", ptr::null_mut(), 0), 6);
+        }
+    }
+
+    #[test]
+    fn test_error() {
+        unsafe {
+            let path = CString::new("/no/such/file.jfr").unwrap();
+            assert!(jfrs_open(path.as_ptr()).is_null());
+            assert!(!jfrs_last_error().is_null());
+            assert!(jfrs_next_chunk(ptr::null_mut()).is_null());
+            let message = CStr::from_ptr(jfrs_last_error()).to_str().unwrap();
+            assert_eq!(message, "reader is NULL");
+        }
+    }
+
+    #[test]
+    fn test_field_error() {
+        unsafe {
+            let path = CString::new(test_data("profiler-wall.jfr")).unwrap();
+            let reader = jfrs_open(path.as_ptr());
+            let chunk = jfrs_next_chunk(reader);
+            let event = jfrs_next_event(chunk);
+            assert!(!event.is_null());
+
+            let mut out = 0;
+            let missing = CString::new("noSuchField").unwrap();
+            assert_eq!(
+                jfrs_event_get_field_i64(event, missing.as_ptr(), &mut out),
+                -1
+            );
+            let message = CStr::from_ptr(jfrs_last_error()).to_str().unwrap();
+            assert_eq!(message, "Field not found: noSuchField");
+
+            let start_time = CString::new("startTime").unwrap();
+            assert_eq!(
+                jfrs_event_get_field_i64(event, start_time.as_ptr(), &mut out),
+                0
+            );
+            assert!(jfrs_last_error().is_null());
+
+            let this = CString::new("").unwrap();
+            assert_eq!(jfrs_event_get_field_i64(event, this.as_ptr(), &mut out), -1);
+            let message = CStr::from_ptr(jfrs_last_error()).to_str().unwrap();
+            assert_eq!(message, "The field is not an integer");
+
+            jfrs_chunk_free(chunk);
+            jfrs_close(reader);
+        }
+    }
+
+    #[test]
+    fn test_panic() {
+        assert_eq!(guard(-1, || panic!("boom")), -1);
+        let message = unsafe { CStr::from_ptr(jfrs_last_error()) };
+        assert_eq!(message.to_str().unwrap(), "Panicked: boom");
+    }
+
+    fn test_data(file_name: &str) -> String {
+        PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("..")
+            .join("test-data")
+            .join(file_name)
+            .to_str()
+            .unwrap()
+            .to_string()
+    }
+}
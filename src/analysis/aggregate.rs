@@ -0,0 +1,366 @@
+//! Group-by aggregation over event fields, to answer "top N by field" questions
+//! (e.g. bytes read by each thread) without exporting the events to another system.
+//!
+//! ```no_run
+//! use jfrs::analysis::aggregate::Aggregator;
+//! use jfrs::reader::JfrReader;
+//! use std::fs::File;
+//!
+//! let mut reader = JfrReader::new(File::open("/path/to/recording.jfr").unwrap());
+//! let table = Aggregator::new()
+//!     .event_type("jdk.FileRead")
+//!     .group_by_path("eventThread.javaName")
+//!     .count()
+//!     .sum("bytesRead")
+//!     .run(&mut reader)
+//!     .unwrap()
+//!     .top("sum(bytesRead)", 10);
+//! ```
+
+use crate::reader::event::{Accessor, Event};
+use crate::reader::{JfrReader, Result};
+use std::collections::HashMap;
+use std::io::{Read, Seek};
+
+/// Aggregate function over the events in a group
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Aggregation {
+    Count,
+    /// The sum of the field. Timespans are summed in nanoseconds
+    Sum(String),
+    Min(String),
+    Max(String),
+    Avg(String),
+}
+
+impl Aggregation {
+    /// The column name, e.g. `count`, `sum(bytesRead)`
+    pub fn column_name(&self) -> String {
+        match self {
+            Aggregation::Count => "count".to_string(),
+            Aggregation::Sum(path) => format!("sum({})", path),
+            Aggregation::Min(path) => format!("min({})", path),
+            Aggregation::Max(path) => format!("max({})", path),
+            Aggregation::Avg(path) => format!("avg({})", path),
+        }
+    }
+
+    fn path(&self) -> Option<&str> {
+        match self {
+            Aggregation::Count => None,
+            Aggregation::Sum(path)
+            | Aggregation::Min(path)
+            | Aggregation::Max(path)
+            | Aggregation::Avg(path) => Some(path),
+        }
+    }
+}
+
+/// Result of the aggregation
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Table {
+    /// The group-by paths followed by the aggregation names
+    pub columns: Vec<String>,
+    pub rows: Vec<Row>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Row {
+    /// The values of the group-by paths. None if the value is missing
+    pub keys: Vec<Option<String>>,
+    /// The results of the aggregations. None if no event in the group has the field
+    pub values: Vec<Option<f64>>,
+}
+
+impl Table {
+    /// Returns the value of the row in the aggregation column
+    pub fn value(&self, row: &Row, column: &str) -> Option<f64> {
+        let idx = self.value_index(column)?;
+        row.values[idx]
+    }
+
+    /// Sorts the rows by the aggregation column in descending order. Missing values come last
+    pub fn sort_by(&mut self, column: &str) {
+        if let Some(idx) = self.value_index(column) {
+            self.rows.sort_by(|a, b| {
+                let key = |r: &Row| r.values[idx].unwrap_or(f64::NEG_INFINITY);
+                key(b).total_cmp(&key(a))
+            });
+        }
+    }
+
+    /// Keeps the top `n` rows by the aggregation column
+    pub fn top(mut self, column: &str, n: usize) -> Self {
+        self.sort_by(column);
+        self.rows.truncate(n);
+        self
+    }
+
+    fn value_index(&self, column: &str) -> Option<usize> {
+        let key_count = self.columns.len() - self.rows.first().map_or(0, |r| r.values.len());
+        self.columns
+            .iter()
+            .skip(key_count)
+            .position(|c| c == column)
+    }
+}
+
+/// Aggregates the events grouped by the values of the field paths
+#[derive(Debug, Default)]
+pub struct Aggregator {
+    event_type: Option<String>,
+    group_by: Vec<String>,
+    aggregations: Vec<Aggregation>,
+    groups: HashMap<Vec<Option<String>>, Vec<Accumulator>>,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct Accumulator {
+    count: u64,
+    sum: f64,
+    min: Option<f64>,
+    max: Option<f64>,
+}
+
+impl Aggregator {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Aggregates only the events of the type. All events are aggregated by default
+    pub fn event_type(mut self, event_type: &str) -> Self {
+        self.event_type = Some(event_type.to_string());
+        self
+    }
+
+    /// Groups the events by the value of the dot-separated path (e.g. `eventThread.javaName`)
+    pub fn group_by_path(mut self, path: &str) -> Self {
+        self.group_by.push(path.to_string());
+        self
+    }
+
+    pub fn count(self) -> Self {
+        self.aggregate(Aggregation::Count)
+    }
+
+    pub fn sum(self, path: &str) -> Self {
+        self.aggregate(Aggregation::Sum(path.to_string()))
+    }
+
+    pub fn min(self, path: &str) -> Self {
+        self.aggregate(Aggregation::Min(path.to_string()))
+    }
+
+    pub fn max(self, path: &str) -> Self {
+        self.aggregate(Aggregation::Max(path.to_string()))
+    }
+
+    pub fn avg(self, path: &str) -> Self {
+        self.aggregate(Aggregation::Avg(path.to_string()))
+    }
+
+    pub fn aggregate(mut self, aggregation: Aggregation) -> Self {
+        self.aggregations.push(aggregation);
+        self
+    }
+
+    /// Reads all chunks of the recording and returns the table
+    pub fn run<T: Read + Seek>(mut self, reader: &mut JfrReader<T>) -> Result<Table> {
+        for chunk in reader.chunks() {
+            let (chunk_reader, chunk) = chunk?;
+            for event in chunk_reader.events(&chunk) {
+                self.add(&event?);
+            }
+        }
+        Ok(self.finish())
+    }
+
+    /// Adds the event. Returns false if the event is not of the aggregated type
+    pub fn add(&mut self, event: &Event) -> bool {
+        if self
+            .event_type
+            .as_deref()
+            .is_some_and(|t| t != event.class.name())
+        {
+            return false;
+        }
+        let value = event.value();
+        let keys = self
+            .group_by
+            .iter()
+            .map(|path| evaluate(&value, path).and_then(|v| key(&v)))
+            .collect::<Vec<_>>();
+        let accumulators = self
+            .groups
+            .entry(keys)
+            .or_insert_with(|| vec![Accumulator::default(); self.aggregations.len()]);
+        for (aggregation, acc) in self.aggregations.iter().zip(accumulators.iter_mut()) {
+            let v = match aggregation.path() {
+                Some(path) => match evaluate(&value, path).and_then(|v| numeric(&v, event)) {
+                    Some(v) => v,
+                    None => continue,
+                },
+                None => 0.0,
+            };
+            acc.count += 1;
+            acc.sum += v;
+            acc.min = Some(acc.min.map_or(v, |m| m.min(v)));
+            acc.max = Some(acc.max.map_or(v, |m| m.max(v)));
+        }
+        true
+    }
+
+    /// Returns the table with the rows ordered by the keys
+    pub fn finish(self) -> Table {
+        let mut columns = self.group_by;
+        columns.extend(self.aggregations.iter().map(|a| a.column_name()));
+        let mut rows = self
+            .groups
+            .into_iter()
+            .map(|(keys, accumulators)| Row {
+                keys,
+                values: self
+                    .aggregations
+                    .iter()
+                    .zip(accumulators)
+                    .map(|(aggregation, acc)| match aggregation {
+                        Aggregation::Count => Some(acc.count as f64),
+                        _ if acc.count == 0 => None,
+                        Aggregation::Sum(_) => Some(acc.sum),
+                        Aggregation::Min(_) => acc.min,
+                        Aggregation::Max(_) => acc.max,
+                        Aggregation::Avg(_) => Some(acc.sum / acc.count as f64),
+                    })
+                    .collect(),
+            })
+            .collect::<Vec<_>>();
+        rows.sort_by(|a, b| a.keys.cmp(&b.keys));
+        Table { columns, rows }
+    }
+}
+
+pub(crate) fn evaluate<'a>(value: &Accessor<'a>, path: &str) -> Option<Accessor<'a>> {
+    path.split('.')
+        .filter(|s| !s.is_empty())
+        .try_fold(*value, |v, name| v.get_field(name))
+}
+
+pub(crate) fn key(value: &Accessor) -> Option<String> {
+    if let Some(s) = value.as_str() {
+        return Some(s.to_string());
+    }
+    if let Some(v) = value.as_i64() {
+        return Some(v.to_string());
+    }
+    if let Some(v) = value.as_bool() {
+        return Some(v.to_string());
+    }
+    value.resolve().map(|v| v.to_string())
+}
+
+/// Returns the numeric value, converting timespans to nanoseconds
+pub(crate) fn numeric(value: &Accessor, event: &Event) -> Option<f64> {
+    match value.field() {
+        Some(field) if field.is_timespan() => field
+            .timespan_nanos(value.as_i64()?, &event.chunk.header)
+            .map(|v| v as f64),
+        _ => value.as_u64().map(|v| v as f64).or_else(|| value.as_f64()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::test_data;
+    use std::fs::File;
+
+    #[test]
+    fn test_group_by() {
+        let mut reader = JfrReader::new(File::open(test_data("recording.jfr")).unwrap());
+        let table = Aggregator::new()
+            .event_type("jdk.ThreadPark")
+            .group_by_path("eventThread.javaName")
+            .count()
+            .sum("duration")
+            .max("duration")
+            .avg("duration")
+            .min("no-such-field")
+            .run(&mut reader)
+            .unwrap();
+        assert_eq!(
+            table.columns,
+            vec![
+                "eventThread.javaName",
+                "count",
+                "sum(duration)",
+                "max(duration)",
+                "avg(duration)",
+                "min(no-such-field)"
+            ]
+        );
+        assert!(!table.rows.is_empty());
+        assert!(table.rows.windows(2).all(|w| w[0].keys < w[1].keys));
+
+        // compare with the events
+        let mut reader = JfrReader::new(File::open(test_data("recording.jfr")).unwrap());
+        let (chunk_reader, chunk) = reader.chunks().next().unwrap().unwrap();
+        let mut count = 0;
+        let mut sum = 0;
+        for event in chunk_reader.events(&chunk).flatten() {
+            if event.class.name() == "jdk.ThreadPark" {
+                count += 1;
+                let d = event.value().get_field("duration").unwrap();
+                sum += chunk.header.ticks_to_nanos(d.as_i64().unwrap());
+            }
+        }
+        let total_count = table
+            .rows
+            .iter()
+            .map(|r| table.value(r, "count").unwrap())
+            .sum::<f64>();
+        let total_sum = table
+            .rows
+            .iter()
+            .map(|r| table.value(r, "sum(duration)").unwrap())
+            .sum::<f64>();
+        assert_eq!(total_count, count as f64);
+        assert!((total_sum - sum as f64).abs() < 1.0);
+        for row in table.rows.iter() {
+            assert_eq!(row.values[4], None);
+            assert!(row.values[2].unwrap() <= row.values[1].unwrap());
+        }
+
+        let top = table.clone().top("sum(duration)", 1);
+        assert_eq!(top.rows.len(), 1);
+        let max = table
+            .rows
+            .iter()
+            .map(|r| table.value(r, "sum(duration)").unwrap())
+            .fold(f64::MIN, f64::max);
+        assert_eq!(top.value(&top.rows[0], "sum(duration)"), Some(max));
+    }
+
+    #[test]
+    fn test_without_group() {
+        let mut reader = JfrReader::new(File::open(test_data("profiler-wall.jfr")).unwrap());
+        let table = Aggregator::new()
+            .event_type("jdk.ExecutionSample")
+            .count()
+            .run(&mut reader)
+            .unwrap();
+        assert_eq!(table.rows.len(), 1);
+        assert!(table.rows[0].keys.is_empty());
+        assert_eq!(table.rows[0].values, vec![Some(8836.0)]);
+
+        let mut reader = JfrReader::new(File::open(test_data("profiler-wall.jfr")).unwrap());
+        let table = Aggregator::new()
+            .event_type("jdk.ExecutionSample")
+            .group_by_path("state.name")
+            .count()
+            .run(&mut reader)
+            .unwrap()
+            .top("count", 2);
+        assert_eq!(table.rows[0].keys, vec![Some("STATE_SLEEPING".to_string())]);
+        assert_eq!(table.rows[1].values, vec![Some(673.0)]);
+    }
+}
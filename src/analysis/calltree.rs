@@ -0,0 +1,448 @@
+//! Call tree aggregated from stack traces, which is the core of "hot methods" reports and flame graphs.
+
+use crate::analysis::frame::{stack_frames, Frame};
+use crate::analysis::weight::Weight;
+use crate::reader::event::Event;
+use crate::reader::{JfrReader, Result};
+use rustc_hash::FxHashMap;
+use std::io::{Read, Seek};
+
+/// Index of the node in the tree
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct NodeId(usize);
+
+#[derive(Debug, Clone)]
+pub struct Node {
+    /// None for the root
+    pub frame: Option<Frame>,
+    /// The weight of the stacks which end at this node
+    pub self_weight: u64,
+    /// The weight of the stacks which go through this node
+    pub total_weight: u64,
+    children: FxHashMap<Frame, NodeId>,
+}
+
+/// Tree of frames weighted by samples.
+///
+/// In the top-down tree (which is built by [`CallTree::add_stack`]), children of a node are the callees.
+/// In the bottom-up tree (see [`CallTree::inverted`]), children are the callers.
+#[derive(Debug, Clone)]
+pub struct CallTree {
+    nodes: Vec<Node>,
+    inverted: bool,
+}
+
+impl Default for CallTree {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl CallTree {
+    /// Creates an empty top-down tree
+    pub fn new() -> Self {
+        Self {
+            nodes: vec![Node {
+                frame: None,
+                self_weight: 0,
+                total_weight: 0,
+                children: FxHashMap::default(),
+            }],
+            inverted: false,
+        }
+    }
+
+    /// Builds the top-down tree from the stack traces of the events of the type (e.g. `jdk.ExecutionSample`).
+    /// Each event is weighted as 1.
+    pub fn build<T: Read + Seek>(reader: &mut JfrReader<T>, event_type: &str) -> Result<Self> {
+        let mut tree = Self::new();
+        for chunk in reader.chunks() {
+            let (chunk_reader, chunk) = chunk?;
+            for event in chunk_reader.events(&chunk) {
+                let event = event?;
+                if event.class.name() == event_type {
+                    tree.add_event(&event, 1);
+                }
+            }
+        }
+        Ok(tree)
+    }
+
+    /// Builds the top-down tree in the same way as [`CallTree::build`], weighting each event by `weight`.
+    /// Events which can't be weighted are skipped.
+    pub fn build_weighted<T: Read + Seek>(
+        reader: &mut JfrReader<T>,
+        event_type: &str,
+        weight: &dyn Weight,
+    ) -> Result<Self> {
+        let mut tree = Self::new();
+        for chunk in reader.chunks() {
+            let (chunk_reader, chunk) = chunk?;
+            for event in chunk_reader.events(&chunk) {
+                let event = event?;
+                if event.class.name() != event_type {
+                    continue;
+                }
+                if let Some(w) = weight.weight(&event) {
+                    tree.add_event(&event, w);
+                }
+            }
+        }
+        Ok(tree)
+    }
+
+    pub fn root(&self) -> NodeId {
+        NodeId(0)
+    }
+
+    pub fn node(&self, id: NodeId) -> &Node {
+        &self.nodes[id.0]
+    }
+
+    /// Returns the children ordered by the total weight in descending order
+    pub fn children(&self, id: NodeId) -> Vec<NodeId> {
+        let mut children = self.nodes[id.0]
+            .children
+            .values()
+            .copied()
+            .collect::<Vec<_>>();
+        children.sort_by(|a, b| {
+            self.nodes[b.0]
+                .total_weight
+                .cmp(&self.nodes[a.0].total_weight)
+                .then_with(|| self.nodes[a.0].frame.cmp(&self.nodes[b.0].frame))
+        });
+        children
+    }
+
+    /// The number of nodes including the root
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.len() == 1
+    }
+
+    /// Returns true if this is the bottom-up tree
+    pub fn is_inverted(&self) -> bool {
+        self.inverted
+    }
+
+    /// Adds the stack with the weight.
+    /// `frames` are ordered from the top (the innermost callee), in the same order as `jdk.types.StackTrace`.
+    pub fn add_stack(&mut self, frames: &[Frame], weight: u64) {
+        if self.inverted {
+            self.add_path(frames.iter(), weight);
+        } else {
+            self.add_path(frames.iter().rev(), weight);
+        }
+    }
+
+    /// Adds the stack trace of the event (`stackTrace` field).
+    /// The frame types are ignored so that interpreted and compiled frames of the same method are merged.
+    /// Returns false if the event has no stack trace.
+    pub fn add_event(&mut self, event: &Event, weight: u64) -> bool {
+        let mut frames = match event.value().get_field("stackTrace") {
+            Some(stack_trace) => stack_frames(&stack_trace),
+            None => return false,
+        };
+        for frame in frames.iter_mut() {
+            frame.frame_type = None;
+        }
+        self.add_stack(&frames, weight);
+        true
+    }
+
+    /// Merges the other tree of the same orientation into this tree
+    pub fn merge(&mut self, other: &CallTree) {
+        assert_eq!(
+            self.inverted, other.inverted,
+            "Trees of different orientation can't be merged"
+        );
+        self.merge_node(self.root(), other, other.root());
+    }
+
+    /// Returns the tree of the subtree rooted at the node, whose root children are the node itself.
+    /// This is useful to focus on a method.
+    pub fn subtree(&self, id: NodeId) -> CallTree {
+        let mut tree = Self::new();
+        tree.inverted = self.inverted;
+        if id == self.root() {
+            tree.merge(self);
+            return tree;
+        }
+        let node = &self.nodes[id.0];
+        let child = tree.child(tree.root(), node.frame.clone().unwrap());
+        tree.nodes[0].total_weight = node.total_weight;
+        tree.nodes[child.0].total_weight = node.total_weight;
+        tree.nodes[child.0].self_weight = node.self_weight;
+        for &c in node.children.values() {
+            tree.copy_subtree(child, self, c);
+        }
+        tree
+    }
+
+    /// Returns the bottom-up tree, whose root children are the frames where the weight is spent.
+    /// Inverting the bottom-up tree returns the top-down tree.
+    pub fn inverted(&self) -> CallTree {
+        let mut tree = Self::new();
+        tree.inverted = !self.inverted;
+        let mut path = vec![];
+        self.visit_stacks(self.root(), &mut path, &mut |path, weight| {
+            // the path from the root of one orientation is the path from the leaf of the other
+            tree.add_path(path.iter().rev().copied(), weight);
+        });
+        tree
+    }
+
+    /// Removes the nodes whose total weight is less than `min_weight`.
+    /// The weight of removed nodes is kept in the total weight of the ancestors.
+    pub fn prune(&mut self, min_weight: u64) {
+        let mut tree = Self::new();
+        tree.inverted = self.inverted;
+        tree.nodes[0].self_weight = self.nodes[0].self_weight;
+        tree.nodes[0].total_weight = self.nodes[0].total_weight;
+        let mut stack = vec![(self.root(), tree.root())];
+        while let Some((src, dst)) = stack.pop() {
+            for &c in self.nodes[src.0].children.values() {
+                let node = &self.nodes[c.0];
+                if node.total_weight < min_weight {
+                    continue;
+                }
+                let id = tree.child(dst, node.frame.clone().unwrap());
+                tree.nodes[id.0].self_weight = node.self_weight;
+                tree.nodes[id.0].total_weight = node.total_weight;
+                stack.push((c, id));
+            }
+        }
+        *self = tree;
+    }
+
+    fn add_path<'a, I: Iterator<Item = &'a Frame>>(&mut self, frames: I, weight: u64) {
+        let mut current = self.root();
+        self.nodes[0].total_weight += weight;
+        for frame in frames {
+            current = self.child(current, frame.clone());
+            self.nodes[current.0].total_weight += weight;
+        }
+        self.nodes[current.0].self_weight += weight;
+    }
+
+    fn child(&mut self, parent: NodeId, frame: Frame) -> NodeId {
+        if let Some(&id) = self.nodes[parent.0].children.get(&frame) {
+            return id;
+        }
+        let id = NodeId(self.nodes.len());
+        self.nodes.push(Node {
+            frame: Some(frame.clone()),
+            self_weight: 0,
+            total_weight: 0,
+            children: FxHashMap::default(),
+        });
+        self.nodes[parent.0].children.insert(frame, id);
+        id
+    }
+
+    fn merge_node(&mut self, dst: NodeId, other: &CallTree, src: NodeId) {
+        let node = &other.nodes[src.0];
+        self.nodes[dst.0].self_weight += node.self_weight;
+        self.nodes[dst.0].total_weight += node.total_weight;
+        for (frame, &c) in node.children.iter() {
+            let id = self.child(dst, frame.clone());
+            self.merge_node(id, other, c);
+        }
+    }
+
+    fn copy_subtree(&mut self, parent: NodeId, other: &CallTree, src: NodeId) {
+        let node = &other.nodes[src.0];
+        let id = self.child(parent, node.frame.clone().unwrap());
+        self.merge_node(id, other, src);
+    }
+
+    /// Calls `f` with the path from the root and the self weight for each node having self weight
+    fn visit_stacks<'a, F: FnMut(&[&'a Frame], u64)>(
+        &'a self,
+        id: NodeId,
+        path: &mut Vec<&'a Frame>,
+        f: &mut F,
+    ) {
+        let node = &self.nodes[id.0];
+        if node.self_weight > 0 {
+            f(path, node.self_weight);
+        }
+        for &c in node.children.values() {
+            path.push(self.nodes[c.0].frame.as_ref().unwrap());
+            self.visit_stacks(c, path, f);
+            path.pop();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analysis::weight::SamplingInterval;
+    use crate::test_util::test_data;
+    use std::fs::File;
+
+    fn frame(name: &str) -> Frame {
+        Frame {
+            class_name: "Test".to_string(),
+            method_name: name.to_string(),
+            descriptor: None,
+            line_number: -1,
+            frame_type: None,
+        }
+    }
+
+    fn frames(names: &str) -> Vec<Frame> {
+        names.split(',').map(frame).collect()
+    }
+
+    /// Renders the tree as `name(total/self)` lines for assertions
+    fn render(tree: &CallTree) -> Vec<String> {
+        fn walk(tree: &CallTree, id: NodeId, depth: usize, out: &mut Vec<String>) {
+            for c in tree.children(id) {
+                let node = tree.node(c);
+                out.push(format!(
+                    "{}{}({}/{})",
+                    "  ".repeat(depth),
+                    node.frame.as_ref().unwrap().method_name,
+                    node.total_weight,
+                    node.self_weight
+                ));
+                walk(tree, c, depth + 1, out);
+            }
+        }
+        let mut out = vec![];
+        walk(tree, tree.root(), 0, &mut out);
+        out
+    }
+
+    fn sample_tree() -> CallTree {
+        let mut tree = CallTree::new();
+        // top frame first
+        tree.add_stack(&frames("c,b,main"), 3);
+        tree.add_stack(&frames("d,b,main"), 2);
+        tree.add_stack(&frames("c,main"), 1);
+        tree.add_stack(&frames("main"), 1);
+        tree
+    }
+
+    #[test]
+    fn test_top_down() {
+        let tree = sample_tree();
+        assert!(!tree.is_inverted());
+        assert_eq!(tree.node(tree.root()).total_weight, 7);
+        assert_eq!(
+            render(&tree),
+            vec![
+                "main(7/1)",
+                "  b(5/0)",
+                "    c(3/3)",
+                "    d(2/2)",
+                "  c(1/1)",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_inverted() {
+        let tree = sample_tree();
+        let inverted = tree.inverted();
+        assert!(inverted.is_inverted());
+        assert_eq!(
+            render(&inverted),
+            vec![
+                "c(4/0)",
+                "  b(3/0)",
+                "    main(3/3)",
+                "  main(1/1)",
+                "d(2/0)",
+                "  b(2/0)",
+                "    main(2/2)",
+                "main(1/1)",
+            ]
+        );
+        assert_eq!(render(&inverted.inverted()), render(&tree));
+
+        // stacks are always given from the top
+        let mut other = CallTree::new().inverted();
+        other.add_stack(&frames("c,b,main"), 1);
+        assert_eq!(render(&other), vec!["c(1/0)", "  b(1/0)", "    main(1/1)"]);
+    }
+
+    #[test]
+    fn test_merge_and_subtree() {
+        let mut tree = sample_tree();
+        tree.merge(&sample_tree());
+        assert_eq!(tree.node(tree.root()).total_weight, 14);
+        assert_eq!(render(&tree)[1], "  b(10/0)");
+
+        let b = tree.children(tree.children(tree.root())[0])[0];
+        let subtree = tree.subtree(b);
+        assert_eq!(render(&subtree), vec!["b(10/0)", "  c(6/6)", "  d(4/4)"]);
+        assert_eq!(render(&tree.subtree(tree.root())), render(&tree));
+    }
+
+    #[test]
+    fn test_prune() {
+        let mut tree = sample_tree();
+        let len = tree.len();
+        tree.prune(2);
+        assert_eq!(
+            render(&tree),
+            vec!["main(7/1)", "  b(5/0)", "    c(3/3)", "    d(2/2)"]
+        );
+        assert_eq!(tree.len(), len - 1);
+        tree.prune(100);
+        assert!(tree.is_empty());
+        assert_eq!(tree.node(tree.root()).total_weight, 7);
+    }
+
+    #[test]
+    fn test_build() {
+        let mut reader = JfrReader::new(File::open(test_data("profiler-wall.jfr")).unwrap());
+        let tree = CallTree::build(&mut reader, "jdk.ExecutionSample").unwrap();
+        assert_eq!(tree.node(tree.root()).total_weight, 8836);
+        let total = tree
+            .children(tree.root())
+            .iter()
+            .map(|c| tree.node(*c).total_weight)
+            .sum::<u64>();
+        assert_eq!(total + tree.node(tree.root()).self_weight, 8836);
+
+        let inverted = tree.inverted();
+        let self_total = inverted
+            .children(inverted.root())
+            .iter()
+            .map(|c| inverted.node(*c).total_weight)
+            .sum::<u64>();
+        assert_eq!(
+            self_total + inverted.node(inverted.root()).self_weight,
+            8836
+        );
+    }
+
+    #[test]
+    fn test_build_weighted() {
+        let mut reader = JfrReader::new(File::open(test_data("profiler-wall.jfr")).unwrap());
+        let tree = CallTree::build_weighted(
+            &mut reader,
+            "jdk.ExecutionSample",
+            &SamplingInterval::new(10_000_000),
+        )
+        .unwrap();
+        assert_eq!(tree.node(tree.root()).total_weight, 8836 * 10_000_000);
+
+        // only the samples of running threads
+        let running = |e: &Event| {
+            let state = e.value().get_field("state")?.get_field("name")?;
+            (state.as_str()? == "STATE_RUNNABLE").then_some(1)
+        };
+        let mut reader = JfrReader::new(File::open(test_data("profiler-wall.jfr")).unwrap());
+        let tree = CallTree::build_weighted(&mut reader, "jdk.ExecutionSample", &running).unwrap();
+        assert_eq!(tree.node(tree.root()).total_weight, 673);
+    }
+}
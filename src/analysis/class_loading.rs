@@ -0,0 +1,526 @@
+//! Aggregation of `jdk.ClassLoad`, `jdk.ClassDefine`, `jdk.ClassUnload` and `jdk.ClassLoadingStatistics`
+//! events by the class loader and the package over time, to diagnose class loader leaks.
+//!
+//! `jdk.ClassLoad` and `jdk.ClassDefine` are disabled in the default settings,
+//! so enable them in the recording (e.g. `jdk.ClassDefine#enabled=true`) to get per-class-loader counts.
+
+use crate::analysis::frame::{class_name, symbol};
+use crate::reader::event::Event;
+use crate::reader::known_types::KnownType;
+use crate::reader::{JfrReader, Result};
+use std::collections::{BTreeMap, HashMap};
+use std::io::{Read, Seek};
+use std::time::Duration;
+
+const DEFAULT_WINDOW: Duration = Duration::from_secs(60);
+
+/// Number of the class loading events
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ClassCounts {
+    /// `jdk.ClassLoad`
+    pub loaded: u64,
+    /// `jdk.ClassDefine`
+    pub defined: u64,
+    /// `jdk.ClassUnload`
+    pub unloaded: u64,
+}
+
+impl ClassCounts {
+    /// Classes defined but not unloaded, which keeps growing if the class loaders leak
+    pub fn retained(&self) -> i64 {
+        self.defined as i64 - self.unloaded as i64
+    }
+
+    fn total(&self) -> u64 {
+        self.loaded + self.defined + self.unloaded
+    }
+}
+
+/// Counts in the window of [`ClassLoadingAggregator::window`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct CountWindow {
+    /// The start of the window in nanoseconds since UNIX epoch
+    pub start_nanos: i64,
+    pub counts: ClassCounts,
+}
+
+/// Counts of the classes by the defining class loader
+#[derive(Debug, Clone, PartialEq)]
+pub struct ClassLoaderSummary {
+    /// The class of the class loader (e.g. `jdk.internal.loader.ClassLoaders$AppClassLoader`),
+    /// None for the bootstrap class loader
+    pub type_name: Option<String>,
+    /// The name given to the class loader (e.g. `app`). Usually None for custom class loaders
+    pub name: Option<String>,
+    pub counts: ClassCounts,
+    /// Windows with any event, in the ascending order
+    pub windows: Vec<CountWindow>,
+}
+
+/// Counts of the classes by the package
+#[derive(Debug, Clone, PartialEq)]
+pub struct PackageSummary {
+    /// e.g. `java.lang`. Empty for the unnamed package
+    pub name: String,
+    pub counts: ClassCounts,
+    /// Windows with any event, in the ascending order
+    pub windows: Vec<CountWindow>,
+}
+
+/// Sample of `jdk.ClassLoadingStatistics`
+#[derive(Debug, Clone, PartialEq)]
+pub struct ClassLoadingSample {
+    /// Nanoseconds since UNIX epoch
+    pub time_nanos: i64,
+    pub loaded_class_count: i64,
+    pub unloaded_class_count: i64,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ClassLoadingReport {
+    /// Sorted by the number of the events in descending order
+    pub class_loaders: Vec<ClassLoaderSummary>,
+    /// Sorted by the number of the events in descending order
+    pub packages: Vec<PackageSummary>,
+    /// Sorted by the time
+    pub statistics: Vec<ClassLoadingSample>,
+}
+
+type ClassLoaderKey = (Option<String>, Option<String>);
+
+#[derive(Debug, Default)]
+struct Counter {
+    counts: ClassCounts,
+    windows: BTreeMap<i64, ClassCounts>,
+}
+
+impl Counter {
+    fn add(&mut self, window_start: i64, f: impl Fn(&mut ClassCounts)) {
+        f(&mut self.counts);
+        f(self.windows.entry(window_start).or_default());
+    }
+
+    fn windows(&self) -> Vec<CountWindow> {
+        self.windows
+            .iter()
+            .map(|(start_nanos, counts)| CountWindow {
+                start_nanos: *start_nanos,
+                counts: *counts,
+            })
+            .collect()
+    }
+}
+
+/// Aggregates the class loading events.
+///
+/// Class loaders are identified by their class and name, since the constants of different chunks are unrelated.
+/// Instances of the same class loader class without names are counted together.
+#[derive(Debug)]
+pub struct ClassLoadingAggregator {
+    window_nanos: i64,
+    class_loaders: HashMap<ClassLoaderKey, Counter>,
+    packages: HashMap<String, Counter>,
+    statistics: Vec<ClassLoadingSample>,
+}
+
+impl Default for ClassLoadingAggregator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ClassLoadingAggregator {
+    pub fn new() -> Self {
+        Self {
+            window_nanos: DEFAULT_WINDOW.as_nanos() as i64,
+            class_loaders: HashMap::new(),
+            packages: HashMap::new(),
+            statistics: vec![],
+        }
+    }
+
+    /// The width of the windows to count the events over time, aligned to UNIX epoch. Default is 1 minute.
+    /// Panics if the width is zero
+    pub fn window(mut self, width: Duration) -> Self {
+        let width_nanos = i64::try_from(width.as_nanos()).unwrap_or(i64::MAX);
+        assert!(width_nanos > 0, "width must be positive");
+        self.window_nanos = width_nanos;
+        self
+    }
+
+    /// Adds the event. Returns false if the event is not a class loading event
+    pub fn add(&mut self, event: &Event) -> bool {
+        let (class_field, count): (&str, fn(&mut ClassCounts)) = match event.class.known_type() {
+            Some(KnownType::ClassLoad) => ("loadedClass", |c| c.loaded += 1),
+            Some(KnownType::ClassDefine) => ("definedClass", |c| c.defined += 1),
+            Some(KnownType::ClassUnload) => ("unloadedClass", |c| c.unloaded += 1),
+            Some(KnownType::ClassLoadingStatistics) => {
+                self.add_statistics(event);
+                return true;
+            }
+            _ => return false,
+        };
+
+        let value = event.value();
+        let time_nanos = start_time_nanos(event).unwrap_or(event.chunk().header.start_time_nanos);
+        let window_start = time_nanos.div_euclid(self.window_nanos) * self.window_nanos;
+
+        let class_loader = value.get_field("definingClassLoader");
+        let key = (
+            class_loader
+                .and_then(|l| l.get_field("type"))
+                .and_then(|c| class_name(&c)),
+            class_loader
+                .and_then(|l| symbol(&l, "name"))
+                .map(String::from),
+        );
+        self.class_loaders
+            .entry(key)
+            .or_default()
+            .add(window_start, count);
+
+        if let Some(class) = value.get_field(class_field) {
+            let package = class
+                .get_field("package")
+                .and_then(|p| symbol(&p, "name"))
+                .map(|p| p.replace('/', "."))
+                .or_else(|| {
+                    class_name(&class).map(|name| match name.rfind('.') {
+                        Some(i) => name[..i].to_string(),
+                        None => String::new(),
+                    })
+                });
+            if let Some(package) = package {
+                self.packages
+                    .entry(package)
+                    .or_default()
+                    .add(window_start, count);
+            }
+        }
+        true
+    }
+
+    fn add_statistics(&mut self, event: &Event) {
+        let value = event.value();
+        let count = |name| value.get_field(name).and_then(|v| v.as_i64()).unwrap_or(0);
+        self.statistics.push(ClassLoadingSample {
+            time_nanos: start_time_nanos(event).unwrap_or(event.chunk().header.start_time_nanos),
+            loaded_class_count: count("loadedClassCount"),
+            unloaded_class_count: count("unloadedClassCount"),
+        });
+    }
+
+    /// Adds the class loading events in all chunks
+    pub fn add_all<T: Read + Seek>(&mut self, reader: &mut JfrReader<T>) -> Result<()> {
+        for chunk in reader.chunks() {
+            let (chunk_reader, chunk) = chunk?;
+            for event in chunk_reader.events(&chunk) {
+                self.add(&event?);
+            }
+        }
+        Ok(())
+    }
+
+    pub fn finish(self) -> ClassLoadingReport {
+        let mut class_loaders = self
+            .class_loaders
+            .into_iter()
+            .map(|((type_name, name), counter)| ClassLoaderSummary {
+                type_name,
+                name,
+                counts: counter.counts,
+                windows: counter.windows(),
+            })
+            .collect::<Vec<_>>();
+        class_loaders.sort_by(|a, b| {
+            b.counts
+                .total()
+                .cmp(&a.counts.total())
+                .then_with(|| (&a.type_name, &a.name).cmp(&(&b.type_name, &b.name)))
+        });
+
+        let mut packages = self
+            .packages
+            .into_iter()
+            .map(|(name, counter)| PackageSummary {
+                name,
+                counts: counter.counts,
+                windows: counter.windows(),
+            })
+            .collect::<Vec<_>>();
+        packages.sort_by(|a, b| {
+            b.counts
+                .total()
+                .cmp(&a.counts.total())
+                .then_with(|| a.name.cmp(&b.name))
+        });
+
+        let mut statistics = self.statistics;
+        statistics.sort_by_key(|s| s.time_nanos);
+
+        ClassLoadingReport {
+            class_loaders,
+            packages,
+            statistics,
+        }
+    }
+}
+
+fn start_time_nanos(event: &Event) -> Option<i64> {
+    let ticks = event.value().get_field("startTime")?.as_i64()?;
+    Some(event.chunk().header.ticks_to_epoch_nanos(ticks))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{constant, long, open_test_data, string, TestRecording};
+    use crate::writer::recording::TypeDeclaration;
+    use serde::Serialize;
+    use std::io::Cursor;
+
+    const SECOND: i64 = 1_000_000_000;
+
+    #[derive(Serialize)]
+    struct Symbol<'a> {
+        string: &'a str,
+    }
+
+    #[derive(Serialize)]
+    struct Package {
+        name: i64,
+    }
+
+    #[derive(Serialize)]
+    struct Class {
+        name: i64,
+        package: i64,
+    }
+
+    #[derive(Serialize)]
+    struct ClassLoader {
+        r#type: i64,
+        name: i64,
+    }
+
+    #[derive(Serialize)]
+    #[serde(rename_all = "camelCase")]
+    struct ClassLoad {
+        start_time: i64,
+        loaded_class: i64,
+        defining_class_loader: Option<i64>,
+    }
+
+    #[derive(Serialize)]
+    #[serde(rename_all = "camelCase")]
+    struct ClassDefine {
+        start_time: i64,
+        defined_class: i64,
+        defining_class_loader: Option<i64>,
+    }
+
+    #[derive(Serialize)]
+    #[serde(rename_all = "camelCase")]
+    struct ClassUnload {
+        start_time: i64,
+        unloaded_class: i64,
+        defining_class_loader: Option<i64>,
+    }
+
+    #[derive(Serialize)]
+    #[serde(rename_all = "camelCase")]
+    struct ClassLoadingStatistics {
+        start_time: i64,
+        loaded_class_count: i64,
+        unloaded_class_count: i64,
+    }
+
+    fn recording() -> JfrReader<Cursor<Vec<u8>>> {
+        let mut recording = TestRecording::new([
+            TypeDeclaration::new("jdk.types.Symbol").field(string("string")),
+            TypeDeclaration::new("jdk.types.Package").field(constant("name", "jdk.types.Symbol")),
+            TypeDeclaration::new("java.lang.Class")
+                .field(constant("name", "jdk.types.Symbol"))
+                .field(constant("package", "jdk.types.Package")),
+            TypeDeclaration::new("jdk.types.ClassLoader")
+                .field(constant("type", "java.lang.Class"))
+                .field(constant("name", "jdk.types.Symbol")),
+            TypeDeclaration::event("jdk.ClassLoad")
+                .field(constant("loadedClass", "java.lang.Class"))
+                .field(constant("definingClassLoader", "jdk.types.ClassLoader")),
+            TypeDeclaration::event("jdk.ClassDefine")
+                .field(constant("definedClass", "java.lang.Class"))
+                .field(constant("definingClassLoader", "jdk.types.ClassLoader")),
+            TypeDeclaration::event("jdk.ClassUnload")
+                .field(constant("unloadedClass", "java.lang.Class"))
+                .field(constant("definingClassLoader", "jdk.types.ClassLoader")),
+            TypeDeclaration::event("jdk.ClassLoadingStatistics")
+                .field(long("loadedClassCount"))
+                .field(long("unloadedClassCount")),
+        ]);
+
+        for (index, string) in [
+            "com/example/Plugin$1",
+            "com/example",
+            "java/lang/Object",
+            "java/lang",
+            "com/example/PluginClassLoader",
+            "plugin",
+        ]
+        .iter()
+        .enumerate()
+        {
+            recording.constant("jdk.types.Symbol", index as i64 + 1, &Symbol { string });
+        }
+        recording
+            .constant("jdk.types.Package", 1, &Package { name: 2 })
+            .constant("jdk.types.Package", 2, &Package { name: 4 });
+        for (index, name, package) in [(1, 1, 1), (2, 3, 2), (3, 5, 1)] {
+            recording.constant("java.lang.Class", index, &Class { name, package });
+        }
+        recording.constant(
+            "jdk.types.ClassLoader",
+            1,
+            &ClassLoader { r#type: 3, name: 6 },
+        );
+
+        let plugin_loader = Some(1);
+        recording
+            .event(
+                "jdk.ClassDefine",
+                &ClassDefine {
+                    start_time: 0,
+                    defined_class: 1,
+                    defining_class_loader: plugin_loader,
+                },
+            )
+            .event(
+                "jdk.ClassLoad",
+                &ClassLoad {
+                    start_time: SECOND,
+                    loaded_class: 1,
+                    defining_class_loader: plugin_loader,
+                },
+            )
+            .event(
+                "jdk.ClassLoad",
+                &ClassLoad {
+                    start_time: 2 * SECOND,
+                    loaded_class: 2,
+                    defining_class_loader: None,
+                },
+            )
+            // defined again by another instance of the class loader
+            .event(
+                "jdk.ClassDefine",
+                &ClassDefine {
+                    start_time: 61 * SECOND,
+                    defined_class: 1,
+                    defining_class_loader: plugin_loader,
+                },
+            )
+            .event(
+                "jdk.ClassUnload",
+                &ClassUnload {
+                    start_time: 120 * SECOND,
+                    unloaded_class: 1,
+                    defining_class_loader: plugin_loader,
+                },
+            );
+        for (start_time, loaded_class_count) in [(90 * SECOND, 10), (30 * SECOND, 5)] {
+            recording.event(
+                "jdk.ClassLoadingStatistics",
+                &ClassLoadingStatistics {
+                    start_time,
+                    loaded_class_count,
+                    unloaded_class_count: 0,
+                },
+            );
+        }
+        recording.reader()
+    }
+
+    #[test]
+    fn test_aggregate() {
+        let mut reader = recording();
+        let mut aggregator = ClassLoadingAggregator::new();
+        aggregator.add_all(&mut reader).unwrap();
+        let report = aggregator.finish();
+
+        assert_eq!(report.class_loaders.len(), 2);
+        let plugin = &report.class_loaders[0];
+        assert_eq!(
+            plugin.type_name.as_deref(),
+            Some("com.example.PluginClassLoader")
+        );
+        assert_eq!(plugin.name.as_deref(), Some("plugin"));
+        assert_eq!(
+            plugin.counts,
+            ClassCounts {
+                loaded: 1,
+                defined: 2,
+                unloaded: 1
+            }
+        );
+        assert_eq!(plugin.counts.retained(), 1);
+        assert_eq!(
+            plugin
+                .windows
+                .iter()
+                .map(|w| (w.start_nanos / SECOND, w.counts.retained()))
+                .collect::<Vec<_>>(),
+            vec![(0, 1), (60, 1), (120, -1)]
+        );
+        let bootstrap = &report.class_loaders[1];
+        assert_eq!((&bootstrap.type_name, &bootstrap.name), (&None, &None));
+        assert_eq!(bootstrap.counts.loaded, 1);
+
+        assert_eq!(
+            report
+                .packages
+                .iter()
+                .map(|p| (p.name.as_str(), p.counts.loaded + p.counts.defined))
+                .collect::<Vec<_>>(),
+            vec![("com.example", 3), ("java.lang", 1)]
+        );
+
+        assert_eq!(
+            report
+                .statistics
+                .iter()
+                .map(|s| (s.time_nanos / SECOND, s.loaded_class_count))
+                .collect::<Vec<_>>(),
+            vec![(30, 5), (90, 10)]
+        );
+    }
+
+    #[test]
+    fn test_window() {
+        let mut reader = recording();
+        let mut aggregator = ClassLoadingAggregator::new().window(Duration::from_secs(3600));
+        aggregator.add_all(&mut reader).unwrap();
+        let report = aggregator.finish();
+        assert_eq!(report.class_loaders[0].windows.len(), 1);
+        assert_eq!(
+            report.class_loaders[0].windows[0].counts,
+            report.class_loaders[0].counts
+        );
+    }
+
+    #[test]
+    fn test_statistics_only() {
+        let mut reader = open_test_data("recording.jfr");
+        let mut aggregator = ClassLoadingAggregator::new();
+        aggregator.add_all(&mut reader).unwrap();
+        let report = aggregator.finish();
+
+        assert!(report.class_loaders.is_empty());
+        assert!(report.packages.is_empty());
+        assert_eq!(report.statistics.len(), 14);
+        assert!(report
+            .statistics
+            .windows(2)
+            .all(|w| w[0].time_nanos <= w[1].time_nanos));
+        assert_eq!(report.statistics[0].loaded_class_count, 1468);
+    }
+}
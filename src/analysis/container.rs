@@ -0,0 +1,366 @@
+//! Limits and resource usage of the container (cgroup) running the recorded JVM,
+//! to compare the JVM's behavior with the limits (e.g. the resources of a Kubernetes pod).
+//!
+//! The container events are recorded since JDK 17 only if the JVM runs in a container.
+
+use crate::reader::de::from_event;
+use crate::reader::event::Event;
+use crate::reader::known_types::KnownType;
+use crate::reader::types::jdk;
+use crate::reader::{JfrReader, Result};
+use std::io::{Read, Seek};
+use std::time::Duration;
+
+/// Limits of the container from `jdk.ContainerConfiguration`. None means unlimited or unsupported
+#[derive(Debug, Clone, PartialEq)]
+pub struct ContainerConfiguration {
+    /// e.g. `cgroupv1`, `cgroupv2`
+    pub container_type: Option<String>,
+    pub cpu_slice_period: Option<Duration>,
+    /// CPU time available in each [`ContainerConfiguration::cpu_slice_period`]
+    pub cpu_quota: Option<Duration>,
+    pub cpu_shares: Option<u64>,
+    /// Number of CPUs the JVM uses, which is derived from the limits
+    pub effective_cpu_count: u64,
+    /// In bytes
+    pub memory_soft_limit: Option<u64>,
+    /// In bytes
+    pub memory_limit: Option<u64>,
+    /// In bytes
+    pub swap_memory_limit: Option<u64>,
+}
+
+impl ContainerConfiguration {
+    /// The CPU limit in the number of CPUs (e.g. 1.5 for the quota of 150ms per 100ms),
+    /// or [`ContainerConfiguration::effective_cpu_count`] if the quota is not set
+    pub fn cpu_limit(&self) -> Option<f64> {
+        match (self.cpu_quota, self.cpu_slice_period) {
+            (Some(quota), Some(period)) if !period.is_zero() => {
+                Some(quota.as_nanos() as f64 / period.as_nanos() as f64)
+            }
+            _ if self.effective_cpu_count > 0 => Some(self.effective_cpu_count as f64),
+            _ => None,
+        }
+    }
+}
+
+/// Sample of `jdk.ContainerCPUUsage`, accumulated since the container started
+#[derive(Debug, Clone, PartialEq)]
+pub struct CpuUsageSample {
+    /// Nanoseconds since UNIX epoch
+    pub time_nanos: i64,
+    pub cpu_time: Duration,
+    pub cpu_user_time: Duration,
+    pub cpu_system_time: Duration,
+}
+
+/// Sample of `jdk.ContainerMemoryUsage`
+#[derive(Debug, Clone, PartialEq)]
+pub struct MemoryUsageSample {
+    /// Nanoseconds since UNIX epoch
+    pub time_nanos: i64,
+    /// Number of times the memory usage hit the limit
+    pub memory_fail_count: u64,
+    /// In bytes
+    pub memory_usage: u64,
+    /// In bytes
+    pub swap_memory_usage: u64,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ContainerMetrics {
+    /// The configuration in the last event, since it may change while recording
+    pub configuration: Option<ContainerConfiguration>,
+    /// Sorted by the time
+    pub cpu_usage: Vec<CpuUsageSample>,
+    /// Sorted by the time
+    pub memory_usage: Vec<MemoryUsageSample>,
+}
+
+impl ContainerMetrics {
+    /// Reads all chunks of the recording to gather the metrics.
+    /// Only the events of the container types are decoded.
+    pub fn from<T: Read + Seek>(reader: &mut JfrReader<T>) -> Result<Self> {
+        let mut metrics = Self::default();
+        for chunk in reader.chunks() {
+            let (chunk_reader, chunk) = chunk?;
+            let events = chunk_reader.events(&chunk).select(|t| {
+                matches!(
+                    t.known_type(),
+                    Some(
+                        KnownType::ContainerConfiguration
+                            | KnownType::ContainerCPUUsage
+                            | KnownType::ContainerMemoryUsage
+                    )
+                )
+            });
+            for event in events {
+                metrics.update(&event?)?;
+            }
+        }
+        metrics.cpu_usage.sort_by_key(|s| s.time_nanos);
+        metrics.memory_usage.sort_by_key(|s| s.time_nanos);
+        Ok(metrics)
+    }
+
+    /// Updates the metrics by the event. Events of other types are ignored.
+    ///
+    /// Samples are appended in the order of the events, which are not necessarily ordered by the time.
+    pub fn update(&mut self, event: &Event) -> Result<()> {
+        let time_nanos = || {
+            let ticks = event
+                .value()
+                .get_field("startTime")
+                .and_then(|t| t.as_i64())
+                .unwrap_or(0);
+            event.chunk.header.ticks_to_epoch_nanos(ticks)
+        };
+        match event.class.known_type() {
+            Some(KnownType::ContainerConfiguration) => {
+                let e: jdk::ContainerConfiguration = from_event(event)?;
+                self.configuration = Some(ContainerConfiguration {
+                    container_type: e.container_type.map(String::from),
+                    cpu_slice_period: limit(e.cpu_slice_period).map(Duration::from_micros),
+                    cpu_quota: limit(e.cpu_quota).map(Duration::from_micros),
+                    cpu_shares: limit(e.cpu_shares),
+                    effective_cpu_count: limit(e.effective_cpu_count).unwrap_or(0),
+                    memory_soft_limit: limit(e.memory_soft_limit),
+                    memory_limit: limit(e.memory_limit),
+                    swap_memory_limit: limit(e.swap_memory_limit),
+                });
+            }
+            Some(KnownType::ContainerCPUUsage) => {
+                let e: jdk::ContainerCPUUsage = from_event(event)?;
+                let nanos = |v: i64| Duration::from_nanos(v.max(0) as u64);
+                self.cpu_usage.push(CpuUsageSample {
+                    time_nanos: time_nanos(),
+                    cpu_time: nanos(e.cpu_time),
+                    cpu_user_time: nanos(e.cpu_user_time),
+                    cpu_system_time: nanos(e.cpu_system_time),
+                });
+            }
+            Some(KnownType::ContainerMemoryUsage) => {
+                let e: jdk::ContainerMemoryUsage = from_event(event)?;
+                self.memory_usage.push(MemoryUsageSample {
+                    time_nanos: time_nanos(),
+                    memory_fail_count: limit(e.memory_fail_count).unwrap_or(0),
+                    memory_usage: limit(e.memory_usage).unwrap_or(0),
+                    swap_memory_usage: limit(e.swap_memory_usage).unwrap_or(0),
+                });
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// Returns the CPU usage between the adjacent samples relative to [`ContainerConfiguration::cpu_limit`]
+    /// as (timestamp of the later sample, ratio). 1.0 means the container used up the limit, i.e. throttled.
+    ///
+    /// Empty if the configuration is not recorded.
+    pub fn cpu_utilization(&self) -> Vec<(i64, f64)> {
+        let cpu_limit = match self.configuration.as_ref().and_then(|c| c.cpu_limit()) {
+            Some(limit) => limit,
+            None => return vec![],
+        };
+        self.cpu_usage
+            .windows(2)
+            .filter_map(|w| {
+                let elapsed_nanos = w[1].time_nanos - w[0].time_nanos;
+                let cpu_nanos = w[1].cpu_time.checked_sub(w[0].cpu_time)?.as_nanos();
+                (elapsed_nanos > 0).then(|| {
+                    (
+                        w[1].time_nanos,
+                        cpu_nanos as f64 / elapsed_nanos as f64 / cpu_limit,
+                    )
+                })
+            })
+            .collect()
+    }
+
+    /// Returns the memory usage relative to [`ContainerConfiguration::memory_limit`] as (timestamp, ratio).
+    ///
+    /// Empty if the configuration is not recorded or the memory is unlimited.
+    pub fn memory_utilization(&self) -> Vec<(i64, f64)> {
+        let memory_limit = match self.configuration.as_ref().and_then(|c| c.memory_limit) {
+            Some(limit) if limit > 0 => limit,
+            _ => return vec![],
+        };
+        self.memory_usage
+            .iter()
+            .map(|s| (s.time_nanos, s.memory_usage as f64 / memory_limit as f64))
+            .collect()
+    }
+}
+
+/// JVM reports negative values for unlimited or unsupported metrics
+fn limit(value: i64) -> Option<u64> {
+    u64::try_from(value).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{long, open_test_data, string, TestRecording};
+    use crate::writer::recording::TypeDeclaration;
+    use serde::Serialize;
+    use std::io::Cursor;
+
+    const SECOND: i64 = 1_000_000_000;
+    const MIB: i64 = 1024 * 1024;
+
+    #[derive(Serialize)]
+    #[serde(rename_all = "camelCase")]
+    struct ContainerConfigurationEvent<'a> {
+        start_time: i64,
+        container_type: &'a str,
+        cpu_slice_period: i64,
+        cpu_quota: i64,
+        cpu_shares: i64,
+        effective_cpu_count: i64,
+        memory_soft_limit: i64,
+        memory_limit: i64,
+        swap_memory_limit: i64,
+    }
+
+    #[derive(Serialize)]
+    #[serde(rename_all = "camelCase")]
+    struct ContainerCpuUsageEvent {
+        start_time: i64,
+        cpu_time: i64,
+        cpu_user_time: i64,
+        cpu_system_time: i64,
+    }
+
+    #[derive(Serialize)]
+    #[serde(rename_all = "camelCase")]
+    struct ContainerMemoryUsageEvent {
+        start_time: i64,
+        memory_fail_count: i64,
+        memory_usage: i64,
+        swap_memory_usage: i64,
+    }
+
+    fn recording() -> JfrReader<Cursor<Vec<u8>>> {
+        let mut recording = TestRecording::new([
+            TypeDeclaration::event("jdk.ContainerConfiguration")
+                .field(string("containerType"))
+                .field(long("cpuSlicePeriod"))
+                .field(long("cpuQuota"))
+                .field(long("cpuShares"))
+                .field(long("effectiveCpuCount"))
+                .field(long("memorySoftLimit"))
+                .field(long("memoryLimit"))
+                .field(long("swapMemoryLimit")),
+            TypeDeclaration::event("jdk.ContainerCPUUsage")
+                .field(long("cpuTime"))
+                .field(long("cpuUserTime"))
+                .field(long("cpuSystemTime")),
+            TypeDeclaration::event("jdk.ContainerMemoryUsage")
+                .field(long("memoryFailCount"))
+                .field(long("memoryUsage"))
+                .field(long("swapMemoryUsage")),
+        ]);
+
+        recording.event(
+            "jdk.ContainerConfiguration",
+            &ContainerConfigurationEvent {
+                start_time: 0,
+                container_type: "cgroupv2",
+                cpu_slice_period: 100_000,
+                cpu_quota: 150_000,
+                cpu_shares: -1,
+                effective_cpu_count: 2,
+                memory_soft_limit: -1,
+                memory_limit: 512 * MIB,
+                swap_memory_limit: -1,
+            },
+        );
+        // written out of order
+        for (start_time, cpu_time) in [(2 * SECOND, 2_250_000_000), (0, 0), (SECOND, 750_000_000)] {
+            recording.event(
+                "jdk.ContainerCPUUsage",
+                &ContainerCpuUsageEvent {
+                    start_time,
+                    cpu_time,
+                    cpu_user_time: cpu_time / 3 * 2,
+                    cpu_system_time: cpu_time / 3,
+                },
+            );
+        }
+        for (start_time, memory_usage) in [(0, 128 * MIB), (SECOND, 256 * MIB)] {
+            recording.event(
+                "jdk.ContainerMemoryUsage",
+                &ContainerMemoryUsageEvent {
+                    start_time,
+                    memory_fail_count: 0,
+                    memory_usage,
+                    swap_memory_usage: 0,
+                },
+            );
+        }
+        recording.reader()
+    }
+
+    #[test]
+    fn test_container_metrics() {
+        let mut reader = recording();
+        let metrics = ContainerMetrics::from(&mut reader).unwrap();
+
+        let configuration = metrics.configuration.as_ref().unwrap();
+        assert_eq!(
+            configuration,
+            &ContainerConfiguration {
+                container_type: Some("cgroupv2".to_string()),
+                cpu_slice_period: Some(Duration::from_millis(100)),
+                cpu_quota: Some(Duration::from_millis(150)),
+                cpu_shares: None,
+                effective_cpu_count: 2,
+                memory_soft_limit: None,
+                memory_limit: Some(512 * MIB as u64),
+                swap_memory_limit: None,
+            }
+        );
+        assert_eq!(configuration.cpu_limit(), Some(1.5));
+
+        assert_eq!(
+            metrics
+                .cpu_usage
+                .iter()
+                .map(|s| (s.time_nanos, s.cpu_time.as_millis()))
+                .collect::<Vec<_>>(),
+            vec![(0, 0), (SECOND, 750), (2 * SECOND, 2250)]
+        );
+        assert_eq!(metrics.cpu_usage[2].cpu_user_time.as_millis(), 1500);
+        assert_eq!(
+            metrics.cpu_utilization(),
+            vec![(SECOND, 0.5), (2 * SECOND, 1.0)]
+        );
+
+        assert_eq!(metrics.memory_usage.len(), 2);
+        assert_eq!(metrics.memory_utilization(), vec![(0, 0.25), (SECOND, 0.5)]);
+    }
+
+    #[test]
+    fn test_cpu_limit_without_quota() {
+        let configuration = ContainerConfiguration {
+            container_type: None,
+            cpu_slice_period: Some(Duration::from_millis(100)),
+            cpu_quota: None,
+            cpu_shares: None,
+            effective_cpu_count: 4,
+            memory_soft_limit: None,
+            memory_limit: None,
+            swap_memory_limit: None,
+        };
+        assert_eq!(configuration.cpu_limit(), Some(4.0));
+    }
+
+    #[test]
+    fn test_no_container_events() {
+        let mut reader = open_test_data("recording-2_1.jfr");
+        let metrics = ContainerMetrics::from(&mut reader).unwrap();
+        assert_eq!(metrics, ContainerMetrics::default());
+        assert!(metrics.cpu_utilization().is_empty());
+        assert!(metrics.memory_utilization().is_empty());
+    }
+}
@@ -0,0 +1,92 @@
+//! CPU profile reports from `jdk.ExecutionSample`.
+
+use crate::analysis::frame::stack_frames;
+use crate::reader::known_types::KnownType;
+use crate::reader::{JfrReader, Result};
+use std::collections::{HashMap, HashSet};
+use std::io::{Read, Seek};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MethodStats {
+    /// e.g. `com.foo.Bar.baz(...)`
+    pub name: String,
+    /// The number of samples where the method is the top frame
+    pub self_samples: u64,
+    /// The number of samples where the method is on the stack.
+    /// Recursive calls are counted once per sample.
+    pub total_samples: u64,
+}
+
+/// Returns the top `n` methods ordered by the self samples in descending order
+pub fn top_methods<T: Read + Seek>(
+    reader: &mut JfrReader<T>,
+    n: usize,
+) -> Result<Vec<MethodStats>> {
+    let mut methods: HashMap<String, MethodStats> = HashMap::new();
+    let mut on_stack = HashSet::new();
+
+    for chunk in reader.chunks() {
+        let (chunk_reader, chunk) = chunk?;
+        for event in chunk_reader.events(&chunk) {
+            let event = event?;
+            if event.class.known_type() != Some(KnownType::ExecutionSample) {
+                continue;
+            }
+            let frames = match event.value().get_field("stackTrace") {
+                Some(stack_trace) => stack_frames(&stack_trace),
+                None => continue,
+            };
+
+            on_stack.clear();
+            for (i, frame) in frames.iter().enumerate() {
+                let name = format!("{}.{}(...)", frame.class_name, frame.method_name);
+                let first = on_stack.insert(name.clone());
+                let stats = methods.entry(name).or_insert_with_key(|name| MethodStats {
+                    name: name.clone(),
+                    self_samples: 0,
+                    total_samples: 0,
+                });
+                if i == 0 {
+                    stats.self_samples += 1;
+                }
+                if first {
+                    stats.total_samples += 1;
+                }
+            }
+        }
+    }
+
+    let mut methods = methods.into_values().collect::<Vec<_>>();
+    methods.sort_by(|a, b| {
+        b.self_samples
+            .cmp(&a.self_samples)
+            .then(b.total_samples.cmp(&a.total_samples))
+            .then_with(|| a.name.cmp(&b.name))
+    });
+    methods.truncate(n);
+    Ok(methods)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::test_data;
+    use std::fs::File;
+
+    #[test]
+    fn test_top_methods() {
+        let mut reader = JfrReader::new(File::open(test_data("profiler-wall.jfr")).unwrap());
+        let all = top_methods(&mut reader, usize::MAX).unwrap();
+        assert_eq!(all.iter().map(|m| m.self_samples).sum::<u64>(), 8836);
+        assert!(all.iter().all(|m| m.self_samples <= m.total_samples));
+        assert!(all.iter().all(|m| m.total_samples <= 8836));
+        assert!(all.iter().all(|m| m.name.ends_with("(...)")));
+        assert!(all
+            .windows(2)
+            .all(|w| w[0].self_samples >= w[1].self_samples));
+
+        let mut reader = JfrReader::new(File::open(test_data("profiler-wall.jfr")).unwrap());
+        let top = top_methods(&mut reader, 3).unwrap();
+        assert_eq!(top.as_slice(), &all[..3]);
+    }
+}
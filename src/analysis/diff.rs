@@ -0,0 +1,203 @@
+//! Differential profile of two recordings (e.g. baseline and regression).
+
+use crate::analysis::frame::stacktrace::StackTraceInterner;
+use crate::analysis::frame::Frame;
+use crate::reader::{JfrReader, Result};
+use std::collections::{BTreeMap, HashMap};
+use std::io::{Read, Seek, Write};
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RecordingDiff {
+    /// Event counts of all event types found in either recording, ordered by the name
+    pub event_types: Vec<CountDelta<String>>,
+    /// Sample counts for each stack, ordered by the absolute delta in descending order
+    pub stacks: Vec<CountDelta<Vec<Frame>>>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CountDelta<K> {
+    pub key: K,
+    pub baseline: u64,
+    pub target: u64,
+}
+
+impl<K> CountDelta<K> {
+    /// `target - baseline`
+    pub fn delta(&self) -> i64 {
+        self.target as i64 - self.baseline as i64
+    }
+}
+
+impl RecordingDiff {
+    /// Compares the event counts, and the stacks of `sample_event_type` (e.g. `jdk.ExecutionSample`)
+    pub fn compute<A, B>(
+        baseline: &mut JfrReader<A>,
+        target: &mut JfrReader<B>,
+        sample_event_type: &str,
+    ) -> Result<Self>
+    where
+        A: Read + Seek,
+        B: Read + Seek,
+    {
+        let baseline = Profile::read(baseline, sample_event_type)?;
+        let target = Profile::read(target, sample_event_type)?;
+
+        let event_types = merge(baseline.event_types, target.event_types)
+            .into_iter()
+            .collect::<BTreeMap<_, _>>()
+            .into_iter()
+            .map(|(key, (baseline, target))| CountDelta {
+                key,
+                baseline,
+                target,
+            })
+            .collect();
+        let mut stacks = merge(baseline.stacks, target.stacks)
+            .into_iter()
+            .map(|(key, (baseline, target))| CountDelta {
+                key,
+                baseline,
+                target,
+            })
+            .collect::<Vec<_>>();
+        stacks.sort_by(|a, b| {
+            b.delta()
+                .abs()
+                .cmp(&a.delta().abs())
+                .then_with(|| a.key.cmp(&b.key))
+        });
+
+        Ok(Self {
+            event_types,
+            stacks,
+        })
+    }
+
+    /// Writes the stacks in the folded format with two counts (`frame;frame;frame baseline target`),
+    /// which is the input of `difffolded.pl` style differential flame graphs.
+    /// Frames are ordered from the bottom (the outermost caller).
+    pub fn write_folded<W: Write>(&self, mut output: W) -> std::io::Result<()> {
+        for stack in self.stacks.iter() {
+            let frames = stack
+                .key
+                .iter()
+                .rev()
+                .map(|f| format!("{}.{}", f.class_name, f.method_name))
+                .collect::<Vec<_>>();
+            writeln!(
+                output,
+                "{} {} {}",
+                frames.join(";"),
+                stack.baseline,
+                stack.target
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Default)]
+struct Profile {
+    event_types: HashMap<String, u64>,
+    stacks: HashMap<Vec<Frame>, u64>,
+}
+
+impl Profile {
+    fn read<T: Read + Seek>(reader: &mut JfrReader<T>, sample_event_type: &str) -> Result<Self> {
+        let mut profile = Self::default();
+        let mut interner = StackTraceInterner::new();
+        let mut counts = HashMap::new();
+        for chunk in reader.chunks() {
+            let (chunk_reader, chunk) = chunk?;
+            for event in chunk_reader.events(&chunk) {
+                let event = event?;
+                *profile
+                    .event_types
+                    .entry(event.class.name().to_string())
+                    .or_default() += 1;
+                if event.class.name() != sample_event_type {
+                    continue;
+                }
+                if let Some(key) = interner.intern_event(&event) {
+                    *counts.entry(key).or_insert(0) += 1;
+                }
+            }
+        }
+        for (key, count) in counts {
+            // ignore frame types so that the recompilation doesn't make a difference
+            let mut frames = interner.get(key).unwrap_or_default().to_vec();
+            for frame in frames.iter_mut() {
+                frame.frame_type = None;
+            }
+            *profile.stacks.entry(frames).or_default() += count;
+        }
+        Ok(profile)
+    }
+}
+
+fn merge<K: Eq + std::hash::Hash>(
+    baseline: HashMap<K, u64>,
+    target: HashMap<K, u64>,
+) -> HashMap<K, (u64, u64)> {
+    let mut merged = baseline
+        .into_iter()
+        .map(|(k, v)| (k, (v, 0)))
+        .collect::<HashMap<_, _>>();
+    for (k, v) in target {
+        merged.entry(k).or_insert((0, 0)).1 = v;
+    }
+    merged
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::test_data;
+    use std::fs::File;
+
+    #[test]
+    fn test_diff() {
+        let mut baseline = JfrReader::new(File::open(test_data("profiler-wall.jfr")).unwrap());
+        let mut target = JfrReader::new(File::open(test_data("profiler-multichunk.jfr")).unwrap());
+        let diff =
+            RecordingDiff::compute(&mut baseline, &mut target, "jdk.ExecutionSample").unwrap();
+
+        let samples = diff
+            .event_types
+            .iter()
+            .find(|d| d.key == "jdk.ExecutionSample")
+            .unwrap();
+        assert_eq!(samples.baseline, 8836);
+        assert_eq!(samples.target, 8888);
+        assert_eq!(samples.delta(), 52);
+        assert!(diff.event_types.windows(2).all(|w| w[0].key < w[1].key));
+
+        assert_eq!(diff.stacks.iter().map(|s| s.baseline).sum::<u64>(), 8836);
+        assert_eq!(diff.stacks.iter().map(|s| s.target).sum::<u64>(), 8888);
+        assert!(diff
+            .stacks
+            .windows(2)
+            .all(|w| w[0].delta().abs() >= w[1].delta().abs()));
+
+        let mut folded = vec![];
+        diff.write_folded(&mut folded).unwrap();
+        let folded = String::from_utf8(folded).unwrap();
+        assert_eq!(folded.lines().count(), diff.stacks.len());
+        let first = folded.lines().next().unwrap();
+        assert!(first.ends_with(&format!(
+            " {} {}",
+            diff.stacks[0].baseline, diff.stacks[0].target
+        )));
+    }
+
+    #[test]
+    fn test_same_recording() {
+        let mut baseline = JfrReader::new(File::open(test_data("recording.jfr")).unwrap());
+        let mut target = JfrReader::new(File::open(test_data("recording.jfr")).unwrap());
+        let diff =
+            RecordingDiff::compute(&mut baseline, &mut target, "jdk.NativeMethodSample").unwrap();
+        assert!(diff.event_types.iter().all(|d| d.delta() == 0));
+        assert!(diff.stacks.iter().all(|d| d.delta() == 0));
+        assert!(!diff.stacks.is_empty());
+    }
+}
@@ -0,0 +1,249 @@
+//! Aggregation of `jdk.JavaExceptionThrow` and `jdk.JavaErrorThrow` events
+//! by the thrown class and the throwing stack, to spot exception storms.
+
+use crate::analysis::frame::{class_name, stack_frames, Frame};
+use crate::reader::event::Event;
+use crate::reader::known_types::KnownType;
+use crate::reader::{JfrReader, Result};
+use std::collections::HashMap;
+use std::io::{Read, Seek};
+
+const DEFAULT_MAX_MESSAGES: usize = 5;
+
+/// Thrown class and its throwing stacks, sorted by the count in descending order
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExceptionSummary {
+    /// e.g. `java.lang.IllegalStateException`
+    pub class_name: String,
+    pub count: u64,
+    pub stacks: Vec<ExceptionStack>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExceptionStack {
+    /// Frames from the top, which is usually the constructor of the throwable
+    pub frames: Vec<Frame>,
+    pub count: u64,
+    /// Distinct messages up to [`ExceptionAggregator::max_messages`] in the order of appearance
+    pub messages: Vec<String>,
+}
+
+/// Aggregates the exception events.
+///
+/// Note that `jdk.JavaExceptionThrow` is emitted for errors too,
+/// so errors are counted twice if both event types are enabled.
+/// Filter the events before adding them if that's the case.
+#[derive(Debug)]
+pub struct ExceptionAggregator {
+    max_messages: usize,
+    groups: HashMap<String, HashMap<Vec<Frame>, ExceptionStack>>,
+}
+
+impl Default for ExceptionAggregator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ExceptionAggregator {
+    pub fn new() -> Self {
+        Self {
+            max_messages: DEFAULT_MAX_MESSAGES,
+            groups: HashMap::new(),
+        }
+    }
+
+    /// Number of sample messages to keep for each stack. Default is 5
+    pub fn max_messages(mut self, max_messages: usize) -> Self {
+        self.max_messages = max_messages;
+        self
+    }
+
+    /// Adds the event. Returns false if the event is not an exception event
+    pub fn add(&mut self, event: &Event) -> bool {
+        if !matches!(
+            event.class.known_type(),
+            Some(KnownType::JavaExceptionThrow) | Some(KnownType::JavaErrorThrow)
+        ) {
+            return false;
+        }
+
+        let value = event.value();
+        let class_name = value
+            .get_field("thrownClass")
+            .and_then(|c| class_name(&c))
+            .unwrap_or_else(|| "<unknown>".to_string());
+        let frames = value
+            .get_field("stackTrace")
+            .map(|s| stack_frames(&s))
+            .unwrap_or_default();
+        let message = value.get_field("message").and_then(|m| m.as_str());
+
+        let stack = self
+            .groups
+            .entry(class_name)
+            .or_default()
+            .entry(frames)
+            .or_insert_with_key(|frames| ExceptionStack {
+                frames: frames.clone(),
+                count: 0,
+                messages: vec![],
+            });
+        stack.count += 1;
+        if let Some(message) = message {
+            if stack.messages.len() < self.max_messages
+                && !stack.messages.iter().any(|m| m == message)
+            {
+                stack.messages.push(message.to_string());
+            }
+        }
+        true
+    }
+
+    /// Adds the exception events in all chunks
+    pub fn add_all<T: Read + Seek>(&mut self, reader: &mut JfrReader<T>) -> Result<()> {
+        for chunk in reader.chunks() {
+            let (chunk_reader, chunk) = chunk?;
+            for event in chunk_reader.events(&chunk) {
+                self.add(&event?);
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the summaries sorted by the count in descending order
+    pub fn finish(self) -> Vec<ExceptionSummary> {
+        let mut result = self
+            .groups
+            .into_iter()
+            .map(|(class_name, stacks)| {
+                let mut stacks = stacks.into_values().collect::<Vec<_>>();
+                stacks.sort_by(|a, b| b.count.cmp(&a.count).then(a.frames.cmp(&b.frames)));
+                ExceptionSummary {
+                    class_name,
+                    count: stacks.iter().map(|s| s.count).sum(),
+                    stacks,
+                }
+            })
+            .collect::<Vec<_>>();
+        result.sort_by(|a, b| b.count.cmp(&a.count).then(a.class_name.cmp(&b.class_name)));
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::reader::byte_stream::ByteStream;
+    use crate::reader::value_descriptor::ValueDescriptor;
+    use crate::reader::Chunk;
+    use crate::test_util::test_data;
+    use crate::writer::to_event;
+    use serde::Serialize;
+    use std::fs::File;
+    use std::io::Cursor;
+
+    #[derive(Serialize)]
+    #[serde(rename_all = "camelCase")]
+    struct JavaErrorThrow<'a> {
+        start_time: i64,
+        stack_trace: Option<u64>,
+        message: Option<&'a str>,
+        thrown_class: Option<u64>,
+    }
+
+    #[test]
+    fn test_aggregate() {
+        let mut reader = JfrReader::new(File::open(test_data("recording.jfr")).unwrap());
+        let (chunk_reader, chunk) = reader.chunks().next().unwrap().unwrap();
+
+        // borrow the class and stack traces in the constant pool from jdk.JavaMonitorWait events
+        let mut stacks = vec![];
+        let mut class = None;
+        for event in chunk_reader.events(&chunk).flatten() {
+            if event.class.name() != "jdk.JavaMonitorWait" {
+                continue;
+            }
+            let index = |name: &str| match event.value().get_field_raw(name).map(|v| v.value) {
+                Some(ValueDescriptor::ConstantPool { constant_index, .. }) => {
+                    Some(*constant_index as u64)
+                }
+                _ => None,
+            };
+            if let Some(stack_trace) = index("stackTrace") {
+                if !stacks.contains(&stack_trace) {
+                    stacks.push(stack_trace);
+                }
+            }
+            class = class.or_else(|| index("monitorClass"));
+        }
+        let (stack_a, stack_b, class) = (stacks[0], stacks[1], class.unwrap());
+
+        let errors = [
+            (stack_a, Some("a")),
+            (stack_a, Some("a")),
+            (stack_a, Some("b")),
+            (stack_b, None),
+        ]
+        .iter()
+        .map(|(stack_trace, message)| {
+            error_event(
+                &chunk,
+                JavaErrorThrow {
+                    start_time: 0,
+                    stack_trace: Some(*stack_trace),
+                    message: *message,
+                    thrown_class: Some(class),
+                },
+            )
+        })
+        .collect::<Vec<_>>();
+
+        let class_desc = chunk
+            .metadata
+            .type_pool
+            .get_by_name("jdk.JavaErrorThrow")
+            .unwrap();
+        let mut aggregator = ExceptionAggregator::new().max_messages(1);
+        for value in errors {
+            let event = Event {
+                byte_offset: 0,
+                byte_size: 0,
+                class: class_desc,
+                chunk: &chunk,
+                value,
+            };
+            assert!(aggregator.add(&event));
+        }
+        let summaries = aggregator.finish();
+
+        assert_eq!(summaries.len(), 1);
+        let summary = &summaries[0];
+        assert!(!summary.class_name.is_empty());
+        assert!(!summary.class_name.contains('/'));
+        assert_eq!(summary.count, 4);
+        assert_eq!(summary.stacks.len(), 2);
+        assert_eq!(summary.stacks[0].count, 3);
+        assert_eq!(summary.stacks[0].messages, vec!["a".to_string()]);
+        assert!(!summary.stacks[0].frames.is_empty());
+        assert_eq!(summary.stacks[1].count, 1);
+        assert!(summary.stacks[1].messages.is_empty());
+    }
+
+    #[test]
+    fn test_no_exceptions() {
+        let mut reader = JfrReader::new(File::open(test_data("recording.jfr")).unwrap());
+        let mut aggregator = ExceptionAggregator::new();
+        aggregator.add_all(&mut reader).unwrap();
+        assert!(aggregator.finish().is_empty());
+    }
+
+    fn error_event(chunk: &Chunk, error: JavaErrorThrow) -> ValueDescriptor {
+        let bytes = to_event(&error, "jdk.JavaErrorThrow", chunk).unwrap();
+        let mut stream = ByteStream::new(Cursor::new(bytes));
+        stream.set_int_encoding(chunk.header.int_encoding());
+        stream.read_i32().unwrap();
+        let class_id = stream.read_i64().unwrap();
+        ValueDescriptor::try_new(&mut stream, class_id, &chunk.metadata).unwrap()
+    }
+}
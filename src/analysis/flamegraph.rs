@@ -0,0 +1,184 @@
+//! Flame graph SVG generation by [inferno](https://github.com/jonhoo/inferno).
+//!
+//! The stacks are aggregated by [`CallTree`] and passed to inferno in the folded format
+//! (`frame;frame;frame weight`), so no external `stackcollapse` step is needed.
+//! Differential flame graphs are generated from [`RecordingDiff`].
+
+use crate::analysis::calltree::CallTree;
+use crate::analysis::diff::RecordingDiff;
+use crate::analysis::frame::format::{format_frame, FormatOptions};
+use crate::analysis::weight::{SampleCount, Weight};
+use crate::reader::{Error, JfrReader, Result};
+use std::io::{Read, Seek, Write};
+
+/// Options of the flame graph
+pub struct FlameGraphOptions<'a> {
+    /// The event type of the samples (e.g. `jdk.ExecutionSample`)
+    pub event_type: String,
+    /// Each sample is weighted as 1 by default. Not used by differential flame graphs
+    pub weight: Box<dyn Weight + 'a>,
+    pub frame_format: FormatOptions,
+    /// Options passed to inferno, e.g. the title and the color palette
+    pub inferno: inferno::flamegraph::Options<'a>,
+}
+
+impl<'a> FlameGraphOptions<'a> {
+    pub fn new(event_type: &str) -> Self {
+        Self {
+            event_type: event_type.to_string(),
+            weight: Box::new(SampleCount),
+            frame_format: FormatOptions::default(),
+            inferno: inferno::flamegraph::Options::default(),
+        }
+    }
+
+    pub fn weight<W: Weight + 'a>(mut self, weight: W) -> Self {
+        self.weight = Box::new(weight);
+        self
+    }
+
+    pub fn frame_format(mut self, format: FormatOptions) -> Self {
+        self.frame_format = format;
+        self
+    }
+
+    pub fn inferno(mut self, options: inferno::flamegraph::Options<'a>) -> Self {
+        self.inferno = options;
+        self
+    }
+}
+
+/// Reads all chunks of the recording and writes the flame graph of the samples as SVG
+pub fn write_svg<T, W>(
+    reader: &mut JfrReader<T>,
+    options: &mut FlameGraphOptions,
+    out: W,
+) -> Result<()>
+where
+    T: Read + Seek,
+    W: Write,
+{
+    let tree = CallTree::build_weighted(reader, &options.event_type, options.weight.as_ref())?;
+    write_tree_svg(&tree, options, out)
+}
+
+/// Writes the flame graph of the top-down call tree as SVG
+pub fn write_tree_svg<W: Write>(
+    tree: &CallTree,
+    options: &mut FlameGraphOptions,
+    out: W,
+) -> Result<()> {
+    let lines = folded_lines(tree, &options.frame_format);
+    render(&mut options.inferno, &lines, out)
+}
+
+/// Writes the differential flame graph of the two recordings as SVG.
+/// Frames are colored by the change of the sample counts from `baseline` to `target`
+pub fn write_diff_svg<A, B, W>(
+    baseline: &mut JfrReader<A>,
+    target: &mut JfrReader<B>,
+    options: &mut FlameGraphOptions,
+    out: W,
+) -> Result<()>
+where
+    A: Read + Seek,
+    B: Read + Seek,
+    W: Write,
+{
+    let diff = RecordingDiff::compute(baseline, target, &options.event_type)?;
+    let mut folded = vec![];
+    diff.write_folded(&mut folded).map_err(Error::IoError)?;
+    let folded = String::from_utf8(folded).map_err(|_| Error::InvalidString)?;
+    let lines = folded.lines().map(String::from).collect::<Vec<_>>();
+    render(&mut options.inferno, &lines, out)
+}
+
+/// Returns the stacks of the top-down call tree in the folded format, one line for each node with the self weight
+pub fn folded_lines(tree: &CallTree, format: &FormatOptions) -> Vec<String> {
+    let mut lines = vec![];
+    let mut stack = vec![(tree.root(), String::new())];
+    while let Some((id, path)) = stack.pop() {
+        let node = tree.node(id);
+        if node.self_weight > 0 && !path.is_empty() {
+            lines.push(format!("{} {}", path, node.self_weight));
+        }
+        for child in tree.children(id) {
+            let frame = match &tree.node(child).frame {
+                Some(frame) => format_frame(frame, format),
+                None => continue,
+            };
+            let child_path = if path.is_empty() {
+                frame
+            } else {
+                format!("{};{}", path, frame)
+            };
+            stack.push((child, child_path));
+        }
+    }
+    lines
+}
+
+fn render<W: Write>(
+    options: &mut inferno::flamegraph::Options,
+    lines: &[String],
+    out: W,
+) -> Result<()> {
+    inferno::flamegraph::from_lines(options, lines.iter().map(|l| l.as_str()), out)
+        .map_err(|e| Error::SerializeError(e.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analysis::frame::format::SignatureStyle;
+    use crate::analysis::frame::Frame;
+    use crate::test_util::test_data;
+    use std::fs::File;
+
+    #[test]
+    fn test_write_svg() {
+        let mut reader = JfrReader::new(File::open(test_data("profiler-wall.jfr")).unwrap());
+        let mut options = FlameGraphOptions::new("jdk.ExecutionSample");
+        options.inferno.title = "wall".to_string();
+        let mut svg = vec![];
+        write_svg(&mut reader, &mut options, &mut svg).unwrap();
+
+        let svg = String::from_utf8(svg).unwrap();
+        assert!(svg.starts_with("<?xml"));
+        assert!(svg.contains(">wall<"));
+        assert!(svg.contains("8,836 samples"));
+    }
+
+    #[test]
+    fn test_write_diff_svg() {
+        let mut baseline = JfrReader::new(File::open(test_data("profiler-wall.jfr")).unwrap());
+        let mut target = JfrReader::new(File::open(test_data("profiler-wall.jfr")).unwrap());
+        let mut svg = vec![];
+        write_diff_svg(
+            &mut baseline,
+            &mut target,
+            &mut FlameGraphOptions::new("jdk.ExecutionSample"),
+            &mut svg,
+        )
+        .unwrap();
+        assert!(String::from_utf8(svg).unwrap().contains("8,836 samples"));
+    }
+
+    #[test]
+    fn test_folded_lines() {
+        let frame = |method: &str| Frame {
+            class_name: "Foo".to_string(),
+            method_name: method.to_string(),
+            descriptor: None,
+            line_number: -1,
+            frame_type: None,
+        };
+        let mut tree = CallTree::new();
+        tree.add_stack(&[frame("a"), frame("main")], 2);
+        tree.add_stack(&[frame("main")], 1);
+        let format = FormatOptions::default().signature_style(SignatureStyle::Omitted);
+        let mut lines = folded_lines(&tree, &format);
+        lines.sort();
+        assert_eq!(lines, vec!["Foo.main 1", "Foo.main;Foo.a 2"]);
+    }
+}
@@ -0,0 +1,225 @@
+//! Parser of JVM field and method descriptors (JVMS 4.3),
+//! which are recorded in `jdk.types.Method.descriptor` (e.g. `(Ljava/lang/String;I)V`).
+
+use crate::analysis::frame::format::{format_class_name, ClassStyle};
+use std::fmt;
+use std::fmt::Formatter;
+
+/// Maximum number of array dimensions (JVMS 4.3.2)
+const MAX_ARRAY_DIMENSIONS: usize = 255;
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum FieldType {
+    Byte,
+    Char,
+    Double,
+    Float,
+    Int,
+    Long,
+    Short,
+    Boolean,
+    /// The class name in internal form (e.g. `java/lang/String`)
+    Object(String),
+    Array(Box<FieldType>),
+}
+
+impl FieldType {
+    /// Parses the field descriptor (e.g. `[Ljava/lang/String;`).
+    /// Returns None if the descriptor is malformed.
+    pub fn parse(descriptor: &str) -> Option<Self> {
+        match Self::parse_prefix(descriptor)? {
+            (ty, "") => Some(ty),
+            _ => None,
+        }
+    }
+
+    /// Parses the first field type in the descriptor, and returns it with the remaining
+    fn parse_prefix(descriptor: &str) -> Option<(Self, &str)> {
+        let component = descriptor.trim_start_matches('[');
+        let dimensions = descriptor.len() - component.len();
+        if dimensions > MAX_ARRAY_DIMENSIONS {
+            return None;
+        }
+        let (mut ty, rest) = Self::parse_component(component)?;
+        for _ in 0..dimensions {
+            ty = FieldType::Array(Box::new(ty));
+        }
+        Some((ty, rest))
+    }
+
+    /// Parses the non-array field type at the head of the descriptor
+    fn parse_component(descriptor: &str) -> Option<(Self, &str)> {
+        let rest = &descriptor[descriptor.chars().next()?.len_utf8()..];
+        let ty = match descriptor.as_bytes()[0] {
+            b'B' => FieldType::Byte,
+            b'C' => FieldType::Char,
+            b'D' => FieldType::Double,
+            b'F' => FieldType::Float,
+            b'I' => FieldType::Int,
+            b'J' => FieldType::Long,
+            b'S' => FieldType::Short,
+            b'Z' => FieldType::Boolean,
+            b'L' => {
+                let end = rest.find(';')?;
+                if end == 0 {
+                    return None;
+                }
+                return Some((FieldType::Object(rest[..end].to_string()), &rest[end + 1..]));
+            }
+            _ => return None,
+        };
+        Some((ty, rest))
+    }
+
+    /// The type in Java source form (e.g. `java.lang.String[]`)
+    pub fn java_name(&self, style: ClassStyle) -> String {
+        match self {
+            FieldType::Byte => "byte".to_string(),
+            FieldType::Char => "char".to_string(),
+            FieldType::Double => "double".to_string(),
+            FieldType::Float => "float".to_string(),
+            FieldType::Int => "int".to_string(),
+            FieldType::Long => "long".to_string(),
+            FieldType::Short => "short".to_string(),
+            FieldType::Boolean => "boolean".to_string(),
+            FieldType::Object(name) => format_class_name(name, style),
+            FieldType::Array(component) => format!("{}[]", component.java_name(style)),
+        }
+    }
+}
+
+impl fmt::Display for FieldType {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.java_name(ClassStyle::FullyQualified))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct MethodDescriptor {
+    pub parameters: Vec<FieldType>,
+    /// None if the method returns void
+    pub return_type: Option<FieldType>,
+}
+
+impl MethodDescriptor {
+    /// Parses the method descriptor (e.g. `(Ljava/lang/String;I)V`).
+    /// Returns None if the descriptor is malformed.
+    pub fn parse(descriptor: &str) -> Option<Self> {
+        let mut remaining = descriptor.strip_prefix('(')?;
+        let mut parameters = vec![];
+        let ret = loop {
+            if let Some(ret) = remaining.strip_prefix(')') {
+                break ret;
+            }
+            let (ty, next) = FieldType::parse_prefix(remaining)?;
+            parameters.push(ty);
+            remaining = next;
+        };
+        let return_type = match ret {
+            "V" => None,
+            ret => Some(FieldType::parse(ret)?),
+        };
+        Some(Self {
+            parameters,
+            return_type,
+        })
+    }
+
+    /// The parameter types in Java source form (e.g. `(java.lang.String, int)`)
+    pub fn java_parameters(&self, style: ClassStyle) -> String {
+        let params = self
+            .parameters
+            .iter()
+            .map(|p| p.java_name(style))
+            .collect::<Vec<_>>();
+        format!("({})", params.join(", "))
+    }
+
+    /// The return type in Java source form (e.g. `void`)
+    pub fn java_return_type(&self, style: ClassStyle) -> String {
+        match &self.return_type {
+            Some(ty) => ty.java_name(style),
+            None => "void".to_string(),
+        }
+    }
+}
+
+impl fmt::Display for MethodDescriptor {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}: {}",
+            self.java_parameters(ClassStyle::FullyQualified),
+            self.java_return_type(ClassStyle::FullyQualified)
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_field_type() {
+        assert_eq!(FieldType::parse("I"), Some(FieldType::Int));
+        assert_eq!(
+            FieldType::parse("Ljava/lang/String;"),
+            Some(FieldType::Object("java/lang/String".to_string()))
+        );
+        assert_eq!(
+            FieldType::parse("[[J"),
+            Some(FieldType::Array(Box::new(FieldType::Array(Box::new(
+                FieldType::Long
+            )))))
+        );
+        assert_eq!(
+            FieldType::parse("[Ljava/lang/Object;").unwrap().to_string(),
+            "java.lang.Object[]"
+        );
+        for malformed in ["", "V", "II", "Ljava/lang/String", "L;", "[", "X", "é"] {
+            assert_eq!(FieldType::parse(malformed), None, "{}", malformed);
+        }
+    }
+
+    #[test]
+    fn test_parse_array_dimensions() {
+        let max = format!("{}I", "[".repeat(MAX_ARRAY_DIMENSIONS));
+        assert_eq!(
+            FieldType::parse(&max).unwrap().to_string(),
+            format!("int{}", "[]".repeat(MAX_ARRAY_DIMENSIONS))
+        );
+        let too_many = format!("{}I", "[".repeat(1_000_000));
+        assert_eq!(FieldType::parse(&too_many), None);
+        assert_eq!(MethodDescriptor::parse(&format!("({})V", too_many)), None);
+    }
+
+    #[test]
+    fn test_parse_method_descriptor() {
+        let descriptor = MethodDescriptor::parse("(Ljava/lang/String;I[Z)V").unwrap();
+        assert_eq!(
+            descriptor.parameters,
+            vec![
+                FieldType::Object("java/lang/String".to_string()),
+                FieldType::Int,
+                FieldType::Array(Box::new(FieldType::Boolean)),
+            ]
+        );
+        assert_eq!(descriptor.return_type, None);
+        assert_eq!(
+            descriptor.to_string(),
+            "(java.lang.String, int, boolean[]): void"
+        );
+        assert_eq!(
+            descriptor.java_parameters(ClassStyle::Short),
+            "(String, int, boolean[])"
+        );
+
+        let descriptor = MethodDescriptor::parse("()Ljava/util/List;").unwrap();
+        assert!(descriptor.parameters.is_empty());
+        assert_eq!(descriptor.java_return_type(ClassStyle::Short), "List");
+
+        for malformed in ["", "()", "V", "(I", "(I)", "(V)V", "(I)VV", "(Lfoo)V"] {
+            assert_eq!(MethodDescriptor::parse(malformed), None, "{}", malformed);
+        }
+    }
+}
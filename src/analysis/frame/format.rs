@@ -0,0 +1,264 @@
+//! Human-readable representation of methods and classes.
+//!
+//! JFR records class names in the internal form (`java/lang/String`)
+//! and method signatures as descriptors (`(Ljava/lang/String;I)V`, see [`super::descriptor`]),
+//! which are converted to Java-style strings (`java.lang.String`, `(String, int): void`).
+
+use crate::analysis::frame::descriptor::{FieldType, MethodDescriptor};
+use crate::analysis::frame::Frame;
+use crate::reader::types::builtin::{Class, JdkMethod};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ClassStyle {
+    /// e.g. `java.util.HashMap$Node`
+    #[default]
+    FullyQualified,
+    /// The name without the package, e.g. `HashMap$Node`
+    Short,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SignatureStyle {
+    /// e.g. `baz`
+    Omitted,
+    /// e.g. `baz(...)`
+    #[default]
+    Ellipsis,
+    /// e.g. `baz(String, int)`
+    Parameters,
+    /// e.g. `baz(String, int): void`
+    Full,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct FormatOptions {
+    /// The style of the declaring class of the method
+    pub class_style: ClassStyle,
+    /// The style of the parameter and return types
+    pub type_style: ClassStyle,
+    pub signature_style: SignatureStyle,
+    /// Appends `:<line>` for frames
+    pub line_number: bool,
+}
+
+impl FormatOptions {
+    pub fn class_style(mut self, style: ClassStyle) -> Self {
+        self.class_style = style;
+        self
+    }
+
+    pub fn type_style(mut self, style: ClassStyle) -> Self {
+        self.type_style = style;
+        self
+    }
+
+    pub fn signature_style(mut self, style: SignatureStyle) -> Self {
+        self.signature_style = style;
+        self
+    }
+
+    pub fn line_number(mut self, line_number: bool) -> Self {
+        self.line_number = line_number;
+        self
+    }
+}
+
+/// Converts the class name in the internal form or binary name form to the given style.
+///
+/// Array descriptors (e.g. `[Ljava/lang/String;`) are converted to Java-style array types (e.g. `java.lang.String[]`).
+pub fn format_class_name(name: &str, style: ClassStyle) -> String {
+    if name.starts_with('[') {
+        if let Some(ty) = FieldType::parse(name) {
+            return ty.java_name(style);
+        }
+    }
+    let name = name.replace('/', ".");
+    match style {
+        ClassStyle::FullyQualified => name,
+        ClassStyle::Short => match name.rfind('.') {
+            Some(idx) => name[idx + 1..].to_string(),
+            None => name,
+        },
+    }
+}
+
+/// Formats the method. The descriptor is used only if the signature style requires it.
+/// If the descriptor is missing or malformed, the parameters are rendered as `(...)`.
+pub fn format_method(
+    class_name: &str,
+    method_name: &str,
+    descriptor: Option<&str>,
+    options: &FormatOptions,
+) -> String {
+    let mut s = String::new();
+    if !class_name.is_empty() {
+        s.push_str(&format_class_name(class_name, options.class_style));
+        s.push('.');
+    }
+    s.push_str(method_name);
+
+    let parsed = descriptor.and_then(MethodDescriptor::parse);
+    match (options.signature_style, parsed) {
+        (SignatureStyle::Omitted, _) => {}
+        (SignatureStyle::Ellipsis, _) | (_, None) => s.push_str("(...)"),
+        (SignatureStyle::Parameters, Some(parsed)) => {
+            s.push_str(&parsed.java_parameters(options.type_style));
+        }
+        (SignatureStyle::Full, Some(parsed)) => {
+            s.push_str(&parsed.java_parameters(options.type_style));
+            s.push_str(": ");
+            s.push_str(&parsed.java_return_type(options.type_style));
+        }
+    }
+    s
+}
+
+/// Formats the resolved frame
+pub fn format_frame(frame: &Frame, options: &FormatOptions) -> String {
+    let mut s = format_method(
+        &frame.class_name,
+        &frame.method_name,
+        frame.descriptor.as_deref(),
+        options,
+    );
+    if options.line_number && frame.line_number >= 0 {
+        s.push(':');
+        s.push_str(&frame.line_number.to_string());
+    }
+    s
+}
+
+/// Formats the deserialized `jdk.types.Method`
+pub fn format_jdk_method(method: &JdkMethod, options: &FormatOptions) -> String {
+    let class_name = method
+        .class
+        .as_ref()
+        .and_then(class_symbol)
+        .unwrap_or_default();
+    let method_name = method
+        .name
+        .as_ref()
+        .and_then(|s| s.string)
+        .unwrap_or_default();
+    let descriptor = method.descriptor.as_ref().and_then(|s| s.string);
+    format_method(class_name, method_name, descriptor, options)
+}
+
+/// Formats the deserialized `java.lang.Class`
+pub fn format_class(class: &Class, style: ClassStyle) -> String {
+    format_class_name(class_symbol(class).unwrap_or_default(), style)
+}
+
+fn class_symbol<'a>(class: &Class<'a>) -> Option<&'a str> {
+    class.name.as_ref().and_then(|s| s.string)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::reader::de::from_value_descriptor;
+    use crate::reader::JfrReader;
+    use crate::test_util::test_data;
+    use std::fs::File;
+
+    #[test]
+    fn test_format_class_name() {
+        let fqcn = ClassStyle::FullyQualified;
+        let short = ClassStyle::Short;
+        assert_eq!(
+            format_class_name("java/lang/String", fqcn),
+            "java.lang.String"
+        );
+        assert_eq!(format_class_name("java.lang.String", short), "String");
+        assert_eq!(
+            format_class_name("java/util/HashMap$Node", short),
+            "HashMap$Node"
+        );
+        assert_eq!(format_class_name("Foo", short), "Foo");
+        assert_eq!(format_class_name("[[I", fqcn), "int[][]");
+        assert_eq!(
+            format_class_name("[Ljava/lang/Object;", fqcn),
+            "java.lang.Object[]"
+        );
+        assert_eq!(format_class_name("[Ljava/lang/Object;", short), "Object[]");
+    }
+
+    #[test]
+    fn test_format_method() {
+        let descriptor = Some("(Ljava/lang/String;I[J)V");
+        let options = FormatOptions::default();
+        assert_eq!(
+            format_method("com/foo/Bar", "baz", descriptor, &options),
+            "com.foo.Bar.baz(...)"
+        );
+        assert_eq!(
+            format_method(
+                "com/foo/Bar",
+                "baz",
+                descriptor,
+                &options.signature_style(SignatureStyle::Omitted)
+            ),
+            "com.foo.Bar.baz"
+        );
+        assert_eq!(
+            format_method(
+                "com/foo/Bar",
+                "baz",
+                descriptor,
+                &options
+                    .signature_style(SignatureStyle::Parameters)
+                    .type_style(ClassStyle::Short)
+            ),
+            "com.foo.Bar.baz(String, int, long[])"
+        );
+        assert_eq!(
+            format_method(
+                "com/foo/Bar",
+                "baz",
+                Some("()[Ljava/lang/String;"),
+                &options
+                    .signature_style(SignatureStyle::Full)
+                    .class_style(ClassStyle::Short)
+            ),
+            "Bar.baz(): java.lang.String[]"
+        );
+        assert_eq!(
+            format_method(
+                "com/foo/Bar",
+                "baz",
+                Some("(Lbroken"),
+                &options.signature_style(SignatureStyle::Full)
+            ),
+            "com.foo.Bar.baz(...)"
+        );
+        assert_eq!(format_method("", "baz", None, &options), "baz(...)");
+    }
+
+    #[test]
+    fn test_format_recorded() {
+        let mut reader = JfrReader::new(File::open(test_data("profiler-wall.jfr")).unwrap());
+        let (chunk_reader, chunk) = reader.chunks().next().unwrap().unwrap();
+        let event = chunk_reader
+            .events(&chunk)
+            .flatten()
+            .find(|e| e.class.name() == "jdk.ExecutionSample")
+            .unwrap();
+        let stack_trace = event.value().get_field("stackTrace").unwrap();
+        let options = FormatOptions::default().signature_style(SignatureStyle::Full);
+
+        for (frame, accessor) in crate::analysis::frame::stack_frames(&stack_trace)
+            .iter()
+            .zip(stack_trace.get_field("frames").unwrap().as_iter().unwrap())
+        {
+            let method: JdkMethod =
+                from_value_descriptor(&chunk, accessor.get_field("method").unwrap().value).unwrap();
+            assert_eq!(
+                format_jdk_method(&method, &options),
+                format_frame(frame, &options)
+            );
+            if let Some(class) = method.class.as_ref() {
+                assert!(!format_class(class, ClassStyle::FullyQualified).contains('/'));
+            }
+        }
+    }
+}
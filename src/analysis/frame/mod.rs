@@ -0,0 +1,144 @@
+//! Resolved stack frames shared by the analyses.
+
+pub mod descriptor;
+pub mod format;
+pub mod stacktrace;
+
+use crate::reader::event::Accessor;
+use std::fmt;
+use std::fmt::Formatter;
+
+/// Stack frame resolved from `jdk.types.StackFrame`.
+/// Unlike the constant pool index, it can be compared across chunks.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Frame {
+    /// The class name in binary name form (e.g. `java.lang.Thread`)
+    pub class_name: String,
+    pub method_name: String,
+    /// The method descriptor (e.g. `(Ljava/lang/String;I)V`)
+    pub descriptor: Option<String>,
+    pub line_number: i32,
+    /// e.g. `Interpreted`, `JIT compiled`, `Inlined`, `Native`
+    pub frame_type: Option<String>,
+}
+
+impl Frame {
+    /// Resolves the value of `jdk.types.StackFrame`.
+    /// Returns None if the method can't be resolved.
+    pub fn from_accessor(frame: &Accessor) -> Option<Self> {
+        let method = frame.get_field("method")?;
+        let method_name = symbol(&method, "name")?;
+        let class_name = method
+            .get_field("type")
+            .and_then(|t| class_name(&t))
+            .unwrap_or_default();
+        let descriptor = symbol(&method, "descriptor").map(String::from);
+        let line_number = frame
+            .get_field("lineNumber")
+            .and_then(|v| i32::try_from(v.value).ok())
+            .unwrap_or(-1);
+        let frame_type = frame
+            .get_field("type")
+            .and_then(|t| t.get_field("description"))
+            .and_then(|v| v.as_str())
+            .map(String::from);
+        Some(Self {
+            class_name,
+            method_name: method_name.to_string(),
+            descriptor,
+            line_number,
+            frame_type,
+        })
+    }
+}
+
+impl fmt::Display for Frame {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        if self.class_name.is_empty() {
+            write!(f, "{}", self.method_name)?;
+        } else {
+            write!(f, "{}.{}", self.class_name, self.method_name)?;
+        }
+        if self.line_number >= 0 {
+            write!(f, ":{}", self.line_number)?;
+        }
+        Ok(())
+    }
+}
+
+/// Resolves the frames of `jdk.types.StackTrace`, from the top frame.
+/// Frames which can't be resolved are skipped.
+pub fn stack_frames(stack_trace: &Accessor) -> Vec<Frame> {
+    stack_trace
+        .get_field("frames")
+        .and_then(|f| f.as_iter())
+        .map(|frames| {
+            frames
+                .filter_map(|frame| Frame::from_accessor(&frame))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Reads the name of `java.lang.Class` in binary name form (e.g. `java.lang.Thread`)
+pub(crate) fn class_name(class: &Accessor) -> Option<String> {
+    symbol(class, "name").map(|s| s.replace('/', "."))
+}
+
+/// Reads `jdk.types.Symbol` field as str
+pub(crate) fn symbol<'a>(accessor: &Accessor<'a>, name: &str) -> Option<&'a str> {
+    let symbol = accessor.get_field(name)?;
+    // old versions may write the symbol as a plain string
+    match symbol.get_field("string") {
+        Some(s) => <&str>::try_from(s.value).ok(),
+        None => <&str>::try_from(symbol.value).ok(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::reader::JfrReader;
+    use crate::test_util::test_data;
+    use std::fs::File;
+
+    #[test]
+    fn test_stack_frames() {
+        let mut reader = JfrReader::new(File::open(test_data("profiler-wall.jfr")).unwrap());
+        let (chunk_reader, chunk) = reader.chunks().next().unwrap().unwrap();
+        let event = chunk_reader
+            .events(&chunk)
+            .flatten()
+            .find(|e| e.class.name() == "jdk.ExecutionSample")
+            .unwrap();
+
+        let stack_trace = event.value().get_field("stackTrace").unwrap();
+        let frames = stack_frames(&stack_trace);
+        let count = stack_trace
+            .get_field("frames")
+            .and_then(|f| f.as_iter())
+            .unwrap()
+            .count();
+        assert_eq!(frames.len(), count);
+        assert!(frames.iter().all(|f| !f.method_name.is_empty()));
+        assert!(frames.iter().all(|f| !f.class_name.contains('/')));
+        assert!(frames.iter().all(|f| f.frame_type.is_some()));
+        assert!(frames.iter().all(|f| f.descriptor.is_some()));
+    }
+
+    #[test]
+    fn test_display() {
+        let mut frame = Frame {
+            class_name: "java.lang.Thread".to_string(),
+            method_name: "run".to_string(),
+            descriptor: None,
+            line_number: 829,
+            frame_type: None,
+        };
+        assert_eq!(frame.to_string(), "java.lang.Thread.run:829");
+        frame.line_number = -1;
+        assert_eq!(frame.to_string(), "java.lang.Thread.run");
+        frame.class_name = String::new();
+        assert_eq!(frame.to_string(), "run");
+    }
+}
@@ -0,0 +1,136 @@
+//! Deduplication of stack traces without resolving frames for each event.
+
+use crate::analysis::frame::{stack_frames, Frame};
+use crate::reader::event::{Accessor, Event};
+use crate::reader::value_descriptor::ValueDescriptor;
+use rustc_hash::{FxHashMap, FxHasher};
+use std::hash::{Hash, Hasher};
+
+/// Hash of the frames of `jdk.types.StackTrace`, computed from the method ids,
+/// the line numbers and the frame types.
+///
+/// The method ids are assigned by the JVM, so the key is stable across chunks
+/// of the same recording, but can't be compared across recordings.
+/// Use resolved [`Frame`]s for that.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct StackTraceKey(u64);
+
+impl StackTraceKey {
+    /// Computes the key of the value of `jdk.types.StackTrace`.
+    /// Returns None if the value has no frames field.
+    pub fn from_accessor(stack_trace: &Accessor) -> Option<Self> {
+        let mut hasher = FxHasher::default();
+        for frame in stack_trace.get_field("frames")?.as_iter()? {
+            match frame.get_field_raw("method").map(|m| m.value) {
+                Some(ValueDescriptor::ConstantPool { constant_index, .. }) => {
+                    constant_index.hash(&mut hasher);
+                }
+                // the method is written inline, which doesn't happen in recordings of HotSpot
+                _ => match Frame::from_accessor(&frame) {
+                    Some(f) => (&f.class_name, &f.method_name, &f.descriptor).hash(&mut hasher),
+                    None => continue,
+                },
+            }
+            frame
+                .get_field("lineNumber")
+                .and_then(|v| i32::try_from(v.value).ok())
+                .hash(&mut hasher);
+            if let Some(ValueDescriptor::ConstantPool { constant_index, .. }) =
+                frame.get_field_raw("type").map(|t| t.value)
+            {
+                constant_index.hash(&mut hasher);
+            }
+        }
+        Some(Self(hasher.finish()))
+    }
+
+    /// Computes the key of the `stackTrace` field of the event
+    pub fn from_event(event: &Event) -> Option<Self> {
+        Self::from_accessor(&event.value().get_field("stackTrace")?)
+    }
+
+    pub fn value(&self) -> u64 {
+        self.0
+    }
+}
+
+/// Map from [`StackTraceKey`] to the resolved frames.
+/// Frames are resolved only for the first occurrence of each stack trace.
+#[derive(Debug, Default)]
+pub struct StackTraceInterner {
+    stacks: FxHashMap<StackTraceKey, Vec<Frame>>,
+}
+
+impl StackTraceInterner {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the key of the stack trace, resolving the frames if it's not interned yet
+    pub fn intern(&mut self, stack_trace: &Accessor) -> Option<StackTraceKey> {
+        let key = StackTraceKey::from_accessor(stack_trace)?;
+        self.stacks
+            .entry(key)
+            .or_insert_with(|| stack_frames(stack_trace));
+        Some(key)
+    }
+
+    /// Interns the `stackTrace` field of the event
+    pub fn intern_event(&mut self, event: &Event) -> Option<StackTraceKey> {
+        self.intern(&event.value().get_field("stackTrace")?)
+    }
+
+    /// Returns the frames from the top frame
+    pub fn get(&self, key: StackTraceKey) -> Option<&[Frame]> {
+        self.stacks.get(&key).map(|f| f.as_slice())
+    }
+
+    pub fn len(&self) -> usize {
+        self.stacks.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.stacks.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (StackTraceKey, &[Frame])> {
+        self.stacks.iter().map(|(k, v)| (*k, v.as_slice()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::reader::JfrReader;
+    use crate::test_util::test_data;
+    use std::collections::HashMap;
+    use std::fs::File;
+
+    #[test]
+    fn test_intern() {
+        let mut reader = JfrReader::new(File::open(test_data("profiler-multichunk.jfr")).unwrap());
+        let mut interner = StackTraceInterner::new();
+        let mut by_key: HashMap<StackTraceKey, Vec<Frame>> = HashMap::new();
+        let mut count = 0;
+
+        for (chunk_reader, chunk) in reader.chunks().flatten() {
+            for event in chunk_reader.events(&chunk).flatten() {
+                if event.class.name() != "jdk.ExecutionSample" {
+                    continue;
+                }
+                count += 1;
+                let key = interner.intern_event(&event).unwrap();
+                assert_eq!(key, StackTraceKey::from_event(&event).unwrap());
+
+                let frames = stack_frames(&event.value().get_field("stackTrace").unwrap());
+                assert_eq!(interner.get(key).unwrap(), frames.as_slice());
+                // same key must always mean the same frames
+                assert_eq!(by_key.entry(key).or_insert_with(|| frames.clone()), &frames);
+            }
+        }
+        assert_eq!(count, 8888);
+        assert_eq!(interner.len(), by_key.len());
+        assert!(interner.len() < count);
+        assert_eq!(interner.iter().count(), interner.len());
+    }
+}
@@ -0,0 +1,172 @@
+//! GC and heap configuration of the recorded JVM, e.g. for capacity planning.
+
+use crate::reader::de::from_event;
+use crate::reader::event::Event;
+use crate::reader::known_types::KnownType;
+use crate::reader::types::jdk;
+use crate::reader::{JfrReader, Result};
+use std::io::{Read, Seek};
+use std::time::Duration;
+
+/// Configuration of the garbage collector and the heap gathered from periodic events
+/// (`jdk.GCConfiguration`, `jdk.GCHeapConfiguration` and `jdk.YoungGenerationConfiguration`).
+///
+/// These events are usually emitted at the beginning and the end of each chunk.
+/// Values in the later chunk take precedence.
+#[derive(Debug, Clone, Default)]
+pub struct GcConfiguration {
+    pub collector: Option<CollectorConfiguration>,
+    pub heap: Option<HeapConfiguration>,
+    pub young_generation: Option<YoungGenerationConfiguration>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct CollectorConfiguration {
+    /// e.g. `G1New`, `ParallelScavenge`
+    pub young_collector: Option<String>,
+    /// e.g. `G1Old`, `ParallelOld`
+    pub old_collector: Option<String>,
+    pub parallel_gc_threads: u32,
+    pub concurrent_gc_threads: u32,
+    pub uses_dynamic_gc_threads: bool,
+    pub is_explicit_gc_concurrent: bool,
+    pub is_explicit_gc_disabled: bool,
+    /// The target pause time, if specified by `-XX:MaxGCPauseMillis` or the collector's default
+    pub pause_target: Option<Duration>,
+    /// The target ratio of the application time to GC time (`-XX:GCTimeRatio`)
+    pub gc_time_ratio: u32,
+}
+
+/// Heap sizes in bytes
+#[derive(Debug, Clone, Default)]
+pub struct HeapConfiguration {
+    pub min_size: u64,
+    pub max_size: u64,
+    pub initial_size: u64,
+    pub uses_compressed_oops: bool,
+    /// e.g. `Zero based`, `32-bit`
+    pub compressed_oops_mode: Option<String>,
+    pub object_alignment: u64,
+    pub heap_address_bits: u8,
+}
+
+/// Young generation sizes in bytes
+#[derive(Debug, Clone, Default)]
+pub struct YoungGenerationConfiguration {
+    pub min_size: u64,
+    pub max_size: u64,
+    /// The ratio of the old generation size to the young generation size (`-XX:NewRatio`)
+    pub new_ratio: u32,
+}
+
+impl GcConfiguration {
+    /// Reads all chunks of the recording to gather the configuration.
+    /// Only the events of the configuration types are decoded.
+    pub fn from<T: Read + Seek>(reader: &mut JfrReader<T>) -> Result<Self> {
+        let mut config = Self::default();
+        for chunk in reader.chunks() {
+            let (chunk_reader, chunk) = chunk?;
+            let events = chunk_reader.events(&chunk).select(|t| {
+                matches!(
+                    t.known_type(),
+                    Some(
+                        KnownType::GCConfiguration
+                            | KnownType::GCHeapConfiguration
+                            | KnownType::YoungGenerationConfiguration
+                    )
+                )
+            });
+            for event in events {
+                config.update(&event?)?;
+            }
+        }
+        Ok(config)
+    }
+
+    /// Updates the configuration by the event. Events of other types are ignored
+    pub fn update(&mut self, event: &Event) -> Result<()> {
+        match event.class.known_type() {
+            Some(KnownType::GCConfiguration) => {
+                let e: jdk::GCConfiguration = from_event(event)?;
+                self.collector = Some(CollectorConfiguration {
+                    young_collector: e.young_collector.and_then(|c| c.name).map(String::from),
+                    old_collector: e.old_collector.and_then(|c| c.name).map(String::from),
+                    parallel_gc_threads: e.parallel_gc_threads,
+                    concurrent_gc_threads: e.concurrent_gc_threads,
+                    uses_dynamic_gc_threads: e.uses_dynamic_gc_threads,
+                    is_explicit_gc_concurrent: e.is_explicit_gc_concurrent,
+                    is_explicit_gc_disabled: e.is_explicit_gc_disabled,
+                    // Long.MIN_VALUE means not set
+                    pause_target: u64::try_from(e.pause_target)
+                        .ok()
+                        .map(Duration::from_millis),
+                    gc_time_ratio: e.gc_time_ratio,
+                });
+            }
+            Some(KnownType::GCHeapConfiguration) => {
+                let e: jdk::GCHeapConfiguration = from_event(event)?;
+                self.heap = Some(HeapConfiguration {
+                    min_size: e.min_size,
+                    max_size: e.max_size,
+                    initial_size: e.initial_size,
+                    uses_compressed_oops: e.uses_compressed_oops,
+                    compressed_oops_mode: e
+                        .compressed_oops_mode
+                        .and_then(|m| m.mode)
+                        .map(String::from),
+                    object_alignment: e.object_alignment,
+                    heap_address_bits: e.heap_address_bits,
+                });
+            }
+            Some(KnownType::YoungGenerationConfiguration) => {
+                let e: jdk::YoungGenerationConfiguration = from_event(event)?;
+                self.young_generation = Some(YoungGenerationConfiguration {
+                    min_size: e.min_size,
+                    max_size: e.max_size,
+                    new_ratio: e.new_ratio,
+                });
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::reader::value_descriptor::StringRepr;
+    use crate::test_util::test_data;
+    use std::fs::File;
+
+    #[test]
+    fn test_gc_configuration() {
+        let mut reader = JfrReader::new(File::open(test_data("recording.jfr")).unwrap())
+            .string_repr(StringRepr::String);
+        let config = GcConfiguration::from(&mut reader).unwrap();
+
+        let collector = config.collector.unwrap();
+        assert_eq!(collector.young_collector.as_deref(), Some("G1New"));
+        assert_eq!(collector.old_collector.as_deref(), Some("G1Old"));
+        assert_eq!(collector.parallel_gc_threads, 4);
+        assert_eq!(collector.concurrent_gc_threads, 1);
+        assert!(collector.uses_dynamic_gc_threads);
+        assert!(!collector.is_explicit_gc_disabled);
+        assert_eq!(collector.pause_target, None);
+        assert_eq!(collector.gc_time_ratio, 12);
+
+        let heap = config.heap.unwrap();
+        assert_eq!(heap.min_size, 6815736);
+        assert_eq!(heap.max_size, 1025507328);
+        assert_eq!(heap.initial_size, 65011712);
+        assert!(heap.uses_compressed_oops);
+        assert_eq!(heap.compressed_oops_mode.as_deref(), Some("32-bit"));
+        assert_eq!(heap.object_alignment, 8);
+        assert_eq!(heap.heap_address_bits, 32);
+
+        let young = config.young_generation.unwrap();
+        assert_eq!(young.min_size, 1363144);
+        assert_eq!(young.max_size, 614465536);
+        assert_eq!(young.new_ratio, 2);
+    }
+}
@@ -0,0 +1,307 @@
+//! Histograms of numeric fields (e.g. `duration` of `jdk.FileRead`) for latency summaries.
+//!
+//! Values are converted to the base unit of the field before recording:
+//! timespans (in ticks, milliseconds, etc.) to nanoseconds, and other fields are recorded as is.
+//! Buckets are log-linear as HdrHistogram, so percentiles are accurate within 1% of the value
+//! with the fixed memory regardless of the range of values.
+
+use crate::reader::event::{Accessor, Event};
+use crate::reader::type_descriptor::Unit;
+use crate::reader::{JfrReader, Result};
+use std::io::{Read, Seek};
+
+/// The number of bits of the sub-bucket, which determines the precision (1 / 2^(bits - 1))
+const SUB_BUCKET_BITS: u32 = 8;
+const SUB_BUCKET_COUNT: u64 = 1 << SUB_BUCKET_BITS;
+const SUB_BUCKET_HALF: u64 = SUB_BUCKET_COUNT / 2;
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Histogram {
+    /// The unit of the recorded values. Timespans are recorded in [`Unit::Nanosecond`]
+    pub unit: Option<Unit>,
+    counts: Vec<u64>,
+    total_count: u64,
+    sum: u128,
+    min: u64,
+    max: u64,
+}
+
+/// Range of the values in a bucket and the count
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Bucket {
+    pub low: u64,
+    /// Inclusive
+    pub high: u64,
+    pub count: u64,
+}
+
+impl Histogram {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Reads all chunks of the recording and records the field of the events of the type
+    pub fn read<T: Read + Seek>(
+        reader: &mut JfrReader<T>,
+        event_type: &str,
+        field: &str,
+    ) -> Result<Self> {
+        let mut histogram = Self::new();
+        for chunk in reader.chunks() {
+            let (chunk_reader, chunk) = chunk?;
+            for event in chunk_reader.events(&chunk) {
+                let event = event?;
+                if event.class.name() == event_type {
+                    histogram.add_event(&event, field);
+                }
+            }
+        }
+        Ok(histogram)
+    }
+
+    /// Records the field of the event converted to the base unit.
+    /// Returns false if the field is missing, not integral or negative
+    pub fn add_event(&mut self, event: &Event, field: &str) -> bool {
+        let value = event.value();
+        match value.get_field(field).and_then(|v| field_value(&v, event)) {
+            Some((v, unit)) => {
+                if self.unit.is_none() {
+                    self.unit = unit;
+                }
+                self.record(v);
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn record(&mut self, value: u64) {
+        self.record_n(value, 1);
+    }
+
+    /// Records the value `count` times
+    pub fn record_n(&mut self, value: u64, count: u64) {
+        if count == 0 {
+            return;
+        }
+        let idx = bucket_index(value);
+        if self.counts.len() <= idx {
+            self.counts.resize(idx + 1, 0);
+        }
+        self.counts[idx] += count;
+        if self.total_count == 0 || value < self.min {
+            self.min = value;
+        }
+        self.max = self.max.max(value);
+        self.total_count += count;
+        self.sum += value as u128 * count as u128;
+    }
+
+    /// Adds all values of the other histogram
+    pub fn merge(&mut self, other: &Histogram) {
+        if other.total_count == 0 {
+            return;
+        }
+        if self.counts.len() < other.counts.len() {
+            self.counts.resize(other.counts.len(), 0);
+        }
+        for (c, o) in self.counts.iter_mut().zip(other.counts.iter()) {
+            *c += o;
+        }
+        if self.total_count == 0 || other.min < self.min {
+            self.min = other.min;
+        }
+        self.max = self.max.max(other.max);
+        self.total_count += other.total_count;
+        self.sum += other.sum;
+        if self.unit.is_none() {
+            self.unit = other.unit;
+        }
+    }
+
+    pub fn count(&self) -> u64 {
+        self.total_count
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.total_count == 0
+    }
+
+    /// The exact minimum value
+    pub fn min(&self) -> Option<u64> {
+        (!self.is_empty()).then_some(self.min)
+    }
+
+    /// The exact maximum value
+    pub fn max(&self) -> Option<u64> {
+        (!self.is_empty()).then_some(self.max)
+    }
+
+    /// The exact mean of the values
+    pub fn mean(&self) -> Option<f64> {
+        (!self.is_empty()).then(|| self.sum as f64 / self.total_count as f64)
+    }
+
+    /// Returns the value at the percentile (0.0 to 100.0), i.e. the value which is greater than or
+    /// equal to `percentile`% of the recorded values. The value is the upper bound of the bucket,
+    /// clamped by the exact minimum and maximum
+    pub fn percentile(&self, percentile: f64) -> Option<u64> {
+        if self.is_empty() {
+            return None;
+        }
+        let percentile = percentile.clamp(0.0, 100.0);
+        let rank = ((percentile / 100.0 * self.total_count as f64).ceil() as u64).max(1);
+        let mut seen = 0;
+        for (idx, &count) in self.counts.iter().enumerate() {
+            seen += count;
+            if seen >= rank {
+                let (_, high) = bucket_range(idx);
+                return Some(high.clamp(self.min, self.max));
+            }
+        }
+        Some(self.max)
+    }
+
+    /// Non-empty buckets ordered by the value
+    pub fn buckets(&self) -> impl Iterator<Item = Bucket> + '_ {
+        self.counts
+            .iter()
+            .enumerate()
+            .filter(|(_, c)| **c > 0)
+            .map(|(idx, &count)| {
+                let (low, high) = bucket_range(idx);
+                Bucket { low, high, count }
+            })
+    }
+}
+
+/// Builds the histogram of the field of the events (e.g. `histogram(events, "duration")`)
+pub fn histogram<'a, 'b: 'a, I>(events: I, field: &str) -> Histogram
+where
+    I: IntoIterator<Item = &'a Event<'b>>,
+{
+    let mut histogram = Histogram::new();
+    for event in events {
+        histogram.add_event(event, field);
+    }
+    histogram
+}
+
+/// Returns the value in the base unit of the field
+fn field_value(accessor: &Accessor, event: &Event) -> Option<(u64, Option<Unit>)> {
+    let v = accessor.as_i64();
+    let field = accessor.field();
+    if let (Some(v), Some(field)) = (v, field) {
+        if field.is_timespan() {
+            let nanos = field.timespan_nanos(v, &event.chunk.header)?;
+            return Some((u64::try_from(nanos).ok()?, Some(Unit::Nanosecond)));
+        }
+    }
+    Some((accessor.as_u64()?, field.and_then(|f| f.unit)))
+}
+
+fn bucket_index(value: u64) -> usize {
+    if value < SUB_BUCKET_COUNT {
+        return value as usize;
+    }
+    let shift = (64 - value.leading_zeros()) - SUB_BUCKET_BITS;
+    let top = value >> shift;
+    (SUB_BUCKET_COUNT + (shift as u64 - 1) * SUB_BUCKET_HALF + (top - SUB_BUCKET_HALF)) as usize
+}
+
+/// Returns (lowest, highest) value of the bucket
+fn bucket_range(idx: usize) -> (u64, u64) {
+    let idx = idx as u64;
+    if idx < SUB_BUCKET_COUNT {
+        return (idx, idx);
+    }
+    let k = idx - SUB_BUCKET_COUNT;
+    let shift = k / SUB_BUCKET_HALF + 1;
+    let top = k % SUB_BUCKET_HALF + SUB_BUCKET_HALF;
+    let low = top << shift;
+    (low, low + ((1u64 << shift) - 1))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::test_data;
+    use std::fs::File;
+
+    #[test]
+    fn test_bucket() {
+        for v in [0, 1, 255, 256, 257, 511, 512, 1000, 123_456_789, u64::MAX] {
+            let (low, high) = bucket_range(bucket_index(v));
+            assert!(low <= v && v <= high, "{}: {}..{}", v, low, high);
+            assert!((high - low) as f64 <= v as f64 / 100.0);
+        }
+        assert_eq!(bucket_index(256), 256);
+        assert_eq!(bucket_range(256), (256, 257));
+    }
+
+    #[test]
+    fn test_percentile() {
+        let mut histogram = Histogram::new();
+        assert_eq!(histogram.percentile(50.0), None);
+        for v in 1..=10_000 {
+            histogram.record(v * 1000);
+        }
+        assert_eq!(histogram.count(), 10_000);
+        assert_eq!(histogram.min(), Some(1000));
+        assert_eq!(histogram.max(), Some(10_000_000));
+        assert_eq!(histogram.mean(), Some(5_000_500.0));
+        for (p, expected) in [
+            (50.0, 5_000_000.0),
+            (99.0, 9_900_000.0),
+            (99.9, 9_990_000.0),
+        ] {
+            let actual = histogram.percentile(p).unwrap() as f64;
+            assert!((actual - expected).abs() / expected < 0.01, "{}", p);
+        }
+        assert_eq!(histogram.percentile(100.0), Some(10_000_000));
+        // the upper bound of the bucket of the minimum
+        assert!(histogram.percentile(0.0).unwrap() - 1000 < 10);
+
+        let mut merged = Histogram::new();
+        merged.merge(&histogram);
+        merged.record_n(1, 10_000);
+        assert_eq!(merged.count(), 20_000);
+        assert_eq!(merged.percentile(50.0), Some(1));
+        assert_eq!(
+            merged.buckets().map(|b| b.count).sum::<u64>(),
+            merged.count()
+        );
+    }
+
+    #[test]
+    fn test_duration() {
+        let mut reader = JfrReader::new(File::open(test_data("recording.jfr")).unwrap());
+        let (chunk_reader, chunk) = reader.chunks().next().unwrap().unwrap();
+        let events = chunk_reader
+            .events(&chunk)
+            .flatten()
+            .filter(|e| e.class.name() == "jdk.ThreadPark")
+            .collect::<Vec<_>>();
+        let histogram = histogram(&events, "duration");
+        assert_eq!(histogram.unit, Some(Unit::Nanosecond));
+        assert_eq!(histogram.count(), events.len() as u64);
+
+        let mut nanos = events
+            .iter()
+            .map(|e| {
+                let d = e.value().get_field("duration").unwrap();
+                chunk.header.ticks_to_nanos(d.as_i64().unwrap()) as u64
+            })
+            .collect::<Vec<_>>();
+        nanos.sort();
+        assert_eq!(histogram.max(), nanos.last().copied());
+        let median = nanos[nanos.len().div_ceil(2) - 1] as f64;
+        let actual = histogram.percentile(50.0).unwrap() as f64;
+        assert!((actual - median).abs() / median < 0.01);
+
+        // other fields are recorded as is
+        let mut reader = JfrReader::new(File::open(test_data("recording.jfr")).unwrap());
+        let histogram = Histogram::read(&mut reader, "jdk.ThreadPark", "timeout").unwrap();
+        assert_eq!(histogram.unit, Some(Unit::Nanosecond));
+    }
+}
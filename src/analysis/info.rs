@@ -0,0 +1,277 @@
+//! JVM, OS and CPU information of the recorded process.
+
+use crate::reader::compat::JdkVersion;
+use crate::reader::de::from_event;
+use crate::reader::event::Event;
+use crate::reader::known_types::KnownType;
+use crate::reader::{JfrReader, Result};
+use std::collections::BTreeMap;
+use std::io::{Read, Seek};
+use std::time::SystemTime;
+
+/// Information of the recorded process gathered from periodic events
+/// (`jdk.JVMInformation`, `jdk.OSInformation`, `jdk.CPUInformation`,
+/// `jdk.InitialSystemProperty` and `jdk.*Flag`).
+///
+/// These events are usually emitted at the beginning and the end of each chunk.
+/// Values in the later chunk take precedence.
+#[derive(Debug, Clone, Default)]
+pub struct RecordingInfo {
+    pub jvm: Option<JvmInformation>,
+    pub os_version: Option<String>,
+    pub cpu: Option<CpuInformation>,
+    pub system_properties: BTreeMap<String, String>,
+    pub flags: BTreeMap<String, JvmFlag>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct JvmInformation {
+    pub jvm_name: Option<String>,
+    pub jvm_version: Option<String>,
+    pub jvm_arguments: Option<String>,
+    pub jvm_flags: Option<String>,
+    pub java_arguments: Option<String>,
+    pub jvm_start_time: Option<SystemTime>,
+    pub pid: i64,
+}
+
+impl JvmInformation {
+    /// Parses the JDK version from `jvm_version`
+    pub fn jdk_version(&self) -> Option<JdkVersion> {
+        self.jvm_version.as_deref().and_then(JdkVersion::parse)
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct CpuInformation {
+    pub cpu: Option<String>,
+    pub description: Option<String>,
+    pub sockets: u32,
+    pub cores: u32,
+    pub hw_threads: u32,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct JvmFlag {
+    pub value: FlagValue,
+    /// How the value was set (e.g. `Default`, `Command line`, `Ergonomic`)
+    pub origin: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum FlagValue {
+    Bool(bool),
+    /// Value of `jdk.IntFlag` or `jdk.LongFlag`
+    Int(i64),
+    /// Value of `jdk.UnsignedIntFlag` or `jdk.UnsignedLongFlag`
+    Unsigned(u64),
+    Double(f64),
+    String(Option<String>),
+}
+
+impl RecordingInfo {
+    /// Reads all chunks of the recording to gather the information
+    pub fn from<T: Read + Seek>(reader: &mut JfrReader<T>) -> Result<Self> {
+        let mut info = Self::default();
+        for chunk in reader.chunks() {
+            let (chunk_reader, chunk) = chunk?;
+            for event in chunk_reader.events(&chunk) {
+                info.update(&event?)?;
+            }
+        }
+        Ok(info)
+    }
+
+    /// Updates the information by the event. Events of other types are ignored
+    pub fn update(&mut self, event: &Event) -> Result<()> {
+        match event.class.known_type() {
+            Some(KnownType::JVMInformation) => {
+                let e: de::JvmInformation = from_event(event)?;
+                self.jvm = Some(JvmInformation {
+                    jvm_name: e.jvm_name.map(String::from),
+                    jvm_version: e.jvm_version.map(String::from),
+                    jvm_arguments: e.jvm_arguments.map(String::from),
+                    jvm_flags: e.jvm_flags.map(String::from),
+                    java_arguments: e.java_arguments.map(String::from),
+                    jvm_start_time: e.jvm_start_time,
+                    pid: e.pid,
+                });
+            }
+            Some(KnownType::OSInformation) => {
+                let e: de::OsInformation = from_event(event)?;
+                self.os_version = e.os_version.map(String::from);
+            }
+            Some(KnownType::CPUInformation) => {
+                let e: de::CpuInformation = from_event(event)?;
+                self.cpu = Some(CpuInformation {
+                    cpu: e.cpu.map(String::from),
+                    description: e.description.map(String::from),
+                    sockets: e.sockets,
+                    cores: e.cores,
+                    hw_threads: e.hw_threads,
+                });
+            }
+            Some(KnownType::InitialSystemProperty) => {
+                let e: de::InitialSystemProperty = from_event(event)?;
+                if let Some(key) = e.key {
+                    self.system_properties
+                        .insert(key.to_string(), e.value.unwrap_or_default().to_string());
+                }
+            }
+            _ => self.update_flag(event)?,
+        }
+        Ok(())
+    }
+
+    fn update_flag(&mut self, event: &Event) -> Result<()> {
+        let (name, value, origin) = match event.class.name() {
+            "jdk.BooleanFlag" => {
+                let e: de::Flag<bool> = from_event(event)?;
+                (e.name, FlagValue::Bool(e.value), e.origin)
+            }
+            "jdk.IntFlag" => {
+                let e: de::Flag<i32> = from_event(event)?;
+                (e.name, FlagValue::Int(e.value as i64), e.origin)
+            }
+            "jdk.LongFlag" => {
+                let e: de::Flag<i64> = from_event(event)?;
+                (e.name, FlagValue::Int(e.value), e.origin)
+            }
+            "jdk.UnsignedIntFlag" => {
+                let e: de::Flag<u32> = from_event(event)?;
+                (e.name, FlagValue::Unsigned(e.value as u64), e.origin)
+            }
+            "jdk.UnsignedLongFlag" => {
+                let e: de::Flag<u64> = from_event(event)?;
+                (e.name, FlagValue::Unsigned(e.value), e.origin)
+            }
+            "jdk.DoubleFlag" => {
+                let e: de::Flag<f64> = from_event(event)?;
+                (e.name, FlagValue::Double(e.value), e.origin)
+            }
+            "jdk.StringFlag" => {
+                let e: de::Flag<Option<&str>> = from_event(event)?;
+                (
+                    e.name,
+                    FlagValue::String(e.value.map(String::from)),
+                    e.origin,
+                )
+            }
+            _ => return Ok(()),
+        };
+        if let Some(name) = name {
+            self.flags.insert(
+                name.to_string(),
+                JvmFlag {
+                    value,
+                    origin: origin.and_then(|o| o.origin).map(String::from),
+                },
+            );
+        }
+        Ok(())
+    }
+}
+
+/// Borrowed representation of the events
+mod de {
+    use serde::Deserialize;
+    use std::time::SystemTime;
+
+    #[derive(Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    pub struct JvmInformation<'a> {
+        pub jvm_name: Option<&'a str>,
+        pub jvm_version: Option<&'a str>,
+        pub jvm_arguments: Option<&'a str>,
+        pub jvm_flags: Option<&'a str>,
+        pub java_arguments: Option<&'a str>,
+        #[serde(default, with = "crate::reader::de::timestamp::option")]
+        pub jvm_start_time: Option<SystemTime>,
+        #[serde(default)]
+        pub pid: i64,
+    }
+
+    #[derive(Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    pub struct OsInformation<'a> {
+        pub os_version: Option<&'a str>,
+    }
+
+    #[derive(Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    pub struct CpuInformation<'a> {
+        pub cpu: Option<&'a str>,
+        pub description: Option<&'a str>,
+        #[serde(default)]
+        pub sockets: u32,
+        #[serde(default)]
+        pub cores: u32,
+        #[serde(default)]
+        pub hw_threads: u32,
+    }
+
+    #[derive(Deserialize)]
+    pub struct InitialSystemProperty<'a> {
+        pub key: Option<&'a str>,
+        pub value: Option<&'a str>,
+    }
+
+    #[derive(Deserialize)]
+    pub struct Flag<'a, T> {
+        pub name: Option<&'a str>,
+        pub value: T,
+        #[serde(borrow)]
+        pub origin: Option<FlagValueOrigin<'a>>,
+    }
+
+    #[derive(Deserialize)]
+    pub struct FlagValueOrigin<'a> {
+        pub origin: Option<&'a str>,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::test_data;
+    use std::fs::File;
+    use std::time::Duration;
+
+    #[test]
+    fn test_recording_info() {
+        let mut reader = JfrReader::new(File::open(test_data("recording.jfr")).unwrap());
+        let info = RecordingInfo::from(&mut reader).unwrap();
+
+        let jvm = info.jvm.unwrap();
+        assert_eq!(jvm.jvm_name.as_deref(), Some("OpenJDK 64-Bit Server VM"));
+        assert_eq!(
+            jvm.java_arguments.as_deref(),
+            Some("Example /data_ext4/out")
+        );
+        assert_eq!(jvm.pid, 3741);
+        assert_eq!(
+            jvm.jdk_version().map(|v| v.to_string()).as_deref(),
+            Some("11.0.16")
+        );
+        assert_eq!(
+            jvm.jvm_start_time,
+            Some(SystemTime::UNIX_EPOCH + Duration::from_millis(1661595161570))
+        );
+        assert!(info.os_version.is_some());
+        assert_eq!(info.cpu.unwrap().hw_threads, 4);
+        assert_eq!(
+            info.system_properties
+                .get("java.vm.info")
+                .map(|s| s.as_str()),
+            Some("mixed mode")
+        );
+        assert_eq!(
+            info.flags.get("ActiveProcessorCount").map(|f| &f.value),
+            Some(&FlagValue::Int(-1))
+        );
+        assert!(info
+            .flags
+            .values()
+            .any(|f| f.origin.as_deref() == Some("Default")));
+    }
+}
@@ -0,0 +1,494 @@
+//! Retention paths of `jdk.OldObjectSample`, i.e. the leak analysis of JMC.
+//!
+//! The path is recorded only if the recording is dumped with `path-to-gc-roots=true`.
+//! Otherwise, only the sampled object and its allocation stack trace are available.
+
+use crate::analysis::frame::{class_name, stack_frames, Frame};
+use crate::reader::event::{Accessor, Event};
+use crate::reader::known_types::KnownType;
+use crate::reader::{JfrReader, Result};
+use std::collections::HashSet;
+use std::fmt;
+use std::fmt::Formatter;
+use std::io::{Read, Seek};
+
+/// The sampled object and the chain of the objects referencing it
+#[derive(Debug, Clone, PartialEq)]
+pub struct LeakPath {
+    pub object: LeakObject,
+    /// Nanoseconds since UNIX epoch
+    pub allocation_time_nanos: i64,
+    pub last_known_heap_usage: u64,
+    /// The number of elements if the object is an array
+    pub array_elements: Option<i32>,
+    pub allocation_stack: Vec<Frame>,
+    /// Referrers from the nearest one (which references the sampled object) to the farthest
+    pub referrers: Vec<Referrer>,
+    /// The GC root which references the farthest object
+    pub root: Option<GcRoot>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LeakObject {
+    pub class_name: Option<String>,
+    pub description: Option<String>,
+    pub address: u64,
+}
+
+/// The object which references the previous object in the path
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Referrer {
+    pub object: LeakObject,
+    pub via: Via,
+    /// The number of objects omitted between this and the previous object
+    pub skip: i32,
+}
+
+/// How the referrer holds the reference
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Via {
+    Field(String),
+    ArrayElement { index: i32, size: i32 },
+    Unknown,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GcRoot {
+    pub description: Option<String>,
+    /// e.g. `Threads`, `JNI Global`, `Class Loader Data`
+    pub system: Option<String>,
+    /// e.g. `Stack Variable`, `Global Object Handle`
+    pub root_type: Option<String>,
+}
+
+impl LeakPath {
+    /// Resolves the path from `jdk.OldObjectSample` event.
+    /// Returns None if the event is of another type or the object is missing.
+    pub fn from_event(event: &Event) -> Option<Self> {
+        if event.class.known_type() != Some(KnownType::OldObjectSample) {
+            return None;
+        }
+        let value = event.value();
+        let object = value.get_field("object")?;
+
+        let mut referrers = vec![];
+        // guard against malformed cyclic references
+        let mut visited = HashSet::new();
+        let mut current = object.get_field("referrer");
+        while let Some(reference) = current {
+            let referrer = match reference.get_field("object") {
+                Some(o) => o,
+                None => break,
+            };
+            let referrer_object = LeakObject::new(&referrer);
+            if !visited.insert(referrer_object.address) {
+                break;
+            }
+            referrers.push(Referrer {
+                object: referrer_object,
+                via: Via::new(&reference),
+                skip: int_field(&reference, "skip").unwrap_or(0),
+            });
+            current = referrer.get_field("referrer");
+        }
+
+        let root = value.get_field("root").map(|root| GcRoot {
+            description: string_field(&root, "description"),
+            system: root
+                .get_field("system")
+                .and_then(|s| string_field(&s, "system")),
+            root_type: root
+                .get_field("type")
+                .and_then(|t| string_field(&t, "type")),
+        });
+        let allocation_time = value
+            .get_field("allocationTime")
+            .and_then(|v| v.as_i64())
+            .unwrap_or(0);
+
+        Some(Self {
+            object: LeakObject::new(&object),
+            allocation_time_nanos: event.chunk.header.ticks_to_epoch_nanos(allocation_time),
+            last_known_heap_usage: value
+                .get_field("lastKnownHeapUsage")
+                .and_then(|v| v.as_u64())
+                .unwrap_or(0),
+            array_elements: int_field(&value, "arrayElements").filter(|n| *n >= 0),
+            allocation_stack: value
+                .get_field("stackTrace")
+                .map(|s| stack_frames(&s))
+                .unwrap_or_default(),
+            referrers,
+            root,
+        })
+    }
+}
+
+impl LeakObject {
+    fn new(object: &Accessor) -> Self {
+        Self {
+            class_name: object.get_field("type").and_then(|t| class_name(&t)),
+            description: string_field(object, "description"),
+            address: object
+                .get_field("address")
+                .and_then(|v| v.as_u64())
+                .unwrap_or(0),
+        }
+    }
+}
+
+impl Via {
+    fn new(reference: &Accessor) -> Self {
+        if let Some(field) = reference
+            .get_field("field")
+            .and_then(|f| string_field(&f, "name"))
+        {
+            return Via::Field(field);
+        }
+        if let Some(array) = reference.get_field("array") {
+            return Via::ArrayElement {
+                index: int_field(&array, "index").unwrap_or(-1),
+                size: int_field(&array, "size").unwrap_or(-1),
+            };
+        }
+        Via::Unknown
+    }
+}
+
+/// Renders the path in the similar format as JMC:
+///
+/// ```text
+/// java.lang.Object[] (array of 16)
+///   <- [3] of java.lang.Object[]
+///   <- ... 2 objects skipped
+///   <- elementData of java.util.ArrayList
+///   <- GC root: Global Object Handle (JNI Global)
+/// ```
+impl fmt::Display for LeakPath {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.object)?;
+        if let Some(n) = self.array_elements {
+            write!(f, " (array of {})", n)?;
+        }
+        for referrer in self.referrers.iter() {
+            if referrer.skip > 0 {
+                write!(f, "\n  <- ... {} objects skipped", referrer.skip)?;
+            }
+            match &referrer.via {
+                Via::Field(name) => write!(f, "\n  <- {} of {}", name, referrer.object)?,
+                Via::ArrayElement { index, .. } => {
+                    write!(f, "\n  <- [{}] of {}", index, referrer.object)?
+                }
+                Via::Unknown => write!(f, "\n  <- {}", referrer.object)?,
+            }
+        }
+        if let Some(root) = &self.root {
+            write!(
+                f,
+                "\n  <- GC root: {}",
+                root.root_type.as_deref().unwrap_or("<unknown>")
+            )?;
+            if let Some(system) = &root.system {
+                write!(f, " ({})", system)?;
+            }
+            if let Some(description) = &root.description {
+                write!(f, " {}", description)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for LeakObject {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.class_name.as_deref().unwrap_or("<unknown>"))?;
+        if let Some(description) = &self.description {
+            write!(f, " {}", description)?;
+        }
+        Ok(())
+    }
+}
+
+/// Resolves the paths of all `jdk.OldObjectSample` events in the recording
+pub fn leak_paths<T: Read + Seek>(reader: &mut JfrReader<T>) -> Result<Vec<LeakPath>> {
+    let mut paths = vec![];
+    for chunk in reader.chunks() {
+        let (chunk_reader, chunk) = chunk?;
+        for event in chunk_reader.events(&chunk) {
+            if let Some(path) = LeakPath::from_event(&event?) {
+                paths.push(path);
+            }
+        }
+    }
+    Ok(paths)
+}
+
+fn string_field(accessor: &Accessor, name: &str) -> Option<String> {
+    accessor
+        .get_field(name)
+        .and_then(|v| v.as_str())
+        .map(String::from)
+}
+
+fn int_field(accessor: &Accessor, name: &str) -> Option<i32> {
+    accessor
+        .get_field(name)
+        .and_then(|v| i32::try_from(v.value).ok())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::reader::byte_stream::ByteStream;
+    use crate::reader::value_descriptor::ValueDescriptor;
+    use crate::reader::Chunk;
+    use crate::test_util::test_data;
+    use crate::writer::{to_event, to_value_descriptor};
+    use serde::Serialize;
+    use std::fs::File;
+    use std::io::Cursor;
+
+    #[derive(Serialize)]
+    #[serde(rename_all = "camelCase")]
+    struct OldObjectSample {
+        start_time: i64,
+        allocation_time: i64,
+        last_known_heap_usage: u64,
+        object: Option<u64>,
+        array_elements: i32,
+        root: Option<u64>,
+    }
+
+    #[derive(Serialize)]
+    struct OldObject<'a> {
+        address: u64,
+        r#type: Option<u64>,
+        description: Option<&'a str>,
+        referrer: Option<u64>,
+    }
+
+    #[derive(Serialize)]
+    struct Reference {
+        array: Option<u64>,
+        field: Option<u64>,
+        object: Option<u64>,
+        skip: i32,
+    }
+
+    #[derive(Serialize)]
+    struct OldObjectArray {
+        size: i32,
+        index: i32,
+    }
+
+    #[derive(Serialize)]
+    struct OldObjectField<'a> {
+        name: &'a str,
+        modifiers: i16,
+    }
+
+    #[derive(Serialize)]
+    struct OldObjectGcRoot<'a> {
+        description: Option<&'a str>,
+        system: Option<u64>,
+        r#type: Option<u64>,
+    }
+
+    #[derive(Serialize)]
+    struct OldObjectRootSystem<'a> {
+        system: &'a str,
+    }
+
+    #[derive(Serialize)]
+    struct OldObjectRootType<'a> {
+        r#type: &'a str,
+    }
+
+    #[test]
+    fn test_leak_path() {
+        let mut reader = JfrReader::new(File::open(test_data("recording.jfr")).unwrap());
+        let (chunk_reader, mut chunk) = reader.chunks().next().unwrap().unwrap();
+
+        // borrow the class in the constant pool from jdk.JavaMonitorWait event
+        let class = chunk_reader
+            .events(&chunk)
+            .flatten()
+            .filter(|e| e.class.name() == "jdk.JavaMonitorWait")
+            .find_map(
+                |e| match e.value().get_field_raw("monitorClass").map(|v| v.value) {
+                    Some(ValueDescriptor::ConstantPool { constant_index, .. }) => {
+                        Some(*constant_index as u64)
+                    }
+                    _ => None,
+                },
+            )
+            .unwrap();
+
+        // sampled(1) <-[3]- array(2) <-(2 skipped)- holder(3) <- root
+        register(
+            &mut chunk,
+            "jdk.types.OldObject",
+            1,
+            &OldObject {
+                address: 0x10,
+                r#type: Some(class),
+                description: Some("sampled"),
+                referrer: Some(1),
+            },
+        );
+        register(
+            &mut chunk,
+            "jdk.types.Reference",
+            1,
+            &Reference {
+                array: Some(1),
+                field: None,
+                object: Some(2),
+                skip: 0,
+            },
+        );
+        register(
+            &mut chunk,
+            "jdk.types.OldObjectArray",
+            1,
+            &OldObjectArray { size: 16, index: 3 },
+        );
+        register(
+            &mut chunk,
+            "jdk.types.OldObject",
+            2,
+            &OldObject {
+                address: 0x20,
+                r#type: Some(class),
+                description: None,
+                referrer: Some(2),
+            },
+        );
+        register(
+            &mut chunk,
+            "jdk.types.Reference",
+            2,
+            &Reference {
+                array: None,
+                field: Some(1),
+                object: Some(3),
+                skip: 2,
+            },
+        );
+        register(
+            &mut chunk,
+            "jdk.types.OldObjectField",
+            1,
+            &OldObjectField {
+                name: "table",
+                modifiers: 0,
+            },
+        );
+        register(
+            &mut chunk,
+            "jdk.types.OldObject",
+            3,
+            &OldObject {
+                address: 0x30,
+                r#type: None,
+                description: None,
+                referrer: None,
+            },
+        );
+        register(
+            &mut chunk,
+            "jdk.types.OldObjectGcRoot",
+            1,
+            &OldObjectGcRoot {
+                description: None,
+                system: Some(1),
+                r#type: Some(1),
+            },
+        );
+        register(
+            &mut chunk,
+            "jdk.types.OldObjectRootSystem",
+            1,
+            &OldObjectRootSystem {
+                system: "JNI Global",
+            },
+        );
+        register(
+            &mut chunk,
+            "jdk.types.OldObjectRootType",
+            1,
+            &OldObjectRootType {
+                r#type: "Global Object Handle",
+            },
+        );
+
+        let sample = OldObjectSample {
+            start_time: chunk.header.start_ticks,
+            allocation_time: chunk.header.start_ticks,
+            last_known_heap_usage: 1024,
+            object: Some(1),
+            array_elements: -1,
+            root: Some(1),
+        };
+        let bytes = to_event(&sample, "jdk.OldObjectSample", &chunk).unwrap();
+        let mut stream = ByteStream::new(Cursor::new(bytes));
+        stream.set_int_encoding(chunk.header.int_encoding());
+        stream.read_i32().unwrap();
+        let class_id = stream.read_i64().unwrap();
+        let value = ValueDescriptor::try_new(&mut stream, class_id, &chunk.metadata).unwrap();
+        let event = Event {
+            byte_offset: 0,
+            byte_size: 0,
+            class: chunk.metadata.type_pool.get(class_id).unwrap(),
+            chunk: &chunk,
+            value,
+        };
+
+        let path = LeakPath::from_event(&event).unwrap();
+        let class_name = path.object.class_name.clone().unwrap();
+        assert!(!class_name.contains('/'));
+        assert_eq!(path.object.address, 0x10);
+        assert_eq!(path.allocation_time_nanos, chunk.header.start_time_nanos);
+        assert_eq!(path.last_known_heap_usage, 1024);
+        assert_eq!(path.array_elements, None);
+        assert_eq!(path.referrers.len(), 2);
+        assert_eq!(
+            path.referrers[0].via,
+            Via::ArrayElement { index: 3, size: 16 }
+        );
+        assert_eq!(path.referrers[1].via, Via::Field("table".to_string()));
+        assert_eq!(path.referrers[1].skip, 2);
+        assert_eq!(
+            path.root,
+            Some(GcRoot {
+                description: None,
+                system: Some("JNI Global".to_string()),
+                root_type: Some("Global Object Handle".to_string()),
+            })
+        );
+        assert_eq!(
+            path.to_string(),
+            format!(
+                "{0} sampled\n  <- [3] of {0}\n  <- ... 2 objects skipped\n  <- table of <unknown>\n  <- GC root: Global Object Handle (JNI Global)",
+                class_name
+            )
+        );
+    }
+
+    #[test]
+    fn test_no_samples() {
+        let mut reader = JfrReader::new(File::open(test_data("recording.jfr")).unwrap());
+        assert!(leak_paths(&mut reader).unwrap().is_empty());
+    }
+
+    fn register<T: Serialize>(chunk: &mut Chunk, type_name: &str, index: i64, value: &T) {
+        let class_id = chunk
+            .metadata
+            .type_pool
+            .get_by_name(type_name)
+            .unwrap()
+            .class_id;
+        let value = to_value_descriptor(value, class_id, &chunk.metadata).unwrap();
+        chunk.constant_pool.register(class_id, index, value);
+    }
+}
@@ -0,0 +1,38 @@
+//! Analyses of recordings built on top of the reader.
+
+pub mod aggregate;
+pub mod calltree;
+pub mod class_loading;
+pub mod container;
+pub mod cpu;
+pub mod diff;
+pub mod exceptions;
+#[cfg(feature = "flamegraph")]
+pub mod flamegraph;
+pub mod frame;
+pub mod gc_config;
+pub mod histogram;
+pub mod info;
+pub mod leak;
+pub mod native;
+pub mod native_memory;
+#[cfg(feature = "otlp")]
+pub mod otlp;
+pub mod perf;
+pub mod safepoint;
+pub mod session;
+pub mod thread_dump;
+pub mod timeline;
+pub mod timeseries;
+pub mod weight;
+
+pub use aggregate::Aggregator;
+pub use calltree::CallTree;
+pub use diff::RecordingDiff;
+pub use exceptions::ExceptionAggregator;
+pub use frame::Frame;
+pub use histogram::{histogram, Histogram};
+pub use info::RecordingInfo;
+pub use leak::LeakPath;
+pub use session::RecordingSessions;
+pub use timeline::Timeline;
@@ -0,0 +1,201 @@
+//! Attributing native addresses to the loaded libraries, from `jdk.NativeLibrary` events.
+//!
+//! Native frames and some event fields (e.g. addresses of monitors, PCs recorded by profilers)
+//! are raw addresses, which are meaningless without the memory map of the process.
+//! [`NativeLibraryMap::symbolize`] turns them into the library and the offset in it,
+//! which can be resolved further to symbol names by an external [`Symbolizer`].
+
+use crate::reader::de::from_event;
+use crate::reader::event::Event;
+use crate::reader::known_types::KnownType;
+use crate::reader::{JfrReader, Result};
+use std::io::{Read, Seek};
+
+/// A library mapped in the address space of the recorded process
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct NativeLibrary {
+    pub name: String,
+    pub base_address: u64,
+    /// The end of the mapping (exclusive)
+    pub top_address: u64,
+}
+
+impl NativeLibrary {
+    pub fn contains(&self, address: u64) -> bool {
+        self.base_address <= address && address < self.top_address
+    }
+}
+
+/// An address attributed to a library
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct NativeLocation<'a> {
+    pub library: &'a NativeLibrary,
+    /// The offset from the base address of the library
+    pub offset: u64,
+    /// The symbol name given by the [`Symbolizer`]
+    pub symbol: Option<String>,
+}
+
+/// Resolves the symbol name of the offset in the library, e.g. by reading ELF symbol tables
+pub trait Symbolizer {
+    fn symbolize(&self, library: &str, offset: u64) -> Option<String>;
+}
+
+/// Address ranges of the native libraries gathered from all chunks
+#[derive(Debug, Clone, Default)]
+pub struct NativeLibraryMap {
+    // sorted by the base address
+    libraries: Vec<NativeLibrary>,
+}
+
+impl NativeLibraryMap {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Reads all chunks of the recording to gather the libraries
+    pub fn from<T: Read + Seek>(reader: &mut JfrReader<T>) -> Result<Self> {
+        let mut map = Self::new();
+        for chunk in reader.chunks() {
+            let (chunk_reader, chunk) = chunk?;
+            for event in chunk_reader.events(&chunk) {
+                map.update(&event?)?;
+            }
+        }
+        Ok(map)
+    }
+
+    /// Adds the library of `jdk.NativeLibrary` event. Events of other types are ignored
+    pub fn update(&mut self, event: &Event) -> Result<()> {
+        if event.class.known_type() != Some(KnownType::NativeLibrary) {
+            return Ok(());
+        }
+        let e: de::NativeLibrary = from_event(event)?;
+        if let Some(name) = e.name {
+            self.insert(NativeLibrary {
+                name: name.to_string(),
+                base_address: e.base_address as u64,
+                top_address: e.top_address as u64,
+            });
+        }
+        Ok(())
+    }
+
+    /// Adds the library. The library mapped at the same base address is replaced,
+    /// as libraries are reported in every chunk and can be unloaded and reloaded
+    pub fn insert(&mut self, library: NativeLibrary) {
+        match self
+            .libraries
+            .binary_search_by_key(&library.base_address, |l| l.base_address)
+        {
+            Ok(idx) => self.libraries[idx] = library,
+            Err(idx) => self.libraries.insert(idx, library),
+        }
+    }
+
+    /// Returns the library which contains the address
+    pub fn find(&self, address: u64) -> Option<&NativeLibrary> {
+        let idx = self
+            .libraries
+            .partition_point(|l| l.base_address <= address)
+            .checked_sub(1)?;
+        Some(&self.libraries[idx]).filter(|l| l.contains(address))
+    }
+
+    /// Attributes the address to the library and the offset in it
+    pub fn symbolize(&self, address: u64) -> Option<NativeLocation<'_>> {
+        self.find(address).map(|library| NativeLocation {
+            library,
+            offset: address - library.base_address,
+            symbol: None,
+        })
+    }
+
+    /// Same as [`NativeLibraryMap::symbolize`], additionally resolving the symbol name by the symbolizer
+    pub fn symbolize_with(
+        &self,
+        address: u64,
+        symbolizer: &dyn Symbolizer,
+    ) -> Option<NativeLocation<'_>> {
+        self.symbolize(address).map(|mut location| {
+            location.symbol = symbolizer.symbolize(&location.library.name, location.offset);
+            location
+        })
+    }
+
+    pub fn libraries(&self) -> &[NativeLibrary] {
+        &self.libraries
+    }
+}
+
+/// Borrowed representation of the events
+mod de {
+    use serde::Deserialize;
+
+    #[derive(Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    pub struct NativeLibrary<'a> {
+        pub name: Option<&'a str>,
+        pub base_address: i64,
+        pub top_address: i64,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::test_data;
+    use std::fs::File;
+
+    struct FixedSymbolizer;
+
+    impl Symbolizer for FixedSymbolizer {
+        fn symbolize(&self, library: &str, offset: u64) -> Option<String> {
+            library
+                .ends_with("/bin/java")
+                .then(|| format!("main+{:#x}", offset))
+        }
+    }
+
+    #[test]
+    fn test_symbolize() {
+        let mut reader = JfrReader::new(File::open(test_data("profiler-wall.jfr")).unwrap());
+        let map = NativeLibraryMap::from(&mut reader).unwrap();
+        assert_eq!(map.libraries().len(), 20);
+
+        let location = map.symbolize(0xaaaae6d20010).unwrap();
+        assert!(location.library.name.ends_with("/bin/java"));
+        assert_eq!(location.offset, 0x10);
+        assert_eq!(location.symbol, None);
+        assert_eq!(
+            map.symbolize_with(0xaaaae6d20010, &FixedSymbolizer)
+                .unwrap()
+                .symbol
+                .as_deref(),
+            Some("main+0x10")
+        );
+
+        // the top address is exclusive
+        assert!(map
+            .symbolize(0xaaaae6d22000)
+            .is_none_or(|l| !l.library.name.ends_with("/bin/java")));
+        assert!(map.symbolize(0).is_none());
+    }
+
+    #[test]
+    fn test_insert() {
+        let mut map = NativeLibraryMap::new();
+        let lib = |name: &str, base, top| NativeLibrary {
+            name: name.to_string(),
+            base_address: base,
+            top_address: top,
+        };
+        map.insert(lib("b", 0x2000, 0x3000));
+        map.insert(lib("a", 0x1000, 0x1800));
+        map.insert(lib("a2", 0x1000, 0x1900));
+        assert_eq!(map.libraries().len(), 2);
+        assert_eq!(map.find(0x1850).map(|l| l.name.as_str()), Some("a2"));
+        assert_eq!(map.find(0x1950), None);
+        assert_eq!(map.find(0x2fff).map(|l| l.name.as_str()), Some("b"));
+    }
+}
@@ -0,0 +1,226 @@
+//! Native memory usage by the Native Memory Tracking (NMT) categories, from `jdk.NativeMemoryUsage`
+//! and `jdk.NativeMemoryUsageTotal`, to diagnose the growth of the off-heap memory.
+//!
+//! The events are recorded since JDK 20 only if NMT is enabled (e.g. `-XX:NativeMemoryTracking=summary`).
+
+use crate::reader::de::from_event;
+use crate::reader::event::Event;
+use crate::reader::known_types::KnownType;
+use crate::reader::types::jdk;
+use crate::reader::{JfrReader, Result};
+use std::collections::BTreeMap;
+use std::io::{Read, Seek};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NativeMemorySample {
+    /// Nanoseconds since UNIX epoch
+    pub time_nanos: i64,
+    /// In bytes
+    pub reserved: u64,
+    /// In bytes
+    pub committed: u64,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct NativeMemoryUsage {
+    /// Samples by the NMT category (e.g. `Java Heap`, `Class`, `Thread`)
+    pub categories: BTreeMap<String, Vec<NativeMemorySample>>,
+    /// Samples of `jdk.NativeMemoryUsageTotal`
+    pub total: Vec<NativeMemorySample>,
+}
+
+impl NativeMemoryUsage {
+    /// Reads all chunks of the recording to gather the samples sorted by the time.
+    /// Only the events of the native memory types are decoded.
+    pub fn from<T: Read + Seek>(reader: &mut JfrReader<T>) -> Result<Self> {
+        let mut usage = Self::default();
+        for chunk in reader.chunks() {
+            let (chunk_reader, chunk) = chunk?;
+            let events = chunk_reader.events(&chunk).select(|t| {
+                matches!(
+                    t.known_type(),
+                    Some(KnownType::NativeMemoryUsage | KnownType::NativeMemoryUsageTotal)
+                )
+            });
+            for event in events {
+                usage.update(&event?)?;
+            }
+        }
+        for samples in usage.categories.values_mut() {
+            samples.sort_by_key(|s| s.time_nanos);
+        }
+        usage.total.sort_by_key(|s| s.time_nanos);
+        Ok(usage)
+    }
+
+    /// Updates the usage by the event. Events of other types are ignored.
+    ///
+    /// Samples are appended in the order of the events, which are not necessarily ordered by the time.
+    pub fn update(&mut self, event: &Event) -> Result<()> {
+        let time_nanos = || {
+            let ticks = event
+                .value()
+                .get_field("startTime")
+                .and_then(|t| t.as_i64())
+                .unwrap_or(0);
+            event.chunk.header.ticks_to_epoch_nanos(ticks)
+        };
+        match event.class.known_type() {
+            Some(KnownType::NativeMemoryUsage) => {
+                let e: jdk::NativeMemoryUsage = from_event(event)?;
+                self.categories
+                    .entry(e.memory_type.unwrap_or("<unknown>").to_string())
+                    .or_default()
+                    .push(NativeMemorySample {
+                        time_nanos: time_nanos(),
+                        reserved: e.reserved,
+                        committed: e.committed,
+                    });
+            }
+            Some(KnownType::NativeMemoryUsageTotal) => {
+                let e: jdk::NativeMemoryUsageTotal = from_event(event)?;
+                self.total.push(NativeMemorySample {
+                    time_nanos: time_nanos(),
+                    reserved: e.reserved,
+                    committed: e.committed,
+                });
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// Returns the growth of the committed memory from the first sample to the last sample by the category,
+    /// sorted by the growth in descending order.
+    pub fn committed_growth(&self) -> Vec<(&str, i64)> {
+        let mut growth = self
+            .categories
+            .iter()
+            .filter_map(|(category, samples)| {
+                let (first, last) = (samples.first()?, samples.last()?);
+                Some((
+                    category.as_str(),
+                    last.committed as i64 - first.committed as i64,
+                ))
+            })
+            .collect::<Vec<_>>();
+        growth.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(b.0)));
+        growth
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{long, open_test_data, string, TestRecording};
+    use crate::writer::recording::TypeDeclaration;
+    use serde::Serialize;
+    use std::io::Cursor;
+
+    const SECOND: i64 = 1_000_000_000;
+    const MIB: u64 = 1024 * 1024;
+
+    #[derive(Serialize)]
+    #[serde(rename_all = "camelCase")]
+    struct NativeMemoryUsageEvent<'a> {
+        start_time: i64,
+        r#type: &'a str,
+        reserved: u64,
+        committed: u64,
+    }
+
+    #[derive(Serialize)]
+    #[serde(rename_all = "camelCase")]
+    struct NativeMemoryUsageTotalEvent {
+        start_time: i64,
+        reserved: u64,
+        committed: u64,
+    }
+
+    fn recording() -> JfrReader<Cursor<Vec<u8>>> {
+        let mut recording = TestRecording::new([
+            TypeDeclaration::event("jdk.NativeMemoryUsage")
+                .field(string("type"))
+                .field(long("reserved"))
+                .field(long("committed")),
+            TypeDeclaration::event("jdk.NativeMemoryUsageTotal")
+                .field(long("reserved"))
+                .field(long("committed")),
+        ]);
+
+        // written out of order
+        for (start_time, heap, class) in [(SECOND, 256, 24), (0, 256, 16), (2 * SECOND, 192, 40)] {
+            for (r#type, committed) in [("Java Heap", heap), ("Class", class)] {
+                recording.event(
+                    "jdk.NativeMemoryUsage",
+                    &NativeMemoryUsageEvent {
+                        start_time,
+                        r#type,
+                        reserved: 1024 * MIB,
+                        committed: committed * MIB,
+                    },
+                );
+            }
+            recording.event(
+                "jdk.NativeMemoryUsageTotal",
+                &NativeMemoryUsageTotalEvent {
+                    start_time,
+                    reserved: 2048 * MIB,
+                    committed: (heap + class) * MIB,
+                },
+            );
+        }
+        recording.reader()
+    }
+
+    #[test]
+    fn test_native_memory_usage() {
+        let mut reader = recording();
+        let usage = NativeMemoryUsage::from(&mut reader).unwrap();
+
+        assert_eq!(
+            usage.categories.keys().collect::<Vec<_>>(),
+            vec!["Class", "Java Heap"]
+        );
+        assert_eq!(
+            usage.categories["Class"],
+            vec![
+                NativeMemorySample {
+                    time_nanos: 0,
+                    reserved: 1024 * MIB,
+                    committed: 16 * MIB,
+                },
+                NativeMemorySample {
+                    time_nanos: SECOND,
+                    reserved: 1024 * MIB,
+                    committed: 24 * MIB,
+                },
+                NativeMemorySample {
+                    time_nanos: 2 * SECOND,
+                    reserved: 1024 * MIB,
+                    committed: 40 * MIB,
+                },
+            ]
+        );
+        assert_eq!(
+            usage
+                .total
+                .iter()
+                .map(|s| (s.time_nanos, s.committed / MIB))
+                .collect::<Vec<_>>(),
+            vec![(0, 272), (SECOND, 280), (2 * SECOND, 232)]
+        );
+        assert_eq!(
+            usage.committed_growth(),
+            vec![("Class", 24 * MIB as i64), ("Java Heap", -64 * MIB as i64)]
+        );
+    }
+
+    #[test]
+    fn test_no_native_memory_events() {
+        let mut reader = open_test_data("recording.jfr");
+        let usage = NativeMemoryUsage::from(&mut reader).unwrap();
+        assert_eq!(usage, NativeMemoryUsage::default());
+        assert!(usage.committed_growth().is_empty());
+    }
+}
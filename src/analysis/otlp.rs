@@ -0,0 +1,419 @@
+//! Conversion of samples into the OpenTelemetry profiling signal.
+//!
+//! The messages follow the pprof-extended `Profile` of
+//! [opentelemetry-proto](https://github.com/open-telemetry/opentelemetry-proto) v1.5.0
+//! (`opentelemetry.proto.collector.profiles.v1development`), with the field names of the OTLP/JSON encoding.
+//! The signal is still in development, so only the fields needed to describe JFR samples are modeled.
+//! Serialize [`ExportProfilesServiceRequest`] with a serde JSON serializer to send it to an OTLP/HTTP endpoint.
+
+use crate::analysis::frame::{stack_frames, Frame};
+use crate::analysis::info::RecordingInfo;
+use crate::analysis::weight::Weight;
+use crate::reader::event::Event;
+use crate::reader::{JfrReader, Result};
+use rustc_hash::FxHashMap;
+use serde::Serialize;
+use std::io::{Read, Seek};
+
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExportProfilesServiceRequest {
+    pub resource_profiles: Vec<ResourceProfiles>,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ResourceProfiles {
+    pub resource: Resource,
+    pub scope_profiles: Vec<ScopeProfiles>,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Resource {
+    pub attributes: Vec<KeyValue>,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ScopeProfiles {
+    pub scope: InstrumentationScope,
+    pub profiles: Vec<Profile>,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InstrumentationScope {
+    pub name: String,
+    pub version: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct KeyValue {
+    pub key: String,
+    pub value: AnyValue,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum AnyValue {
+    StringValue(String),
+    /// Encoded as a decimal string in OTLP/JSON
+    IntValue(String),
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Profile {
+    pub sample_type: Vec<ValueType>,
+    pub sample: Vec<Sample>,
+    pub location_table: Vec<Location>,
+    pub location_indices: Vec<i32>,
+    pub function_table: Vec<Function>,
+    /// The first string is always empty
+    pub string_table: Vec<String>,
+    pub time_nanos: i64,
+    pub duration_nanos: i64,
+    pub period_type: Option<ValueType>,
+    pub period: i64,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ValueType {
+    pub type_strindex: i32,
+    pub unit_strindex: i32,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Sample {
+    /// The range of `location_indices`, from the leaf
+    pub locations_start_index: i32,
+    pub locations_length: i32,
+    pub value: Vec<i64>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Location {
+    pub line: Vec<Line>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Line {
+    pub function_index: i32,
+    pub line: i64,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Function {
+    pub name_strindex: i32,
+    pub system_name_strindex: i32,
+    pub filename_strindex: i32,
+}
+
+/// Builds a [`Profile`] from stacks, merging identical stacks into one sample
+#[derive(Debug)]
+pub struct ProfileBuilder {
+    profile: Profile,
+    strings: FxHashMap<String, i32>,
+    functions: FxHashMap<(i32, i32), i32>,
+    locations: FxHashMap<(i32, i64), i32>,
+    samples: FxHashMap<Vec<i32>, usize>,
+    min_time_nanos: Option<i64>,
+    max_time_nanos: Option<i64>,
+}
+
+impl ProfileBuilder {
+    /// Creates the builder of the profile whose values are of the type (e.g. `cpu`, `nanoseconds`)
+    pub fn new(sample_type: &str, unit: &str) -> Self {
+        let mut builder = Self {
+            profile: Profile::default(),
+            strings: FxHashMap::default(),
+            functions: FxHashMap::default(),
+            locations: FxHashMap::default(),
+            samples: FxHashMap::default(),
+            min_time_nanos: None,
+            max_time_nanos: None,
+        };
+        builder.string_index("");
+        let value_type = builder.value_type(sample_type, unit);
+        builder.profile.sample_type.push(value_type);
+        builder
+    }
+
+    /// Sets the sampling period (e.g. `cpu`, `nanoseconds`, 20000000)
+    pub fn period(mut self, period_type: &str, unit: &str, period: i64) -> Self {
+        self.profile.period_type = Some(self.value_type(period_type, unit));
+        self.profile.period = period;
+        self
+    }
+
+    /// Adds the stack with the value.
+    /// `frames` are ordered from the top, in the same order as `jdk.types.StackTrace`.
+    pub fn add_stack(&mut self, frames: &[Frame], value: i64) {
+        let locations = frames
+            .iter()
+            .map(|f| self.location_index(f))
+            .collect::<Vec<_>>();
+        if let Some(&idx) = self.samples.get(&locations) {
+            self.profile.sample[idx].value[0] += value;
+            return;
+        }
+        self.samples
+            .insert(locations.clone(), self.profile.sample.len());
+        self.profile.sample.push(Sample {
+            locations_start_index: self.profile.location_indices.len() as i32,
+            locations_length: locations.len() as i32,
+            value: vec![value],
+        });
+        self.profile.location_indices.extend(locations);
+    }
+
+    /// Adds the stack trace of the event weighted by `weight`.
+    /// Returns false if the event has no stack trace or can't be weighted.
+    pub fn add_event(&mut self, event: &Event, weight: &dyn Weight) -> bool {
+        let value = event.value();
+        let (stack_trace, w) = match (value.get_field("stackTrace"), weight.weight(event)) {
+            (Some(s), Some(w)) => (s, w),
+            _ => return false,
+        };
+        if let Some(nanos) = value
+            .get_field("startTime")
+            .and_then(|t| t.field()?.timestamp_nanos(t.as_i64()?, &value.chunk.header))
+        {
+            let nanos = nanos as i64;
+            self.min_time_nanos = Some(self.min_time_nanos.map_or(nanos, |t| t.min(nanos)));
+            self.max_time_nanos = Some(self.max_time_nanos.map_or(nanos, |t| t.max(nanos)));
+        }
+        self.add_stack(&stack_frames(&stack_trace), w as i64);
+        true
+    }
+
+    /// Returns the profile, whose time range covers the start times of the added events
+    pub fn build(mut self) -> Profile {
+        if let (Some(min), Some(max)) = (self.min_time_nanos, self.max_time_nanos) {
+            self.profile.time_nanos = min;
+            self.profile.duration_nanos = max - min;
+        }
+        self.profile
+    }
+
+    fn value_type(&mut self, type_name: &str, unit: &str) -> ValueType {
+        ValueType {
+            type_strindex: self.string_index(type_name),
+            unit_strindex: self.string_index(unit),
+        }
+    }
+
+    fn string_index(&mut self, s: &str) -> i32 {
+        if let Some(&idx) = self.strings.get(s) {
+            return idx;
+        }
+        let idx = self.profile.string_table.len() as i32;
+        self.profile.string_table.push(s.to_string());
+        self.strings.insert(s.to_string(), idx);
+        idx
+    }
+
+    fn location_index(&mut self, frame: &Frame) -> i32 {
+        let name = if frame.class_name.is_empty() {
+            frame.method_name.clone()
+        } else {
+            format!("{}.{}", frame.class_name, frame.method_name)
+        };
+        let name_idx = self.string_index(&name);
+        let system_name_idx = match &frame.descriptor {
+            Some(descriptor) => self.string_index(&format!("{}{}", name, descriptor)),
+            None => name_idx,
+        };
+        let function_index = match self.functions.get(&(name_idx, system_name_idx)) {
+            Some(&idx) => idx,
+            None => {
+                let idx = self.profile.function_table.len() as i32;
+                self.profile.function_table.push(Function {
+                    name_strindex: name_idx,
+                    system_name_strindex: system_name_idx,
+                    filename_strindex: 0,
+                });
+                self.functions.insert((name_idx, system_name_idx), idx);
+                idx
+            }
+        };
+
+        let line = frame.line_number.max(0) as i64;
+        if let Some(&idx) = self.locations.get(&(function_index, line)) {
+            return idx;
+        }
+        let idx = self.profile.location_table.len() as i32;
+        self.profile.location_table.push(Location {
+            line: vec![Line {
+                function_index,
+                line,
+            }],
+        });
+        self.locations.insert((function_index, line), idx);
+        idx
+    }
+}
+
+/// Resource attributes of the recorded process, following the semantic conventions
+/// (e.g. `process.pid`, `process.runtime.name`)
+pub fn resource_attributes(info: &RecordingInfo) -> Vec<KeyValue> {
+    let mut attributes = vec![];
+    let mut string = |key: &str, value: &Option<String>| {
+        if let Some(v) = value {
+            attributes.push(KeyValue {
+                key: key.to_string(),
+                value: AnyValue::StringValue(v.clone()),
+            });
+        }
+    };
+    if let Some(jvm) = &info.jvm {
+        string("process.runtime.name", &jvm.jvm_name);
+        string("process.runtime.version", &jvm.jvm_version);
+        string("process.command_line", &jvm.java_arguments);
+    }
+    string("os.description", &info.os_version);
+    if let Some(jvm) = &info.jvm {
+        attributes.push(KeyValue {
+            key: "process.pid".to_string(),
+            value: AnyValue::IntValue(jvm.pid.to_string()),
+        });
+    }
+    attributes
+}
+
+/// Reads all chunks of the recording, converting the events of the type into a profile
+/// with the resource attributes taken from the JVM information events
+pub fn export<T: Read + Seek>(
+    reader: &mut JfrReader<T>,
+    event_type: &str,
+    weight: &dyn Weight,
+    builder: ProfileBuilder,
+) -> Result<ExportProfilesServiceRequest> {
+    let mut builder = builder;
+    let mut info = RecordingInfo::default();
+    for chunk in reader.chunks() {
+        let (chunk_reader, chunk) = chunk?;
+        for event in chunk_reader.events(&chunk) {
+            let event = event?;
+            if event.class.name() == event_type {
+                builder.add_event(&event, weight);
+            } else {
+                info.update(&event)?;
+            }
+        }
+    }
+
+    Ok(ExportProfilesServiceRequest {
+        resource_profiles: vec![ResourceProfiles {
+            resource: Resource {
+                attributes: resource_attributes(&info),
+            },
+            scope_profiles: vec![ScopeProfiles {
+                scope: InstrumentationScope {
+                    name: env!("CARGO_PKG_NAME").to_string(),
+                    version: env!("CARGO_PKG_VERSION").to_string(),
+                },
+                profiles: vec![builder.build()],
+            }],
+        }],
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analysis::weight::SamplingInterval;
+    use crate::test_util::test_data;
+    use std::fs::File;
+
+    #[test]
+    fn test_export() {
+        let mut reader = JfrReader::new(File::open(test_data("profiler-wall.jfr")).unwrap());
+        let builder =
+            ProfileBuilder::new("wall", "nanoseconds").period("wall", "nanoseconds", 10_000_000);
+        let request = export(
+            &mut reader,
+            "jdk.ExecutionSample",
+            &SamplingInterval::new(10_000_000),
+            builder,
+        )
+        .unwrap();
+
+        let resource = &request.resource_profiles[0];
+        assert!(resource.resource.attributes.contains(&KeyValue {
+            key: "process.pid".to_string(),
+            value: AnyValue::IntValue("3741".to_string()),
+        }));
+        assert!(resource.resource.attributes.contains(&KeyValue {
+            key: "process.runtime.version".to_string(),
+            value: AnyValue::StringValue("11.0.16+8".to_string()),
+        }));
+
+        let profile = &resource.scope_profiles[0].profiles[0];
+        assert_eq!(profile.string_table[0], "");
+        let total = profile.sample.iter().map(|s| s.value[0]).sum::<i64>();
+        assert_eq!(total, 8836 * 10_000_000);
+        assert!(profile.sample.len() < 8836);
+        assert!(profile.time_nanos > 0 && profile.duration_nanos > 0);
+
+        // all indices point into the tables
+        for sample in profile.sample.iter() {
+            let start = sample.locations_start_index as usize;
+            let end = start + sample.locations_length as usize;
+            for &idx in &profile.location_indices[start..end] {
+                let location = &profile.location_table[idx as usize];
+                let function = &profile.function_table[location.line[0].function_index as usize];
+                assert!(!profile.string_table[function.name_strindex as usize].is_empty());
+            }
+        }
+        assert_eq!(
+            profile.string_table[profile.sample_type[0].type_strindex as usize],
+            "wall"
+        );
+    }
+
+    #[test]
+    fn test_add_stack() {
+        let frame = |method: &str, line| Frame {
+            class_name: "Foo".to_string(),
+            method_name: method.to_string(),
+            descriptor: Some("()V".to_string()),
+            line_number: line,
+            frame_type: None,
+        };
+        let mut builder = ProfileBuilder::new("samples", "count");
+        builder.add_stack(&[frame("a", 1), frame("main", 10)], 1);
+        builder.add_stack(&[frame("b", 2), frame("main", 10)], 1);
+        builder.add_stack(&[frame("a", 1), frame("main", 10)], 2);
+        let profile = builder.build();
+
+        assert_eq!(profile.sample.len(), 2);
+        assert_eq!(profile.sample[0].value, vec![3]);
+        assert_eq!(profile.location_table.len(), 3);
+        assert_eq!(profile.function_table.len(), 3);
+        assert_eq!(profile.location_indices, vec![0, 1, 2, 1]);
+        let function = profile.function_table[0];
+        assert_eq!(
+            profile.string_table[function.name_strindex as usize],
+            "Foo.a"
+        );
+        assert_eq!(
+            profile.string_table[function.system_name_strindex as usize],
+            "Foo.a()V"
+        );
+
+        let json = serde_json::to_value(&profile).unwrap();
+        assert_eq!(json["sample"][0]["locationsLength"], 2);
+        assert_eq!(
+            json["functionTable"][0]["nameStrindex"],
+            function.name_strindex
+        );
+    }
+}
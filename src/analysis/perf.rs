@@ -0,0 +1,256 @@
+//! Output of samples in the textual format of `perf script`.
+//!
+//! Each sample is written as a header line (`comm pid/tid time: period event:`) followed by
+//! the frames from the top, and separated by an empty line. Tools which consume `perf script`
+//! (e.g. `stackcollapse-perf.pl` of FlameGraph) can process the output directly.
+//!
+//! Java frames are attributed to `perf-<pid>.map` as the JIT symbol map would do,
+//! so that the Java annotation of FlameGraph works.
+
+use crate::analysis::frame::format::{format_frame, FormatOptions, SignatureStyle};
+use crate::analysis::frame::{stack_frames, Frame};
+use crate::analysis::weight::Weight;
+use crate::reader::event::{Accessor, Event};
+use crate::reader::known_types::KnownType;
+use crate::reader::{Error, JfrReader, Result};
+use std::io::{Read, Seek, Write};
+
+const UNKNOWN_DSO: &str = "[unknown]";
+const KERNEL_DSO: &str = "[kernel.kallsyms]";
+
+/// Writes events in `perf script` format
+#[derive(Debug)]
+pub struct PerfScriptWriter<W: Write> {
+    out: W,
+    pid: Option<i64>,
+    format_options: FormatOptions,
+}
+
+impl<W: Write> PerfScriptWriter<W> {
+    pub fn new(out: W) -> Self {
+        Self {
+            out,
+            pid: None,
+            format_options: FormatOptions::default().signature_style(SignatureStyle::Omitted),
+        }
+    }
+
+    /// Sets the pid of the samples.
+    /// If not set, [`PerfScriptWriter::write_recording`] takes it from `jdk.JVMInformation`
+    pub fn pid(mut self, pid: i64) -> Self {
+        self.pid = Some(pid);
+        self
+    }
+
+    /// Sets how the frames are rendered. The signature is omitted by default
+    pub fn format_options(mut self, options: FormatOptions) -> Self {
+        self.format_options = options;
+        self
+    }
+
+    /// Writes the events of the type in all chunks of the recording.
+    /// Returns the number of written samples
+    pub fn write_recording<T: Read + Seek>(
+        &mut self,
+        reader: &mut JfrReader<T>,
+        event_type: &str,
+        weight: &dyn Weight,
+    ) -> Result<usize> {
+        let mut count = 0;
+        for chunk in reader.chunks() {
+            let (chunk_reader, chunk) = chunk?;
+            if self.pid.is_none() {
+                if let Some(class_id) = chunk
+                    .metadata
+                    .type_pool
+                    .class_id_of(KnownType::JVMInformation)
+                {
+                    for event in chunk_reader
+                        .events(&chunk)
+                        .select(|t| t.class_id == class_id)
+                    {
+                        self.pid = event?.value().get_field("pid").and_then(|v| v.as_i64());
+                        if self.pid.is_some() {
+                            break;
+                        }
+                    }
+                }
+            }
+            for event in chunk_reader.events(&chunk) {
+                let event = event?;
+                if event.class.name() == event_type && self.write_event(&event, weight)? {
+                    count += 1;
+                }
+            }
+        }
+        self.out.flush().map_err(Error::IoError)?;
+        Ok(count)
+    }
+
+    /// Writes the sample of the event weighted by `weight`.
+    /// Returns false if the event has no stack trace or can't be weighted
+    pub fn write_event(&mut self, event: &Event, weight: &dyn Weight) -> Result<bool> {
+        let value = event.value();
+        let (stack_trace, period) = match (value.get_field("stackTrace"), weight.weight(event)) {
+            (Some(s), Some(w)) => (s, w),
+            _ => return Ok(false),
+        };
+        let thread = value
+            .get_field("sampledThread")
+            .or_else(|| value.get_field("eventThread"));
+        let tid = thread
+            .as_ref()
+            .and_then(|t| t.get_field("osThreadId"))
+            .and_then(|v| v.as_i64())
+            .unwrap_or(0);
+        let comm = thread.as_ref().and_then(thread_name);
+        let nanos = value
+            .get_field("startTime")
+            .and_then(|t| t.field()?.timestamp_nanos(t.as_i64()?, &value.chunk.header))
+            .unwrap_or(0);
+
+        let frames = stack_frames(&stack_trace);
+        self.write_sample(
+            comm.as_deref().unwrap_or("java"),
+            tid,
+            nanos as i64,
+            period,
+            event.class.name(),
+            &frames,
+        )?;
+        Ok(true)
+    }
+
+    /// Writes the sample. `frames` are ordered from the top
+    pub fn write_sample(
+        &mut self,
+        comm: &str,
+        tid: i64,
+        time_nanos: i64,
+        period: u64,
+        event_name: &str,
+        frames: &[Frame],
+    ) -> Result<()> {
+        let pid = self.pid.unwrap_or(0);
+        let java_dso = format!("perf-{}.map", pid);
+        let mut buf = format!(
+            "{} {}/{} {}.{:06}: {} {}:\n",
+            sanitize_comm(comm),
+            pid,
+            tid,
+            time_nanos.div_euclid(1_000_000_000),
+            time_nanos.rem_euclid(1_000_000_000) / 1000,
+            period,
+            event_name
+        );
+        for frame in frames {
+            let dso = match frame.frame_type.as_deref() {
+                Some("Kernel") => KERNEL_DSO,
+                Some("Native") | Some("C++") => UNKNOWN_DSO,
+                _ if frame.class_name.is_empty() => UNKNOWN_DSO,
+                _ => &java_dso,
+            };
+            buf.push_str(&format!(
+                "\t{:16x} {} ({})\n",
+                0,
+                format_frame(frame, &self.format_options),
+                dso
+            ));
+        }
+        buf.push('\n');
+        self.out.write_all(buf.as_bytes()).map_err(Error::IoError)
+    }
+
+    pub fn into_inner(self) -> W {
+        self.out
+    }
+}
+
+fn thread_name(thread: &Accessor) -> Option<String> {
+    ["javaName", "osName"].iter().find_map(|name| {
+        thread
+            .get_field(name)
+            .and_then(|v| v.as_str())
+            .filter(|s| !s.is_empty())
+            .map(String::from)
+    })
+}
+
+/// Replaces whitespaces, since perf script parsers split the header by whitespaces
+fn sanitize_comm(comm: &str) -> String {
+    comm.chars()
+        .map(|c| if c.is_whitespace() { '_' } else { c })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analysis::weight::SampleCount;
+    use crate::test_util::test_data;
+    use std::fs::File;
+
+    #[test]
+    fn test_write_recording() {
+        let mut reader = JfrReader::new(File::open(test_data("profiler-wall.jfr")).unwrap());
+        let mut writer = PerfScriptWriter::new(vec![]);
+        let count = writer
+            .write_recording(&mut reader, "jdk.ExecutionSample", &SampleCount)
+            .unwrap();
+        assert_eq!(count, 8836);
+
+        let out = String::from_utf8(writer.into_inner()).unwrap();
+        let samples = out
+            .split("\n\n")
+            .filter(|s| !s.is_empty())
+            .collect::<Vec<_>>();
+        assert_eq!(samples.len(), 8836);
+        for sample in samples {
+            let mut lines = sample.lines();
+            let header = lines.next().unwrap();
+            let fields = header.split(' ').collect::<Vec<_>>();
+            assert_eq!(fields.len(), 5, "{}", header);
+            assert!(fields[1].starts_with("3741/"));
+            assert_eq!(fields[3], "1");
+            assert_eq!(fields[4], "jdk.ExecutionSample:");
+            for frame in lines {
+                assert!(frame.starts_with('\t'));
+                assert!(frame.ends_with(')'));
+            }
+        }
+        assert!(out.contains(" (perf-3741.map)\n"));
+    }
+
+    #[test]
+    fn test_write_sample() {
+        let frame = |class: &str, method: &str, frame_type: &str| Frame {
+            class_name: class.to_string(),
+            method_name: method.to_string(),
+            descriptor: Some("()V".to_string()),
+            line_number: 10,
+            frame_type: Some(frame_type.to_string()),
+        };
+        let mut writer = PerfScriptWriter::new(vec![]).pid(42);
+        writer
+            .write_sample(
+                "main thread",
+                43,
+                1_661_595_162_007_123_456,
+                20_000_000,
+                "cpu",
+                &[
+                    frame("", "do_syscall_64", "Kernel"),
+                    frame("", "write", "Native"),
+                    frame("com.example.Foo", "run", "JIT compiled"),
+                ],
+            )
+            .unwrap();
+        assert_eq!(
+            String::from_utf8(writer.into_inner()).unwrap(),
+            "main_thread 42/43 1661595162.007123: 20000000 cpu:\n\
+             \t               0 do_syscall_64 ([kernel.kallsyms])\n\
+             \t               0 write ([unknown])\n\
+             \t               0 com.example.Foo.run (perf-42.map)\n\n"
+        );
+    }
+}
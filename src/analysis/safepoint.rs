@@ -0,0 +1,420 @@
+//! Aggregation of `jdk.SafepointBegin`, `jdk.SafepointEnd` and `jdk.ExecuteVMOperation` events
+//! into safepoint pauses and the VM operations causing them, to investigate the latency spikes.
+
+use crate::reader::event::{Accessor, Event};
+use crate::reader::known_types::KnownType;
+use crate::reader::{JfrReader, Result};
+use std::collections::HashMap;
+use std::io::{Read, Seek};
+use std::time::Duration;
+
+/// Single safepoint pause, during which all Java threads are stopped
+#[derive(Debug, Clone, PartialEq)]
+pub struct SafepointPause {
+    /// The safepoint id, which is unique in the JVM
+    pub id: i64,
+    /// The beginning of the synchronization in nanoseconds since UNIX epoch
+    pub start_nanos: i64,
+    /// From the beginning of the synchronization to the end of the safepoint
+    pub duration: Duration,
+    /// Time to reach the safepoint, i.e. the duration of `jdk.SafepointBegin`
+    pub time_to_safepoint: Duration,
+    pub total_thread_count: u32,
+    pub jni_critical_thread_count: u32,
+    /// VM operations executed in the safepoint in the order of the events
+    pub operations: Vec<String>,
+}
+
+/// Executions of the VM operation
+#[derive(Debug, Clone, PartialEq)]
+pub struct VmOperationSummary {
+    /// e.g. `G1CollectForAllocation`, `HandshakeAllThreads`
+    pub name: String,
+    pub count: u64,
+    /// Number of the executions which required a safepoint
+    pub safepoint_count: u64,
+    pub total_duration: Duration,
+    pub max_duration: Duration,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SafepointReport {
+    /// Sorted by the start time
+    pub pauses: Vec<SafepointPause>,
+    /// Sorted by the total duration in descending order
+    pub operations: Vec<VmOperationSummary>,
+}
+
+impl SafepointReport {
+    pub fn total_pause(&self) -> Duration {
+        self.pauses.iter().map(|p| p.duration).sum()
+    }
+
+    pub fn max_pause(&self) -> Option<&SafepointPause> {
+        self.pauses.iter().max_by_key(|p| p.duration)
+    }
+}
+
+#[derive(Debug)]
+struct Begin {
+    start_nanos: i64,
+    time_to_safepoint: Duration,
+    total_thread_count: u32,
+    jni_critical_thread_count: u32,
+}
+
+/// Aggregates the safepoint events.
+///
+/// Begin and end events are matched by the safepoint id, so the events can be added in any order.
+/// Safepoints whose begin or end is not recorded (e.g. at the end of the recording) are not reported as pauses.
+#[derive(Debug, Default)]
+pub struct SafepointAggregator {
+    begins: HashMap<i64, Begin>,
+    /// End of the safepoint in nanoseconds since UNIX epoch
+    ends: HashMap<i64, i64>,
+    safepoint_operations: HashMap<i64, Vec<String>>,
+    operations: HashMap<String, VmOperationSummary>,
+}
+
+impl SafepointAggregator {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds the event. Returns false if the event is not a safepoint event
+    pub fn add(&mut self, event: &Event) -> bool {
+        let value = event.value();
+        let header = &event.chunk.header;
+        let start_nanos = header.ticks_to_epoch_nanos(i64_field(&value, "startTime").unwrap_or(0));
+        let duration_nanos = i64_field(&value, "duration")
+            .map(|d| header.ticks_to_nanos(d))
+            .unwrap_or(0);
+        let duration = Duration::from_nanos(duration_nanos.max(0) as u64);
+        let safepoint_id = i64_field(&value, "safepointId");
+
+        match event.class.known_type() {
+            Some(KnownType::SafepointBegin) => {
+                if let Some(id) = safepoint_id {
+                    self.begins.insert(
+                        id,
+                        Begin {
+                            start_nanos,
+                            time_to_safepoint: duration,
+                            total_thread_count: u32_field(&value, "totalThreadCount"),
+                            jni_critical_thread_count: u32_field(&value, "jniCriticalThreadCount"),
+                        },
+                    );
+                }
+            }
+            Some(KnownType::SafepointEnd) => {
+                if let Some(id) = safepoint_id {
+                    self.ends.insert(id, start_nanos + duration_nanos.max(0));
+                }
+            }
+            Some(KnownType::ExecuteVMOperation) => {
+                let name = value
+                    .get_field("operation")
+                    .and_then(|o| o.get_field("type"))
+                    .and_then(|t| t.as_str())
+                    .unwrap_or("<unknown>");
+                let at_safepoint = value
+                    .get_field("safepoint")
+                    .and_then(|s| s.as_bool())
+                    .unwrap_or(false);
+
+                let summary = self
+                    .operations
+                    .entry(name.to_string())
+                    .or_insert_with_key(|name| VmOperationSummary {
+                        name: name.clone(),
+                        count: 0,
+                        safepoint_count: 0,
+                        total_duration: Duration::ZERO,
+                        max_duration: Duration::ZERO,
+                    });
+                summary.count += 1;
+                summary.total_duration += duration;
+                summary.max_duration = summary.max_duration.max(duration);
+                if at_safepoint {
+                    summary.safepoint_count += 1;
+                    if let Some(id) = safepoint_id {
+                        self.safepoint_operations
+                            .entry(id)
+                            .or_default()
+                            .push(name.to_string());
+                    }
+                }
+            }
+            _ => return false,
+        }
+        true
+    }
+
+    /// Adds the safepoint events in all chunks
+    pub fn add_all<T: Read + Seek>(&mut self, reader: &mut JfrReader<T>) -> Result<()> {
+        for chunk in reader.chunks() {
+            let (chunk_reader, chunk) = chunk?;
+            for event in chunk_reader.events(&chunk) {
+                self.add(&event?);
+            }
+        }
+        Ok(())
+    }
+
+    pub fn finish(mut self) -> SafepointReport {
+        let mut pauses = self
+            .begins
+            .into_iter()
+            .filter_map(|(id, begin)| {
+                let end_nanos = self.ends.get(&id)?;
+                Some(SafepointPause {
+                    id,
+                    start_nanos: begin.start_nanos,
+                    duration: Duration::from_nanos((end_nanos - begin.start_nanos).max(0) as u64),
+                    time_to_safepoint: begin.time_to_safepoint,
+                    total_thread_count: begin.total_thread_count,
+                    jni_critical_thread_count: begin.jni_critical_thread_count,
+                    operations: self.safepoint_operations.remove(&id).unwrap_or_default(),
+                })
+            })
+            .collect::<Vec<_>>();
+        pauses.sort_by_key(|p| (p.start_nanos, p.id));
+
+        let mut operations = self.operations.into_values().collect::<Vec<_>>();
+        operations.sort_by(|a, b| {
+            b.total_duration
+                .cmp(&a.total_duration)
+                .then_with(|| a.name.cmp(&b.name))
+        });
+        SafepointReport { pauses, operations }
+    }
+}
+
+fn i64_field(accessor: &Accessor, name: &str) -> Option<i64> {
+    accessor.get_field(name).and_then(|v| v.as_i64())
+}
+
+fn u32_field(accessor: &Accessor, name: &str) -> u32 {
+    accessor
+        .get_field(name)
+        .and_then(|v| v.as_u32())
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{constant, int, long, open_test_data, string, TestRecording};
+    use crate::writer::recording::{FieldDeclaration, TypeDeclaration};
+    use serde::Serialize;
+    use std::io::Cursor;
+
+    const MILLI: i64 = 1_000_000;
+
+    #[derive(Serialize)]
+    struct VmOperationType<'a> {
+        r#type: &'a str,
+    }
+
+    #[derive(Serialize)]
+    #[serde(rename_all = "camelCase")]
+    struct SafepointBegin {
+        start_time: i64,
+        duration: i64,
+        safepoint_id: i64,
+        total_thread_count: i32,
+        jni_critical_thread_count: i32,
+    }
+
+    #[derive(Serialize)]
+    #[serde(rename_all = "camelCase")]
+    struct SafepointEnd {
+        start_time: i64,
+        duration: i64,
+        safepoint_id: i64,
+    }
+
+    #[derive(Serialize)]
+    #[serde(rename_all = "camelCase")]
+    struct ExecuteVmOperation {
+        start_time: i64,
+        duration: i64,
+        operation: i64,
+        safepoint: bool,
+        safepoint_id: i64,
+    }
+
+    fn recording() -> JfrReader<Cursor<Vec<u8>>> {
+        let mut recording = TestRecording::new([
+            TypeDeclaration::new("jdk.types.VMOperationType").field(string("type")),
+            TypeDeclaration::event("jdk.SafepointBegin")
+                .field(long("duration"))
+                .field(long("safepointId"))
+                .field(int("totalThreadCount"))
+                .field(int("jniCriticalThreadCount")),
+            TypeDeclaration::event("jdk.SafepointEnd")
+                .field(long("duration"))
+                .field(long("safepointId")),
+            TypeDeclaration::event("jdk.ExecuteVMOperation")
+                .field(long("duration"))
+                .field(constant("operation", "jdk.types.VMOperationType"))
+                .field(FieldDeclaration::new("safepoint", "boolean"))
+                .field(long("safepointId")),
+        ]);
+        for (index, r#type) in ["G1CollectForAllocation", "Cleanup", "HandshakeAllThreads"]
+            .iter()
+            .enumerate()
+        {
+            recording.constant(
+                "jdk.types.VMOperationType",
+                index as i64 + 1,
+                &VmOperationType { r#type },
+            );
+        }
+
+        // (id, start, time to safepoint, operations (type, duration), end)
+        let safepoints = [
+            (1, 0, 2 * MILLI, vec![(1, 30 * MILLI)], 35 * MILLI),
+            (
+                2,
+                100 * MILLI,
+                MILLI,
+                vec![(2, MILLI), (1, 10 * MILLI)],
+                115 * MILLI,
+            ),
+        ];
+        for (safepoint_id, start, time_to_safepoint, operations, end) in safepoints {
+            recording.event(
+                "jdk.SafepointBegin",
+                &SafepointBegin {
+                    start_time: start,
+                    duration: time_to_safepoint,
+                    safepoint_id,
+                    total_thread_count: 20,
+                    jni_critical_thread_count: 1,
+                },
+            );
+            let mut time = start + time_to_safepoint;
+            for (operation, duration) in operations {
+                recording.event(
+                    "jdk.ExecuteVMOperation",
+                    &ExecuteVmOperation {
+                        start_time: time,
+                        duration,
+                        operation,
+                        safepoint: true,
+                        safepoint_id,
+                    },
+                );
+                time += duration;
+            }
+            recording.event(
+                "jdk.SafepointEnd",
+                &SafepointEnd {
+                    start_time: time,
+                    duration: end - time,
+                    safepoint_id,
+                },
+            );
+        }
+        recording
+            // handshakes don't need a safepoint
+            .event(
+                "jdk.ExecuteVMOperation",
+                &ExecuteVmOperation {
+                    start_time: 200 * MILLI,
+                    duration: 5 * MILLI,
+                    operation: 3,
+                    safepoint: false,
+                    safepoint_id: 0,
+                },
+            )
+            // the safepoint is not finished at the end of the recording
+            .event(
+                "jdk.SafepointBegin",
+                &SafepointBegin {
+                    start_time: 300 * MILLI,
+                    duration: MILLI,
+                    safepoint_id: 3,
+                    total_thread_count: 20,
+                    jni_critical_thread_count: 0,
+                },
+            );
+        recording.reader()
+    }
+
+    #[test]
+    fn test_aggregate() {
+        let mut reader = recording();
+        let mut aggregator = SafepointAggregator::new();
+        aggregator.add_all(&mut reader).unwrap();
+        let report = aggregator.finish();
+
+        let millis = |ms: u64| Duration::from_millis(ms);
+        assert_eq!(
+            report.pauses,
+            vec![
+                SafepointPause {
+                    id: 1,
+                    start_nanos: 0,
+                    duration: millis(35),
+                    time_to_safepoint: millis(2),
+                    total_thread_count: 20,
+                    jni_critical_thread_count: 1,
+                    operations: vec!["G1CollectForAllocation".to_string()],
+                },
+                SafepointPause {
+                    id: 2,
+                    start_nanos: 100 * MILLI,
+                    duration: millis(15),
+                    time_to_safepoint: millis(1),
+                    total_thread_count: 20,
+                    jni_critical_thread_count: 1,
+                    operations: vec!["Cleanup".to_string(), "G1CollectForAllocation".to_string()],
+                },
+            ]
+        );
+        assert_eq!(report.total_pause(), millis(50));
+        assert_eq!(report.max_pause().map(|p| p.id), Some(1));
+
+        assert_eq!(
+            report.operations,
+            vec![
+                VmOperationSummary {
+                    name: "G1CollectForAllocation".to_string(),
+                    count: 2,
+                    safepoint_count: 2,
+                    total_duration: millis(40),
+                    max_duration: millis(30),
+                },
+                VmOperationSummary {
+                    name: "HandshakeAllThreads".to_string(),
+                    count: 1,
+                    safepoint_count: 0,
+                    total_duration: millis(5),
+                    max_duration: millis(5),
+                },
+                VmOperationSummary {
+                    name: "Cleanup".to_string(),
+                    count: 1,
+                    safepoint_count: 1,
+                    total_duration: millis(1),
+                    max_duration: millis(1),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_no_safepoint_events() {
+        let mut reader = open_test_data("recording.jfr");
+        let mut aggregator = SafepointAggregator::new();
+        aggregator.add_all(&mut reader).unwrap();
+        let report = aggregator.finish();
+
+        assert!(report.pauses.is_empty());
+        assert!(report.operations.is_empty());
+        assert_eq!(report.total_pause(), Duration::ZERO);
+        assert!(report.max_pause().is_none());
+    }
+}
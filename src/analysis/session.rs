@@ -0,0 +1,198 @@
+//! Recordings which were running in the JVM, from `jdk.ActiveRecording` and `jdk.ActiveSetting` events.
+//!
+//! A JVM can run multiple recordings at the same time (e.g. a continuous recording and
+//! a profiling session started by a tool), and the file contains the events of all of them.
+
+use crate::reader::de::from_event;
+use crate::reader::event::Event;
+use crate::reader::known_types::KnownType;
+use crate::reader::{JfrReader, Result};
+use std::collections::BTreeMap;
+use std::io::{Read, Seek};
+use std::time::{Duration, SystemTime};
+
+/// A recording parsed from `jdk.ActiveRecording` event
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RecordingSession {
+    pub id: i64,
+    pub name: Option<String>,
+    /// The file the recording is written to
+    pub destination: Option<String>,
+    pub start: Option<SystemTime>,
+    /// None if the recording runs until stopped
+    pub duration: Option<Duration>,
+    /// None if the size is unlimited
+    pub max_size: Option<u64>,
+    /// None if the age is unlimited
+    pub max_age: Option<Duration>,
+}
+
+/// Recordings and the settings in effect gathered from all chunks.
+///
+/// These events are emitted at the beginning of each chunk.
+/// Values in the later chunk take precedence.
+#[derive(Debug, Clone, Default)]
+pub struct RecordingSessions {
+    /// Sorted by the recording id
+    pub sessions: Vec<RecordingSession>,
+    /// Event type name to the settings (e.g. `jdk.ExecutionSample` => `period` => `20 ms`).
+    /// JFR merges the settings of the concurrent recordings, so these are not per recording.
+    pub settings: BTreeMap<String, BTreeMap<String, String>>,
+}
+
+impl RecordingSessions {
+    /// Reads all chunks of the recording to gather the sessions
+    pub fn from<T: Read + Seek>(reader: &mut JfrReader<T>) -> Result<Self> {
+        let mut sessions = Self::default();
+        for chunk in reader.chunks() {
+            let (chunk_reader, chunk) = chunk?;
+            for event in chunk_reader.events(&chunk) {
+                sessions.update(&event?)?;
+            }
+        }
+        Ok(sessions)
+    }
+
+    /// Updates the sessions by the event. Events of other types are ignored
+    pub fn update(&mut self, event: &Event) -> Result<()> {
+        match event.class.known_type() {
+            Some(KnownType::ActiveRecording) => {
+                let e: de::ActiveRecording = from_event(event)?;
+                let session = RecordingSession {
+                    id: e.id,
+                    name: e.name.map(String::from),
+                    destination: e.destination.map(String::from),
+                    start: e.recording_start,
+                    duration: millis(e.recording_duration),
+                    max_size: e.max_size.filter(|&v| v > 0).map(|v| v as u64),
+                    max_age: millis(e.max_age),
+                };
+                match self.sessions.binary_search_by_key(&e.id, |s| s.id) {
+                    Ok(idx) => self.sessions[idx] = session,
+                    Err(idx) => self.sessions.insert(idx, session),
+                }
+            }
+            Some(KnownType::ActiveSetting) => {
+                let e: de::ActiveSetting = from_event(event)?;
+                let type_name = match event.value().chunk.metadata.type_pool.get(e.id) {
+                    Some(t) => t.name(),
+                    None => return Ok(()),
+                };
+                if let (Some(name), Some(value)) = (e.name, e.value) {
+                    self.settings
+                        .entry(type_name.to_string())
+                        .or_default()
+                        .insert(name.to_string(), value.to_string());
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    pub fn get(&self, id: i64) -> Option<&RecordingSession> {
+        self.sessions
+            .binary_search_by_key(&id, |s| s.id)
+            .ok()
+            .map(|idx| &self.sessions[idx])
+    }
+
+    /// Returns the setting of the event type (e.g. `jdk.ExecutionSample`, `period`)
+    pub fn setting(&self, type_name: &str, name: &str) -> Option<&str> {
+        self.settings
+            .get(type_name)
+            .and_then(|s| s.get(name))
+            .map(|s| s.as_str())
+    }
+}
+
+/// JFR uses 0 or `Long.MAX_VALUE` for unlimited durations
+fn millis(v: Option<i64>) -> Option<Duration> {
+    match v? {
+        v if v <= 0 || v == i64::MAX => None,
+        v => Some(Duration::from_millis(v as u64)),
+    }
+}
+
+/// Borrowed representation of the events
+mod de {
+    use serde::Deserialize;
+    use std::time::SystemTime;
+
+    #[derive(Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    pub struct ActiveRecording<'a> {
+        pub id: i64,
+        pub name: Option<&'a str>,
+        pub destination: Option<&'a str>,
+        #[serde(default)]
+        pub max_age: Option<i64>,
+        #[serde(default)]
+        pub max_size: Option<i64>,
+        #[serde(default, with = "crate::reader::de::timestamp::option")]
+        pub recording_start: Option<SystemTime>,
+        #[serde(default)]
+        pub recording_duration: Option<i64>,
+    }
+
+    #[derive(Deserialize)]
+    pub struct ActiveSetting<'a> {
+        pub id: i64,
+        pub name: Option<&'a str>,
+        pub value: Option<&'a str>,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::test_data;
+    use std::fs::File;
+
+    #[test]
+    fn test_sessions() {
+        let mut reader = JfrReader::new(File::open(test_data("profiler-multichunk.jfr")).unwrap());
+        let sessions = RecordingSessions::from(&mut reader).unwrap();
+
+        // the same recording is reported in every chunk
+        assert_eq!(sessions.sessions.len(), 1);
+        let session = sessions.get(1).unwrap();
+        assert_eq!(session.name.as_deref(), Some("async-profiler 2.8.3"));
+        assert_eq!(session.destination.as_deref(), Some("async-profiler.jfr"));
+        assert_eq!(session.duration, None);
+        assert_eq!(session.max_size, None);
+        assert_eq!(session.max_age, None);
+        assert!(session.start.unwrap() > SystemTime::UNIX_EPOCH + Duration::from_secs(1661595223));
+    }
+
+    #[test]
+    fn test_settings() {
+        let mut reader = JfrReader::new(File::open(test_data("recording.jfr")).unwrap());
+        let sessions = RecordingSessions::from(&mut reader).unwrap();
+
+        let session = sessions.get(1).unwrap();
+        assert_eq!(session.name.as_deref(), Some("1"));
+        assert_eq!(session.duration, Some(Duration::from_secs(15)));
+        assert_eq!(
+            session.start,
+            Some(SystemTime::UNIX_EPOCH + Duration::from_millis(1661595162007))
+        );
+        assert_eq!(
+            sessions.setting("jdk.ExecutionSample", "period"),
+            Some("20 ms")
+        );
+        assert_eq!(
+            sessions.setting("jdk.ObjectAllocationInNewTLAB", "enabled"),
+            Some("false")
+        );
+        assert_eq!(sessions.setting("jdk.ExecutionSample", "no-such"), None);
+    }
+
+    #[test]
+    fn test_millis() {
+        assert_eq!(millis(None), None);
+        assert_eq!(millis(Some(0)), None);
+        assert_eq!(millis(Some(i64::MAX)), None);
+        assert_eq!(millis(Some(1500)), Some(Duration::from_millis(1500)));
+    }
+}
@@ -0,0 +1,262 @@
+//! Parser of `jdk.ThreadDump`, which holds the thread dump in the text format of `jstack`.
+//!
+//! Stack frames are parsed into [`Frame`]s, so that the dumps can be merged with the sampled stack traces
+//! (e.g. by [`crate::analysis::CallTree::add_stack`]).
+
+use crate::analysis::frame::Frame;
+use crate::reader::event::Event;
+use crate::reader::known_types::KnownType;
+use crate::reader::{JfrReader, Result};
+use std::io::{Read, Seek};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ThreadDump {
+    /// Nanoseconds since UNIX epoch
+    pub time_nanos: i64,
+    /// Threads in the order of the dump
+    pub threads: Vec<DumpedThread>,
+}
+
+/// Thread in the dump. Attributes missing in the dump (e.g. for JVM internal threads) are None
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DumpedThread {
+    pub name: String,
+    /// The number after `#`, i.e. `Thread.getId()`
+    pub java_thread_id: Option<i64>,
+    pub daemon: bool,
+    pub priority: Option<i32>,
+    /// `nid`, which is printed in hex until JDK 18 and in decimal since JDK 19
+    pub os_thread_id: Option<i64>,
+    /// e.g. `RUNNABLE`, `TIMED_WAITING`
+    pub state: Option<String>,
+    /// Frames from the top. The descriptors are not available in the dump
+    pub frames: Vec<Frame>,
+}
+
+impl ThreadDump {
+    /// Parses the `result` field of `jdk.ThreadDump`.
+    /// Returns None if the event is not a thread dump event
+    pub fn from_event(event: &Event) -> Option<Self> {
+        if event.class.known_type() != Some(KnownType::ThreadDump) {
+            return None;
+        }
+        let value = event.value();
+        let ticks = value.get_field("startTime").and_then(|t| t.as_i64())?;
+        let result = value.get_field("result").and_then(|r| r.as_str())?;
+        Some(Self {
+            time_nanos: event.chunk.header.ticks_to_epoch_nanos(ticks),
+            threads: parse(result),
+        })
+    }
+
+    /// Parses all thread dumps in the recording, sorted by the time
+    pub fn read_all<T: Read + Seek>(reader: &mut JfrReader<T>) -> Result<Vec<Self>> {
+        let mut dumps = vec![];
+        for chunk in reader.chunks() {
+            let (chunk_reader, chunk) = chunk?;
+            let events = chunk_reader
+                .events(&chunk)
+                .select(|t| t.known_type() == Some(KnownType::ThreadDump));
+            for event in events {
+                dumps.extend(Self::from_event(&event?));
+            }
+        }
+        dumps.sort_by_key(|d| d.time_nanos);
+        Ok(dumps)
+    }
+}
+
+/// Parses the thread dump text. Lines other than the threads and their frames are ignored
+pub fn parse(dump: &str) -> Vec<DumpedThread> {
+    let mut threads: Vec<DumpedThread> = vec![];
+    for line in dump.lines() {
+        if line.starts_with('"') {
+            threads.extend(parse_header(line));
+            continue;
+        }
+        let thread = match threads.last_mut() {
+            Some(thread) => thread,
+            None => continue,
+        };
+        let line = line.trim_start();
+        if let Some(state) = line.strip_prefix("java.lang.Thread.State: ") {
+            thread.state = state.split_whitespace().next().map(String::from);
+        } else if let Some(frame) = line.strip_prefix("at ") {
+            thread.frames.extend(parse_frame(frame));
+        }
+    }
+    threads
+}
+
+/// Parses the thread line like `"main" #1 prio=5 os_prio=0 tid=0x00007f nid=0xe9f runnable`
+fn parse_header(line: &str) -> Option<DumpedThread> {
+    // the name may contain quotes
+    let end = line.rfind('"').filter(|end| *end > 0)?;
+    let mut thread = DumpedThread {
+        name: line[1..end].to_string(),
+        ..DumpedThread::default()
+    };
+    for attribute in line[end + 1..].split_whitespace() {
+        if let Some(id) = attribute.strip_prefix('#') {
+            thread.java_thread_id = id.parse().ok();
+        } else if attribute == "daemon" {
+            thread.daemon = true;
+        } else if let Some(priority) = attribute.strip_prefix("prio=") {
+            thread.priority = priority.parse().ok();
+        } else if let Some(nid) = attribute.strip_prefix("nid=") {
+            thread.os_thread_id = match nid.strip_prefix("0x") {
+                Some(hex) => i64::from_str_radix(hex, 16).ok(),
+                None => nid.parse().ok(),
+            };
+        }
+    }
+    Some(thread)
+}
+
+/// Parses the frame like `java.lang.Thread.run(java.base@11.0.16/Thread.java:829)`
+fn parse_frame(frame: &str) -> Option<Frame> {
+    let open = frame.find('(')?;
+    let (method, location) = (&frame[..open], &frame[open + 1..]);
+    let location = location.strip_suffix(')').unwrap_or(location);
+    let (class_name, method_name) = match method.rfind('.') {
+        Some(i) => (&method[..i], &method[i + 1..]),
+        None => ("", method),
+    };
+    // strip the class loader and the module, e.g. `app//` or `java.base@11.0.16/`
+    let location = location.rsplit('/').next().unwrap_or(location);
+    let line_number = location
+        .rsplit_once(':')
+        .and_then(|(_, line)| line.parse().ok())
+        .unwrap_or(-1);
+    Some(Frame {
+        class_name: class_name.to_string(),
+        method_name: method_name.to_string(),
+        descriptor: None,
+        line_number,
+        frame_type: (location == "Native Method").then(|| "Native".to_string()),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analysis::CallTree;
+    use crate::reader::value_descriptor::StringRepr;
+    use crate::test_util::test_data;
+    use std::fs::File;
+
+    const DUMP: &str = "2024-01-01 00:00:00
+Full thread dump OpenJDK 64-Bit Server VM (21.0.1+12 mixed mode, sharing):
+
+\"main\" #1 [4711] prio=5 os_prio=0 cpu=44.96ms elapsed=0.50s tid=0x00007f0a nid=4711 waiting on condition  [0x00007f0b]
+   java.lang.Thread.State: TIMED_WAITING (sleeping)
+\tat java.lang.Thread.sleep0(java.base@21.0.1/Native Method)
+\tat java.lang.Thread.sleep(java.base@21.0.1/Thread.java:509)
+\tat com.example.Main.main(app//Main.java:12)
+\t- locked <0x000000062a41a8a8> (a java.lang.Object)
+
+\"worker \"quoted\"\" #22 daemon prio=4 os_prio=0 tid=0x00007f0c nid=0x1a2b runnable  [0x00007f0d]
+   java.lang.Thread.State: RUNNABLE
+\tat com.example.Worker$$Lambda/0x0000000100062c40.run(Unknown Source)
+\tat java.lang.Thread.run(Thread.java)
+
+\"VM Thread\" os_prio=0 cpu=1.00ms elapsed=0.50s tid=0x00007f0e nid=4712 runnable
+
+JNI global refs: 10, weak refs: 0
+";
+
+    #[test]
+    fn test_parse() {
+        let threads = parse(DUMP);
+        assert_eq!(threads.len(), 3);
+
+        let main = &threads[0];
+        assert_eq!(main.name, "main");
+        assert_eq!(main.java_thread_id, Some(1));
+        assert!(!main.daemon);
+        assert_eq!(main.priority, Some(5));
+        assert_eq!(main.os_thread_id, Some(4711));
+        assert_eq!(main.state.as_deref(), Some("TIMED_WAITING"));
+        assert_eq!(
+            main.frames,
+            vec![
+                Frame {
+                    class_name: "java.lang.Thread".to_string(),
+                    method_name: "sleep0".to_string(),
+                    descriptor: None,
+                    line_number: -1,
+                    frame_type: Some("Native".to_string()),
+                },
+                Frame {
+                    class_name: "java.lang.Thread".to_string(),
+                    method_name: "sleep".to_string(),
+                    descriptor: None,
+                    line_number: 509,
+                    frame_type: None,
+                },
+                Frame {
+                    class_name: "com.example.Main".to_string(),
+                    method_name: "main".to_string(),
+                    descriptor: None,
+                    line_number: 12,
+                    frame_type: None,
+                },
+            ]
+        );
+
+        let worker = &threads[1];
+        assert_eq!(worker.name, "worker \"quoted\"");
+        assert_eq!(worker.java_thread_id, Some(22));
+        assert!(worker.daemon);
+        assert_eq!(worker.priority, Some(4));
+        assert_eq!(worker.os_thread_id, Some(0x1a2b));
+        assert_eq!(worker.state.as_deref(), Some("RUNNABLE"));
+        assert_eq!(
+            worker
+                .frames
+                .iter()
+                .map(|f| f.to_string())
+                .collect::<Vec<_>>(),
+            vec![
+                "com.example.Worker$$Lambda/0x0000000100062c40.run",
+                "java.lang.Thread.run"
+            ]
+        );
+
+        let vm_thread = &threads[2];
+        assert_eq!(vm_thread.name, "VM Thread");
+        assert_eq!(vm_thread.java_thread_id, None);
+        assert_eq!(vm_thread.priority, None);
+        assert_eq!(vm_thread.os_thread_id, Some(4712));
+        assert_eq!(vm_thread.state, None);
+        assert!(vm_thread.frames.is_empty());
+    }
+
+    #[test]
+    fn test_read_all() {
+        let mut reader = JfrReader::new(File::open(test_data("recording.jfr")).unwrap())
+            .string_repr(StringRepr::String);
+        let dumps = ThreadDump::read_all(&mut reader).unwrap();
+        assert!(!dumps.is_empty());
+        assert!(dumps.windows(2).all(|w| w[0].time_nanos <= w[1].time_nanos));
+
+        let main = dumps[0].threads.iter().find(|t| t.name == "main").unwrap();
+        assert_eq!(main.java_thread_id, Some(1));
+        assert_eq!(main.os_thread_id, Some(0xe9f));
+        assert_eq!(main.state.as_deref(), Some("TIMED_WAITING"));
+        assert_eq!(
+            main.frames.first().map(|f| f.to_string()).as_deref(),
+            Some("jdk.internal.misc.Unsafe.park")
+        );
+        assert_eq!(
+            main.frames.last().map(|f| f.to_string()).as_deref(),
+            Some("Example.main:78")
+        );
+
+        let mut tree = CallTree::new();
+        for thread in &dumps[0].threads {
+            tree.add_stack(&thread.frames, 1);
+        }
+        assert!(!tree.is_empty());
+    }
+}
@@ -0,0 +1,224 @@
+//! Per-thread timeline of the recording, which can be rendered as thread lanes.
+
+use crate::reader::event::{Accessor, Event};
+use crate::reader::known_types::KnownType;
+use crate::reader::{JfrReader, Result};
+use std::collections::BTreeMap;
+use std::io::{Read, Seek};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum IntervalKind {
+    /// `jdk.ExecutionSample`, which is an instant so the interval is empty
+    ExecutionSample,
+    /// `jdk.NativeMethodSample`, which is an instant so the interval is empty
+    NativeMethodSample,
+    /// `jdk.JavaMonitorEnter`, i.e. blocked on entering the monitor
+    MonitorEnter,
+    /// `jdk.JavaMonitorWait`
+    MonitorWait,
+    /// `jdk.ThreadPark`
+    Park,
+    /// `jdk.ThreadSleep`
+    Sleep,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Interval {
+    /// Nanoseconds since UNIX epoch
+    pub start_nanos: i64,
+    /// Nanoseconds since UNIX epoch
+    pub end_nanos: i64,
+    pub kind: IntervalKind,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ThreadLane {
+    pub os_thread_id: i64,
+    pub os_name: Option<String>,
+    pub java_thread_id: Option<i64>,
+    pub java_name: Option<String>,
+    /// The time of `jdk.ThreadStart` if recorded
+    pub start_nanos: Option<i64>,
+    /// The time of `jdk.ThreadEnd` if recorded
+    pub end_nanos: Option<i64>,
+    /// Intervals ordered by the start time
+    pub intervals: Vec<Interval>,
+}
+
+/// Timeline of the threads keyed by OS thread id.
+///
+/// Intervals are added in the order of the events, and sorted by [`Timeline::finish`].
+#[derive(Debug, Default)]
+pub struct Timeline {
+    lanes: BTreeMap<i64, ThreadLane>,
+}
+
+impl Timeline {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Builds the timeline from all chunks of the recording
+    pub fn build<T: Read + Seek>(reader: &mut JfrReader<T>) -> Result<Self> {
+        let mut timeline = Self::new();
+        for chunk in reader.chunks() {
+            let (chunk_reader, chunk) = chunk?;
+            for event in chunk_reader.events(&chunk) {
+                timeline.add(&event?);
+            }
+        }
+        Ok(timeline.finish())
+    }
+
+    /// Adds the event. Returns false if the event is not relevant to the timeline
+    pub fn add(&mut self, event: &Event) -> bool {
+        let (thread_field, kind) = match event.class.known_type() {
+            Some(KnownType::ExecutionSample) => ("sampledThread", IntervalKind::ExecutionSample),
+            Some(KnownType::NativeMethodSample) => {
+                ("sampledThread", IntervalKind::NativeMethodSample)
+            }
+            Some(KnownType::JavaMonitorEnter) => ("eventThread", IntervalKind::MonitorEnter),
+            Some(KnownType::JavaMonitorWait) => ("eventThread", IntervalKind::MonitorWait),
+            Some(KnownType::ThreadPark) => ("eventThread", IntervalKind::Park),
+            Some(KnownType::ThreadSleep) => ("eventThread", IntervalKind::Sleep),
+            Some(KnownType::ThreadStart) => return self.add_lifecycle(event, true),
+            Some(KnownType::ThreadEnd) => return self.add_lifecycle(event, false),
+            _ => return false,
+        };
+
+        let value = event.value();
+        let lane = match value.get_field(thread_field).and_then(|t| self.lane(&t)) {
+            Some(lane) => lane,
+            None => return false,
+        };
+        let header = &event.chunk.header;
+        let start_nanos = header.ticks_to_epoch_nanos(i64_field(&value, "startTime").unwrap_or(0));
+        let duration_nanos = i64_field(&value, "duration")
+            .map(|d| header.ticks_to_nanos(d))
+            .unwrap_or(0);
+        lane.intervals.push(Interval {
+            start_nanos,
+            end_nanos: start_nanos + duration_nanos,
+            kind,
+        });
+        true
+    }
+
+    /// Sorts the intervals of each thread
+    pub fn finish(mut self) -> Self {
+        for lane in self.lanes.values_mut() {
+            lane.intervals.sort_by_key(|i| (i.start_nanos, i.end_nanos));
+        }
+        self
+    }
+
+    /// Threads ordered by OS thread id
+    pub fn lanes(&self) -> impl Iterator<Item = &ThreadLane> {
+        self.lanes.values()
+    }
+
+    pub fn get(&self, os_thread_id: i64) -> Option<&ThreadLane> {
+        self.lanes.get(&os_thread_id)
+    }
+
+    fn add_lifecycle(&mut self, event: &Event, start: bool) -> bool {
+        let value = event.value();
+        let time = event
+            .chunk
+            .header
+            .ticks_to_epoch_nanos(i64_field(&value, "startTime").unwrap_or(0));
+        match value.get_field("thread").and_then(|t| self.lane(&t)) {
+            Some(lane) => {
+                if start {
+                    lane.start_nanos = Some(time);
+                } else {
+                    lane.end_nanos = Some(time);
+                }
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn lane(&mut self, thread: &Accessor) -> Option<&mut ThreadLane> {
+        let os_thread_id = i64_field(thread, "osThreadId")?;
+        let lane = self
+            .lanes
+            .entry(os_thread_id)
+            .or_insert_with(|| ThreadLane {
+                os_thread_id,
+                ..ThreadLane::default()
+            });
+        // fill the names lazily since some events may lack them
+        if lane.os_name.is_none() {
+            lane.os_name = str_field(thread, "osName");
+        }
+        if lane.java_name.is_none() {
+            lane.java_name = str_field(thread, "javaName");
+        }
+        if lane.java_thread_id.is_none() {
+            // non-Java threads have 0
+            lane.java_thread_id = i64_field(thread, "javaThreadId").filter(|id| *id > 0);
+        }
+        Some(lane)
+    }
+}
+
+fn i64_field(accessor: &Accessor, name: &str) -> Option<i64> {
+    accessor.get_field(name).and_then(|v| v.as_i64())
+}
+
+fn str_field(accessor: &Accessor, name: &str) -> Option<String> {
+    accessor
+        .get_field(name)
+        .and_then(|v| v.as_str())
+        .map(String::from)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::test_data;
+    use std::fs::File;
+
+    #[test]
+    fn test_timeline() {
+        let mut reader = JfrReader::new(File::open(test_data("recording.jfr")).unwrap());
+        let timeline = Timeline::build(&mut reader).unwrap();
+
+        let intervals = timeline
+            .lanes()
+            .flat_map(|l| l.intervals.iter())
+            .collect::<Vec<_>>();
+        let count = |kind| intervals.iter().filter(|i| i.kind == kind).count();
+        assert_eq!(count(IntervalKind::ExecutionSample), 3);
+        assert_eq!(count(IntervalKind::NativeMethodSample), 249);
+        assert_eq!(count(IntervalKind::MonitorWait), 701);
+        assert_eq!(count(IntervalKind::Park), 237);
+
+        for lane in timeline.lanes() {
+            assert!(lane
+                .intervals
+                .windows(2)
+                .all(|w| w[0].start_nanos <= w[1].start_nanos));
+            assert!(lane.intervals.iter().all(|i| i.start_nanos <= i.end_nanos));
+        }
+        let park = intervals
+            .iter()
+            .find(|i| i.kind == IntervalKind::Park)
+            .unwrap();
+        assert!(park.end_nanos > park.start_nanos);
+
+        // jdk.ThreadEnd
+        assert_eq!(
+            timeline.lanes().filter(|l| l.end_nanos.is_some()).count(),
+            1
+        );
+        let writer = timeline
+            .lanes()
+            .find(|l| l.java_name.as_deref() == Some("writer-0"))
+            .unwrap();
+        assert_eq!(timeline.get(writer.os_thread_id), Some(writer));
+        assert!(writer.java_thread_id.is_some());
+    }
+}
@@ -0,0 +1,206 @@
+//! Time series extraction from periodic events (e.g. `jdk.CPULoad`),
+//! which can be ingested to metrics backends.
+
+use crate::reader::event::Event;
+use crate::reader::path::CompiledPath;
+use crate::reader::value_descriptor::{Primitive, ValueDescriptor};
+use crate::reader::{JfrReader, Result};
+use std::collections::BTreeMap;
+use std::io::{Read, Seek};
+
+/// Series of (timestamp in nanoseconds since UNIX epoch, value) sorted by the timestamp
+#[derive(Debug, Clone, PartialEq)]
+pub struct TimeSeries {
+    /// `<event type>.<field>` (e.g. `jdk.CPULoad.machineTotal`)
+    pub name: String,
+    /// Labels to distinguish the series of the same name (e.g. the thread)
+    pub labels: Vec<(String, String)>,
+    pub points: Vec<(i64, f64)>,
+}
+
+/// Extracts `jvmUser`, `jvmSystem` and `machineTotal` of `jdk.CPULoad`
+pub fn cpu_load<T: Read + Seek>(reader: &mut JfrReader<T>) -> Result<Vec<TimeSeries>> {
+    extract(
+        reader,
+        "jdk.CPULoad",
+        &["jvmUser", "jvmSystem", "machineTotal"],
+        None,
+    )
+}
+
+/// Extracts `user` and `system` of `jdk.ThreadCPULoad` for each thread,
+/// labeled by `thread` (the OS thread name)
+pub fn thread_cpu_load<T: Read + Seek>(reader: &mut JfrReader<T>) -> Result<Vec<TimeSeries>> {
+    extract(
+        reader,
+        "jdk.ThreadCPULoad",
+        &["user", "system"],
+        Some(("thread", "eventThread.osName")),
+    )
+}
+
+/// Extracts `activeCount`, `daemonCount`, `accumulatedCount` and `peakCount` of `jdk.JavaThreadStatistics`
+pub fn java_thread_statistics<T: Read + Seek>(
+    reader: &mut JfrReader<T>,
+) -> Result<Vec<TimeSeries>> {
+    extract(
+        reader,
+        "jdk.JavaThreadStatistics",
+        &[
+            "activeCount",
+            "daemonCount",
+            "accumulatedCount",
+            "peakCount",
+        ],
+        None,
+    )
+}
+
+/// Extracts the numeric fields of the event type as time series, using `startTime` as the timestamp.
+///
+/// All series extracted from the same group share the timestamps, i.e. they are aligned.
+/// If `group_by` is given as (label name, field path), the series are split by the value of the path.
+/// Events whose field is missing or not numeric are skipped.
+pub fn extract<T: Read + Seek>(
+    reader: &mut JfrReader<T>,
+    event_type: &str,
+    fields: &[&str],
+    group_by: Option<(&str, &str)>,
+) -> Result<Vec<TimeSeries>> {
+    // group label value => points for each field
+    let mut groups: BTreeMap<Option<String>, Vec<Vec<(i64, f64)>>> = BTreeMap::new();
+
+    for chunk in reader.chunks() {
+        let (chunk_reader, chunk) = chunk?;
+        let start_time = match CompiledPath::compile(&chunk, event_type, "startTime") {
+            Some(path) => path,
+            // the event type isn't declared in this chunk
+            None => continue,
+        };
+        let paths = fields
+            .iter()
+            .map(|f| CompiledPath::compile(&chunk, event_type, f))
+            .collect::<Vec<_>>();
+        let group_path = group_by.and_then(|(_, p)| CompiledPath::compile(&chunk, event_type, p));
+
+        for event in chunk_reader.events(&chunk) {
+            let event = event?;
+            let ticks = match start_time.evaluate(&event).and_then(|v| v.as_i64()) {
+                Some(t) => t,
+                None => continue,
+            };
+            let timestamp = chunk.header.ticks_to_epoch_nanos(ticks);
+            let group = group_path.as_ref().and_then(|p| {
+                p.evaluate(&event)
+                    .and_then(|v| v.as_str())
+                    .map(String::from)
+            });
+
+            let points = groups
+                .entry(group)
+                .or_insert_with(|| vec![vec![]; fields.len()]);
+            for (path, points) in paths.iter().zip(points.iter_mut()) {
+                if let Some(v) = path.as_ref().and_then(|p| evaluate(p, &event)) {
+                    points.push((timestamp, v));
+                }
+            }
+        }
+    }
+
+    let mut result = vec![];
+    for (group, points) in groups {
+        for (field, mut points) in fields.iter().zip(points) {
+            // events are not necessarily ordered by the time
+            points.sort_by_key(|(t, _)| *t);
+            let labels = match (group_by, &group) {
+                (Some((label, _)), Some(value)) => vec![(label.to_string(), value.clone())],
+                _ => vec![],
+            };
+            result.push(TimeSeries {
+                name: format!("{}.{}", event_type, field),
+                labels,
+                points,
+            });
+        }
+    }
+    Ok(result)
+}
+
+fn evaluate(path: &CompiledPath, event: &Event) -> Option<f64> {
+    let accessor = path.evaluate(event)?;
+    let unsigned = accessor.field().map(|f| f.unsigned).unwrap_or(false);
+    match accessor.value {
+        ValueDescriptor::Primitive(p) => match p {
+            Primitive::Integer(v) if unsigned => Some(*v as u32 as f64),
+            Primitive::Long(v) if unsigned => Some(*v as u64 as f64),
+            Primitive::Integer(v) => Some(*v as f64),
+            Primitive::Long(v) => Some(*v as f64),
+            Primitive::Float(v) => Some(*v as f64),
+            Primitive::Double(v) => Some(*v),
+            Primitive::Short(v) => Some(*v as f64),
+            Primitive::Byte(v) => Some(*v as f64),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::test_data;
+    use std::fs::File;
+
+    #[test]
+    fn test_cpu_load() {
+        let mut reader = JfrReader::new(File::open(test_data("recording.jfr")).unwrap());
+        let series = cpu_load(&mut reader).unwrap();
+        let names = series.iter().map(|s| s.name.as_str()).collect::<Vec<_>>();
+        assert_eq!(
+            names,
+            vec![
+                "jdk.CPULoad.jvmUser",
+                "jdk.CPULoad.jvmSystem",
+                "jdk.CPULoad.machineTotal"
+            ]
+        );
+        assert!(!series[0].points.is_empty());
+        assert_eq!(series[0].points[0].0, 1661595163004090361);
+        for s in series.iter() {
+            assert!(s.labels.is_empty());
+            assert!(s.points.windows(2).all(|w| w[0].0 <= w[1].0));
+            // aligned
+            assert_eq!(
+                s.points.iter().map(|p| p.0).collect::<Vec<_>>(),
+                series[0].points.iter().map(|p| p.0).collect::<Vec<_>>()
+            );
+        }
+    }
+
+    #[test]
+    fn test_thread_cpu_load() {
+        let mut reader = JfrReader::new(File::open(test_data("recording.jfr")).unwrap());
+        let series = thread_cpu_load(&mut reader).unwrap();
+        let main = series
+            .iter()
+            .find(|s| {
+                s.name == "jdk.ThreadCPULoad.user"
+                    && s.labels == vec![("thread".to_string(), "main".to_string())]
+            })
+            .unwrap();
+        assert_eq!(main.points.len(), 1);
+    }
+
+    #[test]
+    fn test_java_thread_statistics() {
+        let mut reader = JfrReader::new(File::open(test_data("recording.jfr")).unwrap());
+        let series = java_thread_statistics(&mut reader).unwrap();
+        assert_eq!(series.len(), 4);
+        assert_eq!(series[0].name, "jdk.JavaThreadStatistics.activeCount");
+        assert_eq!(series[0].points[0].1, 13.0);
+
+        // not recorded
+        let mut reader = JfrReader::new(File::open(test_data("profiler-wall.jfr")).unwrap());
+        assert!(java_thread_statistics(&mut reader).unwrap().is_empty());
+    }
+}
@@ -0,0 +1,159 @@
+//! How much each event weighs when aggregating samples.
+//!
+//! The meaning of a sample depends on the profiler mode: a CPU sample represents the sampling
+//! interval of CPU time, a wall-clock sample the interval of elapsed time, and an allocation sample
+//! the bytes allocated since the previous sample. Exporters producing flame graphs or pprof
+//! profiles choose the [`Weight`] to get correctly weighted profiles.
+
+use crate::reader::compat::CompatEvent;
+use crate::reader::configuration::EventTypeConfiguration;
+use crate::reader::event::Event;
+
+/// Source of the weight of an event
+pub trait Weight {
+    /// Returns the weight of the event, or None if the event can't be weighted (e.g. the field is missing)
+    fn weight(&self, event: &Event) -> Option<u64>;
+}
+
+impl<F> Weight for F
+where
+    F: Fn(&Event) -> Option<u64>,
+{
+    fn weight(&self, event: &Event) -> Option<u64> {
+        self(event)
+    }
+}
+
+/// Weights each event as 1
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SampleCount;
+
+impl Weight for SampleCount {
+    fn weight(&self, _event: &Event) -> Option<u64> {
+        Some(1)
+    }
+}
+
+/// Weights the event by the value of the integral field (e.g. `weight` of `jdk.ObjectAllocationSample`)
+#[derive(Debug, Clone)]
+pub struct FieldValue(pub String);
+
+impl Weight for FieldValue {
+    fn weight(&self, event: &Event) -> Option<u64> {
+        event.value().get_field(&self.0)?.as_u64()
+    }
+}
+
+/// Weights the allocation events by the allocated bytes.
+///
+/// `jdk.ObjectAllocationSample` is weighted by `weight`, and the TLAB allocation events
+/// by the corresponding sizes (see [`crate::reader::compat`]).
+#[derive(Debug, Clone, Copy, Default)]
+pub struct AllocationSize;
+
+impl Weight for AllocationSize {
+    fn weight(&self, event: &Event) -> Option<u64> {
+        CompatEvent::new(event).get_field("weight")?.as_u64()
+    }
+}
+
+/// Weights the event by `duration` field in nanoseconds, e.g. for lock contention and I/O events
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DurationNanos;
+
+impl Weight for DurationNanos {
+    fn weight(&self, event: &Event) -> Option<u64> {
+        let value = event.value();
+        let duration = value.get_field("duration")?;
+        let nanos = duration
+            .field()?
+            .timespan_nanos(duration.as_i64()?, &value.chunk.header)?;
+        u64::try_from(nanos).ok()
+    }
+}
+
+/// Weights each sample as the sampling interval in nanoseconds,
+/// so that the total weight approximates the CPU time or the elapsed time of the threads
+#[derive(Debug, Clone, Copy)]
+pub struct SamplingInterval {
+    pub interval_nanos: u64,
+}
+
+impl SamplingInterval {
+    pub fn new(interval_nanos: u64) -> Self {
+        Self { interval_nanos }
+    }
+
+    /// Takes the interval from the `period` setting of the sampled event type (e.g. `jdk.ExecutionSample`).
+    /// Returns None if the period is not a duration
+    pub fn from_configuration(config: &EventTypeConfiguration) -> Option<Self> {
+        config
+            .period_nanos()
+            .and_then(|nanos| u64::try_from(nanos).ok())
+            .map(Self::new)
+    }
+}
+
+impl Weight for SamplingInterval {
+    fn weight(&self, _event: &Event) -> Option<u64> {
+        Some(self.interval_nanos)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::reader::configuration::RecordingConfiguration;
+    use crate::reader::JfrReader;
+    use crate::test_util::test_data;
+    use std::fs::File;
+
+    #[test]
+    fn test_weights() {
+        let mut reader = JfrReader::new(File::open(test_data("recording.jfr")).unwrap());
+        let (chunk_reader, chunk) = reader.chunks().next().unwrap().unwrap();
+        let events = chunk_reader.events(&chunk).flatten().collect::<Vec<_>>();
+        let park = events
+            .iter()
+            .find(|e| e.class.name() == "jdk.ThreadPark")
+            .unwrap();
+
+        assert_eq!(SampleCount.weight(park), Some(1));
+        let nanos = DurationNanos.weight(park).unwrap();
+        assert!(nanos > 0);
+        // the raw value in ticks
+        let ticks = FieldValue("duration".to_string()).weight(park);
+        assert!(ticks.is_some());
+        assert_eq!(FieldValue("no-such".to_string()).weight(park), None);
+        assert_eq!(AllocationSize.weight(park), None);
+        let custom = |e: &Event| e.value().get_field("duration")?.as_u64().map(|v| v * 2);
+        assert_eq!(custom.weight(park), ticks.map(|v| v * 2));
+
+        let config = RecordingConfiguration::read(&chunk_reader, &chunk).unwrap();
+        let interval =
+            SamplingInterval::from_configuration(config.get("jdk.ExecutionSample").unwrap())
+                .unwrap();
+        assert_eq!(interval.weight(park), Some(20_000_000));
+    }
+
+    #[test]
+    fn test_allocation_size() {
+        let mut reader = JfrReader::new(File::open(test_data("profiler-alloc.jfr")).unwrap());
+        let (chunk_reader, chunk) = reader.chunks().next().unwrap().unwrap();
+        let event = chunk_reader
+            .events(&chunk)
+            .flatten()
+            .find(|e| e.class.name() == "jdk.ObjectAllocationInNewTLAB")
+            .unwrap();
+        let tlab_size = event
+            .value()
+            .get_field("tlabSize")
+            .and_then(|v| v.as_u64())
+            .unwrap();
+        assert_eq!(AllocationSize.weight(&event), Some(tlab_size));
+        assert_eq!(
+            FieldValue("allocationSize".to_string()).weight(&event),
+            Some(40)
+        );
+    }
+}
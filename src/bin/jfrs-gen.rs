@@ -0,0 +1,34 @@
+//! Prints Rust structs for the event types declared in the given JFR file.
+//!
+//! Usage: `jfrs-gen <path-to-jfr> > events.rs`
+
+use jfrs::reader::JfrReader;
+use std::fs::File;
+use std::process::exit;
+
+fn main() {
+    let path = match std::env::args().nth(1) {
+        Some(path) => path,
+        None => {
+            eprintln!("Usage: jfrs-gen <path-to-jfr>");
+            exit(1);
+        }
+    };
+    let file = File::open(&path).unwrap_or_else(|e| {
+        eprintln!("Failed to open {}: {}", path, e);
+        exit(1);
+    });
+
+    let mut reader = JfrReader::new(file);
+    match reader.chunk_metadata().next() {
+        Some(Ok((_, chunk))) => print!("{}", jfrs::tools::generate(&chunk.metadata)),
+        Some(Err(e)) => {
+            eprintln!("Failed to read {}: {}", path, e);
+            exit(1);
+        }
+        None => {
+            eprintln!("No chunk found in {}", path);
+            exit(1);
+        }
+    }
+}
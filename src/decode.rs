@@ -0,0 +1,364 @@
+//! Decoding of the primitives and strings in the JFR format with `core` and `alloc` only,
+//! so that the byte-level parser can be used in constrained environments without `std`.
+//!
+//! The input is abstracted by the minimal [`Input`] trait, which is implemented for `&[u8]`.
+//! The byte stream of the reader is built on top of this module when `std` is enabled.
+//! Values, metadata and constant pools are decoded only by the reader, which requires `std`.
+//!
+//! ```
+//! use jfrs::decode::{Decoder, IntEncoding, StringType};
+//!
+//! let bytes: &[u8] = &[0x85, 0xb0, 0x03, 3, 2, b'h', b'i'];
+//! let mut decoder = Decoder::new(bytes);
+//! decoder.set_int_encoding(IntEncoding::Compressed);
+//! assert_eq!(decoder.read_i64().unwrap(), 55301);
+//! assert_eq!(decoder.read_string().unwrap(), StringType::Raw("hi".to_string()));
+//! ```
+
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt;
+use core::fmt::Formatter;
+
+pub(crate) const STRING_ENCODING_NULL: i8 = 0;
+pub(crate) const STRING_ENCODING_EMPTY_STRING: i8 = 1;
+pub(crate) const STRING_ENCODING_CONSTANT_POOL: i8 = 2;
+pub(crate) const STRING_ENCODING_UTF8_BYTE_ARRAY: i8 = 3;
+pub(crate) const STRING_ENCODING_CHAR_ARRAY: i8 = 4;
+pub(crate) const STRING_ENCODING_LATIN1_BYTE_ARRAY: i8 = 5;
+
+#[derive(Debug, Eq, PartialEq)]
+pub enum StringType<S = String> {
+    Null,
+    Empty,
+    Raw(S),
+    ConstantPool(i64),
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum IntEncoding {
+    Raw,
+    Compressed, // varint encoding, but not ZigZag
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+#[non_exhaustive]
+pub enum DecodeError {
+    UnexpectedEof,
+    InvalidFormat,
+    InvalidString,
+    InvalidChar(u32),
+    StringTooLong { limit: u64, actual: u64 },
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            DecodeError::UnexpectedEof => write!(f, "Unexpected end of input"),
+            DecodeError::InvalidFormat => write!(f, "Invalid format"),
+            DecodeError::InvalidString => write!(f, "Invalid string"),
+            DecodeError::InvalidChar(c) => write!(f, "Invalid char: {:#x}", c),
+            DecodeError::StringTooLong { limit, actual } => {
+                write!(
+                    f,
+                    "The string length {} exceeds the limit {}",
+                    actual, limit
+                )
+            }
+        }
+    }
+}
+
+/// Source of the bytes to decode.
+///
+/// In-memory sources expose the unread bytes, so that compressed integers can be decoded
+/// directly from the slice instead of reading byte by byte.
+pub trait Input {
+    type Error: From<DecodeError>;
+
+    /// Fills the buffer entirely
+    fn read_exact(&mut self, buf: &mut [u8]) -> Result<(), Self::Error>;
+
+    /// Returns the bytes which can be read without I/O. Empty if not in memory
+    fn unread(&self) -> &[u8] {
+        &[]
+    }
+
+    /// Marks the first `n` bytes of [`Input::unread`] as read
+    fn consume(&mut self, _n: usize) {}
+}
+
+impl Input for &[u8] {
+    type Error = DecodeError;
+
+    fn read_exact(&mut self, buf: &mut [u8]) -> Result<(), Self::Error> {
+        if self.len() < buf.len() {
+            return Err(DecodeError::UnexpectedEof);
+        }
+        let (head, tail) = self.split_at(buf.len());
+        buf.copy_from_slice(head);
+        *self = tail;
+        Ok(())
+    }
+
+    fn unread(&self) -> &[u8] {
+        self
+    }
+
+    fn consume(&mut self, n: usize) {
+        *self = &self[n..];
+    }
+}
+
+/// Decoder over [`Input`]
+pub struct Decoder<I> {
+    input: I,
+    int_encoding: IntEncoding,
+    max_string_length: Option<u64>,
+}
+
+impl<I: Input> Decoder<I> {
+    pub fn new(input: I) -> Self {
+        Self {
+            input,
+            int_encoding: IntEncoding::Raw,
+            max_string_length: None,
+        }
+    }
+
+    pub fn set_int_encoding(&mut self, encoding: IntEncoding) {
+        self.int_encoding = encoding;
+    }
+
+    /// Sets the limit on the string length. Unlimited by default
+    pub fn set_max_string_length(&mut self, limit: u64) {
+        self.max_string_length = Some(limit);
+    }
+
+    pub fn get_ref(&self) -> &I {
+        &self.input
+    }
+
+    pub fn into_inner(self) -> I {
+        self.input
+    }
+
+    pub fn read_u8(&mut self) -> Result<u8, I::Error> {
+        read_array(&mut self.input).map(u8::from_be_bytes)
+    }
+
+    pub fn read_i8(&mut self) -> Result<i8, I::Error> {
+        read_array(&mut self.input).map(i8::from_be_bytes)
+    }
+
+    pub fn read_i16(&mut self) -> Result<i16, I::Error> {
+        read_i16(&mut self.input, self.int_encoding)
+    }
+
+    pub fn read_i32(&mut self) -> Result<i32, I::Error> {
+        read_i32(&mut self.input, self.int_encoding)
+    }
+
+    pub fn read_i64(&mut self) -> Result<i64, I::Error> {
+        read_i64(&mut self.input, self.int_encoding)
+    }
+
+    pub fn read_char(&mut self) -> Result<char, I::Error> {
+        read_char(&mut self.input, self.int_encoding)
+    }
+
+    pub fn read_f32(&mut self) -> Result<f32, I::Error> {
+        read_array(&mut self.input).map(f32::from_be_bytes)
+    }
+
+    pub fn read_f64(&mut self) -> Result<f64, I::Error> {
+        read_array(&mut self.input).map(f64::from_be_bytes)
+    }
+
+    pub fn read_string(&mut self) -> Result<StringType, I::Error> {
+        read_string(&mut self.input, self.int_encoding, self.max_string_length)
+    }
+}
+
+pub(crate) fn read_array<const N: usize, I: Input>(input: &mut I) -> Result<[u8; N], I::Error> {
+    let mut buf = [0; N];
+    input.read_exact(&mut buf)?;
+    Ok(buf)
+}
+
+pub(crate) fn read_i16<I: Input>(input: &mut I, encoding: IntEncoding) -> Result<i16, I::Error> {
+    match encoding {
+        IntEncoding::Raw => read_array(input).map(i16::from_be_bytes),
+        IntEncoding::Compressed => read_var_i64(input).map(|i| i as i16),
+    }
+}
+
+pub(crate) fn read_i32<I: Input>(input: &mut I, encoding: IntEncoding) -> Result<i32, I::Error> {
+    match encoding {
+        IntEncoding::Raw => read_array(input).map(i32::from_be_bytes),
+        IntEncoding::Compressed => read_var_i64(input).map(|i| i as i32),
+    }
+}
+
+pub(crate) fn read_i64<I: Input>(input: &mut I, encoding: IntEncoding) -> Result<i64, I::Error> {
+    match encoding {
+        IntEncoding::Raw => read_array(input).map(i64::from_be_bytes),
+        IntEncoding::Compressed => read_var_i64(input),
+    }
+}
+
+pub(crate) fn read_char<I: Input>(input: &mut I, encoding: IntEncoding) -> Result<char, I::Error> {
+    let i = match encoding {
+        IntEncoding::Raw => read_i16(input, encoding)? as u16 as u32,
+        IntEncoding::Compressed => read_var_i64(input)? as u32,
+    };
+    char::from_u32(i).ok_or_else(|| DecodeError::InvalidChar(i).into())
+}
+
+pub(crate) fn read_var_i64<I: Input>(input: &mut I) -> Result<i64, I::Error> {
+    // fast path: at most 9 bytes are needed, so bounds are checked once
+    if let Some(&[b0, b1, b2, b3, b4, b5, b6, b7, b8]) = input.unread().get(..9) {
+        let bytes = [b0, b1, b2, b3, b4, b5, b6, b7];
+        let mut ret = 0i64;
+        for (i, &b) in bytes.iter().enumerate() {
+            ret += (b as i64 & 0x7f) << (7 * i);
+            if b < 0x80 {
+                input.consume(i + 1);
+                return Ok(ret);
+            }
+        }
+        input.consume(9);
+        return Ok(ret + ((b8 as i64) << 56));
+    }
+
+    let mut ret = 0i64;
+    for i in 0..8 {
+        let b = read_array(input).map(i8::from_be_bytes)? as i64;
+        ret += (b & 0x7f) << (7 * i);
+        if b >= 0 {
+            return Ok(ret);
+        }
+    }
+    Ok(ret + ((read_array(input).map(u8::from_be_bytes)? as i64) << 56))
+}
+
+/// Reads the length of a string or an array, which must not be negative
+pub(crate) fn read_length<I: Input>(
+    input: &mut I,
+    encoding: IntEncoding,
+) -> Result<usize, I::Error> {
+    let length = read_i32(input, encoding)?;
+    if length < 0 {
+        return Err(DecodeError::InvalidFormat.into());
+    }
+    Ok(length as usize)
+}
+
+pub(crate) fn read_string<I: Input>(
+    input: &mut I,
+    encoding: IntEncoding,
+    max_length: Option<u64>,
+) -> Result<StringType, I::Error> {
+    let string_encoding = read_array(input).map(i8::from_be_bytes)?;
+    if string_encoding == STRING_ENCODING_NULL {
+        return Ok(StringType::Null);
+    }
+    if string_encoding == STRING_ENCODING_EMPTY_STRING {
+        return Ok(StringType::Empty);
+    }
+    if string_encoding == STRING_ENCODING_CONSTANT_POOL {
+        return read_i64(input, encoding).map(StringType::ConstantPool);
+    }
+
+    let size = read_length(input, encoding)?;
+    match max_length {
+        Some(limit) if size as u64 > limit => {
+            return Err(DecodeError::StringTooLong {
+                limit,
+                actual: size as u64,
+            }
+            .into())
+        }
+        _ => {}
+    }
+    if string_encoding == STRING_ENCODING_CHAR_ARRAY {
+        let mut buf = Vec::with_capacity(size);
+        for _ in 0..size {
+            buf.push(read_char(input, encoding)?);
+        }
+        return Ok(StringType::Raw(buf.iter().collect()));
+    }
+
+    let mut buf = Vec::with_capacity(size);
+    for _ in 0..size {
+        buf.push(read_array(input).map(u8::from_be_bytes)?);
+    }
+    if string_encoding == STRING_ENCODING_LATIN1_BYTE_ARRAY {
+        return Ok(StringType::Raw(buf.iter().map(|&c| c as char).collect()));
+    }
+    if string_encoding == STRING_ENCODING_UTF8_BYTE_ARRAY {
+        return Ok(StringType::Raw(
+            String::from_utf8(buf).map_err(|_| DecodeError::InvalidString)?,
+        ));
+    }
+
+    Err(DecodeError::InvalidString.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::string::ToString;
+
+    #[test]
+    fn test_read_compressed() {
+        let bytes: &[u8] = &[0x85, 0xb0, 0x03, 0x7f, 0xff];
+        let mut decoder = Decoder::new(bytes);
+        decoder.set_int_encoding(IntEncoding::Compressed);
+        assert_eq!(decoder.read_i64().unwrap(), 55301);
+        assert_eq!(decoder.read_i32().unwrap(), 0x7f);
+        assert_eq!(decoder.read_i64(), Err(DecodeError::UnexpectedEof));
+
+        let bytes: &[u8] = &[0xff; 9];
+        let mut decoder = Decoder::new(bytes);
+        decoder.set_int_encoding(IntEncoding::Compressed);
+        assert_eq!(decoder.read_i64().unwrap(), -1);
+        assert!(decoder.get_ref().is_empty());
+    }
+
+    #[test]
+    fn test_read_string() {
+        let bytes: &[u8] = &[0, 1, 2, 7, 5, 1, 0xe9, 4, 1, 0xc2, 0x60, 3, 2, b'h', b'i'];
+        let mut decoder = Decoder::new(bytes);
+        decoder.set_int_encoding(IntEncoding::Compressed);
+        assert_eq!(decoder.read_string().unwrap(), StringType::Null);
+        assert_eq!(decoder.read_string().unwrap(), StringType::Empty);
+        assert_eq!(decoder.read_string().unwrap(), StringType::ConstantPool(7));
+        assert_eq!(
+            decoder.read_string().unwrap(),
+            StringType::Raw("é".to_string())
+        );
+        assert_eq!(
+            decoder.read_string().unwrap(),
+            StringType::Raw("あ".to_string())
+        );
+        decoder.set_max_string_length(1);
+        assert_eq!(
+            decoder.read_string(),
+            Err(DecodeError::StringTooLong {
+                limit: 1,
+                actual: 2
+            })
+        );
+    }
+
+    #[test]
+    fn test_primitives() {
+        let bytes: &[u8] = &[0xff, 0x12, 0x34, 0x3f, 0x80, 0x00, 0x00];
+        let mut decoder = Decoder::new(bytes);
+        assert_eq!(decoder.read_i8().unwrap(), -1);
+        assert_eq!(decoder.read_i16().unwrap(), 0x1234);
+        assert_eq!(decoder.read_f32().unwrap(), 1.0);
+        assert_eq!(decoder.read_u8(), Err(DecodeError::UnexpectedEof));
+    }
+}
@@ -1,13 +1,40 @@
 //! This crate provides Rust interfaces to manipulate JFR (Java Flight Recorder) files.
+//!
+//! Without the default `std` feature, the crate is `no_std` (with `alloc`) and provides only
+//! the decoding of the primitives and strings in [`decode`].
+//! Parsing of the values, the metadata and the constant pools (i.e. the `reader` module) requires `std`.
 
-use std::fmt;
-use std::fmt::Formatter;
+#![cfg_attr(not(feature = "std"), no_std)]
 
+extern crate alloc;
+
+use core::fmt;
+use core::fmt::Formatter;
+
+#[cfg(feature = "std")]
+pub mod analysis;
+pub mod decode;
+#[cfg(feature = "std")]
+pub mod query;
+#[cfg(feature = "std")]
 pub mod reader;
+#[cfg(feature = "std")]
+pub mod tools;
+#[cfg(feature = "std")]
+pub mod writer;
+
+#[cfg(all(test, feature = "std"))]
+mod test_util;
+
+#[cfg(feature = "flamegraph")]
+pub use analysis::flamegraph;
 
+#[cfg(feature = "std")]
 const MAGIC: [u8; 4] = [b'F', b'L', b'R', b'\0'];
 
+#[cfg(feature = "std")]
 const EVENT_TYPE_METADATA: i64 = 0;
+#[cfg(feature = "std")]
 const EVENT_TYPE_CONSTANT_POOL: i64 = 1;
 
 #[derive(Copy, Clone, Debug, Eq, PartialEq)]
@@ -0,0 +1,326 @@
+//! SQL-like queries over recordings.
+//!
+//! A query selects the fields of the events of a single type, optionally filtered and aggregated:
+//!
+//! ```text
+//! SELECT sampledThread.osName, COUNT(*) FROM jdk.ExecutionSample
+//! WHERE state.name = 'STATE_RUNNABLE'
+//! GROUP BY 1 ORDER BY 2 DESC LIMIT 10
+//! ```
+//!
+//! - Fields are dot-separated paths from the event (e.g. `eventThread.javaName`).
+//! - Aggregate functions are `COUNT(*)`, `SUM`, `MIN`, `MAX` and `AVG`. Timespans are aggregated in nanoseconds.
+//! - `WHERE` takes comparisons of a field with a string or a number, joined by `AND`.
+//! - Columns in `GROUP BY` and `ORDER BY` are referred by the 1-based position or by the expression.
+//!
+//! Aggregated queries are evaluated by [`Aggregator`], so the grouped columns come first in the result.
+
+mod parser;
+
+pub use parser::ParseError;
+
+use crate::analysis::aggregate::{evaluate, key, numeric, Aggregation, Aggregator, Row, Table};
+use crate::reader::event::Event;
+use crate::reader::{JfrReader, Result};
+use std::cmp::Ordering;
+use std::io::{Read, Seek};
+use std::str::FromStr;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SelectItem {
+    Path(String),
+    Count,
+    Sum(String),
+    Min(String),
+    Max(String),
+    Avg(String),
+}
+
+impl SelectItem {
+    pub fn is_aggregate(&self) -> bool {
+        !matches!(self, SelectItem::Path(_))
+    }
+
+    /// The column name in the result
+    pub fn name(&self) -> String {
+        match self {
+            SelectItem::Path(path) => path.clone(),
+            _ => self.aggregation().unwrap().column_name(),
+        }
+    }
+
+    fn aggregation(&self) -> Option<Aggregation> {
+        match self {
+            SelectItem::Path(_) => None,
+            SelectItem::Count => Some(Aggregation::Count),
+            SelectItem::Sum(path) => Some(Aggregation::Sum(path.clone())),
+            SelectItem::Min(path) => Some(Aggregation::Min(path.clone())),
+            SelectItem::Max(path) => Some(Aggregation::Max(path.clone())),
+            SelectItem::Avg(path) => Some(Aggregation::Avg(path.clone())),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Operator {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+impl Operator {
+    fn test(&self, ordering: Ordering) -> bool {
+        match self {
+            Operator::Eq => ordering == Ordering::Equal,
+            Operator::Ne => ordering != Ordering::Equal,
+            Operator::Lt => ordering == Ordering::Less,
+            Operator::Le => ordering != Ordering::Greater,
+            Operator::Gt => ordering == Ordering::Greater,
+            Operator::Ge => ordering != Ordering::Less,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Literal {
+    Str(String),
+    Number(f64),
+}
+
+/// `<path> <operator> <literal>`. Events without the field don't match
+#[derive(Debug, Clone, PartialEq)]
+pub struct Condition {
+    pub path: String,
+    pub operator: Operator,
+    pub literal: Literal,
+}
+
+impl Condition {
+    pub fn matches(&self, event: &Event) -> bool {
+        let value = match evaluate(&event.value(), &self.path) {
+            Some(v) => v,
+            None => return false,
+        };
+        let ordering = match &self.literal {
+            Literal::Number(n) => numeric(&value, event).and_then(|v| v.partial_cmp(n)),
+            Literal::Str(s) => key(&value).map(|v| v.as_str().cmp(s)),
+        };
+        ordering.is_some_and(|o| self.operator.test(o))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OrderBy {
+    /// The index in the select list
+    pub column: usize,
+    pub descending: bool,
+}
+
+/// Parsed query
+#[derive(Debug, Clone, PartialEq)]
+pub struct Query {
+    pub select: Vec<SelectItem>,
+    pub event_type: String,
+    pub conditions: Vec<Condition>,
+    /// Indices in the select list
+    pub group_by: Vec<usize>,
+    pub order_by: Option<OrderBy>,
+    pub limit: Option<usize>,
+}
+
+impl FromStr for Query {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        parser::parse(s)
+    }
+}
+
+impl Query {
+    pub fn parse(query: &str) -> std::result::Result<Self, ParseError> {
+        parser::parse(query)
+    }
+
+    pub fn is_aggregated(&self) -> bool {
+        !self.group_by.is_empty() || self.select.iter().any(|s| s.is_aggregate())
+    }
+
+    /// Reads all chunks of the recording and returns the result
+    pub fn run<T: Read + Seek>(&self, reader: &mut JfrReader<T>) -> Result<Table> {
+        let mut table = if self.is_aggregated() {
+            self.run_aggregated(reader)?
+        } else {
+            self.run_projection(reader)?
+        };
+
+        if let Some(order_by) = self.order_by {
+            let column = self.result_column(order_by.column);
+            table.rows.sort_by(|a, b| {
+                let ordering = compare(a, b, column);
+                if order_by.descending {
+                    ordering.reverse()
+                } else {
+                    ordering
+                }
+            });
+        }
+        if let Some(limit) = self.limit {
+            table.rows.truncate(limit);
+        }
+        Ok(table)
+    }
+
+    fn run_aggregated<T: Read + Seek>(&self, reader: &mut JfrReader<T>) -> Result<Table> {
+        let mut aggregator = Aggregator::new().event_type(&self.event_type);
+        for item in self.select.iter() {
+            aggregator = match item {
+                SelectItem::Path(path) => aggregator.group_by_path(path),
+                _ => aggregator.aggregate(item.aggregation().unwrap()),
+            };
+        }
+        for chunk in reader.chunks() {
+            let (chunk_reader, chunk) = chunk?;
+            for event in chunk_reader.events(&chunk) {
+                let event = event?;
+                if self.conditions.iter().all(|c| c.matches(&event)) {
+                    aggregator.add(&event);
+                }
+            }
+        }
+        Ok(aggregator.finish())
+    }
+
+    fn run_projection<T: Read + Seek>(&self, reader: &mut JfrReader<T>) -> Result<Table> {
+        let mut table = Table {
+            columns: self.select.iter().map(|s| s.name()).collect(),
+            rows: vec![],
+        };
+        // the rows can't be cut before sorting
+        let limit = match self.order_by {
+            Some(_) => None,
+            None => self.limit,
+        };
+        for chunk in reader.chunks() {
+            let (chunk_reader, chunk) = chunk?;
+            for event in chunk_reader.events(&chunk) {
+                if limit.is_some_and(|l| table.rows.len() >= l) {
+                    return Ok(table);
+                }
+                let event = event?;
+                if event.class.name() != self.event_type
+                    || !self.conditions.iter().all(|c| c.matches(&event))
+                {
+                    continue;
+                }
+                let value = event.value();
+                table.rows.push(Row {
+                    keys: self
+                        .select
+                        .iter()
+                        .map(|s| evaluate(&value, &s.name()).and_then(|v| key(&v)))
+                        .collect(),
+                    values: vec![],
+                });
+            }
+        }
+        Ok(table)
+    }
+
+    /// Maps the index in the select list to the column of the result, where grouped columns come first
+    fn result_column(&self, idx: usize) -> usize {
+        if !self.is_aggregated() {
+            return idx;
+        }
+        let (keys, aggregates): (Vec<_>, Vec<_>) =
+            (0..self.select.len()).partition(|i| !self.select[*i].is_aggregate());
+        match keys.iter().position(|i| *i == idx) {
+            Some(pos) => pos,
+            None => keys.len() + aggregates.iter().position(|i| *i == idx).unwrap(),
+        }
+    }
+}
+
+/// Compares the rows by the column. Keys are compared as numbers if both are numeric
+fn compare(a: &Row, b: &Row, column: usize) -> Ordering {
+    if column < a.keys.len() {
+        let (a, b) = (&a.keys[column], &b.keys[column]);
+        match (
+            a.as_deref().and_then(|s| s.parse::<f64>().ok()),
+            b.as_deref().and_then(|s| s.parse::<f64>().ok()),
+        ) {
+            (Some(x), Some(y)) => x.total_cmp(&y),
+            _ => a.cmp(b),
+        }
+    } else {
+        let idx = column - a.keys.len();
+        let key = |r: &Row| r.values[idx].unwrap_or(f64::NEG_INFINITY);
+        key(a).total_cmp(&key(b))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::test_data;
+    use std::fs::File;
+
+    #[test]
+    fn test_group_by() {
+        let mut reader = JfrReader::new(File::open(test_data("profiler-wall.jfr")).unwrap());
+        let query: Query =
+            "SELECT COUNT(*), state.name FROM jdk.ExecutionSample GROUP BY state.name ORDER BY 1 DESC"
+                .parse()
+                .unwrap();
+        let table = query.run(&mut reader).unwrap();
+        assert_eq!(table.columns, vec!["state.name", "count"]);
+        assert_eq!(table.rows.len(), 2);
+        assert_eq!(table.rows[0].keys, vec![Some("STATE_SLEEPING".to_string())]);
+        assert_eq!(table.rows[0].values, vec![Some(8163.0)]);
+        assert_eq!(table.rows[1].values, vec![Some(673.0)]);
+    }
+
+    #[test]
+    fn test_where() {
+        let mut reader = JfrReader::new(File::open(test_data("profiler-wall.jfr")).unwrap());
+        let table = Query::parse(
+            "SELECT sampledThread.osName, COUNT(*) FROM jdk.ExecutionSample \
+             WHERE state.name = 'STATE_RUNNABLE' GROUP BY 1",
+        )
+        .unwrap()
+        .run(&mut reader)
+        .unwrap();
+        let total = table.rows.iter().map(|r| r.values[0].unwrap()).sum::<f64>();
+        assert_eq!(total, 673.0);
+        assert!(table.rows.windows(2).all(|w| w[0].keys <= w[1].keys));
+    }
+
+    #[test]
+    fn test_projection() {
+        let mut reader = JfrReader::new(File::open(test_data("recording.jfr")).unwrap());
+        let table = Query::parse(
+            "SELECT eventThread.javaName, duration FROM jdk.ThreadPark \
+             WHERE duration >= 0 ORDER BY duration DESC LIMIT 3",
+        )
+        .unwrap()
+        .run(&mut reader)
+        .unwrap();
+        assert_eq!(table.columns, vec!["eventThread.javaName", "duration"]);
+        assert_eq!(table.rows.len(), 3);
+        let durations = table
+            .rows
+            .iter()
+            .map(|r| r.keys[1].as_ref().unwrap().parse::<i64>().unwrap())
+            .collect::<Vec<_>>();
+        assert!(durations.windows(2).all(|w| w[0] >= w[1]));
+
+        let mut reader = JfrReader::new(File::open(test_data("recording.jfr")).unwrap());
+        let table = Query::parse("SELECT startTime FROM jdk.ThreadPark LIMIT 2")
+            .unwrap()
+            .run(&mut reader)
+            .unwrap();
+        assert_eq!(table.rows.len(), 2);
+    }
+}
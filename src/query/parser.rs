@@ -0,0 +1,404 @@
+//! Tokenizer and recursive descent parser of the query language.
+
+use crate::query::{Condition, Literal, Operator, OrderBy, Query, SelectItem};
+use std::fmt;
+use std::fmt::Formatter;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    /// The byte offset in the query where the error is detected
+    pub position: usize,
+    pub message: String,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{} at {}", self.message, self.position)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    /// Keywords are identifiers too
+    Ident(String),
+    Number(f64),
+    Str(String),
+    Symbol(&'static str),
+}
+
+const SYMBOLS: &[&str] = &["<=", ">=", "!=", "<>", "=", "<", ">", "(", ")", ",", "*"];
+
+fn tokenize(query: &str) -> Result<Vec<(usize, Token)>, ParseError> {
+    let mut tokens = vec![];
+    let bytes = query.as_bytes();
+    let mut pos = 0;
+    while pos < bytes.len() {
+        let c = bytes[pos] as char;
+        if c.is_ascii_whitespace() {
+            pos += 1;
+        } else if c == '\'' {
+            // '' is the escaped quote
+            let start = pos;
+            let mut s = String::new();
+            pos += 1;
+            loop {
+                match query[pos..].find('\'') {
+                    Some(idx) => {
+                        s.push_str(&query[pos..pos + idx]);
+                        pos += idx + 1;
+                        if query[pos..].starts_with('\'') {
+                            s.push('\'');
+                            pos += 1;
+                        } else {
+                            break;
+                        }
+                    }
+                    None => return Err(error(start, "unterminated string")),
+                }
+            }
+            tokens.push((start, Token::Str(s)));
+        } else if c.is_ascii_digit()
+            || (c == '-' && bytes.get(pos + 1).is_some_and(u8::is_ascii_digit))
+        {
+            let start = pos;
+            pos += 1;
+            while pos < bytes.len() && (bytes[pos].is_ascii_digit() || bytes[pos] == b'.') {
+                pos += 1;
+            }
+            let n = query[start..pos]
+                .parse()
+                .map_err(|_| error(start, "invalid number"))?;
+            tokens.push((start, Token::Number(n)));
+        } else if c.is_alphabetic() || c == '_' || c == '$' {
+            let start = pos;
+            while pos < bytes.len() {
+                let c = query[pos..].chars().next().unwrap();
+                if c.is_alphanumeric() || c == '_' || c == '$' || c == '.' {
+                    pos += c.len_utf8();
+                } else {
+                    break;
+                }
+            }
+            tokens.push((start, Token::Ident(query[start..pos].to_string())));
+        } else {
+            match SYMBOLS.iter().find(|s| query[pos..].starts_with(**s)) {
+                Some(s) => {
+                    tokens.push((pos, Token::Symbol(s)));
+                    pos += s.len();
+                }
+                None => return Err(error(pos, &format!("unexpected character '{}'", c))),
+            }
+        }
+    }
+    Ok(tokens)
+}
+
+fn error(position: usize, message: &str) -> ParseError {
+    ParseError {
+        position,
+        message: message.to_string(),
+    }
+}
+
+struct Parser {
+    tokens: Vec<(usize, Token)>,
+    idx: usize,
+    len: usize,
+}
+
+impl Parser {
+    fn position(&self) -> usize {
+        self.tokens.get(self.idx).map_or(self.len, |(p, _)| *p)
+    }
+
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.idx).map(|(_, t)| t)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.peek().cloned();
+        self.idx += 1;
+        token
+    }
+
+    fn is_keyword(&self, keyword: &str) -> bool {
+        matches!(self.peek(), Some(Token::Ident(s)) if s.eq_ignore_ascii_case(keyword))
+    }
+
+    fn accept_keyword(&mut self, keyword: &str) -> bool {
+        if self.is_keyword(keyword) {
+            self.idx += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect_keyword(&mut self, keyword: &str) -> Result<(), ParseError> {
+        if self.accept_keyword(keyword) {
+            Ok(())
+        } else {
+            Err(error(self.position(), &format!("expected {}", keyword)))
+        }
+    }
+
+    fn accept_symbol(&mut self, symbol: &str) -> bool {
+        if matches!(self.peek(), Some(Token::Symbol(s)) if *s == symbol) {
+            self.idx += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect_symbol(&mut self, symbol: &str) -> Result<(), ParseError> {
+        if self.accept_symbol(symbol) {
+            Ok(())
+        } else {
+            Err(error(self.position(), &format!("expected '{}'", symbol)))
+        }
+    }
+
+    fn ident(&mut self) -> Result<String, ParseError> {
+        let position = self.position();
+        match self.next() {
+            Some(Token::Ident(s)) => Ok(s),
+            _ => Err(error(position, "expected identifier")),
+        }
+    }
+
+    fn integer(&mut self) -> Result<usize, ParseError> {
+        let position = self.position();
+        match self.next() {
+            Some(Token::Number(n)) if n >= 0.0 && n.fract() == 0.0 => Ok(n as usize),
+            _ => Err(error(position, "expected non-negative integer")),
+        }
+    }
+
+    fn select_item(&mut self) -> Result<SelectItem, ParseError> {
+        let position = self.position();
+        let name = self.ident()?;
+        if !self.accept_symbol("(") {
+            return Ok(SelectItem::Path(name));
+        }
+        let item = match name.to_ascii_uppercase().as_str() {
+            "COUNT" => {
+                self.expect_symbol("*")?;
+                SelectItem::Count
+            }
+            "SUM" => SelectItem::Sum(self.ident()?),
+            "MIN" => SelectItem::Min(self.ident()?),
+            "MAX" => SelectItem::Max(self.ident()?),
+            "AVG" => SelectItem::Avg(self.ident()?),
+            _ => return Err(error(position, &format!("unknown function {}", name))),
+        };
+        self.expect_symbol(")")?;
+        Ok(item)
+    }
+
+    fn condition(&mut self) -> Result<Condition, ParseError> {
+        let path = self.ident()?;
+        let position = self.position();
+        let operator = match self.next() {
+            Some(Token::Symbol("=")) => Operator::Eq,
+            Some(Token::Symbol("!=")) | Some(Token::Symbol("<>")) => Operator::Ne,
+            Some(Token::Symbol("<")) => Operator::Lt,
+            Some(Token::Symbol("<=")) => Operator::Le,
+            Some(Token::Symbol(">")) => Operator::Gt,
+            Some(Token::Symbol(">=")) => Operator::Ge,
+            _ => return Err(error(position, "expected comparison operator")),
+        };
+        let position = self.position();
+        let literal = match self.next() {
+            Some(Token::Number(n)) => Literal::Number(n),
+            Some(Token::Str(s)) => Literal::Str(s),
+            _ => return Err(error(position, "expected literal")),
+        };
+        Ok(Condition {
+            path,
+            operator,
+            literal,
+        })
+    }
+
+    /// A column is referred by the 1-based position in the select list or by the name
+    fn column(&mut self, select: &[SelectItem]) -> Result<usize, ParseError> {
+        let position = self.position();
+        if let Some(Token::Number(_)) = self.peek() {
+            let n = self.integer()?;
+            return if 1 <= n && n <= select.len() {
+                Ok(n - 1)
+            } else {
+                Err(error(position, "column position out of range"))
+            };
+        }
+        let item = self.select_item()?;
+        select
+            .iter()
+            .position(|s| *s == item)
+            .ok_or_else(|| error(position, "column is not in the select list"))
+    }
+}
+
+pub(super) fn parse(query: &str) -> Result<Query, ParseError> {
+    let mut parser = Parser {
+        tokens: tokenize(query)?,
+        idx: 0,
+        len: query.len(),
+    };
+
+    parser.expect_keyword("SELECT")?;
+    let mut select = vec![parser.select_item()?];
+    while parser.accept_symbol(",") {
+        select.push(parser.select_item()?);
+    }
+
+    parser.expect_keyword("FROM")?;
+    let event_type = parser.ident()?;
+
+    let mut conditions = vec![];
+    if parser.accept_keyword("WHERE") {
+        conditions.push(parser.condition()?);
+        while parser.accept_keyword("AND") {
+            conditions.push(parser.condition()?);
+        }
+    }
+
+    let mut group_by = vec![];
+    if parser.accept_keyword("GROUP") {
+        parser.expect_keyword("BY")?;
+        loop {
+            let position = parser.position();
+            let idx = parser.column(&select)?;
+            if select[idx].is_aggregate() {
+                return Err(error(position, "cannot group by an aggregate"));
+            }
+            group_by.push(idx);
+            if !parser.accept_symbol(",") {
+                break;
+            }
+        }
+    }
+
+    let mut order_by = None;
+    if parser.accept_keyword("ORDER") {
+        parser.expect_keyword("BY")?;
+        let column = parser.column(&select)?;
+        let descending = if parser.accept_keyword("DESC") {
+            true
+        } else {
+            parser.accept_keyword("ASC");
+            false
+        };
+        order_by = Some(OrderBy { column, descending });
+    }
+
+    let mut limit = None;
+    if parser.accept_keyword("LIMIT") {
+        limit = Some(parser.integer()?);
+    }
+
+    if parser.peek().is_some() {
+        return Err(error(parser.position(), "unexpected token"));
+    }
+
+    let aggregated = select.iter().any(|s| s.is_aggregate()) || !group_by.is_empty();
+    if aggregated {
+        if let Some(idx) = select
+            .iter()
+            .enumerate()
+            .position(|(i, s)| !s.is_aggregate() && !group_by.contains(&i))
+        {
+            return Err(error(
+                0,
+                &format!("column {} must appear in GROUP BY", select[idx].name()),
+            ));
+        }
+    }
+
+    Ok(Query {
+        select,
+        event_type,
+        conditions,
+        group_by,
+        order_by,
+        limit,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let query = parse(
+            "select sampledThread.osName, count(*) from jdk.ExecutionSample \
+             where state.name = 'STATE_RUNNABLE' and stackTrace.truncated != 1 \
+             group by 1 order by COUNT(*) desc limit 10",
+        )
+        .unwrap();
+        assert_eq!(
+            query.select,
+            vec![
+                SelectItem::Path("sampledThread.osName".to_string()),
+                SelectItem::Count
+            ]
+        );
+        assert_eq!(query.event_type, "jdk.ExecutionSample");
+        assert_eq!(
+            query.conditions,
+            vec![
+                Condition {
+                    path: "state.name".to_string(),
+                    operator: Operator::Eq,
+                    literal: Literal::Str("STATE_RUNNABLE".to_string()),
+                },
+                Condition {
+                    path: "stackTrace.truncated".to_string(),
+                    operator: Operator::Ne,
+                    literal: Literal::Number(1.0),
+                }
+            ]
+        );
+        assert_eq!(query.group_by, vec![0]);
+        assert_eq!(
+            query.order_by,
+            Some(OrderBy {
+                column: 1,
+                descending: true
+            })
+        );
+        assert_eq!(query.limit, Some(10));
+    }
+
+    #[test]
+    fn test_parse_error() {
+        let err = |q: &str| parse(q).unwrap_err();
+        assert_eq!(err("SELECT").position, 6);
+        assert_eq!(err("SELECT a FROM").message, "expected identifier");
+        assert_eq!(
+            err("SELECT a, COUNT(*) FROM t").message,
+            "column a must appear in GROUP BY"
+        );
+        assert_eq!(err("SELECT FOO(a) FROM t").message, "unknown function FOO");
+        assert_eq!(
+            err("SELECT a FROM t GROUP BY 2").message,
+            "column position out of range"
+        );
+        assert_eq!(
+            err("SELECT a FROM t WHERE a = 'x").message,
+            "unterminated string"
+        );
+        assert_eq!(err("SELECT a FROM t LIMIT 1 x").position, 24);
+        assert_eq!(
+            parse("SELECT a FROM t WHERE a = 'it''s'")
+                .unwrap()
+                .conditions[0]
+                .literal,
+            Literal::Str("it's".to_string())
+        );
+    }
+}
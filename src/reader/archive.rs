@@ -0,0 +1,125 @@
+//! Read JFR files inside a zip archive (e.g. a diagnostic bundle) without unpacking to disk.
+
+use crate::reader::{Error, JfrReader, Result};
+use std::io;
+use std::io::{Cursor, Read, Seek};
+use zip::ZipArchive;
+
+/// Upper bound of the buffer allocated upfront by the uncompressed size in the zip header.
+/// Larger files are still read, by growing the buffer as needed
+const MAX_INITIAL_CAPACITY: u64 = 64 * 1024 * 1024;
+
+/// A JFR file found in the archive
+pub struct Recording {
+    /// The path of the file in the archive
+    pub name: String,
+    pub reader: JfrReader<Cursor<Vec<u8>>>,
+}
+
+/// Iterates over the JFR files (files with `.jfr` extension) in a zip archive.
+///
+/// Each file is loaded into memory, so that it can be read by [`JfrReader`] which requires [`Seek`].
+pub struct MultiRecordingReader<R> {
+    archive: ZipArchive<R>,
+    names: Vec<String>,
+    index: usize,
+}
+
+impl<R: Read + Seek> MultiRecordingReader<R> {
+    pub fn new(inner: R) -> Result<Self> {
+        let mut archive = ZipArchive::new(inner)?;
+        let mut names = vec![];
+        for i in 0..archive.len() {
+            let file = archive.by_index_raw(i)?;
+            if !file.is_dir() && is_recording(file.name()) {
+                names.push(file.name().to_string());
+            }
+        }
+        Ok(Self {
+            archive,
+            names,
+            index: 0,
+        })
+    }
+
+    /// Returns the paths of the JFR files in the archive, in the order of iteration
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.names.iter().map(|n| n.as_str())
+    }
+
+    fn internal_next(&mut self) -> Result<Option<Recording>> {
+        while self.index < self.archive.len() {
+            let mut file = self.archive.by_index(self.index)?;
+            self.index += 1;
+            if file.is_dir() || !is_recording(file.name()) {
+                continue;
+            }
+
+            // the size in the header is not trusted, as it may be corrupted or crafted
+            let mut bytes = Vec::with_capacity(file.size().min(MAX_INITIAL_CAPACITY) as usize);
+            file.read_to_end(&mut bytes).map_err(Error::IoError)?;
+            return Ok(Some(Recording {
+                name: file.name().to_string(),
+                reader: JfrReader::new(Cursor::new(bytes)),
+            }));
+        }
+        Ok(None)
+    }
+}
+
+impl<R: Read + Seek> Iterator for MultiRecordingReader<R> {
+    type Item = Result<Recording>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.internal_next() {
+            Ok(Some(r)) => Some(Ok(r)),
+            Ok(None) => None,
+            Err(e) => Some(Err(e)),
+        }
+    }
+}
+
+fn is_recording(name: &str) -> bool {
+    name.to_ascii_lowercase().ends_with(".jfr")
+}
+
+impl From<zip::result::ZipError> for Error {
+    fn from(e: zip::result::ZipError) -> Self {
+        Error::IoError(io::Error::from(e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::test_data;
+    use std::io::Write;
+    use zip::write::FileOptions;
+    use zip::ZipWriter;
+
+    #[test]
+    fn test_read_archive() {
+        let mut zip = ZipWriter::new(Cursor::new(vec![]));
+        for name in ["profiler-wall.jfr", "profiler-multichunk.jfr"] {
+            zip.start_file(format!("bundle/{}", name), FileOptions::default())
+                .unwrap();
+            zip.write_all(&std::fs::read(test_data(name)).unwrap())
+                .unwrap();
+        }
+        zip.start_file("bundle/README.txt", FileOptions::default())
+            .unwrap();
+        zip.write_all(b"not a recording").unwrap();
+        let bytes = zip.finish().unwrap().into_inner();
+
+        let reader = MultiRecordingReader::new(Cursor::new(bytes)).unwrap();
+        assert_eq!(
+            reader.names().collect::<Vec<_>>(),
+            vec!["bundle/profiler-wall.jfr", "bundle/profiler-multichunk.jfr"]
+        );
+
+        let chunk_counts = reader
+            .map(|r| r.unwrap().reader.chunks().flatten().count())
+            .collect::<Vec<_>>();
+        assert_eq!(chunk_counts, vec![1, 3]);
+    }
+}
@@ -0,0 +1,316 @@
+//! Decoding events into a bump-allocated arena.
+//!
+//! [`crate::reader::value_descriptor::ValueDescriptor`] allocates a `Vec` for each object and array, which dominates
+//! the cost of reading large recordings.
+//! With [`for_each_event`], the whole value tree of an event lives in a single arena
+//! which is reset before the next event, so no allocation happens once the arena has grown enough.
+
+use crate::reader::byte_stream::{ByteStream, Input, StringType};
+use crate::reader::event::Accessor;
+use crate::reader::limits::LimitKind;
+use crate::reader::metadata::Metadata;
+use crate::reader::type_descriptor::{FieldDescriptor, TypeDescriptor};
+use crate::reader::{Chunk, ChunkReader, Error, Result};
+use bumpalo::collections::Vec as BumpVec;
+use bumpalo::Bump;
+
+/// Event whose value is allocated in the arena.
+/// The value is valid only until the handler of [`for_each_event`] returns.
+pub struct ArenaEvent<'c, 'b> {
+    pub byte_offset: u64,
+    pub byte_size: u64,
+    pub class: &'c TypeDescriptor,
+    pub chunk: &'c Chunk,
+    pub value: ArenaValue<'b>,
+}
+
+/// Counterpart of [`crate::reader::value_descriptor::ValueDescriptor`] whose children and strings are allocated in the arena
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ArenaValue<'b> {
+    Integer(i32),
+    Long(i64),
+    Float(f32),
+    Double(f64),
+    Character(char),
+    Boolean(bool),
+    Short(i16),
+    Byte(i8),
+    NullString,
+    String(&'b str),
+    Object {
+        class_id: i64,
+        fields: &'b [ArenaValue<'b>],
+    },
+    Array(&'b [ArenaValue<'b>]),
+    ConstantPool {
+        class_id: i64,
+        constant_index: i64,
+    },
+}
+
+impl<'b> ArenaValue<'b> {
+    /// Returns the field of the object without resolving the constant pool reference
+    pub fn get_field_raw(&self, name: &str, chunk: &Chunk) -> Option<&ArenaValue<'b>> {
+        match self {
+            ArenaValue::Object { class_id, fields } => chunk
+                .metadata
+                .type_pool
+                .get(*class_id)
+                .and_then(|t| t.field_index(name))
+                .and_then(|idx| fields.get(idx)),
+            _ => None,
+        }
+    }
+
+    /// Resolves the constant pool reference.
+    /// Constants are not allocated in the arena, so they are accessed through [`Accessor`].
+    pub fn resolve<'c>(&self, chunk: &'c Chunk) -> Option<Accessor<'c>> {
+        match self {
+            ArenaValue::ConstantPool {
+                class_id,
+                constant_index,
+            } => chunk
+                .constant_pool
+                .get(class_id, constant_index)
+                .map(|v| Accessor::new(chunk, v)),
+            _ => None,
+        }
+    }
+
+    pub fn as_i64(&self) -> Option<i64> {
+        match *self {
+            ArenaValue::Long(v) => Some(v),
+            ArenaValue::Integer(v) => Some(v as i64),
+            ArenaValue::Short(v) => Some(v as i64),
+            ArenaValue::Byte(v) => Some(v as i64),
+            _ => None,
+        }
+    }
+
+    pub fn as_f64(&self) -> Option<f64> {
+        match *self {
+            ArenaValue::Double(v) => Some(v),
+            ArenaValue::Float(v) => Some(v as f64),
+            _ => None,
+        }
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        match *self {
+            ArenaValue::Boolean(v) => Some(v),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&'b str> {
+        match *self {
+            ArenaValue::String(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    fn read<T: Input>(
+        stream: &mut ByteStream<T>,
+        class_id: i64,
+        metadata: &Metadata,
+        bump: &'b Bump,
+    ) -> Result<Self> {
+        let type_desc = metadata
+            .type_pool
+            .get(class_id)
+            .ok_or(Error::ClassNotFound(class_id))?;
+
+        if let Some(value) = Self::read_primitive(stream, type_desc, bump)? {
+            return Ok(value);
+        }
+
+        let mut fields = BumpVec::with_capacity_in(type_desc.fields.len(), bump);
+        for field_desc in type_desc.fields.iter() {
+            let value = if field_desc.array_type {
+                let count = stream.read_length(LimitKind::ArrayLength)?;
+                let mut elems = BumpVec::with_capacity_in(count, bump);
+                for _ in 0..count {
+                    elems.push(Self::read_field_single(stream, field_desc, metadata, bump)?);
+                }
+                ArenaValue::Array(elems.into_bump_slice())
+            } else {
+                Self::read_field_single(stream, field_desc, metadata, bump)?
+            };
+            fields.push(value);
+        }
+        Ok(ArenaValue::Object {
+            class_id,
+            fields: fields.into_bump_slice(),
+        })
+    }
+
+    fn read_field_single<T: Input>(
+        stream: &mut ByteStream<T>,
+        field_desc: &FieldDescriptor,
+        metadata: &Metadata,
+        bump: &'b Bump,
+    ) -> Result<Self> {
+        if field_desc.constant_pool {
+            Ok(ArenaValue::ConstantPool {
+                class_id: field_desc.class_id,
+                constant_index: stream.read_i64()?,
+            })
+        } else {
+            Self::read(stream, field_desc.class_id, metadata, bump)
+        }
+    }
+
+    fn read_primitive<T: Input>(
+        stream: &mut ByteStream<T>,
+        type_desc: &TypeDescriptor,
+        bump: &'b Bump,
+    ) -> Result<Option<Self>> {
+        let value = match type_desc.name() {
+            "int" => ArenaValue::Integer(stream.read_i32()?),
+            "long" => ArenaValue::Long(stream.read_i64()?),
+            "float" => ArenaValue::Float(stream.read_f32()?),
+            "double" => ArenaValue::Double(stream.read_f64()?),
+            "char" => ArenaValue::Character(stream.read_char()?),
+            "boolean" => ArenaValue::Boolean(stream.read_i8()? != 0),
+            "short" => ArenaValue::Short(stream.read_i16()?),
+            "byte" => ArenaValue::Byte(stream.read_i8()?),
+            "java.lang.String" => match stream.read_string()? {
+                StringType::Null => ArenaValue::NullString,
+                StringType::Empty => ArenaValue::String(""),
+                StringType::Raw(s) => ArenaValue::String(bump.alloc_str(&s)),
+                StringType::ConstantPool(idx) => ArenaValue::ConstantPool {
+                    class_id: type_desc.class_id,
+                    constant_index: idx,
+                },
+            },
+            _ => return Ok(None),
+        };
+        Ok(Some(value))
+    }
+}
+
+/// Decodes the events of the chunk one by one into the arena and invokes the handler.
+/// The arena is reset after each handler call.
+pub fn for_each_event<F>(chunk_reader: &ChunkReader, chunk: &Chunk, mut handler: F) -> Result<()>
+where
+    F: FnMut(&ArenaEvent) -> Result<()>,
+{
+    let mut bump = Bump::new();
+    let mut events = chunk_reader.events(chunk);
+    while let Some((byte_offset, byte_size, event_type)) = events.next_event_header()? {
+        let class = chunk
+            .metadata
+            .type_pool
+            .get(event_type)
+            .ok_or(Error::ClassNotFound(event_type))?;
+        let value = ArenaValue::read(events.stream(), event_type, &chunk.metadata, &bump)?;
+        handler(&ArenaEvent {
+            byte_offset,
+            byte_size,
+            class,
+            chunk,
+            value,
+        })?;
+        bump.reset();
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::reader::value_descriptor::{Primitive, ValueDescriptor};
+    use crate::reader::JfrReader;
+    use crate::test_util::test_data;
+    use std::fs::File;
+
+    #[test]
+    fn test_for_each_event() {
+        let mut reader = JfrReader::new(File::open(test_data("recording.jfr")).unwrap());
+        let (chunk_reader, chunk) = reader.chunks().next().unwrap().unwrap();
+        let mut expected = chunk_reader.events(&chunk);
+        let mut count = 0;
+
+        for_each_event(&chunk_reader, &chunk, |event| {
+            let e = expected.next().unwrap().unwrap();
+            assert_eq!(event.byte_offset, e.byte_offset);
+            assert_eq!(event.class.name(), e.class.name());
+            assert_same(&event.value, &e.value);
+            count += 1;
+            Ok(())
+        })
+        .unwrap();
+        assert!(expected.next().is_none());
+        assert!(count > 0);
+    }
+
+    #[test]
+    fn test_field_access() {
+        let mut reader = JfrReader::new(File::open(test_data("recording.jfr")).unwrap());
+        let (chunk_reader, chunk) = reader.chunks().next().unwrap().unwrap();
+        let mut jvm_name = None;
+
+        for_each_event(&chunk_reader, &chunk, |event| {
+            if event.class.name() != "jdk.JVMInformation" {
+                return Ok(());
+            }
+            let pid = event.value.get_field_raw("pid", event.chunk).unwrap();
+            assert_eq!(pid.as_i64(), Some(3741));
+            let name = event.value.get_field_raw("jvmName", event.chunk).unwrap();
+            jvm_name = match name {
+                ArenaValue::String(s) => Some(s.to_string()),
+                cp => cp
+                    .resolve(event.chunk)
+                    .and_then(|a| a.as_str())
+                    .map(String::from),
+            };
+            Ok(())
+        })
+        .unwrap();
+        assert_eq!(jvm_name.as_deref(), Some("OpenJDK 64-Bit Server VM"));
+    }
+
+    fn assert_same(a: &ArenaValue, v: &ValueDescriptor) {
+        match (a, v) {
+            (ArenaValue::Object { class_id, fields }, ValueDescriptor::Object(o)) => {
+                assert_eq!(*class_id, o.class_id);
+                assert_eq!(fields.len(), o.fields.len());
+                for (a, v) in fields.iter().zip(o.fields.iter()) {
+                    assert_same(a, v);
+                }
+            }
+            (ArenaValue::Array(elems), ValueDescriptor::Array(v)) => {
+                assert_eq!(elems.len(), v.len());
+                for (a, v) in elems.iter().zip(v.iter()) {
+                    assert_same(a, v);
+                }
+            }
+            (
+                ArenaValue::ConstantPool {
+                    class_id,
+                    constant_index,
+                },
+                ValueDescriptor::ConstantPool {
+                    class_id: c,
+                    constant_index: i,
+                },
+            ) => assert_eq!((class_id, constant_index), (c, i)),
+            (ArenaValue::String(s), v) => assert_eq!(Some(*s), <&str>::try_from(v).ok()),
+            (ArenaValue::NullString, v) => {
+                assert!(matches!(
+                    v,
+                    ValueDescriptor::Primitive(Primitive::NullString)
+                ))
+            }
+            (ArenaValue::Integer(n), v) => assert_eq!(Some(*n), i32::try_from(v).ok()),
+            (ArenaValue::Long(n), v) => assert_eq!(Some(*n), i64::try_from(v).ok()),
+            (ArenaValue::Float(n), v) => assert_eq!(Some(*n), f32::try_from(v).ok()),
+            (ArenaValue::Double(n), v) => assert_eq!(Some(*n), f64::try_from(v).ok()),
+            (ArenaValue::Boolean(b), v) => assert_eq!(Some(*b), bool::try_from(v).ok()),
+            (ArenaValue::Short(n), v) => assert_eq!(Some(*n), i16::try_from(v).ok()),
+            (ArenaValue::Byte(n), v) => assert_eq!(Some(*n), i8::try_from(v).ok()),
+            (ArenaValue::Character(_), _) => {}
+            (a, v) => panic!("mismatch: {:?} {:?}", a, v),
+        }
+    }
+}
@@ -0,0 +1,203 @@
+//! Detection of the recordings written by [async-profiler](https://github.com/async-profiler/async-profiler).
+//!
+//! async-profiler writes JFR by itself rather than through the JVM, with a subset of the JDK event types
+//! and its own types (`profiler.*`, see [`crate::reader::types::profiler`]).
+//! Its options are recorded as `jdk.ActiveSetting` events, which tell what was profiled.
+//! In `wall` mode, `jdk.ExecutionSample` also contains the samples of sleeping threads,
+//! which can be told apart by [`crate::reader::types::jdk::ExecutionSample::is_running`].
+
+use crate::reader::known_types::KnownType;
+use crate::reader::{Chunk, ChunkReader, Result};
+use std::collections::BTreeMap;
+
+const RECORDING_NAME_PREFIX: &str = "async-profiler";
+
+/// What async-profiler profiled
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum ProfilingMode {
+    /// CPU profiling by the event (e.g. `cpu`, `itimer`, `cpu-clock`)
+    Cpu(String),
+    /// Wall-clock profiling, which samples all threads regardless of their state
+    Wall,
+    Alloc,
+    Lock,
+}
+
+/// Options of async-profiler gathered from a chunk
+#[derive(Debug, Clone, Default)]
+pub struct AsyncProfilerInfo {
+    /// The version of async-profiler (e.g. `2.8.3`)
+    pub version: Option<String>,
+    /// Event type name to the options (e.g. `jdk.ActiveRecording` => `event` => `wall`).
+    /// Global options are recorded for `jdk.ActiveRecording`, and per-mode options
+    /// (e.g. `alloc`, `lock`) for the corresponding event types
+    pub settings: BTreeMap<String, BTreeMap<String, String>>,
+}
+
+impl AsyncProfilerInfo {
+    /// Returns the information if the chunk is written by async-profiler
+    pub fn read(reader: &ChunkReader, chunk: &Chunk) -> Result<Option<Self>> {
+        let type_pool = &chunk.metadata.type_pool;
+        let (recording, setting) = match (
+            type_pool.class_id_of(KnownType::ActiveRecording),
+            type_pool.class_id_of(KnownType::ActiveSetting),
+        ) {
+            (Some(r), Some(s)) => (r, s),
+            _ => return Ok(None),
+        };
+
+        let mut detected = is_async_profiler(chunk);
+        let mut info = Self::default();
+        for event in reader
+            .events(chunk)
+            .select(|t| t.class_id == recording || t.class_id == setting)
+        {
+            let event = event?;
+            let value = event.value();
+            if event.class.class_id == recording {
+                detected |= value
+                    .get_field("name")
+                    .and_then(|v| v.as_str())
+                    .is_some_and(|name| name.starts_with(RECORDING_NAME_PREFIX));
+                continue;
+            }
+            let type_name = match value
+                .get_field("id")
+                .and_then(|v| v.as_i64())
+                .and_then(|id| type_pool.get(id))
+            {
+                Some(t) => t.name(),
+                None => continue,
+            };
+            if let (Some(name), Some(v)) = (
+                value.get_field("name").and_then(|v| v.as_str()),
+                value.get_field("value").and_then(|v| v.as_str()),
+            ) {
+                if type_name == KnownType::ActiveRecording.name() && name == "version" {
+                    info.version = Some(v.to_string());
+                }
+                info.settings
+                    .entry(type_name.to_string())
+                    .or_default()
+                    .insert(name.to_string(), v.to_string());
+            }
+        }
+        Ok(if detected { Some(info) } else { None })
+    }
+
+    /// Returns the option (e.g. `event`, `interval`, `jstackdepth`)
+    pub fn option(&self, name: &str) -> Option<&str> {
+        self.setting(KnownType::ActiveRecording.name(), name)
+    }
+
+    pub fn setting(&self, type_name: &str, name: &str) -> Option<&str> {
+        self.settings
+            .get(type_name)
+            .and_then(|s| s.get(name))
+            .map(|s| s.as_str())
+    }
+
+    /// The profiling modes, as multiple modes can be enabled at once (e.g. `event=cpu,alloc,lock`)
+    pub fn modes(&self) -> Vec<ProfilingMode> {
+        let mut modes = vec![];
+        match self.option("event") {
+            Some("wall") => modes.push(ProfilingMode::Wall),
+            // `alloc` and `lock` are also accepted as the event
+            Some("alloc") | Some("lock") | None => {}
+            Some(event) => modes.push(ProfilingMode::Cpu(event.to_string())),
+        }
+        if self.is_enabled(KnownType::ObjectAllocationInNewTLAB) {
+            modes.push(ProfilingMode::Alloc);
+        }
+        if self.is_enabled(KnownType::JavaMonitorEnter) {
+            modes.push(ProfilingMode::Lock);
+        }
+        modes
+    }
+
+    fn is_enabled(&self, known_type: KnownType) -> bool {
+        self.setting(known_type.name(), "enabled") == Some("true")
+    }
+}
+
+/// Returns true if the metadata declares async-profiler specific types.
+/// This doesn't read the events, but the types are not declared by older versions.
+/// Use [`AsyncProfilerInfo::read`] to detect them.
+pub fn is_async_profiler(chunk: &Chunk) -> bool {
+    chunk
+        .metadata
+        .type_pool
+        .get_types()
+        .any(|t| t.name().starts_with("profiler."))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::reader::types::jdk::{ExecutionSample, ObjectAllocationInNewTLAB};
+    use crate::reader::{from_event, JfrReader};
+    use crate::test_util::test_data;
+    use std::fs::File;
+
+    #[test]
+    fn test_wall() {
+        let mut reader = JfrReader::new(File::open(test_data("profiler-wall.jfr")).unwrap());
+        let (chunk_reader, chunk) = reader.chunks().next().unwrap().unwrap();
+        assert!(is_async_profiler(&chunk));
+
+        let info = AsyncProfilerInfo::read(&chunk_reader, &chunk)
+            .unwrap()
+            .unwrap();
+        assert_eq!(info.version.as_deref(), Some("2.8.3"));
+        assert_eq!(info.option("jstackdepth"), Some("2048"));
+        assert_eq!(info.modes(), vec![ProfilingMode::Wall]);
+
+        let (mut running, mut sleeping) = (0, 0);
+        for event in chunk_reader.events(&chunk).flatten() {
+            if event.class.name() != "jdk.ExecutionSample" {
+                continue;
+            }
+            let sample: ExecutionSample = from_event(&event).unwrap();
+            if sample.is_running() {
+                running += 1;
+            } else {
+                sleeping += 1;
+            }
+        }
+        assert_eq!((running, sleeping), (673, 8163));
+    }
+
+    #[test]
+    fn test_alloc() {
+        let mut reader = JfrReader::new(File::open(test_data("profiler-alloc.jfr")).unwrap());
+        let (chunk_reader, chunk) = reader.chunks().next().unwrap().unwrap();
+        let info = AsyncProfilerInfo::read(&chunk_reader, &chunk)
+            .unwrap()
+            .unwrap();
+        assert_eq!(info.modes(), vec![ProfilingMode::Alloc]);
+        assert_eq!(
+            info.setting("jdk.ObjectAllocationInNewTLAB", "alloc"),
+            Some("0")
+        );
+
+        let event = chunk_reader
+            .events(&chunk)
+            .flatten()
+            .find(|e| e.class.name() == "jdk.ObjectAllocationInNewTLAB")
+            .unwrap();
+        let alloc: ObjectAllocationInNewTLAB = from_event(&event).unwrap();
+        assert_eq!(alloc.allocation_size, 40);
+        assert!(alloc.tlab_size > alloc.allocation_size);
+        assert!(alloc.object_class.is_some());
+    }
+
+    #[test]
+    fn test_not_async_profiler() {
+        let mut reader = JfrReader::new(File::open(test_data("recording.jfr")).unwrap());
+        let (chunk_reader, chunk) = reader.chunks().next().unwrap().unwrap();
+        assert!(!is_async_profiler(&chunk));
+        assert!(AsyncProfilerInfo::read(&chunk_reader, &chunk)
+            .unwrap()
+            .is_none());
+    }
+}
@@ -0,0 +1,171 @@
+//! Grouping events into fixed wall-clock windows.
+
+use crate::reader::event::Event;
+use crate::reader::Result;
+use std::collections::{BTreeMap, VecDeque};
+use std::time::Duration;
+
+/// Events whose `startTime` falls in `[start_nanos, end_nanos)`
+pub struct Bucket<'a> {
+    /// The start of the window in nanoseconds since UNIX epoch
+    pub start_nanos: i64,
+    /// The end of the window (exclusive) in nanoseconds since UNIX epoch
+    pub end_nanos: i64,
+    pub events: Vec<Event<'a>>,
+}
+
+impl<'a> Bucket<'a> {
+    /// The total size of the events in bytes
+    pub fn total_bytes(&self) -> u64 {
+        self.events.iter().map(|e| e.byte_size).sum()
+    }
+
+    /// The number of events per second in the window
+    pub fn events_per_sec(&self) -> f64 {
+        self.events.len() as f64 / self.width_secs()
+    }
+
+    /// The bytes of the events per second in the window
+    pub fn bytes_per_sec(&self) -> f64 {
+        self.total_bytes() as f64 / self.width_secs()
+    }
+
+    fn width_secs(&self) -> f64 {
+        (self.end_nanos - self.start_nanos) as f64 / 1_000_000_000.0
+    }
+}
+
+/// Iterator adapter which groups events into windows aligned to UNIX epoch.
+///
+/// Since events in a chunk are not ordered by time, all events are buffered on the first call of
+/// `next`, then windows are returned in ascending order. Windows without events between
+/// the first and the last ones are returned as empty buckets so that rates can be plotted as is.
+/// Events without `startTime` are skipped.
+pub struct Bucketed<'a, I> {
+    events: Option<I>,
+    width_nanos: i64,
+    buckets: VecDeque<Bucket<'a>>,
+    error: Option<crate::reader::Error>,
+}
+
+impl<'a, I> Bucketed<'a, I>
+where
+    I: Iterator<Item = Result<Event<'a>>>,
+{
+    /// Panics if the width is zero
+    pub fn new(events: I, width: Duration) -> Self {
+        let width_nanos = i64::try_from(width.as_nanos()).unwrap_or(i64::MAX);
+        assert!(width_nanos > 0, "width must be positive");
+        Self {
+            events: Some(events),
+            width_nanos,
+            buckets: VecDeque::new(),
+            error: None,
+        }
+    }
+
+    fn fill(&mut self, events: I) {
+        let mut windows: BTreeMap<i64, Vec<Event<'a>>> = BTreeMap::new();
+        for event in events {
+            let event = match event {
+                Ok(e) => e,
+                Err(e) => {
+                    self.error = Some(e);
+                    break;
+                }
+            };
+            let ticks = match event
+                .value
+                .get_field_raw("startTime", event.chunk)
+                .and_then(|v| i64::try_from(v).ok())
+            {
+                Some(t) => t,
+                None => continue,
+            };
+            let nanos = event.chunk.header.ticks_to_epoch_nanos(ticks);
+            let start = nanos.div_euclid(self.width_nanos) * self.width_nanos;
+            windows.entry(start).or_default().push(event);
+        }
+
+        let mut next_start = None;
+        for (start, events) in windows {
+            if let Some(mut empty_start) = next_start {
+                while empty_start < start {
+                    self.buckets.push_back(self.bucket(empty_start, vec![]));
+                    empty_start += self.width_nanos;
+                }
+            }
+            self.buckets.push_back(self.bucket(start, events));
+            next_start = Some(start.saturating_add(self.width_nanos));
+        }
+    }
+
+    fn bucket(&self, start_nanos: i64, events: Vec<Event<'a>>) -> Bucket<'a> {
+        Bucket {
+            start_nanos,
+            end_nanos: start_nanos.saturating_add(self.width_nanos),
+            events,
+        }
+    }
+}
+
+impl<'a, I> Iterator for Bucketed<'a, I>
+where
+    I: Iterator<Item = Result<Event<'a>>>,
+{
+    type Item = Result<Bucket<'a>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(events) = self.events.take() {
+            self.fill(events);
+        }
+        // the error is reported after the buckets of the events read so far
+        match self.buckets.pop_front() {
+            Some(bucket) => Some(Ok(bucket)),
+            None => self.error.take().map(Err),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::reader::JfrReader;
+    use crate::test_util::test_data;
+    use std::fs::File;
+
+    #[test]
+    fn test_bucketed() {
+        let mut reader = JfrReader::new(File::open(test_data("recording.jfr")).unwrap());
+        let (chunk_reader, chunk) = reader.chunks().next().unwrap().unwrap();
+        let total = chunk_reader.events(&chunk).count();
+
+        let buckets = chunk_reader
+            .events(&chunk)
+            .bucketed(Duration::from_secs(1))
+            .collect::<Result<Vec<_>>>()
+            .unwrap();
+        assert!(buckets.len() > 1);
+        assert!(buckets
+            .windows(2)
+            .all(|w| w[0].end_nanos == w[1].start_nanos));
+        assert!(buckets.iter().all(|b| b.start_nanos % 1_000_000_000 == 0));
+        assert!(!buckets.first().unwrap().events.is_empty());
+        assert!(!buckets.last().unwrap().events.is_empty());
+
+        let bucketed = buckets.iter().map(|b| b.events.len()).sum::<usize>();
+        // events without startTime are skipped
+        assert!(bucketed <= total);
+        for bucket in buckets.iter() {
+            assert_eq!(bucket.events_per_sec(), bucket.events.len() as f64);
+            assert_eq!(bucket.bytes_per_sec(), bucket.total_bytes() as f64);
+            for event in bucket.events.iter() {
+                let ticks = event.value().get_field("startTime").unwrap().value;
+                let nanos = chunk
+                    .header
+                    .ticks_to_epoch_nanos(i64::try_from(ticks).unwrap());
+                assert!(bucket.start_nanos <= nanos && nanos < bucket.end_nanos);
+            }
+        }
+    }
+}
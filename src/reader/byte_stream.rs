@@ -2,108 +2,179 @@
 //!
 //! Related JMC code: [SeekableInputStream.java](https://github.com/openjdk/jmc/blob/8.2.0-ga/core/org.openjdk.jmc.flightrecorder/src/main/java/org/openjdk/jmc/flightrecorder/internal/parser/v1/SeekableInputStream.java)
 
+use crate::decode;
+use crate::decode::{
+    STRING_ENCODING_CHAR_ARRAY, STRING_ENCODING_CONSTANT_POOL, STRING_ENCODING_EMPTY_STRING,
+    STRING_ENCODING_LATIN1_BYTE_ARRAY, STRING_ENCODING_NULL, STRING_ENCODING_UTF8_BYTE_ARRAY,
+};
+use crate::reader::limits::{LimitKind, Limits};
+use crate::reader::raw_string::{RawString, StringEncoding};
+use crate::reader::value_descriptor::StringRepr;
 use crate::reader::Error;
 use crate::reader::Result;
-use std::io::{Read, Seek, SeekFrom};
-
-const STRING_ENCODING_NULL: i8 = 0;
-const STRING_ENCODING_EMPTY_STRING: i8 = 1;
-const STRING_ENCODING_CONSTANT_POOL: i8 = 2;
-const STRING_ENCODING_UTF8_BYTE_ARRAY: i8 = 3;
-const STRING_ENCODING_CHAR_ARRAY: i8 = 4;
-const STRING_ENCODING_LATIN1_BYTE_ARRAY: i8 = 5;
-
-#[derive(Debug, Eq, PartialEq)]
-pub enum StringType {
-    Null,
-    Empty,
-    Raw(String),
-    ConstantPool(i64),
+use std::io::{Cursor, Read, Seek, SeekFrom};
+
+pub use crate::decode::{IntEncoding, StringType};
+
+/// Source of [`ByteStream`], which is [`decode::Input`] failing with [`Error`] in addition to [`Read`].
+pub trait Input: Read + decode::Input<Error = Error> {}
+
+impl<T: Read + decode::Input<Error = Error>> Input for T {}
+
+impl<B: AsRef<[u8]>> decode::Input for Cursor<B> {
+    type Error = Error;
+
+    fn read_exact(&mut self, buf: &mut [u8]) -> Result<()> {
+        Read::read_exact(self, buf).map_err(Error::IoError)
+    }
+
+    fn unread(&self) -> &[u8] {
+        let bytes = self.get_ref().as_ref();
+        let position = (self.position() as usize).min(bytes.len());
+        &bytes[position..]
+    }
+
+    fn consume(&mut self, n: usize) {
+        self.set_position(self.position() + n as u64);
+    }
 }
 
-#[derive(Debug)]
-pub enum IntEncoding {
-    Raw,
-    Compressed, // varint encoding, but not ZigZag
+/// [`Input`] over arbitrary [`Read`], which always takes the slow path
+pub struct Unbuffered<T>(pub T);
+
+impl<T: Read> Read for Unbuffered<T> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        self.0.read(buf)
+    }
 }
 
-#[macro_use]
-mod macros {
-    macro_rules! read_num {
-        ($self:ident, $ty:ty) => {
-            return $self.read_exact().map(<$ty>::from_be_bytes)
-        };
+impl<T: Seek> Seek for Unbuffered<T> {
+    fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+        self.0.seek(pos)
+    }
+}
+
+impl<T: Read> decode::Input for Unbuffered<T> {
+    type Error = Error;
+
+    fn read_exact(&mut self, buf: &mut [u8]) -> Result<()> {
+        self.0.read_exact(buf).map_err(Error::IoError)
     }
 }
 
 pub struct ByteStream<T> {
     inner: T,
     int_encoding: IntEncoding,
+    limits: Limits,
+    // how string values are decoded by `ValueDescriptor`
+    string_repr: StringRepr,
 }
 
-impl<T: Read> ByteStream<T> {
+impl<T: Input> ByteStream<T> {
     pub fn new(inner: T) -> Self {
         Self {
             inner,
             int_encoding: IntEncoding::Raw,
+            limits: Limits::default(),
+            string_repr: StringRepr::default(),
         }
     }
 
     pub fn read_as_bytes(&mut self, bytes: usize) -> Result<Vec<u8>> {
         let mut buf = Vec::with_capacity(bytes);
+        self.read_as_bytes_into(bytes, &mut buf)?;
+        Ok(buf)
+    }
+
+    /// Same as [`ByteStream::read_as_bytes`], but reads into `buf` reusing its allocation
+    pub fn read_as_bytes_into(&mut self, bytes: usize, buf: &mut Vec<u8>) -> Result<()> {
+        buf.clear();
+        buf.reserve(bytes);
         self.inner
             .by_ref()
             .take(bytes as u64)
-            .read_to_end(&mut buf)
+            .read_to_end(buf)
             .map_err(Error::IoError)?;
-        Ok(buf)
+        Ok(())
     }
 
     pub fn set_int_encoding(&mut self, encoding: IntEncoding) {
         self.int_encoding = encoding;
     }
 
+    pub fn int_encoding(&self) -> IntEncoding {
+        self.int_encoding
+    }
+
+    pub fn set_limits(&mut self, limits: Limits) {
+        self.limits = limits;
+    }
+
+    pub fn limits(&self) -> Limits {
+        self.limits
+    }
+
+    pub fn set_string_repr(&mut self, string_repr: StringRepr) {
+        self.string_repr = string_repr;
+    }
+
+    pub fn string_repr(&self) -> StringRepr {
+        self.string_repr
+    }
+
+    /// Fails with [`Error::LimitExceeded`] if `actual` exceeds the limit of the kind
+    pub fn check_limit(&self, kind: LimitKind, actual: u64) -> Result<()> {
+        match self.limits.limit_of(kind) {
+            Some(limit) if actual > limit => Err(Error::LimitExceeded {
+                kind,
+                limit,
+                actual,
+            }),
+            _ => Ok(()),
+        }
+    }
+
+    /// Reads the length of a string or an array, checking the limit of the kind
+    pub fn read_length(&mut self, kind: LimitKind) -> Result<usize> {
+        let length = decode::read_length(&mut self.inner, self.int_encoding)?;
+        self.check_limit(kind, length as u64)?;
+        Ok(length)
+    }
+
+    pub fn get_ref(&self) -> &T {
+        &self.inner
+    }
+
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+
     pub fn read_exact<const N: usize>(&mut self) -> Result<[u8; N]> {
-        let mut buf = [0; N];
-        self.inner.read_exact(&mut buf).map_err(Error::IoError)?;
-        Ok(buf)
+        decode::read_array(&mut self.inner)
     }
 
     pub fn read_u8(&mut self) -> Result<u8> {
-        read_num!(self, u8);
+        self.read_exact().map(u8::from_be_bytes)
     }
 
     pub fn read_i8(&mut self) -> Result<i8> {
-        read_num!(self, i8);
+        self.read_exact().map(i8::from_be_bytes)
     }
 
     pub fn read_i16(&mut self) -> Result<i16> {
-        match self.int_encoding {
-            IntEncoding::Raw => read_num!(self, i16),
-            IntEncoding::Compressed => self.read_var_i64().map(|i| i as i16),
-        }
+        decode::read_i16(&mut self.inner, self.int_encoding)
     }
 
     pub fn read_i32(&mut self) -> Result<i32> {
-        match self.int_encoding {
-            IntEncoding::Raw => read_num!(self, i32),
-            IntEncoding::Compressed => self.read_var_i64().map(|i| i as i32),
-        }
+        decode::read_i32(&mut self.inner, self.int_encoding)
     }
 
     pub fn read_i64(&mut self) -> Result<i64> {
-        match self.int_encoding {
-            IntEncoding::Raw => read_num!(self, i64),
-            IntEncoding::Compressed => self.read_var_i64(),
-        }
+        decode::read_i64(&mut self.inner, self.int_encoding)
     }
 
     pub fn read_char(&mut self) -> Result<char> {
-        let i = match self.int_encoding {
-            IntEncoding::Raw => self.read_i16()? as u32,
-            IntEncoding::Compressed => self.read_var_i64()? as u32,
-        };
-        char::try_from(i).map_err(Error::InvalidChar)
+        decode::read_char(&mut self.inner, self.int_encoding)
     }
 
     pub fn read_f32(&mut self) -> Result<f32> {
@@ -114,63 +185,64 @@ impl<T: Read> ByteStream<T> {
         self.read_exact().map(f64::from_be_bytes)
     }
 
-    fn read_var_i64(&mut self) -> Result<i64> {
-        let mut ret = 0i64;
-        for i in 0..8 {
-            let b = self.read_i8()? as i64;
-            ret += (b & 0x7f) << (7 * i);
-            if b >= 0 {
-                return Ok(ret);
-            }
-        }
-        Ok(ret + ((self.read_i8()? as i64 & 0xff) << 56))
+    pub fn read_string(&mut self) -> Result<StringType> {
+        decode::read_string(
+            &mut self.inner,
+            self.int_encoding,
+            self.limits.limit_of(LimitKind::StringLength),
+        )
     }
 
-    pub fn read_string(&mut self) -> Result<StringType> {
-        let encoding = self.read_i8()?;
-        if encoding == STRING_ENCODING_NULL {
-            return Ok(StringType::Null);
-        }
-        if encoding == STRING_ENCODING_EMPTY_STRING {
-            return Ok(StringType::Empty);
-        }
-        if encoding == STRING_ENCODING_CONSTANT_POOL {
-            return self.read_i64().map(StringType::ConstantPool);
-        }
+    /// Same as [`ByteStream::read_string`], but keeps the bytes in the declared encoding
+    pub fn read_raw_string(&mut self) -> Result<StringType<RawString>> {
+        let encoding = match self.read_i8()? {
+            STRING_ENCODING_NULL => return Ok(StringType::Null),
+            STRING_ENCODING_EMPTY_STRING => return Ok(StringType::Empty),
+            STRING_ENCODING_CONSTANT_POOL => return self.read_i64().map(StringType::ConstantPool),
+            STRING_ENCODING_UTF8_BYTE_ARRAY => StringEncoding::Utf8,
+            STRING_ENCODING_LATIN1_BYTE_ARRAY => StringEncoding::Latin1,
+            STRING_ENCODING_CHAR_ARRAY => StringEncoding::CharArray,
+            _ => return Err(Error::InvalidString),
+        };
 
-        let size = self.read_i32()? as usize;
-        if encoding == STRING_ENCODING_CHAR_ARRAY {
-            let mut buf = Vec::with_capacity(size);
+        let size = self.read_length(LimitKind::StringLength)?;
+        let bytes = if encoding == StringEncoding::CharArray {
+            let mut buf = Vec::with_capacity(size * 2);
             for _ in 0..size {
-                buf.push(self.read_char()?);
+                let c = match self.int_encoding {
+                    IntEncoding::Raw => self.read_i16()? as u16,
+                    IntEncoding::Compressed => {
+                        u16::try_from(decode::read_var_i64(&mut self.inner)?)
+                            .map_err(|_| Error::InvalidString)?
+                    }
+                };
+                buf.extend_from_slice(&c.to_be_bytes());
             }
-            return Ok(StringType::Raw(buf.iter().collect()));
-        }
-
-        let mut buf = Vec::with_capacity(size);
-        for _ in 0..size {
-            buf.push(self.read_i8()? as u8);
-        }
-        if encoding == STRING_ENCODING_LATIN1_BYTE_ARRAY {
-            return Ok(StringType::Raw(buf.iter().map(|&c| c as char).collect()));
-        }
-        if encoding == STRING_ENCODING_UTF8_BYTE_ARRAY {
-            return Ok(StringType::Raw(
-                String::from_utf8(buf).map_err(|_| Error::InvalidString)?,
-            ));
-        }
-
-        Err(Error::InvalidString)
+            buf
+        } else {
+            let mut buf = vec![0; size];
+            decode::Input::read_exact(&mut self.inner, &mut buf)?;
+            buf
+        };
+        Ok(StringType::Raw(RawString { encoding, bytes }))
     }
 }
 
-impl<T: Read + Seek> ByteStream<T> {
+impl<T: Input + Seek> ByteStream<T> {
     pub fn seek(&mut self, position: u64) -> Result<()> {
         self.inner
             .seek(SeekFrom::Start(position))
             .map(drop)
             .map_err(Error::IoError)
     }
+
+    /// Returns the length of the underlying stream, keeping the current position
+    pub fn stream_len(&mut self) -> Result<u64> {
+        let position = self.inner.stream_position().map_err(Error::IoError)?;
+        let len = self.inner.seek(SeekFrom::End(0)).map_err(Error::IoError)?;
+        self.seek(position)?;
+        Ok(len)
+    }
 }
 
 #[cfg(test)]
@@ -186,6 +258,75 @@ mod tests {
         assert_eq!(55301, s.read_i64().unwrap());
     }
 
+    #[test]
+    fn test_read_var_i64_fast_path() {
+        let values = [
+            0,
+            1,
+            127,
+            128,
+            55301,
+            i32::MAX as i64,
+            -1,
+            i64::MIN,
+            i64::MAX,
+        ];
+        let mut bytes = vec![];
+        for v in values {
+            let mut v = v as u64;
+            for _ in 0..8 {
+                if v < 0x80 {
+                    break;
+                }
+                bytes.push((v & 0x7f) as u8 | 0x80);
+                v >>= 7;
+            }
+            bytes.push(v as u8);
+        }
+
+        // the same values through the fast path and the byte-by-byte path
+        let mut fast = ByteStream::new(Cursor::new(bytes.as_slice()));
+        let mut slow = ByteStream::new(Unbuffered(Cursor::new(bytes.as_slice())));
+        fast.set_int_encoding(IntEncoding::Compressed);
+        slow.set_int_encoding(IntEncoding::Compressed);
+        for v in values {
+            assert_eq!(v, fast.read_i64().unwrap());
+            assert_eq!(v, slow.read_i64().unwrap());
+        }
+        assert!(fast.read_i64().is_err());
+        assert!(slow.read_i64().is_err());
+    }
+
+    #[test]
+    fn test_limits() {
+        let mut bytes = vec![STRING_ENCODING_UTF8_BYTE_ARRAY as u8];
+        bytes.push(11);
+        bytes.extend_from_slice("hello,world".as_bytes());
+        let mut s = ByteStream::new(Cursor::new(bytes.as_slice()));
+        s.int_encoding = IntEncoding::Compressed;
+        s.set_limits(Limits::new().max_string_length(10));
+        assert!(matches!(
+            s.read_string(),
+            Err(Error::LimitExceeded {
+                kind: LimitKind::StringLength,
+                limit: 10,
+                actual: 11
+            })
+        ));
+
+        let mut s = ByteStream::new(Cursor::new(bytes.as_slice()));
+        s.int_encoding = IntEncoding::Compressed;
+        s.set_limits(Limits::new().max_string_length(11));
+        assert!(s.read_string().is_ok());
+
+        // negative length
+        let mut s = ByteStream::new(Cursor::new([0xff, 0xff, 0xff, 0xff]));
+        assert!(matches!(
+            s.read_length(LimitKind::ArrayLength),
+            Err(Error::InvalidFormat)
+        ));
+    }
+
     #[test]
     fn test_read_string_null() {
         let bytes = [STRING_ENCODING_NULL as u8];
@@ -223,4 +364,34 @@ mod tests {
             s.read_string().unwrap()
         );
     }
+
+    #[test]
+    fn test_read_raw_string() {
+        let mut bytes = vec![STRING_ENCODING_LATIN1_BYTE_ARRAY as u8, 2, b'c', 0xe9];
+        bytes.extend_from_slice(&[STRING_ENCODING_CHAR_ARRAY as u8, 2, b'a', 0xe9, 0x01]);
+        bytes.extend_from_slice(&[STRING_ENCODING_CONSTANT_POOL as u8, 3]);
+        let mut s = ByteStream::new(Cursor::new(bytes.as_slice()));
+        s.int_encoding = IntEncoding::Compressed;
+        assert_eq!(
+            StringType::Raw(RawString {
+                encoding: StringEncoding::Latin1,
+                bytes: vec![b'c', 0xe9],
+            }),
+            s.read_raw_string().unwrap()
+        );
+        assert_eq!(
+            StringType::Raw(RawString {
+                encoding: StringEncoding::CharArray,
+                bytes: vec![0x00, b'a', 0x00, 0xe9],
+            }),
+            s.read_raw_string().unwrap()
+        );
+        assert_eq!(StringType::ConstantPool(3), s.read_raw_string().unwrap());
+
+        // the same string as decoded by read_string
+        let mut s = ByteStream::new(Cursor::new(bytes.as_slice()));
+        s.int_encoding = IntEncoding::Compressed;
+        assert_eq!(StringType::Raw("cé".to_string()), s.read_string().unwrap());
+        assert_eq!(StringType::Raw("aé".to_string()), s.read_string().unwrap());
+    }
 }
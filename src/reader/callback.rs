@@ -0,0 +1,177 @@
+//! Push-based parsing which invokes callbacks for the events of registered types.
+//!
+//! Unlike [`crate::reader::ChunkReader::events`], events of the types without handlers are skipped
+//! without decoding, and the events of each type are decoded into the same scratch value,
+//! so the per-event allocation is minimal.
+
+use crate::reader::event::Accessor;
+use crate::reader::value_descriptor::ValueDescriptor;
+use crate::reader::{JfrReader, Result};
+use rustc_hash::FxHashMap;
+use std::io::{Read, Seek};
+
+type Handler<'h> = Box<dyn FnMut(&Accessor) -> Result<()> + 'h>;
+
+/// Parser which invokes handlers registered by the event type name.
+///
+/// The [`Accessor`] passed to handlers refers to the scratch value which is overwritten by
+/// the next event, so handlers should copy the values they need.
+#[derive(Default)]
+pub struct CallbackParser<'h> {
+    // indexed by the registration order of the event type
+    handlers: Vec<(String, Vec<Handler<'h>>)>,
+}
+
+impl<'h> CallbackParser<'h> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers the handler for the event type (e.g. `jdk.ExecutionSample`).
+    /// Multiple handlers for the same type are invoked in the registration order.
+    pub fn on<F>(self, event_type: &str, mut handler: F) -> Self
+    where
+        F: FnMut(&Accessor) + 'h,
+    {
+        self.try_on(event_type, move |e| {
+            handler(e);
+            Ok(())
+        })
+    }
+
+    /// Registers the fallible handler for the event type.
+    /// The parsing stops at the first error returned by the handler.
+    pub fn try_on<F>(mut self, event_type: &str, handler: F) -> Self
+    where
+        F: FnMut(&Accessor) -> Result<()> + 'h,
+    {
+        match self
+            .handlers
+            .iter_mut()
+            .find(|(name, _)| name == event_type)
+        {
+            Some((_, handlers)) => handlers.push(Box::new(handler)),
+            None => self
+                .handlers
+                .push((event_type.to_string(), vec![Box::new(handler)])),
+        }
+        self
+    }
+
+    /// Parses all chunks and invokes the handlers
+    pub fn parse<T: Read + Seek>(&mut self, reader: &mut JfrReader<T>) -> Result<()> {
+        // scratch values are kept across chunks since the shape of the type rarely changes
+        let mut scratches = self
+            .handlers
+            .iter()
+            .map(|_| ValueDescriptor::Array(vec![]))
+            .collect::<Vec<_>>();
+
+        let mut chunks = reader.chunks();
+        while let Some(chunk) = chunks.next() {
+            let (chunk_reader, chunk) = chunk?;
+            // class ids are resolved once per chunk
+            let slots = self
+                .handlers
+                .iter()
+                .enumerate()
+                .filter_map(|(slot, (name, _))| {
+                    chunk
+                        .metadata
+                        .type_pool
+                        .get_by_name(name)
+                        .map(|t| (t.class_id, slot))
+                })
+                .collect::<FxHashMap<_, _>>();
+            if slots.is_empty() {
+                chunks.recycle(chunk_reader);
+                continue;
+            }
+
+            let mut events = chunk_reader.events(&chunk);
+            while let Some((_, _, event_type)) = events.next_event_header()? {
+                let slot = match slots.get(&event_type) {
+                    Some(slot) => *slot,
+                    None => continue,
+                };
+                let scratch = &mut scratches[slot];
+                events.read_value_into(event_type, scratch)?;
+
+                let accessor = Accessor::new(&chunk, scratch);
+                for handler in self.handlers[slot].1.iter_mut() {
+                    handler(&accessor)?;
+                }
+            }
+            chunks.recycle(chunk_reader);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::test_data;
+    use std::fs::File;
+
+    #[test]
+    fn test_callback() {
+        let mut samples = 0;
+        let mut threads = vec![];
+        let mut parks = 0;
+        let mut durations = 0;
+
+        let mut parser = CallbackParser::new()
+            .on("jdk.NativeMethodSample", |e| {
+                samples += 1;
+                let name = e
+                    .get_field("sampledThread")
+                    .and_then(|t| t.get_field("osName"))
+                    .and_then(|n| <&str>::try_from(n.value).ok())
+                    .map(String::from);
+                threads.push(name);
+            })
+            .on("jdk.ThreadPark", |_| parks += 1)
+            .on("jdk.ThreadPark", |e| {
+                durations += e
+                    .get_field("duration")
+                    .and_then(|d| i64::try_from(d.value).ok())
+                    .unwrap_or(0);
+            })
+            .on("no.such.Event", |_| unreachable!());
+
+        let mut reader = JfrReader::new(File::open(test_data("recording.jfr")).unwrap());
+        parser.parse(&mut reader).unwrap();
+        drop(parser);
+
+        assert_eq!(samples, 249);
+        assert!(threads.iter().all(|t| t.is_some()));
+        assert_eq!(parks, 237);
+
+        let mut reader = JfrReader::new(File::open(test_data("recording.jfr")).unwrap());
+        let (chunk_reader, chunk) = reader.chunks().next().unwrap().unwrap();
+        let expected = chunk_reader
+            .events(&chunk)
+            .flatten()
+            .filter(|e| e.class.name() == "jdk.ThreadPark")
+            .map(|e| i64::try_from(e.value().get_field("duration").unwrap().value).unwrap())
+            .sum::<i64>();
+        assert_eq!(durations, expected);
+    }
+
+    #[test]
+    fn test_read_into() {
+        let mut reader = JfrReader::new(File::open(test_data("profiler-wall.jfr")).unwrap());
+        let (chunk_reader, chunk) = reader.chunks().next().unwrap().unwrap();
+        let mut scratch = ValueDescriptor::Array(vec![]);
+        let mut events = chunk_reader.events(&chunk);
+        let mut expected_iter = chunk_reader.events(&chunk);
+
+        while let Some((_, _, event_type)) = events.next_event_header().unwrap() {
+            events.read_value_into(event_type, &mut scratch).unwrap();
+            let expected = expected_iter.next().unwrap().unwrap();
+            assert_eq!(format!("{:?}", scratch), format!("{:?}", expected.value));
+        }
+        assert!(expected_iter.next().is_none());
+    }
+}
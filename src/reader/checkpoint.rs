@@ -0,0 +1,33 @@
+//! Checkpoint (constant pool) events of the chunk.
+//!
+//! JVM writes a checkpoint event every time it flushes the events into the chunk (JEP 349),
+//! so the checkpoints with the flush type split the chunk into the flush segments.
+
+/// Position and attributes of a checkpoint event
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Checkpoint {
+    /// The offset of the event from the beginning of the chunk body, like [`crate::reader::event::Event::byte_offset()`]
+    pub byte_offset: u64,
+    pub byte_size: u64,
+    pub start_ticks: i64,
+    pub duration_ticks: i64,
+    /// Bit set of `CHECKPOINT_TYPE_*`
+    pub checkpoint_type: u8,
+}
+
+impl Checkpoint {
+    pub const CHECKPOINT_TYPE_FLUSH: u8 = 1;
+    pub const CHECKPOINT_TYPE_CHUNK_HEADER: u8 = 1 << 1;
+    pub const CHECKPOINT_TYPE_STATICS: u8 = 1 << 2;
+    pub const CHECKPOINT_TYPE_THREADS: u8 = 1 << 3;
+
+    /// Returns true if the checkpoint is written at the end of a flush
+    pub fn is_flush(&self) -> bool {
+        self.checkpoint_type & Self::CHECKPOINT_TYPE_FLUSH != 0
+    }
+
+    /// Returns the offset right after the event
+    pub fn end_offset(&self) -> u64 {
+        self.byte_offset + self.byte_size
+    }
+}
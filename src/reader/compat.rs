@@ -0,0 +1,255 @@
+//! JDK version detection and compatibility of event types across JDK releases.
+//!
+//! Event types are renamed, replaced or removed between JDK releases.
+//! For example, `jdk.ObjectAllocationSample` (JDK 16) supersedes the TLAB allocation events
+//! for allocation profiling, and the biased locking events are gone since JDK 18.
+//! [`CompatEvent`] maps such events to one canonical name and field names,
+//! so that tools can handle recordings of any JDK in the same way.
+
+use crate::reader::event::{Accessor, Event};
+use crate::reader::known_types::KnownType;
+use crate::reader::{Chunk, ChunkReader, Result};
+use std::fmt;
+use std::fmt::Formatter;
+
+/// Version of the recorded JDK
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash, PartialOrd, Ord)]
+pub struct JdkVersion {
+    /// The feature version (e.g. 8, 11, 17)
+    pub feature: u32,
+    pub interim: u32,
+    pub update: u32,
+}
+
+impl JdkVersion {
+    /// Parses the `jvmVersion` of `jdk.JVMInformation`
+    /// (e.g. `OpenJDK 64-Bit Server VM (11.0.16+8) for linux-aarch64 JRE (11.0.16+8), built on ...`)
+    /// or a bare version string (e.g. `17.0.4.1+1`, `1.8.0_262-b10`)
+    pub fn parse(s: &str) -> Option<Self> {
+        let version = match s.find("JRE (") {
+            Some(idx) => &s[idx + "JRE (".len()..],
+            None => s.trim(),
+        };
+        let version = version
+            .split(|c: char| c == '+' || c == '-' || c == ')' || c.is_whitespace())
+            .next()?;
+
+        // JDK 8 and earlier use `1.<feature>.0_<update>`
+        if let Some(legacy) = version.strip_prefix("1.") {
+            let (version, update) = legacy.split_once('_').unwrap_or((legacy, "0"));
+            return Some(Self {
+                feature: version.split('.').next()?.parse().ok()?,
+                interim: 0,
+                update: update.parse().ok()?,
+            });
+        }
+
+        let mut components = version.split('.');
+        let feature = components.next()?.parse().ok()?;
+        let mut next = || components.next().map_or(Some(0), |c| c.parse::<u32>().ok());
+        Some(Self {
+            feature,
+            interim: next()?,
+            update: next()?,
+        })
+    }
+
+    /// Detects the version from `jdk.JVMInformation` event of the chunk
+    pub fn detect(reader: &ChunkReader, chunk: &Chunk) -> Result<Option<Self>> {
+        let class_id = match chunk
+            .metadata
+            .type_pool
+            .class_id_of(KnownType::JVMInformation)
+        {
+            Some(id) => id,
+            None => return Ok(None),
+        };
+        for event in reader.events(chunk).select(|t| t.class_id == class_id) {
+            let event = event?;
+            if let Some(version) = event
+                .value()
+                .get_field("jvmVersion")
+                .and_then(|v| v.as_str())
+                .and_then(Self::parse)
+            {
+                return Ok(Some(version));
+            }
+        }
+        Ok(None)
+    }
+}
+
+impl fmt::Display for JdkVersion {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}.{}", self.feature, self.interim, self.update)
+    }
+}
+
+/// Event types replaced by other types: (old name, canonical name)
+const EVENT_ALIASES: &[(&str, &str)] = &[
+    (
+        "jdk.ObjectAllocationInNewTLAB",
+        "jdk.ObjectAllocationSample",
+    ),
+    (
+        "jdk.ObjectAllocationOutsideTLAB",
+        "jdk.ObjectAllocationSample",
+    ),
+];
+
+/// Fields which have different names in the old event types: (event type, canonical field, field)
+const FIELD_ALIASES: &[(&str, &str, &str)] = &[
+    // the TLAB size approximates the allocations sampled by the new TLAB
+    ("jdk.ObjectAllocationInNewTLAB", "weight", "tlabSize"),
+    (
+        "jdk.ObjectAllocationOutsideTLAB",
+        "weight",
+        "allocationSize",
+    ),
+];
+
+/// Event types removed from the JDK: (name, the feature version which removed the type)
+const REMOVED_EVENTS: &[(&str, u32)] = &[
+    ("jdk.BiasedLockRevocation", 18),
+    ("jdk.BiasedLockSelfRevocation", 18),
+    ("jdk.BiasedLockClassRevocation", 18),
+];
+
+/// Returns the canonical name of the event type, which is the name itself unless it has been replaced
+pub fn canonical_name(name: &str) -> &str {
+    EVENT_ALIASES
+        .iter()
+        .find(|(old, _)| *old == name)
+        .map_or(name, |(_, canonical)| canonical)
+}
+
+/// Returns the event type names which map to the canonical name, including itself
+pub fn aliases_of(canonical: &str) -> impl Iterator<Item = &str> {
+    std::iter::once(canonical).chain(
+        EVENT_ALIASES
+            .iter()
+            .filter(move |(_, c)| *c == canonical)
+            .map(|(old, _)| *old),
+    )
+}
+
+/// Returns false if the event type is known to be absent in the JDK version
+pub fn is_available(name: &str, version: JdkVersion) -> bool {
+    if let Some(known) = KnownType::from_name(name) {
+        if known.since() > version.feature {
+            return false;
+        }
+    }
+    !REMOVED_EVENTS
+        .iter()
+        .any(|(removed, since)| *removed == name && *since <= version.feature)
+}
+
+/// Event accessed by the canonical type name and field names
+pub struct CompatEvent<'a> {
+    event: &'a Event<'a>,
+}
+
+impl<'a> CompatEvent<'a> {
+    pub fn new(event: &'a Event<'a>) -> Self {
+        Self { event }
+    }
+
+    pub fn event(&self) -> &'a Event<'a> {
+        self.event
+    }
+
+    pub fn canonical_name(&self) -> &'a str {
+        canonical_name(self.event.class.name())
+    }
+
+    /// Returns the field by the canonical name, falling back to the field with the same name
+    pub fn get_field(&self, name: &str) -> Option<Accessor<'a>> {
+        let type_name = self.event.class.name();
+        let field = FIELD_ALIASES
+            .iter()
+            .find(|(t, canonical, _)| *t == type_name && *canonical == name)
+            .map_or(name, |(_, _, field)| field);
+        self.event.value().get_field(field)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::reader::JfrReader;
+    use crate::test_util::test_data;
+    use std::fs::File;
+
+    #[test]
+    fn test_parse() {
+        let v = |feature, interim, update| JdkVersion {
+            feature,
+            interim,
+            update,
+        };
+        assert_eq!(
+            JdkVersion::parse("OpenJDK 64-Bit Server VM (11.0.16+8) for linux-aarch64 JRE (11.0.16+8), built on Jul 19 2022"),
+            Some(v(11, 0, 16))
+        );
+        assert_eq!(JdkVersion::parse("17.0.4.1+1"), Some(v(17, 0, 4)));
+        assert_eq!(JdkVersion::parse("21+35"), Some(v(21, 0, 0)));
+        assert_eq!(
+            JdkVersion::parse(
+                "OpenJDK 64-Bit Server VM (25.262-b10) for linux-amd64 JRE (1.8.0_262-b10)"
+            ),
+            Some(v(8, 0, 262))
+        );
+        assert_eq!(JdkVersion::parse("unknown"), None);
+        assert_eq!(v(17, 0, 4).to_string(), "17.0.4");
+        assert!(v(8, 0, 262) < v(11, 0, 0));
+    }
+
+    #[test]
+    fn test_detect() {
+        for (file, feature) in [("recording.jfr", 11), ("recording-2_1.jfr", 17)] {
+            let mut reader = JfrReader::new(File::open(test_data(file)).unwrap());
+            let (chunk_reader, chunk) = reader.chunks().next().unwrap().unwrap();
+            let version = JdkVersion::detect(&chunk_reader, &chunk).unwrap().unwrap();
+            assert_eq!(version.feature, feature);
+        }
+    }
+
+    #[test]
+    fn test_compat_event() {
+        let mut reader = JfrReader::new(File::open(test_data("profiler-alloc.jfr")).unwrap());
+        let (chunk_reader, chunk) = reader.chunks().next().unwrap().unwrap();
+        let event = chunk_reader
+            .events(&chunk)
+            .flatten()
+            .find(|e| e.class.name() == "jdk.ObjectAllocationInNewTLAB")
+            .unwrap();
+
+        let compat = CompatEvent::new(&event);
+        assert_eq!(compat.canonical_name(), "jdk.ObjectAllocationSample");
+        assert_eq!(
+            compat.get_field("weight").and_then(|v| v.as_i64()),
+            event.value().get_field("tlabSize").and_then(|v| v.as_i64())
+        );
+        assert!(compat.get_field("objectClass").is_some());
+    }
+
+    #[test]
+    fn test_availability() {
+        let jdk11 = JdkVersion::parse("11.0.16").unwrap();
+        let jdk21 = JdkVersion::parse("21").unwrap();
+        assert!(!is_available("jdk.ObjectAllocationSample", jdk11));
+        assert!(is_available("jdk.ObjectAllocationSample", jdk21));
+        assert!(is_available("jdk.BiasedLockRevocation", jdk11));
+        assert!(!is_available("jdk.BiasedLockRevocation", jdk21));
+        assert_eq!(
+            aliases_of("jdk.ObjectAllocationSample").collect::<Vec<_>>(),
+            [
+                "jdk.ObjectAllocationSample",
+                "jdk.ObjectAllocationInNewTLAB",
+                "jdk.ObjectAllocationOutsideTLAB"
+            ]
+        );
+        assert_eq!(canonical_name("jdk.ThreadPark"), "jdk.ThreadPark");
+    }
+}
@@ -0,0 +1,238 @@
+//! Which event types were enabled in the recording, with their thresholds and periods.
+//!
+//! Event types which were disabled have no events, which is indistinguishable from
+//! "nothing happened" by reading the events alone.
+//! [`RecordingConfiguration`] tells tools whether the data is missing because of the settings,
+//! e.g. "allocation profiling was disabled in this recording".
+
+use crate::reader::known_types::KnownType;
+use crate::reader::{Chunk, ChunkReader, Result};
+use rustc_hash::FxHashMap;
+use std::collections::BTreeMap;
+
+/// Settings of an event type
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct EventTypeConfiguration {
+    pub class_id: i64,
+    pub name: String,
+    pub label: Option<String>,
+    /// Setting name to value (e.g. `threshold` => `20 ms`)
+    pub settings: BTreeMap<String, String>,
+}
+
+impl EventTypeConfiguration {
+    pub fn setting(&self, name: &str) -> Option<&str> {
+        self.settings.get(name).map(|s| s.as_str())
+    }
+
+    /// Returns true unless the `enabled` setting is `false`
+    pub fn enabled(&self) -> bool {
+        self.setting("enabled") != Some("false")
+    }
+
+    pub fn threshold(&self) -> Option<&str> {
+        self.setting("threshold")
+    }
+
+    /// The threshold in nanoseconds
+    pub fn threshold_nanos(&self) -> Option<i64> {
+        self.threshold().and_then(parse_timespan)
+    }
+
+    /// The period, which is either a duration (e.g. `20 ms`) or
+    /// `everyChunk`, `beginChunk` and `endChunk`
+    pub fn period(&self) -> Option<&str> {
+        self.setting("period")
+    }
+
+    /// The period in nanoseconds, or None if the period is not a duration
+    pub fn period_nanos(&self) -> Option<i64> {
+        self.period().and_then(parse_timespan)
+    }
+
+    pub fn stack_trace(&self) -> Option<bool> {
+        match self.setting("stackTrace") {
+            Some("true") => Some(true),
+            Some("false") => Some(false),
+            _ => None,
+        }
+    }
+}
+
+/// Settings of the event types in a chunk, sorted by the type name
+#[derive(Debug, Clone, Default)]
+pub struct RecordingConfiguration {
+    pub event_types: Vec<EventTypeConfiguration>,
+}
+
+impl RecordingConfiguration {
+    /// Reads the settings applied to the recording from `jdk.ActiveSetting` events,
+    /// falling back to the default values declared in the metadata
+    pub fn read(reader: &ChunkReader, chunk: &Chunk) -> Result<Self> {
+        let mut config = Self::from(chunk);
+        let active_setting = match chunk
+            .metadata
+            .type_pool
+            .class_id_of(KnownType::ActiveSetting)
+        {
+            Some(id) => id,
+            None => return Ok(config),
+        };
+        let indices: FxHashMap<i64, usize> = config
+            .event_types
+            .iter()
+            .enumerate()
+            .map(|(idx, t)| (t.class_id, idx))
+            .collect();
+
+        for event in reader
+            .events(chunk)
+            .select(|t| t.class_id == active_setting)
+        {
+            let event = event?;
+            let value = event.value();
+            let (id, name, setting) = match (
+                value.get_field("id").and_then(|v| v.as_i64()),
+                value.get_field("name").and_then(|v| v.as_str()),
+                value.get_field("value").and_then(|v| v.as_str()),
+            ) {
+                (Some(id), Some(name), Some(setting)) => (id, name, setting),
+                _ => continue,
+            };
+            // settings are emitted in order, so the later one wins
+            if let Some(&idx) = indices.get(&id) {
+                config.event_types[idx]
+                    .settings
+                    .insert(name.to_string(), setting.to_string());
+            }
+        }
+        Ok(config)
+    }
+
+    pub fn get(&self, type_name: &str) -> Option<&EventTypeConfiguration> {
+        self.event_types.iter().find(|t| t.name == type_name)
+    }
+
+    /// Returns true if the event type exists in the recording and was enabled
+    pub fn is_enabled(&self, type_name: &str) -> bool {
+        self.get(type_name).is_some_and(|t| t.enabled())
+    }
+
+    pub fn enabled(&self) -> impl Iterator<Item = &EventTypeConfiguration> {
+        self.event_types.iter().filter(|t| t.enabled())
+    }
+
+    pub fn disabled(&self) -> impl Iterator<Item = &EventTypeConfiguration> {
+        self.event_types.iter().filter(|t| !t.enabled())
+    }
+}
+
+/// Only the default values declared in the metadata.
+/// Use [`RecordingConfiguration::read`] for the values actually applied to the recording.
+impl From<&Chunk> for RecordingConfiguration {
+    fn from(chunk: &Chunk) -> Self {
+        let mut event_types = chunk
+            .metadata
+            .type_pool
+            .event_types()
+            .map(|t| EventTypeConfiguration {
+                class_id: t.class_id,
+                name: t.name().to_string(),
+                label: t.label().map(|s| s.to_string()),
+                settings: t
+                    .settings()
+                    .iter()
+                    .filter_map(|s| {
+                        s.default_value()
+                            .map(|v| (s.name().to_string(), v.to_string()))
+                    })
+                    .collect(),
+            })
+            .collect::<Vec<_>>();
+        event_types.sort_by(|a, b| a.name.cmp(&b.name));
+        Self { event_types }
+    }
+}
+
+/// Parses the timespan in the format of JFR settings (e.g. `20 ms`) into nanoseconds
+fn parse_timespan(s: &str) -> Option<i64> {
+    let (value, unit) = s.trim().split_once(' ')?;
+    let value: i64 = value.parse().ok()?;
+    let nanos_per_unit = match unit.trim() {
+        "ns" => 1,
+        "us" => 1_000,
+        "ms" => 1_000_000,
+        "s" => 1_000_000_000,
+        "m" => 60_000_000_000,
+        "h" => 3_600_000_000_000,
+        "d" => 86_400_000_000_000,
+        _ => return None,
+    };
+    value.checked_mul(nanos_per_unit)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::reader::JfrReader;
+    use crate::test_util::test_data;
+    use std::fs::File;
+
+    #[test]
+    fn test_read() {
+        let mut reader = JfrReader::new(File::open(test_data("recording.jfr")).unwrap());
+        let (chunk_reader, chunk) = reader.chunks().next().unwrap().unwrap();
+        let config = RecordingConfiguration::read(&chunk_reader, &chunk).unwrap();
+
+        let sample = config.get("jdk.ExecutionSample").unwrap();
+        assert!(sample.enabled());
+        assert_eq!(sample.period(), Some("20 ms"));
+        assert_eq!(sample.period_nanos(), Some(20_000_000));
+
+        let park = config.get("jdk.ThreadPark").unwrap();
+        assert!(park.enabled());
+        assert_eq!(park.stack_trace(), Some(true));
+
+        assert!(!config.is_enabled("jdk.ObjectAllocationInNewTLAB"));
+        assert!(config
+            .disabled()
+            .any(|t| t.name == "jdk.ObjectAllocationOutsideTLAB"));
+        assert!(!config.is_enabled("no.such.Event"));
+        assert_eq!(
+            config
+                .get("jdk.ThreadAllocationStatistics")
+                .unwrap()
+                .period(),
+            Some("everyChunk")
+        );
+        assert_eq!(
+            config
+                .get("jdk.ThreadAllocationStatistics")
+                .unwrap()
+                .period_nanos(),
+            None
+        );
+        assert!(config.event_types.windows(2).all(|w| w[0].name < w[1].name));
+    }
+
+    #[test]
+    fn test_from_metadata() {
+        let mut reader = JfrReader::new(File::open(test_data("recording.jfr")).unwrap());
+        let (_, chunk) = reader.chunks().next().unwrap().unwrap();
+        let config = RecordingConfiguration::from(&chunk);
+
+        // the defaults declared in the metadata, which differ from the applied values
+        let park = config.get("jdk.ThreadPark").unwrap();
+        assert_eq!(park.setting("enabled"), Some("false"));
+        assert_eq!(park.threshold_nanos(), Some(0));
+    }
+
+    #[test]
+    fn test_parse_timespan() {
+        assert_eq!(parse_timespan("0 ns"), Some(0));
+        assert_eq!(parse_timespan("10 ms"), Some(10_000_000));
+        assert_eq!(parse_timespan("1 h"), Some(3_600_000_000_000));
+        assert_eq!(parse_timespan("everyChunk"), None);
+        assert_eq!(parse_timespan("1 fortnight"), None);
+    }
+}
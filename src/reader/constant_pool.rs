@@ -1,16 +1,62 @@
-use crate::reader::byte_stream::ByteStream;
+use crate::reader::byte_stream::{ByteStream, Input};
+use crate::reader::checkpoint::Checkpoint;
 use crate::reader::metadata::Metadata;
 
-use crate::reader::value_descriptor::ValueDescriptor;
+use crate::reader::known_types::KnownType;
+use crate::reader::limits::LimitKind;
+use crate::reader::value_descriptor::{Primitive, ValueDescriptor};
 use crate::reader::Error;
 use crate::reader::{ChunkHeader, Result};
 use crate::EVENT_TYPE_CONSTANT_POOL;
-use rustc_hash::FxHashMap;
-use std::io::{Read, Seek};
+use rustc_hash::{FxHashMap, FxHashSet};
+use std::io::Seek;
 
 #[derive(Debug, Default)]
 pub struct ConstantPool {
     pub(crate) inner: FxHashMap<ConstantPoolKey, ValueDescriptor>,
+    /// The checkpoint events the constants are read from, ordered by the offset
+    pub(crate) checkpoints: Vec<Checkpoint>,
+}
+
+/// Constant indices of `java.lang.Thread` by thread ids
+#[derive(Debug, Default)]
+pub(crate) struct ThreadIndex {
+    pub(crate) by_java_id: FxHashMap<i64, i64>,
+    pub(crate) by_os_id: FxHashMap<i64, i64>,
+}
+
+impl ThreadIndex {
+    pub(crate) fn new(constant_pool: &ConstantPool, metadata: &Metadata) -> Self {
+        let mut index = Self::default();
+        let class_id = match metadata.type_pool.class_id_of(KnownType::Thread) {
+            Some(id) => id,
+            None => return index,
+        };
+        let thread_type = metadata.type_pool.get(class_id);
+        let id_of = |value: &ValueDescriptor, name: &str| {
+            let (field_idx, _) = thread_type?.get_field(name)?;
+            match value {
+                ValueDescriptor::Object(o) => match o.fields.get(field_idx)? {
+                    ValueDescriptor::Primitive(Primitive::Long(id)) => Some(*id),
+                    _ => None,
+                },
+                _ => None,
+            }
+        };
+        for constant_index in constant_pool.indices_of(class_id) {
+            let value = match constant_pool.get(&class_id, &constant_index) {
+                Some(v) => v,
+                None => continue,
+            };
+            if let Some(id) = id_of(value, "javaThreadId") {
+                index.by_java_id.insert(id, constant_index);
+            }
+            if let Some(id) = id_of(value, "osThreadId") {
+                index.by_os_id.insert(id, constant_index);
+            }
+        }
+        index
+    }
 }
 
 #[derive(Debug, Default, Hash, Eq, PartialEq, Ord, PartialOrd, Copy, Clone)]
@@ -20,7 +66,7 @@ pub struct ConstantPoolKey {
 }
 
 impl ConstantPool {
-    pub fn try_new<T: Read + Seek>(
+    pub fn try_new<T: Input + Seek>(
         stream: &mut ByteStream<T>,
         header: &ChunkHeader,
         metadata: &Metadata,
@@ -31,8 +77,17 @@ impl ConstantPool {
         while delta != 0 {
             offset += delta;
             stream.seek(offset as u64)?;
-            delta = Self::read_constant_pool_event(stream, &mut constant_pool, metadata)?;
+            let (checkpoint, next_delta) = Self::read_constant_pool_event(
+                stream,
+                &mut constant_pool,
+                metadata,
+                (offset as u64).saturating_sub(header.body_start_offset()),
+            )?;
+            constant_pool.checkpoints.push(checkpoint);
+            delta = next_delta;
         }
+        // the chain goes back from the last checkpoint
+        constant_pool.checkpoints.sort_by_key(|c| c.byte_offset);
 
         Ok(constant_pool)
     }
@@ -54,38 +109,97 @@ impl ConstantPool {
         })
     }
 
-    fn read_constant_pool_event<T: Read + Seek>(
+    /// Removes the constants which are not reachable from the roots,
+    /// following the references inside the constants transitively
+    pub(crate) fn retain_reachable(&mut self, roots: Vec<ConstantPoolKey>) {
+        let mut reachable = FxHashSet::default();
+        let mut stack = roots;
+        while let Some(key) = stack.pop() {
+            if !reachable.insert(key) {
+                continue;
+            }
+            if let Some(value) = self.inner.get(&key) {
+                collect_references(value, &mut stack);
+            }
+        }
+        self.inner.retain(|key, _| reachable.contains(key));
+    }
+
+    /// Returns the constant indices of the class in ascending order
+    pub(crate) fn indices_of(&self, class_id: i64) -> Vec<i64> {
+        let mut indices = self
+            .inner
+            .keys()
+            .filter(|k| k.class_id == class_id)
+            .map(|k| k.constant_index)
+            .collect::<Vec<_>>();
+        indices.sort_unstable();
+        indices
+    }
+
+    pub(crate) fn get_mut(&mut self, key: &ConstantPoolKey) -> Option<&mut ValueDescriptor> {
+        self.inner.get_mut(key)
+    }
+
+    fn read_constant_pool_event<T: Input + Seek>(
         stream: &mut ByteStream<T>,
         constant_pool: &mut ConstantPool,
         metadata: &Metadata,
-    ) -> Result<i64> {
-        // size
-        stream.read_i32()?;
+        byte_offset: u64,
+    ) -> Result<(Checkpoint, i64)> {
+        let size = stream.read_i32()?;
         if stream.read_i64()? != EVENT_TYPE_CONSTANT_POOL {
             return Err(Error::InvalidFormat);
         }
 
-        // start
-        stream.read_i64()?;
-        // duration
-        stream.read_i64()?;
-
+        let start_ticks = stream.read_i64()?;
+        let duration_ticks = stream.read_i64()?;
         let delta = stream.read_i64()?;
-        // flush
-        stream.read_i8()?;
+        let checkpoint = Checkpoint {
+            byte_offset,
+            byte_size: size.max(0) as u64,
+            start_ticks,
+            duration_ticks,
+            checkpoint_type: stream.read_i8()? as u8,
+        };
         let pool_count = stream.read_i32()?;
 
         for _ in 0..pool_count {
             let class_id = stream.read_i64()?;
             let constant_count = stream.read_i32()?;
+            stream.check_limit(
+                LimitKind::ConstantPoolSize,
+                constant_pool.inner.len() as u64 + constant_count.max(0) as u64,
+            )?;
 
             for _ in 0..constant_count {
                 let constant_index = stream.read_i64()?;
-                let value = ValueDescriptor::try_new(stream, class_id, metadata)?;
+                let value = ValueDescriptor::try_new(stream, class_id, metadata).map_err(|e| {
+                    e.in_context(|| match metadata.type_pool.get(class_id) {
+                        Some(t) => format!("{}#{}", t.name(), constant_index),
+                        None => format!("{}#{}", class_id, constant_index),
+                    })
+                })?;
                 constant_pool.register(class_id, constant_index, value);
             }
         }
 
-        Ok(delta)
+        Ok((checkpoint, delta))
+    }
+}
+
+/// Pushes the constant pool references inside the value
+pub(crate) fn collect_references(value: &ValueDescriptor, keys: &mut Vec<ConstantPoolKey>) {
+    match value {
+        ValueDescriptor::Object(o) => o.fields.iter().for_each(|v| collect_references(v, keys)),
+        ValueDescriptor::Array(a) => a.iter().for_each(|v| collect_references(v, keys)),
+        ValueDescriptor::ConstantPool {
+            class_id,
+            constant_index,
+        } => keys.push(ConstantPoolKey {
+            class_id: *class_id,
+            constant_index: *constant_index,
+        }),
+        ValueDescriptor::Primitive(_) => {}
     }
 }
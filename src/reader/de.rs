@@ -1,19 +1,92 @@
 use crate::reader::event::Event;
+use crate::reader::type_descriptor::FieldDescriptor;
 use crate::reader::value_descriptor::{Object, Primitive, ValueDescriptor};
 use crate::reader::{Chunk, Error};
-use serde::de::value::StrDeserializer;
+use serde::de::value::{BorrowedStrDeserializer, StrDeserializer};
 use serde::de::{DeserializeSeed, IntoDeserializer, Visitor};
 use serde::forward_to_deserialize_any;
-use std::fmt::Display;
+use std::borrow::Cow;
+use std::fmt::{self, Display};
+
+/// Newtype names to tell the deserializer to convert the value to nanoseconds
+const TIMESPAN: &str = "$jfrs::reader::de::Timespan";
+const TIMESTAMP: &str = "$jfrs::reader::de::Timestamp";
 
 struct Deserializer<'de> {
     chunk: &'de Chunk,
     value: &'de ValueDescriptor,
+    // The descriptor of the field which holds the value, to interpret the unit
+    field: Option<&'de FieldDescriptor>,
 }
 
 impl<'de> Deserializer<'de> {
     pub fn new(chunk: &'de Chunk, value: &'de ValueDescriptor) -> Self {
-        Self { chunk, value }
+        Self {
+            chunk,
+            value,
+            field: None,
+        }
+    }
+
+    fn with_field(
+        chunk: &'de Chunk,
+        value: &'de ValueDescriptor,
+        field: Option<&'de FieldDescriptor>,
+    ) -> Self {
+        Self {
+            chunk,
+            value,
+            field,
+        }
+    }
+
+    /// Converts the value of timespan field to nanoseconds
+    fn timespan_nanos(&self) -> Option<i128> {
+        let v = i64::try_from(self.value).ok()?;
+        self.field?.timespan_nanos(v, &self.chunk.header)
+    }
+
+    /// Converts the value of timestamp field to nanoseconds since UNIX epoch
+    fn timestamp_nanos(&self) -> Option<i128> {
+        let v = i64::try_from(self.value).ok()?;
+        self.field?.timestamp_nanos(v, &self.chunk.header)
+    }
+
+    fn resolve(&self) -> Result<&'de ValueDescriptor, Error> {
+        match self.value {
+            ValueDescriptor::ConstantPool {
+                class_id,
+                constant_index,
+            } => self
+                .chunk
+                .constant_pool
+                .get(class_id, constant_index)
+                .ok_or_else(|| {
+                    Error::DeserializeError(format!(
+                        "Not found in constant pool: class_id={}, index={}",
+                        class_id, constant_index
+                    ))
+                }),
+            _ => Ok(self.value),
+        }
+    }
+
+    /// Returns the string which represents the enum variant.
+    /// Besides plain strings, JFR often represents enum-like values as constant pool objects
+    /// with a single string field (e.g. `jdk.types.FrameType`, `jdk.types.GCCause`).
+    fn variant_name(&self) -> Result<&'de str, Error> {
+        let value = self.resolve()?;
+        let variant = match value {
+            ValueDescriptor::Object(obj) if obj.fields.len() == 1 => {
+                Deserializer::new(self.chunk, &obj.fields[0]).resolve()?
+            }
+            _ => value,
+        };
+        match variant {
+            ValueDescriptor::Primitive(p) => p.as_str(),
+            _ => None,
+        }
+        .ok_or_else(|| Error::DeserializeError("Expected string for enum variant".to_string()))
     }
 }
 
@@ -31,6 +104,7 @@ where
     T: serde::de::Deserialize<'a>,
 {
     T::deserialize(Deserializer::new(event.chunk, &event.value))
+        .map_err(|e| e.in_context(|| event.class.name().to_string()))
 }
 
 pub fn from_value_descriptor<'a, T>(
@@ -40,7 +114,25 @@ pub fn from_value_descriptor<'a, T>(
 where
     T: serde::de::Deserialize<'a>,
 {
-    T::deserialize(Deserializer::new(chunk, value))
+    T::deserialize(Deserializer::new(chunk, value)).map_err(|e| match value {
+        ValueDescriptor::Object(obj) => match chunk.metadata.type_pool.get(obj.class_id) {
+            Some(t) => e.in_context(|| t.name().to_string()),
+            None => e,
+        },
+        _ => e,
+    })
+}
+
+/// Prepends the field name to the path of the error, merging it with the array index
+/// added by [`ArrayDeserializer`] (e.g. `frames` and `[3]` into `frames[3]`)
+fn in_field(e: Error, name: &str) -> Error {
+    match e {
+        Error::Decode { mut path, source } if path[0].starts_with('[') => {
+            path[0].insert_str(0, name);
+            Error::Decode { path, source }
+        }
+        e => e.in_context(|| name.to_string()),
+    }
 }
 
 struct ObjectDeserializer<'de> {
@@ -79,10 +171,22 @@ impl<'de> serde::de::MapAccess<'de> for ObjectDeserializer<'de> {
         V: DeserializeSeed<'de>,
     {
         assert!(self.field_idx < self.value.fields.len());
-        let value = seed.deserialize(Deserializer::new(
-            self.chunk,
-            &self.value.fields[self.field_idx],
-        ))?;
+        let field = self
+            .chunk
+            .metadata
+            .type_pool
+            .get(self.value.class_id)
+            .and_then(|t| t.fields.get(self.field_idx));
+        let value = seed
+            .deserialize(Deserializer::with_field(
+                self.chunk,
+                &self.value.fields[self.field_idx],
+                field,
+            ))
+            .map_err(|e| match field {
+                Some(f) => in_field(e, f.name()),
+                None => e,
+            })?;
         self.field_idx += 1;
         Ok(value)
     }
@@ -90,6 +194,7 @@ impl<'de> serde::de::MapAccess<'de> for ObjectDeserializer<'de> {
 
 struct ArrayDeserializer<'de> {
     chunk: &'de Chunk,
+    field: Option<&'de FieldDescriptor>,
     array_idx: usize,
     value: &'de Vec<ValueDescriptor>,
 }
@@ -104,7 +209,14 @@ impl<'de> serde::de::SeqAccess<'de> for ArrayDeserializer<'de> {
         if self.array_idx >= self.value.len() {
             return Ok(None);
         }
-        let value = seed.deserialize(Deserializer::new(self.chunk, &self.value[self.array_idx]))?;
+        let idx = self.array_idx;
+        let value = seed
+            .deserialize(Deserializer::with_field(
+                self.chunk,
+                &self.value[idx],
+                self.field,
+            ))
+            .map_err(|e| e.in_context(|| format!("[{}]", idx)))?;
         self.array_idx += 1;
         Ok(Some(value))
     }
@@ -120,28 +232,45 @@ impl<'de> serde::Deserializer<'de> for Deserializer<'de> {
         use crate::reader::value_descriptor::Primitive::*;
         use ValueDescriptor::Primitive;
 
+        if self.field.map(|f| f.unsigned).unwrap_or(false) {
+            // reinterpret the bits of the fields annotated with jdk.jfr.Unsigned.
+            // Non-negative values are visited as they are, so that signed types keep working
+            let unsigned = match self.value {
+                Primitive(Integer(v)) if *v < 0 => Some(*v as u32 as u64),
+                Primitive(Long(v)) if *v < 0 => Some(*v as u64),
+                Primitive(Short(v)) if *v < 0 => Some(*v as u16 as u64),
+                Primitive(Byte(v)) if *v < 0 => Some(*v as u8 as u64),
+                _ => None,
+            };
+            if let Some(v) = unsigned {
+                return match i64::try_from(v) {
+                    Ok(v) => visitor.visit_i64(v),
+                    // only u64 (or wider) can hold the value
+                    Err(_) => visitor.visit_u64(v),
+                };
+            }
+        }
+
         match self.value {
             Primitive(Integer(v)) => visitor.visit_i32(*v),
             Primitive(Long(v)) => visitor.visit_i64(*v),
             Primitive(Float(v)) => visitor.visit_f32(*v),
             Primitive(Double(v)) => visitor.visit_f64(*v),
-            Primitive(Character(v)) => {
-                #[cfg(feature = "cstring")]
-                return visitor
-                    .visit_borrowed_str(v.string.as_c_str().to_str().expect("Invalid UTF-8"));
-                #[cfg(not(feature = "cstring"))]
-                return visitor.visit_char(*v);
-            }
+            Primitive(Character(v)) => visitor.visit_char(*v),
             Primitive(Boolean(v)) => visitor.visit_bool(*v),
             Primitive(Short(v)) => visitor.visit_i16(*v),
             Primitive(Byte(v)) => visitor.visit_i8(*v),
-            Primitive(String(v)) => {
-                #[cfg(feature = "cstring")]
-                return visitor
-                    .visit_borrowed_str(v.string.as_c_str().to_str().expect("Invalid UTF-8"));
-                #[cfg(not(feature = "cstring"))]
-                return visitor.visit_borrowed_str(v.as_str());
-            }
+            Primitive(String(v)) => visitor.visit_borrowed_str(v.as_str()),
+            Primitive(CString(v)) => visitor.visit_borrowed_str(
+                v.string
+                    .to_str()
+                    .map_err(|_| Error::DeserializeError("Invalid UTF-8".to_string()))?,
+            ),
+            Primitive(RawString(v)) => match v.decode()? {
+                // borrowed as long as the bytes are UTF-8 as they are
+                Cow::Borrowed(s) => visitor.visit_borrowed_str(s),
+                Cow::Owned(s) => visitor.visit_string(s),
+            },
             Primitive(NullString) => Err(Error::DeserializeError(
                 "Unexpected null string".to_string(),
             )),
@@ -152,6 +281,7 @@ impl<'de> serde::Deserializer<'de> for Deserializer<'de> {
             }),
             ValueDescriptor::Array(array) => visitor.visit_seq(ArrayDeserializer {
                 chunk: self.chunk,
+                field: self.field,
                 array_idx: 0,
                 value: array,
             }),
@@ -159,7 +289,10 @@ impl<'de> serde::Deserializer<'de> for Deserializer<'de> {
                 class_id,
                 constant_index,
             } => match self.chunk.constant_pool.get(class_id, constant_index) {
-                Some(value) => Self::deserialize_any(Deserializer::new(self.chunk, value), visitor),
+                Some(value) => Self::deserialize_any(
+                    Deserializer::with_field(self.chunk, value, self.field),
+                    visitor,
+                ),
                 None => Err(Error::DeserializeError(format!(
                     "Not found in constant pool: class_id={}, index={}",
                     class_id, constant_index
@@ -178,16 +311,206 @@ impl<'de> serde::Deserializer<'de> for Deserializer<'de> {
                 class_id,
                 constant_index,
             } => match self.chunk.constant_pool.get(class_id, constant_index) {
-                Some(value) => visitor.visit_some(Deserializer::new(self.chunk, value)),
+                Some(value) => {
+                    visitor.visit_some(Deserializer::with_field(self.chunk, value, self.field))
+                }
                 None => visitor.visit_none(),
             },
             _ => visitor.visit_some(self),
         }
     }
 
+    fn deserialize_newtype_struct<V>(
+        self,
+        name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        // requested by `timespan` and `timestamp` modules
+        let nanos = match name {
+            TIMESPAN => self.timespan_nanos(),
+            TIMESTAMP => self.timestamp_nanos(),
+            _ => None,
+        };
+        match nanos {
+            Some(nanos) => visitor.visit_i128(nanos),
+            None => visitor.visit_newtype_struct(self),
+        }
+    }
+
+    fn deserialize_enum<V>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        let variant: BorrowedStrDeserializer<Self::Error> =
+            BorrowedStrDeserializer::new(self.variant_name()?);
+        visitor.visit_enum(variant)
+    }
+
+    fn deserialize_identifier<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_borrowed_str(self.variant_name()?)
+    }
+
+    fn deserialize_ignored_any<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        // no need to resolve the value which is just skipped
+        visitor.visit_unit()
+    }
+
     forward_to_deserialize_any! {
         bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
-        bytes byte_buf unit unit_struct newtype_struct seq tuple
-        tuple_struct map enum identifier ignored_any struct
+        bytes byte_buf unit unit_struct seq tuple
+        tuple_struct map struct
+    }
+}
+
+/// Deserializes the timespan field (e.g. `duration`) into `Duration`, converting the ticks
+/// or the unit of the field to nanoseconds.
+///
+/// ```
+/// # use serde::Deserialize;
+/// # use std::time::Duration;
+/// #[derive(Deserialize)]
+/// struct ThreadPark {
+///     #[serde(with = "jfrs::reader::de::timespan")]
+///     duration: Duration,
+///     #[serde(default, with = "jfrs::reader::de::timespan::option")]
+///     timeout: Option<Duration>,
+/// }
+/// ```
+///
+/// Values of the fields without the unit are taken as nanoseconds.
+/// Negative values (e.g. `Long.MIN_VALUE` for no timeout) can't be deserialized.
+pub mod timespan {
+    use super::{NanosVisitor, TIMESPAN};
+    use serde::{Deserialize, Deserializer};
+    use std::time::Duration;
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<Duration, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer
+            .deserialize_newtype_struct(TIMESPAN, NanosVisitor)
+            .map(Duration::from_nanos)
+    }
+
+    /// Deserializes the nullable timespan field into `Option<Duration>`
+    pub mod option {
+        use super::*;
+
+        struct Timespan(Duration);
+
+        impl<'de> Deserialize<'de> for Timespan {
+            fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+            where
+                D: Deserializer<'de>,
+            {
+                super::deserialize(deserializer).map(Timespan)
+            }
+        }
+
+        pub fn deserialize<'de, D>(deserializer: D) -> Result<Option<Duration>, D::Error>
+        where
+            D: Deserializer<'de>,
+        {
+            Option::<Timespan>::deserialize(deserializer).map(|t| t.map(|t| t.0))
+        }
+    }
+}
+
+/// Deserializes the timestamp field (e.g. `startTime`) into `SystemTime`, converting the ticks
+/// or the unit of the field to the time since UNIX epoch.
+///
+/// ```
+/// # use serde::Deserialize;
+/// # use std::time::SystemTime;
+/// #[derive(Deserialize)]
+/// #[serde(rename_all = "camelCase")]
+/// struct ExecutionSample {
+///     #[serde(with = "jfrs::reader::de::timestamp")]
+///     start_time: SystemTime,
+/// }
+/// ```
+///
+/// Values of the fields without the unit are taken as nanoseconds since UNIX epoch.
+pub mod timestamp {
+    use super::{NanosVisitor, TIMESTAMP};
+    use serde::{Deserialize, Deserializer};
+    use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<SystemTime, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer
+            .deserialize_newtype_struct(TIMESTAMP, NanosVisitor)
+            .map(|nanos| UNIX_EPOCH + Duration::from_nanos(nanos))
+    }
+
+    /// Deserializes the nullable timestamp field into `Option<SystemTime>`
+    pub mod option {
+        use super::*;
+
+        struct Timestamp(SystemTime);
+
+        impl<'de> Deserialize<'de> for Timestamp {
+            fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+            where
+                D: Deserializer<'de>,
+            {
+                super::deserialize(deserializer).map(Timestamp)
+            }
+        }
+
+        pub fn deserialize<'de, D>(deserializer: D) -> Result<Option<SystemTime>, D::Error>
+        where
+            D: Deserializer<'de>,
+        {
+            Option::<Timestamp>::deserialize(deserializer).map(|t| t.map(|t| t.0))
+        }
+    }
+}
+
+/// Accepts non-negative nanoseconds
+struct NanosVisitor;
+
+impl<'de> Visitor<'de> for NanosVisitor {
+    type Value = u64;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("non-negative nanoseconds")
+    }
+
+    fn visit_i64<E: serde::de::Error>(self, v: i64) -> Result<Self::Value, E> {
+        self.visit_i128(v as i128)
+    }
+
+    fn visit_u64<E: serde::de::Error>(self, v: u64) -> Result<Self::Value, E> {
+        Ok(v)
+    }
+
+    fn visit_i128<E: serde::de::Error>(self, v: i128) -> Result<Self::Value, E> {
+        u64::try_from(v)
+            .map_err(|_| E::custom(format!("Time out of range can't be deserialized: {} ns", v)))
+    }
+
+    fn visit_newtype_struct<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        deserializer.deserialize_any(self)
     }
 }
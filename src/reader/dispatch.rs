@@ -0,0 +1,104 @@
+//! Routing events to typed handlers, e.g. for fanning events out into multiple sinks.
+
+use crate::reader::callback::CallbackParser;
+use crate::reader::de::from_value_descriptor;
+use crate::reader::event::Accessor;
+use crate::reader::types::EventType;
+use crate::reader::{JfrReader, Result};
+use std::io::{Read, Seek};
+
+/// Registry of handlers keyed by the event type.
+///
+/// Class ids of the registered types are resolved once per chunk, and events of other types
+/// are skipped without decoding.
+///
+/// ```no_run
+/// # use jfrs::reader::JfrReader;
+/// # use jfrs::reader::dispatch::EventDispatcher;
+/// # use jfrs::reader::types::jdk::ExecutionSample;
+/// # use std::fs::File;
+/// let mut samples = 0;
+/// let mut dispatcher = EventDispatcher::new()
+///     .on::<ExecutionSample, _>(|_sample| samples += 1);
+/// let mut reader = JfrReader::new(File::open("recording.jfr").unwrap());
+/// dispatcher.run(&mut reader).unwrap();
+/// ```
+#[derive(Default)]
+pub struct EventDispatcher<'h> {
+    parser: CallbackParser<'h>,
+}
+
+impl<'h> EventDispatcher<'h> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers the handler which receives the event deserialized as `E`.
+    /// Deserialization errors stop [`EventDispatcher::run`].
+    pub fn on<E, F>(self, mut handler: F) -> Self
+    where
+        E: EventType,
+        F: for<'de> FnMut(E::Value<'de>) + 'h,
+    {
+        Self {
+            parser: self.parser.try_on(E::NAME, move |e| {
+                handler(from_value_descriptor(e.chunk, e.value)?);
+                Ok(())
+            }),
+        }
+    }
+
+    /// Registers the handler which receives the event as [`Accessor`],
+    /// for the types which don't have Rust structs
+    pub fn on_raw<F>(self, event_type: &str, handler: F) -> Self
+    where
+        F: FnMut(&Accessor) + 'h,
+    {
+        Self {
+            parser: self.parser.on(event_type, handler),
+        }
+    }
+
+    /// Reads all chunks and routes the events to the handlers
+    pub fn run<T: Read + Seek>(&mut self, reader: &mut JfrReader<T>) -> Result<()> {
+        self.parser.parse(reader)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::reader::types::jdk::ExecutionSample;
+    use crate::test_util::test_data;
+    use std::collections::HashSet;
+    use std::fs::File;
+
+    #[test]
+    fn test_dispatch() {
+        let mut samples = 0;
+        let mut frames = 0;
+        let mut threads = HashSet::new();
+        let mut other = 0;
+
+        let mut dispatcher = EventDispatcher::new()
+            .on::<ExecutionSample, _>(|sample| {
+                samples += 1;
+                if let Some(thread) = sample.sampled_thread.and_then(|t| t.os_name) {
+                    threads.insert(thread.to_string());
+                }
+            })
+            .on::<ExecutionSample, _>(|sample| {
+                frames += sample.stack_trace.map(|s| s.frames.len()).unwrap_or(0);
+            })
+            .on_raw("jdk.ActiveSetting", |_| other += 1);
+
+        let mut reader = JfrReader::new(File::open(test_data("profiler-multichunk.jfr")).unwrap());
+        dispatcher.run(&mut reader).unwrap();
+        drop(dispatcher);
+
+        assert_eq!(samples, 8888);
+        assert!(frames > samples);
+        assert!(!threads.is_empty());
+        assert!(other > 0);
+    }
+}
@@ -0,0 +1,292 @@
+//! Human-readable formatting of the values, like `jfr print`.
+//!
+//! Field names are taken from the metadata, constant pool references are resolved,
+//! and the values of the fields annotated with units are formatted accordingly
+//! (e.g. `1.5 MiB`, `12.345 ms`, `2022-09-04T12:34:56.789000000Z`).
+
+use crate::reader::event::{Accessor, Event};
+use crate::reader::type_descriptor::{FieldDescriptor, Unit};
+use crate::reader::value_descriptor::{Primitive, ValueDescriptor};
+use std::fmt;
+use std::fmt::Formatter;
+
+// constant pool references may form deep chains (e.g. class -> class loader -> class)
+const MAX_DEPTH: usize = 16;
+
+impl fmt::Display for Accessor<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write_value(f, self, 0)
+    }
+}
+
+impl fmt::Display for Event<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write_value(f, &self.value(), 0)
+    }
+}
+
+fn write_value(f: &mut Formatter<'_>, accessor: &Accessor, depth: usize) -> fmt::Result {
+    if depth > MAX_DEPTH {
+        return write!(f, "...");
+    }
+    let resolved = match Accessor::new(accessor.chunk, accessor.value).resolve() {
+        Some(v) => v,
+        None => return write!(f, "null"),
+    };
+    match resolved.value {
+        ValueDescriptor::Primitive(p) => match accessor.field() {
+            Some(field) if field.unit.is_some() || field.tick_unit.is_some() => {
+                write_with_unit(f, accessor, p, field)
+            }
+            _ => write!(f, "{}", p),
+        },
+        ValueDescriptor::Object(o) => {
+            let type_desc = match accessor.chunk.metadata.type_pool.get(o.class_id) {
+                Some(t) => t,
+                None => return write!(f, "{}", o),
+            };
+            write!(f, "{} {{", type_desc.name())?;
+            for (i, field) in type_desc.fields.iter().enumerate() {
+                write!(f, "{}{} = ", if i > 0 { ", " } else { " " }, field.name())?;
+                match resolved.get_field_raw(field.name()) {
+                    Some(v) => write_value(f, &v, depth + 1)?,
+                    None => write!(f, "null")?,
+                }
+            }
+            write!(f, " }}")
+        }
+        ValueDescriptor::Array(_) => {
+            write!(f, "[")?;
+            let elems = Accessor {
+                field: accessor.field(),
+                ..resolved
+            };
+            for (i, v) in elems.as_iter().into_iter().flatten().enumerate() {
+                if i > 0 {
+                    write!(f, ", ")?;
+                }
+                write_value(f, &v, depth + 1)?;
+            }
+            write!(f, "]")
+        }
+        ValueDescriptor::ConstantPool { .. } => write!(f, "{}", resolved.value),
+    }
+}
+
+fn write_with_unit(
+    f: &mut Formatter<'_>,
+    accessor: &Accessor,
+    value: &Primitive,
+    field: &FieldDescriptor,
+) -> fmt::Result {
+    let header = &accessor.chunk.header;
+    if let Ok(v) = i64::try_from(accessor.value) {
+        if let Some(nanos) = field.timestamp_nanos(v, header) {
+            return write_timestamp(f, nanos);
+        }
+        if field.tick_unit.is_some()
+            || matches!(
+                field.unit,
+                Some(Unit::Nanosecond | Unit::Millisecond | Unit::Second)
+            )
+        {
+            // JFR uses the extreme values for the missing and the infinite durations
+            return match v {
+                i64::MIN => write!(f, "N/A"),
+                i64::MAX => write!(f, "Infinity"),
+                _ => match field.timespan_nanos(v, header) {
+                    Some(nanos) => write_duration(f, nanos),
+                    None => write!(f, "{}", value),
+                },
+            };
+        }
+    }
+
+    let float = match value {
+        Primitive::Float(v) => Some(*v as f64),
+        Primitive::Double(v) => Some(*v),
+        _ => None,
+    };
+    let integer = accessor.as_u64().map(|v| v as i128).or(match value {
+        Primitive::Long(v) => Some(*v as i128),
+        Primitive::Integer(v) => Some(*v as i128),
+        Primitive::Short(v) => Some(*v as i128),
+        Primitive::Byte(v) => Some(*v as i128),
+        _ => None,
+    });
+    match (field.unit, integer, float) {
+        (Some(Unit::Byte), Some(v), _) => write_bytes(f, v as f64),
+        (Some(Unit::Byte), _, Some(v)) => write_bytes(f, v),
+        (Some(Unit::PercentUnity), _, Some(v)) => write!(f, "{:.2}%", v * 100.0),
+        (Some(Unit::PercentUnity), Some(v), _) => write!(f, "{}%", v * 100),
+        (Some(Unit::AddressUnity), Some(v), _) => write!(f, "{:#x}", v),
+        (Some(Unit::Hz), Some(v), _) => write!(f, "{} Hz", v),
+        (Some(Unit::Hz), _, Some(v)) => write!(f, "{} Hz", v),
+        _ => write!(f, "{}", value),
+    }
+}
+
+fn write_bytes(f: &mut Formatter<'_>, bytes: f64) -> fmt::Result {
+    const UNITS: [&str; 5] = ["KiB", "MiB", "GiB", "TiB", "PiB"];
+    if bytes.abs() < 1024.0 {
+        return write!(f, "{} bytes", bytes);
+    }
+    let mut v = bytes / 1024.0;
+    let mut unit = 0;
+    while v.abs() >= 1024.0 && unit < UNITS.len() - 1 {
+        v /= 1024.0;
+        unit += 1;
+    }
+    write!(f, "{:.1} {}", v, UNITS[unit])
+}
+
+fn write_duration(f: &mut Formatter<'_>, nanos: i128) -> fmt::Result {
+    let abs = nanos.abs();
+    if abs < 1_000 {
+        write!(f, "{} ns", nanos)
+    } else if abs < 1_000_000 {
+        write!(f, "{:.3} us", nanos as f64 / 1e3)
+    } else if abs < 1_000_000_000 {
+        write!(f, "{:.3} ms", nanos as f64 / 1e6)
+    } else {
+        write!(f, "{:.3} s", nanos as f64 / 1e9)
+    }
+}
+
+/// Writes the time in RFC 3339 format in UTC
+fn write_timestamp(f: &mut Formatter<'_>, epoch_nanos: i128) -> fmt::Result {
+    let secs = epoch_nanos.div_euclid(1_000_000_000);
+    let sub_nanos = epoch_nanos.rem_euclid(1_000_000_000);
+    let days = secs.div_euclid(86400);
+    let secs_of_day = secs.rem_euclid(86400);
+
+    // converts the days since 1970-01-01 to the civil date
+    // http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    write!(
+        f,
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:09}Z",
+        year,
+        month,
+        day,
+        secs_of_day / 3600,
+        secs_of_day % 3600 / 60,
+        secs_of_day % 60,
+        sub_nanos
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::reader::JfrReader;
+    use crate::test_util::test_data;
+    use std::fs::File;
+
+    struct Timestamp(i128);
+
+    impl fmt::Display for Timestamp {
+        fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+            write_timestamp(f, self.0)
+        }
+    }
+
+    #[test]
+    fn test_timestamp() {
+        assert_eq!(Timestamp(0).to_string(), "1970-01-01T00:00:00.000000000Z");
+        assert_eq!(
+            Timestamp(1_662_294_896_789_000_000).to_string(),
+            "2022-09-04T12:34:56.789000000Z"
+        );
+        assert_eq!(
+            Timestamp(951_782_400_000_000_000).to_string(),
+            "2000-02-29T00:00:00.000000000Z"
+        );
+        assert_eq!(Timestamp(-1).to_string(), "1969-12-31T23:59:59.999999999Z");
+    }
+
+    #[test]
+    fn test_display_event() {
+        let mut reader = JfrReader::new(File::open(test_data("recording.jfr")).unwrap());
+        let (chunk_reader, chunk) = reader.chunks().next().unwrap().unwrap();
+        let events = chunk_reader.events(&chunk).flatten().collect::<Vec<_>>();
+
+        let info = events
+            .iter()
+            .find(|e| e.class.name() == "jdk.JVMInformation")
+            .unwrap();
+        let s = info.to_string();
+        assert!(
+            s.starts_with("jdk.JVMInformation { startTime = 20"),
+            "{}",
+            s
+        );
+        assert!(
+            s.contains("jvmName = \"OpenJDK 64-Bit Server VM\""),
+            "{}",
+            s
+        );
+        assert!(s.contains("pid = 3741"), "{}", s);
+
+        let park = events
+            .iter()
+            .find(|e| e.class.name() == "jdk.ThreadPark")
+            .unwrap();
+        let duration = park.value().get_field("duration").unwrap().to_string();
+        assert!(
+            ["ns", "us", "ms", " s"]
+                .iter()
+                .any(|u| duration.ends_with(u)),
+            "{}",
+            duration
+        );
+        // thread is resolved from the constant pool
+        assert!(park
+            .to_string()
+            .contains("eventThread = java.lang.Thread {"));
+    }
+
+    #[test]
+    fn test_value_descriptor() {
+        let value = ValueDescriptor::Object(crate::reader::value_descriptor::Object {
+            class_id: 1,
+            fields: vec![
+                ValueDescriptor::Primitive(Primitive::Integer(42)),
+                ValueDescriptor::Array(vec![
+                    ValueDescriptor::Primitive(Primitive::String("a".to_string())),
+                    ValueDescriptor::Primitive(Primitive::NullString),
+                ]),
+                ValueDescriptor::ConstantPool {
+                    class_id: 2,
+                    constant_index: 3,
+                },
+            ],
+        });
+        assert_eq!(value.to_string(), "#1 { 42, [\"a\", null], @2:3 }");
+
+        // values decoded from the same bytes are equal
+        let mut reader = JfrReader::new(File::open(test_data("recording.jfr")).unwrap());
+        let (chunk_reader, chunk) = reader.chunks().next().unwrap().unwrap();
+        for (a, b) in chunk_reader
+            .events(&chunk)
+            .flatten()
+            .zip(chunk_reader.events(&chunk).flatten())
+            .take(100)
+        {
+            assert_eq!(a.value().value, b.value().value);
+        }
+        assert_ne!(
+            ValueDescriptor::Primitive(Primitive::Long(1)),
+            ValueDescriptor::Primitive(Primitive::Integer(1))
+        );
+    }
+}
@@ -1,10 +1,22 @@
-use crate::reader::type_descriptor::TypeDescriptor;
-use crate::reader::value_descriptor::ValueDescriptor;
-use crate::reader::{Chunk, Error, HeapByteStream, Result};
+use crate::reader::bucket::Bucketed;
+use crate::reader::byte_stream::ByteStream;
+use crate::reader::sorted::SortedEvents;
+use crate::reader::stats::{EventCounters, Stopwatch};
+use crate::reader::type_descriptor::{FieldDescriptor, TypeDescriptor};
+use crate::reader::value_descriptor::{Primitive, ValueDescriptor};
+use crate::reader::{Chunk, Error, Result};
 use crate::{EVENT_TYPE_CONSTANT_POOL, EVENT_TYPE_METADATA};
+use rustc_hash::FxHashSet;
+use std::io::Cursor;
+use std::rc::Rc;
+use std::time::Duration;
+
+// constants referring to other constants are rare, but the resolution is bounded in case of cycles
+const MAX_RESOLVE_DEPTH: usize = 16;
 
 pub struct Event<'a> {
-    pub byte_offset: u64,
+    pub(crate) byte_offset: u64,
+    pub(crate) byte_size: u64,
     pub class: &'a TypeDescriptor,
     pub(crate) chunk: &'a Chunk,
     pub(crate) value: ValueDescriptor,
@@ -15,24 +27,75 @@ impl<'a> Event<'a> {
         Accessor {
             chunk: self.chunk,
             value: &self.value,
+            field: None,
         }
     }
+
+    /// The chunk which the event belongs to
+    pub fn chunk(&self) -> &'a Chunk {
+        self.chunk
+    }
+
+    /// The offset of the event from the beginning of the chunk body,
+    /// which can be passed to [`crate::reader::ChunkReader::events_from_offset`] to read the event again.
+    pub fn byte_offset(&self) -> u64 {
+        self.byte_offset
+    }
+
+    /// The size of the event in bytes including the size and the type fields
+    pub fn byte_size(&self) -> u64 {
+        self.byte_size
+    }
+
+    /// Takes the decoded value, which can be accessed by [`Accessor::new`] with the chunk
+    pub fn into_value(self) -> ValueDescriptor {
+        self.value
+    }
+}
+
+/// Indices of the constant which a field refers to.
+///
+/// Unique within the chunk, so it can be used as the key for deduplicating constants
+/// like stack traces without resolving them. Indices of different chunks are unrelated,
+/// so they should be combined with [`crate::reader::ChunkHeader::position`] across chunks.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash, Ord, PartialOrd)]
+pub struct ConstantRef {
+    pub class_id: i64,
+    pub constant_index: i64,
 }
 
+impl ConstantRef {
+    /// Looks up the constant in the chunk
+    pub fn resolve<'a>(&self, chunk: &'a Chunk) -> Option<Accessor<'a>> {
+        chunk
+            .constant_pool
+            .get(&self.class_id, &self.constant_index)
+            .map(|v| Accessor::new(chunk, v))
+    }
+}
+
+#[derive(Clone, Copy)]
 pub struct Accessor<'a> {
-    chunk: &'a Chunk,
+    pub(crate) chunk: &'a Chunk,
     pub value: &'a ValueDescriptor,
+    // The descriptor of the field which holds the value, if it's accessed through the field
+    pub(crate) field: Option<&'a FieldDescriptor>,
 }
 
 impl<'a> Accessor<'a> {
     pub fn new(chunk: &'a Chunk, value: &'a ValueDescriptor) -> Self {
-        Self { chunk, value }
+        Self {
+            chunk,
+            value,
+            field: None,
+        }
     }
 
     pub fn get_field(&self, name: &str) -> Option<Self> {
         self.value.get_field(name, self.chunk).map(|v| Self {
             chunk: self.chunk,
             value: v,
+            field: self.field_descriptor(name),
         })
     }
 
@@ -40,9 +103,131 @@ impl<'a> Accessor<'a> {
         self.value.get_field_raw(name, self.chunk).map(|v| Self {
             chunk: self.chunk,
             value: v,
+            field: self.field_descriptor(name),
         })
     }
 
+    /// Returns the indices of the constant which the field refers to, without resolving it.
+    /// Returns None if the field doesn't exist or holds the value inline.
+    pub fn get_field_ref(&self, name: &str) -> Option<ConstantRef> {
+        match self.get_field_raw(name)?.value {
+            ValueDescriptor::ConstantPool {
+                class_id,
+                constant_index,
+            } => Some(ConstantRef {
+                class_id: *class_id,
+                constant_index: *constant_index,
+            }),
+            _ => None,
+        }
+    }
+
+    /// Returns the descriptor of the field which holds the value, if it's accessed through the field
+    pub fn field(&self) -> Option<&'a FieldDescriptor> {
+        self.field
+    }
+
+    /// Converts the integral value to u64.
+    ///
+    /// If the field is annotated with `jdk.jfr.Unsigned`, the bits are reinterpreted as unsigned.
+    /// Otherwise, negative values can't be converted.
+    pub fn as_u64(&self) -> Option<u64> {
+        let unsigned = self.field.map(|f| f.unsigned).unwrap_or(false);
+        match self.value {
+            ValueDescriptor::Primitive(p) => match p {
+                Primitive::Long(v) if unsigned => Some(*v as u64),
+                Primitive::Integer(v) if unsigned => Some(*v as u32 as u64),
+                Primitive::Short(v) if unsigned => Some(*v as u16 as u64),
+                Primitive::Byte(v) if unsigned => Some(*v as u8 as u64),
+                Primitive::Long(v) => u64::try_from(*v).ok(),
+                Primitive::Integer(v) => u64::try_from(*v).ok(),
+                Primitive::Short(v) => u64::try_from(*v).ok(),
+                Primitive::Byte(v) => u64::try_from(*v).ok(),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    /// Converts the integral value to u32 in the same way as [`Accessor::as_u64`].
+    /// Returns None if the value doesn't fit in u32.
+    pub fn as_u32(&self) -> Option<u32> {
+        self.as_u64().and_then(|v| u32::try_from(v).ok())
+    }
+
+    /// Returns the integral value widened to i64, resolving the constant pool reference
+    pub fn as_i64(&self) -> Option<i64> {
+        match self.resolved_primitive()? {
+            Primitive::Long(v) => Some(*v),
+            Primitive::Integer(v) => Some(*v as i64),
+            Primitive::Short(v) => Some(*v as i64),
+            Primitive::Byte(v) => Some(*v as i64),
+            _ => None,
+        }
+    }
+
+    /// Returns the numeric value as f64, resolving the constant pool reference.
+    /// Integral values are converted too, which may lose precision for large longs.
+    pub fn as_f64(&self) -> Option<f64> {
+        match self.resolved_primitive()? {
+            Primitive::Double(v) => Some(*v),
+            Primitive::Float(v) => Some(*v as f64),
+            _ => self.as_i64().map(|v| v as f64),
+        }
+    }
+
+    /// Returns the string value, resolving the constant pool reference.
+    /// Null strings are None.
+    pub fn as_str(&self) -> Option<&'a str> {
+        self.resolved_primitive()?.as_str()
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        match self.resolved_primitive()? {
+            Primitive::Boolean(v) => Some(*v),
+            _ => None,
+        }
+    }
+
+    /// Returns the elements of the array in the same way as [`Accessor::as_iter`]
+    pub fn as_array(&self) -> Option<Vec<Accessor<'a>>> {
+        self.as_iter().map(|iter| iter.collect())
+    }
+
+    fn resolved_primitive(&self) -> Option<&'a Primitive> {
+        let value = match self.value {
+            ValueDescriptor::ConstantPool {
+                class_id,
+                constant_index,
+            } => self.chunk.constant_pool.get(class_id, constant_index)?,
+            v => v,
+        };
+        match value {
+            ValueDescriptor::Primitive(p) => Some(p),
+            _ => None,
+        }
+    }
+
+    fn field_descriptor(&self, name: &str) -> Option<&'a FieldDescriptor> {
+        let class_id = match self.value {
+            ValueDescriptor::Object(o) => o.class_id,
+            ValueDescriptor::ConstantPool {
+                class_id,
+                constant_index,
+            } => match self.chunk.constant_pool.get(class_id, constant_index) {
+                Some(ValueDescriptor::Object(o)) => o.class_id,
+                _ => return None,
+            },
+            _ => return None,
+        };
+        self.chunk
+            .metadata
+            .type_pool
+            .get(class_id)
+            .and_then(|t| t.get_field(name))
+            .map(|(_, f)| f)
+    }
+
     pub fn resolve(self) -> Option<Self> {
         match self.value {
             ValueDescriptor::ConstantPool {
@@ -55,12 +240,34 @@ impl<'a> Accessor<'a> {
                 .map(|v| Self {
                     chunk: self.chunk,
                     value: v,
+                    field: self.field,
                 }),
             _ => Some(self),
         }
     }
 
+    /// Resolves the constant pool references until reaching the value which is not a reference.
+    /// Returns None if any reference is not found.
+    pub fn resolve_deep(self) -> Option<Self> {
+        let mut resolved = self;
+        for _ in 0..MAX_RESOLVE_DEPTH {
+            if !matches!(resolved.value, ValueDescriptor::ConstantPool { .. }) {
+                return Some(resolved);
+            }
+            resolved = resolved.resolve()?;
+        }
+        None
+    }
+
+    /// Iterates over the elements of the array.
+    /// Elements which are constant pool references are resolved by [`Accessor::resolve_deep`],
+    /// and the references which are not found are returned as they are.
     pub fn as_iter(self) -> Option<impl Iterator<Item = Accessor<'a>>> {
+        Some(self.as_iter_raw()?.map(|e| e.resolve_deep().unwrap_or(e)))
+    }
+
+    /// Iterates over the elements of the array without resolving the constant pool references
+    pub fn as_iter_raw(self) -> Option<impl Iterator<Item = Accessor<'a>>> {
         let array = match self.value {
             ValueDescriptor::Array(a) => a,
             ValueDescriptor::ConstantPool {
@@ -72,25 +279,88 @@ impl<'a> Accessor<'a> {
             },
             _ => return None,
         };
-        Some(array.iter().map(|v| Accessor {
+        let field = self.field;
+        Some(array.iter().map(move |v| Accessor {
             value: v,
             chunk: self.chunk,
+            field,
         }))
     }
 }
 
+/// Iterator over the events of the chunk.
+/// Each iterator has its own cursor over the chunk bytes, so multiple iterators can be used at the same time.
 pub struct EventIterator<'a, 'b> {
     chunk: &'a Chunk,
-    stream: &'b mut HeapByteStream,
+    stream: ByteStream<Cursor<&'b [u8]>>,
     offset: u64,
+    // the offset to stop the iteration at. The end of the chunk if None
+    until: Option<u64>,
+    // class ids of the event types to decode. All types if None
+    selected: Option<Rc<FxHashSet<i64>>>,
+    // set when the cancellation is reported, to end the iteration
+    cancelled: bool,
+    // the counters of the chunk reader to count the decoded events
+    counters: Option<&'b EventCounters>,
 }
 
 impl<'a, 'b> EventIterator<'a, 'b> {
-    pub fn new(chunk: &'a Chunk, stream: &'b mut HeapByteStream) -> Self {
+    /// `bytes` must be the whole bytes of the chunk including the header
+    pub fn new(chunk: &'a Chunk, bytes: &'b [u8]) -> Self {
+        let mut stream = ByteStream::new(Cursor::new(bytes));
+        stream.set_int_encoding(chunk.header.int_encoding());
+        stream.set_limits(chunk.limits);
+        stream.set_string_repr(chunk.string_repr);
         Self {
             chunk,
             stream,
             offset: 0,
+            until: None,
+            selected: chunk.selected_events.clone(),
+            cancelled: false,
+            counters: None,
+        }
+    }
+
+    pub(crate) fn with_counters(mut self, counters: &'b EventCounters) -> Self {
+        self.counters = Some(counters);
+        self
+    }
+
+    /// Restricts the events to the types matching the predicate.
+    /// Events of other types are skipped without decoding.
+    /// Applied on top of the previous selection.
+    pub fn select<F>(mut self, predicate: F) -> Self
+    where
+        F: Fn(&TypeDescriptor) -> bool,
+    {
+        let selected = self
+            .chunk
+            .metadata
+            .type_pool
+            .event_types()
+            .filter(|t| self.is_selected(t.class_id) && predicate(t))
+            .map(|t| t.class_id)
+            .collect();
+        self.selected = Some(Rc::new(selected));
+        self
+    }
+
+    /// Restricts the events to the types in the category or its subcategories,
+    /// e.g. `&["Java Virtual Machine", "GC"]`. See [`TypeDescriptor::is_in_category`].
+    pub fn in_category(self, category: &[&str]) -> Self {
+        self.select(|t| t.is_in_category(category))
+    }
+
+    /// Restricts the events to the types with the label (e.g. `Execution Sample`)
+    pub fn with_label(self, label: &str) -> Self {
+        self.select(|t| t.label() == Some(label))
+    }
+
+    fn is_selected(&self, event_type: i64) -> bool {
+        match &self.selected {
+            Some(selected) => selected.contains(&event_type),
+            None => true,
         }
     }
 
@@ -98,51 +368,187 @@ impl<'a, 'b> EventIterator<'a, 'b> {
         self.offset = offset;
     }
 
+    /// Stops the iteration at the offset, e.g. the end of a flush segment
+    /// (see [`crate::reader::Chunk::flush_segments`]).
+    pub fn until(mut self, end_offset: u64) -> Self {
+        self.until = Some(end_offset);
+        self
+    }
+
+    /// Reorders the events by `startTime`. See [`SortedEvents`] for the details.
+    pub fn sorted(self) -> SortedEvents<'a, 'b> {
+        SortedEvents::new(self)
+    }
+
+    /// Groups the events into fixed wall-clock windows of the width by `startTime`.
+    /// See [`Bucketed`] for the details.
+    pub fn bucketed(self, width: Duration) -> Bucketed<'a, Self> {
+        Bucketed::new(self, width)
+    }
+
     fn internal_next(&mut self) -> Result<Option<Event<'a>>> {
-        let end_offset = self.chunk.header.chunk_body_size();
+        match self.next_event_header()? {
+            Some(header) => self.read_event(header).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    /// Decodes the body of the event whose header is read by [`EventIterator::next_event_header`]
+    fn read_event(
+        &mut self,
+        (event_offset, size, event_type): (u64, u64, i64),
+    ) -> Result<Event<'a>> {
+        let type_desc = self
+            .chunk
+            .metadata
+            .type_pool
+            .get(event_type)
+            .ok_or(Error::ClassNotFound(event_type))?;
+        let value = ValueDescriptor::try_new(&mut self.stream, event_type, &self.chunk.metadata)
+            .map_err(|e| e.in_context(|| type_desc.name().to_string()))?;
 
-        while self.offset < end_offset {
+        Ok(Event {
+            byte_offset: event_offset,
+            byte_size: size,
+            class: type_desc,
+            chunk: self.chunk,
+            value,
+        })
+    }
+
+    /// Returns the position of the stream in the chunk bytes,
+    /// i.e. the event body right after [`EventIterator::next_event_header`]
+    pub(crate) fn stream_position(&self) -> u64 {
+        self.stream.get_ref().position()
+    }
+
+    /// Decodes the event found by [`EventIterator::next_event_header`] of another iterator over the same chunk,
+    /// whose body starts at `body_position`, without reading the header again
+    pub(crate) fn resume(
+        &mut self,
+        header: (u64, u64, i64),
+        body_position: u64,
+    ) -> Option<Result<Event<'a>>> {
+        self.offset = header.0 + header.1;
+        self.decode_with(|iter| {
+            iter.stream.seek(body_position)?;
+            iter.read_event(header).map(Some)
+        })
+    }
+
+    /// Decodes the next event by `decode`, checking the cancellation and counting into the stats
+    fn decode_with<F>(&mut self, decode: F) -> Option<Result<Event<'a>>>
+    where
+        F: FnOnce(&mut Self) -> Result<Option<Event<'a>>>,
+    {
+        if self.cancelled {
+            return None;
+        }
+        if let Some(token) = &self.chunk.cancellation_token {
+            if token.is_cancelled() {
+                self.cancelled = true;
+                return Some(Err(Error::Cancelled));
+            }
+        }
+        let start = self.counters.map(|_| Stopwatch::start());
+        let result = decode(self);
+        if let (Some(counters), Some(start)) = (self.counters, start) {
+            let decoded = matches!(&result, Ok(Some(_)));
+            counters.add(decoded as u64, start.elapsed());
+        }
+        result.transpose()
+    }
+
+    /// Advances to the next event except metadata, constant pool and the events not selected.
+    /// Returns the offset, the size and the type of the event, and the stream is positioned at the event body.
+    pub(crate) fn next_event_header(&mut self) -> Result<Option<(u64, u64, i64)>> {
+        let end_offset = self.chunk.body_size();
+
+        while self.offset < end_offset && self.until.is_none_or(|until| self.offset < until) {
             self.stream
                 .seek(self.chunk.header.body_start_offset() + self.offset)?;
             let event_offset = self.offset;
 
-            let size = self.stream.read_i32()?;
+            let size = match self.stream.read_i32() {
+                Err(Error::IoError(_)) if self.chunk.is_truncated() => return Ok(None),
+                res => res?,
+            };
+            if size <= 0 {
+                return Err(Error::InvalidFormat);
+            }
+            if self.chunk.is_truncated() && self.offset + size as u64 > end_offset {
+                // the last event is partially written
+                return Ok(None);
+            }
             let event_type = self.stream.read_i64()?;
             self.offset += size as u64;
 
             match event_type {
                 EVENT_TYPE_METADATA | EVENT_TYPE_CONSTANT_POOL => {}
-                _ => {
-                    let type_desc = self
-                        .chunk
-                        .metadata
-                        .type_pool
-                        .get(event_type)
-                        .ok_or(Error::ClassNotFound(event_type))?;
-                    let value =
-                        ValueDescriptor::try_new(self.stream, event_type, &self.chunk.metadata)?;
-
-                    return Ok(Some(Event {
-                        byte_offset: event_offset,
-                        class: type_desc,
-                        chunk: self.chunk,
-                        value,
-                    }));
-                }
+                _ if !self.is_selected(event_type) => {}
+                _ => return Ok(Some((event_offset, size as u64, event_type))),
             }
         }
         Ok(None)
     }
+
+    /// Reads `startTime` of the event whose header is read by [`EventIterator::next_event_header`]
+    /// without decoding the other fields.
+    /// Returns None if the event type doesn't start with `startTime` field.
+    pub(crate) fn read_start_ticks(&mut self, event_type: i64) -> Result<Option<i64>> {
+        let type_pool = &self.chunk.metadata.type_pool;
+        let has_start_time = type_pool
+            .get(event_type)
+            .and_then(|t| t.fields.first())
+            .is_some_and(|f| {
+                f.name() == "startTime"
+                    && !f.array_type
+                    && !f.constant_pool
+                    && type_pool
+                        .get(f.class_id)
+                        .is_some_and(|t| t.name() == "long")
+            });
+        if !has_start_time {
+            return Ok(None);
+        }
+        self.stream.read_i64().map(Some)
+    }
+
+    /// Reads the body of the event whose header is read by [`EventIterator::next_event_header`]
+    pub(crate) fn read_value_into(
+        &mut self,
+        event_type: i64,
+        value: &mut ValueDescriptor,
+    ) -> Result<()> {
+        value
+            .read_into(&mut self.stream, event_type, &self.chunk.metadata)
+            .map_err(|e| {
+                e.in_context(|| {
+                    let type_pool = &self.chunk.metadata.type_pool;
+                    type_pool
+                        .get(event_type)
+                        .map(|t| t.name().to_string())
+                        .unwrap_or_else(|| event_type.to_string())
+                })
+            })
+    }
+
+    #[cfg(feature = "arena")]
+    pub(crate) fn stream(&mut self) -> &mut ByteStream<Cursor<&'b [u8]>> {
+        &mut self.stream
+    }
+
+    /// The offset of the next event to read, which can be passed to [`EventIterator::seek`]
+    /// or [`crate::reader::ChunkReader::events_from_offset`] to resume the iteration
+    pub fn offset(&self) -> u64 {
+        self.offset
+    }
 }
 
 impl<'a, 'b> Iterator for EventIterator<'a, 'b> {
     type Item = Result<Event<'a>>;
 
     fn next(&mut self) -> Option<Self::Item> {
-        match self.internal_next() {
-            Ok(Some(e)) => Some(Ok(e)),
-            Ok(None) => None,
-            Err(e) => Some(Err(e)),
-        }
+        self.decode_with(Self::internal_next)
     }
 }
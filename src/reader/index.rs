@@ -0,0 +1,250 @@
+//! Index of event offsets per event type, which can be persisted as a sidecar file.
+//!
+//! Building the index requires a pass over the whole file, but it only reads the size and
+//! the type of each event without parsing the fields.
+//! Once built, events of a specific type can be read by jumping straight to their offsets.
+
+use crate::reader::byte_stream::{ByteStream, IntEncoding, StringType};
+use crate::reader::event::Event;
+use crate::reader::{Chunk, ChunkReader, Error, JfrReader, Result};
+use crate::writer::byte_writer::ByteWriter;
+use rustc_hash::FxHashMap;
+use std::io;
+use std::io::{Cursor, Read, Seek, Write};
+
+const INDEX_MAGIC: [u8; 4] = [b'J', b'F', b'R', b'I'];
+const INDEX_VERSION: i8 = 1;
+
+/// Event offsets of a recording
+#[derive(Debug, Default, Eq, PartialEq)]
+pub struct Index {
+    chunks: Vec<ChunkIndex>,
+}
+
+/// Event offsets of a chunk
+#[derive(Debug, Eq, PartialEq)]
+pub struct ChunkIndex {
+    position: u64,
+    chunk_size: i64,
+    start_time_nanos: i64,
+    offsets: FxHashMap<String, Vec<u64>>,
+}
+
+impl ChunkIndex {
+    /// The byte offset of the chunk, which can be passed to [`JfrReader::open_chunk_at`]
+    pub fn position(&self) -> u64 {
+        self.position
+    }
+
+    /// Event offsets of the given event type, which can be passed to [`ChunkReader::events_from_offset`]
+    pub fn offsets(&self, type_name: &str) -> &[u64] {
+        self.offsets
+            .get(type_name)
+            .map(|v| v.as_slice())
+            .unwrap_or_default()
+    }
+
+    /// Names of the event types which appear in the chunk
+    pub fn event_types(&self) -> impl Iterator<Item = &str> {
+        self.offsets.keys().map(|k| k.as_str())
+    }
+
+    fn build(reader: &ChunkReader, chunk: &Chunk) -> Result<Self> {
+        let mut offsets: FxHashMap<String, Vec<u64>> = FxHashMap::default();
+        // only the event headers are read, skipping the event bodies
+        let mut events = reader.events(chunk);
+        while let Some((offset, _, event_type)) = events.next_event_header()? {
+            let type_desc = chunk
+                .metadata
+                .type_pool
+                .get(event_type)
+                .ok_or(Error::ClassNotFound(event_type))?;
+            offsets
+                .entry(type_desc.name().to_string())
+                .or_default()
+                .push(offset);
+        }
+
+        Ok(Self {
+            position: chunk.header.position(),
+            chunk_size: chunk.header.chunk_size,
+            start_time_nanos: chunk.header.start_time_nanos,
+            offsets,
+        })
+    }
+
+    fn matches(&self, chunk: &Chunk) -> bool {
+        self.position == chunk.header.position()
+            && self.chunk_size == chunk.header.chunk_size
+            && self.start_time_nanos == chunk.header.start_time_nanos
+    }
+}
+
+impl Index {
+    /// Builds the index by scanning all chunks of the recording from the beginning
+    pub fn build<T: Read + Seek>(reader: &mut JfrReader<T>) -> Result<Self> {
+        reader.chunk_start_position = 0;
+        let mut chunks = vec![];
+        for res in reader.chunk_metadata() {
+            let (chunk_reader, chunk) = res?;
+            chunks.push(ChunkIndex::build(&chunk_reader, &chunk)?);
+        }
+        Ok(Self { chunks })
+    }
+
+    pub fn chunks(&self) -> &[ChunkIndex] {
+        &self.chunks
+    }
+
+    /// Calls `f` for each event of the given type, parsing only the chunks which contain it.
+    ///
+    /// [`Error::StaleIndex`] is returned if the recording doesn't match the index.
+    pub fn for_each_event<T, F>(
+        &self,
+        reader: &mut JfrReader<T>,
+        type_name: &str,
+        mut f: F,
+    ) -> Result<()>
+    where
+        T: Read + Seek,
+        F: FnMut(&Event),
+    {
+        for chunk_index in self.chunks.iter() {
+            let offsets = chunk_index.offsets(type_name);
+            if offsets.is_empty() {
+                continue;
+            }
+            let (chunk_reader, chunk) = reader.open_chunk_at(chunk_index.position)?;
+            if !chunk_index.matches(&chunk) {
+                return Err(Error::StaleIndex);
+            }
+            for &offset in offsets {
+                match chunk_reader.events_from_offset(&chunk, offset).next() {
+                    Some(event) => f(&event?),
+                    None => return Err(Error::StaleIndex),
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Serializes the index, typically to a sidecar file of the recording
+    pub fn write_to<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        let mut w = ByteWriter::new(IntEncoding::Compressed);
+        w.write_bytes(&INDEX_MAGIC);
+        w.write_i8(INDEX_VERSION);
+        w.write_i32(self.chunks.len() as i32);
+        for chunk in self.chunks.iter() {
+            w.write_i64(chunk.position as i64);
+            w.write_i64(chunk.chunk_size);
+            w.write_i64(chunk.start_time_nanos);
+            w.write_i32(chunk.offsets.len() as i32);
+            for (type_name, offsets) in chunk.offsets.iter() {
+                w.write_string(type_name);
+                w.write_i32(offsets.len() as i32);
+                // offsets are ascending, so storing deltas keeps varints short
+                let mut prev = 0;
+                for &offset in offsets {
+                    w.write_i64((offset - prev) as i64);
+                    prev = offset;
+                }
+            }
+        }
+        writer.write_all(&w.into_inner())
+    }
+
+    /// Deserializes the index written by [`Index::write_to`]
+    pub fn read_from<R: Read>(mut reader: R) -> Result<Self> {
+        let mut bytes = vec![];
+        reader.read_to_end(&mut bytes).map_err(Error::IoError)?;
+        let mut stream = ByteStream::new(Cursor::new(bytes));
+        if stream.read_exact::<4>()? != INDEX_MAGIC {
+            return Err(Error::InvalidFormat);
+        }
+        if stream.read_i8()? != INDEX_VERSION {
+            return Err(Error::InvalidFormat);
+        }
+        stream.set_int_encoding(IntEncoding::Compressed);
+
+        let chunk_count = stream.read_i32()?;
+        let mut chunks = Vec::with_capacity(chunk_count.max(0) as usize);
+        for _ in 0..chunk_count {
+            let position = stream.read_i64()? as u64;
+            let chunk_size = stream.read_i64()?;
+            let start_time_nanos = stream.read_i64()?;
+            let type_count = stream.read_i32()?;
+            let mut offsets = FxHashMap::default();
+            for _ in 0..type_count {
+                let type_name = match stream.read_string()? {
+                    StringType::Raw(s) => s,
+                    StringType::Empty => String::new(),
+                    _ => return Err(Error::InvalidFormat),
+                };
+                let count = stream.read_i32()?;
+                let mut type_offsets = Vec::with_capacity(count.max(0) as usize);
+                let mut prev = 0;
+                for _ in 0..count {
+                    prev += stream.read_i64()? as u64;
+                    type_offsets.push(prev);
+                }
+                offsets.insert(type_name, type_offsets);
+            }
+            chunks.push(ChunkIndex {
+                position,
+                chunk_size,
+                start_time_nanos,
+                offsets,
+            });
+        }
+        Ok(Self { chunks })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::test_data;
+    use std::fs::File;
+
+    #[test]
+    fn test_build_and_read() {
+        let mut reader = JfrReader::new(File::open(test_data("profiler-multichunk.jfr")).unwrap());
+        let mut expected = 0;
+        for (chunk_reader, chunk) in reader.chunks().flatten() {
+            expected += chunk_reader
+                .events(&chunk)
+                .flatten()
+                .filter(|e| e.class.name() == "jdk.ExecutionSample")
+                .count();
+        }
+
+        let index = Index::build(&mut reader).unwrap();
+        assert_eq!(index.chunks().len(), 3);
+        let indexed: usize = index
+            .chunks()
+            .iter()
+            .map(|c| c.offsets("jdk.ExecutionSample").len())
+            .sum();
+        assert_eq!(indexed, expected);
+
+        let mut buf = vec![];
+        index.write_to(&mut buf).unwrap();
+        let index = Index::read_from(buf.as_slice()).unwrap();
+        assert_eq!(index.chunks().len(), 3);
+
+        let mut count = 0;
+        index
+            .for_each_event(&mut reader, "jdk.ExecutionSample", |e| {
+                assert_eq!(e.class.name(), "jdk.ExecutionSample");
+                count += 1;
+            })
+            .unwrap();
+        assert_eq!(count, expected);
+
+        let mut reader = JfrReader::new(File::open(test_data("profiler-wall.jfr")).unwrap());
+        assert!(matches!(
+            index.for_each_event(&mut reader, "jdk.ExecutionSample", |_| {}),
+            Err(Error::StaleIndex)
+        ));
+    }
+}
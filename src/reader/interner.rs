@@ -0,0 +1,185 @@
+//! Interning strings across chunks.
+//!
+//! Each chunk has its own constant pool, so symbols like class and method names are decoded
+//! once per chunk. [`StringInterner`] keeps a single copy of each string for the whole recording,
+//! which saves memory when the strings are kept after the chunks are dropped.
+
+use crate::reader::event::Accessor;
+use crate::reader::metadata::Metadata;
+use crate::reader::value_descriptor::ValueDescriptor;
+use rustc_hash::{FxHashMap, FxHashSet};
+use std::rc::Rc;
+
+/// Set of strings shared across chunks.
+///
+/// Strings referenced through the constant pool are cached by the constant index
+/// until the chunk changes, so repeated references don't even hash the string.
+#[derive(Debug, Default)]
+pub struct StringInterner {
+    strings: FxHashSet<Rc<str>>,
+    // the chunk which `constants` belong to, identified by its metadata and position.
+    // Holding the metadata keeps its address from being reused by another chunk
+    chunk: Option<(Rc<Metadata>, u64)>,
+    constants: FxHashMap<(i64, i64), Rc<str>>,
+}
+
+impl StringInterner {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the shared copy of the string
+    pub fn intern(&mut self, s: &str) -> Rc<str> {
+        if let Some(interned) = self.strings.get(s) {
+            return interned.clone();
+        }
+        let interned: Rc<str> = Rc::from(s);
+        self.strings.insert(interned.clone());
+        interned
+    }
+
+    /// Interns the string value, or the `string` field of `jdk.types.Symbol`.
+    /// Returns None if the value is null or not a string.
+    pub fn intern_value(&mut self, value: &Accessor) -> Option<Rc<str>> {
+        let key = match value.value {
+            ValueDescriptor::ConstantPool {
+                class_id,
+                constant_index,
+            } => (*class_id, *constant_index),
+            _ => return Self::as_str(value).map(|s| self.intern(s)),
+        };
+
+        let position = value.chunk.header.position();
+        let same_chunk = match &self.chunk {
+            Some((metadata, p)) => Rc::ptr_eq(metadata, &value.chunk.metadata) && *p == position,
+            None => false,
+        };
+        if !same_chunk {
+            self.chunk = Some((value.chunk.metadata.clone(), position));
+            self.constants.clear();
+        }
+        if let Some(interned) = self.constants.get(&key) {
+            return Some(interned.clone());
+        }
+        let s = Self::as_str(&Accessor::new(value.chunk, value.value).resolve()?)?;
+        let interned = self.intern(s);
+        self.constants.insert(key, interned.clone());
+        Some(interned)
+    }
+
+    /// Interns the field of the object in the same way as [`StringInterner::intern_value`]
+    pub fn intern_field(&mut self, object: &Accessor, name: &str) -> Option<Rc<str>> {
+        self.intern_value(&object.get_field_raw(name)?)
+    }
+
+    /// The number of distinct strings
+    pub fn len(&self) -> usize {
+        self.strings.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.strings.is_empty()
+    }
+
+    /// The total bytes of the distinct strings
+    pub fn total_bytes(&self) -> usize {
+        self.strings.iter().map(|s| s.len()).sum()
+    }
+
+    fn as_str<'a>(value: &Accessor<'a>) -> Option<&'a str> {
+        match value.get_field("string") {
+            Some(s) => <&str>::try_from(s.value).ok(),
+            None => <&str>::try_from(value.value).ok(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::reader::JfrReader;
+    use crate::test_util::{constant, string, test_data, TestRecording};
+    use crate::writer::recording::TypeDeclaration;
+    use serde::Serialize;
+    use std::collections::HashMap;
+    use std::fs::File;
+
+    #[test]
+    fn test_intern_across_chunks() {
+        let mut reader = JfrReader::new(File::open(test_data("profiler-multichunk.jfr")).unwrap());
+        let mut interner = StringInterner::new();
+        let mut by_name: HashMap<String, Rc<str>> = HashMap::new();
+        let mut references = 0;
+
+        for (chunk_reader, chunk) in reader.chunks().flatten() {
+            for event in chunk_reader.events(&chunk).flatten() {
+                if event.class.name() != "jdk.ExecutionSample" {
+                    continue;
+                }
+                let frames = event
+                    .value()
+                    .get_field("stackTrace")
+                    .and_then(|s| s.get_field("frames"))
+                    .and_then(|f| f.as_iter())
+                    .unwrap();
+                for frame in frames {
+                    let method = frame.get_field("method").unwrap();
+                    let name = interner.intern_field(&method, "name").unwrap();
+                    let expected = method
+                        .get_field("name")
+                        .and_then(|n| n.get_field("string"))
+                        .and_then(|s| s.as_str())
+                        .unwrap();
+                    assert_eq!(&*name, expected);
+                    // the same string is always the same allocation
+                    let first = by_name.entry(expected.to_string()).or_insert(name.clone());
+                    assert!(Rc::ptr_eq(first, &name));
+                    references += 1;
+                }
+            }
+        }
+        assert_eq!(interner.len(), by_name.len());
+        assert!(interner.len() < references);
+        assert!(interner.total_bytes() > 0);
+    }
+
+    #[test]
+    fn test_intern_across_recordings() {
+        #[derive(Serialize)]
+        struct Symbol<'a> {
+            string: &'a str,
+        }
+        #[derive(Serialize)]
+        struct Method {
+            name: i64,
+        }
+
+        let mut interner = StringInterner::new();
+        // both recordings have the name at the same constant index of the first chunk
+        for name in ["alice", "bob"] {
+            let mut recording = TestRecording::new([
+                TypeDeclaration::new("jdk.types.Symbol").field(string("string")),
+                TypeDeclaration::event("test.Method").field(constant("name", "jdk.types.Symbol")),
+            ]);
+            recording
+                .constant("jdk.types.Symbol", 1, &Symbol { string: name })
+                .event("test.Method", &Method { name: 1 });
+            let mut reader = recording.reader();
+            let (chunk_reader, chunk) = reader.chunks().next().unwrap().unwrap();
+            let event = chunk_reader.events(&chunk).next().unwrap().unwrap();
+            let interned = interner.intern_field(&event.value(), "name").unwrap();
+            assert_eq!(&*interned, name);
+        }
+        assert_eq!(interner.len(), 2);
+    }
+
+    #[test]
+    fn test_intern() {
+        let mut interner = StringInterner::new();
+        let a = interner.intern("java.lang.String");
+        let b = interner.intern(&String::from("java.lang.String"));
+        assert!(Rc::ptr_eq(&a, &b));
+        assert_eq!(interner.len(), 1);
+        assert_eq!(interner.total_bytes(), "java.lang.String".len());
+    }
+}
@@ -0,0 +1,173 @@
+//! Well-known JDK types and event types.
+//! Class ids are assigned by the JVM for each recording, so they can't be hard-coded.
+//! Instead, each [`TypeDescriptor`] is tagged with the [`KnownType`] when the metadata is read,
+//! which can be compared without string comparison.
+//!
+//! [`TypeDescriptor`]: crate::reader::type_descriptor::TypeDescriptor
+
+macro_rules! known_types {
+    ($($variant:ident => ($name:literal, $since:literal),)*) => {
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+        pub enum KnownType {
+            $($variant,)*
+        }
+
+        impl KnownType {
+            /// All known types in the declaration order
+            pub const ALL: &'static [KnownType] = &[$(KnownType::$variant,)*];
+
+            /// The type name used in the metadata
+            pub fn name(&self) -> &'static str {
+                match self {
+                    $(KnownType::$variant => $name,)*
+                }
+            }
+
+            /// The JDK feature version which introduced the type.
+            ///
+            /// Types introduced in JDK 11 are also available in JDK 8u262 or later,
+            /// which backported JFR from JDK 11.
+            pub fn since(&self) -> u32 {
+                match self {
+                    $(KnownType::$variant => $since,)*
+                }
+            }
+
+            pub fn from_name(name: &str) -> Option<KnownType> {
+                match name {
+                    $($name => Some(KnownType::$variant),)*
+                    _ => None,
+                }
+            }
+        }
+    };
+}
+
+known_types! {
+    // types
+    Boolean => ("boolean", 11),
+    Char => ("char", 11),
+    Float => ("float", 11),
+    Double => ("double", 11),
+    Byte => ("byte", 11),
+    Short => ("short", 11),
+    Int => ("int", 11),
+    Long => ("long", 11),
+    String => ("java.lang.String", 11),
+    Class => ("java.lang.Class", 11),
+    Thread => ("java.lang.Thread", 11),
+    ThreadGroup => ("jdk.types.ThreadGroup", 11),
+    ClassLoader => ("jdk.types.ClassLoader", 11),
+    Method => ("jdk.types.Method", 11),
+    Symbol => ("jdk.types.Symbol", 11),
+    Package => ("jdk.types.Package", 11),
+    Module => ("jdk.types.Module", 11),
+    StackTrace => ("jdk.types.StackTrace", 11),
+    StackFrame => ("jdk.types.StackFrame", 11),
+    FrameType => ("jdk.types.FrameType", 11),
+    ThreadState => ("jdk.types.ThreadState", 11),
+    GCName => ("jdk.types.GCName", 11),
+    GCCause => ("jdk.types.GCCause", 11),
+    OldObject => ("jdk.types.OldObject", 11),
+    Reference => ("jdk.types.Reference", 11),
+    // event types
+    ExecutionSample => ("jdk.ExecutionSample", 11),
+    NativeMethodSample => ("jdk.NativeMethodSample", 11),
+    ObjectAllocationInNewTLAB => ("jdk.ObjectAllocationInNewTLAB", 11),
+    ObjectAllocationOutsideTLAB => ("jdk.ObjectAllocationOutsideTLAB", 11),
+    ObjectAllocationSample => ("jdk.ObjectAllocationSample", 16),
+    OldObjectSample => ("jdk.OldObjectSample", 11),
+    JavaMonitorEnter => ("jdk.JavaMonitorEnter", 11),
+    JavaMonitorWait => ("jdk.JavaMonitorWait", 11),
+    ThreadPark => ("jdk.ThreadPark", 11),
+    ThreadSleep => ("jdk.ThreadSleep", 11),
+    ThreadStart => ("jdk.ThreadStart", 11),
+    ThreadEnd => ("jdk.ThreadEnd", 11),
+    ThreadDump => ("jdk.ThreadDump", 11),
+    SocketRead => ("jdk.SocketRead", 11),
+    SocketWrite => ("jdk.SocketWrite", 11),
+    FileRead => ("jdk.FileRead", 11),
+    FileWrite => ("jdk.FileWrite", 11),
+    JavaExceptionThrow => ("jdk.JavaExceptionThrow", 11),
+    JavaErrorThrow => ("jdk.JavaErrorThrow", 11),
+    ExceptionStatistics => ("jdk.ExceptionStatistics", 11),
+    GarbageCollection => ("jdk.GarbageCollection", 11),
+    GCHeapSummary => ("jdk.GCHeapSummary", 11),
+    GCConfiguration => ("jdk.GCConfiguration", 11),
+    GCHeapConfiguration => ("jdk.GCHeapConfiguration", 11),
+    YoungGenerationConfiguration => ("jdk.YoungGenerationConfiguration", 11),
+    ClassLoad => ("jdk.ClassLoad", 11),
+    ClassDefine => ("jdk.ClassDefine", 11),
+    ClassUnload => ("jdk.ClassUnload", 11),
+    ClassLoadingStatistics => ("jdk.ClassLoadingStatistics", 11),
+    SafepointBegin => ("jdk.SafepointBegin", 11),
+    SafepointEnd => ("jdk.SafepointEnd", 11),
+    ExecuteVMOperation => ("jdk.ExecuteVMOperation", 11),
+    CPULoad => ("jdk.CPULoad", 11),
+    ThreadCPULoad => ("jdk.ThreadCPULoad", 11),
+    JavaThreadStatistics => ("jdk.JavaThreadStatistics", 11),
+    JVMInformation => ("jdk.JVMInformation", 11),
+    OSInformation => ("jdk.OSInformation", 11),
+    CPUInformation => ("jdk.CPUInformation", 11),
+    InitialSystemProperty => ("jdk.InitialSystemProperty", 11),
+    ActiveSetting => ("jdk.ActiveSetting", 11),
+    ActiveRecording => ("jdk.ActiveRecording", 11),
+    NativeLibrary => ("jdk.NativeLibrary", 11),
+    ContainerConfiguration => ("jdk.ContainerConfiguration", 17),
+    ContainerCPUUsage => ("jdk.ContainerCPUUsage", 17),
+    ContainerMemoryUsage => ("jdk.ContainerMemoryUsage", 17),
+    NativeMemoryUsage => ("jdk.NativeMemoryUsage", 20),
+    NativeMemoryUsageTotal => ("jdk.NativeMemoryUsageTotal", 20),
+    VirtualThreadPinned => ("jdk.VirtualThreadPinned", 21),
+}
+
+impl KnownType {
+    /// Known types available in the given JDK feature version
+    pub fn for_version(jdk_version: u32) -> impl Iterator<Item = KnownType> {
+        Self::ALL
+            .iter()
+            .copied()
+            .filter(move |t| t.since() <= jdk_version)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::reader::JfrReader;
+    use crate::test_util::test_data;
+    use std::fs::File;
+
+    #[test]
+    fn test_known_types() {
+        for t in KnownType::ALL {
+            assert_eq!(KnownType::from_name(t.name()), Some(*t));
+        }
+        assert_eq!(KnownType::from_name("no.such.Type"), None);
+        assert!(KnownType::for_version(11).all(|t| t.since() <= 11));
+        assert!(!KnownType::for_version(11).any(|t| t == KnownType::ObjectAllocationSample));
+
+        let mut reader = JfrReader::new(File::open(test_data("profiler-wall.jfr")).unwrap());
+        let (chunk_reader, chunk) = reader.chunks().next().unwrap().unwrap();
+        let type_pool = &chunk.metadata.type_pool;
+
+        let class_id = type_pool.class_id_of(KnownType::ExecutionSample).unwrap();
+        assert_eq!(
+            type_pool.get(class_id).unwrap().name(),
+            "jdk.ExecutionSample"
+        );
+        assert_eq!(
+            type_pool
+                .get_by_name("jdk.types.StackTrace")
+                .and_then(|t| t.known_type()),
+            Some(KnownType::StackTrace)
+        );
+
+        let count = chunk_reader
+            .events(&chunk)
+            .flatten()
+            .filter(|e| e.class.known_type() == Some(KnownType::ExecutionSample))
+            .count();
+        assert_eq!(count, 8836);
+    }
+}
@@ -0,0 +1,74 @@
+//! Limits on the sizes declared in the recording, for reading untrusted files.
+//!
+//! Lengths of strings and arrays are read from the file, and buffers are allocated by them.
+//! A corrupted or hostile file can declare huge lengths, so services reading user-uploaded
+//! recordings should set [`Limits`] to reject such files with [`crate::reader::Error::LimitExceeded`].
+
+use std::fmt;
+
+/// What exceeded the limit
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum LimitKind {
+    /// The number of bytes (or chars) of a string
+    StringLength,
+    /// The number of elements of an array, including the string table of the metadata
+    ArrayLength,
+    /// The number of constants in the constant pool of a chunk
+    ConstantPoolSize,
+}
+
+impl fmt::Display for LimitKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LimitKind::StringLength => write!(f, "string length"),
+            LimitKind::ArrayLength => write!(f, "array length"),
+            LimitKind::ConstantPoolSize => write!(f, "constant pool size"),
+        }
+    }
+}
+
+/// Maximum sizes accepted while parsing. All unlimited by default.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Default)]
+pub struct Limits {
+    pub(crate) max_string_length: Option<u64>,
+    pub(crate) max_array_length: Option<u64>,
+    pub(crate) max_constant_pool_size: Option<u64>,
+}
+
+impl Limits {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Limits which are large enough for the recordings written by JVMs
+    /// (e.g. stack depth is at most 2048 frames by default), while rejecting absurd sizes.
+    pub fn hardened() -> Self {
+        Self::new()
+            .max_string_length(16 * 1024 * 1024)
+            .max_array_length(1024 * 1024)
+            .max_constant_pool_size(16 * 1024 * 1024)
+    }
+
+    pub fn max_string_length(mut self, length: u64) -> Self {
+        self.max_string_length = Some(length);
+        self
+    }
+
+    pub fn max_array_length(mut self, length: u64) -> Self {
+        self.max_array_length = Some(length);
+        self
+    }
+
+    pub fn max_constant_pool_size(mut self, size: u64) -> Self {
+        self.max_constant_pool_size = Some(size);
+        self
+    }
+
+    pub(crate) fn limit_of(&self, kind: LimitKind) -> Option<u64> {
+        match kind {
+            LimitKind::StringLength => self.max_string_length,
+            LimitKind::ArrayLength => self.max_array_length,
+            LimitKind::ConstantPoolSize => self.max_constant_pool_size,
+        }
+    }
+}
@@ -0,0 +1,142 @@
+//! Chunk which owns its bytes together with the metadata and the constant pool.
+
+use crate::reader::event::EventIterator;
+use crate::reader::sorted::SortedEvents;
+use crate::reader::{Chunk, ChunkReader, JfrReader, Result};
+use std::io::{Read, Seek};
+use std::ops::Deref;
+
+/// Single value combining [`ChunkReader`] and [`Chunk`], so that events can be read
+/// without keeping two values alive.
+///
+/// Derefs to [`Chunk`], so the header and the metadata can be accessed directly.
+pub struct LoadedChunk {
+    reader: ChunkReader,
+    chunk: Chunk,
+}
+
+impl LoadedChunk {
+    pub fn new(reader: ChunkReader, chunk: Chunk) -> Self {
+        Self { reader, chunk }
+    }
+
+    /// Returns an iterator over the events.
+    /// Each iterator has its own position, so multiple iterators can be used at the same time.
+    pub fn events(&self) -> EventIterator<'_, '_> {
+        self.reader.events(&self.chunk)
+    }
+
+    /// Returns an iterator over the events starting from the offset (i.e. [`crate::reader::event::Event::byte_offset()`])
+    pub fn events_from_offset(&self, start_offset: u64) -> EventIterator<'_, '_> {
+        self.reader.events_from_offset(&self.chunk, start_offset)
+    }
+
+    /// Returns an iterator over the events in the ascending order of `startTime`
+    pub fn events_sorted(&self) -> SortedEvents<'_, '_> {
+        self.reader.events_sorted(&self.chunk)
+    }
+
+    pub fn chunk(&self) -> &Chunk {
+        &self.chunk
+    }
+
+    pub fn into_parts(self) -> (ChunkReader, Chunk) {
+        (self.reader, self.chunk)
+    }
+}
+
+impl Deref for LoadedChunk {
+    type Target = Chunk;
+
+    fn deref(&self) -> &Chunk {
+        &self.chunk
+    }
+}
+
+impl From<(ChunkReader, Chunk)> for LoadedChunk {
+    fn from((reader, chunk): (ChunkReader, Chunk)) -> Self {
+        Self::new(reader, chunk)
+    }
+}
+
+impl<'a> IntoIterator for &'a LoadedChunk {
+    type Item = Result<crate::reader::event::Event<'a>>;
+    type IntoIter = EventIterator<'a, 'a>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.events()
+    }
+}
+
+/// Iterator over [`LoadedChunk`], returned by [`JfrReader::loaded_chunks`]
+pub struct LoadedChunkIterator<'a, T> {
+    pub(crate) reader: &'a mut JfrReader<T>,
+    pub(crate) finished: bool,
+}
+
+impl<'a, T: Read + Seek> Iterator for LoadedChunkIterator<'a, T> {
+    type Item = Result<LoadedChunk>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.finished {
+            return None;
+        }
+        // the position of the next chunk is kept by the reader, so the iterator can be recreated
+        let next = self.reader.chunks().next();
+        if !matches!(next, Some(Ok(_))) {
+            self.finished = true;
+        }
+        next.map(|r| r.map(LoadedChunk::from))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::test_data;
+    use std::fs::File;
+
+    #[test]
+    fn test_loaded_chunks() {
+        let mut reader = JfrReader::new(File::open(test_data("profiler-multichunk.jfr")).unwrap());
+        let chunks = reader.loaded_chunks().collect::<Result<Vec<_>>>().unwrap();
+        assert!(chunks.len() > 1);
+
+        let count = chunks
+            .iter()
+            .flat_map(|c| c.events())
+            .flatten()
+            .filter(|e| e.class.name() == "jdk.ExecutionSample")
+            .count();
+        assert_eq!(count, 8888);
+    }
+
+    #[test]
+    fn test_multiple_iterators() {
+        let mut reader = JfrReader::new(File::open(test_data("recording.jfr")).unwrap());
+        let chunk = reader.loaded_chunks().next().unwrap().unwrap();
+        assert_eq!(chunk.header.position(), 0);
+
+        let mut first = chunk.events();
+        let mut second = chunk.events();
+        let a = first.next().unwrap().unwrap();
+        let b = first.next().unwrap().unwrap();
+        // the second iterator starts from the beginning regardless of the first one
+        let c = second.next().unwrap().unwrap();
+        assert_eq!(a.byte_offset(), c.byte_offset());
+        assert!(a.byte_offset() < b.byte_offset());
+
+        let rest = chunk.events_from_offset(b.byte_offset()).count();
+        assert_eq!(rest + 1, chunk.events().count());
+
+        let mut count = 0;
+        for event in &chunk {
+            event.unwrap();
+            count += 1;
+        }
+        assert_eq!(count, rest + 1);
+
+        let (chunk_reader, chunk) = chunk.into_parts();
+        assert_eq!(chunk_reader.events(&chunk).count(), rest + 1);
+    }
+}
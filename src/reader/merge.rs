@@ -0,0 +1,241 @@
+//! Merging events of multiple recordings (e.g. from different JVMs on one host) in wall-clock time order.
+
+use crate::reader::event::Event;
+use crate::reader::sorted::scan_start_ticks;
+use crate::reader::{Chunk, ChunkReader, Error, JfrReader, Result};
+use std::collections::VecDeque;
+use std::io::{Read, Seek};
+
+/// Event of the merged recordings
+pub struct MergedEvent<'a> {
+    /// The index of the recording in the readers passed to [`MergedEvents::new`]
+    pub source: usize,
+    /// `startTime` in nanoseconds since UNIX epoch, or the start time of the chunk if the event
+    /// doesn't have `startTime`
+    pub start_time_nanos: i64,
+    pub event: Event<'a>,
+}
+
+/// Yields the events of multiple recordings in the ascending order of `startTime`.
+///
+/// Since ticks are specific to each recording, `startTime` is compared in wall-clock time
+/// by converting with the chunk header. Each recording is read one chunk at a time,
+/// and the events of the chunk are sorted as [`crate::reader::sorted::SortedEvents`] does,
+/// so chunks of a recording are expected not to overlap in time.
+///
+/// Like [`crate::reader::AllEvents`], this is not an `Iterator` because the events borrow the chunks.
+///
+/// ```no_run
+/// use jfrs::reader::merge::MergedEvents;
+/// use jfrs::reader::JfrReader;
+/// use std::fs::File;
+///
+/// let mut events = MergedEvents::new(vec![
+///     JfrReader::new(File::open("/path/to/app.jfr").unwrap()),
+///     JfrReader::new(File::open("/path/to/db.jfr").unwrap()),
+/// ]);
+/// while let Some(merged) = events.next() {
+///     let merged = merged.unwrap();
+///     println!("{} {} from #{}", merged.start_time_nanos, merged.event.class.name(), merged.source);
+/// }
+/// ```
+pub struct MergedEvents<T> {
+    sources: Vec<Source<T>>,
+}
+
+struct Source<T> {
+    reader: JfrReader<T>,
+    current: Option<(ChunkReader, Chunk)>,
+    // (start time in epoch nanos, offset) of the remaining events of the current chunk
+    queue: VecDeque<(i64, u64)>,
+    // the error on reading the current chunk, which is reported after its events
+    error: Option<Error>,
+    finished: bool,
+}
+
+impl<T: Read + Seek> Source<T> {
+    /// Reads the chunks until an event or the error is available
+    fn fill(&mut self) {
+        while self.queue.is_empty() && self.error.is_none() && !self.finished {
+            if let Some((chunk_reader, _)) = self.current.take() {
+                self.reader.recycle(chunk_reader);
+            }
+            let (chunk_reader, chunk) = match self.reader.chunks().next() {
+                Some(Ok(chunk)) => chunk,
+                Some(Err(e)) => {
+                    self.error = Some(e);
+                    self.finished = true;
+                    return;
+                }
+                None => {
+                    self.finished = true;
+                    return;
+                }
+            };
+            let (entries, error) = scan_start_ticks(&mut chunk_reader.events(&chunk));
+            self.queue = entries
+                .into_iter()
+                .map(|(ticks, offset)| {
+                    let nanos = match ticks {
+                        i64::MIN => chunk.header.start_time_nanos,
+                        ticks => chunk.header.ticks_to_epoch_nanos(ticks),
+                    };
+                    (nanos, offset)
+                })
+                .collect();
+            // events without startTime are placed at the chunk start, which may come after others
+            self.queue
+                .make_contiguous()
+                .sort_by_key(|(nanos, _)| *nanos);
+            self.error = error;
+            self.current = Some((chunk_reader, chunk));
+        }
+    }
+}
+
+impl<T: Read + Seek> MergedEvents<T> {
+    pub fn new(readers: Vec<JfrReader<T>>) -> Self {
+        let sources = readers
+            .into_iter()
+            .map(|reader| Source {
+                reader,
+                current: None,
+                queue: VecDeque::new(),
+                error: None,
+                finished: false,
+            })
+            .collect();
+        Self { sources }
+    }
+
+    /// Returns the earliest event among the recordings.
+    /// Errors are returned in place of the events which failed to read, then the recording is skipped.
+    #[allow(clippy::should_implement_trait)]
+    pub fn next(&mut self) -> Option<Result<MergedEvent<'_>>> {
+        for source in self.sources.iter_mut() {
+            source.fill();
+        }
+        // the event queue of the source is exhausted when the error is reported
+        if let Some(source) = self
+            .sources
+            .iter_mut()
+            .find(|s| s.queue.is_empty() && s.error.is_some())
+        {
+            source.finished = true;
+            return source.error.take().map(Err);
+        }
+
+        let (index, source) = self
+            .sources
+            .iter_mut()
+            .enumerate()
+            .filter(|(_, s)| !s.queue.is_empty())
+            .min_by_key(|(_, s)| s.queue[0].0)?;
+        let (start_time_nanos, offset) = source.queue.pop_front()?;
+        let (chunk_reader, chunk) = source.current.as_ref()?;
+        let event = chunk_reader.events_from_offset(chunk, offset).next()?;
+        Some(event.map(|event| MergedEvent {
+            source: index,
+            start_time_nanos,
+            event,
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::test_data;
+    use std::fs::File;
+
+    fn count_events(file_name: &str) -> usize {
+        let mut reader = JfrReader::new(File::open(test_data(file_name)).unwrap());
+        let mut events = reader.all_events();
+        let mut count = 0;
+        while let Some(event) = events.next() {
+            event.unwrap();
+            count += 1;
+        }
+        count
+    }
+
+    #[test]
+    fn test_merge() {
+        let files = [
+            "recording.jfr",
+            "profiler-multichunk.jfr",
+            "profiler-wall.jfr",
+        ];
+        let mut events = MergedEvents::new(
+            files
+                .iter()
+                .map(|f| JfrReader::new(File::open(test_data(f)).unwrap()))
+                .collect(),
+        );
+
+        let mut counts = vec![0; files.len()];
+        let mut last_nanos = i64::MIN;
+        let mut out_of_order = 0;
+        while let Some(merged) = events.next() {
+            let merged = merged.unwrap();
+            counts[merged.source] += 1;
+            if merged.start_time_nanos < last_nanos {
+                out_of_order += 1;
+            }
+            last_nanos = merged.start_time_nanos;
+        }
+        assert_eq!(
+            counts,
+            files.iter().map(|f| count_events(f)).collect::<Vec<_>>()
+        );
+        // the recordings are taken at different times, so they are merged without interleaving,
+        // except the chunks of the multi-chunk recording overlapping slightly
+        assert!(out_of_order < counts[1] / 100);
+    }
+
+    #[test]
+    fn test_merge_same_recording() {
+        let mut events = MergedEvents::new(vec![
+            JfrReader::new(File::open(test_data("recording.jfr")).unwrap()),
+            JfrReader::new(File::open(test_data("recording.jfr")).unwrap()),
+        ]);
+
+        let mut merged = vec![];
+        while let Some(event) = events.next() {
+            let event = event.unwrap();
+            merged.push((
+                event.source,
+                event.start_time_nanos,
+                event.event.byte_offset(),
+            ));
+        }
+        assert_eq!(merged.len(), count_events("recording.jfr") * 2);
+        assert!(merged.windows(2).all(|w| w[0].1 <= w[1].1));
+        let of_source = |source| {
+            merged
+                .iter()
+                .filter(|(s, _, _)| *s == source)
+                .map(|(_, nanos, offset)| (*nanos, *offset))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(of_source(0), of_source(1));
+        // the first source comes first on ties
+        assert_eq!(merged[0].0, 0);
+    }
+
+    #[test]
+    fn test_merge_error() {
+        let mut events = MergedEvents::new(vec![
+            JfrReader::new(File::open(test_data("invalid.jfr")).unwrap()),
+            JfrReader::new(File::open(test_data("profiler-wall.jfr")).unwrap()),
+        ]);
+        assert!(events.next().unwrap().is_err());
+
+        let mut count = 0;
+        while let Some(event) = events.next() {
+            assert_eq!(event.unwrap().source, 1);
+            count += 1;
+        }
+        assert_eq!(count, count_events("profiler-wall.jfr"));
+    }
+}
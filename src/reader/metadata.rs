@@ -3,14 +3,16 @@
 //!
 //! Related JMC code: [ChunkMetadata.java](https://github.com/openjdk/jmc/blob/8.2.0-ga/core/org.openjdk.jmc.flightrecorder/src/main/java/org/openjdk/jmc/flightrecorder/internal/parser/v1/ChunkMetadata.java)
 
-use crate::reader::byte_stream::ByteStream;
+use crate::reader::byte_stream::{ByteStream, Input};
+use crate::reader::known_types::KnownType;
 use crate::reader::type_descriptor::{
-    FieldDescriptor, StringTable, TickUnit, TypeDescriptor, TypePool, Unit,
+    FieldDescriptor, SettingDescriptor, StringTable, TickUnit, TypeDescriptor, TypePool, Unit,
 };
 use crate::reader::{ChunkHeader, Error, Result};
 use crate::EVENT_TYPE_METADATA;
+use rustc_hash::FxHashMap;
 use std::collections::HashMap;
-use std::io::{Read, Seek};
+use std::io::{Cursor, Seek};
 use std::rc::Rc;
 
 #[derive(Debug)]
@@ -52,7 +54,7 @@ impl<'st> ElementType<'st> {
             ElementType::Class(e) => match child {
                 ElementType::Field(f) => e.fields.push(f),
                 ElementType::Annotation(a) => e.annotations.push(a),
-                ElementType::Setting(s) => e.setting = Some(s),
+                ElementType::Setting(s) => e.settings.push(s),
                 _ => {}
             },
             ElementType::Field(e) => {
@@ -89,6 +91,19 @@ impl<'st> ElementType<'st> {
                 "dimension" => f.dimension = Some(value.parse().map_err(|_| Error::InvalidFormat)?),
                 _ => {}
             },
+            ElementType::Region(r) => match key {
+                "locale" => r.locale = Some(value.clone()),
+                "gmtOffset" => {
+                    r.gmt_offset = Some(value.parse().map_err(|_| Error::InvalidFormat)?)
+                }
+                _ => {}
+            },
+            ElementType::Setting(s) => match key {
+                "name" => s.setting_identifier = Some(value),
+                "class" => s.class_id = value.parse().map_err(|_| Error::InvalidFormat)?,
+                "defaultValue" => s.default_value = Some(value),
+                _ => {}
+            },
             ElementType::Annotation(a) => match key {
                 "class" => a.class_id = value.parse().map_err(|_| Error::InvalidFormat)?,
                 _ => {
@@ -113,13 +128,16 @@ struct MetadataElement<'st> {
 }
 
 #[derive(Debug, Default)]
-struct RegionElement {}
+struct RegionElement {
+    locale: Option<Rc<str>>,
+    gmt_offset: Option<i64>,
+}
 
 #[derive(Debug, Default)]
 struct ClassElement<'st> {
     annotations: Vec<AnnotationElement<'st>>,
     fields: Vec<FieldElement<'st>>,
-    setting: Option<SettingElement<'st>>,
+    settings: Vec<SettingElement<'st>>,
     class_id: i64,
     type_identifier: Option<&'st Rc<str>>,
     super_type: Option<&'st Rc<str>>,
@@ -144,22 +162,106 @@ struct AnnotationElement<'st> {
 #[derive(Debug, Default)]
 struct SettingElement<'st> {
     annotations: Vec<AnnotationElement<'st>>,
+    setting_identifier: Option<&'st Rc<str>>,
+    class_id: i64,
+    default_value: Option<&'st Rc<str>>,
+}
+
+/// Metadata of the last chunk, which is reused when the next chunk has the identical metadata body.
+///
+/// Consecutive chunks of a recording usually repeat the same metadata unless new event types
+/// are registered, so this saves parsing and allocating the type pool for each chunk.
+/// Each chunk iterator has its own cache, so the metadata is not shared across iterations.
+#[derive(Debug, Default)]
+pub(crate) struct MetadataCache {
+    body: Vec<u8>,
+    metadata: Option<Rc<Metadata>>,
+}
+
+impl MetadataCache {
+    /// Reads the metadata of the chunk, or returns the cached one if the body is identical.
+    /// The stream is positioned at the end of the metadata in both cases.
+    pub(crate) fn read<B: AsRef<[u8]>>(
+        &mut self,
+        stream: &mut ByteStream<Cursor<B>>,
+        header: &ChunkHeader,
+    ) -> Result<Rc<Metadata>> {
+        let size = Metadata::read_event_header(stream, header)?;
+        let start = stream.get_ref().position();
+        let end = (header.metadata_offset as u64)
+            .checked_add(size)
+            .ok_or(Error::InvalidFormat)?;
+
+        let body = match stream
+            .get_ref()
+            .get_ref()
+            .as_ref()
+            .get(start as usize..end as usize)
+        {
+            Some(body) => body,
+            // the metadata is not complete, so let the parser report the error
+            None => return Metadata::read_body(stream).map(Rc::new),
+        };
+        if let Some(metadata) = &self.metadata {
+            if self.body == body {
+                stream.seek(end)?;
+                return Ok(metadata.clone());
+            }
+        }
+
+        self.body.clear();
+        self.body.extend_from_slice(body);
+        self.metadata = None;
+        let metadata = Rc::new(Metadata::read_body(stream)?);
+        self.metadata = Some(metadata.clone());
+        Ok(metadata)
+    }
 }
 
 #[derive(Debug)]
 pub struct Metadata {
     pub type_pool: TypePool,
+    region: Option<Region>,
+}
+
+/// The locale and timezone of the recording, which JMC uses to render local times
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Region {
+    pub(crate) locale: Option<Rc<str>>,
+    /// The offset from GMT in milliseconds
+    pub gmt_offset_millis: i64,
+}
+
+impl Region {
+    /// The locale of the recording JVM (e.g. `en_US`)
+    pub fn locale(&self) -> Option<&str> {
+        self.locale.as_ref().map(|s| s.as_ref())
+    }
+
+    /// Converts the nanoseconds since UNIX epoch to the local time in the recording's timezone,
+    /// represented as nanoseconds since the local epoch
+    pub fn to_local_nanos(&self, epoch_nanos: i64) -> i64 {
+        epoch_nanos + self.gmt_offset_millis * 1_000_000
+    }
 }
 
 impl Metadata {
-    pub fn try_new<T: Read + Seek>(
+    pub fn try_new<T: Input + Seek>(
         stream: &mut ByteStream<T>,
         header: &ChunkHeader,
     ) -> Result<Self> {
+        Self::read_event_header(stream, header)?;
+        Self::read_body(stream)
+    }
+
+    /// Reads the fields of the metadata event before the body, and returns the size of the event
+    fn read_event_header<T: Input + Seek>(
+        stream: &mut ByteStream<T>,
+        header: &ChunkHeader,
+    ) -> Result<u64> {
         stream.seek(header.metadata_offset as u64)?;
 
-        // size
-        stream.read_i32()?;
+        let size = u64::try_from(stream.read_i32()?).map_err(|_| Error::InvalidFormat)?;
         if stream.read_i64()? != EVENT_TYPE_METADATA {
             return Err(Error::InvalidFormat);
         }
@@ -170,16 +272,25 @@ impl Metadata {
         // metadata id
         stream.read_i64()?;
 
+        Ok(size)
+    }
+
+    pub(crate) fn read_body<T: Input>(stream: &mut ByteStream<T>) -> Result<Self> {
         let string_table = StringTable::try_new(stream)?;
-        let type_pool = Self::read_types(stream, &string_table)?;
+        let (type_pool, region) = Self::read_types(stream, &string_table)?;
+
+        Ok(Self { type_pool, region })
+    }
 
-        Ok(Self { type_pool })
+    /// Returns the region of the recording, if the metadata has it
+    pub fn region(&self) -> Option<&Region> {
+        self.region.as_ref()
     }
 
-    fn read_types<T: Read>(
+    fn read_types<T: Input>(
         stream: &mut ByteStream<T>,
         string_table: &StringTable,
-    ) -> Result<TypePool> {
+    ) -> Result<(TypePool, Option<Region>)> {
         let mut class_name_map = HashMap::new();
 
         // we don't care root element name. just consume
@@ -192,16 +303,18 @@ impl Metadata {
             ElementType::Root(RootElement::default()),
         )?;
 
-        let type_pool = if let ElementType::Root(root) = root_element {
-            Self::declare_types(root, class_name_map)?
+        if let ElementType::Root(mut root) = root_element {
+            let region = root.region.take().map(|r| Region {
+                locale: r.locale,
+                gmt_offset_millis: r.gmt_offset.unwrap_or(0),
+            });
+            Ok((Self::declare_types(root, class_name_map)?, region))
         } else {
-            return Err(Error::InvalidFormat);
-        };
-
-        Ok(type_pool)
+            Err(Error::InvalidFormat)
+        }
     }
 
-    fn read_element<'st, T: Read>(
+    fn read_element<'st, T: Input>(
         stream: &mut ByteStream<T>,
         string_table: &'st StringTable,
         class_name_map: &mut HashMap<i64, &'st str>,
@@ -247,19 +360,23 @@ impl Metadata {
         };
 
         for class_element in classes {
+            let name = class_element
+                .type_identifier
+                .cloned()
+                .ok_or(Error::InvalidFormat)?;
             let mut desc = TypeDescriptor {
                 class_id: class_element.class_id,
-                name: class_element
-                    .type_identifier
-                    .cloned()
-                    .ok_or(Error::InvalidFormat)?,
+                known_type: KnownType::from_name(&name),
+                name,
                 super_type: class_element.super_type.cloned(),
                 simple_type: class_element.simple_type.unwrap_or(false),
                 fields: Vec::with_capacity(class_element.fields.len()),
+                field_indices: FxHashMap::default(),
                 label: None,
                 description: None,
                 experimental: false,
                 category: vec![],
+                settings: Vec::with_capacity(class_element.settings.len()),
             };
 
             for annot in class_element.annotations {
@@ -286,9 +403,30 @@ impl Metadata {
                 for annot in field.annotations {
                     Self::resolve_field_annotation(&mut field_desc, &annot, &class_name_map)?;
                 }
+                desc.field_indices
+                    .entry(field_desc.name.clone())
+                    .or_insert(desc.fields.len());
                 desc.fields.push(field_desc);
             }
 
+            for setting in class_element.settings {
+                let mut setting_desc = SettingDescriptor {
+                    class_id: setting.class_id,
+                    name: setting
+                        .setting_identifier
+                        .cloned()
+                        .ok_or(Error::InvalidFormat)?,
+                    default_value: setting.default_value.cloned(),
+                    label: None,
+                    description: None,
+                };
+
+                for annot in setting.annotations {
+                    Self::resolve_setting_annotation(&mut setting_desc, &annot, &class_name_map)?;
+                }
+                desc.settings.push(setting_desc);
+            }
+
             pool.register(class_element.class_id, desc);
         }
 
@@ -322,6 +460,21 @@ impl Metadata {
         Ok(())
     }
 
+    fn resolve_setting_annotation(
+        desc: &mut SettingDescriptor,
+        annot: &AnnotationElement,
+        class_name_map: &HashMap<i64, &str>,
+    ) -> Result<()> {
+        if let Some(&name) = class_name_map.get(&annot.class_id) {
+            match name {
+                "jdk.jfr.Label" => desc.label = annot.attributes.get("value").cloned(),
+                "jdk.jfr.Description" => desc.description = annot.attributes.get("value").cloned(),
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
     fn resolve_field_annotation(
         desc: &mut FieldDescriptor,
         annot: &AnnotationElement,
@@ -367,6 +520,7 @@ impl Metadata {
 }
 
 #[cfg(test)]
+#[allow(clippy::field_reassign_with_default)]
 mod tests {
     use super::*;
 
@@ -413,7 +567,7 @@ mod tests {
         element
     }
 
-    fn field(class_id: i64, name: &Rc<str>) -> FieldElement {
+    fn field(class_id: i64, name: &Rc<str>) -> FieldElement<'_> {
         let mut element = FieldElement::default();
         element.class_id = class_id;
         element.field_identifier = Some(name);
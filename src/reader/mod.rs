@@ -1,33 +1,137 @@
 //! Module to read JFR files and parse as Rust data structures.
 
-use crate::reader::byte_stream::{ByteStream, IntEncoding};
-use crate::reader::constant_pool::ConstantPool;
-use crate::reader::event::EventIterator;
-use crate::reader::metadata::Metadata;
+use crate::decode::DecodeError;
+use crate::reader::byte_stream::{ByteStream, Input, IntEncoding, Unbuffered};
+use crate::reader::checkpoint::Checkpoint;
+use crate::reader::constant_pool::{collect_references, ConstantPool, ThreadIndex};
+use crate::reader::event::{Event, EventIterator};
+use crate::reader::known_types::KnownType;
+use crate::reader::limits::{LimitKind, Limits};
+use crate::reader::loaded_chunk::LoadedChunkIterator;
+use crate::reader::metadata::{Metadata, MetadataCache};
+use crate::reader::progress::{CancellationToken, Progress};
+use crate::reader::stats::{EventCounters, ParseStats, Stopwatch};
+use crate::reader::types::builtin::{Class, JdkMethod, JdkThread, Symbol};
+use crate::reader::value_descriptor::{StringRepr, ValueDescriptor};
 use crate::{Version, MAGIC};
+use rustc_hash::FxHashSet;
+use std::cell::OnceCell;
 use std::fmt::Formatter;
 use std::io::{Cursor, Read, Seek};
+use std::ops::Range;
+use std::rc::Rc;
 use std::{fmt, io};
 
-mod byte_stream;
-mod constant_pool;
+#[cfg(feature = "zip")]
+pub mod archive;
+#[cfg(feature = "arena")]
+pub mod arena;
+pub mod async_profiler;
+pub mod bucket;
+pub(crate) mod byte_stream;
+pub mod callback;
+pub mod checkpoint;
+pub mod compat;
+pub mod configuration;
+pub(crate) mod constant_pool;
 pub mod de;
+pub mod dispatch;
+mod display;
 pub mod event;
+pub mod index;
+pub mod interner;
+pub mod known_types;
+pub mod limits;
+pub mod loaded_chunk;
+pub mod merge;
 pub mod metadata;
+pub mod path;
+pub mod progress;
+pub mod raw_string;
+pub mod schema;
+mod ser;
+pub mod sorted;
+pub mod stats;
+pub mod streaming;
+pub mod summary;
 pub mod type_descriptor;
 pub mod types;
+pub mod value;
 pub mod value_descriptor;
 
+/// Errors returned while reading recordings.
+///
+/// New variants may be added in minor releases, so matches should have a wildcard arm.
+/// The underlying error (e.g. [`io::Error`], or the cause wrapped by [`Error::Decode`])
+/// is available through [`std::error::Error::source`].
 #[derive(Debug)]
+#[non_exhaustive]
 pub enum Error {
     InvalidFormat,
     InvalidStringIndex(i32),
     InvalidString,
     InvalidChar(std::char::CharTryFromError),
     UnsupportedVersion(Version),
+    /// The chunk header has feature bits which are unknown to this crate.
+    /// This is returned only under [`VersionPolicy::Strict`].
+    UnsupportedFeatures(i32),
     ClassNotFound(i64),
     IoError(io::Error),
     DeserializeError(String),
+    SerializeError(String),
+    /// The file ends in the middle of a chunk.
+    /// `valid_bytes` is the length of the file prefix which consists of complete chunks.
+    TruncatedChunk {
+        valid_bytes: u64,
+    },
+    /// The file has fewer chunks than the requested index.
+    ChunkNotFound(usize),
+    /// The event offset index doesn't match the file.
+    StaleIndex,
+    /// The chunk header declares a size larger than [`JfrReader::max_chunk_size`].
+    ChunkTooLarge {
+        size: u64,
+        limit: u64,
+    },
+    /// The size declared in the recording exceeds [`Limits`] set by [`JfrReader::limits`].
+    LimitExceeded {
+        kind: LimitKind,
+        limit: u64,
+        actual: u64,
+    },
+    /// Decoding a value failed.
+    /// `path` is where the failure happened from the event type or the constant,
+    /// e.g. `["jdk.ExecutionSample", "stackTrace", "frames[3]", "method"]`.
+    Decode {
+        path: Vec<String>,
+        source: Box<Error>,
+    },
+    /// The parse is cancelled by [`progress::CancellationToken`].
+    Cancelled,
+}
+
+impl Error {
+    /// Returns the innermost error, skipping [`Error::Decode`] contexts
+    pub fn root_cause(&self) -> &Error {
+        match self {
+            Error::Decode { source, .. } => source.root_cause(),
+            e => e,
+        }
+    }
+
+    /// Prepends the segment to the decoding path
+    pub(crate) fn in_context<F: FnOnce() -> String>(self, segment: F) -> Self {
+        match self {
+            Error::Decode { mut path, source } => {
+                path.insert(0, segment());
+                Error::Decode { path, source }
+            }
+            e => Error::Decode {
+                path: vec![segment()],
+                source: Box::new(e),
+            },
+        }
+    }
 }
 
 impl fmt::Display for Error {
@@ -38,73 +142,469 @@ impl fmt::Display for Error {
             Error::InvalidString => write!(f, "Invalid string"),
             Error::InvalidChar(e) => write!(f, "Invalid char: {}", e),
             Error::UnsupportedVersion(v) => write!(f, "Unsupported version: {}", v),
+            Error::UnsupportedFeatures(bits) => {
+                write!(f, "Unsupported feature bits: {:#x}", bits)
+            }
             Error::ClassNotFound(i) => write!(f, "Class not found for id: {}", i),
             Error::IoError(e) => write!(f, "IO error: {}", e),
             Error::DeserializeError(msg) => write!(f, "Failed to deserialize: {}", msg),
+            Error::SerializeError(msg) => write!(f, "Failed to serialize: {}", msg),
+            Error::TruncatedChunk { valid_bytes } => {
+                write!(f, "Truncated chunk after {} valid bytes", valid_bytes)
+            }
+            Error::ChunkNotFound(i) => write!(f, "Chunk not found for index: {}", i),
+            Error::StaleIndex => write!(f, "Index doesn't match the file"),
+            Error::ChunkTooLarge { size, limit } => {
+                write!(f, "Chunk size {} exceeds the limit {}", size, limit)
+            }
+            Error::LimitExceeded {
+                kind,
+                limit,
+                actual,
+            } => write!(f, "The {} {} exceeds the limit {}", kind, actual, limit),
+            Error::Decode { path, source } => {
+                write!(f, "Failed to decode {}: {}", path.join(" > "), source)
+            }
+            Error::Cancelled => write!(f, "Cancelled"),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::InvalidChar(e) => Some(e),
+            Error::IoError(e) => Some(e),
+            Error::Decode { source, .. } => Some(source.as_ref()),
+            _ => None,
         }
     }
 }
 
-impl std::error::Error for Error {}
+impl From<DecodeError> for Error {
+    fn from(e: DecodeError) -> Self {
+        match e {
+            DecodeError::UnexpectedEof => Error::IoError(io::ErrorKind::UnexpectedEof.into()),
+            DecodeError::InvalidFormat => Error::InvalidFormat,
+            DecodeError::InvalidString => Error::InvalidString,
+            DecodeError::InvalidChar(c) => Error::InvalidChar(char::try_from(c).unwrap_err()),
+            DecodeError::StringTooLong { limit, actual } => Error::LimitExceeded {
+                kind: LimitKind::StringLength,
+                limit,
+                actual,
+            },
+        }
+    }
+}
 
 pub type Result<T> = std::result::Result<T, Error>;
 type HeapByteStream = ByteStream<Cursor<Vec<u8>>>;
 
+/// How to handle the versions and the features which are unknown to this crate
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Default)]
+pub enum VersionPolicy {
+    /// Accept only the versions and the feature bits known to this crate.
+    Strict,
+    /// Parse any minor version of the supported major versions, ignoring unknown feature bits.
+    /// The unknowns can be inspected through [`ChunkHeader::is_known_version`] and [`ChunkHeader::unknown_features`].
+    #[default]
+    BestEffort,
+}
+
+/// How much of the constant pools to keep in memory
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Default)]
+pub enum ConstantPoolMode {
+    /// Keep all constants
+    #[default]
+    Full,
+    /// Keep only the constants referenced from the events to read.
+    /// See [`JfrReader::retain_constants_for`] to name the event types explicitly
+    ReferencedOnly,
+    /// Don't read the constant pools, so that references in the events can't be resolved.
+    /// This is the fastest when only the metadata or primitive fields are needed
+    Skip,
+}
+
+/// Which events of the types annotated with `jdk.jfr.Experimental` to read
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Default)]
+pub enum ExperimentalEvents {
+    #[default]
+    Include,
+    Exclude,
+    /// Read only the experimental events, e.g. for testing them in JDK development
+    Only,
+}
+
+/// Event types to read, set by [`JfrReader::event_allowlist`], [`JfrReader::event_denylist`]
+/// and [`JfrReader::experimental_events`]
+#[derive(Debug, Clone, Default)]
+pub(crate) struct EventFilter {
+    experimental_events: ExperimentalEvents,
+    allowlist: Option<FxHashSet<String>>,
+    denylist: FxHashSet<String>,
+}
+
+impl EventFilter {
+    /// Resolves the type names to the class ids of the event types to read.
+    /// Returns None if all types are read.
+    pub(crate) fn resolve(&self, metadata: &Metadata) -> Option<Rc<FxHashSet<i64>>> {
+        if self.experimental_events == ExperimentalEvents::Include
+            && self.allowlist.is_none()
+            && self.denylist.is_empty()
+        {
+            return None;
+        }
+        let selected = metadata
+            .type_pool
+            .event_types()
+            .filter(|t| match self.experimental_events {
+                ExperimentalEvents::Include => true,
+                ExperimentalEvents::Exclude => !t.experimental,
+                ExperimentalEvents::Only => t.experimental,
+            })
+            .filter(|t| {
+                self.allowlist
+                    .as_ref()
+                    .is_none_or(|allowlist| allowlist.contains(t.name()))
+            })
+            .filter(|t| !self.denylist.contains(t.name()))
+            .map(|t| t.class_id)
+            .collect();
+        Some(Rc::new(selected))
+    }
+}
+
 #[derive(Debug)]
 pub struct ChunkHeader {
+    position: u64,
+    version: Version,
     pub chunk_size: i64,
-    constant_pool_offset: i64,
-    metadata_offset: i64,
+    pub(crate) constant_pool_offset: i64,
+    pub(crate) metadata_offset: i64,
     pub start_time_nanos: i64,
     pub duration_nanos: i64,
     pub start_ticks: i64,
+    /// Always positive, which is validated on reading the header
     pub ticks_per_second: i64,
-    features: i32,
+    pub(crate) features: i32,
 }
 
 impl ChunkHeader {
     /// The size from the beginning of the chunk (right before MAGIC) to the header end
-    const HEADER_SIZE: u64 = 68;
-    const FEATURES_COMPRESSED_INTS: i32 = 1;
+    pub(crate) const HEADER_SIZE: u64 = 68;
+    /// The offset of the features field from the beginning of the chunk
+    pub(crate) const FEATURES_OFFSET: usize = 64;
+    pub(crate) const FEATURES_COMPRESSED_INTS: i32 = 1;
+    pub(crate) const FEATURES_FINAL_CHUNK: i32 = 1 << 1;
+    const KNOWN_FEATURES: i32 = Self::FEATURES_COMPRESSED_INTS | Self::FEATURES_FINAL_CHUNK;
+    /// The highest byte of the features field is the file state rather than feature bits
+    const FEATURES_MASK: i32 = 0x00ff_ffff;
+    const FILE_STATE_UPDATING: u8 = 255;
+    const KNOWN_VERSIONS: [Version; 3] = [
+        Version { major: 1, minor: 0 },
+        Version { major: 2, minor: 0 },
+        Version { major: 2, minor: 1 },
+    ];
+
+    pub fn version(&self) -> Version {
+        self.version
+    }
+
+    /// Returns true if the version is known to this crate
+    pub fn is_known_version(&self) -> bool {
+        Self::KNOWN_VERSIONS.contains(&self.version)
+    }
+
+    /// Returns the feature bits which are unknown to this crate
+    pub fn unknown_features(&self) -> i32 {
+        self.flags() & !Self::KNOWN_FEATURES
+    }
+
+    /// Returns the raw feature bits
+    pub fn flags(&self) -> i32 {
+        self.features & Self::FEATURES_MASK
+    }
+
+    /// Returns true if integers in the chunk are encoded as varint
+    pub fn is_compressed_ints(&self) -> bool {
+        self.features & Self::FEATURES_COMPRESSED_INTS != 0
+    }
+
+    /// Returns true if this is the last chunk of the recording.
+    /// Note that older JVMs (before JDK 14) and async-profiler don't set this flag.
+    pub fn is_final_chunk(&self) -> bool {
+        self.features & Self::FEATURES_FINAL_CHUNK != 0
+    }
 
-    fn int_encoding(&self) -> IntEncoding {
-        if self.features & Self::FEATURES_COMPRESSED_INTS != 0 {
+    /// Returns the file state byte, which JVM sets to 255 while it's updating the header
+    pub fn file_state(&self) -> u8 {
+        (self.features >> 24) as u8
+    }
+
+    /// Returns true if the chunk is completely written.
+    ///
+    /// JVM leaves the duration 0 until the chunk is finished, and sets the file state
+    /// while it's updating the header.
+    pub fn is_finished(&self) -> bool {
+        self.duration_nanos != 0 && self.file_state() != Self::FILE_STATE_UPDATING
+    }
+
+    /// Returns the byte offset of the chunk from the beginning of the file,
+    /// which can be passed to [`JfrReader::open_chunk_at`].
+    pub fn position(&self) -> u64 {
+        self.position
+    }
+
+    pub(crate) fn int_encoding(&self) -> IntEncoding {
+        if self.is_compressed_ints() {
             IntEncoding::Compressed
         } else {
             IntEncoding::Raw
         }
     }
 
-    fn chunk_body_size(&self) -> u64 {
+    /// Converts the ticks (e.g. the value of `startTime` field) to the nanoseconds since UNIX epoch
+    pub fn ticks_to_epoch_nanos(&self, ticks: i64) -> i64 {
+        let elapsed = (ticks - self.start_ticks) as i128 * 1_000_000_000;
+        self.start_time_nanos + (elapsed / self.ticks_per_second as i128) as i64
+    }
+
+    /// Converts the ticks (e.g. the value of `duration` field) to nanoseconds
+    pub fn ticks_to_nanos(&self, ticks: i64) -> i64 {
+        (ticks as i128 * 1_000_000_000 / self.ticks_per_second as i128) as i64
+    }
+
+    /// The end of this chunk in nanoseconds since UNIX epoch
+    pub fn end_time_nanos(&self) -> i64 {
+        self.start_time_nanos + self.duration_nanos
+    }
+
+    pub(crate) fn chunk_body_size(&self) -> u64 {
         self.chunk_size as u64 - Self::HEADER_SIZE
     }
 
-    fn body_start_offset(&self) -> u64 {
+    pub(crate) fn body_start_offset(&self) -> u64 {
         Self::HEADER_SIZE
     }
 }
 
 pub struct Chunk {
     pub header: ChunkHeader,
-    pub metadata: Metadata,
-    constant_pool: ConstantPool,
+    /// Shared with the other chunks of the recording which have the identical metadata
+    pub metadata: Rc<Metadata>,
+    pub(crate) constant_pool: ConstantPool,
+    // The number of bytes actually available when the chunk is truncated
+    truncated_size: Option<u64>,
+    // Built on the first lookup of the thread
+    thread_index: OnceCell<ThreadIndex>,
+    // Applied to the event iterators
+    pub(crate) limits: Limits,
+    pub(crate) string_repr: StringRepr,
+    // Class ids of the event types to read, resolved from the event filter once per chunk. All types if None
+    pub(crate) selected_events: Option<Rc<FxHashSet<i64>>>,
+    pub(crate) cancellation_token: Option<CancellationToken>,
+}
+
+impl Chunk {
+    /// Returns true if this chunk is the readable prefix of a truncated chunk.
+    /// Events of such chunk are read until the last complete one.
+    pub fn is_truncated(&self) -> bool {
+        self.truncated_size.is_some()
+    }
+
+    /// Returns the checkpoint (constant pool) events ordered by the offset.
+    /// Empty if the constant pool is not read, e.g. by [`JfrReader::chunk_metadata`].
+    pub fn checkpoints(&self) -> &[Checkpoint] {
+        &self.constant_pool.checkpoints
+    }
+
+    /// Returns the ranges of the event offsets written by each flush, in the order they are written.
+    ///
+    /// Each segment ends at a flush checkpoint, and the last one ends at the end of the chunk,
+    /// so the last one may contain no events. Chunks written without flushes consist of single segment.
+    /// The events of a segment are read by [`ChunkReader::events_from_offset`] and [`EventIterator::until`].
+    pub fn flush_segments(&self) -> Vec<Range<u64>> {
+        let body_size = self.body_size();
+        let mut segments = vec![];
+        let mut start = 0;
+        for checkpoint in self.checkpoints().iter().filter(|c| c.is_flush()) {
+            let end = checkpoint.end_offset().min(body_size);
+            if end > start {
+                segments.push(start..end);
+                start = end;
+            }
+        }
+        if start < body_size || segments.is_empty() {
+            segments.push(start..body_size);
+        }
+        segments
+    }
+
+    /// Deserializes the constant pool entries of the type lazily, ordered by the constant index.
+    /// Each item is the pair of the constant index and the value.
+    pub fn constants<'a, T>(
+        &'a self,
+        type_name: &str,
+    ) -> impl Iterator<Item = Result<(i64, T)>> + 'a
+    where
+        T: serde::de::Deserialize<'a>,
+    {
+        let class_id = self
+            .metadata
+            .type_pool
+            .get_by_name(type_name)
+            .map(|t| t.class_id);
+        self.constants_of(class_id)
+    }
+
+    /// Deserializes all `jdk.types.Symbol` in the constant pool
+    pub fn symbols(&self) -> impl Iterator<Item = Result<(i64, Symbol<'_>)>> + '_ {
+        self.constants_of(self.metadata.type_pool.class_id_of(KnownType::Symbol))
+    }
+
+    /// Deserializes all `java.lang.Class` in the constant pool
+    pub fn classes(&self) -> impl Iterator<Item = Result<(i64, Class<'_>)>> + '_ {
+        self.constants_of(self.metadata.type_pool.class_id_of(KnownType::Class))
+    }
+
+    /// Deserializes all `jdk.types.Method` in the constant pool
+    pub fn methods(&self) -> impl Iterator<Item = Result<(i64, JdkMethod<'_>)>> + '_ {
+        self.constants_of(self.metadata.type_pool.class_id_of(KnownType::Method))
+    }
+
+    /// Deserializes all `java.lang.Thread` in the constant pool
+    pub fn threads(&self) -> impl Iterator<Item = Result<(i64, JdkThread<'_>)>> + '_ {
+        self.constants_of(self.metadata.type_pool.class_id_of(KnownType::Thread))
+    }
+
+    /// Looks up `java.lang.Thread` in the constant pool by the Java thread id
+    pub fn thread_by_id(&self, java_thread_id: i64) -> Option<Result<JdkThread<'_>>> {
+        let index = *self.thread_index().by_java_id.get(&java_thread_id)?;
+        self.thread_at(index)
+    }
+
+    /// Looks up `java.lang.Thread` in the constant pool by the OS thread id
+    pub fn thread_by_os_id(&self, os_thread_id: i64) -> Option<Result<JdkThread<'_>>> {
+        let index = *self.thread_index().by_os_id.get(&os_thread_id)?;
+        self.thread_at(index)
+    }
+
+    fn thread_at(&self, constant_index: i64) -> Option<Result<JdkThread<'_>>> {
+        let class_id = self.metadata.type_pool.class_id_of(KnownType::Thread)?;
+        let value = self.constant_pool.get(&class_id, &constant_index)?;
+        Some(de::from_value_descriptor(self, value))
+    }
+
+    fn thread_index(&self) -> &ThreadIndex {
+        self.thread_index
+            .get_or_init(|| ThreadIndex::new(&self.constant_pool, &self.metadata))
+    }
+
+    fn constants_of<'a, T>(
+        &'a self,
+        class_id: Option<i64>,
+    ) -> impl Iterator<Item = Result<(i64, T)>> + 'a
+    where
+        T: serde::de::Deserialize<'a>,
+    {
+        let indices = class_id
+            .map(|id| self.constant_pool.indices_of(id))
+            .unwrap_or_default();
+        indices.into_iter().filter_map(move |index| {
+            let value = self.constant_pool.get(&class_id?, &index)?;
+            Some(de::from_value_descriptor(self, value).map(|v| (index, v)))
+        })
+    }
+
+    /// Drops the constants which are not referenced from the events of the types, directly or indirectly
+    fn retain_referenced_constants(
+        &mut self,
+        chunk_reader: &ChunkReader,
+        class_ids: &FxHashSet<i64>,
+    ) -> Result<()> {
+        let mut roots = vec![];
+        let mut value = ValueDescriptor::Array(vec![]);
+        let mut events = chunk_reader.events(self);
+        while let Some((_, _, event_type)) = events.next_event_header()? {
+            if class_ids.contains(&event_type) {
+                events.read_value_into(event_type, &mut value)?;
+                collect_references(&value, &mut roots);
+            }
+        }
+        self.constant_pool.retain_reachable(roots);
+        Ok(())
+    }
+
+    fn body_size(&self) -> u64 {
+        match self.truncated_size {
+            Some(size) => size.saturating_sub(ChunkHeader::HEADER_SIZE),
+            None => self.header.chunk_body_size(),
+        }
+    }
 }
 
 pub struct ChunkReader {
     stream: HeapByteStream,
+    // the stats of reading the chunk, without the events
+    stats: ParseStats,
+    // updated by the event iterators
+    event_counters: EventCounters,
+}
+
+// the chunk reader can be shared by the threads iterating the events
+#[allow(dead_code)]
+fn _assert_sync() {
+    fn assert_sync<T: Sync>() {}
+    assert_sync::<ChunkReader>();
 }
 
 impl ChunkReader {
-    pub fn events<'a, 'b>(&'b mut self, chunk: &'a Chunk) -> EventIterator<'a, 'b> {
-        EventIterator::new(chunk, &mut self.stream)
+    /// Whole bytes of the chunk including the header
+    pub(crate) fn bytes(&self) -> &[u8] {
+        self.stream.get_ref().get_ref()
+    }
+
+    /// Releases the buffer holding the chunk bytes
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.stream.into_inner().into_inner()
+    }
+
+    /// Returns the statistics of parsing the chunk so far, including the events decoded
+    /// by the event iterators of this reader
+    pub fn stats(&self) -> ParseStats {
+        let mut stats = self.stats;
+        self.event_counters.add_to(&mut stats);
+        stats
+    }
+
+    /// Returns an iterator over the events.
+    /// Each iterator has its own position, so multiple iterators can be used at the same time.
+    pub fn events<'a, 'b>(&'b self, chunk: &'a Chunk) -> EventIterator<'a, 'b> {
+        EventIterator::new(chunk, self.bytes()).with_counters(&self.event_counters)
+    }
+
+    /// Returns an iterator over the events in the ascending order of `startTime`.
+    /// See [`sorted::SortedEvents`] for the details.
+    pub fn events_sorted<'a, 'b>(&'b self, chunk: &'a Chunk) -> sorted::SortedEvents<'a, 'b> {
+        self.events(chunk).sorted()
+    }
+
+    /// Returns an iterator over the events of the types in the category or its subcategories,
+    /// e.g. `&["Java Virtual Machine", "GC"]`
+    pub fn events_in_category<'a, 'b>(
+        &'b self,
+        chunk: &'a Chunk,
+        category: &[&str],
+    ) -> EventIterator<'a, 'b> {
+        self.events(chunk).in_category(category)
     }
 
     pub fn events_from_offset<'a, 'b>(
-        &'b mut self,
+        &'b self,
         chunk: &'a Chunk,
         start_offset: u64,
     ) -> EventIterator<'a, 'b> {
-        let mut iter = EventIterator::new(chunk, &mut self.stream);
+        let mut iter = EventIterator::new(chunk, self.bytes()).with_counters(&self.event_counters);
         iter.seek(start_offset);
         iter
     }
@@ -115,108 +615,385 @@ pub struct ChunkIterator<'a, T> {
     // Whether to skip constant pool or not.
     // This is used for the case where we want to parse the type metadata only.
     skip_constant_pool: bool,
+    // Whether to stop silently at the incomplete chunk, which will be read on the next poll.
+    // This is used for following a file which is being written.
+    follow: bool,
+    // Set when the readable prefix of the truncated chunk is returned.
+    // Then the next call reports the truncation as an error.
+    truncated: bool,
+    finished: bool,
+    // Kept here rather than in the reader, since the metadata isn't Send
+    metadata_cache: MetadataCache,
 }
 
 impl<'a, T: Read + Seek> Iterator for ChunkIterator<'a, T> {
     type Item = Result<(ChunkReader, Chunk)>;
 
     fn next(&mut self) -> Option<Self::Item> {
+        if self.finished {
+            return None;
+        }
         match self.internal_next() {
             Ok(Some(chunk)) => Some(Ok(chunk)),
-            Ok(None) => None,
-            Err(e) => Some(Err(e)),
+            Ok(None) => {
+                self.finished = true;
+                None
+            }
+            Err(Error::TruncatedChunk { .. }) if self.follow => {
+                self.finished = true;
+                None
+            }
+            Err(e) => {
+                // we can't proceed to the next chunk anymore once we got an error
+                self.finished = true;
+                Some(Err(e))
+            }
         }
     }
 }
 
 impl<'a, T: Read + Seek> ChunkIterator<'a, T> {
-    fn internal_next(&mut self) -> Result<Option<(ChunkReader, Chunk)>> {
-        self.reader.stream.set_int_encoding(IntEncoding::Raw);
-        self.reader.stream.seek(self.reader.chunk_start_position)?;
-        match self.reader.stream.read_u8() {
-            Ok(magic_head) => {
-                let mut magic = [magic_head, 0, 0, 0];
-                let magic_tail: [u8; 3] = self.reader.stream.read_exact()?;
-                magic[1..].clone_from_slice(&magic_tail);
+    /// Returns the buffer of the chunk reader to be reused for the next chunk.
+    /// See [`JfrReader::recycle`].
+    pub fn recycle(&mut self, chunk_reader: ChunkReader) {
+        self.reader.recycle(chunk_reader);
+    }
 
-                if magic != MAGIC {
-                    return Err(Error::InvalidFormat);
-                }
-            }
-            // Reaching EOF at the beginning of the chunk means just we reached the end of the file
-            // normally, so just returns Ok(None)
-            Err(Error::IoError(e)) if e.kind() == io::ErrorKind::UnexpectedEof => {
-                return Ok(None);
-            }
-            Err(e) => {
-                return Err(e);
-            }
+    fn internal_next(&mut self) -> Result<Option<(ChunkReader, Chunk)>> {
+        if self.reader.is_cancelled() {
+            return Err(Error::Cancelled);
         }
-
-        let version = Version {
-            major: self.reader.stream.read_i16()?,
-            minor: self.reader.stream.read_i16()?,
+        if self.truncated {
+            return Err(self.reader.truncated_error());
+        }
+        let (version, chunk_size) = match self.reader.read_chunk_start()? {
+            Some(v) => v,
+            None => return Ok(None),
         };
-        match version.major {
-            1 | 2 => {}
-            _ => {
-                return Err(Error::UnsupportedVersion(version));
+
+        if let Some(limit) = self.reader.max_chunk_size {
+            if chunk_size as u64 > limit {
+                return Err(Error::ChunkTooLarge {
+                    size: chunk_size as u64,
+                    limit,
+                });
             }
         }
 
-        let chunk_size = self.reader.stream.read_i64()?;
-
         // To reduce the overhead of read against the file, we load entire chunk into memory
         // and do all further operations on it.
         self.reader.stream.seek(self.reader.chunk_start_position)?;
-        let mut heap_stream = ByteStream::new(Cursor::new(
-            self.reader.stream.read_as_bytes(chunk_size as usize)?,
-        ));
+        let mut bytes = self.reader.spare_buffer.take().unwrap_or_default();
+        self.reader
+            .stream
+            .read_as_bytes_into(chunk_size as usize, &mut bytes)?;
+        let chunk_end = self.reader.chunk_start_position + bytes.len() as u64;
+        let truncated_size = if (bytes.len() as u64) < chunk_size as u64 {
+            if self.follow
+                || !self.reader.parse_truncated_chunk
+                || (bytes.len() as u64) < ChunkHeader::HEADER_SIZE
+            {
+                return Err(self.reader.truncated_error());
+            }
+            Some(bytes.len() as u64)
+        } else {
+            None
+        };
+        let mut heap_stream = ByteStream::new(Cursor::new(bytes));
+        heap_stream.set_limits(self.reader.limits);
+        heap_stream.set_string_repr(self.reader.string_repr);
         // magic + version + chunk_size
         heap_stream.seek(4 + 4 + 8)?;
 
-        let header = Self::read_chunk_header(&mut heap_stream, chunk_size)?;
+        let header = self
+            .reader
+            .read_chunk_header(&mut heap_stream, version, chunk_size)?;
         heap_stream.set_int_encoding(header.int_encoding());
+        if self.follow && !header.is_finished() {
+            // the chunk is flushed but still being written, so we stay at the chunk boundary
+            // to read the chunk again on the next call
+            self.reader.spare_buffer = Some(heap_stream.into_inner().into_inner());
+            return Ok(None);
+        }
+
+        let mut stats = ParseStats {
+            bytes_read: chunk_end - self.reader.chunk_start_position,
+            ..ParseStats::default()
+        };
+        let (metadata, constant_pool) = match truncated_size {
+            Some(size) => {
+                self.read_truncated_chunk_body(&mut heap_stream, &header, size, &mut stats)?
+            }
+            None => {
+                let start = Stopwatch::start();
+                let metadata = self.metadata_cache.read(&mut heap_stream, &header)?;
+                stats.metadata_time = start.elapsed();
+                let start = Stopwatch::start();
+                let constant_pool = if self.skip_constant_pool {
+                    ConstantPool::default()
+                } else {
+                    ConstantPool::try_new(&mut heap_stream, &header, &metadata)?
+                };
+                stats.constant_pool_time = start.elapsed();
+                (metadata, constant_pool)
+            }
+        };
+
+        if truncated_size.is_some() {
+            // we stay at the truncated chunk so that the truncation is reported on the next call
+            self.truncated = true;
+        } else {
+            // update to next chunk start
+            self.reader.chunk_start_position += chunk_size as u64;
+        }
+
+        let mut chunk_reader = ChunkReader {
+            stream: heap_stream,
+            stats: ParseStats::default(),
+            event_counters: EventCounters::default(),
+        };
+        let selected_events = self.reader.event_filter.resolve(&metadata);
+        let mut chunk = Chunk {
+            header,
+            metadata,
+            constant_pool,
+            truncated_size,
+            thread_index: OnceCell::new(),
+            limits: self.reader.limits,
+            string_repr: self.reader.string_repr,
+            selected_events,
+            cancellation_token: self.reader.cancellation_token.clone(),
+        };
+        let retained_class_ids = match &self.reader.retained_event_types {
+            Some(event_types) => Some(Rc::new(
+                event_types
+                    .iter()
+                    .filter_map(|name| chunk.metadata.type_pool.get_by_name(name))
+                    .map(|t| t.class_id)
+                    .collect(),
+            )),
+            None if self.reader.constant_pool_mode == ConstantPoolMode::ReferencedOnly => {
+                chunk.selected_events.clone()
+            }
+            None => None,
+        };
+        if let Some(class_ids) = retained_class_ids {
+            if !self.skip_constant_pool {
+                let start = Stopwatch::start();
+                chunk.retain_referenced_constants(&chunk_reader, &class_ids)?;
+                stats.constant_pool_time += start.elapsed();
+            }
+        }
+        stats.constant_pool_entries = chunk.constant_pool.inner.len() as u64;
+        if let Some(string_class) = chunk.metadata.type_pool.class_id_of(KnownType::String) {
+            stats.strings_interned = chunk
+                .constant_pool
+                .inner
+                .keys()
+                .filter(|k| k.class_id == string_class)
+                .count() as u64;
+        }
+        chunk_reader.stats = stats;
+        self.reader.report_progress(chunk_end)?;
+        Ok(Some((chunk_reader, chunk)))
+    }
+
+    /// Reads metadata and constant pool as long as they are within the available bytes.
+    /// Metadata is mandatory to interpret events, while constant pool is left empty
+    /// if it's not written yet.
+    fn read_truncated_chunk_body(
+        &mut self,
+        stream: &mut HeapByteStream,
+        header: &ChunkHeader,
+        available: u64,
+        stats: &mut ParseStats,
+    ) -> Result<(Rc<Metadata>, ConstantPool)> {
+        let within = |offset: i64| offset > 0 && (offset as u64) < available;
 
-        let metadata = Metadata::try_new(&mut heap_stream, &header)?;
-        let constant_pool = if self.skip_constant_pool {
+        if !within(header.metadata_offset) {
+            return Err(self.reader.truncated_error());
+        }
+        let start = Stopwatch::start();
+        let metadata = self
+            .metadata_cache
+            .read(stream, header)
+            .map_err(|_| self.reader.truncated_error())?;
+        stats.metadata_time = start.elapsed();
+        let start = Stopwatch::start();
+        let constant_pool = if self.skip_constant_pool || !within(header.constant_pool_offset) {
             ConstantPool::default()
         } else {
-            ConstantPool::try_new(&mut heap_stream, &header, &metadata)?
+            ConstantPool::try_new(stream, header, &metadata).unwrap_or_default()
         };
+        stats.constant_pool_time = start.elapsed();
+        Ok((metadata, constant_pool))
+    }
+}
 
-        // update to next chunk start
-        self.reader.chunk_start_position += chunk_size as u64;
+/// Events across all chunks, returned by [`JfrReader::all_events`].
+///
+/// Since each event borrows the chunk which is owned by this struct,
+/// this doesn't implement [`Iterator`]. Use `while let` instead:
+///
+/// ```no_run
+/// # use jfrs::reader::JfrReader;
+/// # use std::fs::File;
+/// let mut reader = JfrReader::new(File::open("recording.jfr").unwrap());
+/// let mut events = reader.all_events();
+/// while let Some(event) = events.next() {
+///     let event = event.unwrap();
+///     println!("{} at chunk {}", event.class.name(), event.chunk().header.position());
+/// }
+/// ```
+pub struct AllEvents<'a, T> {
+    chunks: ChunkIterator<'a, T>,
+    current: Option<(ChunkReader, Chunk)>,
+    // The offset of the next event in the current chunk
+    offset: u64,
+    finished: bool,
+}
 
-        Ok(Some((
-            ChunkReader {
-                stream: heap_stream,
-            },
-            Chunk {
-                header,
-                metadata,
-                constant_pool,
-            },
-        )))
+impl<'a, T: Read + Seek> AllEvents<'a, T> {
+    /// Returns the next event, advancing to the next chunk if necessary
+    #[allow(clippy::should_implement_trait)]
+    pub fn next(&mut self) -> Option<Result<Event<'_>>> {
+        if self.finished {
+            return None;
+        }
+        // Find the chunk which has the next event first, since returning the event borrowing
+        // the current chunk and replacing the chunk can't be done in the same loop.
+        // The header found here is reused to decode the event
+        let (header, body_position) = loop {
+            let (chunk_reader, chunk) = match self.current.as_ref() {
+                Some(current) => current,
+                None => match self.chunks.next() {
+                    Some(Ok(chunk)) => {
+                        self.offset = 0;
+                        self.current.insert(chunk)
+                    }
+                    Some(Err(e)) => {
+                        self.finished = true;
+                        return Some(Err(e));
+                    }
+                    None => {
+                        self.finished = true;
+                        return None;
+                    }
+                },
+            };
+            let mut events = chunk_reader.events_from_offset(chunk, self.offset);
+            match events.next_event_header() {
+                Ok(Some(header)) => break (header, events.stream_position()),
+                Ok(None) => {
+                    if let Some((chunk_reader, _)) = self.current.take() {
+                        self.chunks.recycle(chunk_reader);
+                    }
+                }
+                Err(e) => {
+                    self.finished = true;
+                    return Some(Err(e));
+                }
+            }
+        };
+
+        let (chunk_reader, chunk) = self.current.as_ref()?;
+        let mut events = chunk_reader.events_from_offset(chunk, self.offset);
+        let event = events.resume(header, body_position);
+        self.offset = events.offset();
+        if let Some(Err(_)) = event {
+            self.finished = true;
+        }
+        event
     }
+}
 
-    fn read_chunk_header(stream: &mut HeapByteStream, chunk_size: i64) -> Result<ChunkHeader> {
-        Ok(ChunkHeader {
-            chunk_size,
-            constant_pool_offset: stream.read_i64()?,
-            metadata_offset: stream.read_i64()?,
-            start_time_nanos: stream.read_i64()?,
-            duration_nanos: stream.read_i64()?,
-            start_ticks: stream.read_i64()?,
-            ticks_per_second: stream.read_i64()?,
-            features: stream.read_i32()?,
-        })
+/// Iterator over chunk headers, which skips the chunk body
+pub struct ChunkHeaderIterator<'a, T> {
+    reader: &'a mut JfrReader<T>,
+    finished: bool,
+}
+
+impl<'a, T: Read + Seek> Iterator for ChunkHeaderIterator<'a, T> {
+    type Item = Result<ChunkHeader>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.finished {
+            return None;
+        }
+        match self.internal_next() {
+            Ok(Some(header)) => Some(Ok(header)),
+            Ok(None) => {
+                self.finished = true;
+                None
+            }
+            Err(e) => {
+                self.finished = true;
+                Some(Err(e))
+            }
+        }
+    }
+}
+
+impl<'a, T: Read + Seek> ChunkHeaderIterator<'a, T> {
+    fn internal_next(&mut self) -> Result<Option<ChunkHeader>> {
+        let (version, chunk_size) = match self.reader.read_chunk_start()? {
+            Some(v) => v,
+            None => return Ok(None),
+        };
+        let bytes = self
+            .reader
+            .stream
+            .read_as_bytes((ChunkHeader::HEADER_SIZE - 16) as usize)?;
+        let mut stream = ByteStream::new(Cursor::new(bytes));
+        let header = self
+            .reader
+            .read_chunk_header(&mut stream, version, chunk_size);
+        let header = self.reader.eof_as_truncated(header)?;
+
+        // make sure the chunk is complete by reading its last byte
+        self.reader
+            .stream
+            .seek(self.reader.chunk_start_position + chunk_size as u64 - 1)?;
+        let last = self.reader.stream.read_u8();
+        self.reader.eof_as_truncated(last)?;
+
+        self.reader.chunk_start_position += chunk_size as u64;
+        Ok(Some(header))
     }
 }
 
 pub struct JfrReader<T> {
-    stream: ByteStream<T>,
+    stream: ByteStream<Unbuffered<T>>,
     chunk_start_position: u64,
+    parse_truncated_chunk: bool,
+    version_policy: VersionPolicy,
+    // buffer for the bytes of the next chunk
+    spare_buffer: Option<Vec<u8>>,
+    retained_event_types: Option<Vec<String>>,
+    max_chunk_size: Option<u64>,
+    limits: Limits,
+    string_repr: StringRepr,
+    event_filter: EventFilter,
+    constant_pool_mode: ConstantPoolMode,
+    progress: Option<Box<dyn FnMut(Progress) + Send>>,
+    // the file size, computed on the first progress report
+    total_bytes: Option<u64>,
+    cancellation_token: Option<CancellationToken>,
+}
+
+// the reader can be moved to a worker thread
+#[allow(dead_code)]
+fn _assert_send() {
+    fn assert_send<T: Send>() {}
+    assert_send::<JfrReader<std::fs::File>>();
+}
+
+impl<B: AsRef<[u8]>> JfrReader<Cursor<B>> {
+    /// Reads the recording in memory, e.g. the bytes of the file uploaded to a browser-based viewer
+    /// where no file system is available.
+    pub fn from_bytes(bytes: B) -> Self {
+        Self::new(Cursor::new(bytes))
+    }
 }
 
 impl<T> JfrReader<T>
@@ -225,52 +1002,399 @@ where
 {
     pub fn new(inner: T) -> Self {
         Self {
-            stream: ByteStream::new(inner),
+            stream: ByteStream::new(Unbuffered(inner)),
             chunk_start_position: 0,
+            parse_truncated_chunk: false,
+            version_policy: VersionPolicy::default(),
+            spare_buffer: None,
+            retained_event_types: None,
+            max_chunk_size: None,
+            limits: Limits::default(),
+            string_repr: StringRepr::default(),
+            event_filter: EventFilter::default(),
+            constant_pool_mode: ConstantPoolMode::default(),
+            progress: None,
+            total_bytes: None,
+            cancellation_token: None,
         }
     }
 
-    pub fn chunks(&mut self) -> ChunkIterator<T> {
-        ChunkIterator {
-            reader: self,
-            skip_constant_pool: false,
-        }
+    /// Sets the maximum chunk size to load into memory. Unlimited by default.
+    ///
+    /// Since the whole chunk is loaded into memory, a corrupted header declaring a huge size
+    /// would make the reader try to allocate it. With the limit, such chunks fail with
+    /// [`Error::ChunkTooLarge`] before allocating.
+    pub fn max_chunk_size(mut self, bytes: u64) -> Self {
+        self.max_chunk_size = Some(bytes);
+        self
     }
 
-    /// Returns an iterator over chunk.
-    /// This iterator skips constant pool which is useful when you want to parse only type metadata.
-    pub fn chunk_metadata(&mut self) -> ChunkIterator<T> {
-        ChunkIterator {
-            reader: self,
-            skip_constant_pool: true,
+    /// Sets the limits on the lengths of strings and arrays and the size of constant pools.
+    /// Unlimited by default. Use [`Limits::hardened`] to read untrusted files.
+    pub fn limits(mut self, limits: Limits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// Sets how string values are decoded. See [`StringRepr::default`] for the default.
+    ///
+    /// With [`StringRepr::Raw`], strings are kept as [`raw_string::RawString`] in the encoding of
+    /// the recording. Strings which are valid UTF-8 as they are (UTF-8 and ASCII-only Latin-1 strings)
+    /// can still be accessed as `&str`. Strings in the metadata are always decoded.
+    pub fn string_repr(mut self, string_repr: StringRepr) -> Self {
+        self.string_repr = string_repr;
+        self
+    }
+
+    /// Keeps only the constants referenced from the events of the types (e.g. `jdk.ExecutionSample`),
+    /// directly or through other constants, to reduce the memory of the chunks.
+    ///
+    /// The events of each chunk are scanned once more when the chunk is read, and the constants are
+    /// still decoded to find their references. Events of other types may contain unresolvable references.
+    pub fn retain_constants_for(mut self, event_types: &[&str]) -> Self {
+        self.retained_event_types = Some(event_types.iter().map(|s| s.to_string()).collect());
+        self
+    }
+
+    /// Sets the buffer to read the first chunk into, e.g. the one taken by [`JfrReader::take_buffer`]
+    /// from the reader of the previous recording.
+    pub fn reuse_buffer(mut self, buffer: Vec<u8>) -> Self {
+        self.spare_buffer = Some(buffer);
+        self
+    }
+
+    /// Returns the buffer of the chunk reader which is no longer used, so that the next chunk is read
+    /// into it instead of allocating a new buffer.
+    /// The larger one is kept if there is a buffer already.
+    pub fn recycle(&mut self, chunk_reader: ChunkReader) {
+        let bytes = chunk_reader.into_bytes();
+        match &self.spare_buffer {
+            Some(spare) if spare.capacity() >= bytes.capacity() => {}
+            _ => self.spare_buffer = Some(bytes),
         }
     }
-}
 
-pub use de::from_event;
+    /// Takes the buffer returned by [`JfrReader::recycle`]
+    pub fn take_buffer(&mut self) -> Option<Vec<u8>> {
+        self.spare_buffer.take()
+    }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use std::collections::HashSet;
-    use std::fs::File;
+    /// Sets whether to read the events of experimental types. Defaults to [`ExperimentalEvents::Include`].
+    /// Events of the excluded types are skipped without decoding.
+    pub fn experimental_events(mut self, experimental_events: ExperimentalEvents) -> Self {
+        self.event_filter.experimental_events = experimental_events;
+        self
+    }
 
-    use crate::reader::types::jdk::ExecutionSample;
-    use crate::reader::value_descriptor::{Primitive, ValueDescriptor};
+    /// Reads only the events of the types (e.g. `jdk.ExecutionSample`).
+    /// Events of other types are skipped without decoding, as [`EventIterator::select`] does.
+    ///
+    /// The names are resolved to the class ids once per chunk, so the type of each event is checked
+    /// without comparing the names.
+    pub fn event_allowlist(mut self, event_types: &[&str]) -> Self {
+        self.event_filter.allowlist = Some(event_types.iter().map(|s| s.to_string()).collect());
+        self
+    }
 
-    use crate::reader::de::from_value_descriptor;
-    use crate::reader::types::builtin::StackTrace;
-    use std::path::PathBuf;
+    /// Skips the events of the types (e.g. `jdk.ObjectAllocationSample`) without decoding.
+    /// Applied on top of [`JfrReader::event_allowlist`].
+    pub fn event_denylist(mut self, event_types: &[&str]) -> Self {
+        self.event_filter.denylist = event_types.iter().map(|s| s.to_string()).collect();
+        self
+    }
 
-    #[test]
-    fn test_read_single_chunk() {
-        let mut reader = JfrReader::new(File::open(test_data("profiler-wall.jfr")).unwrap());
+    /// Sets how much of the constant pools to keep. Defaults to [`ConstantPoolMode::Full`].
+    /// [`ConstantPoolMode::ReferencedOnly`] keeps the constants referenced from the events read by
+    /// [`JfrReader::event_allowlist`] and [`JfrReader::event_denylist`], and has no effect without them.
+    pub fn constant_pool_mode(mut self, mode: ConstantPoolMode) -> Self {
+        self.constant_pool_mode = mode;
+        self
+    }
 
-        let mut chunk_count = 0;
-        for res in reader.chunks() {
-            let res = res.unwrap();
-            let (mut reader, chunk) = res;
-            chunk_count += 1;
+    /// Sets the callback which is called every time a chunk is read, with the end offset of the chunk
+    /// and the file size. Useful to show the progress of parsing huge files.
+    pub fn progress<F: FnMut(Progress) + Send + 'static>(mut self, callback: F) -> Self {
+        self.progress = Some(Box::new(callback));
+        self
+    }
+
+    /// Sets the token to abort the parse. Once cancelled, the chunk iterators and the event iterators
+    /// of the chunks read by this reader return [`Error::Cancelled`] then end.
+    pub fn cancellation_token(mut self, token: CancellationToken) -> Self {
+        self.cancellation_token = Some(token);
+        self
+    }
+
+    /// Sets how to handle unknown versions and feature bits. Defaults to [`VersionPolicy::BestEffort`].
+    pub fn version_policy(mut self, policy: VersionPolicy) -> Self {
+        self.version_policy = policy;
+        self
+    }
+
+    /// Sets whether to parse the readable prefix of the last chunk when the file is truncated,
+    /// which is typical for recordings copied from a running JVM.
+    ///
+    /// If enabled, the chunk iterator returns the truncated chunk as long as its metadata is readable,
+    /// then returns [`Error::TruncatedChunk`].
+    /// Otherwise, [`Error::TruncatedChunk`] is returned right after the complete chunks.
+    pub fn parse_truncated_chunk(mut self, enabled: bool) -> Self {
+        self.parse_truncated_chunk = enabled;
+        self
+    }
+
+    pub fn chunks(&mut self) -> ChunkIterator<'_, T> {
+        let skip_constant_pool = self.constant_pool_mode == ConstantPoolMode::Skip;
+        ChunkIterator {
+            reader: self,
+            skip_constant_pool,
+            follow: false,
+            truncated: false,
+            finished: false,
+            metadata_cache: MetadataCache::default(),
+        }
+    }
+
+    /// Returns an iterator over chunks which own their bytes, metadata and constant pool.
+    /// See [`loaded_chunk::LoadedChunk`].
+    pub fn loaded_chunks(&mut self) -> LoadedChunkIterator<'_, T> {
+        LoadedChunkIterator {
+            reader: self,
+            finished: false,
+        }
+    }
+
+    /// Returns events across all chunks, so that nested loops over chunks and events are not necessary.
+    /// The chunk of each event is available by [`Event::chunk`].
+    pub fn all_events(&mut self) -> AllEvents<'_, T> {
+        AllEvents {
+            chunks: self.chunks(),
+            current: None,
+            offset: 0,
+            finished: false,
+        }
+    }
+
+    /// Returns an iterator over the complete chunks appended since the last call.
+    ///
+    /// The iterator ends without error at the chunk which is still being written,
+    /// i.e. partially written or flushed but not finished (see [`ChunkHeader::is_finished`]),
+    /// and the next call resumes from that chunk boundary.
+    /// This is useful for continuously monitoring a recording file being written by the JVM.
+    pub fn follow(&mut self) -> ChunkIterator<'_, T> {
+        let skip_constant_pool = self.constant_pool_mode == ConstantPoolMode::Skip;
+        ChunkIterator {
+            reader: self,
+            skip_constant_pool,
+            follow: true,
+            truncated: false,
+            finished: false,
+            metadata_cache: MetadataCache::default(),
+        }
+    }
+
+    /// Returns an iterator over chunk headers.
+    /// This iterator reads only the header of each chunk, which is useful to quickly list
+    /// the chunks of a huge file before deciding what to parse.
+    pub fn chunk_headers(&mut self) -> ChunkHeaderIterator<'_, T> {
+        ChunkHeaderIterator {
+            reader: self,
+            finished: false,
+        }
+    }
+
+    /// Parses the N-th (0-origin) chunk.
+    ///
+    /// Only chunk headers of the preceding chunks are read to locate the chunk.
+    /// Subsequent iteration by [`JfrReader::chunks`] resumes from the next chunk.
+    pub fn open_chunk(&mut self, index: usize) -> Result<(ChunkReader, Chunk)> {
+        self.chunk_start_position = 0;
+        let position = match self.chunk_headers().nth(index) {
+            Some(header) => header?.position(),
+            None => return Err(Error::ChunkNotFound(index)),
+        };
+        self.open_chunk_at(position)
+    }
+
+    /// Parses the chunk which starts at the given byte offset.
+    /// The offset is typically obtained by [`ChunkHeader::position`].
+    ///
+    /// Subsequent iteration by [`JfrReader::chunks`] resumes from the next chunk.
+    pub fn open_chunk_at(&mut self, offset: u64) -> Result<(ChunkReader, Chunk)> {
+        self.chunk_start_position = offset;
+        match self.chunks().next() {
+            Some(chunk) => chunk,
+            None => Err(Error::IoError(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                format!("No chunk at offset {}", offset),
+            ))),
+        }
+    }
+
+    /// Returns an iterator over chunk.
+    /// This iterator skips constant pool which is useful when you want to parse only type metadata.
+    pub fn chunk_metadata(&mut self) -> ChunkIterator<'_, T> {
+        ChunkIterator {
+            reader: self,
+            skip_constant_pool: true,
+            follow: false,
+            truncated: false,
+            finished: false,
+            metadata_cache: MetadataCache::default(),
+        }
+    }
+}
+
+impl<T: Read + Seek> JfrReader<T> {
+    /// Reads magic, version and chunk size at the current chunk start position.
+    /// Returns None if we reached the end of the file.
+    fn read_chunk_start(&mut self) -> Result<Option<(Version, i64)>> {
+        self.stream.set_int_encoding(IntEncoding::Raw);
+        self.stream.seek(self.chunk_start_position)?;
+        match self.stream.read_u8() {
+            Ok(magic_head) => {
+                let mut magic = [magic_head, 0, 0, 0];
+                let magic_tail = self.stream.read_exact::<3>();
+                let magic_tail = self.eof_as_truncated(magic_tail)?;
+                magic[1..].clone_from_slice(&magic_tail);
+
+                if magic != MAGIC {
+                    return Err(Error::InvalidFormat);
+                }
+            }
+            // Reaching EOF at the beginning of the chunk means just we reached the end of the file
+            // normally, so just returns Ok(None)
+            Err(Error::IoError(e)) if e.kind() == io::ErrorKind::UnexpectedEof => {
+                return Ok(None);
+            }
+            Err(e) => {
+                return Err(e);
+            }
+        }
+
+        let major = self.stream.read_i16();
+        let minor = self.stream.read_i16();
+        let version = Version {
+            major: self.eof_as_truncated(major)?,
+            minor: self.eof_as_truncated(minor)?,
+        };
+        match version.major {
+            1 | 2 => {}
+            _ => {
+                return Err(Error::UnsupportedVersion(version));
+            }
+        }
+
+        let chunk_size = self.stream.read_i64();
+        let chunk_size = self.eof_as_truncated(chunk_size)?;
+        // JVM may not fill the chunk size yet while the chunk is being written
+        if (chunk_size as u64) < ChunkHeader::HEADER_SIZE {
+            return Err(self.truncated_error());
+        }
+
+        Ok(Some((version, chunk_size)))
+    }
+
+    /// Reads the rest of the header after magic, version and chunk size
+    fn read_chunk_header<R: Input>(
+        &self,
+        stream: &mut ByteStream<R>,
+        version: Version,
+        chunk_size: i64,
+    ) -> Result<ChunkHeader> {
+        let header = ChunkHeader {
+            position: self.chunk_start_position,
+            version,
+            chunk_size,
+            constant_pool_offset: stream.read_i64()?,
+            metadata_offset: stream.read_i64()?,
+            start_time_nanos: stream.read_i64()?,
+            duration_nanos: stream.read_i64()?,
+            start_ticks: stream.read_i64()?,
+            ticks_per_second: stream.read_i64()?,
+            features: stream.read_i32()?,
+        };
+        // ticks are converted to nanoseconds by dividing by this
+        if header.ticks_per_second <= 0 {
+            return Err(Error::InvalidFormat);
+        }
+        if self.version_policy == VersionPolicy::Strict {
+            if !header.is_known_version() {
+                return Err(Error::UnsupportedVersion(version));
+            }
+            if header.unknown_features() != 0 {
+                return Err(Error::UnsupportedFeatures(header.unknown_features()));
+            }
+        }
+        Ok(header)
+    }
+
+    fn is_cancelled(&self) -> bool {
+        self.cancellation_token
+            .as_ref()
+            .is_some_and(|t| t.is_cancelled())
+    }
+
+    fn report_progress(&mut self, bytes_processed: u64) -> Result<()> {
+        if let Some(callback) = &mut self.progress {
+            let total_bytes = match self.total_bytes {
+                Some(total) => total,
+                None => {
+                    let total = self.stream.stream_len()?;
+                    self.total_bytes = Some(total);
+                    total
+                }
+            };
+            callback(Progress {
+                bytes_processed,
+                total_bytes,
+            });
+        }
+        Ok(())
+    }
+
+    fn truncated_error(&self) -> Error {
+        Error::TruncatedChunk {
+            valid_bytes: self.chunk_start_position,
+        }
+    }
+
+    // Reaching EOF in the middle of the chunk header means the file ends in the middle of the chunk.
+    fn eof_as_truncated<V>(&self, res: Result<V>) -> Result<V> {
+        match res {
+            Err(Error::IoError(e)) if e.kind() == io::ErrorKind::UnexpectedEof => {
+                Err(self.truncated_error())
+            }
+            res => res,
+        }
+    }
+}
+
+pub use de::from_event;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::test_data;
+    use std::collections::{HashMap, HashSet};
+    use std::fs::File;
+    use std::io::Write;
+
+    use crate::reader::types::jdk::ExecutionSample;
+    use crate::reader::value_descriptor::{Primitive, ValueDescriptor};
+
+    use crate::reader::de::from_value_descriptor;
+    use crate::reader::types::builtin::StackTrace;
+    use serde::Deserialize;
+
+    #[test]
+    fn test_read_single_chunk() {
+        let mut reader = JfrReader::new(File::open(test_data("profiler-wall.jfr")).unwrap());
+
+        let mut chunk_count = 0;
+        for res in reader.chunks() {
+            let res = res.unwrap();
+            let (reader, chunk) = res;
+            chunk_count += 1;
 
             // You can see these values on JMC
             assert_eq!(
@@ -301,17 +1425,10 @@ mod tests {
                 .get(&30, &203)
                 .and_then(|c| c.get_field("string", &chunk))
                 .unwrap();
-            if let ValueDescriptor::Primitive(Primitive::String(s)) = field {
-                #[cfg(feature = "cstring")]
-                assert_eq!(
-                    s.string.to_str().unwrap(),
-                    "CompileBroker::compiler_thread_loop"
-                );
-                #[cfg(not(feature = "cstring"))]
-                assert_eq!(s, "CompileBroker::compiler_thread_loop");
-            } else {
-                panic!("Unexpected value type: {:?}", field);
-            }
+            assert_eq!(
+                <&str>::try_from(field),
+                Ok("CompileBroker::compiler_thread_loop")
+            );
 
             let count = reader
                 .events(&chunk)
@@ -355,11 +1472,12 @@ mod tests {
     }
 
     #[test]
+    #[allow(clippy::explicit_counter_loop, clippy::needless_borrow)]
     fn test_de() {
         let mut reader = JfrReader::new(File::open(test_data("profiler-wall.jfr")).unwrap());
 
         let mut chunk_count = 0;
-        for (mut reader, chunk) in reader.chunks().flatten() {
+        for (reader, chunk) in reader.chunks().flatten() {
             chunk_count += 1;
             let mut events = 0;
             for event in reader
@@ -390,6 +1508,515 @@ mod tests {
         assert_eq!(chunk_count, 1);
     }
 
+    #[test]
+    fn test_unsigned() {
+        #[derive(Deserialize)]
+        struct UnsignedLongFlag<'a> {
+            name: &'a str,
+            value: u64,
+        }
+
+        let mut reader = JfrReader::new(File::open(test_data("recording.jfr")).unwrap());
+        let mut negative_bits = 0;
+        for (reader, chunk) in reader.chunks().flatten() {
+            for event in reader
+                .events(&chunk)
+                .flatten()
+                .filter(|e| e.class.name() == "jdk.UnsignedLongFlag")
+            {
+                let value = event.value().get_field("value").unwrap();
+                let raw = i64::try_from(value.value).unwrap();
+                assert!(value.field().unwrap().unsigned);
+                assert_eq!(value.as_u64(), Some(raw as u64));
+
+                let flag: UnsignedLongFlag = from_event(&event).unwrap();
+                assert!(!flag.name.is_empty());
+                assert_eq!(flag.value, raw as u64);
+                if raw < 0 {
+                    negative_bits += 1;
+                }
+            }
+        }
+        assert!(negative_bits > 0);
+
+        // signed types can still be used as long as the value fits
+        #[derive(Deserialize)]
+        struct SignedLongFlag {
+            value: i64,
+        }
+        for (reader, chunk) in JfrReader::new(File::open(test_data("recording.jfr")).unwrap())
+            .chunks()
+            .flatten()
+        {
+            for event in reader
+                .events(&chunk)
+                .flatten()
+                .filter(|e| e.class.name() == "jdk.UnsignedLongFlag")
+            {
+                let raw = i64::try_from(event.value().get_field("value").unwrap().value).unwrap();
+                let flag = from_event::<SignedLongFlag>(&event);
+                if raw < 0 {
+                    assert!(flag.is_err());
+                } else {
+                    assert_eq!(flag.unwrap().value, raw);
+                }
+            }
+        }
+
+        // signed fields can't be converted if negative
+        let value = ValueDescriptor::Primitive(Primitive::Long(-1));
+        let chunk = JfrReader::new(File::open(test_data("recording.jfr")).unwrap())
+            .chunk_metadata()
+            .next()
+            .unwrap()
+            .unwrap()
+            .1;
+        assert_eq!(event::Accessor::new(&chunk, &value).as_u64(), None);
+    }
+
+    #[test]
+    fn test_typed_getters() {
+        let mut reader = JfrReader::new(File::open(test_data("recording.jfr")).unwrap());
+        let (chunk_reader, chunk) = reader.chunks().next().unwrap().unwrap();
+        let events = chunk_reader.events(&chunk).flatten().collect::<Vec<_>>();
+
+        let info = events
+            .iter()
+            .find(|e| e.class.name() == "jdk.JVMInformation")
+            .unwrap()
+            .value();
+        // the int field is widened
+        assert_eq!(info.get_field("pid").and_then(|v| v.as_i64()), Some(3741));
+        assert_eq!(info.get_field("pid").and_then(|v| v.as_f64()), Some(3741.0));
+        assert_eq!(
+            info.get_field("jvmName").and_then(|v| v.as_str()),
+            Some("OpenJDK 64-Bit Server VM")
+        );
+
+        let park = events
+            .iter()
+            .find(|e| e.class.name() == "jdk.ThreadPark")
+            .unwrap()
+            .value();
+        // through the constant pool reference without resolving explicitly
+        let os_name = park
+            .get_field_raw("eventThread")
+            .unwrap()
+            .get_field("osName")
+            .and_then(|v| v.as_str());
+        assert!(os_name.is_some());
+        assert!(park
+            .get_field("duration")
+            .and_then(|v| v.as_bool())
+            .is_none());
+
+        let sample = events
+            .iter()
+            .find(|e| e.class.name() == "jdk.NativeMethodSample")
+            .unwrap()
+            .value();
+        let frames = sample
+            .get_field("stackTrace")
+            .and_then(|s| s.get_field("frames"))
+            .and_then(|f| f.as_array())
+            .unwrap();
+        assert!(!frames.is_empty());
+        assert!(sample
+            .get_field("stackTrace")
+            .and_then(|s| s.get_field("truncated"))
+            .and_then(|t| t.as_bool())
+            .is_some());
+        assert!(sample.as_array().is_none());
+    }
+
+    #[test]
+    fn test_resolve_array_elements() {
+        let mut reader = JfrReader::new(File::open(test_data("recording.jfr")).unwrap());
+        let (chunk_reader, chunk) = reader.chunks().next().unwrap().unwrap();
+        let thread = chunk_reader
+            .events(&chunk)
+            .flatten()
+            .find(|e| e.class.name() == "jdk.ThreadPark")
+            .unwrap();
+        let thread_ref = match thread.value().get_field_raw("eventThread").unwrap().value {
+            ValueDescriptor::ConstantPool {
+                class_id,
+                constant_index,
+            } => (*class_id, *constant_index),
+            v => panic!("unexpected value: {:?}", v),
+        };
+
+        let array = ValueDescriptor::Array(vec![
+            ValueDescriptor::ConstantPool {
+                class_id: thread_ref.0,
+                constant_index: thread_ref.1,
+            },
+            ValueDescriptor::ConstantPool {
+                class_id: thread_ref.0,
+                constant_index: i64::MAX,
+            },
+        ]);
+        let accessor = event::Accessor::new(&chunk, &array);
+        let elems = accessor.as_iter().unwrap().collect::<Vec<_>>();
+        assert!(matches!(elems[0].value, ValueDescriptor::Object(_)));
+        assert!(elems[0]
+            .get_field("osName")
+            .and_then(|v| v.as_str())
+            .is_some());
+        // not found in the constant pool
+        assert!(matches!(
+            elems[1].value,
+            ValueDescriptor::ConstantPool { .. }
+        ));
+        assert!(elems[1].resolve_deep().is_none());
+
+        assert!(accessor
+            .as_iter_raw()
+            .unwrap()
+            .all(|e| matches!(e.value, ValueDescriptor::ConstantPool { .. })));
+    }
+
+    #[test]
+    fn test_get_field_ref() {
+        let mut reader = JfrReader::new(File::open(test_data("profiler-wall.jfr")).unwrap());
+        let (chunk_reader, chunk) = reader.chunks().next().unwrap().unwrap();
+        let mut frame_counts = HashMap::new();
+        let mut samples = 0;
+        for event in chunk_reader
+            .events(&chunk)
+            .flatten()
+            .filter(|e| e.class.name() == "jdk.ExecutionSample")
+        {
+            let stack_trace = event.value().get_field_ref("stackTrace").unwrap();
+            let frames = stack_trace
+                .resolve(&chunk)
+                .and_then(|s| s.get_field("frames"))
+                .and_then(|f| f.as_array())
+                .unwrap()
+                .len();
+            // the same stack trace always has the same frames
+            assert_eq!(*frame_counts.entry(stack_trace).or_insert(frames), frames);
+            samples += 1;
+        }
+        assert!(frame_counts.len() < samples);
+
+        let event = chunk_reader.events(&chunk).flatten().next().unwrap();
+        // inline values and missing fields
+        assert!(event.value().get_field_ref("startTime").is_none());
+        assert!(event.value().get_field_ref("noSuchField").is_none());
+    }
+
+    #[test]
+    fn test_events_in_category() {
+        let mut reader = JfrReader::new(File::open(test_data("recording.jfr")).unwrap());
+        let (chunk_reader, chunk) = reader.chunks().next().unwrap().unwrap();
+        let type_pool = &chunk.metadata.type_pool;
+
+        let gc = ["Java Virtual Machine", "GC"];
+        let expected = chunk_reader
+            .events(&chunk)
+            .flatten()
+            .filter(|e| e.class.category().take(2).eq(gc.iter().copied()))
+            .map(|e| e.byte_offset)
+            .collect::<Vec<_>>();
+        let actual = chunk_reader
+            .events_in_category(&chunk, &gc)
+            .flatten()
+            .map(|e| e.byte_offset)
+            .collect::<Vec<_>>();
+        assert!(!actual.is_empty());
+        assert_eq!(actual, expected);
+        assert!(type_pool
+            .event_types_in_category(&gc)
+            .any(|t| t.name() == "jdk.GarbageCollection"));
+        assert!(type_pool
+            .event_types_in_category(&["Java Virtual Machine", "GC", "Collector"])
+            .all(|t| t.is_in_category(&gc)));
+        assert_eq!(
+            type_pool.event_types_in_category(&[]).count(),
+            type_pool.event_types().count()
+        );
+
+        let label = type_pool
+            .get_by_name("jdk.ThreadPark")
+            .unwrap()
+            .label()
+            .unwrap();
+        assert_eq!(
+            type_pool
+                .event_types_with_label(label)
+                .map(|t| t.name())
+                .collect::<Vec<_>>(),
+            vec!["jdk.ThreadPark"]
+        );
+        assert_eq!(chunk_reader.events(&chunk).with_label(label).count(), 237);
+        // selections are combined
+        assert_eq!(
+            chunk_reader
+                .events(&chunk)
+                .with_label(label)
+                .in_category(&gc)
+                .count(),
+            0
+        );
+    }
+
+    #[test]
+    fn test_event_allowlist() {
+        let mut reader = JfrReader::new(File::open(test_data("recording.jfr")).unwrap())
+            .event_allowlist(&["jdk.ThreadPark", "jdk.CPULoad"]);
+        let (chunk_reader, chunk) = reader.chunks().next().unwrap().unwrap();
+        let types = chunk_reader
+            .events(&chunk)
+            .flatten()
+            .map(|e| e.class.name().to_string())
+            .collect::<HashSet<_>>();
+        assert_eq!(
+            types,
+            HashSet::from(["jdk.ThreadPark".to_string(), "jdk.CPULoad".to_string()])
+        );
+        // selection is applied on top of the allowlist
+        assert_eq!(
+            chunk_reader
+                .events(&chunk)
+                .select(|t| t.name() == "jdk.ThreadPark")
+                .flatten()
+                .count(),
+            237
+        );
+        assert_eq!(
+            chunk_reader
+                .events(&chunk)
+                .select(|t| t.name() == "jdk.ExecutionSample")
+                .count(),
+            0
+        );
+    }
+
+    #[test]
+    fn test_constant_pool_mode() {
+        let read = |mode: ConstantPoolMode| {
+            let mut reader = JfrReader::new(File::open(test_data("recording.jfr")).unwrap())
+                .event_allowlist(&["jdk.ThreadPark"])
+                .constant_pool_mode(mode);
+            let (chunk_reader, chunk) = reader.chunks().next().unwrap().unwrap();
+            let resolved = chunk_reader
+                .events(&chunk)
+                .flatten()
+                .filter(|e| e.value().get_field("eventThread").is_some())
+                .count();
+            (resolved, chunk.constant_pool.inner.len())
+        };
+
+        let (full_resolved, full) = read(ConstantPoolMode::Full);
+        let (referenced_resolved, referenced) = read(ConstantPoolMode::ReferencedOnly);
+        let (skip_resolved, skipped) = read(ConstantPoolMode::Skip);
+        assert_eq!(full_resolved, 237);
+        assert_eq!(referenced_resolved, 237);
+        assert!(0 < referenced && referenced < full);
+        assert_eq!((skip_resolved, skipped), (0, 0));
+    }
+
+    #[test]
+    fn test_event_denylist() {
+        let open = |reader: JfrReader<File>| {
+            let mut reader = reader;
+            let (chunk_reader, chunk) = reader.chunks().next().unwrap().unwrap();
+            let names = chunk_reader
+                .events(&chunk)
+                .flatten()
+                .map(|e| e.class.name().to_string())
+                .collect::<HashSet<_>>();
+            (names, chunk)
+        };
+        let file = || File::open(test_data("recording.jfr")).unwrap();
+
+        let (all, chunk) = open(JfrReader::new(file()));
+        assert!(chunk.selected_events.is_none());
+        assert!(all.contains("jdk.ThreadPark"));
+
+        let (names, chunk) = open(JfrReader::new(file()).event_denylist(&["jdk.ThreadPark"]));
+        assert!(!names.contains("jdk.ThreadPark"));
+        assert_eq!(names.len(), all.len() - 1);
+        let park = chunk
+            .metadata
+            .type_pool
+            .get_by_name("jdk.ThreadPark")
+            .unwrap()
+            .class_id;
+        assert!(!chunk.selected_events.as_ref().unwrap().contains(&park));
+
+        let (names, _) = open(
+            JfrReader::new(file())
+                .event_allowlist(&["jdk.ThreadPark", "jdk.CPULoad"])
+                .event_denylist(&["jdk.ThreadPark"]),
+        );
+        assert_eq!(names, HashSet::from(["jdk.CPULoad".to_string()]));
+    }
+
+    #[test]
+    fn test_experimental_events() {
+        let open = |experimental_events: ExperimentalEvents| {
+            let mut reader = JfrReader::new(File::open(test_data("recording.jfr")).unwrap())
+                .experimental_events(experimental_events);
+            let (chunk_reader, mut chunk) = reader.chunks().next().unwrap().unwrap();
+            drop(reader);
+            // none of the types in the test data is experimental
+            let metadata = Rc::get_mut(&mut chunk.metadata).unwrap();
+            let park = metadata
+                .type_pool
+                .get_by_name("jdk.ThreadPark")
+                .unwrap()
+                .class_id;
+            metadata
+                .type_pool
+                .inner
+                .get_mut(&park)
+                .unwrap()
+                .experimental = true;
+            // the filter is resolved when the chunk is read, so resolve again for the modified metadata
+            let filter = EventFilter {
+                experimental_events,
+                ..EventFilter::default()
+            };
+            chunk.selected_events = filter.resolve(&chunk.metadata);
+            (chunk_reader, chunk)
+        };
+
+        let (chunk_reader, chunk) = open(ExperimentalEvents::Include);
+        let all = chunk_reader.events(&chunk).count();
+
+        let (chunk_reader, chunk) = open(ExperimentalEvents::Only);
+        let names = chunk_reader
+            .events(&chunk)
+            .flatten()
+            .map(|e| e.class.name().to_string())
+            .collect::<HashSet<_>>();
+        assert_eq!(names, HashSet::from(["jdk.ThreadPark".to_string()]));
+        assert_eq!(chunk_reader.events(&chunk).count(), 237);
+
+        let (chunk_reader, chunk) = open(ExperimentalEvents::Exclude);
+        assert!(chunk_reader
+            .events(&chunk)
+            .flatten()
+            .all(|e| e.class.name() != "jdk.ThreadPark"));
+        assert_eq!(chunk_reader.events(&chunk).count(), all - 237);
+    }
+
+    #[test]
+    fn test_de_time() {
+        use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+        #[derive(Deserialize)]
+        #[serde(rename_all = "camelCase")]
+        struct ThreadPark {
+            #[serde(with = "crate::reader::de::timestamp")]
+            start_time: SystemTime,
+            #[serde(with = "crate::reader::de::timespan")]
+            duration: Duration,
+            #[serde(with = "crate::reader::de::timespan::option")]
+            timeout: Option<Duration>,
+        }
+
+        let mut reader = JfrReader::new(File::open(test_data("recording.jfr")).unwrap());
+        let mut count = 0;
+        for (reader, chunk) in reader.chunks().flatten() {
+            for event in reader
+                .events(&chunk)
+                .flatten()
+                .filter(|e| e.class.name() == "jdk.ThreadPark")
+            {
+                let ticks = event
+                    .value()
+                    .get_field("startTime")
+                    .and_then(|v| i64::try_from(v.value).ok())
+                    .unwrap();
+                let timeout = event
+                    .value()
+                    .get_field("timeout")
+                    .and_then(|v| i64::try_from(v.value).ok())
+                    .unwrap();
+                if timeout < 0 {
+                    // negative timeout (i.e. no timeout) can't be represented as Duration
+                    assert!(from_event::<ThreadPark>(&event).is_err());
+                    continue;
+                }
+
+                let park: ThreadPark = from_event(&event).unwrap();
+                assert_eq!(
+                    park.start_time
+                        .duration_since(UNIX_EPOCH)
+                        .unwrap()
+                        .as_nanos() as i64,
+                    chunk.header.ticks_to_epoch_nanos(ticks)
+                );
+                assert!(park.duration < Duration::from_secs(chunk.header.duration_nanos as u64));
+                assert_eq!(park.timeout, Some(Duration::from_nanos(timeout as u64)));
+                count += 1;
+
+                // ticks are not converted without the `with` attribute
+                #[derive(Deserialize)]
+                struct Plain {
+                    #[allow(dead_code)]
+                    duration: Duration,
+                }
+                assert!(from_event::<Plain>(&event).is_err());
+            }
+        }
+        assert!(count > 0);
+    }
+
+    #[test]
+    fn test_de_enum() {
+        #[derive(Debug, Deserialize, Eq, PartialEq, Hash)]
+        enum FrameType {
+            Interpreted,
+            #[serde(rename = "JIT compiled")]
+            JitCompiled,
+            Inlined,
+            Native,
+            #[serde(other)]
+            Other,
+        }
+
+        #[derive(Deserialize)]
+        struct LineNumber(i32);
+
+        #[derive(Deserialize)]
+        #[serde(rename_all = "camelCase")]
+        struct Frame {
+            #[serde(rename = "type")]
+            frame_type: FrameType,
+            line_number: LineNumber,
+        }
+
+        let mut reader = JfrReader::new(File::open(test_data("profiler-wall.jfr")).unwrap());
+        let mut frame_types = HashSet::new();
+        for (reader, chunk) in reader.chunks().flatten() {
+            for event in reader
+                .events(&chunk)
+                .flatten()
+                .filter(|e| e.class.name() == "jdk.ExecutionSample")
+            {
+                let frames = event
+                    .value()
+                    .get_field("stackTrace")
+                    .and_then(|s| s.get_field("frames"))
+                    .and_then(|f| f.as_iter())
+                    .unwrap();
+                for frame in frames {
+                    let frame: Frame = from_value_descriptor(&chunk, frame.value).unwrap();
+                    assert!(frame.line_number.0 >= -1);
+                    frame_types.insert(frame.frame_type);
+                }
+            }
+        }
+        assert!(frame_types.contains(&FrameType::JitCompiled));
+        assert!(frame_types.contains(&FrameType::Native));
+        // e.g. "C++" frames of async-profiler
+        assert!(frame_types.contains(&FrameType::Other));
+    }
+
     #[test]
     fn test_invalid_jfr() {
         let mut reader = JfrReader::new(File::open(test_data("invalid.jfr")).unwrap());
@@ -403,7 +2030,7 @@ mod tests {
 
         let mut chunk_count = 0;
         for chunk in reader.chunks() {
-            let (mut reader, chunk) = chunk.unwrap();
+            let (reader, chunk) = chunk.unwrap();
             chunk_count += 1;
             let count = reader
                 .events(&chunk)
@@ -432,9 +2059,869 @@ mod tests {
         assert_eq!(chunk_count, 1);
     }
 
-    fn test_data(file_name: &str) -> PathBuf {
-        PathBuf::from(env!("CARGO_MANIFEST_DIR"))
-            .join("test-data")
-            .join(file_name)
+    #[test]
+    fn test_type_pool_lookup() {
+        let mut reader = JfrReader::new(File::open(test_data("recording.jfr")).unwrap());
+        let (_, chunk) = reader.chunk_metadata().next().unwrap().unwrap();
+        let type_pool = &chunk.metadata.type_pool;
+
+        assert_eq!(
+            type_pool.get_by_name("java.lang.Class").unwrap().class_id,
+            20
+        );
+        assert!(type_pool.get_by_name("no.such.Type").is_none());
+
+        let park = type_pool.get_by_name("jdk.ThreadPark").unwrap();
+        for (idx, field) in park.fields.iter().enumerate() {
+            assert_eq!(park.field_index(field.name()), Some(idx));
+        }
+        assert_eq!(park.get_field("timeout").unwrap().1.name(), "timeout");
+        assert!(park.field_index("noSuchField").is_none());
+
+        let event_types = type_pool
+            .event_types()
+            .map(|t| t.name())
+            .collect::<Vec<_>>();
+        assert!(event_types.contains(&"jdk.ThreadPark"));
+        assert!(!event_types.contains(&"java.lang.Class"));
+        assert_eq!(
+            event_types.len(),
+            type_pool
+                .get_types()
+                .filter(|t| t.super_type() == Some("jdk.jfr.Event"))
+                .count()
+        );
+    }
+
+    #[test]
+    fn test_type_settings() {
+        let mut reader = JfrReader::new(File::open(test_data("recording.jfr")).unwrap());
+        let (_, chunk) = reader.chunk_metadata().next().unwrap().unwrap();
+        let park = chunk
+            .metadata
+            .type_pool
+            .get_by_name("jdk.ThreadPark")
+            .unwrap();
+        let mut names = park.settings().iter().map(|s| s.name()).collect::<Vec<_>>();
+        names.sort();
+        assert_eq!(names, vec!["enabled", "stackTrace", "threshold"]);
+        let threshold = park.get_setting("threshold").unwrap();
+        assert_eq!(threshold.default_value(), Some("0 ns"));
+        assert_eq!(
+            chunk
+                .metadata
+                .type_pool
+                .get(threshold.class_id)
+                .unwrap()
+                .name(),
+            "jdk.settings.Threshold"
+        );
+    }
+
+    #[test]
+    fn test_region() {
+        let mut reader = JfrReader::new(File::open(test_data("recording.jfr")).unwrap());
+        let (_, chunk) = reader.chunk_metadata().next().unwrap().unwrap();
+        let region = chunk.metadata.region().unwrap();
+        assert_eq!(region.locale(), Some("en"));
+        assert_eq!(region.gmt_offset_millis, 0);
+
+        let region = metadata::Region {
+            locale: None,
+            gmt_offset_millis: 9 * 3600 * 1000,
+        };
+        assert_eq!(region.to_local_nanos(0), 9 * 3600 * 1_000_000_000);
+    }
+
+    #[test]
+    fn test_chunk_headers() {
+        let mut reader = JfrReader::new(File::open(test_data("profiler-multichunk.jfr")).unwrap());
+        let headers = reader.chunk_headers().collect::<Result<Vec<_>>>().unwrap();
+        assert_eq!(
+            headers.iter().map(|h| h.chunk_size).collect::<Vec<_>>(),
+            vec![60169, 57333, 57451]
+        );
+        assert_eq!(headers[1].start_time_nanos, 1661595228226873000);
+        assert_eq!(
+            headers.iter().map(|h| h.position()).collect::<Vec<_>>(),
+            vec![0, 60169, 60169 + 57333]
+        );
+
+        let bytes = std::fs::read(test_data("profiler-multichunk.jfr")).unwrap();
+        let mut reader = JfrReader::new(Cursor::new(bytes[..70000].to_vec()));
+        let headers = reader.chunk_headers().collect::<Vec<_>>();
+        assert_eq!(headers.len(), 2);
+        assert!(matches!(
+            headers[1],
+            Err(Error::TruncatedChunk { valid_bytes: 60169 })
+        ));
+    }
+
+    #[test]
+    fn test_open_chunk() {
+        let mut reader = JfrReader::new(File::open(test_data("profiler-multichunk.jfr")).unwrap());
+        let expected = reader
+            .chunks()
+            .flatten()
+            .map(|(r, c)| r.events(&c).count())
+            .collect::<Vec<_>>();
+
+        let (r, c) = reader.open_chunk(1).unwrap();
+        assert_eq!(c.header.position(), 60169);
+        assert_eq!(c.header.start_time_nanos, 1661595228226873000);
+        assert_eq!(r.events(&c).count(), expected[1]);
+        // iteration resumes from the next chunk
+        assert_eq!(reader.chunks().count(), 1);
+
+        let (r, c) = reader.open_chunk_at(60169 + 57333).unwrap();
+        assert_eq!(r.events(&c).count(), expected[2]);
+
+        assert!(matches!(reader.open_chunk(3), Err(Error::ChunkNotFound(3))));
+        assert!(matches!(
+            reader.open_chunk_at(60169 + 57333 + 57451),
+            Err(Error::IoError(_))
+        ));
+    }
+
+    #[test]
+    fn test_chunk_header_features() {
+        let mut bytes = std::fs::read(test_data("recording.jfr")).unwrap();
+
+        let mut reader = JfrReader::new(Cursor::new(bytes.clone()));
+        let (_, chunk) = reader.chunk_metadata().next().unwrap().unwrap();
+        assert!(chunk.header.is_compressed_ints());
+        assert!(!chunk.header.is_final_chunk());
+        assert!(chunk.header.is_finished());
+        assert_eq!(chunk.header.flags(), 1);
+
+        // JVM is updating the header
+        bytes[ChunkHeader::FEATURES_OFFSET] = 255;
+        let mut reader = JfrReader::new(Cursor::new(bytes));
+        let (_, chunk) = reader.chunk_metadata().next().unwrap().unwrap();
+        assert_eq!(chunk.header.file_state(), 255);
+        assert!(!chunk.header.is_finished());
+    }
+
+    #[test]
+    fn test_invalid_ticks_per_second() {
+        let mut bytes = std::fs::read(test_data("recording.jfr")).unwrap();
+        // ticks per second is right before the features
+        let offset = ChunkHeader::FEATURES_OFFSET - 8;
+        bytes[offset..offset + 8].copy_from_slice(&0i64.to_be_bytes());
+
+        let mut reader = JfrReader::new(Cursor::new(bytes.clone()));
+        assert!(matches!(
+            reader.chunks().next(),
+            Some(Err(Error::InvalidFormat))
+        ));
+
+        bytes[offset..offset + 8].copy_from_slice(&(-1i64).to_be_bytes());
+        let mut reader = JfrReader::new(Cursor::new(bytes));
+        assert!(matches!(
+            reader.chunk_metadata().next(),
+            Some(Err(Error::InvalidFormat))
+        ));
+    }
+
+    #[test]
+    fn test_version_policy() {
+        let mut bytes = std::fs::read(test_data("profiler-wall.jfr")).unwrap();
+        // set unknown feature bit
+        bytes[ChunkHeader::FEATURES_OFFSET + 3] |= 0x80;
+
+        let mut reader = JfrReader::new(Cursor::new(bytes.clone()));
+        let (_, chunk) = reader.chunks().next().unwrap().unwrap();
+        assert!(chunk.header.is_known_version());
+        assert_eq!(chunk.header.unknown_features(), 0x80);
+
+        let mut reader =
+            JfrReader::new(Cursor::new(bytes.clone())).version_policy(VersionPolicy::Strict);
+        assert!(matches!(
+            reader.chunks().next(),
+            Some(Err(Error::UnsupportedFeatures(0x80)))
+        ));
+
+        // unknown minor version 2.99
+        bytes[ChunkHeader::FEATURES_OFFSET + 3] &= !0x80;
+        bytes[7] = 99;
+        let mut reader = JfrReader::new(Cursor::new(bytes.clone()));
+        let (_, chunk) = reader.chunks().next().unwrap().unwrap();
+        assert!(!chunk.header.is_known_version());
+
+        let mut reader = JfrReader::new(Cursor::new(bytes)).version_policy(VersionPolicy::Strict);
+        assert!(matches!(
+            reader.chunks().next(),
+            Some(Err(Error::UnsupportedVersion(_)))
+        ));
+    }
+
+    #[test]
+    fn test_truncated_chunk() {
+        let bytes = std::fs::read(test_data("profiler-multichunk.jfr")).unwrap();
+        // cut in the middle of the 3rd chunk
+        let valid_bytes = 60169 + 57333;
+        let truncated = bytes[..valid_bytes + 30000].to_vec();
+
+        let mut reader = JfrReader::new(Cursor::new(truncated));
+        let mut chunks = reader.chunks();
+        assert!(chunks.next().unwrap().is_ok());
+        assert!(chunks.next().unwrap().is_ok());
+        match chunks.next() {
+            Some(Err(Error::TruncatedChunk { valid_bytes: v })) => {
+                assert_eq!(v, valid_bytes as u64)
+            }
+            _ => panic!("TruncatedChunk error is expected"),
+        }
+        assert!(chunks.next().is_none());
+    }
+
+    #[test]
+    fn test_parse_truncated_chunk() {
+        let bytes = std::fs::read(test_data("profiler-multichunk.jfr")).unwrap();
+        let valid_bytes = 60169 + 57333;
+        let truncated = bytes[..valid_bytes + 30000].to_vec();
+
+        let mut reader = JfrReader::new(Cursor::new(truncated)).parse_truncated_chunk(true);
+        let mut results = reader.chunks().collect::<Vec<_>>();
+        assert_eq!(results.len(), 4);
+        assert!(matches!(
+            results.pop(),
+            Some(Err(Error::TruncatedChunk { .. }))
+        ));
+
+        let (reader, chunk) = results.pop().unwrap().unwrap();
+        assert!(chunk.is_truncated());
+        let count = reader
+            .events(&chunk)
+            .flatten()
+            .filter(|e| e.class.name() == "jdk.ExecutionSample")
+            .count();
+        assert!(count > 0);
+    }
+
+    #[test]
+    fn test_flush_segments() {
+        let mut reader = JfrReader::new(File::open(test_data("recording.jfr")).unwrap());
+        let (chunk_reader, chunk) = reader.chunks().next().unwrap().unwrap();
+
+        let checkpoints = chunk.checkpoints();
+        assert_eq!(checkpoints.len(), 6);
+        assert!(checkpoints
+            .windows(2)
+            .all(|w| w[0].end_offset() <= w[1].byte_offset));
+        assert_eq!(checkpoints.iter().filter(|c| c.is_flush()).count(), 1);
+
+        let segments = chunk.flush_segments();
+        assert_eq!(segments.len(), 2);
+        assert_eq!(segments[0].start, 0);
+        assert_eq!(segments[0].end, segments[1].start);
+        assert_eq!(segments[1].end, chunk.header.chunk_body_size());
+
+        let mut count = 0;
+        for segment in &segments {
+            for event in chunk_reader
+                .events_from_offset(&chunk, segment.start)
+                .until(segment.end)
+            {
+                assert!(segment.contains(&event.unwrap().byte_offset()));
+                count += 1;
+            }
+        }
+        assert!(count > 0);
+        assert_eq!(count, chunk_reader.events(&chunk).count());
+    }
+
+    #[test]
+    fn test_flush_segments_without_flush() {
+        let mut reader = JfrReader::new(File::open(test_data("profiler-wall.jfr")).unwrap());
+        let (_, chunk) = reader.chunks().next().unwrap().unwrap();
+        assert_eq!(
+            chunk.flush_segments(),
+            vec![0..chunk.header.chunk_body_size()]
+        );
+
+        let mut reader = JfrReader::new(File::open(test_data("recording.jfr")).unwrap());
+        let (_, chunk) = reader.chunk_metadata().next().unwrap().unwrap();
+        assert!(chunk.checkpoints().is_empty());
+        assert_eq!(chunk.flush_segments().len(), 1);
+    }
+
+    #[test]
+    fn test_follow() {
+        let bytes = std::fs::read(test_data("profiler-multichunk.jfr")).unwrap();
+        let path =
+            std::env::temp_dir().join(format!("jfrs-test-follow-{}.jfr", std::process::id()));
+        let mut file = File::create(&path).unwrap();
+
+        let mut reader = JfrReader::new(File::open(&path).unwrap());
+        assert_eq!(reader.follow().count(), 0);
+
+        // first chunk and the part of second chunk are written
+        file.write_all(&bytes[..70000]).unwrap();
+        assert_eq!(reader.follow().flatten().count(), 1);
+        assert_eq!(reader.follow().count(), 0);
+
+        file.write_all(&bytes[70000..]).unwrap();
+        assert_eq!(reader.follow().flatten().count(), 2);
+        assert_eq!(reader.follow().count(), 0);
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_follow_unfinished_chunk() {
+        let bytes = std::fs::read(test_data("recording.jfr")).unwrap();
+        let path = std::env::temp_dir().join(format!(
+            "jfrs-test-follow-unfinished-{}.jfr",
+            std::process::id()
+        ));
+
+        // the chunk is flushed but JVM leaves the duration 0 until the chunk is finished
+        let mut flushed = bytes.clone();
+        flushed[40..48].copy_from_slice(&0i64.to_be_bytes());
+        std::fs::write(&path, &flushed).unwrap();
+
+        let mut reader = JfrReader::new(File::open(&path).unwrap());
+        assert_eq!(reader.follow().count(), 0);
+        assert_eq!(reader.follow().count(), 0);
+
+        std::fs::write(&path, &bytes).unwrap();
+        assert_eq!(reader.follow().flatten().count(), 1);
+        assert_eq!(reader.follow().count(), 0);
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_constants() {
+        let mut reader = JfrReader::new(File::open(test_data("recording.jfr")).unwrap());
+        let (_, chunk) = reader.chunks().next().unwrap().unwrap();
+
+        let threads = chunk.threads().collect::<Result<Vec<_>>>().unwrap();
+        assert!(!threads.is_empty());
+        assert!(threads.windows(2).all(|w| w[0].0 < w[1].0));
+        assert!(threads
+            .iter()
+            .any(|(_, t)| t.java_name == Some("main") && t.os_thread_id > 0));
+
+        let classes = chunk.classes().collect::<Result<Vec<_>>>().unwrap();
+        assert!(classes
+            .iter()
+            .any(|(_, c)| c.name.as_ref().and_then(|s| s.string) == Some("java/lang/Thread")));
+
+        let methods = chunk.methods().collect::<Result<Vec<_>>>().unwrap();
+        assert!(methods.iter().all(|(_, m)| m.name.is_some()));
+
+        let symbols = chunk.symbols().collect::<Result<Vec<_>>>().unwrap();
+        let class_id = chunk
+            .metadata
+            .type_pool
+            .class_id_of(KnownType::Symbol)
+            .unwrap();
+        assert_eq!(
+            symbols.len(),
+            chunk.constant_pool.indices_of(class_id).len()
+        );
+
+        let generic = chunk
+            .constants::<Symbol>("jdk.types.Symbol")
+            .collect::<Result<Vec<_>>>()
+            .unwrap();
+        assert_eq!(
+            generic
+                .iter()
+                .map(|(i, s)| (*i, s.string))
+                .collect::<Vec<_>>(),
+            symbols
+                .iter()
+                .map(|(i, s)| (*i, s.string))
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(chunk.constants::<Symbol>("no.such.Type").count(), 0);
+    }
+
+    #[test]
+    fn test_thread_by_id() {
+        let mut reader = JfrReader::new(File::open(test_data("recording.jfr")).unwrap());
+        let (_, chunk) = reader.chunks().next().unwrap().unwrap();
+
+        let threads = chunk.threads().collect::<Result<Vec<_>>>().unwrap();
+        let main = threads
+            .iter()
+            .map(|(_, t)| t)
+            .find(|t| t.java_name == Some("main"))
+            .unwrap();
+        let found = chunk.thread_by_id(main.java_thread_id).unwrap().unwrap();
+        assert_eq!(found.java_name, Some("main"));
+        assert_eq!(found.os_thread_id, main.os_thread_id);
+
+        let found = chunk.thread_by_os_id(main.os_thread_id).unwrap().unwrap();
+        assert_eq!(found.java_thread_id, main.java_thread_id);
+
+        assert!(chunk.thread_by_id(i64::MIN).is_none());
+    }
+
+    #[test]
+    fn test_concurrent_event_iterators() {
+        let mut reader = JfrReader::new(File::open(test_data("recording.jfr")).unwrap());
+        let (chunk_reader, chunk) = reader.chunks().next().unwrap().unwrap();
+
+        let mut parks = chunk_reader
+            .events(&chunk)
+            .flatten()
+            .filter(|e| e.class.name() == "jdk.ThreadPark");
+        let mut waits = chunk_reader
+            .events(&chunk)
+            .flatten()
+            .filter(|e| e.class.name() == "jdk.JavaMonitorWait");
+        let mut park_count = 0;
+        let mut wait_count = 0;
+        loop {
+            let park = parks.next();
+            let wait = waits.next();
+            if park.is_none() && wait.is_none() {
+                break;
+            }
+            park_count += park.is_some() as usize;
+            wait_count += wait.is_some() as usize;
+        }
+        assert_eq!(park_count, 237);
+        assert_eq!(wait_count, 701);
+    }
+
+    #[test]
+    fn test_recycle_buffer() {
+        let path = test_data("profiler-multichunk.jfr");
+        // large enough for any chunk
+        let buffer = Vec::with_capacity(std::fs::metadata(&path).unwrap().len() as usize);
+        let ptr = buffer.as_ptr();
+
+        let mut reader = JfrReader::new(File::open(&path).unwrap()).reuse_buffer(buffer);
+        let mut chunks = reader.chunks();
+        let mut chunk_count = 0;
+        let mut count = 0;
+        while let Some(chunk) = chunks.next() {
+            let (chunk_reader, chunk) = chunk.unwrap();
+            assert_eq!(chunk_reader.bytes().as_ptr(), ptr);
+            count += chunk_reader
+                .events(&chunk)
+                .flatten()
+                .filter(|e| e.class.name() == "jdk.ExecutionSample")
+                .count();
+            chunk_count += 1;
+            chunks.recycle(chunk_reader);
+        }
+        assert!(chunk_count > 1);
+        assert_eq!(count, 8888);
+        let buffer = reader.take_buffer().unwrap();
+        assert_eq!(buffer.as_ptr(), ptr);
+        assert!(reader.take_buffer().is_none());
+    }
+
+    #[test]
+    fn test_negative_metadata_size() {
+        let mut bytes = std::fs::read(test_data("recording.jfr")).unwrap();
+        let header = JfrReader::new(Cursor::new(&bytes))
+            .chunk_headers()
+            .next()
+            .unwrap()
+            .unwrap();
+        // replace the padded 4-byte size by -1, which takes 5 bytes in the compressed encoding
+        let offset = header.metadata_offset as usize;
+        bytes.splice(offset..offset + 4, [0xff, 0xff, 0xff, 0xff, 0x0f]);
+        let chunk_size = header.chunk_size + 1;
+        bytes[8..16].copy_from_slice(&chunk_size.to_be_bytes());
+
+        let mut reader = JfrReader::from_bytes(bytes);
+        assert!(matches!(
+            reader.chunks().next(),
+            Some(Err(Error::InvalidFormat))
+        ));
+    }
+
+    #[test]
+    fn test_shared_metadata() {
+        let mut reader = JfrReader::new(File::open(test_data("profiler-multichunk.jfr")).unwrap());
+        let chunks = reader.chunks().collect::<Result<Vec<_>>>().unwrap();
+        assert!(chunks.len() > 1);
+        let shared = chunks
+            .windows(2)
+            .filter(|w| Rc::ptr_eq(&w[0].1.metadata, &w[1].1.metadata))
+            .count();
+        assert!(shared > 0);
+
+        // shared metadata must be the same as parsing each chunk separately
+        for (chunk_reader, chunk) in chunks.iter() {
+            let mut stream = ByteStream::new(Cursor::new(chunk_reader.bytes()));
+            stream.set_int_encoding(chunk.header.int_encoding());
+            let metadata = Metadata::try_new(&mut stream, &chunk.header).unwrap();
+            assert_eq!(
+                format!("{:?}", metadata.type_pool),
+                format!("{:?}", chunk.metadata.type_pool)
+            );
+        }
+    }
+
+    #[test]
+    fn test_retain_constants() {
+        let read = |reader: &mut JfrReader<File>| {
+            let (chunk_reader, chunk) = reader.chunks().next().unwrap().unwrap();
+            let events = chunk_reader
+                .events(&chunk)
+                .flatten()
+                .filter(|e| e.class.name() == "jdk.ThreadPark")
+                .map(|e| serde_json::to_string(&e).unwrap())
+                .collect::<Vec<_>>();
+            (events, chunk.constant_pool.inner.len())
+        };
+
+        let mut reader = JfrReader::new(File::open(test_data("recording.jfr")).unwrap());
+        let (expected, all_constants) = read(&mut reader);
+        let mut reader = JfrReader::new(File::open(test_data("recording.jfr")).unwrap())
+            .retain_constants_for(&["jdk.ThreadPark"]);
+        let (events, retained_constants) = read(&mut reader);
+
+        assert_eq!(events.len(), 237);
+        assert_eq!(events, expected);
+        assert!(retained_constants > 0);
+        assert!(retained_constants < all_constants);
+    }
+
+    #[test]
+    fn test_max_chunk_size() {
+        let mut bytes = std::fs::read(test_data("recording.jfr")).unwrap();
+        let chunk_size = JfrReader::new(Cursor::new(&bytes))
+            .chunk_headers()
+            .next()
+            .unwrap()
+            .unwrap()
+            .chunk_size as u64;
+
+        let mut reader = JfrReader::new(Cursor::new(&bytes)).max_chunk_size(chunk_size);
+        assert!(reader.chunks().next().unwrap().is_ok());
+
+        let mut reader = JfrReader::new(Cursor::new(&bytes)).max_chunk_size(chunk_size - 1);
+        let mut chunks = reader.chunks();
+        assert!(matches!(
+            chunks.next(),
+            Some(Err(Error::ChunkTooLarge { size, limit })) if size == chunk_size && limit == chunk_size - 1
+        ));
+        assert!(chunks.next().is_none());
+
+        // corrupted chunk size declaring 1 TiB
+        bytes[8..16].copy_from_slice(&(1i64 << 40).to_be_bytes());
+        let mut reader = JfrReader::new(Cursor::new(&bytes)).max_chunk_size(1 << 30);
+        assert!(matches!(
+            reader.chunks().next(),
+            Some(Err(Error::ChunkTooLarge { size, .. })) if size == 1 << 40
+        ));
+    }
+
+    #[test]
+    fn test_limits() {
+        let count = |reader: &mut JfrReader<File>| -> Result<usize> {
+            let mut count = 0;
+            for chunk in reader.chunks() {
+                let (chunk_reader, chunk) = chunk?;
+                for event in chunk_reader.events(&chunk) {
+                    event?;
+                    count += 1;
+                }
+            }
+            Ok(count)
+        };
+        let open = |limits: Limits| {
+            JfrReader::new(File::open(test_data("profiler-multichunk.jfr")).unwrap()).limits(limits)
+        };
+
+        let expected = count(&mut open(Limits::default())).unwrap();
+        assert_eq!(count(&mut open(Limits::hardened())).unwrap(), expected);
+
+        // limits are checked deep inside values, so the errors may be wrapped in the decoding path
+        let err = count(&mut open(Limits::new().max_array_length(2))).unwrap_err();
+        assert!(matches!(
+            err.root_cause(),
+            Error::LimitExceeded {
+                kind: LimitKind::ArrayLength,
+                limit: 2,
+                ..
+            }
+        ));
+        let err = count(&mut open(Limits::new().max_string_length(4))).unwrap_err();
+        assert!(matches!(
+            err.root_cause(),
+            Error::LimitExceeded {
+                kind: LimitKind::StringLength,
+                limit: 4,
+                ..
+            }
+        ));
+        let err = count(&mut open(Limits::new().max_constant_pool_size(10))).unwrap_err();
+        assert!(matches!(
+            err.root_cause(),
+            Error::LimitExceeded {
+                kind: LimitKind::ConstantPoolSize,
+                limit: 10,
+                actual,
+            } if *actual > 10
+        ));
+    }
+
+    #[test]
+    fn test_event_byte_range() {
+        let mut reader = JfrReader::new(File::open(test_data("recording.jfr")).unwrap());
+        let (chunk_reader, chunk) = reader.chunks().next().unwrap().unwrap();
+
+        let events = chunk_reader.events(&chunk).flatten().collect::<Vec<_>>();
+        assert!(events
+            .windows(2)
+            .all(|w| w[0].byte_offset() + w[0].byte_size() <= w[1].byte_offset()));
+        let last = events.last().unwrap();
+        assert!(last.byte_offset() + last.byte_size() <= chunk.header.chunk_body_size());
+
+        // re-read a specific event later by the offset
+        let sampled = events.iter().step_by(100).collect::<Vec<_>>();
+        for event in sampled {
+            let reread = chunk_reader
+                .events_from_offset(&chunk, event.byte_offset())
+                .next()
+                .unwrap()
+                .unwrap();
+            assert_eq!(reread.class.name(), event.class.name());
+            assert_eq!(reread.byte_size(), event.byte_size());
+        }
+    }
+
+    #[test]
+    fn test_decode_error_context() {
+        let mut reader = JfrReader::new(File::open(test_data("profiler-wall.jfr")).unwrap());
+        let (chunk_reader, chunk) = reader.chunks().next().unwrap().unwrap();
+        let event = chunk_reader
+            .events(&chunk)
+            .flatten()
+            .find(|e| e.class.name() == "jdk.ExecutionSample")
+            .unwrap();
+        let last_field = event.class.fields.last().unwrap().name().to_string();
+
+        // cut the last byte of the event
+        let end = chunk.header.body_start_offset() + event.byte_offset + event.byte_size;
+        let bytes = &chunk_reader.bytes()[..end as usize - 1];
+        let mut events = EventIterator::new(&chunk, bytes);
+        events.seek(event.byte_offset);
+        let err = match events.next() {
+            Some(Err(e)) => e,
+            _ => panic!("error is expected"),
+        };
+
+        match &err {
+            Error::Decode { path, source } => {
+                assert_eq!(path, &vec!["jdk.ExecutionSample".to_string(), last_field]);
+                assert!(matches!(**source, Error::IoError(_)));
+            }
+            e => panic!("unexpected error: {:?}", e),
+        }
+        assert!(matches!(err.root_cause(), Error::IoError(_)));
+        assert!(err
+            .to_string()
+            .starts_with("Failed to decode jdk.ExecutionSample > "));
+
+        let err = Error::InvalidFormat
+            .in_context(|| "method".to_string())
+            .in_context(|| "frames[3]".to_string())
+            .in_context(|| "stackTrace".to_string());
+        assert_eq!(
+            err.to_string(),
+            "Failed to decode stackTrace > frames[3] > method: Invalid format"
+        );
+    }
+
+    #[test]
+    fn test_from_bytes() {
+        fn event_counts<T: Read + Seek>(mut reader: JfrReader<T>) -> Vec<usize> {
+            let mut counts = vec![];
+            for chunk in reader.chunks() {
+                let (chunk_reader, chunk) = chunk.unwrap();
+                let mut count = 0;
+                for event in chunk_reader.events(&chunk) {
+                    event.unwrap();
+                    count += 1;
+                }
+                counts.push(count);
+            }
+            counts
+        }
+
+        let bytes = std::fs::read(test_data("recording.jfr")).unwrap();
+        let counts = event_counts(JfrReader::from_bytes(bytes.as_slice()));
+        assert!(!counts.is_empty());
+        assert_eq!(
+            counts,
+            event_counts(JfrReader::new(
+                File::open(test_data("recording.jfr")).unwrap()
+            ))
+        );
+    }
+
+    #[test]
+    fn test_error_source() {
+        #[derive(Deserialize)]
+        struct Sample {
+            #[serde(rename = "stackTrace")]
+            _stack_trace: Trace,
+        }
+        #[derive(Deserialize)]
+        struct Trace {
+            #[serde(rename = "frames")]
+            _frames: Vec<Frame>,
+        }
+        #[derive(Deserialize)]
+        struct Frame {
+            #[serde(rename = "noSuchField")]
+            _no_such_field: i32,
+        }
+
+        let mut reader = JfrReader::new(File::open(test_data("profiler-wall.jfr")).unwrap());
+        let (chunk_reader, chunk) = reader.chunks().next().unwrap().unwrap();
+        let event = chunk_reader
+            .events(&chunk)
+            .flatten()
+            .find(|e| e.class.name() == "jdk.ExecutionSample")
+            .unwrap();
+
+        let err = match crate::reader::de::from_event::<Sample>(&event) {
+            Err(e) => e,
+            Ok(_) => panic!("error is expected"),
+        };
+        match &err {
+            Error::Decode { path, .. } => assert_eq!(
+                path,
+                &vec![
+                    "jdk.ExecutionSample".to_string(),
+                    "stackTrace".to_string(),
+                    "frames[0]".to_string()
+                ]
+            ),
+            e => panic!("unexpected error: {:?}", e),
+        }
+        let source = std::error::Error::source(&err)
+            .and_then(|e| e.downcast_ref::<Error>())
+            .unwrap();
+        assert!(matches!(source, Error::DeserializeError(_)));
+        assert!(std::error::Error::source(source).is_none());
+
+        let err = Error::IoError(io::Error::other("broken")).in_context(|| "frames[0]".to_string());
+        let io_error = std::error::Error::source(&err)
+            .and_then(std::error::Error::source)
+            .and_then(|e| e.downcast_ref::<io::Error>())
+            .unwrap();
+        assert_eq!(io_error.to_string(), "broken");
+    }
+
+    #[test]
+    fn test_raw_strings() {
+        let mut reader = JfrReader::new(File::open(test_data("recording.jfr")).unwrap());
+        let mut raw_reader = JfrReader::new(File::open(test_data("recording.jfr")).unwrap())
+            .string_repr(StringRepr::Raw);
+        let (chunk_reader, chunk) = reader.chunks().next().unwrap().unwrap();
+        let (raw_chunk_reader, raw_chunk) = raw_reader.chunks().next().unwrap().unwrap();
+
+        let mut raw_strings = 0;
+        for (event, raw_event) in chunk_reader
+            .events(&chunk)
+            .flatten()
+            .zip(raw_chunk_reader.events(&raw_chunk).flatten())
+        {
+            assert_eq!(
+                serde_json::to_string(&event).unwrap(),
+                serde_json::to_string(&raw_event).unwrap()
+            );
+            if raw_event.class.name() == "jdk.JVMInformation" {
+                let name = raw_event.value().get_field("jvmName").unwrap();
+                let raw = <&raw_string::RawString>::try_from(name.value).unwrap();
+                assert_eq!(raw.decode().unwrap(), "OpenJDK 64-Bit Server VM");
+                assert_eq!(<&str>::try_from(name.value), Ok("OpenJDK 64-Bit Server VM"));
+                raw_strings += 1;
+            }
+        }
+        assert_eq!(raw_strings, 1);
+
+        // typed structs borrowing strings still work
+        let mut reader = JfrReader::new(File::open(test_data("profiler-wall.jfr")).unwrap())
+            .string_repr(StringRepr::Raw);
+        let (chunk_reader, chunk) = reader.chunks().next().unwrap().unwrap();
+        let sample = chunk_reader
+            .events(&chunk)
+            .flatten()
+            .find(|e| e.class.name() == "jdk.ExecutionSample")
+            .unwrap();
+        let sample: ExecutionSample = from_event(&sample).unwrap();
+        let name = sample.stack_trace.unwrap().frames[0]
+            .as_ref()
+            .and_then(|f| f.method.as_ref())
+            .and_then(|m| m.name.as_ref())
+            .and_then(|n| n.string)
+            .unwrap();
+        assert!(!name.is_empty());
+    }
+
+    #[test]
+    fn test_string_repr() {
+        let jvm_name = |repr: StringRepr, check: &dyn Fn(&ValueDescriptor)| {
+            let mut reader =
+                JfrReader::new(File::open(test_data("recording.jfr")).unwrap()).string_repr(repr);
+            let (chunk_reader, chunk) = reader.chunks().next().unwrap().unwrap();
+            let event = chunk_reader
+                .events(&chunk)
+                .flatten()
+                .find(|e| e.class.name() == "jdk.JVMInformation")
+                .unwrap();
+            let name = event.value().get_field("jvmName").unwrap();
+            assert_eq!(<&str>::try_from(name.value), Ok("OpenJDK 64-Bit Server VM"));
+            check(name.value);
+        };
+
+        // both representations in the same build
+        jvm_name(StringRepr::String, &|v| {
+            assert!(matches!(
+                v,
+                ValueDescriptor::Primitive(Primitive::String(_))
+            ))
+        });
+        jvm_name(StringRepr::CString, &|v| {
+            let s = <&std::ffi::CStr>::try_from(v).unwrap();
+            assert_eq!(s.to_bytes(), b"OpenJDK 64-Bit Server VM");
+        });
+    }
+
+    #[test]
+    fn test_all_events() {
+        let mut reader = JfrReader::new(File::open(test_data("profiler-multichunk.jfr")).unwrap());
+        let mut events = reader.all_events();
+        let mut count = 0;
+        let mut positions = HashSet::new();
+        while let Some(event) = events.next() {
+            let event = event.unwrap();
+            if event.class.name() == "jdk.ExecutionSample" {
+                count += 1;
+                positions.insert(event.chunk().header.position());
+            }
+        }
+        assert_eq!(count, 8888);
+        assert!(positions.len() > 1);
+        assert!(events.next().is_none());
+
+        let mut reader = JfrReader::new(File::open(test_data("profiler-multichunk.jfr")).unwrap());
+        let mut expected = 0;
+        for (chunk_reader, chunk) in reader.chunks().flatten() {
+            expected += chunk_reader.events(&chunk).count();
+        }
+        let mut reader = JfrReader::new(File::open(test_data("profiler-multichunk.jfr")).unwrap());
+        let mut events = reader.all_events();
+        let mut total = 0;
+        while let Some(event) = events.next() {
+            event.unwrap();
+            total += 1;
+        }
+        assert_eq!(total, expected);
     }
 }
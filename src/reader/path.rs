@@ -0,0 +1,107 @@
+//! Field paths resolved against the chunk metadata in advance.
+//! This is useful to read the same fields from a large number of events in a tight loop.
+
+use crate::reader::event::{Accessor, Event};
+use crate::reader::value_descriptor::ValueDescriptor;
+use crate::reader::Chunk;
+
+/// Dot-separated field path (e.g. `sampledThread.osName`) compiled into field indices.
+///
+/// Since class ids may differ between chunks, the path must be compiled for each chunk.
+#[derive(Debug, Clone)]
+pub struct CompiledPath {
+    class_id: i64,
+    field_indices: Vec<usize>,
+}
+
+impl CompiledPath {
+    /// Resolves the path from the given event type.
+    /// Returns None if the event type or any field in the path is not declared in the chunk.
+    pub fn compile(chunk: &Chunk, event_type: &str, path: &str) -> Option<Self> {
+        let type_pool = &chunk.metadata.type_pool;
+        let root = type_pool.get_by_name(event_type)?;
+
+        let mut field_indices = vec![];
+        let mut current = root;
+        for name in path.split('.').filter(|s| !s.is_empty()) {
+            let (idx, field) = current.get_field(name)?;
+            field_indices.push(idx);
+            current = type_pool.get(field.class_id)?;
+        }
+
+        Some(Self {
+            class_id: root.class_id,
+            field_indices,
+        })
+    }
+
+    /// Evaluates the path against the event.
+    /// Returns None if the event is not of the compiled type, or any value in the path is missing.
+    pub fn evaluate<'a>(&self, event: &'a Event) -> Option<Accessor<'a>> {
+        if event.class.class_id != self.class_id {
+            return None;
+        }
+        self.evaluate_value(event.chunk, &event.value)
+    }
+
+    /// Evaluates the path against the value of the compiled type.
+    pub fn evaluate_value<'a>(
+        &self,
+        chunk: &'a Chunk,
+        value: &'a ValueDescriptor,
+    ) -> Option<Accessor<'a>> {
+        let mut current = value;
+        for &idx in self.field_indices.iter() {
+            current = match Self::resolve(chunk, current)? {
+                ValueDescriptor::Object(o) => o.fields.get(idx)?,
+                _ => return None,
+            };
+        }
+        Some(Accessor::new(chunk, Self::resolve(chunk, current)?))
+    }
+
+    fn resolve<'a>(chunk: &'a Chunk, value: &'a ValueDescriptor) -> Option<&'a ValueDescriptor> {
+        match value {
+            ValueDescriptor::ConstantPool {
+                class_id,
+                constant_index,
+            } => chunk.constant_pool.get(class_id, constant_index),
+            _ => Some(value),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::reader::JfrReader;
+    use crate::test_util::test_data;
+    use std::fs::File;
+
+    #[test]
+    fn test_compiled_path() {
+        let mut reader = JfrReader::new(File::open(test_data("profiler-wall.jfr")).unwrap());
+        let (chunk_reader, chunk) = reader.chunks().next().unwrap().unwrap();
+
+        let path =
+            CompiledPath::compile(&chunk, "jdk.ExecutionSample", "sampledThread.osName").unwrap();
+        assert!(CompiledPath::compile(&chunk, "jdk.ExecutionSample", "noSuchField").is_none());
+        assert!(CompiledPath::compile(&chunk, "no.such.Event", "startTime").is_none());
+
+        let mut count = 0;
+        for event in chunk_reader.events(&chunk).flatten() {
+            let expected = event
+                .value()
+                .get_field("sampledThread")
+                .and_then(|v| v.get_field("osName"))
+                .and_then(|v| v.as_str());
+            let actual = path.evaluate(&event).and_then(|v| v.as_str());
+            assert_eq!(actual, expected);
+            if event.class.name() == "jdk.ExecutionSample" {
+                assert!(actual.is_some());
+                count += 1;
+            }
+        }
+        assert_eq!(count, 8836);
+    }
+}
@@ -0,0 +1,105 @@
+//! Progress reporting and cancellation of long parses, e.g. for interactive tools reading multi-GB files.
+//!
+//! The progress is reported by [`JfrReader::progress`](crate::reader::JfrReader::progress) every time a chunk is read.
+//! The cancellation is checked before reading each chunk and each event,
+//! and the iterators return [`Error::Cancelled`](crate::reader::Error::Cancelled) once cancelled.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct Progress {
+    /// The end offset of the last read chunk
+    pub bytes_processed: u64,
+    /// The size of the file
+    pub total_bytes: u64,
+}
+
+impl Progress {
+    /// Returns the processed ratio from 0.0 to 1.0
+    pub fn fraction(&self) -> f64 {
+        if self.total_bytes == 0 {
+            1.0
+        } else {
+            (self.bytes_processed as f64 / self.total_bytes as f64).min(1.0)
+        }
+    }
+}
+
+/// Token to cancel the parse from another thread (e.g. the UI thread).
+/// Clones share the same state.
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken {
+    cancelled: Arc<AtomicBool>,
+}
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::reader::{Error, JfrReader};
+    use crate::test_util::test_data;
+    use std::fs::File;
+    use std::sync::{Arc, Mutex};
+
+    #[test]
+    fn test_progress() {
+        let reports = Arc::new(Mutex::new(vec![]));
+        let r = reports.clone();
+        let mut reader = JfrReader::new(File::open(test_data("recording-2_1.jfr")).unwrap())
+            .progress(move |p| r.lock().unwrap().push(p));
+        let chunks = reader.chunks().flatten().count();
+
+        let reports = reports.lock().unwrap();
+        assert_eq!(reports.len(), chunks);
+        assert!(reports
+            .windows(2)
+            .all(|w| w[0].bytes_processed < w[1].bytes_processed));
+        let last = reports.last().unwrap();
+        assert_eq!(last.bytes_processed, last.total_bytes);
+        assert_eq!(last.fraction(), 1.0);
+    }
+
+    #[test]
+    fn test_cancel_between_events() {
+        let token = CancellationToken::new();
+        let mut reader = JfrReader::new(File::open(test_data("recording.jfr")).unwrap())
+            .cancellation_token(token.clone());
+        let (chunk_reader, chunk) = reader.chunks().next().unwrap().unwrap();
+        let mut events = chunk_reader.events(&chunk);
+        assert!(events.next().unwrap().is_ok());
+        token.cancel();
+        assert!(matches!(events.next(), Some(Err(Error::Cancelled))));
+        assert!(events.next().is_none());
+        assert!(matches!(
+            reader.chunks().next(),
+            Some(Err(Error::Cancelled))
+        ));
+    }
+
+    #[test]
+    fn test_cancel_between_chunks() {
+        let token = CancellationToken::new();
+        let t = token.clone();
+        let mut reader = JfrReader::new(File::open(test_data("recording-2_1.jfr")).unwrap())
+            .progress(move |_| t.cancel())
+            .cancellation_token(token);
+        let mut chunks = reader.chunks();
+        assert!(chunks.next().unwrap().is_ok());
+        assert!(matches!(chunks.next(), Some(Err(Error::Cancelled))));
+        assert!(chunks.next().is_none());
+    }
+}
@@ -0,0 +1,114 @@
+//! Strings kept in the encoding they are written in the recording.
+//!
+//! JFR strings are encoded as UTF-8, Latin-1 or an array of Java chars.
+//! With [`crate::reader::value_descriptor::StringRepr::Raw`], string values are decoded into [`RawString`]
+//! which holds the original bytes, so that tools handling many symbols can avoid transcoding them.
+
+use crate::reader::{Error, Result};
+use std::borrow::Cow;
+
+/// The encoding declared for the string in the recording
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum StringEncoding {
+    Utf8,
+    Latin1,
+    /// Java chars, stored as UTF-16 code units in big-endian
+    CharArray,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct RawString {
+    pub encoding: StringEncoding,
+    pub bytes: Vec<u8>,
+}
+
+impl RawString {
+    /// Returns the string without copying if the bytes are valid UTF-8 as they are,
+    /// i.e. UTF-8 strings and ASCII-only Latin-1 strings
+    pub fn as_str(&self) -> Option<&str> {
+        match self.encoding {
+            StringEncoding::Utf8 => std::str::from_utf8(&self.bytes).ok(),
+            StringEncoding::Latin1 if self.bytes.is_ascii() => {
+                std::str::from_utf8(&self.bytes).ok()
+            }
+            _ => None,
+        }
+    }
+
+    /// Decodes the string, copying only if the bytes are not UTF-8 as they are
+    pub fn decode(&self) -> Result<Cow<'_, str>> {
+        if let Some(s) = self.as_str() {
+            return Ok(Cow::Borrowed(s));
+        }
+        match self.encoding {
+            StringEncoding::Utf8 => Err(Error::InvalidString),
+            StringEncoding::Latin1 => {
+                Ok(Cow::Owned(self.bytes.iter().map(|&b| b as char).collect()))
+            }
+            StringEncoding::CharArray => {
+                let units = self
+                    .bytes
+                    .chunks_exact(2)
+                    .map(|u| u16::from_be_bytes([u[0], u[1]]));
+                char::decode_utf16(units)
+                    .collect::<std::result::Result<String, _>>()
+                    .map(Cow::Owned)
+                    .map_err(|_| Error::InvalidString)
+            }
+        }
+    }
+
+    /// The number of bytes of the encoded string
+    pub fn len(&self) -> usize {
+        self.bytes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bytes.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode() {
+        let utf8 = RawString {
+            encoding: StringEncoding::Utf8,
+            bytes: "こんにちは".as_bytes().to_vec(),
+        };
+        assert_eq!(utf8.as_str(), Some("こんにちは"));
+        assert!(matches!(
+            utf8.decode().unwrap(),
+            Cow::Borrowed("こんにちは")
+        ));
+
+        let ascii = RawString {
+            encoding: StringEncoding::Latin1,
+            bytes: b"java/lang/Object".to_vec(),
+        };
+        assert_eq!(ascii.as_str(), Some("java/lang/Object"));
+
+        let latin1 = RawString {
+            encoding: StringEncoding::Latin1,
+            bytes: vec![b'c', 0xe9],
+        };
+        assert_eq!(latin1.as_str(), None);
+        assert_eq!(latin1.decode().unwrap(), "cé");
+
+        // a surrogate pair
+        let chars = RawString {
+            encoding: StringEncoding::CharArray,
+            bytes: vec![0x00, b'a', 0xd8, 0x3d, 0xde, 0x00],
+        };
+        assert_eq!(chars.as_str(), None);
+        assert_eq!(chars.decode().unwrap(), "a😀");
+
+        let invalid = RawString {
+            encoding: StringEncoding::CharArray,
+            bytes: vec![0xd8, 0x3d],
+        };
+        assert!(matches!(invalid.decode(), Err(Error::InvalidString)));
+    }
+}
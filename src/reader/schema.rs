@@ -0,0 +1,111 @@
+//! Structured description of the event types declared in a recording.
+//! This is useful to generate documentation or UI pickers from an actual recording.
+
+use crate::reader::type_descriptor::{FieldDescriptor, TickUnit, TypeDescriptor, TypePool, Unit};
+use serde::Serialize;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct EventSchema {
+    /// Sorted by the event type name
+    pub event_types: Vec<EventTypeSchema>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct EventTypeSchema {
+    pub name: String,
+    pub label: Option<String>,
+    pub description: Option<String>,
+    pub experimental: bool,
+    pub category: Vec<String>,
+    pub fields: Vec<FieldSchema>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FieldSchema {
+    pub name: String,
+    /// The name of the field type. None if the type isn't declared in the recording.
+    pub type_name: Option<String>,
+    pub label: Option<String>,
+    pub description: Option<String>,
+    pub experimental: bool,
+    pub array_type: bool,
+    pub unsigned: bool,
+    pub unit: Option<Unit>,
+    pub tick_unit: Option<TickUnit>,
+}
+
+impl EventSchema {
+    /// Collects the types which extend `jdk.jfr.Event`
+    pub fn new(type_pool: &TypePool) -> Self {
+        let mut event_types = type_pool
+            .event_types()
+            .map(|t| EventTypeSchema::new(t, type_pool))
+            .collect::<Vec<_>>();
+        event_types.sort_by(|a, b| a.name.cmp(&b.name));
+        Self { event_types }
+    }
+
+    pub fn get(&self, name: &str) -> Option<&EventTypeSchema> {
+        self.event_types.iter().find(|t| t.name == name)
+    }
+}
+
+impl EventTypeSchema {
+    fn new(desc: &TypeDescriptor, type_pool: &TypePool) -> Self {
+        Self {
+            name: desc.name().to_string(),
+            label: desc.label().map(|s| s.to_string()),
+            description: desc.description().map(|s| s.to_string()),
+            experimental: desc.experimental,
+            category: desc.category().map(|s| s.to_string()).collect(),
+            fields: desc
+                .fields
+                .iter()
+                .map(|f| FieldSchema::new(f, type_pool))
+                .collect(),
+        }
+    }
+}
+
+impl FieldSchema {
+    fn new(desc: &FieldDescriptor, type_pool: &TypePool) -> Self {
+        Self {
+            name: desc.name().to_string(),
+            type_name: type_pool.get(desc.class_id).map(|t| t.name().to_string()),
+            label: desc.label().map(|s| s.to_string()),
+            description: desc.description().map(|s| s.to_string()),
+            experimental: desc.experimental,
+            array_type: desc.array_type,
+            unsigned: desc.unsigned,
+            unit: desc.unit,
+            tick_unit: desc.tick_unit,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::reader::JfrReader;
+    use crate::test_util::test_data;
+    use std::fs::File;
+
+    #[test]
+    fn test_event_schema() {
+        let mut reader = JfrReader::new(File::open(test_data("recording.jfr")).unwrap());
+        let (_, chunk) = reader.chunk_metadata().next().unwrap().unwrap();
+        let schema = EventSchema::new(&chunk.metadata.type_pool);
+
+        assert!(schema.event_types.windows(2).all(|w| w[0].name < w[1].name));
+        assert!(schema.get("jdk.types.StackTrace").is_none());
+
+        let park = schema.get("jdk.ThreadPark").unwrap();
+        assert_eq!(park.label.as_deref(), Some("Java Thread Park"));
+        assert_eq!(park.category, vec!["Java Application"]);
+        let start_time = park.fields.iter().find(|f| f.name == "startTime").unwrap();
+        assert_eq!(start_time.type_name.as_deref(), Some("long"));
+        assert_eq!(start_time.tick_unit, Some(TickUnit::Timestamp));
+        let timeout = park.fields.iter().find(|f| f.name == "timeout").unwrap();
+        assert_eq!(timeout.unit, Some(Unit::Nanosecond));
+    }
+}
@@ -0,0 +1,138 @@
+//! `serde::Serialize` implementation to output arbitrary events (e.g. as JSON).
+//! Constant pool references are resolved, objects are emitted as maps and arrays as sequences.
+
+use crate::reader::event::{Accessor, Event};
+use crate::reader::value_descriptor::{Primitive, ValueDescriptor};
+use crate::reader::Chunk;
+use serde::ser::{Error, SerializeMap, SerializeSeq};
+use serde::{Serialize, Serializer};
+
+impl<'a> Serialize for Accessor<'a> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        Value {
+            chunk: self.chunk,
+            value: self.value,
+        }
+        .serialize(serializer)
+    }
+}
+
+impl<'a> Serialize for Event<'a> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        self.value().serialize(serializer)
+    }
+}
+
+struct Value<'a> {
+    chunk: &'a Chunk,
+    value: &'a ValueDescriptor,
+}
+
+impl<'a> Serialize for Value<'a> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match self.value {
+            ValueDescriptor::Primitive(p) => match p {
+                Primitive::Integer(v) => serializer.serialize_i32(*v),
+                Primitive::Long(v) => serializer.serialize_i64(*v),
+                Primitive::Float(v) => serializer.serialize_f32(*v),
+                Primitive::Double(v) => serializer.serialize_f64(*v),
+                Primitive::Character(v) => serializer.serialize_char(*v),
+                Primitive::Boolean(v) => serializer.serialize_bool(*v),
+                Primitive::Short(v) => serializer.serialize_i16(*v),
+                Primitive::Byte(v) => serializer.serialize_i8(*v),
+                Primitive::NullString => serializer.serialize_none(),
+                Primitive::String(_) | Primitive::CString(_) => match p.as_str() {
+                    Some(s) => serializer.serialize_str(s),
+                    None => Err(S::Error::custom("Invalid UTF-8")),
+                },
+                Primitive::RawString(s) => {
+                    serializer.serialize_str(&s.decode().map_err(S::Error::custom)?)
+                }
+            },
+            ValueDescriptor::Object(obj) => {
+                let type_desc =
+                    self.chunk
+                        .metadata
+                        .type_pool
+                        .get(obj.class_id)
+                        .ok_or_else(|| {
+                            S::Error::custom(format!("Class not found for id: {}", obj.class_id))
+                        })?;
+                let mut map = serializer.serialize_map(Some(obj.fields.len()))?;
+                for (field, value) in type_desc.fields.iter().zip(obj.fields.iter()) {
+                    map.serialize_entry(
+                        field.name(),
+                        &Value {
+                            chunk: self.chunk,
+                            value,
+                        },
+                    )?;
+                }
+                map.end()
+            }
+            ValueDescriptor::Array(array) => {
+                let mut seq = serializer.serialize_seq(Some(array.len()))?;
+                for value in array.iter() {
+                    seq.serialize_element(&Value {
+                        chunk: self.chunk,
+                        value,
+                    })?;
+                }
+                seq.end()
+            }
+            ValueDescriptor::ConstantPool {
+                class_id,
+                constant_index,
+            } => match self.chunk.constant_pool.get(class_id, constant_index) {
+                Some(value) => Value {
+                    chunk: self.chunk,
+                    value,
+                }
+                .serialize(serializer),
+                None => serializer.serialize_none(),
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::reader::JfrReader;
+    use crate::test_util::test_data;
+    use std::fs::File;
+
+    #[test]
+    fn test_serialize() {
+        let mut reader = JfrReader::new(File::open(test_data("profiler-wall.jfr")).unwrap());
+        let (chunk_reader, chunk) = reader.chunks().next().unwrap().unwrap();
+        let event = chunk_reader
+            .events(&chunk)
+            .flatten()
+            .find(|e| e.class.name() == "jdk.ExecutionSample")
+            .unwrap();
+
+        let json: serde_json::Value =
+            serde_json::from_str(&serde_json::to_string(&event.value()).unwrap()).unwrap();
+        assert_eq!(
+            json["sampledThread"]["osName"].as_str(),
+            event
+                .value()
+                .get_field("sampledThread")
+                .and_then(|v| v.get_field("osName"))
+                .and_then(|v| v.as_str())
+        );
+        assert!(!json["stackTrace"]["frames"].as_array().unwrap().is_empty());
+        assert!(json["stackTrace"]["frames"][0]["method"]["name"]["string"].is_string());
+
+        assert_eq!(serde_json::to_value(&event).unwrap(), json);
+    }
+}
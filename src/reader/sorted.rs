@@ -0,0 +1,141 @@
+//! Iterating events of a chunk in chronological order.
+
+use crate::reader::event::{Event, EventIterator};
+use crate::reader::{Error, Result};
+use std::vec;
+
+/// Iterator adapter which yields the events in the ascending order of `startTime`.
+///
+/// Events in a chunk are ordered by when the thread-local buffers are flushed rather than by time.
+/// On the first call of `next`, the offsets and `startTime` of all events are scanned without decoding
+/// the other fields, then each event is decoded when it's yielded.
+/// Events with the same `startTime` keep the order in the chunk, and events without `startTime` come first.
+pub struct SortedEvents<'a, 'b> {
+    events: EventIterator<'a, 'b>,
+    offsets: Option<vec::IntoIter<u64>>,
+    // the error on scanning, which is reported after the events scanned so far
+    error: Option<Error>,
+}
+
+impl<'a, 'b> SortedEvents<'a, 'b> {
+    pub fn new(events: EventIterator<'a, 'b>) -> Self {
+        Self {
+            events,
+            offsets: None,
+            error: None,
+        }
+    }
+
+    fn scan(&mut self) -> Vec<u64> {
+        let (entries, error) = scan_start_ticks(&mut self.events);
+        self.error = error;
+        entries.into_iter().map(|(_, offset)| offset).collect()
+    }
+}
+
+/// Reads `startTime` and the offset of the events, then sorts them by `startTime`.
+/// Events without `startTime` are treated as `i64::MIN`.
+/// The error is returned together with the events scanned so far.
+pub(crate) fn scan_start_ticks(events: &mut EventIterator) -> (Vec<(i64, u64)>, Option<Error>) {
+    let mut entries = vec![];
+    let mut error = None;
+    loop {
+        let (offset, _, event_type) = match events.next_event_header() {
+            Ok(Some(header)) => header,
+            Ok(None) => break,
+            Err(e) => {
+                error = Some(e);
+                break;
+            }
+        };
+        match events.read_start_ticks(event_type) {
+            Ok(ticks) => entries.push((ticks.unwrap_or(i64::MIN), offset)),
+            Err(e) => {
+                error = Some(e);
+                break;
+            }
+        }
+    }
+    // stable sort to keep the order of the events with the same time
+    entries.sort_by_key(|(ticks, _)| *ticks);
+    (entries, error)
+}
+
+impl<'a, 'b> Iterator for SortedEvents<'a, 'b> {
+    type Item = Result<Event<'a>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.offsets.is_none() {
+            self.offsets = Some(self.scan().into_iter());
+        }
+        match self.offsets.as_mut().and_then(|o| o.next()) {
+            Some(offset) => {
+                self.events.seek(offset);
+                self.events.next()
+            }
+            None => self.error.take().map(Err),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::reader::JfrReader;
+    use crate::test_util::test_data;
+    use std::fs::File;
+
+    fn start_ticks(event: &Event) -> Option<i64> {
+        event
+            .value()
+            .get_field("startTime")
+            .and_then(|v| v.as_i64())
+    }
+
+    #[test]
+    fn test_events_sorted() {
+        let mut reader = JfrReader::new(File::open(test_data("recording.jfr")).unwrap());
+        let (chunk_reader, chunk) = reader.chunks().next().unwrap().unwrap();
+
+        let sorted = chunk_reader
+            .events_sorted(&chunk)
+            .collect::<Result<Vec<_>>>()
+            .unwrap();
+        assert_eq!(sorted.len(), chunk_reader.events(&chunk).count());
+        // events in the chunk are not ordered by time
+        assert!(chunk_reader
+            .events(&chunk)
+            .flatten()
+            .map(|e| start_ticks(&e))
+            .collect::<Vec<_>>()
+            .windows(2)
+            .any(|w| w[0] > w[1]));
+        assert!(sorted
+            .windows(2)
+            .all(|w| start_ticks(&w[0]) <= start_ticks(&w[1])));
+        assert!(sorted
+            .windows(2)
+            .all(|w| start_ticks(&w[0]) != start_ticks(&w[1])
+                || w[0].byte_offset() < w[1].byte_offset()));
+    }
+
+    #[test]
+    fn test_events_sorted_selected() {
+        let mut reader = JfrReader::new(File::open(test_data("recording.jfr")).unwrap());
+        let (chunk_reader, chunk) = reader.chunks().next().unwrap().unwrap();
+
+        let sorted = chunk_reader
+            .events(&chunk)
+            .select(|t| t.name() == "jdk.ExecutionSample")
+            .sorted()
+            .collect::<Result<Vec<_>>>()
+            .unwrap();
+        assert!(!sorted.is_empty());
+        assert!(sorted
+            .iter()
+            .all(|e| e.class.name() == "jdk.ExecutionSample"));
+        assert!(sorted
+            .windows(2)
+            .all(|w| start_ticks(&w[0]) <= start_ticks(&w[1])));
+    }
+}
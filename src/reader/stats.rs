@@ -0,0 +1,154 @@
+//! Metrics of the parser itself, to track the parsing performance in downstream applications
+//! without external profilers.
+//!
+//! ```no_run
+//! use jfrs::reader::stats::ParseStats;
+//! use jfrs::reader::JfrReader;
+//! use std::fs::File;
+//!
+//! let mut reader = JfrReader::new(File::open("/path/to/recording.jfr").unwrap());
+//! let mut total = ParseStats::default();
+//! for chunk in reader.chunks() {
+//!     let (chunk_reader, chunk) = chunk.unwrap();
+//!     for event in chunk_reader.events(&chunk) {
+//!         event.unwrap();
+//!     }
+//!     total.merge(&chunk_reader.stats());
+//! }
+//! println!("{} events in {:?}", total.events_decoded, total.event_time);
+//! ```
+//!
+//! The times are always zero on `wasm32-unknown-unknown`, where no clock is available.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+#[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+use std::time::Instant;
+
+/// Statistics of parsing a chunk
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub struct ParseStats {
+    /// The bytes of the chunk loaded into memory
+    pub bytes_read: u64,
+    /// The number of the entries in the constant pool
+    pub constant_pool_entries: u64,
+    /// The number of the strings in the constant pool
+    pub strings_interned: u64,
+    /// The number of the events decoded by the event iterators of the chunk.
+    /// Events skipped by the selection are not counted
+    pub events_decoded: u64,
+    /// Time to read the metadata
+    pub metadata_time: Duration,
+    /// Time to read the constant pool
+    pub constant_pool_time: Duration,
+    /// Time spent in the event iterators of the chunk
+    pub event_time: Duration,
+}
+
+impl ParseStats {
+    /// Adds the stats of another chunk
+    pub fn merge(&mut self, other: &ParseStats) {
+        self.bytes_read += other.bytes_read;
+        self.constant_pool_entries += other.constant_pool_entries;
+        self.strings_interned += other.strings_interned;
+        self.events_decoded += other.events_decoded;
+        self.metadata_time += other.metadata_time;
+        self.constant_pool_time += other.constant_pool_time;
+        self.event_time += other.event_time;
+    }
+
+    /// The total time of all phases
+    pub fn total_time(&self) -> Duration {
+        self.metadata_time + self.constant_pool_time + self.event_time
+    }
+}
+
+/// Counters of the events decoded by the event iterators of a chunk.
+/// Atomic so that the iterators can run on multiple threads sharing the chunk reader
+#[derive(Debug, Default)]
+pub(crate) struct EventCounters {
+    events_decoded: AtomicU64,
+    event_nanos: AtomicU64,
+}
+
+impl EventCounters {
+    pub(crate) fn add(&self, events_decoded: u64, event_time: Duration) {
+        self.events_decoded
+            .fetch_add(events_decoded, Ordering::Relaxed);
+        self.event_nanos
+            .fetch_add(event_time.as_nanos() as u64, Ordering::Relaxed);
+    }
+
+    /// Adds the counts to the stats
+    pub(crate) fn add_to(&self, stats: &mut ParseStats) {
+        stats.events_decoded += self.events_decoded.load(Ordering::Relaxed);
+        stats.event_time += Duration::from_nanos(self.event_nanos.load(Ordering::Relaxed));
+    }
+}
+
+/// Measures the time of a phase
+#[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+pub(crate) struct Stopwatch(Instant);
+
+#[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+impl Stopwatch {
+    pub(crate) fn start() -> Self {
+        Self(Instant::now())
+    }
+
+    pub(crate) fn elapsed(&self) -> Duration {
+        self.0.elapsed()
+    }
+}
+
+/// `Instant::now` panics on wasm32-unknown-unknown
+#[cfg(all(target_arch = "wasm32", target_os = "unknown"))]
+pub(crate) struct Stopwatch;
+
+#[cfg(all(target_arch = "wasm32", target_os = "unknown"))]
+impl Stopwatch {
+    pub(crate) fn start() -> Self {
+        Self
+    }
+
+    pub(crate) fn elapsed(&self) -> Duration {
+        Duration::ZERO
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::reader::JfrReader;
+    use crate::test_util::test_data;
+    use std::fs::File;
+
+    #[test]
+    fn test_stats() {
+        let path = test_data("recording.jfr");
+        let mut reader = JfrReader::new(File::open(&path).unwrap());
+        let mut total = ParseStats::default();
+        let mut events = 0;
+        for chunk in reader.chunks() {
+            let (chunk_reader, chunk) = chunk.unwrap();
+            let stats = chunk_reader.stats();
+            assert_eq!(stats.events_decoded, 0);
+            assert_eq!(stats.bytes_read, chunk.header.chunk_size as u64);
+            assert_eq!(
+                stats.constant_pool_entries,
+                chunk.constant_pool.inner.len() as u64
+            );
+            assert!(0 < stats.strings_interned);
+            assert!(stats.strings_interned < stats.constant_pool_entries);
+
+            events += chunk_reader.events(&chunk).flatten().count() as u64;
+            // selected out events are not decoded
+            chunk_reader.events(&chunk).select(|_| false).for_each(drop);
+            total.merge(&chunk_reader.stats());
+        }
+        assert_eq!(total.events_decoded, events);
+        assert_eq!(total.bytes_read, std::fs::metadata(path).unwrap().len());
+        assert!(total.event_time > Duration::ZERO);
+        assert!(total.total_time() >= total.event_time);
+    }
+}
@@ -0,0 +1,334 @@
+//! Streaming of the recording being written by the JVM (JEP 349).
+//!
+//! With event streaming, the JVM flushes the buffered events into the chunk file of the disk repository
+//! periodically (once per second by default). Every flush appends the events followed by a checkpoint
+//! (constant pool) event, and the metadata if new types are registered, then updates the chunk header
+//! so that the chunk size, the constant pool offset and the metadata offset point to the last flush.
+//! Hence the chunk can be parsed as a complete chunk at any flush boundary, and only the events
+//! appended since the previous boundary need to be read.
+
+use crate::reader::event::EventIterator;
+use crate::reader::{Chunk, ChunkHeader, ChunkReader, Error, JfrReader, Result};
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::{fs, io};
+
+/// Events flushed into the chunk since the previous [`RepositoryStream::poll`].
+pub struct FlushSegment {
+    path: PathBuf,
+    reader: ChunkReader,
+    chunk: Chunk,
+    start_offset: u64,
+}
+
+impl FlushSegment {
+    /// Returns an iterator over the events of this segment.
+    ///
+    /// The constant pool accumulated up to this flush is available, so constants referenced
+    /// from the events are resolved as usual.
+    pub fn events(&self) -> EventIterator<'_, '_> {
+        self.reader
+            .events_from_offset(&self.chunk, self.start_offset)
+    }
+
+    /// Returns the chunk as of this flush
+    pub fn chunk(&self) -> &Chunk {
+        &self.chunk
+    }
+
+    /// Returns the path of the chunk file
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Returns the offset of the first event of this segment from the beginning of the chunk body
+    pub fn start_offset(&self) -> u64 {
+        self.start_offset
+    }
+}
+
+/// Follows the chunk files of the JVM's disk repository (e.g. the directory specified by
+/// `-XX:FlightRecorderOptions:repository=...`) while the JVM is still recording.
+///
+/// Chunk files are read in the order of their names, which the JVM generates from the chunk start time.
+/// Once the current chunk is finished, the stream moves to the next chunk file.
+///
+/// Note that each flush re-reads and re-parses the current chunk file from the beginning,
+/// i.e. the metadata and the whole constant pool chain, although only the events appended since
+/// the previous flush are iterated. So the cost of a poll grows with the size of the current chunk,
+/// which is bounded by the JVM's chunk rotation (`maxchunksize`, 12 MB by default).
+///
+/// ```no_run
+/// use jfrs::reader::streaming::RepositoryStream;
+/// use std::time::Duration;
+///
+/// let mut stream = RepositoryStream::new("/path/to/repository");
+/// loop {
+///     while let Some(segment) = stream.poll().unwrap() {
+///         for event in segment.events().flatten() {
+///             println!("{}", event.class.name());
+///         }
+///     }
+///     std::thread::sleep(Duration::from_secs(1));
+/// }
+/// ```
+pub struct RepositoryStream {
+    repository: PathBuf,
+    current: Option<ChunkFollower>,
+}
+
+impl RepositoryStream {
+    /// Creates a stream which starts from the oldest chunk file in the repository
+    pub fn new<P: Into<PathBuf>>(repository: P) -> Self {
+        Self {
+            repository: repository.into(),
+            current: None,
+        }
+    }
+
+    /// Reads the events flushed since the last call.
+    ///
+    /// Returns `None` if nothing is flushed yet, or the JVM is in the middle of updating the header.
+    /// Since each call returns at most one segment, call this repeatedly until `None` is returned.
+    pub fn poll(&mut self) -> Result<Option<FlushSegment>> {
+        loop {
+            let follower = match &mut self.current {
+                Some(follower) => follower,
+                None => match self.next_file(None)? {
+                    Some(path) => self.current.insert(ChunkFollower::new(path)),
+                    None => return Ok(None),
+                },
+            };
+            match follower.poll() {
+                Ok(Some(segment)) => return Ok(Some(segment)),
+                Ok(None) if !follower.finished => return Ok(None),
+                Ok(None) => {}
+                // the chunk file is removed by the JVM's repository cleanup
+                Err(Error::IoError(e)) if e.kind() == io::ErrorKind::NotFound => {}
+                Err(e) => return Err(e),
+            }
+
+            let path = follower.path.clone();
+            match self.next_file(Some(&path))? {
+                Some(next) => self.current = Some(ChunkFollower::new(next)),
+                None => return Ok(None),
+            }
+        }
+    }
+
+    /// Returns the first chunk file whose name comes after the given one
+    fn next_file(&self, after: Option<&Path>) -> Result<Option<PathBuf>> {
+        let mut next: Option<PathBuf> = None;
+        for entry in fs::read_dir(&self.repository).map_err(Error::IoError)? {
+            let path = entry.map_err(Error::IoError)?.path();
+            if path.extension().is_none_or(|ext| ext != "jfr") {
+                continue;
+            }
+            if after.is_some_and(|after| path.file_name() <= after.file_name()) {
+                continue;
+            }
+            if next
+                .as_ref()
+                .is_none_or(|next| path.file_name() < next.file_name())
+            {
+                next = Some(path);
+            }
+        }
+        Ok(next)
+    }
+}
+
+/// Follows the flushes of single chunk file
+struct ChunkFollower {
+    path: PathBuf,
+    reader: Option<(File, JfrReader<File>)>,
+    /// The chunk size as of the last flush read
+    consumed_size: u64,
+    finished: bool,
+}
+
+impl ChunkFollower {
+    fn new(path: PathBuf) -> Self {
+        Self {
+            path,
+            reader: None,
+            consumed_size: 0,
+            finished: false,
+        }
+    }
+
+    fn poll(&mut self) -> Result<Option<FlushSegment>> {
+        if self.reader.is_none() {
+            let file = File::open(&self.path).map_err(Error::IoError)?;
+            let reader = JfrReader::new(file.try_clone().map_err(Error::IoError)?);
+            self.reader = Some((file, reader));
+        }
+        let (file, reader) = self.reader.as_mut().unwrap();
+
+        let header = match read_header(file)? {
+            Some(header) if !is_updating(&header) => header,
+            _ => return Ok(None),
+        };
+        let chunk_size = i64::from_be_bytes(header[8..16].try_into().unwrap()) as u64;
+        if chunk_size <= self.consumed_size || chunk_size < ChunkHeader::HEADER_SIZE {
+            let duration_nanos = i64::from_be_bytes(header[40..48].try_into().unwrap());
+            self.finished = chunk_size > 0 && duration_nanos != 0;
+            return Ok(None);
+        }
+
+        // the constant pools of all flushes so far are needed to resolve the new events,
+        // so the whole chunk is parsed again. See the cost noted in `RepositoryStream`
+        let (chunk_reader, chunk) = match reader.open_chunk_at(0) {
+            Ok(chunk) => chunk,
+            Err(Error::TruncatedChunk { .. }) => return Ok(None),
+            Err(e) => return Err(e),
+        };
+        // the header may be updated while reading the chunk, then retry on the next poll
+        if read_header(file)? != Some(header) {
+            return Ok(None);
+        }
+
+        let start_offset = self.consumed_size.saturating_sub(ChunkHeader::HEADER_SIZE);
+        self.consumed_size = chunk_size;
+        self.finished = chunk.header.is_finished();
+        Ok(Some(FlushSegment {
+            path: self.path.clone(),
+            reader: chunk_reader,
+            chunk,
+            start_offset,
+        }))
+    }
+}
+
+/// Reads the raw chunk header. Returns `None` if the header is not written yet
+fn read_header(file: &mut File) -> Result<Option<[u8; ChunkHeader::HEADER_SIZE as usize]>> {
+    let mut header = [0u8; ChunkHeader::HEADER_SIZE as usize];
+    file.seek(SeekFrom::Start(0)).map_err(Error::IoError)?;
+    match file.read_exact(&mut header) {
+        Ok(()) => Ok(Some(header)),
+        Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => Ok(None),
+        Err(e) => Err(Error::IoError(e)),
+    }
+}
+
+fn is_updating(header: &[u8]) -> bool {
+    header[ChunkHeader::FEATURES_OFFSET] == ChunkHeader::FILE_STATE_UPDATING
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::decode::IntEncoding;
+    use crate::writer::recording::{FieldDeclaration, JfrWriter, TypeDeclaration};
+    use serde::Serialize;
+
+    #[derive(Serialize)]
+    #[serde(rename_all = "camelCase")]
+    struct Tick {
+        start_time: i64,
+        value: i64,
+    }
+
+    /// Writes single chunk with raw integers, so that offsets can be patched in place
+    fn write_chunk(values: std::ops::Range<i64>) -> Vec<u8> {
+        let mut writer = JfrWriter::new(vec![]).int_encoding(IntEncoding::Raw);
+        writer
+            .declare_type(
+                TypeDeclaration::event("app.Tick").field(FieldDeclaration::new("value", "long")),
+            )
+            .unwrap();
+        for value in values {
+            let tick = Tick {
+                start_time: 1000 + value,
+                value,
+            };
+            writer.write_event("app.Tick", &tick).unwrap();
+        }
+        writer.finish().unwrap()
+    }
+
+    fn read_i64(bytes: &[u8], offset: usize) -> i64 {
+        i64::from_be_bytes(bytes[offset..offset + 8].try_into().unwrap())
+    }
+
+    fn write_i64(bytes: &mut [u8], offset: usize, value: i64) {
+        bytes[offset..offset + 8].copy_from_slice(&value.to_be_bytes());
+    }
+
+    /// Builds the chunk in the states after the first and the second flush like JVM writes,
+    /// by appending the body of `second` to `first` and chaining the constant pool events
+    fn flushes(first: &[u8], second: &[u8]) -> (Vec<u8>, Vec<u8>) {
+        let header_size = ChunkHeader::HEADER_SIZE as usize;
+        let mut first_flush = first.to_vec();
+        // duration
+        write_i64(&mut first_flush, 40, 0);
+
+        let mut second_flush = first_flush.clone();
+        second_flush.extend_from_slice(&second[header_size..]);
+        let shift = first.len() as i64 - header_size as i64;
+        let constant_pool_offset = read_i64(second, 16) + shift;
+        let metadata_offset = read_i64(second, 24) + shift;
+        let chunk_size = second_flush.len() as i64;
+        write_i64(&mut second_flush, 8, chunk_size);
+        write_i64(&mut second_flush, 16, constant_pool_offset);
+        write_i64(&mut second_flush, 24, metadata_offset);
+        // delta to the constant pool event of the first flush
+        write_i64(
+            &mut second_flush,
+            constant_pool_offset as usize + 28,
+            read_i64(first, 16) - constant_pool_offset,
+        );
+        (first_flush, second_flush)
+    }
+
+    fn poll_values(stream: &mut RepositoryStream) -> Vec<i64> {
+        let mut values = vec![];
+        while let Some(segment) = stream.poll().unwrap() {
+            for event in segment.events() {
+                let event = event.unwrap();
+                values.push(event.value().get_field("value").unwrap().as_i64().unwrap());
+            }
+        }
+        values
+    }
+
+    #[test]
+    fn test_poll() {
+        let repository =
+            std::env::temp_dir().join(format!("jfrs-test-streaming-{}", std::process::id()));
+        fs::create_dir_all(&repository).unwrap();
+        let chunk_path = repository.join("2026_01_01_00_00_00.jfr");
+
+        let mut stream = RepositoryStream::new(&repository);
+        assert!(poll_values(&mut stream).is_empty());
+
+        let (first_flush, mut second_flush) = flushes(&write_chunk(0..3), &write_chunk(3..5));
+        fs::write(&chunk_path, &first_flush).unwrap();
+        assert_eq!(poll_values(&mut stream), vec![0, 1, 2]);
+        assert!(poll_values(&mut stream).is_empty());
+
+        let mut updating = second_flush.clone();
+        updating[ChunkHeader::FEATURES_OFFSET] = ChunkHeader::FILE_STATE_UPDATING;
+        fs::write(&chunk_path, &updating).unwrap();
+        assert!(poll_values(&mut stream).is_empty());
+
+        fs::write(&chunk_path, &second_flush).unwrap();
+        assert_eq!(poll_values(&mut stream), vec![3, 4]);
+
+        // the next chunk is not read until the current chunk is finished
+        fs::write(
+            repository.join("2026_01_01_00_00_10.jfr"),
+            write_chunk(5..6),
+        )
+        .unwrap();
+        assert!(poll_values(&mut stream).is_empty());
+
+        write_i64(&mut second_flush, 40, 1);
+        fs::write(&chunk_path, &second_flush).unwrap();
+        assert_eq!(poll_values(&mut stream), vec![5]);
+        assert!(poll_values(&mut stream).is_empty());
+
+        fs::remove_dir_all(&repository).unwrap();
+    }
+}
@@ -0,0 +1,139 @@
+//! Statistics of the events in a chunk, like `jfr summary` command.
+
+use crate::reader::{Chunk, ChunkReader, Result};
+use rustc_hash::FxHashMap;
+
+/// Statistics of an event type
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct EventTypeSummary {
+    pub name: String,
+    pub count: u64,
+    /// Total size of the events in bytes
+    pub total_bytes: u64,
+    /// The earliest `startTime` of the events in nanoseconds since UNIX epoch
+    pub min_start_time_nanos: Option<i64>,
+    /// The latest `startTime` of the events in nanoseconds since UNIX epoch
+    pub max_start_time_nanos: Option<i64>,
+}
+
+/// Number of events per category path (e.g. `["Java Virtual Machine", "GC"]`)
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct CategorySummary {
+    pub category: Vec<String>,
+    pub count: u64,
+}
+
+/// Statistics of the events in a chunk
+#[derive(Debug, Clone, Default)]
+pub struct ChunkSummary {
+    /// Sorted by the number of events in descending order
+    pub event_types: Vec<EventTypeSummary>,
+    /// Sorted by the number of events in descending order
+    pub categories: Vec<CategorySummary>,
+}
+
+impl ChunkSummary {
+    pub fn compute(reader: &ChunkReader, chunk: &Chunk) -> Result<Self> {
+        let mut event_types: FxHashMap<i64, EventTypeSummary> = FxHashMap::default();
+        for event in reader.events(chunk) {
+            let event = event?;
+            let summary =
+                event_types
+                    .entry(event.class.class_id)
+                    .or_insert_with(|| EventTypeSummary {
+                        name: event.class.name().to_string(),
+                        count: 0,
+                        total_bytes: 0,
+                        min_start_time_nanos: None,
+                        max_start_time_nanos: None,
+                    });
+            summary.count += 1;
+            summary.total_bytes += event.byte_size;
+
+            if let Some(ticks) = event
+                .value
+                .get_field_raw("startTime", chunk)
+                .and_then(|v| i64::try_from(v).ok())
+            {
+                let nanos = chunk.header.ticks_to_epoch_nanos(ticks);
+                summary.min_start_time_nanos =
+                    Some(summary.min_start_time_nanos.map_or(nanos, |t| t.min(nanos)));
+                summary.max_start_time_nanos =
+                    Some(summary.max_start_time_nanos.map_or(nanos, |t| t.max(nanos)));
+            }
+        }
+
+        let mut categories: FxHashMap<Vec<String>, u64> = FxHashMap::default();
+        for (class_id, summary) in event_types.iter() {
+            if let Some(desc) = chunk.metadata.type_pool.get(*class_id) {
+                let category = desc.category().map(|c| c.to_string()).collect();
+                *categories.entry(category).or_default() += summary.count;
+            }
+        }
+
+        let mut event_types = event_types.into_values().collect::<Vec<_>>();
+        event_types.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.name.cmp(&b.name)));
+        let mut categories = categories
+            .into_iter()
+            .map(|(category, count)| CategorySummary { category, count })
+            .collect::<Vec<_>>();
+        categories.sort_by(|a, b| {
+            b.count
+                .cmp(&a.count)
+                .then_with(|| a.category.cmp(&b.category))
+        });
+
+        Ok(Self {
+            event_types,
+            categories,
+        })
+    }
+
+    /// Total number of events in the chunk
+    pub fn event_count(&self) -> u64 {
+        self.event_types.iter().map(|s| s.count).sum()
+    }
+
+    pub fn get(&self, type_name: &str) -> Option<&EventTypeSummary> {
+        self.event_types.iter().find(|s| s.name == type_name)
+    }
+
+    /// Returns the `n` categories which have the most events
+    pub fn top_categories(&self, n: usize) -> &[CategorySummary] {
+        &self.categories[..n.min(self.categories.len())]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::reader::JfrReader;
+    use crate::test_util::test_data;
+    use std::fs::File;
+
+    #[test]
+    fn test_compute() {
+        let mut reader = JfrReader::new(File::open(test_data("recording.jfr")).unwrap());
+        let (chunk_reader, chunk) = reader.chunks().next().unwrap().unwrap();
+        let summary = ChunkSummary::compute(&chunk_reader, &chunk).unwrap();
+
+        assert_eq!(summary.event_count(), 4065);
+        let park = summary.get("jdk.ThreadPark").unwrap();
+        assert_eq!(park.count, 237);
+        assert!(park.total_bytes > 0);
+        let min = park.min_start_time_nanos.unwrap();
+        let max = park.max_start_time_nanos.unwrap();
+        assert!(chunk.header.start_time_nanos <= min);
+        assert!(min <= max && max <= chunk.header.end_time_nanos());
+
+        assert!(summary
+            .event_types
+            .windows(2)
+            .all(|w| w[0].count >= w[1].count));
+        assert_eq!(
+            summary.categories.iter().map(|c| c.count).sum::<u64>(),
+            4065
+        );
+        assert_eq!(summary.top_categories(1).len(), 1);
+    }
+}
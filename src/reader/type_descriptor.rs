@@ -2,11 +2,13 @@
 //! TypeDescriptor defines the "schema" of types.
 //! Event and ConstantPool values are parsed based on declared TypeDescriptor.
 
-use crate::reader::byte_stream::{ByteStream, StringType};
-use crate::reader::{Error, Result};
-use std::io::Read;
+use crate::reader::byte_stream::{ByteStream, Input, StringType};
+use crate::reader::known_types::KnownType;
+use crate::reader::limits::LimitKind;
+use crate::reader::{ChunkHeader, Error, Result};
 
 use rustc_hash::FxHashMap;
+use serde::Serialize;
 use std::rc::Rc;
 
 /// String intern pool
@@ -14,9 +16,9 @@ use std::rc::Rc;
 pub struct StringTable(Vec<Option<Rc<str>>>);
 
 impl StringTable {
-    pub fn try_new<T: Read>(stream: &mut ByteStream<T>) -> Result<Self> {
-        let string_count = stream.read_i32()?;
-        let mut strings = Vec::with_capacity(string_count as usize);
+    pub fn try_new<T: Input>(stream: &mut ByteStream<T>) -> Result<Self> {
+        let string_count = stream.read_length(LimitKind::ArrayLength)?;
+        let mut strings = Vec::with_capacity(string_count);
 
         for _ in 0..string_count {
             match stream.read_string()? {
@@ -38,20 +40,67 @@ impl StringTable {
     }
 }
 
+/// The super type of all event types
+pub(crate) const EVENT_SUPER_TYPE: &str = "jdk.jfr.Event";
+
 #[derive(Debug, Default)]
 pub struct TypePool {
     pub(crate) inner: FxHashMap<i64, TypeDescriptor>,
+    names: FxHashMap<Rc<str>, i64>,
+    event_type_ids: Vec<i64>,
 }
 
 impl TypePool {
     pub fn register(&mut self, class_id: i64, desc: TypeDescriptor) {
-        self.inner.insert(class_id, desc);
+        let name = desc.name.clone();
+        let is_event_type = desc.super_type() == Some(EVENT_SUPER_TYPE);
+        if let Some(old) = self.inner.insert(class_id, desc) {
+            self.names.remove(&old.name);
+            self.event_type_ids.retain(|&id| id != class_id);
+        }
+        self.names.insert(name, class_id);
+        if is_event_type {
+            self.event_type_ids.push(class_id);
+        }
     }
 
     pub fn get(&self, class_id: i64) -> Option<&TypeDescriptor> {
         self.inner.get(&class_id)
     }
 
+    pub fn get_by_name(&self, name: &str) -> Option<&TypeDescriptor> {
+        self.names.get(name).and_then(|id| self.inner.get(id))
+    }
+
+    /// Returns the class id of the known type in this chunk, which can be compared
+    /// with [`TypeDescriptor::class_id`] in hot loops
+    pub fn class_id_of(&self, known_type: KnownType) -> Option<i64> {
+        self.names.get(known_type.name()).copied()
+    }
+
+    /// Returns the types which extend `jdk.jfr.Event`
+    pub fn event_types(&self) -> impl Iterator<Item = &TypeDescriptor> {
+        self.event_type_ids
+            .iter()
+            .filter_map(|id| self.inner.get(id))
+    }
+
+    /// Returns the event types in the category or its subcategories, like the event browser of JMC
+    pub fn event_types_in_category<'a>(
+        &'a self,
+        category: &'a [&str],
+    ) -> impl Iterator<Item = &'a TypeDescriptor> {
+        self.event_types().filter(|t| t.is_in_category(category))
+    }
+
+    /// Returns the event types with the label (e.g. `Execution Sample`)
+    pub fn event_types_with_label<'a>(
+        &'a self,
+        label: &'a str,
+    ) -> impl Iterator<Item = &'a TypeDescriptor> {
+        self.event_types().filter(move |t| t.label() == Some(label))
+    }
+
     pub fn get_types(&self) -> impl Iterator<Item = &TypeDescriptor> {
         self.inner.values()
     }
@@ -64,28 +113,38 @@ pub struct TypeDescriptor {
     pub(crate) super_type: Option<Rc<str>>,
     pub simple_type: bool,
     pub fields: Vec<FieldDescriptor>,
+    pub(crate) field_indices: FxHashMap<Rc<str>, usize>,
+    pub(crate) known_type: Option<KnownType>,
 
     // these fields are filled by annotations
     pub(crate) label: Option<Rc<str>>,
     pub(crate) description: Option<Rc<str>>,
     pub experimental: bool,
     pub(crate) category: Vec<Rc<str>>,
+
+    pub(crate) settings: Vec<SettingDescriptor>,
 }
 
 impl TypeDescriptor {
     pub fn get_field(&self, name: &str) -> Option<(usize, &FieldDescriptor)> {
-        for (idx, field) in self.fields.iter().enumerate() {
-            if field.name.as_ref() == name {
-                return Some((idx, field));
-            }
-        }
-        None
+        self.field_index(name).map(|idx| (idx, &self.fields[idx]))
+    }
+
+    /// Returns the index of the field in the object value, which can be cached to access
+    /// the field without name lookup
+    pub fn field_index(&self, name: &str) -> Option<usize> {
+        self.field_indices.get(name).copied()
     }
 
     pub fn name(&self) -> &str {
         self.name.as_ref()
     }
 
+    /// Returns the well-known type if the type is one of them
+    pub fn known_type(&self) -> Option<KnownType> {
+        self.known_type
+    }
+
     pub fn super_type(&self) -> Option<&str> {
         self.super_type.as_ref().map(|s| s.as_ref())
     }
@@ -101,6 +160,57 @@ impl TypeDescriptor {
     pub fn category(&self) -> impl Iterator<Item = &str> {
         self.category.iter().map(|s| s.as_ref())
     }
+
+    /// Returns true if the category path of the type starts with `category`,
+    /// i.e. the type is in the category or its subcategories
+    pub fn is_in_category(&self, category: &[&str]) -> bool {
+        self.category.len() >= category.len()
+            && self
+                .category
+                .iter()
+                .zip(category)
+                .all(|(a, b)| a.as_ref() == *b)
+    }
+
+    /// Settings of the event type (e.g. `enabled`, `threshold`, `period`, `stackTrace`)
+    pub fn settings(&self) -> &[SettingDescriptor] {
+        &self.settings
+    }
+
+    pub fn get_setting(&self, name: &str) -> Option<&SettingDescriptor> {
+        self.settings.iter().find(|s| s.name.as_ref() == name)
+    }
+}
+
+/// Setting declared by the event type.
+///
+/// Note that the value declared in the metadata is the default value.
+/// The values actually applied to the recording are recorded as `jdk.ActiveSetting` events.
+#[derive(Debug)]
+pub struct SettingDescriptor {
+    pub class_id: i64,
+    pub(crate) name: Rc<str>,
+    pub(crate) default_value: Option<Rc<str>>,
+    pub(crate) label: Option<Rc<str>>,
+    pub(crate) description: Option<Rc<str>>,
+}
+
+impl SettingDescriptor {
+    pub fn name(&self) -> &str {
+        self.name.as_ref()
+    }
+
+    pub fn default_value(&self) -> Option<&str> {
+        self.default_value.as_ref().map(|s| s.as_ref())
+    }
+
+    pub fn label(&self) -> Option<&str> {
+        self.label.as_ref().map(|s| s.as_ref())
+    }
+
+    pub fn description(&self) -> Option<&str> {
+        self.description.as_ref().map(|s| s.as_ref())
+    }
 }
 
 #[derive(Debug)]
@@ -129,9 +239,46 @@ impl FieldDescriptor {
     pub fn description(&self) -> Option<&str> {
         self.description.as_ref().map(|s| s.as_ref())
     }
+
+    /// Returns true if the value is a duration, in ticks or in the time unit
+    pub(crate) fn is_timespan(&self) -> bool {
+        self.tick_unit == Some(TickUnit::Timespan)
+            || matches!(
+                self.unit,
+                Some(Unit::Nanosecond | Unit::Millisecond | Unit::Second)
+            )
+    }
+
+    /// Converts the value of the timespan field to nanoseconds
+    pub(crate) fn timespan_nanos(&self, v: i64, header: &ChunkHeader) -> Option<i128> {
+        let v = v as i128;
+        if self.tick_unit == Some(TickUnit::Timespan) {
+            return (v * 1_000_000_000).checked_div(header.ticks_per_second as i128);
+        }
+        match self.unit? {
+            Unit::Nanosecond => Some(v),
+            Unit::Millisecond => Some(v * 1_000_000),
+            Unit::Second => Some(v * 1_000_000_000),
+            _ => None,
+        }
+    }
+
+    /// Converts the value of the timestamp field to nanoseconds since UNIX epoch
+    pub(crate) fn timestamp_nanos(&self, v: i64, header: &ChunkHeader) -> Option<i128> {
+        if self.tick_unit == Some(TickUnit::Timestamp) {
+            return Some(header.ticks_to_epoch_nanos(v) as i128);
+        }
+        let v = v as i128;
+        match self.unit? {
+            Unit::EpochNano => Some(v),
+            Unit::EpochMilli => Some(v * 1_000_000),
+            Unit::EpochSecond => Some(v * 1_000_000_000),
+            _ => None,
+        }
+    }
 }
 
-#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Serialize)]
 pub enum Unit {
     Byte,
     PercentUnity,
@@ -145,7 +292,7 @@ pub enum Unit {
     EpochSecond,
 }
 
-#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Serialize)]
 pub enum TickUnit {
     Timespan,
     Timestamp,
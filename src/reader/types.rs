@@ -3,6 +3,17 @@
 //! Related JMC code: [TypesImpl.java](https://github.com/openjdk/jmc/blob/8.2.0-ga/core/org.openjdk.jmc.flightrecorder.writer/src/main/java/org/openjdk/jmc/flightrecorder/writer/TypesImpl.java)
 //! TODO: should refer TypeManager instead?
 
+use serde::Deserialize;
+
+/// Rust struct which represents an event type, used to route events by the type.
+///
+/// Since the structs borrow strings from the chunk, the struct is given as a generic associated type.
+pub trait EventType {
+    /// The event type name (e.g. `jdk.ExecutionSample`)
+    const NAME: &'static str;
+    type Value<'de>: Deserialize<'de>;
+}
+
 pub mod builtin {
     use serde::Deserialize;
 
@@ -132,11 +143,25 @@ pub mod builtin {
     pub struct ThreadState<'a> {
         pub name: Option<&'a str>,
     }
+
+    #[derive(Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    pub struct GCName<'a> {
+        pub name: Option<&'a str>,
+    }
+
+    #[derive(Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    pub struct NarrowOopMode<'a> {
+        pub mode: Option<&'a str>,
+    }
 }
 
 pub mod jdk {
     use super::builtin::*;
+    use super::EventType;
     use serde::Deserialize;
+    use std::time::Duration;
 
     #[derive(Deserialize)]
     #[serde(rename_all = "camelCase")]
@@ -148,4 +173,312 @@ pub mod jdk {
         #[serde(borrow)]
         pub state: Option<ThreadState<'a>>,
     }
+
+    impl EventType for ExecutionSample<'_> {
+        const NAME: &'static str = "jdk.ExecutionSample";
+        type Value<'de> = ExecutionSample<'de>;
+    }
+
+    impl ExecutionSample<'_> {
+        /// Returns true if the thread was running when sampled.
+        /// Wall-clock profilers (e.g. async-profiler in `wall` mode) also sample sleeping threads.
+        pub fn is_running(&self) -> bool {
+            self.state
+                .as_ref()
+                .and_then(|s| s.name)
+                .is_none_or(|s| s == "STATE_RUNNABLE")
+        }
+    }
+
+    #[derive(Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    pub struct ObjectAllocationInNewTLAB<'a> {
+        #[serde(borrow)]
+        pub event_thread: Option<JdkThread<'a>>,
+        #[serde(borrow)]
+        pub stack_trace: Option<StackTrace<'a>>,
+        #[serde(borrow)]
+        pub object_class: Option<Class<'a>>,
+        #[serde(default)]
+        pub allocation_size: i64,
+        #[serde(default)]
+        pub tlab_size: i64,
+    }
+
+    impl EventType for ObjectAllocationInNewTLAB<'_> {
+        const NAME: &'static str = "jdk.ObjectAllocationInNewTLAB";
+        type Value<'de> = ObjectAllocationInNewTLAB<'de>;
+    }
+
+    #[derive(Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    pub struct ObjectAllocationOutsideTLAB<'a> {
+        #[serde(borrow)]
+        pub event_thread: Option<JdkThread<'a>>,
+        #[serde(borrow)]
+        pub stack_trace: Option<StackTrace<'a>>,
+        #[serde(borrow)]
+        pub object_class: Option<Class<'a>>,
+        #[serde(default)]
+        pub allocation_size: i64,
+    }
+
+    impl EventType for ObjectAllocationOutsideTLAB<'_> {
+        const NAME: &'static str = "jdk.ObjectAllocationOutsideTLAB";
+        type Value<'de> = ObjectAllocationOutsideTLAB<'de>;
+    }
+
+    #[derive(Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    pub struct JavaMonitorEnter<'a> {
+        #[serde(default, with = "crate::reader::de::timespan::option")]
+        pub duration: Option<Duration>,
+        #[serde(borrow)]
+        pub event_thread: Option<JdkThread<'a>>,
+        #[serde(borrow)]
+        pub stack_trace: Option<StackTrace<'a>>,
+        #[serde(borrow)]
+        pub monitor_class: Option<Class<'a>>,
+        #[serde(borrow, default)]
+        pub previous_owner: Option<JdkThread<'a>>,
+        #[serde(default)]
+        pub address: i64,
+    }
+
+    impl EventType for JavaMonitorEnter<'_> {
+        const NAME: &'static str = "jdk.JavaMonitorEnter";
+        type Value<'de> = JavaMonitorEnter<'de>;
+    }
+
+    #[derive(Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    pub struct ThreadPark<'a> {
+        #[serde(default, with = "crate::reader::de::timespan::option")]
+        pub duration: Option<Duration>,
+        #[serde(borrow)]
+        pub event_thread: Option<JdkThread<'a>>,
+        #[serde(borrow)]
+        pub stack_trace: Option<StackTrace<'a>>,
+        #[serde(borrow)]
+        pub parked_class: Option<Class<'a>>,
+        #[serde(default)]
+        pub address: i64,
+    }
+
+    impl EventType for ThreadPark<'_> {
+        const NAME: &'static str = "jdk.ThreadPark";
+        type Value<'de> = ThreadPark<'de>;
+    }
+
+    #[derive(Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    pub struct GCConfiguration<'a> {
+        #[serde(borrow)]
+        pub young_collector: Option<GCName<'a>>,
+        #[serde(borrow)]
+        pub old_collector: Option<GCName<'a>>,
+        #[serde(rename = "parallelGCThreads", default)]
+        pub parallel_gc_threads: u32,
+        #[serde(rename = "concurrentGCThreads", default)]
+        pub concurrent_gc_threads: u32,
+        #[serde(rename = "usesDynamicGCThreads", default)]
+        pub uses_dynamic_gc_threads: bool,
+        #[serde(rename = "isExplicitGCConcurrent", default)]
+        pub is_explicit_gc_concurrent: bool,
+        #[serde(rename = "isExplicitGCDisabled", default)]
+        pub is_explicit_gc_disabled: bool,
+        /// The target pause time in milliseconds, or `i64::MIN` if not set
+        #[serde(default)]
+        pub pause_target: i64,
+        #[serde(default)]
+        pub gc_time_ratio: u32,
+    }
+
+    impl EventType for GCConfiguration<'_> {
+        const NAME: &'static str = "jdk.GCConfiguration";
+        type Value<'de> = GCConfiguration<'de>;
+    }
+
+    #[derive(Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    pub struct GCHeapConfiguration<'a> {
+        #[serde(default)]
+        pub min_size: u64,
+        #[serde(default)]
+        pub max_size: u64,
+        #[serde(default)]
+        pub initial_size: u64,
+        #[serde(default)]
+        pub uses_compressed_oops: bool,
+        #[serde(borrow)]
+        pub compressed_oops_mode: Option<NarrowOopMode<'a>>,
+        #[serde(default)]
+        pub object_alignment: u64,
+        #[serde(default)]
+        pub heap_address_bits: u8,
+    }
+
+    impl EventType for GCHeapConfiguration<'_> {
+        const NAME: &'static str = "jdk.GCHeapConfiguration";
+        type Value<'de> = GCHeapConfiguration<'de>;
+    }
+
+    #[derive(Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    pub struct YoungGenerationConfiguration {
+        #[serde(default)]
+        pub min_size: u64,
+        #[serde(default)]
+        pub max_size: u64,
+        #[serde(default)]
+        pub new_ratio: u32,
+    }
+
+    impl EventType for YoungGenerationConfiguration {
+        const NAME: &'static str = "jdk.YoungGenerationConfiguration";
+        type Value<'de> = YoungGenerationConfiguration;
+    }
+
+    /// Limits of the container. Negative values mean unlimited or unsupported
+    #[derive(Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    pub struct ContainerConfiguration<'a> {
+        /// e.g. `cgroupv1`, `cgroupv2`
+        #[serde(borrow)]
+        pub container_type: Option<&'a str>,
+        /// In microseconds
+        #[serde(default)]
+        pub cpu_slice_period: i64,
+        /// In microseconds per [`ContainerConfiguration::cpu_slice_period`]
+        #[serde(default)]
+        pub cpu_quota: i64,
+        #[serde(default)]
+        pub cpu_shares: i64,
+        #[serde(default)]
+        pub effective_cpu_count: i64,
+        #[serde(default)]
+        pub memory_soft_limit: i64,
+        #[serde(default)]
+        pub memory_limit: i64,
+        #[serde(default)]
+        pub swap_memory_limit: i64,
+    }
+
+    impl EventType for ContainerConfiguration<'_> {
+        const NAME: &'static str = "jdk.ContainerConfiguration";
+        type Value<'de> = ContainerConfiguration<'de>;
+    }
+
+    /// Accumulated CPU time of the container in nanoseconds
+    #[derive(Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    pub struct ContainerCPUUsage {
+        #[serde(default)]
+        pub cpu_time: i64,
+        #[serde(default)]
+        pub cpu_user_time: i64,
+        #[serde(default)]
+        pub cpu_system_time: i64,
+    }
+
+    impl EventType for ContainerCPUUsage {
+        const NAME: &'static str = "jdk.ContainerCPUUsage";
+        type Value<'de> = ContainerCPUUsage;
+    }
+
+    /// Memory usage of the container in bytes
+    #[derive(Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    pub struct ContainerMemoryUsage {
+        /// Number of times the memory usage hit the limit
+        #[serde(default)]
+        pub memory_fail_count: i64,
+        #[serde(default)]
+        pub memory_usage: i64,
+        #[serde(default)]
+        pub swap_memory_usage: i64,
+    }
+
+    impl EventType for ContainerMemoryUsage {
+        const NAME: &'static str = "jdk.ContainerMemoryUsage";
+        type Value<'de> = ContainerMemoryUsage;
+    }
+
+    /// Native memory usage of the NMT category in bytes
+    #[derive(Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    pub struct NativeMemoryUsage<'a> {
+        /// e.g. `Java Heap`, `Class`, `Thread`
+        #[serde(rename = "type", borrow)]
+        pub memory_type: Option<&'a str>,
+        #[serde(default)]
+        pub reserved: u64,
+        #[serde(default)]
+        pub committed: u64,
+    }
+
+    impl EventType for NativeMemoryUsage<'_> {
+        const NAME: &'static str = "jdk.NativeMemoryUsage";
+        type Value<'de> = NativeMemoryUsage<'de>;
+    }
+
+    /// Native memory usage of all NMT categories in bytes
+    #[derive(Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    pub struct NativeMemoryUsageTotal {
+        #[serde(default)]
+        pub reserved: u64,
+        #[serde(default)]
+        pub committed: u64,
+    }
+
+    impl EventType for NativeMemoryUsageTotal {
+        const NAME: &'static str = "jdk.NativeMemoryUsageTotal";
+        type Value<'de> = NativeMemoryUsageTotal;
+    }
+}
+
+/// Event types specific to [async-profiler](https://github.com/async-profiler/async-profiler)
+pub mod profiler {
+    use super::builtin::*;
+    use super::EventType;
+    use serde::Deserialize;
+
+    #[derive(Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    pub struct Log<'a> {
+        #[serde(borrow)]
+        pub level: Option<LogLevel<'a>>,
+        pub message: Option<&'a str>,
+    }
+
+    impl EventType for Log<'_> {
+        const NAME: &'static str = "profiler.Log";
+        type Value<'de> = Log<'de>;
+    }
+
+    #[derive(Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    pub struct LogLevel<'a> {
+        pub name: Option<&'a str>,
+    }
+
+    /// Object which was still alive at the end of the recording (`live` option, since 2.9)
+    #[derive(Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    pub struct LiveObject<'a> {
+        #[serde(borrow)]
+        pub event_thread: Option<JdkThread<'a>>,
+        #[serde(borrow)]
+        pub stack_trace: Option<StackTrace<'a>>,
+        #[serde(borrow)]
+        pub object_class: Option<Class<'a>>,
+        #[serde(default)]
+        pub allocation_size: i64,
+    }
+
+    impl EventType for LiveObject<'_> {
+        const NAME: &'static str = "profiler.LiveObject";
+        type Value<'de> = LiveObject<'de>;
+    }
 }
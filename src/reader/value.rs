@@ -0,0 +1,197 @@
+//! Owned and fully resolved representation of JFR values, like `serde_json::Value`.
+//! Unlike [`ValueDescriptor`], it doesn't refer to the chunk so it can be stored after the chunk is dropped.
+
+use crate::reader::event::Accessor;
+use crate::reader::value_descriptor::{Primitive, ValueDescriptor};
+use crate::reader::Chunk;
+use serde::ser::{SerializeMap, SerializeSeq};
+use serde::{Serialize, Serializer};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum JfrValue {
+    Null,
+    Bool(bool),
+    Int(i64),
+    Float(f64),
+    String(String),
+    Array(Vec<JfrValue>),
+    /// Fields in the declared order
+    Object(Vec<(String, JfrValue)>),
+}
+
+impl JfrValue {
+    /// Returns the field value if this is an object
+    pub fn get(&self, name: &str) -> Option<&JfrValue> {
+        match self {
+            JfrValue::Object(fields) => fields.iter().find(|(n, _)| n == name).map(|(_, v)| v),
+            _ => None,
+        }
+    }
+
+    pub fn is_null(&self) -> bool {
+        matches!(self, JfrValue::Null)
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            JfrValue::Bool(v) => Some(*v),
+            _ => None,
+        }
+    }
+
+    pub fn as_i64(&self) -> Option<i64> {
+        match self {
+            JfrValue::Int(v) => Some(*v),
+            _ => None,
+        }
+    }
+
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            JfrValue::Float(v) => Some(*v),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            JfrValue::String(v) => Some(v.as_str()),
+            _ => None,
+        }
+    }
+
+    pub fn as_array(&self) -> Option<&[JfrValue]> {
+        match self {
+            JfrValue::Array(v) => Some(v.as_slice()),
+            _ => None,
+        }
+    }
+
+    fn new(chunk: &Chunk, value: &ValueDescriptor) -> Self {
+        match value {
+            ValueDescriptor::Primitive(p) => match p {
+                Primitive::Integer(v) => JfrValue::Int(*v as i64),
+                Primitive::Long(v) => JfrValue::Int(*v),
+                Primitive::Float(v) => JfrValue::Float(*v as f64),
+                Primitive::Double(v) => JfrValue::Float(*v),
+                Primitive::Character(v) => JfrValue::String(v.to_string()),
+                Primitive::Boolean(v) => JfrValue::Bool(*v),
+                Primitive::Short(v) => JfrValue::Int(*v as i64),
+                Primitive::Byte(v) => JfrValue::Int(*v as i64),
+                Primitive::NullString => JfrValue::Null,
+                Primitive::String(_) | Primitive::CString(_) => match p.as_str() {
+                    Some(s) => JfrValue::String(s.to_string()),
+                    None => JfrValue::Null,
+                },
+                Primitive::RawString(s) => match s.decode() {
+                    Ok(s) => JfrValue::String(s.into_owned()),
+                    Err(_) => JfrValue::Null,
+                },
+            },
+            ValueDescriptor::Object(obj) => match chunk.metadata.type_pool.get(obj.class_id) {
+                Some(type_desc) => JfrValue::Object(
+                    type_desc
+                        .fields
+                        .iter()
+                        .zip(obj.fields.iter())
+                        .map(|(f, v)| (f.name().to_string(), Self::new(chunk, v)))
+                        .collect(),
+                ),
+                None => JfrValue::Null,
+            },
+            ValueDescriptor::Array(array) => {
+                JfrValue::Array(array.iter().map(|v| Self::new(chunk, v)).collect())
+            }
+            ValueDescriptor::ConstantPool {
+                class_id,
+                constant_index,
+            } => match chunk.constant_pool.get(class_id, constant_index) {
+                Some(v) => Self::new(chunk, v),
+                None => JfrValue::Null,
+            },
+        }
+    }
+}
+
+impl<'a> From<&Accessor<'a>> for JfrValue {
+    fn from(accessor: &Accessor<'a>) -> Self {
+        Self::new(accessor.chunk, accessor.value)
+    }
+}
+
+impl<'a> From<Accessor<'a>> for JfrValue {
+    fn from(accessor: Accessor<'a>) -> Self {
+        Self::from(&accessor)
+    }
+}
+
+impl Serialize for JfrValue {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match self {
+            JfrValue::Null => serializer.serialize_none(),
+            JfrValue::Bool(v) => serializer.serialize_bool(*v),
+            JfrValue::Int(v) => serializer.serialize_i64(*v),
+            JfrValue::Float(v) => serializer.serialize_f64(*v),
+            JfrValue::String(v) => serializer.serialize_str(v),
+            JfrValue::Array(array) => {
+                let mut seq = serializer.serialize_seq(Some(array.len()))?;
+                for v in array.iter() {
+                    seq.serialize_element(v)?;
+                }
+                seq.end()
+            }
+            JfrValue::Object(fields) => {
+                let mut map = serializer.serialize_map(Some(fields.len()))?;
+                for (k, v) in fields.iter() {
+                    map.serialize_entry(k, v)?;
+                }
+                map.end()
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::reader::JfrReader;
+    use crate::test_util::test_data;
+    use std::fs::File;
+
+    #[test]
+    fn test_from_accessor() {
+        let mut reader = JfrReader::new(File::open(test_data("profiler-wall.jfr")).unwrap());
+        let values = reader
+            .chunks()
+            .flatten()
+            .flat_map(|(chunk_reader, chunk)| {
+                chunk_reader
+                    .events(&chunk)
+                    .flatten()
+                    .filter(|e| e.class.name() == "jdk.ExecutionSample")
+                    .map(|e| JfrValue::from(e.value()))
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(values.len(), 8836);
+
+        let value = &values[0];
+        assert!(value
+            .get("sampledThread")
+            .and_then(|t| t.get("osName"))
+            .and_then(|n| n.as_str())
+            .is_some());
+        assert!(value.get("startTime").and_then(|t| t.as_i64()).is_some());
+        assert!(!value
+            .get("stackTrace")
+            .and_then(|s| s.get("frames"))
+            .and_then(|f| f.as_array())
+            .unwrap()
+            .is_empty());
+        assert!(value.get("noSuchField").is_none());
+        assert_eq!(value.clone(), *value);
+    }
+}
@@ -1,13 +1,16 @@
 //! Low-level representation of the decoded JFR values.
 
-use crate::reader::byte_stream::{ByteStream, StringType};
+use crate::reader::byte_stream::{ByteStream, Input, StringType};
+use crate::reader::limits::LimitKind;
 use crate::reader::metadata::Metadata;
+use crate::reader::raw_string::{RawString, StringEncoding};
 
 use crate::reader::type_descriptor::{FieldDescriptor, TypeDescriptor};
 use crate::reader::{Chunk, Error, Result};
-use std::io::Read;
+use std::fmt;
+use std::fmt::Formatter;
 
-#[derive(Debug)]
+#[derive(Debug, PartialEq)]
 pub enum ValueDescriptor {
     Primitive(Primitive),
     Object(Object),
@@ -16,7 +19,7 @@ pub enum ValueDescriptor {
 }
 
 impl ValueDescriptor {
-    pub fn try_new<T: Read>(
+    pub fn try_new<T: Input>(
         stream: &mut ByteStream<T>,
         class_id: i64,
         metadata: &Metadata,
@@ -37,14 +40,19 @@ impl ValueDescriptor {
 
         for field_desc in type_desc.fields.iter() {
             let value = if field_desc.array_type {
-                let count = stream.read_i32()? as usize;
+                let count = stream
+                    .read_length(LimitKind::ArrayLength)
+                    .map_err(|e| e.in_context(|| field_desc.name().to_string()))?;
                 let mut elems = Vec::with_capacity(count);
-                for _ in 0..count {
-                    elems.push(Self::try_read_field_single(stream, field_desc, metadata)?);
+                for i in 0..count {
+                    let elem = Self::try_read_field_single(stream, field_desc, metadata)
+                        .map_err(|e| e.in_context(|| format!("{}[{}]", field_desc.name(), i)))?;
+                    elems.push(elem);
                 }
                 ValueDescriptor::Array(elems)
             } else {
-                Self::try_read_field_single(stream, field_desc, metadata)?
+                Self::try_read_field_single(stream, field_desc, metadata)
+                    .map_err(|e| e.in_context(|| field_desc.name().to_string()))?
             };
             obj.fields.push(value);
         }
@@ -52,6 +60,75 @@ impl ValueDescriptor {
         Ok(ValueDescriptor::Object(obj))
     }
 
+    /// Reads the value into `self` in the same way as [`ValueDescriptor::try_new`].
+    ///
+    /// If `self` is an object of the same class, allocations of the fields and the arrays are reused,
+    /// so reading values of the same type repeatedly into the same scratch value doesn't allocate
+    /// except for strings.
+    pub fn read_into<T: Input>(
+        &mut self,
+        stream: &mut ByteStream<T>,
+        class_id: i64,
+        metadata: &Metadata,
+    ) -> Result<()> {
+        let type_desc = metadata
+            .type_pool
+            .get(class_id)
+            .ok_or(Error::ClassNotFound(class_id))?;
+
+        if let Some(value) = Self::try_read_primitive(stream, type_desc)? {
+            *self = value;
+            return Ok(());
+        }
+
+        let obj = match self {
+            ValueDescriptor::Object(o) if o.class_id == class_id => o,
+            _ => {
+                *self = Self::try_new(stream, class_id, metadata)?;
+                return Ok(());
+            }
+        };
+        for (i, field_desc) in type_desc.fields.iter().enumerate() {
+            if i == obj.fields.len() {
+                obj.fields.push(ValueDescriptor::Array(vec![]));
+            }
+            let value = &mut obj.fields[i];
+            let in_field = |e: Error| e.in_context(|| field_desc.name().to_string());
+            if field_desc.array_type {
+                let count = stream
+                    .read_length(LimitKind::ArrayLength)
+                    .map_err(in_field)?;
+                if !matches!(value, ValueDescriptor::Array(_)) {
+                    *value = ValueDescriptor::Array(Vec::with_capacity(count));
+                }
+                if let ValueDescriptor::Array(elems) = value {
+                    elems.truncate(count);
+                    for j in 0..count {
+                        let result = if j < elems.len() {
+                            Self::read_field_single_into(
+                                &mut elems[j],
+                                stream,
+                                field_desc,
+                                metadata,
+                            )
+                        } else {
+                            Self::try_read_field_single(stream, field_desc, metadata)
+                                .map(|elem| elems.push(elem))
+                        };
+                        result.map_err(|e| {
+                            e.in_context(|| format!("{}[{}]", field_desc.name(), j))
+                        })?;
+                    }
+                }
+            } else {
+                Self::read_field_single_into(value, stream, field_desc, metadata)
+                    .map_err(in_field)?;
+            }
+        }
+        obj.fields.truncate(type_desc.fields.len());
+        Ok(())
+    }
+
     pub fn get_field<'a>(&'a self, name: &str, chunk: &'a Chunk) -> Option<&'a ValueDescriptor> {
         self.inner_get_field(name, chunk, true)
     }
@@ -110,7 +187,7 @@ impl ValueDescriptor {
         }
     }
 
-    fn try_read_field_single<T: Read>(
+    fn try_read_field_single<T: Input>(
         stream: &mut ByteStream<T>,
         field_desc: &FieldDescriptor,
         metadata: &Metadata,
@@ -125,7 +202,24 @@ impl ValueDescriptor {
         }
     }
 
-    fn try_read_primitive<T: Read>(
+    fn read_field_single_into<T: Input>(
+        value: &mut ValueDescriptor,
+        stream: &mut ByteStream<T>,
+        field_desc: &FieldDescriptor,
+        metadata: &Metadata,
+    ) -> Result<()> {
+        if field_desc.constant_pool {
+            *value = ValueDescriptor::ConstantPool {
+                class_id: field_desc.class_id,
+                constant_index: stream.read_i64()?,
+            };
+            Ok(())
+        } else {
+            value.read_into(stream, field_desc.class_id, metadata)
+        }
+    }
+
+    fn try_read_primitive<T: Input>(
         stream: &mut ByteStream<T>,
         type_desc: &TypeDescriptor,
     ) -> Result<Option<ValueDescriptor>> {
@@ -142,19 +236,9 @@ impl ValueDescriptor {
             "double" => Some(ValueDescriptor::Primitive(Primitive::Double(
                 stream.read_f64()?,
             ))),
-            "char" => {
-                let c = stream.read_char()?;
-                Some(ValueDescriptor::Primitive(Primitive::Character(
-                    #[cfg(feature = "cstring")]
-                    CString {
-                        string: std::ffi::CString::new(c.to_string())
-                            .expect("Failed to create CString"),
-                        len: 1,
-                    },
-                    #[cfg(not(feature = "cstring"))]
-                    c,
-                )))
-            }
+            "char" => Some(ValueDescriptor::Primitive(Primitive::Character(
+                stream.read_char()?,
+            ))),
             "boolean" => Some(ValueDescriptor::Primitive(Primitive::Boolean(
                 stream.read_i8()? != 0,
             ))),
@@ -164,6 +248,19 @@ impl ValueDescriptor {
             "byte" => Some(ValueDescriptor::Primitive(Primitive::Byte(
                 stream.read_i8()?,
             ))),
+            "java.lang.String" if stream.string_repr() == StringRepr::Raw => {
+                match stream.read_raw_string()? {
+                    StringType::Null => Some(ValueDescriptor::Primitive(Primitive::NullString)),
+                    StringType::Empty => Some(ValueDescriptor::Primitive(Primitive::from_string(
+                        String::new(),
+                    ))),
+                    StringType::Raw(s) => Some(ValueDescriptor::Primitive(Primitive::RawString(s))),
+                    StringType::ConstantPool(idx) => Some(ValueDescriptor::ConstantPool {
+                        class_id: type_desc.class_id,
+                        constant_index: idx,
+                    }),
+                }
+            }
             "java.lang.String" => match stream.read_string()? {
                 StringType::Null => Some(ValueDescriptor::Primitive(Primitive::NullString)),
                 s @ (StringType::Empty | StringType::Raw(_)) => {
@@ -172,17 +269,10 @@ impl ValueDescriptor {
                     } else {
                         "".to_string()
                     };
-                    #[allow(unused_variables)]
-                    let len = s.len();
-                    Some(ValueDescriptor::Primitive(Primitive::String(
-                        #[cfg(feature = "cstring")]
-                        CString {
-                            string: std::ffi::CString::new(s).expect("Failed to create CString"),
-                            len,
-                        },
-                        #[cfg(not(feature = "cstring"))]
+                    Some(ValueDescriptor::Primitive(Primitive::from_string_as(
                         s,
-                    )))
+                        stream.string_repr(),
+                    )?))
                 }
                 StringType::ConstantPool(idx) => Some(ValueDescriptor::ConstantPool {
                     class_id: type_desc.class_id,
@@ -195,39 +285,153 @@ impl ValueDescriptor {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, PartialEq)]
 pub struct Object {
     pub class_id: i64,
     pub fields: Vec<ValueDescriptor>,
 }
 
-#[cfg(feature = "cstring")]
-#[derive(Debug)]
+#[derive(Debug, PartialEq)]
 pub struct CString {
     pub string: std::ffi::CString,
+    /// The length in bytes without the terminating NUL
     pub len: usize,
 }
 
-#[derive(Debug)]
+/// How string values are decoded into [`Primitive`], set by [`crate::reader::JfrReader::string_repr`]
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum StringRepr {
+    /// [`Primitive::String`]
+    String,
+    /// [`Primitive::CString`], which can be passed to C without copying.
+    /// Strings containing NUL fail with [`Error::InvalidString`].
+    CString,
+    /// [`Primitive::RawString`] in the encoding of the recording
+    Raw,
+}
+
+impl Default for StringRepr {
+    /// [`StringRepr::CString`] if the `cstring` feature is enabled, [`StringRepr::String`] otherwise
+    fn default() -> Self {
+        if cfg!(feature = "cstring") {
+            StringRepr::CString
+        } else {
+            StringRepr::String
+        }
+    }
+}
+
+/// Primitive values. The variant of strings depends on [`StringRepr`],
+/// and all of them can be read as `&str` through `TryFrom`.
+#[derive(Debug, PartialEq)]
 pub enum Primitive {
     Integer(i32),
     Long(i64),
     Float(f32),
     Double(f64),
-    // Rust's char can't be mapped to C in natural way
-    // so we just encode it as string
-    #[cfg(feature = "cstring")]
-    Character(CString),
-    #[cfg(not(feature = "cstring"))]
     Character(char),
     Boolean(bool),
     Short(i16),
     Byte(i8),
     NullString,
-    #[cfg(feature = "cstring")]
-    String(CString),
-    #[cfg(not(feature = "cstring"))]
     String(String),
+    CString(CString),
+    /// The string in the encoding of the recording
+    RawString(RawString),
+}
+
+impl Primitive {
+    pub(crate) fn from_string(s: String) -> Self {
+        Primitive::String(s)
+    }
+
+    /// Returns the string value in the representation. [`StringRepr::Raw`] is treated as UTF-8.
+    pub(crate) fn from_string_as(s: String, repr: StringRepr) -> Result<Self> {
+        match repr {
+            StringRepr::String => Ok(Primitive::String(s)),
+            StringRepr::CString => {
+                let len = s.len();
+                let string = std::ffi::CString::new(s).map_err(|_| Error::InvalidString)?;
+                Ok(Primitive::CString(CString { string, len }))
+            }
+            StringRepr::Raw => Ok(Primitive::RawString(RawString {
+                encoding: StringEncoding::Utf8,
+                bytes: s.into_bytes(),
+            })),
+        }
+    }
+
+    pub(crate) fn from_char(c: char) -> Self {
+        Primitive::Character(c)
+    }
+
+    pub(crate) fn as_str(&self) -> Option<&str> {
+        match self {
+            Primitive::String(s) => Some(s.as_str()),
+            Primitive::CString(s) => s.string.to_str().ok(),
+            Primitive::RawString(s) => s.as_str(),
+            _ => None,
+        }
+    }
+}
+
+/// Formats the values without the chunk, so objects are shown by the field values in the declared order
+/// and constant pool references are not resolved.
+/// Use the `Display` of [`crate::reader::event::Accessor`] for the field names and the units.
+impl fmt::Display for ValueDescriptor {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            ValueDescriptor::Primitive(p) => write!(f, "{}", p),
+            ValueDescriptor::Object(o) => write!(f, "{}", o),
+            ValueDescriptor::Array(values) => {
+                write!(f, "[")?;
+                for (i, v) in values.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}", v)?;
+                }
+                write!(f, "]")
+            }
+            ValueDescriptor::ConstantPool {
+                class_id,
+                constant_index,
+            } => write!(f, "@{}:{}", class_id, constant_index),
+        }
+    }
+}
+
+impl fmt::Display for Object {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "#{} {{", self.class_id)?;
+        for (i, v) in self.fields.iter().enumerate() {
+            write!(f, "{}{}", if i > 0 { ", " } else { " " }, v)?;
+        }
+        write!(f, " }}")
+    }
+}
+
+/// Strings and chars are quoted
+impl fmt::Display for Primitive {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Primitive::Integer(v) => write!(f, "{}", v),
+            Primitive::Long(v) => write!(f, "{}", v),
+            Primitive::Float(v) => write!(f, "{}", v),
+            Primitive::Double(v) => write!(f, "{}", v),
+            Primitive::Character(v) => write!(f, "{:?}", v),
+            Primitive::Boolean(v) => write!(f, "{}", v),
+            Primitive::Short(v) => write!(f, "{}", v),
+            Primitive::Byte(v) => write!(f, "{}", v),
+            Primitive::NullString => write!(f, "null"),
+            Primitive::String(v) => write!(f, "{:?}", v),
+            Primitive::CString(v) => write!(f, "{:?}", v.string.to_string_lossy()),
+            Primitive::RawString(v) => match v.decode() {
+                Ok(s) => write!(f, "{:?}", s),
+                Err(_) => write!(f, "{:?}", v.bytes),
+            },
+        }
+    }
 }
 
 #[macro_use]
@@ -259,7 +463,6 @@ impl_try_from_primitive!(Integer, i32);
 impl_try_from_primitive!(Long, i64);
 impl_try_from_primitive!(Float, f32);
 impl_try_from_primitive!(Double, f64);
-#[cfg(not(feature = "cstring"))]
 impl_try_from_primitive!(Character, char);
 impl_try_from_primitive!(Boolean, bool);
 impl_try_from_primitive!(Short, i16);
@@ -268,12 +471,33 @@ impl_try_from_primitive!(Byte, i8);
 impl<'a> TryFrom<&'a ValueDescriptor> for &'a str {
     type Error = ();
 
+    /// Raw strings are converted only if they are valid UTF-8 as they are (see [`RawString::as_str`])
+    fn try_from(value: &'a ValueDescriptor) -> std::result::Result<Self, Self::Error> {
+        match value {
+            ValueDescriptor::Primitive(p) => p.as_str().ok_or(()),
+            _ => Err(()),
+        }
+    }
+}
+
+impl<'a> TryFrom<&'a ValueDescriptor> for &'a std::ffi::CStr {
+    type Error = ();
+
+    fn try_from(value: &'a ValueDescriptor) -> std::result::Result<Self, Self::Error> {
+        if let ValueDescriptor::Primitive(Primitive::CString(s)) = value {
+            Ok(s.string.as_c_str())
+        } else {
+            Err(())
+        }
+    }
+}
+
+impl<'a> TryFrom<&'a ValueDescriptor> for &'a RawString {
+    type Error = ();
+
     fn try_from(value: &'a ValueDescriptor) -> std::result::Result<Self, Self::Error> {
-        if let ValueDescriptor::Primitive(Primitive::String(s)) = value {
-            #[cfg(feature = "cstring")]
-            return s.string.as_c_str().to_str().map_err(|_| ());
-            #[cfg(not(feature = "cstring"))]
-            return Ok(s.as_str());
+        if let ValueDescriptor::Primitive(Primitive::RawString(s)) = value {
+            Ok(s)
         } else {
             Err(())
         }
@@ -0,0 +1,77 @@
+//! Helpers shared by the unit tests
+
+use crate::reader::value_descriptor::StringRepr;
+use crate::reader::JfrReader;
+use crate::writer::recording::{FieldDeclaration, JfrWriter, TypeDeclaration};
+use serde::Serialize;
+use std::fs::File;
+use std::io::Cursor;
+use std::path::PathBuf;
+
+/// Returns the path of the file in `test-data`
+pub(crate) fn test_data(file_name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("test-data")
+        .join(file_name)
+}
+
+/// Opens the file in `test-data`, e.g. to check that an analysis finds nothing in a recording
+/// which doesn't have the events
+pub(crate) fn open_test_data(file_name: &str) -> JfrReader<File> {
+    JfrReader::new(File::open(test_data(file_name)).unwrap())
+}
+
+/// Recording written in memory by [`JfrWriter`], to test with the events the test data doesn't have.
+/// Event timestamps are epoch nanos since the writer's ticks are nanoseconds.
+pub(crate) struct TestRecording {
+    writer: JfrWriter<Vec<u8>>,
+}
+
+impl TestRecording {
+    pub(crate) fn new<I: IntoIterator<Item = TypeDeclaration>>(declarations: I) -> Self {
+        let mut writer = JfrWriter::new(vec![]);
+        for declaration in declarations {
+            writer.declare_type(declaration).unwrap();
+        }
+        Self { writer }
+    }
+
+    pub(crate) fn constant<T: Serialize>(
+        &mut self,
+        type_name: &str,
+        constant_index: i64,
+        value: &T,
+    ) -> &mut Self {
+        self.writer
+            .add_constant(type_name, constant_index, value)
+            .unwrap();
+        self
+    }
+
+    pub(crate) fn event<T: Serialize>(&mut self, type_name: &str, value: &T) -> &mut Self {
+        self.writer.write_event(type_name, value).unwrap();
+        self
+    }
+
+    /// Finishes the recording and reads it with strings as [`StringRepr::String`]
+    pub(crate) fn reader(self) -> JfrReader<Cursor<Vec<u8>>> {
+        JfrReader::from_bytes(self.writer.finish().unwrap()).string_repr(StringRepr::String)
+    }
+}
+
+pub(crate) fn long(name: &str) -> FieldDeclaration {
+    FieldDeclaration::new(name, "long")
+}
+
+pub(crate) fn int(name: &str) -> FieldDeclaration {
+    FieldDeclaration::new(name, "int")
+}
+
+pub(crate) fn string(name: &str) -> FieldDeclaration {
+    FieldDeclaration::new(name, "java.lang.String")
+}
+
+/// Field referring to the constant pool of the type
+pub(crate) fn constant(name: &str, type_name: &str) -> FieldDeclaration {
+    FieldDeclaration::new(name, type_name).constant_pool(true)
+}
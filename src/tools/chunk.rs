@@ -0,0 +1,257 @@
+//! Chunk-level manipulation of JFR files.
+//!
+//! Chunks are self-contained so they can be copied as is.
+//! Only the final-chunk flag in the header is fixed up so that it's set on the last chunk of each output.
+//!
+//! Related JDK code: [Assemble.java](https://github.com/openjdk/jdk/blob/jdk-17%2B35/src/jdk.jfr/share/classes/jdk/jfr/internal/tool/Assemble.java)
+
+use crate::reader::{ChunkHeader, Error, Result};
+use crate::MAGIC;
+use std::io;
+use std::io::{Read, Write};
+
+/// Concatenates the chunks of `inputs` in order and writes them into `output`.
+///
+/// Chunks larger than `max_chunk_size` fail with [`Error::ChunkTooLarge`] (see [`crate::reader::JfrReader::max_chunk_size`]).
+pub fn assemble<I, R, W>(inputs: I, mut output: W, max_chunk_size: Option<u64>) -> Result<()>
+where
+    I: IntoIterator<Item = R>,
+    R: Read,
+    W: Write,
+{
+    let mut pending: Option<Vec<u8>> = None;
+    for input in inputs {
+        let mut chunks = RawChunks::new(input, max_chunk_size);
+        while let Some(chunk) = chunks.next_chunk()? {
+            if let Some(prev) = pending.replace(chunk) {
+                write_chunk(&mut output, prev, false)?;
+            }
+        }
+    }
+    if let Some(last) = pending {
+        write_chunk(&mut output, last, true)?;
+    }
+    output.flush().map_err(Error::IoError)
+}
+
+/// Splits `input` into multiple files so that each file contains at most `max_chunk_count` chunks.
+///
+/// `create_output` is called with the 0-based index of the file to create the output.
+/// Returns the number of created files.
+/// Chunks larger than `max_chunk_size` fail with [`Error::ChunkTooLarge`] as [`assemble`].
+///
+/// # Panics
+/// Panics if `max_chunk_count` is 0.
+pub fn split<R, W, F>(
+    input: R,
+    max_chunk_count: usize,
+    max_chunk_size: Option<u64>,
+    mut create_output: F,
+) -> Result<usize>
+where
+    R: Read,
+    W: Write,
+    F: FnMut(usize) -> io::Result<W>,
+{
+    assert!(max_chunk_count > 0, "max_chunk_count must be positive");
+
+    let mut chunks = RawChunks::new(input, max_chunk_size);
+    let mut file_count = 0;
+    let mut next = chunks.next_chunk()?;
+    while next.is_some() {
+        let mut output = create_output(file_count).map_err(Error::IoError)?;
+        file_count += 1;
+
+        let mut count = 0;
+        while let Some(chunk) = next {
+            count += 1;
+            next = chunks.next_chunk()?;
+            let last = count == max_chunk_count || next.is_none();
+            write_chunk(&mut output, chunk, last)?;
+            if last {
+                break;
+            }
+        }
+        output.flush().map_err(Error::IoError)?;
+    }
+    Ok(file_count)
+}
+
+fn write_chunk<W: Write>(output: &mut W, mut chunk: Vec<u8>, final_chunk: bool) -> Result<()> {
+    let offset = ChunkHeader::FEATURES_OFFSET;
+    let mut features = i32::from_be_bytes([
+        chunk[offset],
+        chunk[offset + 1],
+        chunk[offset + 2],
+        chunk[offset + 3],
+    ]);
+    if final_chunk {
+        features |= ChunkHeader::FEATURES_FINAL_CHUNK;
+    } else {
+        features &= !ChunkHeader::FEATURES_FINAL_CHUNK;
+    }
+    chunk[offset..offset + 4].copy_from_slice(&features.to_be_bytes());
+
+    output.write_all(&chunk).map_err(Error::IoError)
+}
+
+/// Reads whole bytes of chunks sequentially without interpreting the contents
+struct RawChunks<R> {
+    inner: R,
+    position: u64,
+    max_chunk_size: Option<u64>,
+}
+
+impl<R: Read> RawChunks<R> {
+    fn new(inner: R, max_chunk_size: Option<u64>) -> Self {
+        Self {
+            inner,
+            position: 0,
+            max_chunk_size,
+        }
+    }
+
+    fn next_chunk(&mut self) -> Result<Option<Vec<u8>>> {
+        // magic + version + chunk_size
+        let mut head = [0u8; 16];
+        let read = self.read_fully(&mut head)?;
+        if read == 0 {
+            return Ok(None);
+        }
+        if read < head.len() {
+            return Err(self.truncated_error());
+        }
+        if head[..4] != MAGIC {
+            return Err(Error::InvalidFormat);
+        }
+
+        let chunk_size = i64::from_be_bytes(head[8..16].try_into().unwrap());
+        if chunk_size < ChunkHeader::HEADER_SIZE as i64 {
+            return Err(Error::InvalidFormat);
+        }
+        let chunk_size = chunk_size as u64;
+        if let Some(limit) = self.max_chunk_size {
+            if chunk_size > limit {
+                return Err(Error::ChunkTooLarge {
+                    size: chunk_size,
+                    limit,
+                });
+            }
+        }
+
+        // the buffer grows by the bytes actually read rather than the size in the header
+        let mut chunk = head.to_vec();
+        (&mut self.inner)
+            .take(chunk_size - head.len() as u64)
+            .read_to_end(&mut chunk)
+            .map_err(Error::IoError)?;
+        if (chunk.len() as u64) < chunk_size {
+            return Err(self.truncated_error());
+        }
+
+        self.position += chunk_size;
+        Ok(Some(chunk))
+    }
+
+    fn read_fully(&mut self, buf: &mut [u8]) -> Result<usize> {
+        let mut read = 0;
+        while read < buf.len() {
+            match self.inner.read(&mut buf[read..]) {
+                Ok(0) => break,
+                Ok(n) => read += n,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(Error::IoError(e)),
+            }
+        }
+        Ok(read)
+    }
+
+    fn truncated_error(&self) -> Error {
+        Error::TruncatedChunk {
+            valid_bytes: self.position,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::reader::JfrReader;
+    use crate::test_util::test_data;
+    use std::fs::File;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_assemble() {
+        let inputs = vec![
+            File::open(test_data("profiler-wall.jfr")).unwrap(),
+            File::open(test_data("profiler-multichunk.jfr")).unwrap(),
+        ];
+        let mut output = vec![];
+        assemble(inputs, &mut output, None).unwrap();
+
+        let mut reader = JfrReader::new(Cursor::new(output));
+        let headers = reader
+            .chunk_metadata()
+            .map(|c| c.unwrap().1.header)
+            .collect::<Vec<_>>();
+        assert_eq!(headers.len(), 4);
+        assert_eq!(headers[0].chunk_size, 146798);
+        for (i, header) in headers.iter().enumerate() {
+            assert_eq!(header.is_final_chunk(), i == headers.len() - 1);
+        }
+    }
+
+    #[test]
+    fn test_split() {
+        let input = File::open(test_data("profiler-multichunk.jfr")).unwrap();
+        let output_path = |i: usize| {
+            std::env::temp_dir().join(format!("jfrs-test-split-{}-{}.jfr", std::process::id(), i))
+        };
+        let count = split(input, 2, None, |i| File::create(output_path(i))).unwrap();
+        assert_eq!(count, 2);
+
+        let chunk_counts = (0..count)
+            .map(|i| {
+                let file = File::open(output_path(i)).unwrap();
+                let chunks = JfrReader::new(file).chunks().flatten().count();
+                std::fs::remove_file(output_path(i)).unwrap();
+                chunks
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(chunk_counts, vec![2, 1]);
+    }
+
+    #[test]
+    fn test_invalid_chunk_size() {
+        let bytes = std::fs::read(test_data("recording.jfr")).unwrap();
+
+        let mut output = vec![];
+        let result = assemble(vec![Cursor::new(&bytes)], &mut output, Some(1024));
+        assert!(matches!(
+            result,
+            Err(Error::ChunkTooLarge { limit: 1024, .. })
+        ));
+
+        let mut negative = bytes.clone();
+        negative[8..16].copy_from_slice(&(-1i64).to_be_bytes());
+        let result = assemble(vec![Cursor::new(&negative)], &mut output, None);
+        assert!(matches!(result, Err(Error::InvalidFormat)));
+
+        // the size in the header is larger than the input
+        let mut huge = bytes;
+        huge[8..16].copy_from_slice(&i64::MAX.to_be_bytes());
+        let result = assemble(vec![Cursor::new(&huge)], &mut output, None);
+        assert!(matches!(
+            result,
+            Err(Error::TruncatedChunk { valid_bytes: 0 })
+        ));
+    }
+
+    #[test]
+    #[should_panic(expected = "max_chunk_count must be positive")]
+    fn test_split_zero_chunk_count() {
+        let input = File::open(test_data("recording.jfr")).unwrap();
+        let _ = split(input, 0, None, |_| io::Result::Ok(io::sink()));
+    }
+}
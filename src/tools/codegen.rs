@@ -0,0 +1,350 @@
+//! Generate Rust structs from the type metadata of a recording.
+//! The generated structs derive `serde::Deserialize` so they can be read by [`crate::reader::de::from_event`],
+//! which is useful to access vendor-specific events without writing structs by hand.
+
+use crate::reader::metadata::Metadata;
+use crate::reader::type_descriptor::{FieldDescriptor, TypeDescriptor, TypePool};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::fmt::Write;
+
+const RUST_KEYWORDS: &[&str] = &[
+    "as", "async", "await", "break", "const", "continue", "crate", "dyn", "else", "enum", "extern",
+    "false", "fn", "for", "if", "impl", "in", "let", "loop", "match", "mod", "move", "mut", "pub",
+    "ref", "return", "static", "struct", "super", "trait", "true", "type", "unsafe", "use",
+    "where", "while", "abstract", "become", "box", "do", "final", "macro", "override", "priv",
+    "typeof", "unsized", "virtual", "yield", "try",
+];
+
+/// Generates the struct definitions for all event types declared in the metadata,
+/// and the types referred by them.
+pub fn generate(metadata: &Metadata) -> String {
+    Generator::new(&metadata.type_pool).generate()
+}
+
+struct Generator<'a> {
+    type_pool: &'a TypePool,
+    // types to generate, keyed by the class id
+    types: BTreeMap<i64, &'a TypeDescriptor>,
+    struct_names: HashMap<i64, String>,
+    borrowed: HashMap<i64, bool>,
+}
+
+impl<'a> Generator<'a> {
+    fn new(type_pool: &'a TypePool) -> Self {
+        let mut types = BTreeMap::new();
+        let mut stack = type_pool.event_types().collect::<Vec<_>>();
+        while let Some(t) = stack.pop() {
+            if is_primitive(t) || types.insert(t.class_id, t).is_some() {
+                continue;
+            }
+            for field in t.fields.iter() {
+                if let Some(field_type) = type_pool.get(field.class_id) {
+                    stack.push(field_type);
+                }
+            }
+        }
+
+        let struct_names = Self::struct_names(&types);
+        let mut generator = Self {
+            type_pool,
+            types,
+            struct_names,
+            borrowed: HashMap::new(),
+        };
+        let class_ids = generator.types.keys().copied().collect::<Vec<_>>();
+        for class_id in class_ids {
+            generator.compute_borrowed(class_id, &mut HashSet::new());
+        }
+        generator
+    }
+
+    /// Uses the simple name (e.g. `ExecutionSample` for `jdk.ExecutionSample`) if it's unique,
+    /// otherwise the full name in camel case (e.g. `JavaLangThread`)
+    fn struct_names(types: &BTreeMap<i64, &TypeDescriptor>) -> HashMap<i64, String> {
+        let mut simple_names: HashMap<String, usize> = HashMap::new();
+        for t in types.values() {
+            *simple_names.entry(simple_name(t.name())).or_default() += 1;
+        }
+        types
+            .values()
+            .map(|t| {
+                let simple = simple_name(t.name());
+                let name = if simple_names[&simple] > 1 {
+                    t.name().split('.').map(camel_case).collect()
+                } else {
+                    simple
+                };
+                (t.class_id, name)
+            })
+            .collect()
+    }
+
+    /// Whether the struct has borrowed strings, i.e. requires a lifetime parameter
+    fn compute_borrowed(&mut self, class_id: i64, visiting: &mut HashSet<i64>) -> bool {
+        if let Some(&b) = self.borrowed.get(&class_id) {
+            return b;
+        }
+        let t = match self.types.get(&class_id) {
+            Some(t) => *t,
+            None => return false,
+        };
+        if !visiting.insert(class_id) {
+            return false;
+        }
+        let mut borrowed = false;
+        for field in t.fields.iter() {
+            let field_borrowed = match self.type_pool.get(field.class_id) {
+                Some(ft) if ft.name() == "java.lang.String" => true,
+                Some(ft) if is_primitive(ft) => false,
+                Some(ft) => self.compute_borrowed(ft.class_id, visiting),
+                None => false,
+            };
+            borrowed |= field_borrowed;
+        }
+        visiting.remove(&class_id);
+        self.borrowed.insert(class_id, borrowed);
+        borrowed
+    }
+
+    fn reaches(&self, from: i64, to: i64) -> bool {
+        let mut visited = HashSet::new();
+        let mut stack = vec![from];
+        while let Some(class_id) = stack.pop() {
+            if class_id == to {
+                return true;
+            }
+            if !visited.insert(class_id) {
+                continue;
+            }
+            if let Some(t) = self.types.get(&class_id) {
+                stack.extend(t.fields.iter().map(|f| f.class_id));
+            }
+        }
+        false
+    }
+
+    fn generate(&self) -> String {
+        let mut out = String::new();
+        writeln!(out, "// Generated by jfrs. Do not edit.").unwrap();
+        writeln!(out).unwrap();
+        writeln!(out, "use serde::Deserialize;").unwrap();
+
+        let mut sorted = self
+            .types
+            .values()
+            .map(|t| (self.struct_names[&t.class_id].as_str(), *t))
+            .collect::<Vec<_>>();
+        sorted.sort_by_key(|(name, _)| *name);
+        for (name, t) in sorted {
+            writeln!(out).unwrap();
+            self.write_struct(&mut out, name, t);
+        }
+        out
+    }
+
+    fn write_struct(&self, out: &mut String, name: &str, t: &TypeDescriptor) {
+        write_doc(out, "", t.label(), t.description());
+        writeln!(out, "/// Type: `{}`", t.name()).unwrap();
+        writeln!(out, "#[derive(Debug, Deserialize)]").unwrap();
+        writeln!(out, "pub struct {}{} {{", name, self.lifetime(t.class_id)).unwrap();
+
+        let mut field_names = BTreeSet::new();
+        for field in t.fields.iter() {
+            let mut field_name = snake_case(field.name());
+            while !field_names.insert(field_name.clone()) {
+                field_name.push('_');
+            }
+            self.write_field(out, t, field, &field_name);
+        }
+        writeln!(out, "}}").unwrap();
+    }
+
+    fn write_field(
+        &self,
+        out: &mut String,
+        owner: &TypeDescriptor,
+        field: &FieldDescriptor,
+        name: &str,
+    ) {
+        let field_type = self.type_pool.get(field.class_id);
+        let (mut ty, borrowed, primitive) = match field_type {
+            Some(ft) if ft.name() == "java.lang.String" => {
+                ("Option<&'a str>".to_string(), true, false)
+            }
+            Some(ft) if is_primitive(ft) => (primitive_type(ft, field).to_string(), false, true),
+            Some(ft) => {
+                let mut ty = format!(
+                    "{}{}",
+                    self.struct_names[&ft.class_id],
+                    self.lifetime(ft.class_id)
+                );
+                if !field.array_type && self.reaches(ft.class_id, owner.class_id) {
+                    ty = format!("Box<{}>", ty);
+                }
+                (
+                    format!("Option<{}>", ty),
+                    self.borrowed[&ft.class_id],
+                    false,
+                )
+            }
+            // the type is not declared, so just skip the value
+            None => ("serde::de::IgnoredAny".to_string(), false, true),
+        };
+        if field.array_type {
+            ty = format!("Vec<{}>", ty);
+        }
+
+        let mut attributes = vec![];
+        let rust_name = if RUST_KEYWORDS.contains(&name) {
+            format!("r#{}", name)
+        } else {
+            name.to_string()
+        };
+        if name != field.name() {
+            attributes.push(format!("rename = \"{}\"", field.name()));
+        }
+        // &str in Option or Vec requires explicit borrow
+        if borrowed && (field.array_type || !primitive) {
+            attributes.push("borrow".to_string());
+        }
+        attributes.push("default".to_string());
+
+        write_doc(out, "    ", field.label(), field.description());
+        writeln!(out, "    #[serde({})]", attributes.join(", ")).unwrap();
+        writeln!(out, "    pub {}: {},", rust_name, ty).unwrap();
+    }
+
+    fn lifetime(&self, class_id: i64) -> &'static str {
+        if self.borrowed.get(&class_id).copied().unwrap_or(false) {
+            "<'a>"
+        } else {
+            ""
+        }
+    }
+}
+
+fn is_primitive(t: &TypeDescriptor) -> bool {
+    matches!(
+        t.name(),
+        "int"
+            | "long"
+            | "float"
+            | "double"
+            | "char"
+            | "boolean"
+            | "short"
+            | "byte"
+            | "java.lang.String"
+    )
+}
+
+fn primitive_type(t: &TypeDescriptor, field: &FieldDescriptor) -> &'static str {
+    match (t.name(), field.unsigned) {
+        ("int", false) => "i32",
+        ("int", true) => "u32",
+        ("long", false) => "i64",
+        ("long", true) => "u64",
+        ("short", false) => "i16",
+        ("short", true) => "u16",
+        ("byte", false) => "i8",
+        ("byte", true) => "u8",
+        ("float", _) => "f32",
+        ("double", _) => "f64",
+        ("char", _) => "char",
+        ("boolean", _) => "bool",
+        _ => unreachable!(),
+    }
+}
+
+fn write_doc(out: &mut String, indent: &str, label: Option<&str>, description: Option<&str>) {
+    for doc in [label, description].into_iter().flatten() {
+        for line in doc.lines() {
+            writeln!(out, "{}/// {}", indent, line.trim()).unwrap();
+        }
+    }
+}
+
+fn simple_name(type_name: &str) -> String {
+    camel_case(type_name.rsplit('.').next().unwrap_or(type_name))
+}
+
+fn camel_case(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    let mut upper = true;
+    for c in s.chars() {
+        if !c.is_ascii_alphanumeric() {
+            upper = true;
+            continue;
+        }
+        if upper {
+            out.push(c.to_ascii_uppercase());
+            upper = false;
+        } else {
+            out.push(c);
+        }
+    }
+    if out.starts_with(|c: char| c.is_ascii_digit()) {
+        out.insert(0, '_');
+    }
+    out
+}
+
+fn snake_case(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 4);
+    let mut prev_lower = false;
+    for c in s.chars() {
+        if !c.is_ascii_alphanumeric() {
+            out.push('_');
+            prev_lower = false;
+        } else if c.is_ascii_uppercase() {
+            if prev_lower {
+                out.push('_');
+            }
+            out.push(c.to_ascii_lowercase());
+            prev_lower = false;
+        } else {
+            out.push(c);
+            prev_lower = true;
+        }
+    }
+    if out.starts_with(|c: char| c.is_ascii_digit()) || out.is_empty() {
+        out.insert(0, '_');
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::reader::JfrReader;
+    use crate::test_util::test_data;
+    use std::fs::File;
+
+    #[test]
+    fn test_generate() {
+        let mut reader = JfrReader::new(File::open(test_data("recording.jfr")).unwrap());
+        let (_, chunk) = reader.chunk_metadata().next().unwrap().unwrap();
+        let code = generate(&chunk.metadata);
+
+        assert!(code.contains("/// Type: `jdk.ThreadPark`\n#[derive(Debug, Deserialize)]\npub struct ThreadPark<'a> {"));
+        assert!(code
+            .contains("    #[serde(rename = \"startTime\", default)]\n    pub start_time: i64,"));
+        assert!(code.contains("    #[serde(rename = \"eventThread\", borrow, default)]\n    pub event_thread: Option<Thread<'a>>,"));
+        // recursive type
+        assert!(code.contains("    pub parent: Option<Box<ThreadGroup<'a>>>,"));
+        // unsigned
+        assert!(code.contains("pub struct UnsignedLongFlag<'a> {"));
+        assert!(code.contains("    pub value: u64,"));
+        // keyword
+        assert!(code.contains("    pub r#type: Option<"));
+    }
+
+    #[test]
+    fn test_names() {
+        assert_eq!(simple_name("jdk.types.StackTrace"), "StackTrace");
+        assert_eq!(camel_case("java"), "Java");
+        assert_eq!(snake_case("osName"), "os_name");
+        assert_eq!(snake_case("startTLAB"), "start_tlab");
+        assert_eq!(snake_case("gcId"), "gc_id");
+    }
+}
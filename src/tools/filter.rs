@@ -0,0 +1,111 @@
+//! Copy JFR files keeping only the selected events.
+//! This is useful to shrink huge recordings before sharing.
+
+use crate::reader::event::Event;
+use crate::reader::Result;
+use crate::tools::rewrite::rewrite;
+use std::io::{Read, Seek, Write};
+
+/// Copies `input` into `output` keeping only the events of the given types.
+///
+/// Constant pools are regenerated so that only the entries referenced from the remaining events are kept.
+pub fn filter_event_types<R, W>(input: R, output: W, event_types: &[&str]) -> Result<()>
+where
+    R: Read + Seek,
+    W: Write,
+{
+    filter_events(input, output, |e| event_types.contains(&e.class.name()))
+}
+
+/// Copies `input` into `output` keeping only the events which `keep` returns true.
+pub fn filter_events<R, W, F>(input: R, output: W, keep: F) -> Result<()>
+where
+    R: Read + Seek,
+    W: Write,
+    F: FnMut(&Event) -> bool,
+{
+    rewrite(input, output, keep)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::reader::de::from_event;
+    use crate::reader::types::jdk::ExecutionSample;
+    use crate::reader::{Chunk, JfrReader};
+    use crate::test_util::test_data;
+    use std::fs::File;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_filter_event_types() {
+        let input = File::open(test_data("profiler-wall.jfr")).unwrap();
+        let mut output = vec![];
+        filter_event_types(input, &mut output, &["jdk.ExecutionSample"]).unwrap();
+        assert!(
+            (output.len() as u64)
+                < File::open(test_data("profiler-wall.jfr"))
+                    .unwrap()
+                    .metadata()
+                    .unwrap()
+                    .len()
+        );
+
+        let mut reader = JfrReader::new(Cursor::new(output));
+        let mut count = 0;
+        for (reader, chunk) in reader.chunks().flatten() {
+            for event in reader.events(&chunk) {
+                let event = event.unwrap();
+                assert_eq!(event.class.name(), "jdk.ExecutionSample");
+                let sample: ExecutionSample = from_event(&event).unwrap();
+                assert!(sample.stack_trace.is_some());
+                count += 1;
+            }
+        }
+        assert_eq!(count, 8836);
+    }
+
+    #[test]
+    fn test_filter_events_recording() {
+        let input = File::open(test_data("recording.jfr")).unwrap();
+        let mut output = vec![];
+        filter_events(input, &mut output, |e| e.class.name() == "jdk.ThreadPark").unwrap();
+
+        let mut reader = JfrReader::new(Cursor::new(output));
+        let (reader, chunk) = reader.chunks().next().unwrap().unwrap();
+        let names = reader
+            .events(&chunk)
+            .map(|e| {
+                let event = e.unwrap();
+                event
+                    .value()
+                    .get_field("eventThread")
+                    .and_then(|t| t.get_field("javaName"))
+                    .and_then(|n| n.as_str())
+                    .map(|n| n.to_string())
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(names.len(), 237);
+        assert!(names.iter().all(|n| n.is_some()));
+        // java.lang.Class entries which are not referenced by ThreadPark are dropped
+        let class_count = |chunk: &Chunk| {
+            let class_id = chunk
+                .metadata
+                .type_pool
+                .get_by_name("java.lang.Class")
+                .unwrap()
+                .class_id;
+            chunk
+                .constant_pool
+                .inner
+                .keys()
+                .filter(|k| k.class_id == class_id)
+                .count()
+        };
+        let mut original = JfrReader::new(File::open(test_data("recording.jfr")).unwrap());
+        let (_, original_chunk) = original.chunks().next().unwrap().unwrap();
+        let count = class_count(&chunk);
+        assert!(0 < count);
+        assert!(count < class_count(&original_chunk));
+    }
+}
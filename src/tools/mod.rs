@@ -0,0 +1,14 @@
+//! Tools to manipulate JFR files without JVM.
+
+pub mod chunk;
+pub mod codegen;
+pub mod filter;
+mod rewrite;
+pub mod scrub;
+pub mod trim;
+
+pub use chunk::{assemble, split};
+pub use codegen::generate;
+pub use filter::{filter_event_types, filter_events};
+pub use scrub::Scrubber;
+pub use trim::trim;
@@ -0,0 +1,218 @@
+//! Rewrite chunks with the subset of events.
+//!
+//! Metadata is copied as is, and events are copied byte-by-byte unless they are modified.
+//! Constant pool is regenerated so that only the entries referenced from the remaining events are kept.
+
+use crate::reader::byte_stream::ByteStream;
+use crate::reader::constant_pool::ConstantPoolKey;
+use crate::reader::event::Event;
+use crate::reader::metadata::Metadata;
+use crate::reader::value_descriptor::ValueDescriptor;
+use crate::reader::{Chunk, ChunkHeader, Error, JfrReader, Result};
+use crate::writer::byte_writer::ByteWriter;
+use crate::writer::constant_pool::write_constant_pool_event;
+use crate::writer::value;
+use std::collections::BTreeSet;
+use std::io::{Cursor, Read, Seek, Write};
+
+/// Offsets of the header fields from the beginning of the chunk
+const CHUNK_SIZE_OFFSET: usize = 8;
+const CONSTANT_POOL_OFFSET_OFFSET: usize = 16;
+const METADATA_OFFSET_OFFSET: usize = 24;
+
+/// Decides which chunks and events to keep, and how to modify the values
+pub(crate) trait Rewrite {
+    /// Returns false to drop the whole chunk
+    fn keep_chunk(&mut self, _chunk: &Chunk) -> bool {
+        true
+    }
+
+    fn keep_event(&mut self, event: &Event) -> bool;
+
+    /// Modifies the value of a kept event or a referenced constant.
+    /// Returns true if the value is modified.
+    fn rewrite_value(&mut self, _value: &mut ValueDescriptor, _metadata: &Metadata) -> bool {
+        false
+    }
+}
+
+/// A piece of the chunk body
+enum EventBytes {
+    /// The range of the original chunk bytes
+    Original(usize, usize),
+    Encoded(Vec<u8>),
+}
+
+impl<F: FnMut(&Event) -> bool> Rewrite for F {
+    fn keep_event(&mut self, event: &Event) -> bool {
+        self(event)
+    }
+}
+
+pub(crate) fn rewrite<R, W, X>(input: R, mut output: W, mut rewriter: X) -> Result<()>
+where
+    R: Read + Seek,
+    W: Write,
+    X: Rewrite,
+{
+    let mut reader = JfrReader::new(input);
+    for res in reader.chunks() {
+        let (chunk_reader, mut chunk) = res?;
+        if !rewriter.keep_chunk(&chunk) {
+            continue;
+        }
+
+        let mut events = vec![];
+        let mut constants = BTreeSet::new();
+        for event in chunk_reader.events(&chunk) {
+            let mut event = event?;
+            if !rewriter.keep_event(&event) {
+                continue;
+            }
+            if rewriter.rewrite_value(&mut event.value, &chunk.metadata) {
+                events.push(EventBytes::Encoded(encode_event(&event)?));
+            } else {
+                let start = chunk.header.body_start_offset() + event.byte_offset;
+                events.push(EventBytes::Original(
+                    start as usize,
+                    (start + event.byte_size) as usize,
+                ));
+            }
+            collect_constants(&event.value, &chunk, &mut constants);
+        }
+
+        for key in constants.iter() {
+            if let Some(value) = chunk.constant_pool.get_mut(key) {
+                rewriter.rewrite_value(value, &chunk.metadata);
+            }
+        }
+
+        let bytes = write_chunk(chunk_reader.bytes(), &chunk, &events, &constants)?;
+        output.write_all(&bytes).map_err(Error::IoError)?;
+    }
+    output.flush().map_err(Error::IoError)
+}
+
+/// Collects constant pool entries referenced from the value recursively
+fn collect_constants(
+    value: &ValueDescriptor,
+    chunk: &Chunk,
+    constants: &mut BTreeSet<ConstantPoolKey>,
+) {
+    match value {
+        ValueDescriptor::Primitive(_) => {}
+        ValueDescriptor::Object(obj) => {
+            for field in obj.fields.iter() {
+                collect_constants(field, chunk, constants);
+            }
+        }
+        ValueDescriptor::Array(elems) => {
+            for elem in elems.iter() {
+                collect_constants(elem, chunk, constants);
+            }
+        }
+        ValueDescriptor::ConstantPool {
+            class_id,
+            constant_index,
+        } => {
+            let key = ConstantPoolKey {
+                class_id: *class_id,
+                constant_index: *constant_index,
+            };
+            if constants.insert(key) {
+                if let Some(v) = chunk.constant_pool.get(class_id, constant_index) {
+                    collect_constants(v, chunk, constants);
+                }
+            }
+        }
+    }
+}
+
+fn encode_event(event: &Event) -> Result<Vec<u8>> {
+    value::encode_event(
+        &event.value,
+        event.class.class_id,
+        &event.chunk.metadata,
+        event.chunk.header.int_encoding(),
+    )
+}
+
+fn write_chunk(
+    src: &[u8],
+    chunk: &Chunk,
+    events: &[EventBytes],
+    constants: &BTreeSet<ConstantPoolKey>,
+) -> Result<Vec<u8>> {
+    let header_size = ChunkHeader::HEADER_SIZE as usize;
+    let mut writer = ByteWriter::new(chunk.header.int_encoding());
+    writer.write_bytes(src.get(..header_size).ok_or(Error::InvalidFormat)?);
+
+    let metadata_offset = writer.len();
+    writer.write_bytes(metadata_event(src, &chunk.header)?);
+
+    for event in events {
+        match event {
+            EventBytes::Original(start, end) => {
+                writer.write_bytes(src.get(*start..*end).ok_or(Error::InvalidFormat)?)
+            }
+            EventBytes::Encoded(bytes) => writer.write_bytes(bytes),
+        }
+    }
+
+    let constant_pool_offset = writer.len();
+    write_constant_pool_event(
+        &mut writer,
+        constants.iter().filter_map(|key| {
+            chunk
+                .constant_pool
+                .get(&key.class_id, &key.constant_index)
+                .map(|value| (*key, value))
+        }),
+        chunk.header.start_ticks,
+        &chunk.metadata,
+    )?;
+
+    let mut bytes = writer.into_inner();
+    let chunk_size = bytes.len();
+    for (offset, value) in [
+        (CHUNK_SIZE_OFFSET, chunk_size),
+        (CONSTANT_POOL_OFFSET_OFFSET, constant_pool_offset),
+        (METADATA_OFFSET_OFFSET, metadata_offset),
+    ] {
+        bytes[offset..offset + 8].copy_from_slice(&(value as i64).to_be_bytes());
+    }
+    Ok(bytes)
+}
+
+fn metadata_event<'a>(src: &'a [u8], header: &ChunkHeader) -> Result<&'a [u8]> {
+    let offset = header.metadata_offset as usize;
+    let mut stream = ByteStream::new(Cursor::new(src));
+    stream.set_int_encoding(header.int_encoding());
+    stream.seek(offset as u64)?;
+    let size = usize::try_from(stream.read_i32()?).map_err(|_| Error::InvalidFormat)?;
+    let end = offset.checked_add(size).ok_or(Error::InvalidFormat)?;
+
+    src.get(offset..end).ok_or(Error::InvalidFormat)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::open_test_data;
+
+    #[test]
+    fn test_corrupted_metadata_size() {
+        let mut reader = open_test_data("recording.jfr");
+        let (chunk_reader, chunk) = reader.chunks().next().unwrap().unwrap();
+        let mut bytes = chunk_reader.into_bytes();
+        assert!(metadata_event(&bytes, &chunk.header).is_ok());
+
+        // -1 in the compressed encoding
+        let offset = chunk.header.metadata_offset as usize;
+        bytes[offset..offset + 5].copy_from_slice(&[0xff, 0xff, 0xff, 0xff, 0x0f]);
+        assert!(matches!(
+            metadata_event(&bytes, &chunk.header),
+            Err(Error::InvalidFormat)
+        ));
+    }
+}
@@ -0,0 +1,240 @@
+//! Anonymize JFR files by rewriting strings.
+//! This is useful to share recordings outside the company without leaking
+//! thread names, system properties, file paths, usernames and so on.
+//!
+//! Both the strings in events and in constant pools are rewritten, while the structure of the file is kept.
+
+use crate::reader::event::Event;
+use crate::reader::metadata::Metadata;
+use crate::reader::value_descriptor::{Primitive, ValueDescriptor};
+use crate::reader::{Error, Result};
+use crate::tools::rewrite::{rewrite, Rewrite};
+use std::io::{Read, Seek, Write};
+
+const DEFAULT_PLACEHOLDER: &str = "[REDACTED]";
+
+type Predicate = Box<dyn Fn(&str) -> bool>;
+
+/// Rewrites strings in a JFR file by the configured rules
+///
+/// ```no_run
+/// use jfrs::tools::scrub::Scrubber;
+/// use std::fs::File;
+///
+/// Scrubber::new()
+///     .replace("alice", "user")
+///     .redact_field("jdk.InitialSystemProperty", "value")
+///     .redact_if(|s| s.contains("secret"))
+///     .scrub(
+///         File::open("/path/to/recording.jfr").unwrap(),
+///         File::create("/path/to/scrubbed.jfr").unwrap(),
+///     )
+///     .unwrap();
+/// ```
+pub struct Scrubber {
+    placeholder: String,
+    replacements: Vec<(String, String)>,
+    predicates: Vec<Predicate>,
+    fields: Vec<(String, String)>,
+}
+
+impl Default for Scrubber {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Scrubber {
+    pub fn new() -> Self {
+        Self {
+            placeholder: DEFAULT_PLACEHOLDER.to_string(),
+            replacements: vec![],
+            predicates: vec![],
+            fields: vec![],
+        }
+    }
+
+    /// Sets the string to replace redacted strings with. Defaults to `[REDACTED]`.
+    /// Must not contain NUL. See [`Scrubber::scrub`]
+    pub fn placeholder(mut self, placeholder: &str) -> Self {
+        self.placeholder = placeholder.to_string();
+        self
+    }
+
+    /// Replaces all occurrences of `from` in strings with `to`
+    pub fn replace(mut self, from: &str, to: &str) -> Self {
+        self.replacements.push((from.to_string(), to.to_string()));
+        self
+    }
+
+    /// Replaces whole strings which `predicate` returns true with the placeholder
+    pub fn redact_if<F>(mut self, predicate: F) -> Self
+    where
+        F: Fn(&str) -> bool + 'static,
+    {
+        self.predicates.push(Box::new(predicate));
+        self
+    }
+
+    /// Replaces the string field of the type (either event type or constant type like `java.lang.Thread`)
+    /// with the placeholder
+    pub fn redact_field(mut self, type_name: &str, field_name: &str) -> Self {
+        self.fields
+            .push((type_name.to_string(), field_name.to_string()));
+        self
+    }
+
+    /// Copies `input` into `output` rewriting strings
+    ///
+    /// Fails with [`Error::InvalidString`] if the placeholder contains NUL,
+    /// since the scrubbed recording couldn't be read as
+    /// [`crate::reader::value_descriptor::StringRepr::CString`].
+    pub fn scrub<R, W>(&self, input: R, output: W) -> Result<()>
+    where
+        R: Read + Seek,
+        W: Write,
+    {
+        if self.placeholder.contains('\0') {
+            return Err(Error::InvalidString);
+        }
+        rewrite(input, output, ScrubRewrite { scrubber: self })
+    }
+
+    fn scrub_value(&self, value: &mut ValueDescriptor, metadata: &Metadata) -> bool {
+        match value {
+            ValueDescriptor::Primitive(p) => self.scrub_string(p),
+            ValueDescriptor::Object(obj) => {
+                let type_desc = match metadata.type_pool.get(obj.class_id) {
+                    Some(t) => t,
+                    None => return false,
+                };
+                let mut modified = false;
+                for (field_desc, field) in type_desc.fields.iter().zip(obj.fields.iter_mut()) {
+                    let redact = self.fields.iter().any(|(t, f)| {
+                        t.as_str() == type_desc.name() && f.as_str() == field_desc.name()
+                    });
+                    if redact && Self::is_string(field, metadata) {
+                        *field = ValueDescriptor::Primitive(Primitive::from_string(
+                            self.placeholder.clone(),
+                        ));
+                        modified = true;
+                    } else {
+                        modified |= self.scrub_value(field, metadata);
+                    }
+                }
+                modified
+            }
+            ValueDescriptor::Array(elems) => {
+                let mut modified = false;
+                for elem in elems.iter_mut() {
+                    modified |= self.scrub_value(elem, metadata);
+                }
+                modified
+            }
+            // constants are scrubbed separately
+            ValueDescriptor::ConstantPool { .. } => false,
+        }
+    }
+
+    fn scrub_string(&self, value: &mut Primitive) -> bool {
+        let s = match value.as_str() {
+            Some(s) => s,
+            None => return false,
+        };
+        let scrubbed = if self.predicates.iter().any(|p| p(s)) {
+            self.placeholder.clone()
+        } else {
+            self.replacements
+                .iter()
+                .fold(s.to_string(), |acc, (from, to)| acc.replace(from, to))
+        };
+        if scrubbed == s {
+            return false;
+        }
+        *value = Primitive::from_string(scrubbed);
+        true
+    }
+
+    fn is_string(value: &ValueDescriptor, metadata: &Metadata) -> bool {
+        match value {
+            ValueDescriptor::Primitive(p) => p.as_str().is_some(),
+            ValueDescriptor::ConstantPool { class_id, .. } => metadata
+                .type_pool
+                .get(*class_id)
+                .map(|t| t.name() == "java.lang.String")
+                .unwrap_or(false),
+            _ => false,
+        }
+    }
+}
+
+struct ScrubRewrite<'a> {
+    scrubber: &'a Scrubber,
+}
+
+impl<'a> Rewrite for ScrubRewrite<'a> {
+    fn keep_event(&mut self, _event: &Event) -> bool {
+        true
+    }
+
+    fn rewrite_value(&mut self, value: &mut ValueDescriptor, metadata: &Metadata) -> bool {
+        self.scrubber.scrub_value(value, metadata)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::reader::JfrReader;
+    use crate::test_util::test_data;
+    use std::fs::File;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_scrub() {
+        let input = File::open(test_data("recording.jfr")).unwrap();
+        let mut output = vec![];
+        Scrubber::new()
+            .replace("hokada", "user")
+            .redact_field("jdk.InitialSystemProperty", "value")
+            .scrub(input, &mut output)
+            .unwrap();
+        assert!(!output.windows(6).any(|w| w == b"hokada"));
+
+        let mut reader = JfrReader::new(Cursor::new(output));
+        let mut event_count = 0;
+        let mut property_count = 0;
+        for (reader, chunk) in reader.chunks().flatten() {
+            for event in reader.events(&chunk) {
+                let event = event.unwrap();
+                event_count += 1;
+                if event.class.name() == "jdk.InitialSystemProperty" {
+                    let value = event
+                        .value()
+                        .get_field("value")
+                        .and_then(|v| v.as_str())
+                        .unwrap();
+                    assert_eq!(value, DEFAULT_PLACEHOLDER);
+                    property_count += 1;
+                }
+            }
+        }
+        assert_eq!(event_count, 4065);
+        assert_eq!(property_count, 15);
+    }
+
+    #[test]
+    fn test_placeholder() {
+        let scrubber = Scrubber::new().placeholder("***");
+        assert_eq!(scrubber.placeholder, "***");
+        let input = File::open(test_data("recording.jfr")).unwrap();
+        let mut output = vec![];
+        assert!(matches!(
+            Scrubber::new()
+                .placeholder("nul\0")
+                .scrub(input, &mut output),
+            Err(Error::InvalidString)
+        ));
+        assert!(output.is_empty());
+    }
+}
@@ -0,0 +1,100 @@
+//! Extract the time window of JFR files into a new file.
+//! This is useful to attach only the incident window to a ticket.
+
+use crate::reader::event::Event;
+use crate::reader::{Chunk, Result};
+use crate::tools::rewrite::{rewrite, Rewrite};
+use std::io::{Read, Seek, Write};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Copies the events which started within `[start, end)` from `input` into `output`.
+///
+/// Chunks which don't overlap with the window are dropped entirely.
+/// Events without `startTime` field are kept as long as the chunk overlaps with the window.
+pub fn trim<R, W>(input: R, output: W, start: SystemTime, end: SystemTime) -> Result<()>
+where
+    R: Read + Seek,
+    W: Write,
+{
+    rewrite(
+        input,
+        output,
+        TimeWindow {
+            start_nanos: epoch_nanos(start),
+            end_nanos: epoch_nanos(end),
+        },
+    )
+}
+
+struct TimeWindow {
+    start_nanos: i64,
+    end_nanos: i64,
+}
+
+impl Rewrite for TimeWindow {
+    fn keep_chunk(&mut self, chunk: &Chunk) -> bool {
+        chunk.header.start_time_nanos < self.end_nanos
+            && chunk.header.end_time_nanos() >= self.start_nanos
+    }
+
+    fn keep_event(&mut self, event: &Event) -> bool {
+        match event
+            .value
+            .get_field_raw("startTime", event.chunk)
+            .and_then(|v| i64::try_from(v).ok())
+        {
+            Some(ticks) => {
+                let nanos = event.chunk.header.ticks_to_epoch_nanos(ticks);
+                self.start_nanos <= nanos && nanos < self.end_nanos
+            }
+            None => true,
+        }
+    }
+}
+
+fn epoch_nanos(time: SystemTime) -> i64 {
+    match time.duration_since(UNIX_EPOCH) {
+        Ok(d) => d.as_nanos() as i64,
+        Err(e) => -(e.duration().as_nanos() as i64),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::reader::JfrReader;
+    use crate::test_util::test_data;
+    use std::fs::File;
+    use std::io::Cursor;
+    use std::time::Duration;
+
+    #[test]
+    fn test_trim() {
+        // the second chunk starts at this time
+        let chunk_start = UNIX_EPOCH + Duration::from_nanos(1661595228226873000);
+        let start = chunk_start + Duration::from_secs(1);
+        let end = chunk_start + Duration::from_secs(2);
+
+        let input = File::open(test_data("profiler-multichunk.jfr")).unwrap();
+        let mut output = vec![];
+        trim(input, &mut output, start, end).unwrap();
+
+        let mut reader = JfrReader::new(Cursor::new(output));
+        let chunks = reader.chunks().flatten().collect::<Vec<_>>();
+        assert_eq!(chunks.len(), 1);
+
+        let (reader, chunk) = chunks.into_iter().next().unwrap();
+        let mut count = 0;
+        for event in reader.events(&chunk).flatten() {
+            let ticks = event
+                .value()
+                .get_field("startTime")
+                .and_then(|v| v.as_i64())
+                .unwrap();
+            let nanos = chunk.header.ticks_to_epoch_nanos(ticks);
+            assert!(epoch_nanos(start) <= nanos && nanos < epoch_nanos(end));
+            count += 1;
+        }
+        assert!(count > 0);
+    }
+}
@@ -0,0 +1,201 @@
+//! Provides functionality to write primitives as JFR byte stream.
+//! This is the counterpart of [`crate::reader::byte_stream`].
+
+use crate::decode::{
+    IntEncoding, STRING_ENCODING_CONSTANT_POOL, STRING_ENCODING_EMPTY_STRING, STRING_ENCODING_NULL,
+    STRING_ENCODING_UTF8_BYTE_ARRAY,
+};
+use crate::reader::{Error, Result};
+
+pub struct ByteWriter {
+    buf: Vec<u8>,
+    int_encoding: IntEncoding,
+}
+
+impl ByteWriter {
+    pub fn new(int_encoding: IntEncoding) -> Self {
+        Self {
+            buf: vec![],
+            int_encoding,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.buf.len()
+    }
+
+    pub fn into_inner(self) -> Vec<u8> {
+        self.buf
+    }
+
+    pub fn write_bytes(&mut self, bytes: &[u8]) {
+        self.buf.extend_from_slice(bytes);
+    }
+
+    pub fn write_i8(&mut self, v: i8) {
+        self.buf.push(v as u8);
+    }
+
+    pub fn write_i16(&mut self, v: i16) {
+        match self.int_encoding {
+            IntEncoding::Raw => self.write_bytes(&v.to_be_bytes()),
+            IntEncoding::Compressed => self.write_var_u64(v as u16 as u64),
+        }
+    }
+
+    pub fn write_i32(&mut self, v: i32) {
+        match self.int_encoding {
+            IntEncoding::Raw => self.write_bytes(&v.to_be_bytes()),
+            IntEncoding::Compressed => self.write_var_u64(v as u32 as u64),
+        }
+    }
+
+    pub fn write_i64(&mut self, v: i64) {
+        match self.int_encoding {
+            IntEncoding::Raw => self.write_bytes(&v.to_be_bytes()),
+            IntEncoding::Compressed => self.write_var_u64(v as u64),
+        }
+    }
+
+    pub fn write_char(&mut self, c: char) {
+        match self.int_encoding {
+            IntEncoding::Raw => self.write_i16(c as u32 as i16),
+            IntEncoding::Compressed => self.write_var_u64(c as u64),
+        }
+    }
+
+    pub fn write_f32(&mut self, v: f32) {
+        self.write_bytes(&v.to_be_bytes());
+    }
+
+    pub fn write_f64(&mut self, v: f64) {
+        self.write_bytes(&v.to_be_bytes());
+    }
+
+    pub fn write_string(&mut self, s: &str) {
+        if s.is_empty() {
+            self.write_i8(STRING_ENCODING_EMPTY_STRING);
+        } else {
+            self.write_i8(STRING_ENCODING_UTF8_BYTE_ARRAY);
+            self.write_i32(s.len() as i32);
+            self.write_bytes(s.as_bytes());
+        }
+    }
+
+    pub fn write_null_string(&mut self) {
+        self.write_i8(STRING_ENCODING_NULL);
+    }
+
+    pub fn write_constant_pool_string(&mut self, constant_index: i64) {
+        self.write_i8(STRING_ENCODING_CONSTANT_POOL);
+        self.write_i64(constant_index);
+    }
+
+    /// Writes i32 which always occupies 4 bytes regardless of the encoding,
+    /// so that it can be overwritten later by [`Self::set_padded_i32`].
+    /// This is used for the size field of events.
+    pub fn write_padded_i32(&mut self, v: i32) -> Result<()> {
+        let position = self.buf.len();
+        self.write_bytes(&[0; 4]);
+        self.set_padded_i32(position, v)
+    }
+
+    /// Overwrites i32 written by [`Self::write_padded_i32`].
+    /// Fails if the value doesn't fit in 4 bytes, i.e. negative or 2^28 or larger
+    /// in the compressed encoding, which has 7 bits per byte.
+    pub fn set_padded_i32(&mut self, position: usize, v: i32) -> Result<()> {
+        let bytes = match self.int_encoding {
+            IntEncoding::Raw => v.to_be_bytes(),
+            IntEncoding::Compressed => {
+                if !(0..1 << 28).contains(&v) {
+                    return Err(Error::SerializeError(format!(
+                        "{} doesn't fit in the padded compressed integer",
+                        v
+                    )));
+                }
+                let v = v as u32;
+                [
+                    (v & 0x7f) as u8 | 0x80,
+                    ((v >> 7) & 0x7f) as u8 | 0x80,
+                    ((v >> 14) & 0x7f) as u8 | 0x80,
+                    ((v >> 21) & 0x7f) as u8,
+                ]
+            }
+        };
+        self.buf[position..position + 4].copy_from_slice(&bytes);
+        Ok(())
+    }
+
+    fn write_var_u64(&mut self, mut v: u64) {
+        for _ in 0..8 {
+            if v < 0x80 {
+                self.buf.push(v as u8);
+                return;
+            }
+            self.buf.push((v & 0x7f) as u8 | 0x80);
+            v >>= 7;
+        }
+        self.buf.push(v as u8);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::reader::byte_stream::{ByteStream, StringType};
+    use std::io::Cursor;
+
+    #[test]
+    fn test_write_i64_compressed() {
+        let mut w = ByteWriter::new(IntEncoding::Compressed);
+        w.write_i64(55301);
+        assert_eq!(w.into_inner(), vec![0x85u8, 0xb0, 0x3]);
+    }
+
+    #[test]
+    fn test_round_trip() {
+        for encoding in [IntEncoding::Raw, IntEncoding::Compressed] {
+            let mut w = ByteWriter::new(encoding);
+            w.write_padded_i32(42).unwrap();
+            w.write_i16(-2);
+            w.write_i32(i32::MIN);
+            w.write_i64(-1);
+            w.write_i64(i64::MAX);
+            w.write_char('あ');
+            w.write_string("hello,world");
+            w.write_constant_pool_string(55301);
+            w.write_null_string();
+
+            let mut s = ByteStream::new(Cursor::new(w.into_inner()));
+            s.set_int_encoding(encoding);
+            assert_eq!(42, s.read_i32().unwrap());
+            assert_eq!(-2, s.read_i16().unwrap());
+            assert_eq!(i32::MIN, s.read_i32().unwrap());
+            assert_eq!(-1, s.read_i64().unwrap());
+            assert_eq!(i64::MAX, s.read_i64().unwrap());
+            assert_eq!('あ', s.read_char().unwrap());
+            assert_eq!(
+                StringType::Raw("hello,world".to_string()),
+                s.read_string().unwrap()
+            );
+            assert_eq!(StringType::ConstantPool(55301), s.read_string().unwrap());
+            assert_eq!(StringType::Null, s.read_string().unwrap());
+        }
+    }
+
+    #[test]
+    fn test_padded_i32_out_of_range() {
+        let mut w = ByteWriter::new(IntEncoding::Compressed);
+        w.write_padded_i32((1 << 28) - 1).unwrap();
+        assert!(w.set_padded_i32(0, 1 << 28).is_err());
+        assert!(w.set_padded_i32(0, -1).is_err());
+
+        let mut s = ByteStream::new(Cursor::new(w.into_inner()));
+        s.set_int_encoding(IntEncoding::Compressed);
+        assert_eq!((1 << 28) - 1, s.read_i32().unwrap());
+
+        let mut w = ByteWriter::new(IntEncoding::Raw);
+        w.write_padded_i32(i32::MAX).unwrap();
+        w.set_padded_i32(0, -1).unwrap();
+    }
+}
@@ -0,0 +1,58 @@
+//! Encode the constant pool event.
+//! This is the counterpart of [`crate::reader::constant_pool`].
+
+use crate::reader::constant_pool::ConstantPoolKey;
+use crate::reader::metadata::Metadata;
+use crate::reader::value_descriptor::ValueDescriptor;
+use crate::reader::Result;
+use crate::writer::byte_writer::ByteWriter;
+use crate::writer::value::write_value;
+use crate::EVENT_TYPE_CONSTANT_POOL;
+
+/// Writes the constants as single constant pool event.
+/// The constants must be sorted by the class id, e.g. by iterating `BTreeSet<ConstantPoolKey>`.
+pub(crate) fn write_constant_pool_event<'a, I>(
+    writer: &mut ByteWriter,
+    constants: I,
+    start_ticks: i64,
+    metadata: &Metadata,
+) -> Result<()>
+where
+    I: IntoIterator<Item = (ConstantPoolKey, &'a ValueDescriptor)>,
+{
+    let mut pools: Vec<(i64, Vec<(i64, &ValueDescriptor)>)> = vec![];
+    for (key, value) in constants {
+        match pools.last_mut() {
+            // constants of the same class are consecutive
+            Some((class_id, entries)) if *class_id == key.class_id => {
+                entries.push((key.constant_index, value))
+            }
+            _ => pools.push((key.class_id, vec![(key.constant_index, value)])),
+        }
+    }
+
+    let start = writer.len();
+    // size
+    writer.write_padded_i32(0)?;
+    writer.write_i64(EVENT_TYPE_CONSTANT_POOL);
+    writer.write_i64(start_ticks);
+    // duration
+    writer.write_i64(0);
+    // delta to the next constant pool event. 0 means this is the last one
+    writer.write_i64(0);
+    // flush
+    writer.write_i8(0);
+    writer.write_i32(pools.len() as i32);
+    for (class_id, entries) in pools {
+        writer.write_i64(class_id);
+        writer.write_i32(entries.len() as i32);
+        for (constant_index, value) in entries {
+            writer.write_i64(constant_index);
+            write_value(writer, value, class_id, metadata)?;
+        }
+    }
+    let size = writer.len() - start;
+    writer.set_padded_i32(start, size as i32)?;
+
+    Ok(())
+}
@@ -0,0 +1,774 @@
+//! Generates syntactically valid chunks (metadata, constant pools and events) from seeds,
+//! as the infrastructure of property tests and fuzzing of the parser.
+//!
+//! The type declarations, the constants and the events are all random, and the generator
+//! returns the values it encoded, so that the parsed values can be checked against them.
+//!
+//! ```
+//! use jfrs::reader::value_descriptor::StringRepr;
+//! use jfrs::reader::JfrReader;
+//! use jfrs::writer::generator::ChunkGenerator;
+//!
+//! let generated = ChunkGenerator::new(42).generate();
+//! let mut reader = JfrReader::from_bytes(&generated.bytes).string_repr(StringRepr::String);
+//! let (chunk_reader, chunk) = reader.chunks().next().unwrap().unwrap();
+//! let values = chunk_reader
+//!     .events(&chunk)
+//!     .map(|e| e.unwrap().into_value())
+//!     .collect::<Vec<_>>();
+//! assert!(values.iter().eq(generated.events.iter().map(|e| &e.value)));
+//! ```
+//!
+//! For coverage-guided fuzzers, derive the seed from the input bytes,
+//! and mutate [`GeneratedChunk::bytes`] from there.
+
+use crate::decode::{
+    IntEncoding, STRING_ENCODING_CHAR_ARRAY, STRING_ENCODING_EMPTY_STRING,
+    STRING_ENCODING_LATIN1_BYTE_ARRAY,
+};
+use crate::reader::value_descriptor::{Object, Primitive, ValueDescriptor};
+use crate::reader::ChunkHeader;
+use crate::writer::byte_writer::ByteWriter;
+use crate::writer::metadata::{encode_metadata_event, Element};
+use crate::{EVENT_TYPE_CONSTANT_POOL, MAGIC};
+
+const PRIMITIVE_TYPES: [&str; 8] = [
+    "int", "long", "float", "double", "char", "boolean", "short", "byte",
+];
+const STRING_TYPE: &str = "java.lang.String";
+const EVENT_SUPER_TYPE: &str = "jdk.jfr.Event";
+/// Class ids 0 and 1 are taken by the metadata and the constant pool events
+const FIRST_CLASS_ID: i64 = 100;
+const MAX_STRUCT_TYPES: u64 = 5;
+const MAX_EVENT_TYPES: u64 = 4;
+const MAX_FIELDS: u64 = 5;
+const MAX_ARRAY_LENGTH: u64 = 3;
+const MAX_CONSTANTS: u64 = 4;
+const MAX_STRING_LENGTH: u64 = 8;
+/// Limits nesting of the inlined (i.e. not constant pool) struct fields,
+/// to keep the size of the values small
+const MAX_INLINE_DEPTH: usize = 2;
+const VERSIONS: [(i16, i16); 3] = [(1, 0), (2, 0), (2, 1)];
+/// The characters of the generated strings. Not every one is representable in every string encoding
+const ALPHABET: [char; 12] = [
+    'a', 'Z', '0', ' ', '.', 'é', 'ß', 'ÿ', 'あ', '漢', '한', '🦀',
+];
+
+/// A small and stable PRNG (splitmix64), so that a seed reproduces the same chunk everywhere
+#[derive(Debug, Clone)]
+pub(crate) struct Rng(u64);
+
+impl Rng {
+    pub(crate) fn new(seed: u64) -> Self {
+        Self(seed)
+    }
+
+    pub(crate) fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// Returns a value in `0..n`
+    pub(crate) fn below(&mut self, n: u64) -> u64 {
+        self.next_u64() % n
+    }
+
+    /// Returns true with the probability of `1 / n`
+    pub(crate) fn one_in(&mut self, n: u64) -> bool {
+        self.below(n) == 0
+    }
+
+    fn pick<'a, T>(&mut self, items: &'a [T]) -> &'a T {
+        &items[self.below(items.len() as u64) as usize]
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum Kind {
+    /// Index of [`PRIMITIVE_TYPES`]
+    Primitive(usize),
+    String,
+    Struct,
+    Event,
+}
+
+#[derive(Debug)]
+struct ClassDef {
+    id: i64,
+    name: String,
+    kind: Kind,
+    fields: Vec<FieldDef>,
+    depth: usize,
+}
+
+#[derive(Debug)]
+struct FieldDef {
+    name: String,
+    class_id: i64,
+    constant_pool: bool,
+    array: bool,
+}
+
+#[derive(Debug)]
+pub struct GeneratedEvent {
+    pub class_id: i64,
+    pub type_name: String,
+    pub value: ValueDescriptor,
+}
+
+#[derive(Debug)]
+pub struct GeneratedConstant {
+    pub class_id: i64,
+    pub constant_index: i64,
+    pub value: ValueDescriptor,
+}
+
+#[derive(Debug)]
+pub struct GeneratedChunk {
+    pub bytes: Vec<u8>,
+    pub int_encoding: IntEncoding,
+    /// The offset of the metadata event from the beginning of the chunk.
+    /// The bytes between the header and this offset are the events and the constant pools
+    pub metadata_offset: u64,
+    /// The events in the order of the bytes
+    pub events: Vec<GeneratedEvent>,
+    pub constants: Vec<GeneratedConstant>,
+}
+
+/// Generates chunks from the seed. Each [`ChunkGenerator::generate`] call returns a different chunk,
+/// and the chunks can be concatenated into a multi-chunk recording.
+#[derive(Debug, Clone)]
+pub struct ChunkGenerator {
+    rng: Rng,
+    max_events: u64,
+}
+
+impl ChunkGenerator {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            rng: Rng::new(seed),
+            max_events: 16,
+        }
+    }
+
+    /// Sets the maximum number of the events in a chunk. Defaults to 16
+    pub fn max_events(mut self, max_events: u64) -> Self {
+        self.max_events = max_events;
+        self
+    }
+
+    pub fn generate(&mut self) -> GeneratedChunk {
+        let int_encoding = if self.rng.one_in(2) {
+            IntEncoding::Compressed
+        } else {
+            IntEncoding::Raw
+        };
+        let classes = self.generate_classes();
+        let pools = self.generate_pool_indices(&classes);
+        let start_ticks = self.rng.below(1 << 40) as i64;
+
+        let mut body = vec![];
+        let mut events = vec![];
+        let event_types = classes
+            .iter()
+            .filter(|c| c.kind == Kind::Event)
+            .collect::<Vec<_>>();
+        for _ in 0..self.rng.below(self.max_events + 1) {
+            let class = *self.rng.pick(&event_types);
+            let mut value = self.generate_value(&classes, &pools, class.id, true);
+            if let ValueDescriptor::Object(obj) = &mut value {
+                obj.fields[0] = ValueDescriptor::Primitive(Primitive::Long(
+                    start_ticks + self.rng.below(1 << 30) as i64,
+                ));
+            }
+            let mut writer = ByteWriter::new(int_encoding);
+            writer
+                .write_padded_i32(0)
+                .expect("generated chunks are small enough for the size fields");
+            writer.write_i64(class.id);
+            self.write_value(&mut writer, &classes, class.id, &value);
+            let size = writer.len();
+            writer
+                .set_padded_i32(0, size as i32)
+                .expect("generated chunks are small enough for the size fields");
+            body.extend(writer.into_inner());
+            events.push(GeneratedEvent {
+                class_id: class.id,
+                type_name: class.name.clone(),
+                value,
+            });
+        }
+
+        let mut constants = vec![];
+        for (class_id, indices) in &pools {
+            for &constant_index in indices {
+                let value = self.generate_value(&classes, &pools, *class_id, false);
+                constants.push(GeneratedConstant {
+                    class_id: *class_id,
+                    constant_index,
+                    value,
+                });
+            }
+        }
+        let constant_pool_offset =
+            self.write_constant_pools(&mut body, &classes, &constants, start_ticks, int_encoding);
+
+        let metadata_offset = ChunkHeader::HEADER_SIZE + body.len() as u64;
+        body.extend(self.encode_metadata(&classes, start_ticks, int_encoding));
+
+        let (major, minor) = *self.rng.pick(&VERSIONS);
+        let mut header = ByteWriter::new(IntEncoding::Raw);
+        header.write_bytes(&MAGIC);
+        header.write_i16(major);
+        header.write_i16(minor);
+        // chunk size
+        header.write_i64((ChunkHeader::HEADER_SIZE as usize + body.len()) as i64);
+        header.write_i64(constant_pool_offset as i64);
+        header.write_i64(metadata_offset as i64);
+        // start time
+        header.write_i64(1_600_000_000_000_000_000 + self.rng.below(1 << 50) as i64);
+        // duration
+        header.write_i64(1 + self.rng.below(1 << 40) as i64);
+        header.write_i64(start_ticks);
+        // ticks per second
+        header.write_i64(1_000_000_000);
+        header.write_i32(if int_encoding == IntEncoding::Compressed {
+            1
+        } else {
+            0
+        });
+
+        let mut bytes = header.into_inner();
+        bytes.extend(body);
+        GeneratedChunk {
+            bytes,
+            int_encoding,
+            metadata_offset,
+            events,
+            constants,
+        }
+    }
+
+    fn generate_classes(&mut self) -> Vec<ClassDef> {
+        let mut classes = vec![];
+        let mut next_id = FIRST_CLASS_ID;
+        let mut push = |classes: &mut Vec<ClassDef>, name: String, kind, fields: Vec<FieldDef>| {
+            let depth = fields
+                .iter()
+                .filter(|f| !f.constant_pool)
+                .map(|f| class_of(classes, f.class_id).depth + 1)
+                .max()
+                .unwrap_or(0);
+            classes.push(ClassDef {
+                id: next_id,
+                name,
+                kind,
+                fields,
+                depth,
+            });
+            next_id += 1;
+        };
+
+        for (i, name) in PRIMITIVE_TYPES.iter().enumerate() {
+            push(&mut classes, name.to_string(), Kind::Primitive(i), vec![]);
+        }
+        push(&mut classes, STRING_TYPE.to_string(), Kind::String, vec![]);
+        for i in 0..self.rng.below(MAX_STRUCT_TYPES + 1) {
+            let fields = self.generate_fields(&classes);
+            push(
+                &mut classes,
+                format!("gen.Struct{}", i),
+                Kind::Struct,
+                fields,
+            );
+        }
+        for i in 0..1 + self.rng.below(MAX_EVENT_TYPES) {
+            let mut fields = vec![FieldDef {
+                name: "startTime".to_string(),
+                class_id: FIRST_CLASS_ID + 1,
+                constant_pool: false,
+                array: false,
+            }];
+            fields.extend(self.generate_fields(&classes));
+            push(&mut classes, format!("gen.Event{}", i), Kind::Event, fields);
+        }
+        classes
+    }
+
+    /// Generates fields referring to the primitives, strings and the structs declared so far
+    fn generate_fields(&mut self, classes: &[ClassDef]) -> Vec<FieldDef> {
+        let candidates = classes
+            .iter()
+            .filter(|c| c.kind != Kind::Event)
+            .collect::<Vec<_>>();
+        (0..self.rng.below(MAX_FIELDS + 1))
+            .map(|i| {
+                let class = *self.rng.pick(&candidates);
+                let constant_pool = match class.kind {
+                    Kind::Struct if class.depth >= MAX_INLINE_DEPTH => true,
+                    Kind::String | Kind::Struct => self.rng.one_in(2),
+                    _ => false,
+                };
+                FieldDef {
+                    name: format!("field{}", i),
+                    class_id: class.id,
+                    constant_pool,
+                    array: self.rng.one_in(4),
+                }
+            })
+            .collect()
+    }
+
+    /// Every string and struct type has a non-empty pool, so that any of them can be referred
+    fn generate_pool_indices(&mut self, classes: &[ClassDef]) -> Vec<(i64, Vec<i64>)> {
+        classes
+            .iter()
+            .filter(|c| matches!(c.kind, Kind::String | Kind::Struct))
+            .map(|c| {
+                let mut index = self.rng.below(1000) as i64;
+                let indices = (0..1 + self.rng.below(MAX_CONSTANTS))
+                    .map(|_| {
+                        index += 1 + self.rng.below(100) as i64;
+                        index
+                    })
+                    .collect();
+                (c.id, indices)
+            })
+            .collect()
+    }
+
+    /// Generates a value of the class. Strings in the string pool are never references to the pool
+    fn generate_value(
+        &mut self,
+        classes: &[ClassDef],
+        pools: &[(i64, Vec<i64>)],
+        class_id: i64,
+        string_reference: bool,
+    ) -> ValueDescriptor {
+        let class = class_of(classes, class_id);
+        let primitive = match class.kind {
+            Kind::Primitive(0) => Primitive::Integer(self.rng.next_u64() as i32),
+            Kind::Primitive(1) => Primitive::Long(self.rng.next_u64() as i64),
+            Kind::Primitive(2) => {
+                let v = f32::from_bits(self.rng.next_u64() as u32);
+                Primitive::Float(if v.is_nan() { 0.5 } else { v })
+            }
+            Kind::Primitive(3) => {
+                let v = f64::from_bits(self.rng.next_u64());
+                Primitive::Double(if v.is_nan() { 0.5 } else { v })
+            }
+            Kind::Primitive(4) => Primitive::Character(self.generate_char()),
+            Kind::Primitive(5) => Primitive::Boolean(self.rng.one_in(2)),
+            Kind::Primitive(6) => Primitive::Short(self.rng.next_u64() as i16),
+            Kind::Primitive(_) => Primitive::Byte(self.rng.next_u64() as i8),
+            Kind::String => match self.rng.below(4) {
+                0 => Primitive::NullString,
+                1 if string_reference => {
+                    return self.generate_reference(pools, class_id);
+                }
+                _ => Primitive::String(self.generate_string()),
+            },
+            Kind::Struct | Kind::Event => {
+                let fields = class
+                    .fields
+                    .iter()
+                    .map(|f| {
+                        if f.array {
+                            let length = self.rng.below(MAX_ARRAY_LENGTH + 1);
+                            ValueDescriptor::Array(
+                                (0..length)
+                                    .map(|_| self.generate_field_single(classes, pools, f))
+                                    .collect(),
+                            )
+                        } else {
+                            self.generate_field_single(classes, pools, f)
+                        }
+                    })
+                    .collect();
+                return ValueDescriptor::Object(Object { class_id, fields });
+            }
+        };
+        ValueDescriptor::Primitive(primitive)
+    }
+
+    fn generate_field_single(
+        &mut self,
+        classes: &[ClassDef],
+        pools: &[(i64, Vec<i64>)],
+        field: &FieldDef,
+    ) -> ValueDescriptor {
+        if field.constant_pool {
+            self.generate_reference(pools, field.class_id)
+        } else {
+            self.generate_value(classes, pools, field.class_id, true)
+        }
+    }
+
+    fn generate_reference(&mut self, pools: &[(i64, Vec<i64>)], class_id: i64) -> ValueDescriptor {
+        let (_, indices) = pools.iter().find(|(id, _)| *id == class_id).unwrap();
+        ValueDescriptor::ConstantPool {
+            class_id,
+            constant_index: *self.rng.pick(indices),
+        }
+    }
+
+    /// Generates a char of the basic multilingual plane, which a Java char can represent
+    fn generate_char(&mut self) -> char {
+        loop {
+            if let Some(c) = char::from_u32(self.rng.below(0x10000) as u32) {
+                return c;
+            }
+        }
+    }
+
+    fn generate_string(&mut self) -> String {
+        // restrict some strings to narrower alphabets, so that every encoding gets exercised
+        let alphabet = match self.rng.below(3) {
+            0 => &ALPHABET[..8],
+            1 => &ALPHABET[..11],
+            _ => &ALPHABET[..],
+        };
+        (0..self.rng.below(MAX_STRING_LENGTH + 1))
+            .map(|_| *self.rng.pick(alphabet))
+            .collect()
+    }
+
+    fn write_value(
+        &mut self,
+        writer: &mut ByteWriter,
+        classes: &[ClassDef],
+        class_id: i64,
+        value: &ValueDescriptor,
+    ) {
+        let class = class_of(classes, class_id);
+        match value {
+            ValueDescriptor::Primitive(p) => self.write_primitive(writer, p),
+            ValueDescriptor::ConstantPool { constant_index, .. } => {
+                writer.write_constant_pool_string(*constant_index)
+            }
+            ValueDescriptor::Object(obj) => {
+                for (field, value) in class.fields.iter().zip(obj.fields.iter()) {
+                    match value {
+                        ValueDescriptor::Array(elems) if field.array => {
+                            writer.write_i32(elems.len() as i32);
+                            for elem in elems {
+                                self.write_field_single(writer, classes, field, elem);
+                            }
+                        }
+                        _ => self.write_field_single(writer, classes, field, value),
+                    }
+                }
+            }
+            ValueDescriptor::Array(_) => unreachable!("arrays are written by the fields"),
+        }
+    }
+
+    fn write_field_single(
+        &mut self,
+        writer: &mut ByteWriter,
+        classes: &[ClassDef],
+        field: &FieldDef,
+        value: &ValueDescriptor,
+    ) {
+        match value {
+            ValueDescriptor::ConstantPool { constant_index, .. } if field.constant_pool => {
+                writer.write_i64(*constant_index)
+            }
+            _ => self.write_value(writer, classes, field.class_id, value),
+        }
+    }
+
+    fn write_primitive(&mut self, writer: &mut ByteWriter, value: &Primitive) {
+        match value {
+            Primitive::Integer(v) => writer.write_i32(*v),
+            Primitive::Long(v) => writer.write_i64(*v),
+            Primitive::Float(v) => writer.write_f32(*v),
+            Primitive::Double(v) => writer.write_f64(*v),
+            Primitive::Character(v) => writer.write_char(*v),
+            Primitive::Boolean(v) => writer.write_i8(*v as i8),
+            Primitive::Short(v) => writer.write_i16(*v),
+            Primitive::Byte(v) => writer.write_i8(*v),
+            Primitive::NullString => writer.write_null_string(),
+            Primitive::String(s) => self.write_string(writer, s),
+            _ => unreachable!("other representations are not generated"),
+        }
+    }
+
+    /// Writes the string in one of the encodings which can represent it
+    fn write_string(&mut self, writer: &mut ByteWriter, s: &str) {
+        let latin1 = s.chars().all(|c| (c as u32) <= 0xff);
+        let bmp = s.chars().all(|c| (c as u32) <= 0xffff);
+        match self.rng.below(4) {
+            0 if s.is_empty() => writer.write_i8(STRING_ENCODING_EMPTY_STRING),
+            1 if latin1 => {
+                writer.write_i8(STRING_ENCODING_LATIN1_BYTE_ARRAY);
+                writer.write_i32(s.chars().count() as i32);
+                for c in s.chars() {
+                    writer.write_i8(c as u8 as i8);
+                }
+            }
+            2 if bmp => {
+                writer.write_i8(STRING_ENCODING_CHAR_ARRAY);
+                writer.write_i32(s.chars().count() as i32);
+                for c in s.chars() {
+                    writer.write_char(c);
+                }
+            }
+            _ => writer.write_string(s),
+        }
+    }
+
+    /// Writes the constants into one or two constant pool events,
+    /// and returns the offset of the one which the header points to
+    fn write_constant_pools(
+        &mut self,
+        body: &mut Vec<u8>,
+        classes: &[ClassDef],
+        constants: &[GeneratedConstant],
+        start_ticks: i64,
+        int_encoding: IntEncoding,
+    ) -> u64 {
+        let mut class_ids = constants.iter().map(|c| c.class_id).collect::<Vec<_>>();
+        class_ids.dedup();
+        let split = if class_ids.len() > 1 && self.rng.one_in(2) {
+            1 + self.rng.below(class_ids.len() as u64 - 1) as usize
+        } else {
+            class_ids.len()
+        };
+
+        // like JVM, the header points to the last event, and the deltas point backwards
+        let mut previous_offset = None;
+        for ids in [&class_ids[..split], &class_ids[split..]] {
+            if ids.is_empty() {
+                continue;
+            }
+            let offset = ChunkHeader::HEADER_SIZE + body.len() as u64;
+            let mut writer = ByteWriter::new(int_encoding);
+            writer
+                .write_padded_i32(0)
+                .expect("generated chunks are small enough for the size fields");
+            writer.write_i64(EVENT_TYPE_CONSTANT_POOL);
+            writer.write_i64(start_ticks);
+            // duration
+            writer.write_i64(0);
+            writer.write_i64(previous_offset.map_or(0, |p: u64| p as i64 - offset as i64));
+            // flush
+            writer.write_i8(1);
+            writer.write_i32(ids.len() as i32);
+            for &class_id in ids {
+                let pool = constants
+                    .iter()
+                    .filter(|c| c.class_id == class_id)
+                    .collect::<Vec<_>>();
+                writer.write_i64(class_id);
+                writer.write_i32(pool.len() as i32);
+                for constant in pool {
+                    writer.write_i64(constant.constant_index);
+                    self.write_value(&mut writer, classes, class_id, &constant.value);
+                }
+            }
+            let size = writer.len();
+            writer
+                .set_padded_i32(0, size as i32)
+                .expect("generated chunks are small enough for the size fields");
+            body.extend(writer.into_inner());
+            previous_offset = Some(offset);
+        }
+        previous_offset.unwrap_or(0)
+    }
+
+    fn encode_metadata(
+        &mut self,
+        classes: &[ClassDef],
+        start_ticks: i64,
+        int_encoding: IntEncoding,
+    ) -> Vec<u8> {
+        let classes = classes
+            .iter()
+            .map(|c| {
+                let mut attributes = vec![("id", c.id.to_string()), ("name", c.name.clone())];
+                if c.kind == Kind::Event {
+                    attributes.push(("superType", EVENT_SUPER_TYPE.to_string()));
+                }
+                let fields = c
+                    .fields
+                    .iter()
+                    .map(|f| {
+                        let mut attributes =
+                            vec![("name", f.name.clone()), ("class", f.class_id.to_string())];
+                        if f.constant_pool {
+                            attributes.push(("constantPool", "true".to_string()));
+                        }
+                        if f.array {
+                            attributes.push(("dimension", "1".to_string()));
+                        }
+                        Element::new("field", attributes, vec![])
+                    })
+                    .collect();
+                Element::new("class", attributes, fields)
+            })
+            .collect();
+        let mut children = vec![Element::new("metadata", vec![], classes)];
+        if self.rng.one_in(2) {
+            let region = vec![
+                ("locale", "en_US".to_string()),
+                ("gmtOffset", "0".to_string()),
+            ];
+            children.push(Element::new("region", region, vec![]));
+        }
+        let root = Element::new("root", vec![], children);
+        encode_metadata_event(&root, start_ticks, int_encoding)
+            .expect("generated chunks are small enough for the size fields")
+    }
+}
+
+fn class_of(classes: &[ClassDef], class_id: i64) -> &ClassDef {
+    &classes[(class_id - FIRST_CLASS_ID) as usize]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::reader::byte_stream::ByteStream;
+    use crate::reader::limits::Limits;
+    use crate::reader::value_descriptor::StringRepr;
+    use crate::reader::JfrReader;
+    use crate::writer::value::encode_event;
+    use std::io::Cursor;
+
+    const SEEDS: u64 = 300;
+
+    #[test]
+    fn test_parse_generated() {
+        for seed in 0..SEEDS {
+            let generated = ChunkGenerator::new(seed).generate();
+            let mut reader =
+                JfrReader::from_bytes(&generated.bytes).string_repr(StringRepr::String);
+            let mut chunks = reader.chunks();
+            let (chunk_reader, chunk) = chunks.next().unwrap().unwrap();
+            assert!(chunks.next().is_none());
+
+            let events = chunk_reader
+                .events(&chunk)
+                .collect::<Result<Vec<_>, _>>()
+                .unwrap_or_else(|e| panic!("seed {}: {}", seed, e));
+            assert_eq!(events.len(), generated.events.len(), "seed {}", seed);
+            for (event, expected) in events.into_iter().zip(generated.events.iter()) {
+                assert_eq!(event.class.name(), expected.type_name, "seed {}", seed);
+                assert_eq!(event.into_value(), expected.value, "seed {}", seed);
+            }
+
+            assert_eq!(
+                chunk.constant_pool.inner.len(),
+                generated.constants.len(),
+                "seed {}",
+                seed
+            );
+            for constant in &generated.constants {
+                assert_eq!(
+                    chunk
+                        .constant_pool
+                        .get(&constant.class_id, &constant.constant_index),
+                    Some(&constant.value),
+                    "seed {}",
+                    seed
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_writer_round_trip() {
+        for seed in 0..SEEDS {
+            let generated = ChunkGenerator::new(seed).generate();
+            let mut reader =
+                JfrReader::from_bytes(&generated.bytes).string_repr(StringRepr::String);
+            let (chunk_reader, chunk) = reader.chunks().next().unwrap().unwrap();
+            for event in chunk_reader.events(&chunk) {
+                let event = event.unwrap();
+                let class_id = event.class.class_id;
+                let value = event.into_value();
+                let bytes = encode_event(&value, class_id, &chunk.metadata, generated.int_encoding)
+                    .unwrap();
+
+                let mut stream = ByteStream::new(Cursor::new(&bytes));
+                stream.set_int_encoding(generated.int_encoding);
+                stream.set_string_repr(StringRepr::String);
+                assert_eq!(stream.read_i32().unwrap() as usize, bytes.len());
+                assert_eq!(stream.read_i64().unwrap(), class_id);
+                let decoded = ValueDescriptor::try_new(&mut stream, class_id, &chunk.metadata);
+                assert_eq!(decoded.unwrap(), value, "seed {}", seed);
+            }
+        }
+    }
+
+    #[test]
+    fn test_multi_chunk() {
+        for seed in 0..SEEDS / 10 {
+            let mut generator = ChunkGenerator::new(seed);
+            let generated = (0..3).map(|_| generator.generate()).collect::<Vec<_>>();
+            let bytes = generated
+                .iter()
+                .flat_map(|g| g.bytes.clone())
+                .collect::<Vec<_>>();
+
+            let mut reader = JfrReader::from_bytes(bytes).string_repr(StringRepr::String);
+            let mut count = 0;
+            for (chunk, expected) in reader.chunks().zip(generated.iter()) {
+                let (chunk_reader, chunk) = chunk.unwrap();
+                let values = chunk_reader
+                    .events(&chunk)
+                    .map(|e| e.unwrap().into_value())
+                    .collect::<Vec<_>>();
+                assert!(values.iter().eq(expected.events.iter().map(|e| &e.value)));
+                count += 1;
+            }
+            assert_eq!(count, generated.len());
+        }
+    }
+
+    /// Corrupted or truncated chunks must be rejected by errors, not by panics
+    #[test]
+    fn test_corrupted() {
+        let mut rng = Rng::new(0);
+        for seed in 0..SEEDS / 3 {
+            let generated = ChunkGenerator::new(seed).generate();
+            for _ in 0..20 {
+                let mut bytes = generated.bytes.clone();
+                if rng.one_in(4) {
+                    bytes.truncate(rng.below(bytes.len() as u64) as usize);
+                } else {
+                    // the metadata is left intact, since recursive types can't be parsed yet
+                    let range = generated.metadata_offset - ChunkHeader::HEADER_SIZE;
+                    for _ in 0..1 + rng.below(4) {
+                        let position = (ChunkHeader::HEADER_SIZE + rng.below(range)) as usize;
+                        bytes[position] = rng.next_u64() as u8;
+                    }
+                }
+                read_all(bytes);
+            }
+        }
+    }
+
+    fn read_all(bytes: Vec<u8>) {
+        let mut reader = JfrReader::from_bytes(bytes)
+            .limits(Limits::hardened())
+            .max_chunk_size(1024 * 1024);
+        for chunk in reader.chunks() {
+            let (chunk_reader, chunk) = match chunk {
+                Ok(chunk) => chunk,
+                Err(_) => break,
+            };
+            // an invalid event size can't be skipped, so stop at the first error
+            for event in chunk_reader.events(&chunk) {
+                match event {
+                    Ok(event) => drop(event.value().to_string()),
+                    Err(_) => break,
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,103 @@
+//! Encode the metadata event from the element tree.
+//! This is the counterpart of [`crate::reader::metadata`].
+
+use crate::decode::IntEncoding;
+use crate::reader::Result;
+use crate::writer::byte_writer::ByteWriter;
+use crate::EVENT_TYPE_METADATA;
+use std::collections::HashMap;
+
+/// An element of the metadata, e.g. `class` or `field`
+pub(crate) struct Element {
+    name: &'static str,
+    attributes: Vec<(&'static str, String)>,
+    children: Vec<Element>,
+}
+
+impl Element {
+    pub(crate) fn new(
+        name: &'static str,
+        attributes: Vec<(&'static str, String)>,
+        children: Vec<Element>,
+    ) -> Self {
+        Self {
+            name,
+            attributes,
+            children,
+        }
+    }
+
+    fn intern(&self, strings: &mut StringTable) {
+        strings.intern(self.name);
+        for (key, value) in &self.attributes {
+            strings.intern(key);
+            strings.intern(value);
+        }
+        for child in &self.children {
+            child.intern(strings);
+        }
+    }
+
+    fn write(&self, writer: &mut ByteWriter, strings: &StringTable) {
+        writer.write_i32(self.attributes.len() as i32);
+        for (key, value) in &self.attributes {
+            writer.write_i32(strings.index(key));
+            writer.write_i32(strings.index(value));
+        }
+        writer.write_i32(self.children.len() as i32);
+        for child in &self.children {
+            writer.write_i32(strings.index(child.name));
+            child.write(writer, strings);
+        }
+    }
+}
+
+#[derive(Default)]
+struct StringTable {
+    strings: Vec<String>,
+    indices: HashMap<String, i32>,
+}
+
+impl StringTable {
+    fn intern(&mut self, s: &str) {
+        if !self.indices.contains_key(s) {
+            self.indices
+                .insert(s.to_string(), self.strings.len() as i32);
+            self.strings.push(s.to_string());
+        }
+    }
+
+    fn index(&self, s: &str) -> i32 {
+        self.indices[s]
+    }
+}
+
+/// Encodes the metadata event including the size and the type id.
+/// `root` is the root element, whose children are `metadata` and optionally `region`
+pub(crate) fn encode_metadata_event(
+    root: &Element,
+    start_ticks: i64,
+    int_encoding: IntEncoding,
+) -> Result<Vec<u8>> {
+    let mut strings = StringTable::default();
+    root.intern(&mut strings);
+
+    let mut writer = ByteWriter::new(int_encoding);
+    // size
+    writer.write_padded_i32(0)?;
+    writer.write_i64(EVENT_TYPE_METADATA);
+    writer.write_i64(start_ticks);
+    // duration
+    writer.write_i64(0);
+    // metadata id
+    writer.write_i64(1);
+    writer.write_i32(strings.strings.len() as i32);
+    for s in &strings.strings {
+        writer.write_string(s);
+    }
+    writer.write_i32(strings.index(root.name));
+    root.write(&mut writer, &strings);
+    let size = writer.len();
+    writer.set_padded_i32(0, size as i32)?;
+    Ok(writer.into_inner())
+}
@@ -0,0 +1,14 @@
+//! Module to write JFR data structures as bytes.
+
+pub(crate) mod byte_writer;
+pub(crate) mod constant_pool;
+#[cfg(any(test, feature = "generator"))]
+pub mod generator;
+pub(crate) mod metadata;
+pub mod recording;
+pub mod ser;
+#[cfg(feature = "backtrace")]
+pub mod stack_trace;
+pub(crate) mod value;
+
+pub use ser::{to_event, to_value_descriptor};
@@ -0,0 +1,629 @@
+//! Write recordings of the events of Rust applications.
+//!
+//! Types are declared by [`TypeDeclaration`], and events are written from Rust structs
+//! through [`crate::writer::ser`] or from [`ValueDescriptor`].
+//! The recording is split into self-contained chunks by [`RotationPolicy`] like JVM does,
+//! so that each chunk can be read without loading the entire recording.
+//!
+//! ```no_run
+//! use jfrs::writer::recording::{ticks, FieldDeclaration, JfrWriter, TypeDeclaration};
+//! use serde::Serialize;
+//! use std::fs::File;
+//! use std::time::SystemTime;
+//!
+//! #[derive(Serialize)]
+//! #[serde(rename_all = "camelCase")]
+//! struct Request<'a> {
+//!     start_time: i64,
+//!     path: &'a str,
+//! }
+//!
+//! let mut writer = JfrWriter::new(File::create("/path/to/recording.jfr").unwrap());
+//! writer
+//!     .declare_type(
+//!         TypeDeclaration::event("app.Request")
+//!             .field(FieldDeclaration::new("path", "java.lang.String")),
+//!     )
+//!     .unwrap();
+//! let start_time = ticks(SystemTime::now());
+//! writer
+//!     .write_event("app.Request", &Request { start_time, path: "/index.html" })
+//!     .unwrap();
+//! writer.finish().unwrap();
+//! ```
+//!
+//! Timestamps are in ticks, which are nanoseconds since the UNIX epoch.
+
+use crate::decode::IntEncoding;
+use crate::reader::byte_stream::ByteStream;
+use crate::reader::constant_pool::{collect_references, ConstantPoolKey};
+use crate::reader::metadata::Metadata;
+use crate::reader::stats::Stopwatch;
+use crate::reader::type_descriptor::EVENT_SUPER_TYPE;
+use crate::reader::value_descriptor::ValueDescriptor;
+use crate::reader::{ChunkHeader, Error, Result};
+use crate::writer::byte_writer::ByteWriter;
+use crate::writer::constant_pool::write_constant_pool_event;
+use crate::writer::metadata::{encode_metadata_event, Element};
+use crate::writer::ser::to_value_descriptor;
+use crate::writer::value::encode_event;
+use crate::MAGIC;
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::io::{Cursor, Write};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// The types which are declared from the beginning
+const BUILTIN_TYPES: [&str; 9] = [
+    "int",
+    "long",
+    "float",
+    "double",
+    "char",
+    "boolean",
+    "short",
+    "byte",
+    "java.lang.String",
+];
+/// Class ids 0 and 1 are taken by the metadata and the constant pool events
+const FIRST_CLASS_ID: i64 = 100;
+const TICKS_PER_SECOND: i64 = 1_000_000_000;
+const VERSION: (i16, i16) = (2, 1);
+
+/// Converts the time to ticks of the written recordings
+pub fn ticks(time: SystemTime) -> i64 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|d| d.as_nanos() as i64)
+        .unwrap_or(0)
+}
+
+/// When to finish the current chunk and start a new one
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct RotationPolicy {
+    /// Starts a new chunk before the events of the chunk exceed this size.
+    /// A single event larger than this gets a chunk of its own
+    pub max_chunk_bytes: Option<u64>,
+    /// Starts a new chunk when an event is written after the chunk is older than this
+    pub max_chunk_duration: Option<Duration>,
+}
+
+impl Default for RotationPolicy {
+    /// 12 MB of events per chunk, which is the default of JVM
+    fn default() -> Self {
+        Self {
+            max_chunk_bytes: Some(12 * 1024 * 1024),
+            max_chunk_duration: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct TypeDeclaration {
+    name: String,
+    super_type: Option<String>,
+    fields: Vec<FieldDeclaration>,
+}
+
+impl TypeDeclaration {
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            super_type: None,
+            fields: vec![],
+        }
+    }
+
+    /// Declares an event type, which has `startTime` as the first field like JVM's event types
+    pub fn event(name: &str) -> Self {
+        Self::new(name)
+            .super_type(EVENT_SUPER_TYPE)
+            .field(FieldDeclaration::new("startTime", "long"))
+    }
+
+    pub fn super_type(mut self, super_type: &str) -> Self {
+        self.super_type = Some(super_type.to_string());
+        self
+    }
+
+    pub fn field(mut self, field: FieldDeclaration) -> Self {
+        self.fields.push(field);
+        self
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct FieldDeclaration {
+    name: String,
+    type_name: String,
+    constant_pool: bool,
+    array: bool,
+}
+
+impl FieldDeclaration {
+    pub fn new(name: &str, type_name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            type_name: type_name.to_string(),
+            constant_pool: false,
+            array: false,
+        }
+    }
+
+    /// The field holds the index of the constant instead of the value
+    pub fn constant_pool(mut self, constant_pool: bool) -> Self {
+        self.constant_pool = constant_pool;
+        self
+    }
+
+    pub fn array(mut self, array: bool) -> Self {
+        self.array = array;
+        self
+    }
+}
+
+struct DeclaredType {
+    class_id: i64,
+    declaration: TypeDeclaration,
+    field_class_ids: Vec<i64>,
+}
+
+/// The chunk being written
+struct ChunkState {
+    events: Vec<u8>,
+    constants: BTreeSet<ConstantPoolKey>,
+    start_time: SystemTime,
+    stopwatch: Stopwatch,
+}
+
+pub struct JfrWriter<W: Write> {
+    output: W,
+    rotation_policy: RotationPolicy,
+    int_encoding: IntEncoding,
+    types: Vec<DeclaredType>,
+    class_ids: HashMap<String, i64>,
+    // parsed from the encoded metadata, to encode the values. Reset on every declaration
+    metadata: Option<Metadata>,
+    constants: BTreeMap<ConstantPoolKey, ValueDescriptor>,
+    chunk: Option<ChunkState>,
+    chunk_count: usize,
+}
+
+impl<W: Write> JfrWriter<W> {
+    pub fn new(output: W) -> Self {
+        let mut writer = Self {
+            output,
+            rotation_policy: RotationPolicy::default(),
+            int_encoding: IntEncoding::Compressed,
+            types: vec![],
+            class_ids: HashMap::new(),
+            metadata: None,
+            constants: BTreeMap::new(),
+            chunk: None,
+            chunk_count: 0,
+        };
+        for name in BUILTIN_TYPES {
+            writer
+                .declare_type(TypeDeclaration::new(name))
+                .expect("builtin types must be valid");
+        }
+        writer
+    }
+
+    pub fn rotation_policy(mut self, policy: RotationPolicy) -> Self {
+        self.rotation_policy = policy;
+        self
+    }
+
+    /// Sets how the integers are encoded. Defaults to [`IntEncoding::Compressed`] like modern JVMs,
+    /// which makes the recording smaller
+    pub fn int_encoding(mut self, int_encoding: IntEncoding) -> Self {
+        self.int_encoding = int_encoding;
+        self
+    }
+
+    /// Declares the type and returns its class id.
+    /// The types of the fields must be declared before,
+    /// except the type itself referred through the constant pool
+    pub fn declare_type(&mut self, declaration: TypeDeclaration) -> Result<i64> {
+        if self.class_ids.contains_key(&declaration.name) {
+            return Err(Error::SerializeError(format!(
+                "Type already declared: {}",
+                declaration.name
+            )));
+        }
+        let class_id = FIRST_CLASS_ID + self.types.len() as i64;
+        let field_class_ids = declaration
+            .fields
+            .iter()
+            .map(|f| {
+                if f.type_name == declaration.name && f.constant_pool {
+                    Ok(class_id)
+                } else {
+                    self.class_id(&f.type_name)
+                }
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        self.class_ids.insert(declaration.name.clone(), class_id);
+        self.types.push(DeclaredType {
+            class_id,
+            declaration,
+            field_class_ids,
+        });
+        self.metadata = None;
+        Ok(class_id)
+    }
+
+    pub fn class_id(&self, type_name: &str) -> Result<i64> {
+        self.class_ids
+            .get(type_name)
+            .copied()
+            .ok_or_else(|| Error::SerializeError(format!("Type not found: {}", type_name)))
+    }
+
+    /// Registers the constant, which is written into every chunk having the events referring to it
+    pub fn add_constant<T>(&mut self, type_name: &str, constant_index: i64, value: &T) -> Result<()>
+    where
+        T: Serialize + ?Sized,
+    {
+        let class_id = self.class_id(type_name)?;
+        let value = to_value_descriptor(value, class_id, self.metadata()?)?;
+        self.add_constant_value(class_id, constant_index, value);
+        Ok(())
+    }
+
+    pub fn add_constant_value(
+        &mut self,
+        class_id: i64,
+        constant_index: i64,
+        value: ValueDescriptor,
+    ) {
+        self.constants.insert(
+            ConstantPoolKey {
+                class_id,
+                constant_index,
+            },
+            value,
+        );
+    }
+
+    pub fn write_event<T>(&mut self, type_name: &str, value: &T) -> Result<()>
+    where
+        T: Serialize + ?Sized,
+    {
+        let class_id = self.class_id(type_name)?;
+        let value = to_value_descriptor(value, class_id, self.metadata()?)?;
+        self.write_event_value(class_id, &value)
+    }
+
+    pub fn write_event_value(&mut self, class_id: i64, value: &ValueDescriptor) -> Result<()> {
+        let int_encoding = self.int_encoding;
+        let bytes = encode_event(value, class_id, self.metadata()?, int_encoding)?;
+        if self.should_rotate(bytes.len() as u64) {
+            self.finish_chunk(false)?;
+        }
+
+        let chunk = self.chunk.get_or_insert_with(|| ChunkState {
+            events: vec![],
+            constants: BTreeSet::new(),
+            start_time: SystemTime::now(),
+            stopwatch: Stopwatch::start(),
+        });
+        chunk.events.extend_from_slice(&bytes);
+        let mut references = vec![];
+        collect_references(value, &mut references);
+        chunk.constants.extend(references);
+        Ok(())
+    }
+
+    /// Returns the number of the chunks written so far
+    pub fn chunk_count(&self) -> usize {
+        self.chunk_count
+    }
+
+    /// Writes the current chunk as the final chunk, and returns the output
+    pub fn finish(mut self) -> Result<W> {
+        self.finish_chunk(true)?;
+        self.output.flush().map_err(Error::IoError)?;
+        Ok(self.output)
+    }
+
+    fn should_rotate(&self, event_size: u64) -> bool {
+        let chunk = match &self.chunk {
+            Some(chunk) => chunk,
+            None => return false,
+        };
+        let too_large = self
+            .rotation_policy
+            .max_chunk_bytes
+            .is_some_and(|max| chunk.events.len() as u64 + event_size > max);
+        let too_old = self
+            .rotation_policy
+            .max_chunk_duration
+            .is_some_and(|max| chunk.stopwatch.elapsed() >= max);
+        too_large || too_old
+    }
+
+    fn finish_chunk(&mut self, final_chunk: bool) -> Result<()> {
+        let chunk = match self.chunk.take() {
+            Some(chunk) => chunk,
+            None => return Ok(()),
+        };
+        let start_ticks = ticks(chunk.start_time);
+        let duration = chunk.stopwatch.elapsed().as_nanos().max(1) as i64;
+
+        self.metadata()?;
+        let metadata = self.metadata.as_ref().unwrap();
+
+        let mut writer = ByteWriter::new(self.int_encoding);
+        writer.write_bytes(&chunk.events);
+        let constant_pool_offset = ChunkHeader::HEADER_SIZE as usize + writer.len();
+        let constants = self.referenced_constants(chunk.constants);
+        write_constant_pool_event(
+            &mut writer,
+            constants
+                .iter()
+                .filter_map(|key| self.constants.get(key).map(|value| (*key, value))),
+            start_ticks,
+            metadata,
+        )?;
+        let metadata_offset = ChunkHeader::HEADER_SIZE as usize + writer.len();
+        writer.write_bytes(&self.encode_metadata(start_ticks)?);
+        let body = writer.into_inner();
+
+        let mut header = ByteWriter::new(IntEncoding::Raw);
+        header.write_bytes(&MAGIC);
+        header.write_i16(VERSION.0);
+        header.write_i16(VERSION.1);
+        // chunk size
+        header.write_i64((ChunkHeader::HEADER_SIZE as usize + body.len()) as i64);
+        header.write_i64(constant_pool_offset as i64);
+        header.write_i64(metadata_offset as i64);
+        // start time in nanos
+        header.write_i64(start_ticks);
+        header.write_i64(duration);
+        header.write_i64(start_ticks);
+        header.write_i64(TICKS_PER_SECOND);
+        let mut features = 0;
+        if self.int_encoding == IntEncoding::Compressed {
+            features |= ChunkHeader::FEATURES_COMPRESSED_INTS;
+        }
+        if final_chunk {
+            features |= ChunkHeader::FEATURES_FINAL_CHUNK;
+        }
+        header.write_i32(features);
+
+        self.output
+            .write_all(&header.into_inner())
+            .and_then(|_| self.output.write_all(&body))
+            .map_err(Error::IoError)?;
+        self.chunk_count += 1;
+        Ok(())
+    }
+
+    /// Adds the constants referred from the constants transitively
+    fn referenced_constants(
+        &self,
+        mut constants: BTreeSet<ConstantPoolKey>,
+    ) -> BTreeSet<ConstantPoolKey> {
+        let mut stack = constants.iter().copied().collect::<Vec<_>>();
+        while let Some(key) = stack.pop() {
+            if let Some(value) = self.constants.get(&key) {
+                let mut references = vec![];
+                collect_references(value, &mut references);
+                for reference in references {
+                    if constants.insert(reference) {
+                        stack.push(reference);
+                    }
+                }
+            }
+        }
+        constants
+    }
+
+    fn metadata(&mut self) -> Result<&Metadata> {
+        if self.metadata.is_none() {
+            let bytes = self.encode_metadata(0)?;
+            let mut stream = ByteStream::new(Cursor::new(bytes));
+            stream.set_int_encoding(self.int_encoding);
+            // size, type, start, duration and metadata id
+            stream.read_i32()?;
+            for _ in 0..4 {
+                stream.read_i64()?;
+            }
+            self.metadata = Some(Metadata::read_body(&mut stream)?);
+        }
+        Ok(self.metadata.as_ref().unwrap())
+    }
+
+    fn encode_metadata(&self, start_ticks: i64) -> Result<Vec<u8>> {
+        let classes = self
+            .types
+            .iter()
+            .map(|t| {
+                let mut attributes = vec![
+                    ("id", t.class_id.to_string()),
+                    ("name", t.declaration.name.clone()),
+                ];
+                if let Some(super_type) = &t.declaration.super_type {
+                    attributes.push(("superType", super_type.clone()));
+                }
+                let fields = t
+                    .declaration
+                    .fields
+                    .iter()
+                    .zip(t.field_class_ids.iter())
+                    .map(|(f, class_id)| {
+                        let mut attributes =
+                            vec![("name", f.name.clone()), ("class", class_id.to_string())];
+                        if f.constant_pool {
+                            attributes.push(("constantPool", "true".to_string()));
+                        }
+                        if f.array {
+                            attributes.push(("dimension", "1".to_string()));
+                        }
+                        Element::new("field", attributes, vec![])
+                    })
+                    .collect();
+                Element::new("class", attributes, fields)
+            })
+            .collect();
+        let root = Element::new(
+            "root",
+            vec![],
+            vec![Element::new("metadata", vec![], classes)],
+        );
+        encode_metadata_event(&root, start_ticks, self.int_encoding)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::reader::value_descriptor::StringRepr;
+    use crate::reader::JfrReader;
+
+    #[derive(Serialize)]
+    #[serde(rename_all = "camelCase")]
+    struct Request<'a> {
+        start_time: i64,
+        path: &'a str,
+        thread: i64,
+    }
+
+    #[derive(Serialize)]
+    struct Thread<'a> {
+        name: &'a str,
+    }
+
+    fn writer(output: Vec<u8>) -> JfrWriter<Vec<u8>> {
+        let mut writer = JfrWriter::new(output);
+        writer
+            .declare_type(
+                TypeDeclaration::new("app.Thread")
+                    .field(FieldDeclaration::new("name", "java.lang.String")),
+            )
+            .unwrap();
+        writer
+            .declare_type(
+                TypeDeclaration::event("app.Request")
+                    .field(FieldDeclaration::new("path", "java.lang.String"))
+                    .field(FieldDeclaration::new("thread", "app.Thread").constant_pool(true)),
+            )
+            .unwrap();
+        writer
+            .add_constant("app.Thread", 1, &Thread { name: "main" })
+            .unwrap();
+        writer
+    }
+
+    fn write_requests(writer: &mut JfrWriter<Vec<u8>>, count: usize) {
+        for i in 0..count {
+            let request = Request {
+                start_time: 1000 + i as i64,
+                path: "/index.html",
+                thread: 1,
+            };
+            writer.write_event("app.Request", &request).unwrap();
+        }
+    }
+
+    /// Returns (event count, final flag) of each chunk
+    fn read_chunks(bytes: Vec<u8>) -> Vec<(usize, bool)> {
+        let mut reader = JfrReader::from_bytes(bytes).string_repr(StringRepr::String);
+        reader
+            .chunks()
+            .map(|c| {
+                let (chunk_reader, chunk) = c.unwrap();
+                let mut count = 0;
+                for event in chunk_reader.events(&chunk) {
+                    let event = event.unwrap();
+                    assert_eq!(event.class.name(), "app.Request");
+                    let value = event.value();
+                    assert_eq!(
+                        value.get_field("path").and_then(|v| v.as_str()),
+                        Some("/index.html")
+                    );
+                    assert_eq!(
+                        value
+                            .get_field("thread")
+                            .and_then(|v| v.get_field("name"))
+                            .and_then(|v| v.as_str()),
+                        Some("main")
+                    );
+                    count += 1;
+                }
+                (count, chunk.header.is_final_chunk())
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_write() {
+        let mut writer = writer(vec![]);
+        write_requests(&mut writer, 10);
+        assert_eq!(read_chunks(writer.finish().unwrap()), vec![(10, true)]);
+    }
+
+    #[test]
+    fn test_rotate_by_size() {
+        let mut writer = writer(vec![]).rotation_policy(RotationPolicy {
+            max_chunk_bytes: Some(100),
+            max_chunk_duration: None,
+        });
+        write_requests(&mut writer, 10);
+        let chunks = read_chunks(writer.finish().unwrap());
+        assert!(chunks.len() > 1);
+        assert_eq!(chunks.iter().map(|c| c.0).sum::<usize>(), 10);
+        for (i, (_, final_chunk)) in chunks.iter().enumerate() {
+            assert_eq!(*final_chunk, i == chunks.len() - 1);
+        }
+    }
+
+    #[test]
+    fn test_rotate_by_duration() {
+        let mut writer = writer(vec![]).rotation_policy(RotationPolicy {
+            max_chunk_bytes: None,
+            max_chunk_duration: Some(Duration::ZERO),
+        });
+        write_requests(&mut writer, 3);
+        assert_eq!(writer.chunk_count(), 2);
+        assert_eq!(
+            read_chunks(writer.finish().unwrap()),
+            vec![(1, false), (1, false), (1, true)]
+        );
+    }
+
+    #[test]
+    fn test_int_encoding() {
+        let write = |int_encoding| {
+            let mut writer = writer(vec![]).int_encoding(int_encoding);
+            write_requests(&mut writer, 10);
+            writer.finish().unwrap()
+        };
+        let raw = write(IntEncoding::Raw);
+        let compressed = write(IntEncoding::Compressed);
+        assert!(compressed.len() < raw.len());
+
+        for (bytes, compressed) in [(raw, false), (compressed, true)] {
+            let mut reader = JfrReader::from_bytes(&bytes);
+            let (_, chunk) = reader.chunks().next().unwrap().unwrap();
+            assert_eq!(chunk.header.is_compressed_ints(), compressed);
+            assert_eq!(read_chunks(bytes), vec![(10, true)]);
+        }
+    }
+
+    #[test]
+    fn test_unknown_type() {
+        let mut writer = writer(vec![]);
+        assert!(writer
+            .declare_type(
+                TypeDeclaration::new("app.Foo").field(FieldDeclaration::new("bar", "app.Bar"))
+            )
+            .is_err());
+        assert!(writer
+            .declare_type(TypeDeclaration::new("app.Thread"))
+            .is_err());
+        assert!(writer.write_event("app.Bar", &()).is_err());
+    }
+}
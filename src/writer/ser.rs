@@ -0,0 +1,633 @@
+//! `serde::Serializer` to build [`ValueDescriptor`] from Rust structs based on the declared types.
+//! This is the counterpart of [`crate::reader::de`].
+//!
+//! Struct fields are matched with the declared fields by name, and the missing fields are filled
+//! with default values (zero, null string, empty array, or null constant pool reference).
+//! Fields referring to the constant pool must be given as the constant index.
+
+use crate::reader::metadata::Metadata;
+use crate::reader::type_descriptor::{FieldDescriptor, TypeDescriptor};
+use crate::reader::value_descriptor::{Object, Primitive, ValueDescriptor};
+use crate::reader::{Chunk, Error, Result};
+use crate::writer::value::encode_event;
+use serde::ser::{Impossible, SerializeSeq, SerializeStruct, SerializeTuple, SerializeTupleStruct};
+use serde::{Serialize, Serializer};
+use std::fmt::Display;
+
+impl serde::ser::Error for Error {
+    fn custom<T>(msg: T) -> Self
+    where
+        T: Display,
+    {
+        Error::SerializeError(msg.to_string())
+    }
+}
+
+/// Encodes the struct as the event body of the given event type, including the size and the type id.
+pub fn to_event<T>(value: &T, type_name: &str, chunk: &Chunk) -> Result<Vec<u8>>
+where
+    T: Serialize + ?Sized,
+{
+    let type_desc = chunk
+        .metadata
+        .type_pool
+        .get_by_name(type_name)
+        .ok_or_else(|| Error::SerializeError(format!("Type not found: {}", type_name)))?;
+    let value = to_value_descriptor(value, type_desc.class_id, &chunk.metadata)?;
+    encode_event(
+        &value,
+        type_desc.class_id,
+        &chunk.metadata,
+        chunk.header.int_encoding(),
+    )
+}
+
+/// Converts the value to [`ValueDescriptor`] of the given type.
+pub fn to_value_descriptor<T>(
+    value: &T,
+    class_id: i64,
+    metadata: &Metadata,
+) -> Result<ValueDescriptor>
+where
+    T: Serialize + ?Sized,
+{
+    value.serialize(ValueSerializer {
+        metadata,
+        class_id,
+        constant_pool: false,
+        unsigned: false,
+    })
+}
+
+#[derive(Copy, Clone)]
+struct ValueSerializer<'m> {
+    metadata: &'m Metadata,
+    class_id: i64,
+    // Whether the value is a constant pool reference
+    constant_pool: bool,
+    unsigned: bool,
+}
+
+impl<'m> ValueSerializer<'m> {
+    fn for_field(metadata: &'m Metadata, field: &FieldDescriptor) -> Self {
+        Self {
+            metadata,
+            class_id: field.class_id,
+            constant_pool: field.constant_pool,
+            unsigned: field.unsigned,
+        }
+    }
+
+    fn type_desc(&self) -> Result<&'m TypeDescriptor> {
+        self.metadata
+            .type_pool
+            .get(self.class_id)
+            .ok_or(Error::ClassNotFound(self.class_id))
+    }
+
+    fn mismatch(&self, actual: &str) -> Error {
+        let expected = self
+            .type_desc()
+            .map(|t| t.name().to_string())
+            .unwrap_or_default();
+        Error::SerializeError(format!("Expected {} but got {}", expected, actual))
+    }
+
+    fn integer(self, v: i128) -> Result<ValueDescriptor> {
+        if self.constant_pool {
+            return i64::try_from(v)
+                .map(|constant_index| ValueDescriptor::ConstantPool {
+                    class_id: self.class_id,
+                    constant_index,
+                })
+                .map_err(|_| self.mismatch("out of range constant index"));
+        }
+
+        // values beyond the signed range are accepted only for unsigned fields
+        macro_rules! convert {
+            ($signed:ty, $unsigned:ty) => {
+                <$signed>::try_from(v).ok().or_else(|| {
+                    <$unsigned>::try_from(v)
+                        .ok()
+                        .filter(|_| self.unsigned)
+                        .map(|u| u as $signed)
+                })
+            };
+        }
+        let value = match self.type_desc()?.name() {
+            "int" => convert!(i32, u32).map(Primitive::Integer),
+            "long" => convert!(i64, u64).map(Primitive::Long),
+            "short" => convert!(i16, u16).map(Primitive::Short),
+            "byte" => convert!(i8, u8).map(Primitive::Byte),
+            "float" => Some(Primitive::Float(v as f32)),
+            "double" => Some(Primitive::Double(v as f64)),
+            _ => return Err(self.mismatch("integer")),
+        };
+        value
+            .map(ValueDescriptor::Primitive)
+            .ok_or_else(|| self.mismatch("out of range integer"))
+    }
+
+    fn float(self, v: f64) -> Result<ValueDescriptor> {
+        match self.type_desc()?.name() {
+            "float" if !self.constant_pool => Ok(Primitive::Float(v as f32)),
+            "double" if !self.constant_pool => Ok(Primitive::Double(v)),
+            _ => Err(self.mismatch("float")),
+        }
+        .map(ValueDescriptor::Primitive)
+    }
+
+    fn string(self, v: &str) -> Result<ValueDescriptor> {
+        if self.constant_pool {
+            return Err(self.mismatch("string for constant pool field"));
+        }
+        match self.type_desc()?.name() {
+            "java.lang.String" => Ok(Primitive::from_string(v.to_string())),
+            "char" => {
+                let mut chars = v.chars();
+                match (chars.next(), chars.next()) {
+                    (Some(c), None) => Ok(Primitive::from_char(c)),
+                    _ => Err(self.mismatch("string")),
+                }
+            }
+            _ => Err(self.mismatch("string")),
+        }
+        .map(ValueDescriptor::Primitive)
+    }
+
+    fn null(self) -> Result<ValueDescriptor> {
+        if self.constant_pool {
+            // index 0 is treated as null by convention
+            return Ok(ValueDescriptor::ConstantPool {
+                class_id: self.class_id,
+                constant_index: 0,
+            });
+        }
+        match self.type_desc()?.name() {
+            "java.lang.String" => Ok(ValueDescriptor::Primitive(Primitive::NullString)),
+            _ => default_value(self.type_desc()?, self.metadata),
+        }
+    }
+}
+
+fn default_field_value(field: &FieldDescriptor, metadata: &Metadata) -> Result<ValueDescriptor> {
+    if field.array_type {
+        return Ok(ValueDescriptor::Array(vec![]));
+    }
+    ValueSerializer::for_field(metadata, field).null()
+}
+
+fn default_value(type_desc: &TypeDescriptor, metadata: &Metadata) -> Result<ValueDescriptor> {
+    let primitive = match type_desc.name() {
+        "int" => Primitive::Integer(0),
+        "long" => Primitive::Long(0),
+        "float" => Primitive::Float(0.0),
+        "double" => Primitive::Double(0.0),
+        "char" => Primitive::from_char('\0'),
+        "boolean" => Primitive::Boolean(false),
+        "short" => Primitive::Short(0),
+        "byte" => Primitive::Byte(0),
+        "java.lang.String" => Primitive::NullString,
+        _ => {
+            let fields = type_desc
+                .fields
+                .iter()
+                .map(|f| default_field_value(f, metadata))
+                .collect::<Result<Vec<_>>>()?;
+            return Ok(ValueDescriptor::Object(Object {
+                class_id: type_desc.class_id,
+                fields,
+            }));
+        }
+    };
+    Ok(ValueDescriptor::Primitive(primitive))
+}
+
+impl<'m> Serializer for ValueSerializer<'m> {
+    type Ok = ValueDescriptor;
+    type Error = Error;
+    type SerializeSeq = SeqSerializer<'m>;
+    type SerializeTuple = SeqSerializer<'m>;
+    type SerializeTupleStruct = SeqSerializer<'m>;
+    // enum variants with values and maps have no counterpart in JFR types
+    type SerializeTupleVariant = Impossible<ValueDescriptor, Error>;
+    type SerializeMap = Impossible<ValueDescriptor, Error>;
+    type SerializeStruct = StructSerializer<'m>;
+    type SerializeStructVariant = Impossible<ValueDescriptor, Error>;
+
+    fn serialize_bool(self, v: bool) -> Result<Self::Ok> {
+        match self.type_desc()?.name() {
+            "boolean" if !self.constant_pool => {
+                Ok(ValueDescriptor::Primitive(Primitive::Boolean(v)))
+            }
+            _ => Err(self.mismatch("bool")),
+        }
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<Self::Ok> {
+        self.integer(v as i128)
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<Self::Ok> {
+        self.integer(v as i128)
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<Self::Ok> {
+        self.integer(v as i128)
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<Self::Ok> {
+        self.integer(v as i128)
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<Self::Ok> {
+        self.integer(v as i128)
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<Self::Ok> {
+        self.integer(v as i128)
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<Self::Ok> {
+        self.integer(v as i128)
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<Self::Ok> {
+        self.integer(v as i128)
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<Self::Ok> {
+        self.float(v as f64)
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<Self::Ok> {
+        self.float(v)
+    }
+
+    fn serialize_char(self, v: char) -> Result<Self::Ok> {
+        self.string(v.encode_utf8(&mut [0; 4]))
+    }
+
+    fn serialize_str(self, v: &str) -> Result<Self::Ok> {
+        self.string(v)
+    }
+
+    fn serialize_bytes(self, _v: &[u8]) -> Result<Self::Ok> {
+        Err(self.mismatch("bytes"))
+    }
+
+    fn serialize_none(self) -> Result<Self::Ok> {
+        self.null()
+    }
+
+    fn serialize_some<T>(self, value: &T) -> Result<Self::Ok>
+    where
+        T: Serialize + ?Sized,
+    {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<Self::Ok> {
+        self.null()
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<Self::Ok> {
+        self.null()
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+    ) -> Result<Self::Ok> {
+        self.string(variant)
+    }
+
+    fn serialize_newtype_struct<T>(self, _name: &'static str, value: &T) -> Result<Self::Ok>
+    where
+        T: Serialize + ?Sized,
+    {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _value: &T,
+    ) -> Result<Self::Ok>
+    where
+        T: Serialize + ?Sized,
+    {
+        Err(self.mismatch("enum variant with value"))
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<Self::SerializeSeq> {
+        Ok(SeqSerializer {
+            element: self,
+            elements: Vec::with_capacity(len.unwrap_or(0)),
+        })
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<Self::SerializeTuple> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeTupleStruct> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleVariant> {
+        Err(self.mismatch("enum variant with value"))
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap> {
+        Err(self.mismatch("map"))
+    }
+
+    fn serialize_struct(self, _name: &'static str, _len: usize) -> Result<Self::SerializeStruct> {
+        if self.constant_pool {
+            return Err(self.mismatch("struct for constant pool field"));
+        }
+        let type_desc = self.type_desc()?;
+        Ok(StructSerializer {
+            metadata: self.metadata,
+            type_desc,
+            fields: (0..type_desc.fields.len()).map(|_| None).collect(),
+        })
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStructVariant> {
+        Err(self.mismatch("enum variant with value"))
+    }
+}
+
+struct SeqSerializer<'m> {
+    element: ValueSerializer<'m>,
+    elements: Vec<ValueDescriptor>,
+}
+
+impl<'m> SerializeSeq for SeqSerializer<'m> {
+    type Ok = ValueDescriptor;
+    type Error = Error;
+
+    fn serialize_element<T>(&mut self, value: &T) -> Result<()>
+    where
+        T: Serialize + ?Sized,
+    {
+        self.elements.push(value.serialize(self.element)?);
+        Ok(())
+    }
+
+    fn end(self) -> Result<Self::Ok> {
+        Ok(ValueDescriptor::Array(self.elements))
+    }
+}
+
+impl<'m> SerializeTuple for SeqSerializer<'m> {
+    type Ok = ValueDescriptor;
+    type Error = Error;
+
+    fn serialize_element<T>(&mut self, value: &T) -> Result<()>
+    where
+        T: Serialize + ?Sized,
+    {
+        SerializeSeq::serialize_element(self, value)
+    }
+
+    fn end(self) -> Result<Self::Ok> {
+        SerializeSeq::end(self)
+    }
+}
+
+impl<'m> SerializeTupleStruct for SeqSerializer<'m> {
+    type Ok = ValueDescriptor;
+    type Error = Error;
+
+    fn serialize_field<T>(&mut self, value: &T) -> Result<()>
+    where
+        T: Serialize + ?Sized,
+    {
+        SerializeSeq::serialize_element(self, value)
+    }
+
+    fn end(self) -> Result<Self::Ok> {
+        SerializeSeq::end(self)
+    }
+}
+
+struct StructSerializer<'m> {
+    metadata: &'m Metadata,
+    type_desc: &'m TypeDescriptor,
+    fields: Vec<Option<ValueDescriptor>>,
+}
+
+impl<'m> SerializeStruct for StructSerializer<'m> {
+    type Ok = ValueDescriptor;
+    type Error = Error;
+
+    fn serialize_field<T>(&mut self, key: &'static str, value: &T) -> Result<()>
+    where
+        T: Serialize + ?Sized,
+    {
+        let (idx, field) = self.type_desc.get_field(key).ok_or_else(|| {
+            Error::SerializeError(format!(
+                "Field {} is not declared in {}",
+                key,
+                self.type_desc.name()
+            ))
+        })?;
+        let value = value.serialize(ValueSerializer::for_field(self.metadata, field))?;
+        if field.array_type != matches!(value, ValueDescriptor::Array(_)) {
+            return Err(Error::SerializeError(format!(
+                "Field {} of {} must {}be a sequence",
+                key,
+                self.type_desc.name(),
+                if field.array_type { "" } else { "not " }
+            )));
+        }
+        self.fields[idx] = Some(value);
+        Ok(())
+    }
+
+    fn end(self) -> Result<Self::Ok> {
+        let fields = self
+            .fields
+            .into_iter()
+            .zip(self.type_desc.fields.iter())
+            .map(|(value, field)| match value {
+                Some(value) => Ok(value),
+                None => default_field_value(field, self.metadata),
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Ok(ValueDescriptor::Object(Object {
+            class_id: self.type_desc.class_id,
+            fields,
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::reader::byte_stream::ByteStream;
+    use crate::reader::de::from_value_descriptor;
+    use crate::reader::event::Accessor;
+    use crate::reader::value::JfrValue;
+    use crate::reader::JfrReader;
+    use crate::test_util::test_data;
+    use serde::Deserialize;
+    use std::fs::File;
+    use std::io::Cursor;
+
+    #[derive(Serialize)]
+    #[serde(rename_all = "camelCase")]
+    struct ThreadPark {
+        start_time: i64,
+        duration: i64,
+        event_thread: Option<u64>,
+        stack_trace: Option<u64>,
+        timeout: i64,
+        until: i64,
+        address: u64,
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let mut reader = JfrReader::new(File::open(test_data("recording.jfr")).unwrap());
+        let (chunk_reader, chunk) = reader.chunks().next().unwrap().unwrap();
+        let class_id = chunk
+            .metadata
+            .type_pool
+            .get_by_name("jdk.ThreadPark")
+            .unwrap()
+            .class_id;
+
+        let mut count = 0;
+        for event in chunk_reader
+            .events(&chunk)
+            .flatten()
+            .filter(|e| e.class.class_id == class_id)
+        {
+            let raw = |name: &str| {
+                event
+                    .value()
+                    .get_field_raw(name)
+                    .and_then(|v| match v.value {
+                        ValueDescriptor::ConstantPool { constant_index, .. } => {
+                            Some(*constant_index as u64)
+                        }
+                        _ => None,
+                    })
+            };
+            let park = ThreadPark {
+                start_time: field_i64(&event.value(), "startTime"),
+                duration: field_i64(&event.value(), "duration"),
+                event_thread: raw("eventThread"),
+                stack_trace: raw("stackTrace"),
+                timeout: field_i64(&event.value(), "timeout"),
+                until: field_i64(&event.value(), "until"),
+                address: field_i64(&event.value(), "address") as u64,
+            };
+
+            let bytes = to_event(&park, "jdk.ThreadPark", &chunk).unwrap();
+            let mut stream = ByteStream::new(Cursor::new(bytes));
+            stream.set_int_encoding(chunk.header.int_encoding());
+            stream.read_i32().unwrap();
+            assert_eq!(stream.read_i64().unwrap(), class_id);
+            let value = ValueDescriptor::try_new(&mut stream, class_id, &chunk.metadata).unwrap();
+
+            // fields not in the struct (parkedClass) are filled with null
+            let decoded = JfrValue::from(Accessor::new(&chunk, &value));
+            let original = JfrValue::from(event.value());
+            let fields = match &original {
+                JfrValue::Object(fields) => fields,
+                _ => unreachable!(),
+            };
+            for (name, v) in fields.iter() {
+                if name == "parkedClass" {
+                    assert!(decoded.get(name).unwrap().is_null());
+                } else {
+                    assert_eq!(decoded.get(name), Some(v));
+                }
+            }
+            count += 1;
+        }
+        assert_eq!(count, 237);
+    }
+
+    #[test]
+    fn test_to_value_descriptor() {
+        #[derive(Debug, Serialize, Deserialize, PartialEq)]
+        #[serde(rename_all = "camelCase")]
+        struct InlineThreadPark {
+            start_time: i64,
+            timeout: i64,
+            address: u64,
+        }
+
+        let mut reader = JfrReader::new(File::open(test_data("recording.jfr")).unwrap());
+        let (_, chunk) = reader.chunk_metadata().next().unwrap().unwrap();
+        let class_id = chunk
+            .metadata
+            .type_pool
+            .get_by_name("jdk.ThreadPark")
+            .unwrap()
+            .class_id;
+
+        let park = InlineThreadPark {
+            start_time: 1,
+            timeout: -1,
+            address: u64::MAX,
+        };
+        let value = to_value_descriptor(&park, class_id, &chunk.metadata).unwrap();
+        assert_eq!(
+            from_value_descriptor::<InlineThreadPark>(&chunk, &value).unwrap(),
+            park
+        );
+
+        #[derive(Serialize)]
+        struct Unknown {
+            no_such_field: i32,
+        }
+        assert!(matches!(
+            to_value_descriptor(&Unknown { no_such_field: 0 }, class_id, &chunk.metadata),
+            Err(Error::SerializeError(_))
+        ));
+
+        #[derive(Serialize)]
+        #[serde(rename_all = "camelCase")]
+        struct WrongType {
+            start_time: &'static str,
+        }
+        assert!(matches!(
+            to_value_descriptor(&WrongType { start_time: "x" }, class_id, &chunk.metadata),
+            Err(Error::SerializeError(_))
+        ));
+    }
+
+    fn field_i64(accessor: &Accessor, name: &str) -> i64 {
+        accessor
+            .get_field(name)
+            .and_then(|v| i64::try_from(v.value).ok())
+            .unwrap()
+    }
+}
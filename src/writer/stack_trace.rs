@@ -0,0 +1,450 @@
+//! Capture stack traces of Rust code by the `backtrace` crate, and write them into the types
+//! compatible with `jdk.types.StackTrace`, so that JMC and [`crate::analysis`] can visualize
+//! CPU profiles of Rust applications.
+//!
+//! ```no_run
+//! use jfrs::writer::recording::{ticks, JfrWriter};
+//! use jfrs::writer::stack_trace::StackTraceRecorder;
+//! use std::fs::File;
+//! use std::time::SystemTime;
+//!
+//! let mut writer = JfrWriter::new(File::create("/path/to/recording.jfr").unwrap());
+//! let mut recorder = StackTraceRecorder::new(&mut writer).unwrap();
+//! let thread = recorder.thread(&mut writer, "main", 1).unwrap();
+//! let stack_trace = recorder.capture(&mut writer).unwrap();
+//! recorder
+//!     .write_execution_sample(&mut writer, ticks(SystemTime::now()), thread, stack_trace)
+//!     .unwrap();
+//! writer.finish().unwrap();
+//! ```
+//!
+//! Only the stack of the calling thread can be captured,
+//! so samples are taken by the sampled threads themselves, e.g. from periodically called code.
+//! Functions are written as methods whose class is the path of the function
+//! (e.g. `jfrs::reader::JfrReader::new` as the method `new` of the class `jfrs::reader::JfrReader`).
+
+use crate::reader::Result;
+use crate::writer::recording::{FieldDeclaration, JfrWriter, TypeDeclaration};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::io::Write;
+
+const EXECUTION_SAMPLE: &str = "jdk.ExecutionSample";
+const SYMBOL: &str = "jdk.types.Symbol";
+const CLASS: &str = "java.lang.Class";
+const METHOD: &str = "jdk.types.Method";
+const FRAME_TYPE: &str = "jdk.types.FrameType";
+const STACK_FRAME: &str = "jdk.types.StackFrame";
+const STACK_TRACE: &str = "jdk.types.StackTrace";
+const THREAD: &str = "java.lang.Thread";
+const THREAD_STATE: &str = "jdk.types.ThreadState";
+/// Same as the default `stackdepth` of JFR
+const DEFAULT_MAX_DEPTH: usize = 64;
+
+/// A frame of Rust code
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RustFrame {
+    /// The demangled path of the function, e.g. `jfrs::reader::JfrReader::new`
+    pub function: String,
+    pub line_number: Option<u32>,
+    /// The function is inlined into the caller frame
+    pub inlined: bool,
+}
+
+#[derive(Serialize)]
+struct Symbol<'a> {
+    string: &'a str,
+}
+
+#[derive(Serialize)]
+struct Class {
+    name: i64,
+    modifiers: i32,
+    hidden: bool,
+}
+
+#[derive(Serialize)]
+struct Method {
+    r#type: i64,
+    name: i64,
+    modifiers: i32,
+    hidden: bool,
+}
+
+#[derive(Serialize)]
+struct FrameType<'a> {
+    description: &'a str,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct StackFrame {
+    method: i64,
+    line_number: i32,
+    bytecode_index: i32,
+    r#type: i64,
+}
+
+#[derive(Serialize)]
+struct StackTrace {
+    truncated: bool,
+    frames: Vec<StackFrame>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Thread<'a> {
+    os_name: &'a str,
+    os_thread_id: i64,
+    java_name: &'a str,
+    java_thread_id: i64,
+}
+
+#[derive(Serialize)]
+struct ThreadState<'a> {
+    name: &'a str,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ExecutionSample {
+    start_time: i64,
+    sampled_thread: i64,
+    stack_trace: i64,
+    state: i64,
+}
+
+/// Interns the constants of the stack traces written into a [`JfrWriter`]
+#[derive(Debug)]
+pub struct StackTraceRecorder {
+    max_depth: usize,
+    symbols: HashMap<String, i64>,
+    classes: HashMap<String, i64>,
+    methods: HashMap<(i64, i64), i64>,
+    stack_traces: HashMap<Vec<(i64, i32, bool)>, i64>,
+    threads: HashMap<u64, i64>,
+    // constant indices of FrameType "Native" and "Inlined", and ThreadState "STATE_RUNNABLE"
+    native_frame: i64,
+    inlined_frame: i64,
+    runnable: i64,
+}
+
+impl StackTraceRecorder {
+    /// Declares `jdk.ExecutionSample` and the types it refers to in the writer
+    pub fn new<W: Write>(writer: &mut JfrWriter<W>) -> Result<Self> {
+        let string = "java.lang.String";
+        let declarations = [
+            TypeDeclaration::new(SYMBOL).field(FieldDeclaration::new("string", string)),
+            TypeDeclaration::new(CLASS)
+                .field(FieldDeclaration::new("name", SYMBOL).constant_pool(true))
+                .field(FieldDeclaration::new("modifiers", "int"))
+                .field(FieldDeclaration::new("hidden", "boolean")),
+            TypeDeclaration::new(METHOD)
+                .field(FieldDeclaration::new("type", CLASS).constant_pool(true))
+                .field(FieldDeclaration::new("name", SYMBOL).constant_pool(true))
+                .field(FieldDeclaration::new("modifiers", "int"))
+                .field(FieldDeclaration::new("hidden", "boolean")),
+            TypeDeclaration::new(FRAME_TYPE).field(FieldDeclaration::new("description", string)),
+            TypeDeclaration::new(STACK_FRAME)
+                .field(FieldDeclaration::new("method", METHOD).constant_pool(true))
+                .field(FieldDeclaration::new("lineNumber", "int"))
+                .field(FieldDeclaration::new("bytecodeIndex", "int"))
+                .field(FieldDeclaration::new("type", FRAME_TYPE).constant_pool(true)),
+            TypeDeclaration::new(STACK_TRACE)
+                .field(FieldDeclaration::new("truncated", "boolean"))
+                .field(FieldDeclaration::new("frames", STACK_FRAME).array(true)),
+            TypeDeclaration::new(THREAD)
+                .field(FieldDeclaration::new("osName", string))
+                .field(FieldDeclaration::new("osThreadId", "long"))
+                .field(FieldDeclaration::new("javaName", string))
+                .field(FieldDeclaration::new("javaThreadId", "long")),
+            TypeDeclaration::new(THREAD_STATE).field(FieldDeclaration::new("name", string)),
+            TypeDeclaration::event(EXECUTION_SAMPLE)
+                .field(FieldDeclaration::new("sampledThread", THREAD).constant_pool(true))
+                .field(FieldDeclaration::new("stackTrace", STACK_TRACE).constant_pool(true))
+                .field(FieldDeclaration::new("state", THREAD_STATE).constant_pool(true)),
+        ];
+        for declaration in declarations {
+            writer.declare_type(declaration)?;
+        }
+
+        writer.add_constant(
+            FRAME_TYPE,
+            1,
+            &FrameType {
+                description: "Native",
+            },
+        )?;
+        writer.add_constant(
+            FRAME_TYPE,
+            2,
+            &FrameType {
+                description: "Inlined",
+            },
+        )?;
+        writer.add_constant(
+            THREAD_STATE,
+            1,
+            &ThreadState {
+                name: "STATE_RUNNABLE",
+            },
+        )?;
+        Ok(Self {
+            max_depth: DEFAULT_MAX_DEPTH,
+            symbols: HashMap::new(),
+            classes: HashMap::new(),
+            methods: HashMap::new(),
+            stack_traces: HashMap::new(),
+            threads: HashMap::new(),
+            native_frame: 1,
+            inlined_frame: 2,
+            runnable: 1,
+        })
+    }
+
+    /// Sets the maximum number of the frames of a stack trace. Defaults to 64.
+    /// Deeper stack traces are truncated
+    pub fn max_depth(mut self, max_depth: usize) -> Self {
+        self.max_depth = max_depth;
+        self
+    }
+
+    /// Captures the stack trace of the calling thread, and returns its constant index
+    #[inline(never)]
+    pub fn capture<W: Write>(&mut self, writer: &mut JfrWriter<W>) -> Result<i64> {
+        let mut frames = vec![];
+        backtrace::trace(|frame| {
+            let mut symbols = vec![];
+            backtrace::resolve_frame(frame, |symbol| {
+                if let Some(name) = symbol.name() {
+                    symbols.push((format!("{:#}", name), symbol.lineno()));
+                }
+            });
+            // the symbols of a frame are from the innermost inlined function
+            let count = symbols.len();
+            for (i, (function, line_number)) in symbols.into_iter().enumerate() {
+                frames.push(RustFrame {
+                    function,
+                    line_number,
+                    inlined: i + 1 < count,
+                });
+            }
+            true
+        });
+
+        // skip the frames of this function and the backtrace crate
+        let skip = frames
+            .iter()
+            .position(|f| {
+                !f.function.starts_with("backtrace::")
+                    && !f.function.ends_with("StackTraceRecorder::capture")
+            })
+            .unwrap_or(frames.len());
+        self.record_frames(writer, &frames[skip..])
+    }
+
+    /// Registers the stack trace of the frames from the top frame, and returns its constant index
+    pub fn record_frames<W: Write>(
+        &mut self,
+        writer: &mut JfrWriter<W>,
+        frames: &[RustFrame],
+    ) -> Result<i64> {
+        let truncated = frames.len() > self.max_depth;
+        let mut key = Vec::with_capacity(frames.len().min(self.max_depth));
+        for frame in frames.iter().take(self.max_depth) {
+            let (class, method) = match frame.function.rsplit_once("::") {
+                Some((class, method)) => (class, method),
+                None => ("", frame.function.as_str()),
+            };
+            let method = self.method(writer, class, method)?;
+            let line_number = frame.line_number.map_or(-1, |l| l as i32);
+            key.push((method, line_number, frame.inlined));
+        }
+
+        if let Some(&index) = self.stack_traces.get(&key) {
+            return Ok(index);
+        }
+        let index = self.stack_traces.len() as i64 + 1;
+        let stack_trace = StackTrace {
+            truncated,
+            frames: key
+                .iter()
+                .map(|&(method, line_number, inlined)| StackFrame {
+                    method,
+                    line_number,
+                    bytecode_index: -1,
+                    r#type: if inlined {
+                        self.inlined_frame
+                    } else {
+                        self.native_frame
+                    },
+                })
+                .collect(),
+        };
+        writer.add_constant(STACK_TRACE, index, &stack_trace)?;
+        self.stack_traces.insert(key, index);
+        Ok(index)
+    }
+
+    /// Registers the thread by its id, and returns its constant index
+    pub fn thread<W: Write>(
+        &mut self,
+        writer: &mut JfrWriter<W>,
+        name: &str,
+        thread_id: u64,
+    ) -> Result<i64> {
+        if let Some(&index) = self.threads.get(&thread_id) {
+            return Ok(index);
+        }
+        let index = self.threads.len() as i64 + 1;
+        let thread = Thread {
+            os_name: name,
+            os_thread_id: thread_id as i64,
+            java_name: name,
+            java_thread_id: thread_id as i64,
+        };
+        writer.add_constant(THREAD, index, &thread)?;
+        self.threads.insert(thread_id, index);
+        Ok(index)
+    }
+
+    /// Writes `jdk.ExecutionSample` of the thread and the stack trace registered by this recorder
+    pub fn write_execution_sample<W: Write>(
+        &self,
+        writer: &mut JfrWriter<W>,
+        start_time: i64,
+        thread: i64,
+        stack_trace: i64,
+    ) -> Result<()> {
+        let sample = ExecutionSample {
+            start_time,
+            sampled_thread: thread,
+            stack_trace,
+            state: self.runnable,
+        };
+        writer.write_event(EXECUTION_SAMPLE, &sample)
+    }
+
+    fn method<W: Write>(
+        &mut self,
+        writer: &mut JfrWriter<W>,
+        class: &str,
+        method: &str,
+    ) -> Result<i64> {
+        let class = match self.classes.get(class) {
+            Some(&index) => index,
+            None => {
+                let name = self.symbol(writer, class)?;
+                let index = self.classes.len() as i64 + 1;
+                let value = Class {
+                    name,
+                    modifiers: 0,
+                    hidden: false,
+                };
+                writer.add_constant(CLASS, index, &value)?;
+                self.classes.insert(class.to_string(), index);
+                index
+            }
+        };
+        let name = self.symbol(writer, method)?;
+        if let Some(&index) = self.methods.get(&(class, name)) {
+            return Ok(index);
+        }
+        let index = self.methods.len() as i64 + 1;
+        let value = Method {
+            r#type: class,
+            name,
+            modifiers: 0,
+            hidden: false,
+        };
+        writer.add_constant(METHOD, index, &value)?;
+        self.methods.insert((class, name), index);
+        Ok(index)
+    }
+
+    fn symbol<W: Write>(&mut self, writer: &mut JfrWriter<W>, s: &str) -> Result<i64> {
+        if let Some(&index) = self.symbols.get(s) {
+            return Ok(index);
+        }
+        let index = self.symbols.len() as i64 + 1;
+        writer.add_constant(SYMBOL, index, &Symbol { string: s })?;
+        self.symbols.insert(s.to_string(), index);
+        Ok(index)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analysis::frame::stack_frames;
+    use crate::reader::JfrReader;
+
+    fn frame(function: &str, line_number: u32, inlined: bool) -> RustFrame {
+        RustFrame {
+            function: function.to_string(),
+            line_number: Some(line_number),
+            inlined,
+        }
+    }
+
+    /// Returns the frames of each sample as strings
+    fn read_samples(bytes: Vec<u8>) -> Vec<Vec<String>> {
+        let mut reader = JfrReader::from_bytes(bytes);
+        let (chunk_reader, chunk) = reader.chunks().next().unwrap().unwrap();
+        chunk_reader
+            .events(&chunk)
+            .flatten()
+            .map(|e| {
+                assert_eq!(e.class.name(), EXECUTION_SAMPLE);
+                let stack_trace = e.value().get_field("stackTrace").unwrap();
+                stack_frames(&stack_trace)
+                    .iter()
+                    .map(|f| format!("{} {}", f, f.frame_type.as_deref().unwrap()))
+                    .collect()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_record_frames() {
+        let mut writer = JfrWriter::new(vec![]);
+        let mut recorder = StackTraceRecorder::new(&mut writer).unwrap().max_depth(2);
+        let thread = recorder.thread(&mut writer, "worker", 42).unwrap();
+        let frames = vec![
+            frame("app::parse", 10, true),
+            frame("app::Handler::handle", 20, false),
+            frame("main", 30, false),
+        ];
+        let stack_trace = recorder.record_frames(&mut writer, &frames).unwrap();
+        assert_eq!(
+            recorder.record_frames(&mut writer, &frames).unwrap(),
+            stack_trace
+        );
+        for start_time in 0..3 {
+            recorder
+                .write_execution_sample(&mut writer, start_time, thread, stack_trace)
+                .unwrap();
+        }
+
+        let samples = read_samples(writer.finish().unwrap());
+        assert_eq!(samples.len(), 3);
+        assert_eq!(
+            samples[0],
+            vec!["app.parse:10 Inlined", "app::Handler.handle:20 Native"]
+        );
+    }
+
+    #[test]
+    fn test_capture() {
+        let mut writer = JfrWriter::new(vec![]);
+        let mut recorder = StackTraceRecorder::new(&mut writer).unwrap();
+        let thread = recorder.thread(&mut writer, "main", 1).unwrap();
+        let stack_trace = recorder.capture(&mut writer).unwrap();
+        recorder
+            .write_execution_sample(&mut writer, 0, thread, stack_trace)
+            .unwrap();
+
+        let samples = read_samples(writer.finish().unwrap());
+        assert!(samples[0][0].starts_with("jfrs::writer::stack_trace::tests.test_capture:"));
+    }
+}
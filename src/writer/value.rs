@@ -0,0 +1,108 @@
+//! Encode [`ValueDescriptor`] as bytes based on the declared types.
+//! This is the counterpart of [`ValueDescriptor::try_new`].
+
+use crate::reader::byte_stream::IntEncoding;
+use crate::reader::metadata::Metadata;
+use crate::reader::type_descriptor::FieldDescriptor;
+use crate::reader::value_descriptor::{Primitive, ValueDescriptor};
+use crate::reader::{Error, Result};
+use crate::writer::byte_writer::ByteWriter;
+
+/// Encodes the event including the size and the type id
+pub fn encode_event(
+    value: &ValueDescriptor,
+    class_id: i64,
+    metadata: &Metadata,
+    int_encoding: IntEncoding,
+) -> Result<Vec<u8>> {
+    let mut writer = ByteWriter::new(int_encoding);
+    // size
+    writer.write_padded_i32(0)?;
+    writer.write_i64(class_id);
+    write_value(&mut writer, value, class_id, metadata)?;
+    let size = writer.len();
+    writer.set_padded_i32(0, size as i32)?;
+    Ok(writer.into_inner())
+}
+
+pub fn write_value(
+    writer: &mut ByteWriter,
+    value: &ValueDescriptor,
+    class_id: i64,
+    metadata: &Metadata,
+) -> Result<()> {
+    let type_desc = metadata
+        .type_pool
+        .get(class_id)
+        .ok_or(Error::ClassNotFound(class_id))?;
+
+    match value {
+        ValueDescriptor::Primitive(p) => write_primitive(writer, p),
+        ValueDescriptor::ConstantPool { constant_index, .. }
+            if type_desc.name() == "java.lang.String" =>
+        {
+            writer.write_constant_pool_string(*constant_index);
+            Ok(())
+        }
+        ValueDescriptor::Object(obj) => {
+            if obj.fields.len() != type_desc.fields.len() {
+                return Err(Error::InvalidFormat);
+            }
+            for (field_desc, value) in type_desc.fields.iter().zip(obj.fields.iter()) {
+                if field_desc.array_type {
+                    let elems = match value {
+                        ValueDescriptor::Array(elems) => elems,
+                        _ => return Err(Error::InvalidFormat),
+                    };
+                    writer.write_i32(elems.len() as i32);
+                    for elem in elems {
+                        write_field_single(writer, elem, field_desc, metadata)?;
+                    }
+                } else {
+                    write_field_single(writer, value, field_desc, metadata)?;
+                }
+            }
+            Ok(())
+        }
+        _ => Err(Error::InvalidFormat),
+    }
+}
+
+fn write_field_single(
+    writer: &mut ByteWriter,
+    value: &ValueDescriptor,
+    field_desc: &FieldDescriptor,
+    metadata: &Metadata,
+) -> Result<()> {
+    if field_desc.constant_pool {
+        match value {
+            ValueDescriptor::ConstantPool { constant_index, .. } => {
+                writer.write_i64(*constant_index);
+                Ok(())
+            }
+            _ => Err(Error::InvalidFormat),
+        }
+    } else {
+        write_value(writer, value, field_desc.class_id, metadata)
+    }
+}
+
+fn write_primitive(writer: &mut ByteWriter, value: &Primitive) -> Result<()> {
+    match value {
+        Primitive::Integer(v) => writer.write_i32(*v),
+        Primitive::Long(v) => writer.write_i64(*v),
+        Primitive::Float(v) => writer.write_f32(*v),
+        Primitive::Double(v) => writer.write_f64(*v),
+        Primitive::Character(v) => writer.write_char(*v),
+        Primitive::Boolean(v) => writer.write_i8(*v as i8),
+        Primitive::Short(v) => writer.write_i16(*v),
+        Primitive::Byte(v) => writer.write_i8(*v),
+        Primitive::NullString => writer.write_null_string(),
+        Primitive::String(v) => writer.write_string(v),
+        Primitive::CString(v) => {
+            writer.write_string(v.string.to_str().map_err(|_| Error::InvalidString)?)
+        }
+        Primitive::RawString(v) => writer.write_string(&v.decode()?),
+    }
+    Ok(())
+}